@@ -0,0 +1,62 @@
+//! Handing an identity to a newly linked device.
+//!
+//! The new device generates its own Ed25519 key and shows it to an existing
+//! device in a signed link request. The existing device seals the account
+//! material (identity secret, DHT record keys, contacts) to the Montgomery
+//! form of that key with [`seal_for_device`], a [`sealed_box`] under the
+//! device-link context.
+//!
+//! [`sealed_box`]: crate::sealed_box
+
+use zeroize::Zeroizing;
+
+use crate::error::CryptoError;
+use crate::identity::Identity;
+use crate::sealed_box;
+
+/// Context label for sealed link grants.
+const LINK_CONTEXT: &[u8] = b"rekindle-device-link-v1";
+
+/// Seal `plaintext` so only the device holding `device_key`'s secret can open it.
+pub fn seal_for_device(device_key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let device_key = <[u8; 32]>::try_from(device_key)
+        .map_err(|_| CryptoError::InvalidKey("device key must be 32 bytes".into()))?;
+    let recipient = Identity::peer_ed25519_to_x25519(&device_key)?;
+    sealed_box::seal(LINK_CONTEXT, &recipient, plaintext)
+}
+
+/// Open a grant produced by [`seal_for_device`] with our device key.
+pub fn open_for_device(device: &Identity, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+    sealed_box::open(LINK_CONTEXT, &device.to_x25519_secret(), sealed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_grant_opens_on_its_device() {
+        let device = Identity::generate();
+        let sealed = seal_for_device(&device.public_key_bytes(), b"account material").unwrap();
+        let opened = open_for_device(&device, &sealed).unwrap();
+        assert_eq!(opened.as_slice(), b"account material");
+    }
+
+    #[test]
+    fn sealed_grant_does_not_open_elsewhere() {
+        let device = Identity::generate();
+        let other = Identity::generate();
+        let sealed = seal_for_device(&device.public_key_bytes(), b"account material").unwrap();
+        assert!(open_for_device(&other, &sealed).is_err());
+    }
+
+    #[test]
+    fn tampered_grant_is_rejected() {
+        let device = Identity::generate();
+        let mut sealed = seal_for_device(&device.public_key_bytes(), b"account material").unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(open_for_device(&device, &sealed).is_err());
+        assert!(open_for_device(&device, &sealed[..20]).is_err());
+    }
+}
@@ -10,9 +10,10 @@ const TAG_LEN: usize = 16;
 
 /// Symmetric key for encrypting DHT record contents.
 ///
-/// Two derivation modes:
+/// Three modes:
 /// - **Account key**: HKDF from Ed25519 secret — only the owner can read.
 /// - **Conversation key**: DH shared secret — both parties can read/write.
+/// - **Shared key**: random, handed to every member of a group conversation.
 ///
 /// Encryption uses XChaCha20-Poly1305 (24-byte nonce, 16-byte tag).
 #[derive(ZeroizeOnDrop)]
@@ -63,6 +64,27 @@ impl DhtRecordKey {
         Self { key }
     }
 
+    /// Generate a random key for a record shared by more than two parties.
+    ///
+    /// The creator sends the raw bytes (see [`Self::to_bytes`]) to each member
+    /// over their Signal session.
+    pub fn generate() -> Self {
+        use rand::RngCore;
+        let mut key = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        Self { key }
+    }
+
+    /// Rebuild a shared key from the bytes another member sent us.
+    pub fn from_bytes(key: [u8; 32]) -> Self {
+        Self { key }
+    }
+
+    /// Raw key bytes, for handing a shared key to another member.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.key
+    }
+
     /// Encrypt plaintext with XChaCha20-Poly1305.
     ///
     /// Returns `[24-byte nonce || ciphertext || 16-byte tag]`.
@@ -195,4 +217,16 @@ mod tests {
         let pt = key_b.decrypt(&ct).unwrap();
         assert_eq!(pt, msg);
     }
+
+    #[test]
+    fn shared_key_round_trip_through_bytes() {
+        let key = DhtRecordKey::generate();
+        let copy = DhtRecordKey::from_bytes(key.to_bytes());
+
+        let ct = key.encrypt(b"group header").unwrap();
+        assert_eq!(copy.decrypt(&ct).unwrap(), b"group header");
+
+        // Two generated keys are independent
+        assert_ne!(key.to_bytes(), DhtRecordKey::generate().to_bytes());
+    }
 }
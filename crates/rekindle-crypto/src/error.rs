@@ -28,4 +28,15 @@ pub enum CryptoError {
 
     #[error("key storage error: {0}")]
     StorageError(String),
+
+    /// A session init named a one-time prekey that is already gone. The
+    /// initiator should start a new session from fresh prekeys.
+    #[error("one-time prekey {0} was already used")]
+    OneTimePreKeyConsumed(u32),
+
+    #[error("identity key changed for {0}")]
+    IdentityKeyChanged(String),
+
+    #[error("removed from group")]
+    RemovedFromGroup,
 }
@@ -0,0 +1,178 @@
+//! Encryption for shared files.
+//!
+//! Every file gets a fresh random [`FileKey`]. The file is split into
+//! fixed-size chunks, each sealed with AES-256-GCM on its own so chunks
+//! can be fetched, verified and written out of order or across restarts.
+//! The nonce is the chunk index (the key is never reused across files),
+//! and the AAD binds each chunk to its index and the total chunk count:
+//! chunks can't be reordered, swapped between positions, or the file
+//! truncated without a chunk failing to open.
+//!
+//! The key travels inside the Signal-encrypted message that announces the
+//! file, next to a SHA-256 [`FileChecksum`] of the plaintext that the
+//! receiver checks once every chunk has been written.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::error::CryptoError;
+
+const CHUNK_AAD_LABEL: &[u8] = b"rekindle-file-chunk-v1";
+
+/// Random per-file content key.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct FileKey([u8; 32]);
+
+impl FileKey {
+    /// Generate a key for a new file.
+    pub fn generate() -> Self {
+        let mut key = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// Restore a key received in a message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| CryptoError::InvalidKey(format!("file key is {} bytes", bytes.len())))?;
+        Ok(Self(key))
+    }
+
+    /// Raw key bytes (for delivery over a Signal session).
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Seal chunk `index` of a file split into `chunk_count` chunks.
+    pub fn seal_chunk(
+        &self,
+        index: u32,
+        chunk_count: u32,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let aad = chunk_aad(index, chunk_count);
+        self.cipher()?
+            .encrypt(
+                &chunk_nonce(index),
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|e| CryptoError::EncryptionError(e.to_string()))
+    }
+
+    /// Open chunk `index` of a file split into `chunk_count` chunks.
+    pub fn open_chunk(
+        &self,
+        index: u32,
+        chunk_count: u32,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let aad = chunk_aad(index, chunk_count);
+        self.cipher()?
+            .decrypt(
+                &chunk_nonce(index),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|e| CryptoError::DecryptionError(format!("chunk {index}: {e}")))
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, CryptoError> {
+        Aes256Gcm::new_from_slice(&self.0).map_err(|e| CryptoError::InvalidKey(e.to_string()))
+    }
+}
+
+/// The chunk index, big-endian in the last four bytes.
+fn chunk_nonce(index: u32) -> Nonce<aes_gcm::aead::consts::U12> {
+    let mut nonce = [0u8; 12];
+    nonce[8..].copy_from_slice(&index.to_be_bytes());
+    Nonce::from(nonce)
+}
+
+/// `label || index (u32 BE) || chunk_count (u32 BE)`.
+fn chunk_aad(index: u32, chunk_count: u32) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(CHUNK_AAD_LABEL.len() + 8);
+    aad.extend_from_slice(CHUNK_AAD_LABEL);
+    aad.extend_from_slice(&index.to_be_bytes());
+    aad.extend_from_slice(&chunk_count.to_be_bytes());
+    aad
+}
+
+/// Streaming SHA-256 over a file's plaintext.
+#[derive(Clone, Default)]
+pub struct FileChecksum(Sha256);
+
+impl FileChecksum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next bytes of the file.
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// The finished digest.
+    pub fn finalize(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+
+    /// Whether the finished digest matches `expected`.
+    pub fn verify(self, expected: &[u8]) -> bool {
+        self.finalize().as_slice() == expected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let key = FileKey::generate();
+        let sealed = key.seal_chunk(3, 10, b"chunk three").unwrap();
+        assert_eq!(key.open_chunk(3, 10, &sealed).unwrap(), b"chunk three");
+
+        let restored = FileKey::from_bytes(key.as_bytes()).unwrap();
+        assert_eq!(restored.open_chunk(3, 10, &sealed).unwrap(), b"chunk three");
+    }
+
+    #[test]
+    fn test_chunk_bound_to_position() {
+        let key = FileKey::generate();
+        let sealed = key.seal_chunk(3, 10, b"chunk three").unwrap();
+        // Moved to another index
+        assert!(key.open_chunk(4, 10, &sealed).is_err());
+        // Passed off as the last chunk of a truncated file
+        assert!(key.open_chunk(3, 4, &sealed).is_err());
+        // Another file's key
+        assert!(FileKey::generate().open_chunk(3, 10, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_bad_key_length() {
+        assert!(FileKey::from_bytes(&[0u8; 16]).is_err());
+    }
+
+    #[test]
+    fn test_checksum_is_streaming_sha256() {
+        let mut streamed = FileChecksum::new();
+        streamed.update(b"hello ");
+        streamed.update(b"world");
+        let digest: [u8; 32] = Sha256::digest(b"hello world").into();
+        assert!(streamed.clone().verify(&digest));
+        assert_eq!(streamed.finalize(), digest);
+
+        let mut other = FileChecksum::new();
+        other.update(b"hello there");
+        assert!(!other.verify(&digest));
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
@@ -42,6 +44,30 @@ impl MediaEncryptionKey {
         self.generation
     }
 
+    /// Serialize as `generation (u64 LE) || key (32)` — the form stored in
+    /// Stronghold and delivered to members.
+    pub fn to_payload(&self) -> [u8; MEK_PAYLOAD_LEN] {
+        let mut payload = [0u8; MEK_PAYLOAD_LEN];
+        payload[..8].copy_from_slice(&self.generation.to_le_bytes());
+        payload[8..].copy_from_slice(&self.key);
+        payload
+    }
+
+    /// Parse a payload produced by [`to_payload`](Self::to_payload).
+    pub fn from_payload(payload: &[u8]) -> Result<Self, CryptoError> {
+        if payload.len() != MEK_PAYLOAD_LEN {
+            return Err(CryptoError::InvalidKey(format!(
+                "MEK payload must be {MEK_PAYLOAD_LEN} bytes, got {}",
+                payload.len()
+            )));
+        }
+        let mut generation = [0u8; 8];
+        generation.copy_from_slice(&payload[..8]);
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&payload[8..]);
+        Ok(Self::from_bytes(key, u64::from_le_bytes(generation)))
+    }
+
     /// Encrypt a plaintext message.
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
         let cipher =
@@ -80,6 +106,86 @@ impl MediaEncryptionKey {
     }
 }
 
+/// Length of a serialized MEK: generation (8) + key (32).
+pub const MEK_PAYLOAD_LEN: usize = 8 + 32;
+
+/// Every MEK generation a member holds for one community.
+///
+/// Messages are tagged with the generation they were encrypted under, so
+/// history sent before a rotation stays readable after it. New messages
+/// are always encrypted with the highest generation.
+#[derive(Default)]
+pub struct MediaKeyRing {
+    keys: BTreeMap<u64, MediaEncryptionKey>,
+}
+
+impl MediaKeyRing {
+    /// Create an empty keyring.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a generation, replacing any key already held for it.
+    pub fn insert(&mut self, mek: MediaEncryptionKey) {
+        self.keys.insert(mek.generation(), mek);
+    }
+
+    /// Key for a specific generation, if held.
+    pub fn get(&self, generation: u64) -> Option<&MediaEncryptionKey> {
+        self.keys.get(&generation)
+    }
+
+    /// The newest generation — the one to encrypt outgoing messages with.
+    pub fn current(&self) -> Option<&MediaEncryptionKey> {
+        self.keys.values().next_back()
+    }
+
+    /// Generation number of [`current`](Self::current), if any.
+    pub fn current_generation(&self) -> Option<u64> {
+        self.keys.keys().next_back().copied()
+    }
+
+    /// Whether the keyring holds no generations.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Decrypt a message encrypted under `generation`.
+    pub fn decrypt(&self, generation: u64, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.get(generation)
+            .ok_or_else(|| CryptoError::DecryptionError(format!("no MEK for generation {generation}")))?
+            .decrypt(data)
+    }
+
+    /// Serialize as concatenated MEK payloads in ascending generation order.
+    ///
+    /// A single-generation keyring serializes exactly like
+    /// [`MediaEncryptionKey::to_payload`], so older single-key vault entries
+    /// load unchanged.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.keys.len() * MEK_PAYLOAD_LEN);
+        for mek in self.keys.values() {
+            out.extend_from_slice(&mek.to_payload());
+        }
+        out
+    }
+
+    /// Parse bytes produced by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CryptoError> {
+        if !data.len().is_multiple_of(MEK_PAYLOAD_LEN) {
+            return Err(CryptoError::InvalidKey(format!(
+                "MEK keyring length {} is not a multiple of {MEK_PAYLOAD_LEN}",
+                data.len()
+            )));
+        }
+        let mut ring = Self::new();
+        for payload in data.chunks_exact(MEK_PAYLOAD_LEN) {
+            ring.insert(MediaEncryptionKey::from_payload(payload)?);
+        }
+        Ok(ring)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,4 +210,35 @@ mod tests {
         let encrypted = mek1.encrypt(plaintext).unwrap();
         assert!(mek2.decrypt(&encrypted).is_err());
     }
+
+    #[test]
+    fn keyring_decrypts_by_generation() {
+        let mut ring = MediaKeyRing::new();
+        let old = MediaEncryptionKey::generate(1);
+        let old_ciphertext = old.encrypt(b"before rotation").unwrap();
+        ring.insert(old);
+        ring.insert(MediaEncryptionKey::generate(2));
+
+        assert_eq!(ring.current_generation(), Some(2));
+        assert_eq!(ring.decrypt(1, &old_ciphertext).unwrap(), b"before rotation");
+        assert!(ring.decrypt(2, &old_ciphertext).is_err());
+        assert!(ring.decrypt(3, &old_ciphertext).is_err());
+    }
+
+    #[test]
+    fn keyring_bytes_roundtrip_and_legacy_payload() {
+        let mut ring = MediaKeyRing::new();
+        ring.insert(MediaEncryptionKey::generate(3));
+        ring.insert(MediaEncryptionKey::generate(1));
+        let restored = MediaKeyRing::from_bytes(&ring.to_bytes()).unwrap();
+        assert_eq!(restored.current_generation(), Some(3));
+        assert_eq!(restored.get(1).unwrap().as_bytes(), ring.get(1).unwrap().as_bytes());
+
+        // A single legacy `gen || key` entry is a one-generation keyring
+        let legacy = MediaEncryptionKey::generate(7);
+        let restored = MediaKeyRing::from_bytes(&legacy.to_payload()).unwrap();
+        assert_eq!(restored.current().unwrap().as_bytes(), legacy.as_bytes());
+
+        assert!(MediaKeyRing::from_bytes(&[0u8; 41]).is_err());
+    }
 }
@@ -0,0 +1,254 @@
+//! MEK delivery to community members.
+//!
+//! Two modes:
+//! - **Server-held MEKs:** the community server initiates one X3DH session
+//!   per member pseudonym, using a prekey bundle the member attaches to its
+//!   join or MEK request. Every MEK generation then travels as a Double
+//!   Ratchet message on that session, so key bytes never cross the network
+//!   in the clear.
+//! - **Zero-knowledge:** a privileged member's client generates each MEK
+//!   and wraps it to every member's pseudonym key with
+//!   [`wrap_mek_for_pseudonym`]. The server only stores and relays the
+//!   opaque blobs.
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey as X25519Public, StaticSecret};
+
+use crate::error::CryptoError;
+use crate::group::media_key::MediaEncryptionKey;
+use crate::group::pseudonym::pseudonym_to_x25519;
+use crate::sealed_box;
+use crate::signal::{PreKeyBundle, SessionInitInfo, SignalSessionManager};
+
+/// Signal identity `(private, public)` key pair for a member's pseudonym.
+///
+/// This is the X25519 form of the pseudonym key, so the server can tie a
+/// bundle to the pseudonym that signed the request (see
+/// [`bundle_matches_pseudonym`]).
+pub fn pseudonym_signal_identity(pseudonym: &SigningKey) -> (Vec<u8>, Vec<u8>) {
+    identity_pair(&pseudonym_to_x25519(pseudonym))
+}
+
+/// Signal identity `(private, public)` key pair a community server uses
+/// for MEK delivery.
+///
+/// Derived from the community's DHT owner secret so it is stable across
+/// server restarts without extra storage.
+pub fn server_signal_identity(owner_secret: &[u8], community_id: &str) -> (Vec<u8>, Vec<u8>) {
+    let hkdf = Hkdf::<Sha256>::new(Some(b"rekindle-mek-delivery-v1"), owner_secret);
+    let mut seed = [0u8; 32];
+    hkdf.expand(community_id.as_bytes(), &mut seed)
+        .expect("32-byte output is a valid HKDF-SHA256 length");
+    identity_pair(&StaticSecret::from(seed))
+}
+
+/// Whether a prekey bundle's identity key belongs to `pseudonym_pubkey`.
+///
+/// The server has already authenticated the pseudonym via the request
+/// envelope signature, so this stops a relayed or substituted bundle from
+/// receiving someone else's MEK.
+pub fn bundle_matches_pseudonym(bundle: &PreKeyBundle, pseudonym_pubkey: &[u8]) -> bool {
+    let Ok(bytes) = <[u8; 32]>::try_from(pseudonym_pubkey) else {
+        return false;
+    };
+    let Ok(verifying) = VerifyingKey::from_bytes(&bytes) else {
+        return false;
+    };
+    bundle.identity_key == verifying.to_montgomery().to_bytes()
+}
+
+/// Server side: encrypt one MEK generation for a member.
+///
+/// When `bundle` is given, a fresh session is established first and its
+/// init parameters are returned for the member to respond with. Otherwise
+/// the existing session is used.
+pub fn seal_mek(
+    manager: &SignalSessionManager,
+    member_address: &str,
+    bundle: Option<&PreKeyBundle>,
+    mek: &MediaEncryptionKey,
+) -> Result<(Vec<u8>, Option<SessionInitInfo>), CryptoError> {
+    let init = match bundle {
+        Some(bundle) => Some(manager.establish_session(member_address, bundle)?),
+        None => None,
+    };
+    let ciphertext = manager.encrypt(member_address, &mek.to_payload())?;
+    Ok((ciphertext, init))
+}
+
+/// Member side: decrypt a MEK produced by [`seal_mek`].
+///
+/// `session_init` carries the server's identity key and X3DH parameters
+/// when the server started a new session for this delivery.
+pub fn open_mek(
+    manager: &SignalSessionManager,
+    server_address: &str,
+    session_init: Option<(&[u8], &SessionInitInfo)>,
+    ciphertext: &[u8],
+) -> Result<MediaEncryptionKey, CryptoError> {
+    if let Some((server_identity, init)) = session_init {
+        manager.respond_to_session(
+            server_address,
+            server_identity,
+            &init.ephemeral_public_key,
+            init.signed_prekey_id,
+            init.one_time_prekey_id,
+        )?;
+    }
+    let payload = manager.decrypt(server_address, ciphertext)?;
+    MediaEncryptionKey::from_payload(&payload)
+}
+
+/// Context label for wrapped MEKs.
+const WRAP_CONTEXT: &[u8] = b"rekindle-mek-wrap-v1";
+
+/// Length of a wrapped MEK: a sealed box around the 40-byte MEK payload.
+pub const WRAPPED_MEK_LEN: usize = sealed_box::SEAL_OVERHEAD + 40;
+
+/// Wrap a MEK to one member's pseudonym (zero-knowledge mode).
+///
+/// The MEK payload is a [`sealed_box`] to the Montgomery form of the
+/// recipient's pseudonym key.
+///
+/// [`sealed_box`]: crate::sealed_box
+pub fn wrap_mek_for_pseudonym(
+    mek: &MediaEncryptionKey,
+    recipient_pseudonym: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let recipient = <[u8; 32]>::try_from(recipient_pseudonym)
+        .ok()
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| CryptoError::InvalidKey("invalid recipient pseudonym key".into()))?;
+    let recipient_x25519 = X25519Public::from(recipient.to_montgomery().to_bytes());
+    sealed_box::seal(WRAP_CONTEXT, &recipient_x25519, &mek.to_payload())
+}
+
+/// Open a MEK produced by [`wrap_mek_for_pseudonym`] with our pseudonym key.
+pub fn unwrap_mek(
+    pseudonym: &SigningKey,
+    wrapped: &[u8],
+) -> Result<MediaEncryptionKey, CryptoError> {
+    if wrapped.len() != WRAPPED_MEK_LEN {
+        return Err(CryptoError::DecryptionError(
+            "wrapped MEK has wrong length".into(),
+        ));
+    }
+    let payload = sealed_box::open(WRAP_CONTEXT, &pseudonym_to_x25519(pseudonym), wrapped)?;
+    MediaEncryptionKey::from_payload(&payload)
+}
+
+fn identity_pair(secret: &StaticSecret) -> (Vec<u8>, Vec<u8>) {
+    let public = X25519Public::from(secret);
+    (secret.to_bytes().to_vec(), public.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_link;
+    use crate::group::pseudonym::derive_community_pseudonym;
+    use crate::identity::Identity;
+    use crate::signal::{MemoryIdentityStore, MemoryPreKeyStore, MemorySessionStore};
+
+    const COMMUNITY: &str = "community_abc";
+
+    fn manager((private, public): (Vec<u8>, Vec<u8>)) -> SignalSessionManager {
+        SignalSessionManager::new(
+            Box::new(MemoryIdentityStore::new(private, public, 1)),
+            Box::new(MemoryPreKeyStore::new()),
+            Box::new(MemorySessionStore::new()),
+        )
+    }
+
+    #[test]
+    fn mek_roundtrip_over_session() {
+        let pseudonym = derive_community_pseudonym(&[5u8; 32], COMMUNITY);
+        let member_hex = hex::encode(pseudonym.verifying_key().as_bytes());
+        let server = manager(server_signal_identity(b"owner secret", COMMUNITY));
+        let member = manager(pseudonym_signal_identity(&pseudonym));
+
+        let bundle = member.generate_prekey_bundle(1, None).unwrap();
+        assert!(bundle_matches_pseudonym(
+            &bundle,
+            pseudonym.verifying_key().as_bytes()
+        ));
+
+        // First delivery establishes the session
+        let gen1 = MediaEncryptionKey::generate(1);
+        let (ciphertext, init) = seal_mek(&server, &member_hex, Some(&bundle), &gen1).unwrap();
+        let init = init.unwrap();
+        let (_, server_identity) = server_signal_identity(b"owner secret", COMMUNITY);
+        let opened = open_mek(
+            &member,
+            COMMUNITY,
+            Some((&server_identity, &init)),
+            &ciphertext,
+        )
+        .unwrap();
+        assert_eq!(opened.generation(), 1);
+        assert_eq!(opened.as_bytes(), gen1.as_bytes());
+
+        // Later generations reuse it
+        let gen2 = MediaEncryptionKey::generate(2);
+        let (ciphertext, init) = seal_mek(&server, &member_hex, None, &gen2).unwrap();
+        assert!(init.is_none());
+        let opened = open_mek(&member, COMMUNITY, None, &ciphertext).unwrap();
+        assert_eq!(opened.as_bytes(), gen2.as_bytes());
+    }
+
+    #[test]
+    fn bundle_for_other_pseudonym_rejected() {
+        let alice = derive_community_pseudonym(&[1u8; 32], COMMUNITY);
+        let mallory = derive_community_pseudonym(&[2u8; 32], COMMUNITY);
+        let bundle = manager(pseudonym_signal_identity(&mallory))
+            .generate_prekey_bundle(1, None)
+            .unwrap();
+        assert!(!bundle_matches_pseudonym(
+            &bundle,
+            alice.verifying_key().as_bytes()
+        ));
+        assert!(!bundle_matches_pseudonym(&bundle, &[0u8; 5]));
+    }
+
+    #[test]
+    fn wrapped_mek_opens_only_for_recipient() {
+        let alice = derive_community_pseudonym(&[1u8; 32], COMMUNITY);
+        let bob = derive_community_pseudonym(&[2u8; 32], COMMUNITY);
+        let mek = MediaEncryptionKey::generate(4);
+
+        let wrapped = wrap_mek_for_pseudonym(&mek, alice.verifying_key().as_bytes()).unwrap();
+        assert_eq!(wrapped.len(), WRAPPED_MEK_LEN);
+        let opened = unwrap_mek(&alice, &wrapped).unwrap();
+        assert_eq!(opened.generation(), 4);
+        assert_eq!(opened.as_bytes(), mek.as_bytes());
+
+        assert!(unwrap_mek(&bob, &wrapped).is_err());
+
+        let mut tampered = wrapped;
+        tampered[40] ^= 1;
+        assert!(unwrap_mek(&alice, &tampered).is_err());
+    }
+
+    #[test]
+    fn device_grants_and_wrapped_meks_do_not_open_as_each_other() {
+        // Same key pair and payload length: only the context label differs
+        let pseudonym = derive_community_pseudonym(&[3u8; 32], COMMUNITY);
+        let device = Identity::from_secret_bytes(&pseudonym.to_bytes());
+        let mek = MediaEncryptionKey::generate(1);
+
+        let wrapped = wrap_mek_for_pseudonym(&mek, pseudonym.verifying_key().as_bytes()).unwrap();
+        assert!(device_link::open_for_device(&device, &wrapped).is_err());
+
+        let grant =
+            device_link::seal_for_device(pseudonym.verifying_key().as_bytes(), &mek.to_payload())
+                .unwrap();
+        assert_eq!(grant.len(), WRAPPED_MEK_LEN);
+        assert!(unwrap_mek(&pseudonym, &grant).is_err());
+        assert_eq!(
+            device_link::open_for_device(&device, &grant).unwrap().as_slice(),
+            mek.to_payload().as_slice()
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Signed statements that a pseudonym belongs to a community.
+//!
+//! In zero-knowledge communities key holders wrap the MEK only for members
+//! they can trace back to someone they trust themselves, so a server that
+//! lists a pseudonym of its own gets nothing. A member vouches for another
+//! by signing the community ID and the other's pseudonym. Invites carry a
+//! key derived by the inviter: the inviter vouches for that key, and the
+//! newcomer signs their own pseudonym with it to join.
+
+use std::collections::HashSet;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+/// Domain separator for vouch signatures.
+const VOUCH_CONTEXT: &[u8] = b"rekindle-member-vouch-v1";
+
+/// `voucher` says `member` belongs to the community.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberVouch {
+    /// Ed25519 key of whoever vouches: a member's pseudonym or an invite key.
+    pub voucher: [u8; 32],
+    /// Ed25519 key vouched for.
+    pub member: [u8; 32],
+    /// Signature by `voucher` over the community ID and `member`.
+    pub signature: Vec<u8>,
+}
+
+impl MemberVouch {
+    /// Whether the signature is `voucher`'s and covers this community.
+    pub fn verify(&self, community_id: &str) -> bool {
+        let Ok(voucher) = VerifyingKey::from_bytes(&self.voucher) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        voucher
+            .verify(&vouch_message(community_id, &self.member), &signature)
+            .is_ok()
+    }
+}
+
+/// Vouch for `member` in `community_id` with our pseudonym (or invite key).
+pub fn vouch(voucher: &SigningKey, community_id: &str, member: &[u8; 32]) -> MemberVouch {
+    MemberVouch {
+        voucher: voucher.verifying_key().to_bytes(),
+        member: *member,
+        signature: voucher
+            .sign(&vouch_message(community_id, member))
+            .to_bytes()
+            .to_vec(),
+    }
+}
+
+/// Key for the invite `invite_code`, derived from the inviter's pseudonym
+/// so the inviter can rebuild its link at any time. The server never sees
+/// it, so it cannot admit anyone in the invite's name.
+pub fn invite_key(inviter: &SigningKey, invite_code: &str) -> SigningKey {
+    let hkdf = Hkdf::<Sha256>::new(Some(b"rekindle-invite-key-v1"), inviter.as_bytes());
+    let mut seed = [0u8; 32];
+    hkdf.expand(invite_code.as_bytes(), &mut seed)
+        .expect("32-byte output is a valid HKDF-SHA256 length");
+    SigningKey::from_bytes(&seed)
+}
+
+/// Every key reachable from `anchors` through valid vouches for
+/// `community_id`, anchors included.
+///
+/// Vouches only extend trust downwards: one made by a key nobody trusted
+/// adds nothing, however many others it names.
+pub fn vouched_members(
+    community_id: &str,
+    anchors: &[[u8; 32]],
+    vouches: &[MemberVouch],
+) -> HashSet<[u8; 32]> {
+    let valid: Vec<&MemberVouch> = vouches.iter().filter(|v| v.verify(community_id)).collect();
+    let mut trusted: HashSet<[u8; 32]> = anchors.iter().copied().collect();
+    let mut frontier: Vec<[u8; 32]> = anchors.to_vec();
+    while let Some(key) = frontier.pop() {
+        for v in valid.iter().filter(|v| v.voucher == key) {
+            if trusted.insert(v.member) {
+                frontier.push(v.member);
+            }
+        }
+    }
+    trusted
+}
+
+fn vouch_message(community_id: &str, member: &[u8; 32]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(VOUCH_CONTEXT.len() + 4 + community_id.len() + 32);
+    msg.extend_from_slice(VOUCH_CONTEXT);
+    msg.extend_from_slice(&u32::try_from(community_id.len()).unwrap_or(u32::MAX).to_be_bytes());
+    msg.extend_from_slice(community_id.as_bytes());
+    msg.extend_from_slice(member);
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::pseudonym::derive_community_pseudonym;
+
+    const COMMUNITY: &str = "community_abc";
+
+    fn pseudonym(seed: u8) -> SigningKey {
+        derive_community_pseudonym(&[seed; 32], COMMUNITY)
+    }
+
+    fn key(k: &SigningKey) -> [u8; 32] {
+        k.verifying_key().to_bytes()
+    }
+
+    #[test]
+    fn invited_member_is_traced_to_the_inviter() {
+        let owner = pseudonym(1);
+        let admin = pseudonym(2);
+        let newcomer = pseudonym(3);
+        let invite = invite_key(&admin, "code123");
+        assert_eq!(key(&invite), key(&invite_key(&admin, "code123")));
+
+        let vouches = vec![
+            vouch(&owner, COMMUNITY, &key(&admin)),
+            vouch(&admin, COMMUNITY, &key(&invite)),
+            vouch(&invite, COMMUNITY, &key(&newcomer)),
+        ];
+        let trusted = vouched_members(COMMUNITY, &[key(&owner)], &vouches);
+        assert!(trusted.contains(&key(&newcomer)));
+        assert!(trusted.contains(&key(&admin)));
+    }
+
+    #[test]
+    fn server_listed_member_without_a_chain_is_not_trusted() {
+        let owner = pseudonym(1);
+        let member = pseudonym(2);
+        // The server makes up a key, has it vouch for itself and a sock puppet
+        let server_key = pseudonym(9);
+        let puppet = pseudonym(10);
+        let vouches = vec![
+            vouch(&owner, COMMUNITY, &key(&member)),
+            vouch(&server_key, COMMUNITY, &key(&puppet)),
+            vouch(&server_key, COMMUNITY, &key(&member)),
+        ];
+        let trusted = vouched_members(COMMUNITY, &[key(&owner)], &vouches);
+        assert!(trusted.contains(&key(&member)));
+        assert!(!trusted.contains(&key(&puppet)));
+        assert!(!trusted.contains(&key(&server_key)));
+    }
+
+    #[test]
+    fn forged_or_misdirected_vouches_are_ignored() {
+        let owner = pseudonym(1);
+        let puppet = pseudonym(10);
+
+        // Signature copied onto another member
+        let mut forged = vouch(&owner, COMMUNITY, &key(&pseudonym(2)));
+        forged.member = key(&puppet);
+        assert!(!forged.verify(COMMUNITY));
+
+        // A real vouch from another community
+        let elsewhere = vouch(&owner, "community_xyz", &key(&puppet));
+        assert!(!elsewhere.verify(COMMUNITY));
+
+        let trusted = vouched_members(COMMUNITY, &[key(&owner)], &[forged, elsewhere]);
+        assert_eq!(trusted.len(), 1);
+    }
+}
@@ -1,2 +1,5 @@
 pub mod media_key;
+pub mod member_vouch;
+pub mod mek_delivery;
 pub mod pseudonym;
+pub mod tree;
@@ -0,0 +1,55 @@
+//! Minimal HPKE-style public-key encryption for path secrets and welcomes.
+//!
+//! A [`sealed_box`] to the recipient's node key under the tree's own
+//! context label, with the caller's AAD — the group context, so a
+//! ciphertext can't be replayed into another epoch — bound on top.
+//!
+//! [`sealed_box`]: crate::sealed_box
+
+use serde::{Deserialize, Serialize};
+use x25519_dalek::{PublicKey as X25519Public, StaticSecret};
+
+use crate::error::CryptoError;
+use crate::sealed_box;
+
+/// Context label for tree path secrets and welcomes.
+const HPKE_CONTEXT: &[u8] = b"rekindle-tree-hpke-v2";
+
+/// A value encrypted to one tree node's public key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HpkeCiphertext {
+    /// Sender's ephemeral X25519 public key.
+    pub kem_output: [u8; 32],
+    /// Nonce followed by the AES-256-GCM ciphertext with tag.
+    pub ciphertext: Vec<u8>,
+}
+
+/// Encrypt `plaintext` to `recipient`.
+pub(super) fn seal(
+    recipient: &[u8; 32],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<HpkeCiphertext, CryptoError> {
+    let mut sealed =
+        sealed_box::seal_with_aad(HPKE_CONTEXT, &X25519Public::from(*recipient), aad, plaintext)?;
+    let ciphertext = sealed.split_off(32);
+    let kem_output = <[u8; 32]>::try_from(sealed.as_slice())
+        .map_err(|_| CryptoError::EncryptionError("sealed box too short".into()))?;
+    Ok(HpkeCiphertext {
+        kem_output,
+        ciphertext,
+    })
+}
+
+/// Decrypt a value sealed to the public key of `secret`.
+pub(super) fn open(
+    secret: &StaticSecret,
+    sealed: &HpkeCiphertext,
+    aad: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let mut joined = Vec::with_capacity(32 + sealed.ciphertext.len());
+    joined.extend_from_slice(&sealed.kem_output);
+    joined.extend_from_slice(&sealed.ciphertext);
+    let mut plaintext = sealed_box::open_with_aad(HPKE_CONTEXT, secret, aad, &joined)?;
+    Ok(std::mem::take(&mut *plaintext))
+}
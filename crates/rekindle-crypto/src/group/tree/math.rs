@@ -0,0 +1,127 @@
+//! Array-based left-balanced binary tree arithmetic (RFC 9420, appendix C).
+//!
+//! Leaves sit at even node indices (leaf `i` is node `2i`), parents at odd
+//! ones. Trees here are always full — the leaf count is a power of two —
+//! so every index below the node width is valid and growing a tree keeps
+//! every existing index in place.
+
+/// Level of a node: 0 for leaves, `k` for a parent whose subtree has `2^k` leaves.
+pub(super) fn level(x: u32) -> u32 {
+    x.trailing_ones()
+}
+
+/// Number of nodes in a tree with `leaves` leaves.
+pub(super) fn node_width(leaves: u32) -> u32 {
+    if leaves == 0 {
+        0
+    } else {
+        2 * (leaves - 1) + 1
+    }
+}
+
+/// Root node index of a tree with `leaves` leaves.
+pub(super) fn root(leaves: u32) -> u32 {
+    let width = node_width(leaves);
+    (1 << width.ilog2()) - 1
+}
+
+/// Node index of a leaf.
+pub(super) fn leaf_node(leaf: u32) -> u32 {
+    2 * leaf
+}
+
+fn left(x: u32) -> u32 {
+    let k = level(x);
+    debug_assert!(k > 0, "leaf has no children");
+    x ^ (1 << (k - 1))
+}
+
+fn right(x: u32) -> u32 {
+    let k = level(x);
+    debug_assert!(k > 0, "leaf has no children");
+    x ^ (3 << (k - 1))
+}
+
+/// Children of a parent node.
+pub(super) fn children(x: u32) -> (u32, u32) {
+    (left(x), right(x))
+}
+
+fn parent(x: u32) -> u32 {
+    let k = level(x);
+    let b = (x >> (k + 1)) & 1;
+    (x | (1 << k)) ^ (b << (k + 1))
+}
+
+fn sibling(x: u32) -> u32 {
+    let p = parent(x);
+    if x < p {
+        right(p)
+    } else {
+        left(p)
+    }
+}
+
+/// Parents from `x` (exclusive) up to the root (inclusive).
+pub(super) fn direct_path(x: u32, leaves: u32) -> Vec<u32> {
+    let r = root(leaves);
+    let mut path = Vec::new();
+    let mut node = x;
+    while node != r {
+        node = parent(node);
+        path.push(node);
+    }
+    path
+}
+
+/// Siblings of `x` and of each node on its direct path, below the root.
+///
+/// `copath(x)[i]` is the child of `direct_path(x)[i]` that is not on the
+/// path from `x`.
+pub(super) fn copath(x: u32, leaves: u32) -> Vec<u32> {
+    let r = root(leaves);
+    let mut path = Vec::new();
+    let mut node = x;
+    while node != r {
+        path.push(sibling(node));
+        node = parent(node);
+    }
+    path
+}
+
+/// Lowest node that has both `x` and `y` in its subtree.
+pub(super) fn common_ancestor(x: u32, y: u32) -> u32 {
+    let (lx, ly) = (level(x) + 1, level(y) + 1);
+    if lx <= ly && x >> ly == y >> ly {
+        return y;
+    }
+    if ly <= lx && x >> lx == y >> lx {
+        return x;
+    }
+    let (mut xn, mut yn, mut k) = (x, y, 0);
+    while xn != yn {
+        xn >>= 1;
+        yn >>= 1;
+        k += 1;
+    }
+    (xn << k) + (1 << (k - 1)) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eight_leaf_tree_shape() {
+        assert_eq!(node_width(8), 15);
+        assert_eq!(root(8), 7);
+        assert_eq!(root(1), 0);
+        assert_eq!(direct_path(0, 8), vec![1, 3, 7]);
+        assert_eq!(copath(0, 8), vec![2, 5, 11]);
+        assert_eq!(direct_path(10, 8), vec![9, 11, 7]);
+        assert_eq!(children(7), (3, 11));
+        assert_eq!(common_ancestor(0, 6), 3);
+        assert_eq!(common_ancestor(2, 12), 7);
+        assert_eq!(common_ancestor(8, 10), 9);
+    }
+}
@@ -0,0 +1,1084 @@
+//! `TreeKEM` group key agreement for large communities.
+//!
+//! Members sit at the leaves of a ratchet tree; every parent node holds an
+//! X25519 key pair known exactly to the members below it. A commit applies
+//! a batch of add/remove proposals and replaces every key on the
+//! committer's direct path, encrypting each new path secret only to the
+//! resolution of the sibling subtree. Re-keying therefore costs O(log n)
+//! encryptions instead of one pairwise delivery per member.
+//!
+//! Each commit advances the epoch. The epoch secret chains from the
+//! previous epoch's init secret and the commit secret at the top of the
+//! committer's path, and the epoch's MEK is derived from it, with the
+//! epoch number as the MEK generation. New members receive a [`Welcome`]
+//! carrying the public tree and the secrets they need to join.
+//!
+//! The community server is only the delivery service: it accepts the first
+//! commit for each epoch, rejects the rest, and relays them in order. It
+//! never sees a secret. Commits and welcomes are signed with the sender's
+//! community pseudonym, which doubles as the leaf credential.
+
+mod hpke;
+mod math;
+
+use std::collections::BTreeMap;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey as X25519Public, StaticSecret};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+pub use hpke::HpkeCiphertext;
+
+use crate::error::CryptoError;
+use crate::group::media_key::MediaEncryptionKey;
+use math::leaf_node;
+
+const KDF_CONTEXT: &[u8] = b"rekindle-tree-v1";
+const SIGN_COMMIT: &[u8] = b"rekindle-tree-commit-v1";
+const SIGN_WELCOME: &[u8] = b"rekindle-tree-welcome-v1";
+const SIGN_KEY_PACKAGE: &[u8] = b"rekindle-tree-key-package-v1";
+const TREE_HASH: &[u8] = b"rekindle-tree-hash-v1";
+
+/// A member's leaf: their credential and current encryption key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeafNode {
+    /// Ed25519 public key — the member's community pseudonym.
+    pub credential: [u8; 32],
+    /// X25519 key that path secrets for this leaf are encrypted to.
+    pub encryption_key: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Node {
+    Leaf(LeafNode),
+    Parent([u8; 32]),
+}
+
+/// Public half of the ratchet tree, identical for every member in an epoch.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RatchetTree {
+    nodes: Vec<Option<Node>>,
+}
+
+impl RatchetTree {
+    /// Number of leaf slots (occupied or blank).
+    pub fn leaf_count(&self) -> u32 {
+        u32::try_from(self.nodes.len())
+            .unwrap_or(u32::MAX)
+            .div_ceil(2)
+    }
+
+    /// The member at a leaf slot, if occupied.
+    pub fn leaf(&self, leaf: u32) -> Option<&LeafNode> {
+        match self.node(leaf_node(leaf))? {
+            Node::Leaf(leaf) => Some(leaf),
+            Node::Parent(_) => None,
+        }
+    }
+
+    /// Occupied leaves as `(leaf index, leaf)`.
+    pub fn members(&self) -> impl Iterator<Item = (u32, &LeafNode)> {
+        (0..self.leaf_count()).filter_map(|i| self.leaf(i).map(|leaf| (i, leaf)))
+    }
+
+    /// Leaf index of the member with this credential.
+    pub fn find(&self, credential: &[u8; 32]) -> Option<u32> {
+        self.members()
+            .find(|(_, leaf)| leaf.credential == *credential)
+            .map(|(i, _)| i)
+    }
+
+    fn node(&self, x: u32) -> Option<&Node> {
+        self.nodes.get(x as usize)?.as_ref()
+    }
+
+    fn set(&mut self, x: u32, node: Option<Node>) {
+        self.nodes[x as usize] = node;
+    }
+
+    fn node_key(&self, x: u32) -> Option<[u8; 32]> {
+        match self.node(x)? {
+            Node::Leaf(leaf) => Some(leaf.encryption_key),
+            Node::Parent(key) => Some(*key),
+        }
+    }
+
+    /// Smallest set of occupied nodes covering every member below `x`.
+    fn resolution(&self, x: u32) -> Vec<u32> {
+        if self.node(x).is_some() {
+            return vec![x];
+        }
+        if math::level(x) == 0 {
+            return Vec::new();
+        }
+        let (left, right) = math::children(x);
+        let mut nodes = self.resolution(left);
+        nodes.extend(self.resolution(right));
+        nodes
+    }
+
+    /// Blank a leaf and every node above it.
+    fn blank_path(&mut self, leaf: u32) {
+        let x = leaf_node(leaf);
+        self.set(x, None);
+        for p in math::direct_path(x, self.leaf_count()) {
+            self.set(p, None);
+        }
+    }
+
+    /// Put a member in the leftmost blank slot, doubling the tree if it is
+    /// full. The new leaf's direct path is blanked: it knows none of those
+    /// secrets until the committer's path update covers it.
+    fn add_leaf(&mut self, leaf: LeafNode) -> u32 {
+        let free = (0..self.leaf_count()).find(|i| self.node(leaf_node(*i)).is_none());
+        let index = free.unwrap_or_else(|| {
+            let leaves = self.leaf_count();
+            self.nodes
+                .resize(math::node_width((leaves * 2).max(1)) as usize, None);
+            leaves
+        });
+        let x = leaf_node(index);
+        self.set(x, Some(Node::Leaf(leaf)));
+        for p in math::direct_path(x, self.leaf_count()) {
+            self.set(p, None);
+        }
+        index
+    }
+
+    fn set_leaf_key(&mut self, leaf: u32, encryption_key: [u8; 32]) {
+        if let Some(Some(Node::Leaf(node))) = self.nodes.get_mut(leaf_node(leaf) as usize) {
+            node.encryption_key = encryption_key;
+        }
+    }
+
+    /// Whether the node array has the shape of a full tree.
+    fn validate(&self) -> Result<(), CryptoError> {
+        let leaves = self.leaf_count();
+        let well_formed = leaves.is_power_of_two()
+            && self.nodes.len() == math::node_width(leaves) as usize
+            && self.nodes.iter().enumerate().all(|(i, node)| match node {
+                None => true,
+                Some(Node::Leaf(_)) => i % 2 == 0,
+                Some(Node::Parent(_)) => i % 2 == 1,
+            });
+        if well_formed {
+            Ok(())
+        } else {
+            Err(CryptoError::VerificationError(
+                "malformed ratchet tree".into(),
+            ))
+        }
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new().chain_update(TREE_HASH);
+        for node in &self.nodes {
+            match node {
+                None => hasher.update([0]),
+                Some(Node::Leaf(leaf)) => {
+                    hasher.update([1]);
+                    hasher.update(leaf.credential);
+                    hasher.update(leaf.encryption_key);
+                }
+                Some(Node::Parent(key)) => {
+                    hasher.update([2]);
+                    hasher.update(key);
+                }
+            }
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// A prospective member's signed leaf, published so someone can add them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyPackage {
+    /// The leaf the member will occupy.
+    pub leaf: LeafNode,
+    /// Ed25519 signature by `leaf.credential`.
+    pub signature: Vec<u8>,
+}
+
+/// Private half of a [`KeyPackage`], kept until the matching welcome arrives.
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct KeyPackageSecret {
+    encryption_secret: [u8; 32],
+}
+
+impl KeyPackage {
+    /// Generate a key package for the holder of `signing_key`.
+    pub fn generate(signing_key: &SigningKey) -> (Self, KeyPackageSecret) {
+        let encryption_secret = random_secret();
+        let leaf = LeafNode {
+            credential: signing_key.verifying_key().to_bytes(),
+            encryption_key: public_key(&encryption_secret),
+        };
+        let signature = signing_key
+            .sign(&key_package_tbs(&leaf))
+            .to_bytes()
+            .to_vec();
+        (
+            Self { leaf, signature },
+            KeyPackageSecret { encryption_secret },
+        )
+    }
+
+    /// Check the signature against the leaf's own credential.
+    pub fn verify(&self) -> Result<(), CryptoError> {
+        verify_signature(
+            &self.leaf.credential,
+            &key_package_tbs(&self.leaf),
+            &self.signature,
+        )
+    }
+}
+
+/// A change to group membership, applied by a [`Commit`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Proposal {
+    /// Add the owner of a key package.
+    Add(KeyPackage),
+    /// Remove the member at a leaf.
+    Remove { leaf: u32 },
+}
+
+/// New public key for one node on the committer's direct path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdatePathNode {
+    /// The node's new X25519 public key.
+    pub public_key: [u8; 32],
+    /// The node's path secret, encrypted to each node in the resolution of
+    /// its other child, in resolution order.
+    pub encrypted_path_secret: Vec<HpkeCiphertext>,
+}
+
+/// A signed transition from one epoch to the next.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Commit {
+    /// Epoch the commit was made in; the group moves to `epoch + 1`.
+    pub epoch: u64,
+    /// Committer's leaf index.
+    pub sender: u32,
+    /// Membership changes, applied removes first, then adds.
+    pub proposals: Vec<Proposal>,
+    /// Committer's new leaf encryption key.
+    pub leaf_key: [u8; 32],
+    /// New keys for the committer's direct path, leaf to root.
+    pub path: Vec<UpdatePathNode>,
+    /// Proves the committer derived the same epoch secret as receivers.
+    pub confirmation_tag: [u8; 32],
+    /// Ed25519 signature by the committer's credential.
+    pub signature: Vec<u8>,
+}
+
+/// Everything a new member needs to join at a commit's new epoch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Welcome {
+    /// Group (community) identifier.
+    pub group_id: Vec<u8>,
+    /// Epoch the new member joins at.
+    pub epoch: u64,
+    /// Committer's leaf index.
+    pub sender: u32,
+    /// The new member's leaf index.
+    pub new_leaf: u32,
+    /// Public tree after the commit.
+    pub tree: RatchetTree,
+    /// Joiner secret and the path secret at the committer/joiner common
+    /// ancestor, encrypted to the key package's encryption key.
+    pub group_secrets: HpkeCiphertext,
+    /// Same confirmation tag as the commit.
+    pub confirmation_tag: [u8; 32],
+    /// Ed25519 signature by the committer's credential.
+    pub signature: Vec<u8>,
+}
+
+impl Welcome {
+    /// Credential of the member this welcome is for.
+    pub fn recipient(&self) -> Option<&[u8; 32]> {
+        self.tree.leaf(self.new_leaf).map(|leaf| &leaf.credential)
+    }
+}
+
+/// A commit ready to send, with the welcomes for members it adds and the
+/// group state to switch to once the server has accepted it.
+pub struct CommitOutput {
+    pub commit: Commit,
+    pub welcomes: Vec<Welcome>,
+    pub next: TreeGroup,
+}
+
+/// One member's view of a `TreeKEM` group.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TreeGroup {
+    group_id: Vec<u8>,
+    epoch: u64,
+    tree: RatchetTree,
+    own_leaf: u32,
+    /// X25519 secrets for our leaf and the parents we know, by node index.
+    private_keys: BTreeMap<u32, [u8; 32]>,
+    epoch_secret: [u8; 32],
+    init_secret: [u8; 32],
+}
+
+impl Drop for TreeGroup {
+    fn drop(&mut self) {
+        for key in self.private_keys.values_mut() {
+            key.zeroize();
+        }
+        self.epoch_secret.zeroize();
+        self.init_secret.zeroize();
+    }
+}
+
+impl TreeGroup {
+    /// Start a group with ourselves as its only member.
+    ///
+    /// `epoch` is usually one past the community's current MEK generation,
+    /// so tree-derived MEKs never collide with earlier ones.
+    pub fn create(group_id: &[u8], signing_key: &SigningKey, epoch: u64) -> Self {
+        let leaf_secret = random_secret();
+        let mut tree = RatchetTree::default();
+        tree.add_leaf(LeafNode {
+            credential: signing_key.verifying_key().to_bytes(),
+            encryption_key: public_key(&leaf_secret),
+        });
+        let epoch_secret = random_secret();
+        Self {
+            group_id: group_id.to_vec(),
+            epoch,
+            tree,
+            own_leaf: 0,
+            private_keys: BTreeMap::from([(leaf_node(0), leaf_secret)]),
+            init_secret: expand(&epoch_secret, b"init", &[]),
+            epoch_secret,
+        }
+    }
+
+    /// Join from a welcome addressed to our key package.
+    pub fn join(
+        welcome: &Welcome,
+        key_package_secret: &KeyPackageSecret,
+    ) -> Result<Self, CryptoError> {
+        welcome.tree.validate()?;
+        let own = welcome
+            .tree
+            .leaf(welcome.new_leaf)
+            .ok_or_else(|| CryptoError::InvalidKey("welcome names an empty leaf".into()))?;
+        if own.encryption_key != public_key(&key_package_secret.encryption_secret) {
+            return Err(CryptoError::InvalidKey(
+                "welcome is for a different key package".into(),
+            ));
+        }
+        let sender = welcome
+            .tree
+            .leaf(welcome.sender)
+            .ok_or_else(|| CryptoError::InvalidKey("welcome sender is not in the tree".into()))?;
+        let tree_hash = welcome.tree.hash();
+        verify_signature(
+            &sender.credential,
+            &welcome_tbs(welcome, &tree_hash),
+            &welcome.signature,
+        )?;
+
+        let context = group_context(&welcome.group_id, welcome.epoch, &tree_hash);
+        let mut secrets = hpke::open(
+            &StaticSecret::from(key_package_secret.encryption_secret),
+            &welcome.group_secrets,
+            &context,
+        )?;
+        let parsed =
+            (secrets.len() == 64).then(|| (to_secret(&secrets[..32]), to_secret(&secrets[32..])));
+        secrets.zeroize();
+        let Some((joiner_secret, mut path_secret)) = parsed else {
+            return Err(CryptoError::DecryptionError(
+                "malformed welcome secrets".into(),
+            ));
+        };
+
+        let own_node = leaf_node(welcome.new_leaf);
+        let lca = math::common_ancestor(leaf_node(welcome.sender), own_node);
+        let mut private_keys = BTreeMap::from([(own_node, key_package_secret.encryption_secret)]);
+        for node in math::direct_path(own_node, welcome.tree.leaf_count())
+            .into_iter()
+            .skip_while(|n| *n != lca)
+        {
+            let secret = node_secret(&path_secret);
+            if welcome.tree.node_key(node) != Some(public_key(&secret)) {
+                return Err(CryptoError::VerificationError(
+                    "welcome path secret does not match the tree".into(),
+                ));
+            }
+            private_keys.insert(node, secret);
+            path_secret = expand(&path_secret, b"path", &[]);
+        }
+        path_secret.zeroize();
+
+        let mut group = Self {
+            group_id: welcome.group_id.clone(),
+            epoch: welcome.epoch,
+            tree: welcome.tree.clone(),
+            own_leaf: welcome.new_leaf,
+            private_keys,
+            epoch_secret: [0u8; 32],
+            init_secret: [0u8; 32],
+        };
+        group.enter_epoch(&joiner_secret, &context);
+        if group.confirmation_tag(&context) != welcome.confirmation_tag {
+            return Err(CryptoError::VerificationError(
+                "welcome confirmation tag mismatch".into(),
+            ));
+        }
+        Ok(group)
+    }
+
+    /// Current epoch — also the generation of [`mek`](Self::mek).
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Our leaf index.
+    pub fn own_leaf(&self) -> u32 {
+        self.own_leaf
+    }
+
+    /// The public tree.
+    pub fn tree(&self) -> &RatchetTree {
+        &self.tree
+    }
+
+    /// This epoch's media encryption key.
+    pub fn mek(&self) -> MediaEncryptionKey {
+        MediaEncryptionKey::from_bytes(expand(&self.epoch_secret, b"mek", &[]), self.epoch)
+    }
+
+    /// Build a commit applying `proposals` and refreshing our path.
+    ///
+    /// `self` is left unchanged; switch to [`CommitOutput::next`] once the
+    /// delivery service has accepted the commit, or drop it if another
+    /// commit for this epoch won.
+    pub fn commit(
+        &self,
+        signing_key: &SigningKey,
+        proposals: Vec<Proposal>,
+    ) -> Result<CommitOutput, CryptoError> {
+        let sender = self.own_leaf;
+        if self.tree.leaf(sender).map(|leaf| leaf.credential)
+            != Some(signing_key.verifying_key().to_bytes())
+        {
+            return Err(CryptoError::InvalidKey(
+                "signing key does not match our leaf".into(),
+            ));
+        }
+
+        let mut next = self.clone();
+        let added = next.apply_proposals(sender, &proposals)?;
+
+        let sender_node = leaf_node(sender);
+        let leaves = next.tree.leaf_count();
+        let direct_path = math::direct_path(sender_node, leaves);
+        let copath = math::copath(sender_node, leaves);
+
+        // path_secrets[0] is the leaf's, [i + 1] belongs to direct_path[i]
+        // and the last one is the commit secret
+        let mut path_secrets = vec![random_secret()];
+        for i in 0..=direct_path.len() {
+            let secret = expand(&path_secrets[i], b"path", &[]);
+            path_secrets.push(secret);
+        }
+
+        let leaf_secret = node_secret(&path_secrets[0]);
+        let leaf_key = public_key(&leaf_secret);
+        next.tree.set_leaf_key(sender, leaf_key);
+        next.private_keys.insert(sender_node, leaf_secret);
+        for (i, &node) in direct_path.iter().enumerate() {
+            let secret = node_secret(&path_secrets[i + 1]);
+            next.tree.set(node, Some(Node::Parent(public_key(&secret))));
+            next.private_keys.insert(node, secret);
+        }
+        next.epoch = self.epoch + 1;
+        let context = next.context();
+
+        let mut path = Vec::with_capacity(direct_path.len());
+        for (i, (&node, &sibling)) in direct_path.iter().zip(&copath).enumerate() {
+            let encrypted_path_secret = next
+                .resolution_excluding(sibling, &added)
+                .into_iter()
+                .map(|r| {
+                    let key = next
+                        .tree
+                        .node_key(r)
+                        .expect("resolution nodes are occupied");
+                    hpke::seal(&key, &context, &path_secrets[i + 1])
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            path.push(UpdatePathNode {
+                public_key: next.tree.node_key(node).expect("path node was just set"),
+                encrypted_path_secret,
+            });
+        }
+
+        let joiner_secret = extract(&self.init_secret, &path_secrets[direct_path.len() + 1]);
+        next.enter_epoch(&joiner_secret, &context);
+        let confirmation_tag = next.confirmation_tag(&context);
+
+        let mut commit = Commit {
+            epoch: self.epoch,
+            sender,
+            proposals,
+            leaf_key,
+            path,
+            confirmation_tag,
+            signature: Vec::new(),
+        };
+        commit.signature = signing_key
+            .sign(&commit_tbs(&self.group_id, &commit))
+            .to_bytes()
+            .to_vec();
+
+        let welcomes = added
+            .iter()
+            .map(|&new_leaf| {
+                let lca = math::common_ancestor(sender_node, leaf_node(new_leaf));
+                let position = direct_path
+                    .iter()
+                    .position(|n| *n == lca)
+                    .expect("common ancestor lies on the committer's direct path");
+                next.welcome(
+                    signing_key,
+                    new_leaf,
+                    &joiner_secret,
+                    &path_secrets[position + 1],
+                    confirmation_tag,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for secret in &mut path_secrets {
+            secret.zeroize();
+        }
+        Ok(CommitOutput {
+            commit,
+            welcomes,
+            next,
+        })
+    }
+
+    /// Apply another member's commit, moving to the next epoch.
+    ///
+    /// Fails with [`CryptoError::RemovedFromGroup`] if the commit removes
+    /// us. On any error the group is left unchanged.
+    pub fn process_commit(&mut self, commit: &Commit) -> Result<(), CryptoError> {
+        if commit.epoch != self.epoch {
+            return Err(CryptoError::VerificationError(format!(
+                "commit is for epoch {} but the group is at {}",
+                commit.epoch, self.epoch
+            )));
+        }
+        if commit.sender == self.own_leaf {
+            return Err(CryptoError::VerificationError(
+                "own commit — switch to the staged group instead".into(),
+            ));
+        }
+        let sender = self.tree.leaf(commit.sender).ok_or_else(|| {
+            CryptoError::VerificationError("commit sender is not in the tree".into())
+        })?;
+        verify_signature(
+            &sender.credential,
+            &commit_tbs(&self.group_id, commit),
+            &commit.signature,
+        )?;
+        if commit
+            .proposals
+            .iter()
+            .any(|p| matches!(p, Proposal::Remove { leaf } if *leaf == self.own_leaf))
+        {
+            return Err(CryptoError::RemovedFromGroup);
+        }
+
+        let mut next = self.clone();
+        let added = next.apply_proposals(commit.sender, &commit.proposals)?;
+
+        let sender_node = leaf_node(commit.sender);
+        let leaves = next.tree.leaf_count();
+        let direct_path = math::direct_path(sender_node, leaves);
+        let copath = math::copath(sender_node, leaves);
+        if commit.path.len() != direct_path.len() {
+            return Err(CryptoError::VerificationError(
+                "update path has the wrong length".into(),
+            ));
+        }
+
+        next.tree.set_leaf_key(commit.sender, commit.leaf_key);
+        for (&node, update) in direct_path.iter().zip(&commit.path) {
+            next.tree.set(node, Some(Node::Parent(update.public_key)));
+            next.private_keys.remove(&node);
+        }
+        next.epoch = self.epoch + 1;
+        let context = next.context();
+
+        let lca = math::common_ancestor(sender_node, leaf_node(self.own_leaf));
+        let position = direct_path.iter().position(|n| *n == lca).ok_or_else(|| {
+            CryptoError::VerificationError("commit path misses our subtree".into())
+        })?;
+        let resolution = next.resolution_excluding(copath[position], &added);
+        let encrypted = &commit.path[position].encrypted_path_secret;
+        if encrypted.len() != resolution.len() {
+            return Err(CryptoError::VerificationError(
+                "update path does not match the tree".into(),
+            ));
+        }
+        let (slot, key) = resolution
+            .iter()
+            .enumerate()
+            .find_map(|(i, r)| next.private_keys.get(r).map(|key| (i, *key)))
+            .ok_or_else(|| {
+                CryptoError::DecryptionError(
+                    "no key for any node the path secret was sent to".into(),
+                )
+            })?;
+        let mut plaintext = hpke::open(&StaticSecret::from(key), &encrypted[slot], &context)?;
+        if plaintext.len() != 32 {
+            return Err(CryptoError::DecryptionError("malformed path secret".into()));
+        }
+        let mut path_secret = to_secret(&plaintext);
+        plaintext.zeroize();
+
+        for (&node, update) in direct_path.iter().zip(&commit.path).skip(position) {
+            let secret = node_secret(&path_secret);
+            if public_key(&secret) != update.public_key {
+                return Err(CryptoError::VerificationError(
+                    "path secret does not match the committed public key".into(),
+                ));
+            }
+            next.private_keys.insert(node, secret);
+            path_secret = expand(&path_secret, b"path", &[]);
+        }
+
+        // What is left at the top of the path is the commit secret
+        let joiner_secret = extract(&self.init_secret, &path_secret);
+        path_secret.zeroize();
+        next.enter_epoch(&joiner_secret, &context);
+        if next.confirmation_tag(&context) != commit.confirmation_tag {
+            return Err(CryptoError::VerificationError(
+                "commit confirmation tag mismatch".into(),
+            ));
+        }
+
+        *self = next;
+        Ok(())
+    }
+
+    /// Apply removes, then adds. Returns the leaves of added members.
+    fn apply_proposals(
+        &mut self,
+        sender: u32,
+        proposals: &[Proposal],
+    ) -> Result<Vec<u32>, CryptoError> {
+        for proposal in proposals {
+            if let Proposal::Remove { leaf } = proposal {
+                if *leaf == sender {
+                    return Err(CryptoError::InvalidKey(
+                        "a committer cannot remove itself".into(),
+                    ));
+                }
+                if self.tree.leaf(*leaf).is_none() {
+                    return Err(CryptoError::InvalidKey(format!(
+                        "leaf {leaf} is already empty"
+                    )));
+                }
+                self.tree.blank_path(*leaf);
+            }
+        }
+
+        let mut added = Vec::new();
+        for proposal in proposals {
+            if let Proposal::Add(key_package) = proposal {
+                key_package.verify()?;
+                if self.tree.find(&key_package.leaf.credential).is_some() {
+                    return Err(CryptoError::InvalidKey(
+                        "member is already in the group".into(),
+                    ));
+                }
+                added.push(self.tree.add_leaf(key_package.leaf.clone()));
+            }
+        }
+
+        let tree = &self.tree;
+        self.private_keys
+            .retain(|node, _| tree.node(*node).is_some());
+        Ok(added)
+    }
+
+    /// Resolution of `x`, leaving out members added by the current commit
+    /// (they get the secrets from their welcome).
+    fn resolution_excluding(&self, x: u32, added: &[u32]) -> Vec<u32> {
+        self.tree
+            .resolution(x)
+            .into_iter()
+            .filter(|r| !added.iter().any(|leaf| leaf_node(*leaf) == *r))
+            .collect()
+    }
+
+    fn welcome(
+        &self,
+        signing_key: &SigningKey,
+        new_leaf: u32,
+        joiner_secret: &[u8; 32],
+        path_secret: &[u8; 32],
+        confirmation_tag: [u8; 32],
+    ) -> Result<Welcome, CryptoError> {
+        let tree_hash = self.tree.hash();
+        let context = group_context(&self.group_id, self.epoch, &tree_hash);
+        let recipient = self
+            .tree
+            .leaf(new_leaf)
+            .expect("added leaf is occupied")
+            .encryption_key;
+
+        let mut secrets = Vec::with_capacity(64);
+        secrets.extend_from_slice(joiner_secret);
+        secrets.extend_from_slice(path_secret);
+        let group_secrets = hpke::seal(&recipient, &context, &secrets);
+        secrets.zeroize();
+
+        let mut welcome = Welcome {
+            group_id: self.group_id.clone(),
+            epoch: self.epoch,
+            sender: self.own_leaf,
+            new_leaf,
+            tree: self.tree.clone(),
+            group_secrets: group_secrets?,
+            confirmation_tag,
+            signature: Vec::new(),
+        };
+        welcome.signature = signing_key
+            .sign(&welcome_tbs(&welcome, &tree_hash))
+            .to_bytes()
+            .to_vec();
+        Ok(welcome)
+    }
+
+    fn context(&self) -> Vec<u8> {
+        group_context(&self.group_id, self.epoch, &self.tree.hash())
+    }
+
+    fn enter_epoch(&mut self, joiner_secret: &[u8; 32], context: &[u8]) {
+        self.epoch_secret = expand(joiner_secret, b"epoch", context);
+        self.init_secret = expand(&self.epoch_secret, b"init", &[]);
+    }
+
+    fn confirmation_tag(&self, context: &[u8]) -> [u8; 32] {
+        expand(&self.epoch_secret, b"confirm", context)
+    }
+}
+
+fn random_secret() -> [u8; 32] {
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+fn to_secret(bytes: &[u8]) -> [u8; 32] {
+    let mut secret = [0u8; 32];
+    secret.copy_from_slice(bytes);
+    secret
+}
+
+fn expand(secret: &[u8; 32], label: &[u8], context: &[u8]) -> [u8; 32] {
+    let mut info = Vec::with_capacity(label.len() + context.len());
+    info.extend_from_slice(label);
+    info.extend_from_slice(context);
+    let mut out = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(KDF_CONTEXT), secret)
+        .expand(&info, &mut out)
+        .expect("32-byte output is a valid HKDF-SHA256 length");
+    out
+}
+
+fn extract(salt: &[u8; 32], ikm: &[u8; 32]) -> [u8; 32] {
+    let (prk, _) = Hkdf::<Sha256>::extract(Some(salt), ikm);
+    to_secret(&prk)
+}
+
+/// X25519 secret for the node a path secret belongs to.
+fn node_secret(path_secret: &[u8; 32]) -> [u8; 32] {
+    expand(path_secret, b"node", &[])
+}
+
+fn public_key(secret: &[u8; 32]) -> [u8; 32] {
+    X25519Public::from(&StaticSecret::from(*secret)).to_bytes()
+}
+
+fn verify_signature(
+    credential: &[u8; 32],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), CryptoError> {
+    let key =
+        VerifyingKey::from_bytes(credential).map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+    let signature = Signature::from_slice(signature)
+        .map_err(|e| CryptoError::VerificationError(e.to_string()))?;
+    key.verify_strict(message, &signature)
+        .map_err(|e| CryptoError::VerificationError(e.to_string()))
+}
+
+fn put_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&u32::try_from(len).unwrap_or(u32::MAX).to_be_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_len(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn group_context(group_id: &[u8], epoch: u64, tree_hash: &[u8; 32]) -> Vec<u8> {
+    let mut context = Vec::with_capacity(4 + group_id.len() + 8 + 32);
+    put_bytes(&mut context, group_id);
+    context.extend_from_slice(&epoch.to_be_bytes());
+    context.extend_from_slice(tree_hash);
+    context
+}
+
+fn key_package_tbs(leaf: &LeafNode) -> Vec<u8> {
+    let mut out = SIGN_KEY_PACKAGE.to_vec();
+    out.extend_from_slice(&leaf.credential);
+    out.extend_from_slice(&leaf.encryption_key);
+    out
+}
+
+fn commit_tbs(group_id: &[u8], commit: &Commit) -> Vec<u8> {
+    let mut out = SIGN_COMMIT.to_vec();
+    put_bytes(&mut out, group_id);
+    out.extend_from_slice(&commit.epoch.to_be_bytes());
+    out.extend_from_slice(&commit.sender.to_be_bytes());
+    put_len(&mut out, commit.proposals.len());
+    for proposal in &commit.proposals {
+        match proposal {
+            Proposal::Add(key_package) => {
+                out.push(1);
+                out.extend_from_slice(&key_package.leaf.credential);
+                out.extend_from_slice(&key_package.leaf.encryption_key);
+                put_bytes(&mut out, &key_package.signature);
+            }
+            Proposal::Remove { leaf } => {
+                out.push(2);
+                out.extend_from_slice(&leaf.to_be_bytes());
+            }
+        }
+    }
+    out.extend_from_slice(&commit.leaf_key);
+    put_len(&mut out, commit.path.len());
+    for node in &commit.path {
+        out.extend_from_slice(&node.public_key);
+        put_len(&mut out, node.encrypted_path_secret.len());
+        for sealed in &node.encrypted_path_secret {
+            out.extend_from_slice(&sealed.kem_output);
+            put_bytes(&mut out, &sealed.ciphertext);
+        }
+    }
+    out.extend_from_slice(&commit.confirmation_tag);
+    out
+}
+
+fn welcome_tbs(welcome: &Welcome, tree_hash: &[u8; 32]) -> Vec<u8> {
+    let mut out = SIGN_WELCOME.to_vec();
+    put_bytes(&mut out, &welcome.group_id);
+    out.extend_from_slice(&welcome.epoch.to_be_bytes());
+    out.extend_from_slice(&welcome.sender.to_be_bytes());
+    out.extend_from_slice(&welcome.new_leaf.to_be_bytes());
+    out.extend_from_slice(tree_hash);
+    out.extend_from_slice(&welcome.group_secrets.kem_output);
+    put_bytes(&mut out, &welcome.group_secrets.ciphertext);
+    out.extend_from_slice(&welcome.confirmation_tag);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudonym() -> SigningKey {
+        SigningKey::generate(&mut rand::rngs::OsRng)
+    }
+
+    fn credential(key: &SigningKey) -> [u8; 32] {
+        key.verifying_key().to_bytes()
+    }
+
+    fn assert_same_mek(groups: &[&TreeGroup]) {
+        let first = groups[0].mek();
+        for group in &groups[1..] {
+            assert_eq!(group.epoch(), groups[0].epoch());
+            assert_eq!(group.mek().as_bytes(), first.as_bytes());
+        }
+    }
+
+    /// Alice creates a group and adds everyone in `others` with one commit.
+    fn group_of(alice_key: &SigningKey, others: &[SigningKey]) -> (TreeGroup, Vec<TreeGroup>) {
+        let alice = TreeGroup::create(b"community", alice_key, 1);
+        let (packages, secrets): (Vec<_>, Vec<_>) = others.iter().map(KeyPackage::generate).unzip();
+        let output = alice
+            .commit(alice_key, packages.into_iter().map(Proposal::Add).collect())
+            .unwrap();
+        let joined = output
+            .welcomes
+            .iter()
+            .zip(&secrets)
+            .map(|(welcome, secret)| TreeGroup::join(welcome, secret).unwrap())
+            .collect();
+        (output.next, joined)
+    }
+
+    #[test]
+    fn added_members_share_the_epoch_mek() {
+        let alice_key = pseudonym();
+        let bob_key = pseudonym();
+        let (mut alice, mut joined) = group_of(&alice_key, &[bob_key.clone(), pseudonym()]);
+        let mut carol = joined.pop().unwrap();
+        let bob = joined.pop().unwrap();
+        assert_eq!(alice.epoch(), 2);
+        assert_same_mek(&[&alice, &bob, &carol]);
+
+        // An empty commit just refreshes Bob's path
+        let output = bob.commit(&bob_key, Vec::new()).unwrap();
+        alice.process_commit(&output.commit).unwrap();
+        carol.process_commit(&output.commit).unwrap();
+        let bob = output.next;
+        assert_eq!(bob.epoch(), 3);
+        assert_eq!(bob.mek().generation(), 3);
+        assert_same_mek(&[&alice, &bob, &carol]);
+    }
+
+    #[test]
+    fn removed_member_cannot_follow() {
+        let alice_key = pseudonym();
+        let bob_key = pseudonym();
+        let carol_key = pseudonym();
+        let (mut alice, mut joined) = group_of(&alice_key, &[bob_key.clone(), carol_key.clone()]);
+        let mut carol = joined.pop().unwrap();
+        let bob = joined.pop().unwrap();
+        let old_mek = *alice.mek().as_bytes();
+
+        let carol_leaf = alice.tree().find(&credential(&carol_key)).unwrap();
+        let output = bob
+            .commit(&bob_key, vec![Proposal::Remove { leaf: carol_leaf }])
+            .unwrap();
+        assert!(matches!(
+            carol.process_commit(&output.commit),
+            Err(CryptoError::RemovedFromGroup)
+        ));
+
+        alice.process_commit(&output.commit).unwrap();
+        let bob = output.next;
+        assert_same_mek(&[&alice, &bob]);
+        assert_ne!(*alice.mek().as_bytes(), old_mek);
+        assert!(alice.tree().find(&credential(&carol_key)).is_none());
+    }
+
+    #[test]
+    fn tree_grows_and_shrinks() {
+        let keys: Vec<SigningKey> = (0..10).map(|_| pseudonym()).collect();
+        let mut groups = vec![TreeGroup::create(b"community", &keys[0], 1)];
+
+        // Add members one at a time, past a power of two
+        for key in &keys[1..] {
+            let (package, secret) = KeyPackage::generate(key);
+            let output = groups[0]
+                .commit(&keys[0], vec![Proposal::Add(package)])
+                .unwrap();
+            for group in &mut groups[1..] {
+                group.process_commit(&output.commit).unwrap();
+            }
+            groups[0] = output.next;
+            groups.push(TreeGroup::join(&output.welcomes[0], &secret).unwrap());
+        }
+        assert_eq!(groups[0].tree().leaf_count(), 16);
+        assert_same_mek(&groups.iter().collect::<Vec<_>>());
+
+        // A member in the middle removes two others in one commit
+        let removed = [3, 8];
+        let proposals = removed
+            .iter()
+            .map(|&i| Proposal::Remove {
+                leaf: groups[0].tree().find(&credential(&keys[i])).unwrap(),
+            })
+            .collect();
+        let output = groups[5].commit(&keys[5], proposals).unwrap();
+        for (i, group) in groups.iter_mut().enumerate() {
+            if i == 5 {
+                continue;
+            }
+            let result = group.process_commit(&output.commit);
+            if removed.contains(&i) {
+                assert!(matches!(result, Err(CryptoError::RemovedFromGroup)));
+            } else {
+                result.unwrap();
+            }
+        }
+        groups[5] = output.next;
+
+        let remaining: Vec<&TreeGroup> = groups
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !removed.contains(i))
+            .map(|(_, group)| group)
+            .collect();
+        assert_same_mek(&remaining);
+        assert_eq!(remaining[0].tree().members().count(), 8);
+    }
+
+    #[test]
+    fn tampered_or_stale_commit_rejected() {
+        let alice_key = pseudonym();
+        let (alice, mut joined) = group_of(&alice_key, &[pseudonym()]);
+        let mut bob = joined.pop().unwrap();
+
+        assert!(alice.commit(&pseudonym(), Vec::new()).is_err());
+
+        let (package, _) = KeyPackage::generate(&pseudonym());
+        let commit = alice
+            .commit(&alice_key, vec![Proposal::Add(package)])
+            .unwrap()
+            .commit;
+
+        let mut tampered = commit.clone();
+        tampered.leaf_key[0] ^= 1;
+        assert!(matches!(
+            bob.process_commit(&tampered),
+            Err(CryptoError::VerificationError(_))
+        ));
+
+        let mut stale = commit.clone();
+        stale.epoch -= 1;
+        assert!(bob.process_commit(&stale).is_err());
+
+        // Rejections leave the group untouched
+        bob.process_commit(&commit).unwrap();
+        assert_eq!(bob.epoch(), 3);
+    }
+
+    #[test]
+    fn welcome_needs_the_matching_key_package() {
+        let alice_key = pseudonym();
+        let alice = TreeGroup::create(b"community", &alice_key, 1);
+        let bob = pseudonym();
+        let (package, _) = KeyPackage::generate(&bob);
+        let (_, other_secret) = KeyPackage::generate(&bob);
+        let output = alice
+            .commit(&alice_key, vec![Proposal::Add(package)])
+            .unwrap();
+        assert_eq!(output.welcomes[0].recipient(), Some(&credential(&bob)));
+        assert!(TreeGroup::join(&output.welcomes[0], &other_secret).is_err());
+
+        let mut forged = KeyPackage::generate(&bob).0;
+        forged.leaf.credential = credential(&pseudonym());
+        assert!(alice
+            .commit(&alice_key, vec![Proposal::Add(forged)])
+            .is_err());
+    }
+}
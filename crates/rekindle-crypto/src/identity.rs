@@ -1,6 +1,6 @@
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
-use zeroize::ZeroizeOnDrop;
+use zeroize::{ZeroizeOnDrop, Zeroizing};
 
 use crate::error::CryptoError;
 
@@ -27,6 +27,59 @@ impl Identity {
         Self { signing_key }
     }
 
+    /// Restore an identity from its 24-word BIP-39 recovery phrase.
+    ///
+    /// Case and extra whitespace are ignored.
+    pub fn from_recovery_phrase(phrase: &str) -> Result<Self, CryptoError> {
+        let normalized = Zeroizing::new(
+            phrase
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        let mnemonic = bip39::Mnemonic::parse_normalized(&normalized)
+            .map_err(|e| CryptoError::InvalidKey(format!("invalid recovery phrase: {e}")))?;
+        let entropy = Zeroizing::new(mnemonic.to_entropy());
+        let bytes: &[u8; 32] = entropy.as_slice().try_into().map_err(|_| {
+            CryptoError::InvalidKey("recovery phrase must be 24 words".into())
+        })?;
+        Ok(Self::from_secret_bytes(bytes))
+    }
+
+    /// The 24-word BIP-39 recovery phrase encoding this identity's secret key.
+    ///
+    /// # Security
+    /// Anyone holding the phrase holds the identity.
+    pub fn recovery_phrase(&self) -> Zeroizing<String> {
+        let mnemonic = bip39::Mnemonic::from_entropy(self.secret_key_bytes())
+            .expect("32 bytes is a valid BIP-39 entropy length");
+        Zeroizing::new(mnemonic.to_string())
+    }
+
+    /// The Ed25519 key that owns this identity's account DHT record.
+    ///
+    /// Derived with HKDF-SHA256 (info = `b"rekindle-account-owner-v1"`), so
+    /// the account record's key can be found again from the identity secret
+    /// alone. It must differ from the identity key, which already owns the
+    /// mailbox record.
+    pub fn account_record_owner(&self) -> Self {
+        Self::derive(self.secret_key_bytes(), b"rekindle-account-owner-v1")
+    }
+
+    /// Derive an identity from keying material with HKDF-SHA256, using
+    /// `info` to separate its uses.
+    ///
+    /// The same inputs always give the same key, so a record owner or
+    /// signing key derived this way never has to be stored.
+    pub fn derive(ikm: &[u8], info: &[u8]) -> Self {
+        let hk = hkdf::Hkdf::<sha2::Sha256>::new(None, ikm);
+        let mut seed = Zeroizing::new([0u8; 32]);
+        hk.expand(info, seed.as_mut())
+            .expect("32-byte output is valid for HKDF-SHA256");
+        Self::from_secret_bytes(&seed)
+    }
+
     /// Get the public verifying key (this is your "address" / identity).
     pub fn public_key(&self) -> VerifyingKey {
         self.signing_key.verifying_key()
@@ -128,6 +181,69 @@ mod tests {
         assert_eq!(identity.public_key_bytes(), restored.public_key_bytes());
     }
 
+    #[test]
+    fn recovery_phrase_roundtrip() {
+        let identity = Identity::generate();
+        let phrase = identity.recovery_phrase();
+        assert_eq!(phrase.split(' ').count(), 24);
+
+        let restored = Identity::from_recovery_phrase(&phrase).unwrap();
+        assert_eq!(identity.public_key_bytes(), restored.public_key_bytes());
+
+        // Case and spacing don't matter
+        let messy = format!("  {}\n", phrase.to_uppercase().replace(' ', "   "));
+        let restored = Identity::from_recovery_phrase(&messy).unwrap();
+        assert_eq!(identity.public_key_bytes(), restored.public_key_bytes());
+    }
+
+    #[test]
+    fn invalid_recovery_phrase_is_rejected() {
+        let phrase = Identity::generate().recovery_phrase();
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+
+        // A 12-word phrase is valid BIP-39 but too short for an Ed25519 seed
+        assert!(Identity::from_recovery_phrase(&words[..12].join(" ")).is_err());
+
+        // A word outside the BIP-39 list
+        words[5] = "rekindle";
+        assert!(Identity::from_recovery_phrase(&words.join(" ")).is_err());
+        assert!(Identity::from_recovery_phrase("not a recovery phrase").is_err());
+    }
+
+    #[test]
+    fn account_record_owner_is_deterministic() {
+        let identity = Identity::generate();
+        let owner = identity.account_record_owner();
+        let restored = Identity::from_secret_bytes(identity.secret_key_bytes());
+
+        assert_eq!(
+            owner.public_key_bytes(),
+            restored.account_record_owner().public_key_bytes()
+        );
+        assert_ne!(owner.public_key_bytes(), identity.public_key_bytes());
+        assert_ne!(
+            owner.public_key_bytes(),
+            Identity::generate().account_record_owner().public_key_bytes()
+        );
+    }
+
+    #[test]
+    fn derive_separates_uses() {
+        let a = Identity::derive(b"keying material", b"use-a");
+        assert_eq!(
+            a.public_key_bytes(),
+            Identity::derive(b"keying material", b"use-a").public_key_bytes()
+        );
+        assert_ne!(
+            a.public_key_bytes(),
+            Identity::derive(b"keying material", b"use-b").public_key_bytes()
+        );
+        assert_ne!(
+            a.public_key_bytes(),
+            Identity::derive(b"other material", b"use-a").public_key_bytes()
+        );
+    }
+
     #[test]
     fn x25519_derivation() {
         let alice = Identity::generate();
@@ -26,6 +26,12 @@ pub const VAULT_IDENTITY: &str = "identity";
 pub const KEY_ED25519_PRIVATE: &str = "ed25519_private";
 /// X25519 Diffie-Hellman private key.
 pub const KEY_X25519_PRIVATE: &str = "x25519_private";
+/// `SQLCipher` key for the identity's local database.
+pub const KEY_DATABASE: &str = "database_key";
+/// Replacement database key, present only while a re-key is in progress.
+pub const KEY_DATABASE_NEXT: &str = "database_key_next";
+/// Ed25519 key of this device, present only on linked (non-primary) devices.
+pub const KEY_DEVICE_PRIVATE: &str = "device_private";
 
 /// Vault for Signal Protocol keys.
 pub const VAULT_SIGNAL: &str = "signal";
@@ -48,3 +54,8 @@ pub const VAULT_COMMUNITIES: &str = "communities";
 pub fn mek_key_name(community_id: &str) -> String {
     format!("mek_{community_id}")
 }
+
+/// Generate the key name for a community's `TreeKEM` group state.
+pub fn tree_key_name(community_id: &str) -> String {
+    format!("tree_{community_id}")
+}
@@ -1,8 +1,13 @@
+pub mod backup;
+pub mod device_link;
 pub mod dht_crypto;
 pub mod error;
+pub mod file_key;
 pub mod group;
 pub mod identity;
 pub mod keychain;
+pub mod sealed_box;
+pub mod sframe;
 pub mod signal;
 
 pub use dht_crypto::DhtRecordKey;
@@ -0,0 +1,165 @@
+//! Anonymous public-key sealing to a single X25519 key.
+//!
+//! An ephemeral X25519 key is agreed with the recipient's key, HKDF-SHA256
+//! turns the shared secret into an AES-256-GCM key, and the ciphertext is
+//! authenticated against the context label and both public keys. The
+//! context label keeps blobs sealed for one purpose (a device grant, a
+//! wrapped MEK, a tree path secret) from opening as another. Callers can
+//! bind extra associated data of their own, as the ratchet tree does with
+//! its group context. Low-order keys on either side are rejected, so the
+//! shared secret always depends on the recipient's key.
+//!
+//! Layout: `ephemeral public (32) || nonce (12) || ciphertext`.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey as X25519Public, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::error::CryptoError;
+
+/// Bytes a sealed box adds to its plaintext: ephemeral key, nonce and tag.
+pub const SEAL_OVERHEAD: usize = 32 + 12 + 16;
+
+/// Seal `plaintext` so only the holder of `recipient`'s secret can open it.
+///
+/// `context` is the HKDF salt and the prefix of the associated data; the
+/// same label must be passed to [`open`].
+pub fn seal(context: &[u8], recipient: &X25519Public, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    seal_with_aad(context, recipient, &[], plaintext)
+}
+
+/// [`seal`] with caller associated data, which [`open_with_aad`] must be
+/// given unchanged.
+pub fn seal_with_aad(
+    context: &[u8],
+    recipient: &X25519Public,
+    extra_aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let ephemeral = StaticSecret::random_from_rng(rand::rngs::OsRng);
+    let ephemeral_public = X25519Public::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(recipient);
+    if !shared.was_contributory() {
+        return Err(CryptoError::InvalidKey("low-order recipient key".into()));
+    }
+
+    let info = associated_data(context, ephemeral_public.as_bytes(), recipient.as_bytes());
+    let cipher = box_cipher(context, shared.as_bytes(), &info)?;
+    let mut aad = info;
+    aad.extend_from_slice(extra_aad);
+    let mut nonce = [0u8; 12];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &aad })
+        .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
+
+    let mut out = Vec::with_capacity(SEAL_OVERHEAD + plaintext.len());
+    out.extend_from_slice(ephemeral_public.as_bytes());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Open a box produced by [`seal`] under the same `context` with our secret.
+pub fn open(context: &[u8], secret: &StaticSecret, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+    open_with_aad(context, secret, &[], sealed)
+}
+
+/// Open a box produced by [`seal_with_aad`].
+pub fn open_with_aad(
+    context: &[u8],
+    secret: &StaticSecret,
+    extra_aad: &[u8],
+    sealed: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+    if sealed.len() < SEAL_OVERHEAD {
+        return Err(CryptoError::DecryptionError("sealed box too short".into()));
+    }
+    let (ephemeral_bytes, rest) = sealed.split_at(32);
+    let (nonce, ciphertext) = rest.split_at(12);
+    let ephemeral_public = X25519Public::from(
+        <[u8; 32]>::try_from(ephemeral_bytes)
+            .map_err(|_| CryptoError::InvalidKey("ephemeral key wrong length".into()))?,
+    );
+
+    let our_public = X25519Public::from(secret);
+    let shared = secret.diffie_hellman(&ephemeral_public);
+    if !shared.was_contributory() {
+        return Err(CryptoError::DecryptionError("low-order ephemeral key".into()));
+    }
+
+    let info = associated_data(context, ephemeral_public.as_bytes(), our_public.as_bytes());
+    let cipher = box_cipher(context, shared.as_bytes(), &info)?;
+    let mut aad = info;
+    aad.extend_from_slice(extra_aad);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+        .map(Zeroizing::new)
+        .map_err(|e| CryptoError::DecryptionError(e.to_string()))
+}
+
+fn box_cipher(context: &[u8], shared: &[u8; 32], info: &[u8]) -> Result<Aes256Gcm, CryptoError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(context), shared)
+        .expand(info, key.as_mut())
+        .map_err(|e| CryptoError::EncryptionError(format!("HKDF expand failed: {e}")))?;
+    Aes256Gcm::new_from_slice(key.as_ref()).map_err(|e| CryptoError::EncryptionError(e.to_string()))
+}
+
+fn associated_data(context: &[u8], ephemeral: &[u8; 32], recipient: &[u8; 32]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(context.len() + 64);
+    aad.extend_from_slice(context);
+    aad.extend_from_slice(ephemeral);
+    aad.extend_from_slice(recipient);
+    aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTEXT: &[u8] = b"rekindle-sealed-box-test";
+
+    #[test]
+    fn sealed_box_opens_for_recipient_only() {
+        let recipient = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let other = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let sealed = seal(CONTEXT, &X25519Public::from(&recipient), b"payload").unwrap();
+        assert_eq!(sealed.len(), SEAL_OVERHEAD + 7);
+
+        assert_eq!(open(CONTEXT, &recipient, &sealed).unwrap().as_slice(), b"payload");
+        assert!(open(CONTEXT, &other, &sealed).is_err());
+        assert!(open(b"another-context", &recipient, &sealed).is_err());
+        assert!(open(CONTEXT, &recipient, &sealed[..SEAL_OVERHEAD - 1]).is_err());
+    }
+
+    #[test]
+    fn extra_aad_must_match() {
+        let recipient = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let public = X25519Public::from(&recipient);
+        let sealed = seal_with_aad(CONTEXT, &public, b"epoch 1", b"payload").unwrap();
+
+        assert_eq!(open_with_aad(CONTEXT, &recipient, b"epoch 1", &sealed).unwrap().as_slice(), b"payload");
+        assert!(open_with_aad(CONTEXT, &recipient, b"epoch 2", &sealed).is_err());
+        assert!(open(CONTEXT, &recipient, &sealed).is_err());
+
+        // No extra data is the same as plain `seal`
+        let plain = seal(CONTEXT, &public, b"payload").unwrap();
+        assert_eq!(open_with_aad(CONTEXT, &recipient, &[], &plain).unwrap().as_slice(), b"payload");
+    }
+
+    #[test]
+    fn low_order_keys_are_rejected() {
+        let identity_point = X25519Public::from([0u8; 32]);
+        assert!(seal(CONTEXT, &identity_point, b"payload").is_err());
+
+        let recipient = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        // An all-zero ephemeral key is the identity point
+        let forged = vec![0u8; SEAL_OVERHEAD + 7];
+        assert!(open(CONTEXT, &recipient, &forged).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+//! Per-frame media encryption in the style of `SFrame` (RFC 9605).
+//!
+//! A [`FrameKey`] is a 32-byte base secret with a key id — a MEK
+//! generation for voice channels, or a random key sent over the Signal
+//! session for 1:1 calls. Every sender derives their own AES-256-GCM key
+//! and nonce salt from it, bound to their public key, so one key shared by
+//! a whole channel never repeats a nonce across senders. The frame header
+//! (key id and counter) and the sender's public key are authenticated as
+//! AAD: a frame can't be replayed under another key or passed off as
+//! coming from someone else.
+//!
+//! Key changes are gapless: receivers keep the last few keys, and a sender
+//! only switches to a new key once receivers have had time to install it.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::error::CryptoError;
+use crate::group::media_key::MediaEncryptionKey;
+
+/// Keys a receiver keeps so frames in flight across a key change still open.
+pub const RETAINED_FRAME_KEYS: usize = 4;
+
+const MEK_FRAME_INFO: &[u8] = b"rekindle-sframe-mek-v1";
+const SENDER_SALT: &[u8] = b"rekindle-sframe-sender-v1";
+
+/// Base secret that frame encryption keys are derived from.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct FrameKey {
+    #[zeroize(skip)]
+    id: u64,
+    base: [u8; 32],
+}
+
+impl FrameKey {
+    /// Wrap an existing base secret.
+    pub fn new(id: u64, base: [u8; 32]) -> Self {
+        Self { id, base }
+    }
+
+    /// Generate a random frame key.
+    pub fn generate(id: u64) -> Self {
+        let mut base = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut base);
+        Self { id, base }
+    }
+
+    /// Frame key for a voice channel, derived from a MEK generation.
+    ///
+    /// The key id is the generation, so receivers know which MEK to use.
+    pub fn from_mek(mek: &MediaEncryptionKey) -> Self {
+        let mut base = [0u8; 32];
+        Hkdf::<Sha256>::new(None, mek.as_bytes())
+            .expand(MEK_FRAME_INFO, &mut base)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        Self {
+            id: mek.generation(),
+            base,
+        }
+    }
+
+    /// Key id carried in each frame header.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Raw base secret (for delivery over a Signal session).
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.base
+    }
+
+    /// AES-256-GCM cipher and nonce salt for one sender.
+    fn sender_cipher(&self, sender: &[u8]) -> Result<(Aes256Gcm, [u8; 12]), CryptoError> {
+        let mut okm = [0u8; 44];
+        Hkdf::<Sha256>::new(Some(SENDER_SALT), &self.base)
+            .expand(sender, &mut okm)
+            .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+        let cipher = Aes256Gcm::new_from_slice(&okm[..32])
+            .map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+        let mut salt = [0u8; 12];
+        salt.copy_from_slice(&okm[32..]);
+        okm.zeroize();
+        Ok((cipher, salt))
+    }
+}
+
+/// One encrypted media frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedFrame {
+    /// Id of the [`FrameKey`] the frame was sealed with.
+    pub key_id: u64,
+    /// Per-sender frame counter, unique under each key.
+    pub counter: u64,
+    /// AES-256-GCM ciphertext with tag.
+    pub ciphertext: Vec<u8>,
+}
+
+/// `nonce = salt XOR counter`, the counter right-aligned as in `SFrame`.
+fn frame_nonce(salt: &[u8; 12], counter: u64) -> [u8; 12] {
+    let mut nonce = *salt;
+    for (n, c) in nonce[4..].iter_mut().zip(counter.to_be_bytes()) {
+        *n ^= c;
+    }
+    nonce
+}
+
+/// `key_id (u64 BE) || counter (u64 BE) || sender`.
+fn frame_aad(key_id: u64, counter: u64, sender: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(16 + sender.len());
+    aad.extend_from_slice(&key_id.to_be_bytes());
+    aad.extend_from_slice(&counter.to_be_bytes());
+    aad.extend_from_slice(sender);
+    aad
+}
+
+/// Sending side: seals frames with the current key and switches keys
+/// on schedule.
+pub struct FrameEncryptor {
+    sender: Vec<u8>,
+    current: Option<FrameKey>,
+    next: Option<(FrameKey, Instant)>,
+    counter: u64,
+}
+
+impl FrameEncryptor {
+    /// Encryptor for frames sent under `sender` (our public key).
+    ///
+    /// The counter starts at a random value: a MEK outlives a call, and a
+    /// fixed start would repeat nonces each time we rejoin the channel.
+    pub fn new(sender: Vec<u8>) -> Self {
+        Self {
+            sender,
+            current: None,
+            next: None,
+            counter: rand::rngs::OsRng.next_u64(),
+        }
+    }
+
+    /// Switch to `key` at `activate_at`, or right away if we have no key yet.
+    ///
+    /// Replaces any key still waiting to be activated.
+    pub fn set_key(&mut self, key: FrameKey, activate_at: Instant) {
+        if self.current.is_none() {
+            self.current = Some(key);
+            self.next = None;
+        } else {
+            self.next = Some((key, activate_at));
+        }
+    }
+
+    /// Id of the key frames are currently sealed with.
+    pub fn key_id(&self) -> Option<u64> {
+        self.current.as_ref().map(FrameKey::id)
+    }
+
+    /// Seal one frame, first switching keys if a pending key is due.
+    pub fn encrypt(
+        &mut self,
+        now: Instant,
+        plaintext: &[u8],
+    ) -> Result<EncryptedFrame, CryptoError> {
+        if self.next.as_ref().is_some_and(|(_, at)| now >= *at) {
+            self.current = self.next.take().map(|(key, _)| key);
+        }
+        let key = self
+            .current
+            .as_ref()
+            .ok_or_else(|| CryptoError::EncryptionError("no frame key".into()))?;
+
+        let counter = self.counter;
+        self.counter = self.counter.wrapping_add(1);
+
+        let (cipher, salt) = key.sender_cipher(&self.sender)?;
+        let aad = frame_aad(key.id, counter, &self.sender);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&frame_nonce(&salt, counter)),
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
+        Ok(EncryptedFrame {
+            key_id: key.id,
+            counter,
+            ciphertext,
+        })
+    }
+}
+
+/// Receiving side: the most recent [`RETAINED_FRAME_KEYS`] keys.
+#[derive(Default)]
+pub struct FrameDecryptor {
+    keys: VecDeque<FrameKey>,
+}
+
+impl FrameDecryptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key, replacing one with the same id and evicting the oldest.
+    pub fn add_key(&mut self, key: FrameKey) {
+        self.keys.retain(|k| k.id != key.id);
+        self.keys.push_back(key);
+        while self.keys.len() > RETAINED_FRAME_KEYS {
+            self.keys.pop_front();
+        }
+    }
+
+    /// Whether a key with this id is held.
+    pub fn has_key(&self, key_id: u64) -> bool {
+        self.keys.iter().any(|k| k.id == key_id)
+    }
+
+    /// Open a frame sent by `sender`.
+    pub fn decrypt(&self, sender: &[u8], frame: &EncryptedFrame) -> Result<Vec<u8>, CryptoError> {
+        let key = self
+            .keys
+            .iter()
+            .find(|k| k.id == frame.key_id)
+            .ok_or_else(|| {
+                CryptoError::DecryptionError(format!("unknown frame key {}", frame.key_id))
+            })?;
+
+        let (cipher, salt) = key.sender_cipher(sender)?;
+        let aad = frame_aad(frame.key_id, frame.counter, sender);
+        cipher
+            .decrypt(
+                Nonce::from_slice(&frame_nonce(&salt, frame.counter)),
+                Payload {
+                    msg: &frame.ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|e| CryptoError::DecryptionError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const ALICE: &[u8] = &[0xA1; 32];
+    const BOB: &[u8] = &[0xB0; 32];
+
+    fn decryptor_with(keys: &[&FrameKey]) -> FrameDecryptor {
+        let mut decryptor = FrameDecryptor::new();
+        for key in keys {
+            decryptor.add_key((*key).clone());
+        }
+        decryptor
+    }
+
+    #[test]
+    fn frames_round_trip_and_bind_the_sender() {
+        let key = FrameKey::from_mek(&MediaEncryptionKey::generate(3));
+        let mut alice = FrameEncryptor::new(ALICE.to_vec());
+        alice.set_key(key.clone(), Instant::now());
+
+        let frame = alice.encrypt(Instant::now(), b"opus frame").unwrap();
+        assert_eq!(frame.key_id, 3);
+
+        let decryptor = decryptor_with(&[&key]);
+        assert_eq!(decryptor.decrypt(ALICE, &frame).unwrap(), b"opus frame");
+        // Same channel key, but Bob's derived key differs
+        assert!(decryptor.decrypt(BOB, &frame).is_err());
+    }
+
+    #[test]
+    fn tampered_header_rejected() {
+        let key = FrameKey::generate(1);
+        let mut alice = FrameEncryptor::new(ALICE.to_vec());
+        alice.set_key(key.clone(), Instant::now());
+        let decryptor = decryptor_with(&[&key]);
+
+        let frame = alice.encrypt(Instant::now(), b"hello").unwrap();
+        let mut replayed = frame.clone();
+        replayed.counter = replayed.counter.wrapping_add(1);
+        assert!(decryptor.decrypt(ALICE, &replayed).is_err());
+
+        let mut flipped = frame;
+        flipped.ciphertext[0] ^= 1;
+        assert!(decryptor.decrypt(ALICE, &flipped).is_err());
+    }
+
+    #[test]
+    fn senders_never_reuse_a_nonce() {
+        let key = FrameKey::generate(1);
+        let mut alice = FrameEncryptor::new(ALICE.to_vec());
+        alice.set_key(key, Instant::now());
+        let a = alice.encrypt(Instant::now(), b"same").unwrap();
+        let b = alice.encrypt(Instant::now(), b"same").unwrap();
+        assert_eq!(b.counter, a.counter.wrapping_add(1));
+        assert_ne!(a.ciphertext, b.ciphertext);
+    }
+
+    #[test]
+    fn key_switch_waits_for_activation() {
+        let old = FrameKey::generate(1);
+        let new = FrameKey::generate(2);
+        let start = Instant::now();
+        let mut alice = FrameEncryptor::new(ALICE.to_vec());
+        alice.set_key(old.clone(), start);
+        alice.set_key(new.clone(), start + Duration::from_secs(2));
+
+        let before = alice.encrypt(start + Duration::from_secs(1), b"a").unwrap();
+        assert_eq!(before.key_id, 1);
+        let after = alice.encrypt(start + Duration::from_secs(3), b"b").unwrap();
+        assert_eq!(after.key_id, 2);
+        assert_eq!(alice.key_id(), Some(2));
+
+        // A receiver holding both keys opens frames from either side of the switch
+        let decryptor = decryptor_with(&[&old, &new]);
+        assert_eq!(decryptor.decrypt(ALICE, &before).unwrap(), b"a");
+        assert_eq!(decryptor.decrypt(ALICE, &after).unwrap(), b"b");
+    }
+
+    #[test]
+    fn old_keys_are_evicted() {
+        let mut decryptor = FrameDecryptor::new();
+        for id in 0..=RETAINED_FRAME_KEYS as u64 {
+            decryptor.add_key(FrameKey::generate(id));
+        }
+        assert!(!decryptor.has_key(0));
+        assert!(decryptor.has_key(RETAINED_FRAME_KEYS as u64));
+    }
+
+    #[test]
+    fn no_key_no_frame() {
+        let mut alice = FrameEncryptor::new(ALICE.to_vec());
+        assert!(alice.encrypt(Instant::now(), b"x").is_err());
+    }
+}
@@ -7,7 +7,7 @@
 //! **WARNING**: Data is lost on process exit. For production use,
 //! implement the traits using Stronghold + `SQLite` via the Tauri backend.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 
 use crate::CryptoError;
@@ -22,6 +22,7 @@ pub struct MemoryIdentityStore {
     identity_public: Vec<u8>,
     registration_id: u32,
     trusted: Mutex<HashMap<String, Vec<u8>>>,
+    verified: Mutex<HashSet<String>>,
 }
 
 impl MemoryIdentityStore {
@@ -31,6 +32,7 @@ impl MemoryIdentityStore {
             identity_public,
             registration_id,
             trusted: Mutex::new(HashMap::new()),
+            verified: Mutex::new(HashSet::new()),
         }
     }
 }
@@ -53,10 +55,42 @@ impl IdentityKeyStore for MemoryIdentityStore {
     }
 
     fn save_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
+        let mut trusted = self.trusted.lock().unwrap();
+        match trusted.get(address) {
+            Some(stored) if stored != identity_key => {
+                Err(CryptoError::IdentityKeyChanged(address.to_string()))
+            }
+            _ => {
+                trusted.insert(address.to_string(), identity_key.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    fn get_identity(&self, address: &str) -> Result<Option<Vec<u8>>, CryptoError> {
+        Ok(self.trusted.lock().unwrap().get(address).cloned())
+    }
+
+    fn replace_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
         self.trusted
             .lock()
             .unwrap()
             .insert(address.to_string(), identity_key.to_vec());
+        self.verified.lock().unwrap().remove(address);
+        Ok(())
+    }
+
+    fn is_verified(&self, address: &str) -> Result<bool, CryptoError> {
+        Ok(self.verified.lock().unwrap().contains(address))
+    }
+
+    fn set_verified(&self, address: &str, verified: bool) -> Result<(), CryptoError> {
+        let mut set = self.verified.lock().unwrap();
+        if verified {
+            set.insert(address.to_string());
+        } else {
+            set.remove(address);
+        }
         Ok(())
     }
 }
@@ -122,6 +156,19 @@ impl PreKeyStore for MemoryPreKeyStore {
             .insert(signed_prekey_id, key_data.to_vec());
         Ok(())
     }
+
+    fn remove_signed_prekey(&self, signed_prekey_id: u32) -> Result<(), CryptoError> {
+        self.signed_prekeys.lock().unwrap().remove(&signed_prekey_id);
+        Ok(())
+    }
+
+    fn list_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        Ok(self.prekeys.lock().unwrap().keys().copied().collect())
+    }
+
+    fn list_signed_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        Ok(self.signed_prekeys.lock().unwrap().keys().copied().collect())
+    }
 }
 
 /// In-memory session store.
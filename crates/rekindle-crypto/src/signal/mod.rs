@@ -1,11 +1,13 @@
 pub mod prekeys;
+pub mod safety_number;
 pub mod session;
 pub mod store;
 pub mod memory_stores;
 #[cfg(test)]
 mod test_stores;
 
-pub use prekeys::PreKeyBundle;
+pub use prekeys::{OneTimePreKeyPublic, PreKeyBundle};
+pub use safety_number::SafetyNumber;
 pub use session::{SessionInitInfo, SignalSessionManager};
 pub use store::{IdentityKeyStore, PreKeyStore, SessionStore};
 pub use memory_stores::{MemoryIdentityStore, MemoryPreKeyStore, MemorySessionStore};
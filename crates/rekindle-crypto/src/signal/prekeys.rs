@@ -1,7 +1,9 @@
 //! `PreKey` generation and bundle creation for Signal Protocol.
 //!
 //! Pre-key bundles are published to DHT profile subkey 5 so that
-//! new contacts can establish a Signal session asynchronously.
+//! new contacts can establish a Signal session asynchronously. One-time
+//! prekeys are published separately (one per profile subkey) so a pool of
+//! them can be consumed and replenished independently of the bundle.
 
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +20,44 @@ pub struct PreKeyBundle {
     pub one_time_prekey: Option<Vec<u8>>,
     /// Registration ID for Signal Protocol.
     pub registration_id: u32,
+    /// ID of the signed prekey, echoed back by the initiator.
+    ///
+    /// Bundles published before prekey rotation always used ID 1.
+    #[serde(default = "legacy_prekey_id")]
+    pub signed_prekey_id: u32,
+    /// ID of the one-time prekey, if one is included.
+    #[serde(default)]
+    pub one_time_prekey_id: Option<u32>,
 }
 
-// TODO: Implement prekey generation, bundle creation, and rotation
+impl PreKeyBundle {
+    /// Attach a one-time prekey fetched separately (e.g., from a DHT pool subkey).
+    pub fn with_one_time_prekey(mut self, prekey: OneTimePreKeyPublic) -> Self {
+        self.one_time_prekey = Some(prekey.public_key);
+        self.one_time_prekey_id = Some(prekey.id);
+        self
+    }
+
+    /// The one-time prekey ID the responder must use for this bundle.
+    ///
+    /// Legacy bundles carried a one-time prekey without an ID; those were
+    /// always generated with ID 1.
+    pub fn effective_one_time_prekey_id(&self) -> Option<u32> {
+        self.one_time_prekey
+            .as_ref()
+            .map(|_| self.one_time_prekey_id.unwrap_or_else(legacy_prekey_id))
+    }
+}
+
+/// The public half of a one-time prekey, as published to the DHT pool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneTimePreKeyPublic {
+    /// Prekey ID, used by the responder to find the private half.
+    pub id: u32,
+    /// X25519 public key.
+    pub public_key: Vec<u8>,
+}
+
+fn legacy_prekey_id() -> u32 {
+    1
+}
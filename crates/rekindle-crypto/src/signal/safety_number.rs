@@ -0,0 +1,153 @@
+use std::fmt::Write as _;
+
+use sha2::{Digest, Sha512};
+
+use crate::CryptoError;
+
+/// Fingerprint format version, mixed into every hash.
+const FINGERPRINT_VERSION: u16 = 0;
+
+/// Iterated hash rounds — makes brute-forcing a colliding key expensive.
+const FINGERPRINT_ITERATIONS: usize = 5200;
+
+/// Bytes of each iterated hash kept as the fingerprint.
+const FINGERPRINT_LEN: usize = 32;
+
+/// Version byte prefixed to the QR payload.
+const QR_VERSION: u8 = 1;
+
+/// Safety number for verifying a contact's identity key out-of-band.
+///
+/// Each side's fingerprint is an iterated SHA-512 over its stable identifier
+/// and identity key. The displayable number is the two 30-digit halves in
+/// sorted order, so both parties see the same 60 digits. The QR payload
+/// carries both raw fingerprints in local-first order; scanning the peer's
+/// code compares them crosswise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafetyNumber {
+    local: [u8; FINGERPRINT_LEN],
+    remote: [u8; FINGERPRINT_LEN],
+}
+
+impl SafetyNumber {
+    /// Compute the safety number between a local and a remote identity.
+    pub fn new(local_id: &str, local_key: &[u8], remote_id: &str, remote_key: &[u8]) -> Self {
+        Self {
+            local: fingerprint(local_id, local_key),
+            remote: fingerprint(remote_id, remote_key),
+        }
+    }
+
+    /// The 60-digit number both parties compare (no separators).
+    pub fn displayable(&self) -> String {
+        let local = digits(&self.local);
+        let remote = digits(&self.remote);
+        if local <= remote {
+            local + &remote
+        } else {
+            remote + &local
+        }
+    }
+
+    /// Bytes to encode in a QR code: `version || local || remote`.
+    pub fn qr_payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(1 + 2 * FINGERPRINT_LEN);
+        payload.push(QR_VERSION);
+        payload.extend_from_slice(&self.local);
+        payload.extend_from_slice(&self.remote);
+        payload
+    }
+
+    /// Check a QR payload scanned from the peer's device.
+    ///
+    /// Returns `Ok(false)` when the fingerprints differ (keys don't match)
+    /// and an error when the payload is malformed or from another version.
+    pub fn matches_scanned(&self, scanned: &[u8]) -> Result<bool, CryptoError> {
+        if scanned.len() != 1 + 2 * FINGERPRINT_LEN {
+            return Err(CryptoError::VerificationError(
+                "safety number QR payload has wrong length".into(),
+            ));
+        }
+        if scanned[0] != QR_VERSION {
+            return Err(CryptoError::VerificationError(format!(
+                "unsupported safety number version {}",
+                scanned[0]
+            )));
+        }
+        // The scanner's "remote" is us, and its "local" is them
+        let (their_local, their_remote) = scanned[1..].split_at(FINGERPRINT_LEN);
+        Ok(their_local == self.remote && their_remote == self.local)
+    }
+}
+
+/// Iterated SHA-512 fingerprint of one identity.
+fn fingerprint(stable_id: &str, identity_key: &[u8]) -> [u8; FINGERPRINT_LEN] {
+    let mut hash = Sha512::new()
+        .chain_update(FINGERPRINT_VERSION.to_be_bytes())
+        .chain_update(identity_key)
+        .chain_update(stable_id.as_bytes())
+        .finalize();
+    for _ in 1..FINGERPRINT_ITERATIONS {
+        hash = Sha512::new()
+            .chain_update(hash)
+            .chain_update(identity_key)
+            .finalize();
+    }
+    let mut out = [0u8; FINGERPRINT_LEN];
+    out.copy_from_slice(&hash[..FINGERPRINT_LEN]);
+    out
+}
+
+/// Render the first 30 bytes of a fingerprint as six 5-digit groups.
+fn digits(fingerprint: &[u8; FINGERPRINT_LEN]) -> String {
+    let mut out = String::with_capacity(30);
+    for chunk in fingerprint[..30].chunks(5) {
+        let value = chunk.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        let _ = write!(out, "{:05}", value % 100_000);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "alice_ed25519_hex";
+    const BOB: &str = "bob_ed25519_hex";
+
+    #[test]
+    fn both_sides_see_same_number() {
+        let alice_view = SafetyNumber::new(ALICE, &[1u8; 32], BOB, &[2u8; 32]);
+        let bob_view = SafetyNumber::new(BOB, &[2u8; 32], ALICE, &[1u8; 32]);
+        assert_eq!(alice_view.displayable(), bob_view.displayable());
+        assert_eq!(alice_view.displayable().len(), 60);
+        assert!(alice_view.displayable().chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn changed_key_changes_number() {
+        let before = SafetyNumber::new(ALICE, &[1u8; 32], BOB, &[2u8; 32]);
+        let after = SafetyNumber::new(ALICE, &[1u8; 32], BOB, &[3u8; 32]);
+        assert_ne!(before.displayable(), after.displayable());
+    }
+
+    #[test]
+    fn scanned_qr_matches_crosswise() {
+        let alice_view = SafetyNumber::new(ALICE, &[1u8; 32], BOB, &[2u8; 32]);
+        let bob_view = SafetyNumber::new(BOB, &[2u8; 32], ALICE, &[1u8; 32]);
+        assert!(alice_view.matches_scanned(&bob_view.qr_payload()).unwrap());
+
+        // A MITM'd key on Bob's side produces a mismatch
+        let mitm_view = SafetyNumber::new(BOB, &[2u8; 32], ALICE, &[9u8; 32]);
+        assert!(!alice_view.matches_scanned(&mitm_view.qr_payload()).unwrap());
+    }
+
+    #[test]
+    fn malformed_qr_rejected() {
+        let view = SafetyNumber::new(ALICE, &[1u8; 32], BOB, &[2u8; 32]);
+        let mut payload = view.qr_payload();
+        payload[0] = 0xFF;
+        assert!(view.matches_scanned(&payload).is_err());
+        assert!(view.matches_scanned(&payload[..10]).is_err());
+    }
+}
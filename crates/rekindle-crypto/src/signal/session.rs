@@ -1,8 +1,9 @@
 use crate::error::CryptoError;
-use crate::signal::prekeys::PreKeyBundle;
+use crate::signal::prekeys::{OneTimePreKeyPublic, PreKeyBundle};
+use crate::signal::safety_number::SafetyNumber;
 use crate::signal::store::{IdentityKeyStore, PreKeyStore, SessionStore};
 
-use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
 use aes_gcm::{Aes256Gcm, Nonce};
 use ed25519_dalek::{Signer, SigningKey};
 use hkdf::Hkdf;
@@ -24,8 +25,12 @@ pub struct SessionInitInfo {
 
 /// Manages Signal Protocol sessions for 1:1 encrypted messaging.
 ///
-/// Uses X3DH for session establishment and a simplified Double Ratchet
-/// for forward-secret message encryption.
+/// Uses X3DH for session establishment and the Double Ratchet for
+/// forward-secret, self-healing message encryption. Every message header
+/// carries the sender's current DH ratchet public key; receiving a new key
+/// triggers a root-key step. Keys for messages that arrive out of order are
+/// kept in a bounded skipped-key store so reordered or retried mailbox
+/// deliveries still decrypt.
 #[allow(clippy::struct_field_names)] // Store suffix clarifies the role of each field
 pub struct SignalSessionManager {
     identity_store: Box<dyn IdentityKeyStore>,
@@ -33,7 +38,87 @@ pub struct SignalSessionManager {
     session_store: Box<dyn SessionStore>,
 }
 
-/// An established session's symmetric ratchet state.
+/// Maximum number of message keys skipped within a single receiving chain.
+///
+/// Bounds the work an attacker can force with a forged header counter.
+const MAX_SKIP: u32 = 1000;
+
+/// Maximum number of skipped message keys retained per session.
+///
+/// When exceeded, the oldest skipped keys are evicted first.
+const MAX_SKIPPED_KEYS: usize = 2000;
+
+/// Version byte at the start of every encrypted message.
+///
+/// Version 1 (implicit, no version byte) was the symmetric-only format:
+/// `counter(8) || nonce(12) || ciphertext`.
+const MESSAGE_VERSION: u8 = 2;
+
+/// Length of the message header: version(1) + DH public(32) + pn(4) + n(4).
+const HEADER_LEN: usize = 1 + 32 + 4 + 4;
+
+/// AES-GCM authentication tag length.
+const TAG_LEN: usize = 16;
+
+/// Magic prefix identifying a versioned ratchet state blob.
+const RATCHET_MAGIC: &[u8; 4] = b"RKDR";
+
+/// Current ratchet state serialization version.
+const RATCHET_VERSION: u8 = 2;
+
+/// Header sent in the clear (but authenticated) with every message.
+struct MessageHeader {
+    /// Sender's current DH ratchet public key.
+    dh_public: [u8; 32],
+    /// Number of messages in the sender's previous sending chain.
+    previous_chain_len: u32,
+    /// Message number within the current sending chain.
+    counter: u32,
+}
+
+impl MessageHeader {
+    fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0u8; HEADER_LEN];
+        out[0] = MESSAGE_VERSION;
+        out[1..33].copy_from_slice(&self.dh_public);
+        out[33..37].copy_from_slice(&self.previous_chain_len.to_le_bytes());
+        out[37..41].copy_from_slice(&self.counter.to_le_bytes());
+        out
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self, CryptoError> {
+        if data.len() < HEADER_LEN {
+            return Err(CryptoError::DecryptionError("message too short".into()));
+        }
+        if data[0] != MESSAGE_VERSION {
+            return Err(CryptoError::DecryptionError(format!(
+                "unsupported message version {}",
+                data[0]
+            )));
+        }
+        Ok(Self {
+            dh_public: read_array(data, 1)
+                .map_err(|_| CryptoError::DecryptionError("invalid header".into()))?,
+            previous_chain_len: read_u32(data, 33)
+                .map_err(|_| CryptoError::DecryptionError("invalid header".into()))?,
+            counter: read_u32(data, 37)
+                .map_err(|_| CryptoError::DecryptionError("invalid header".into()))?,
+        })
+    }
+}
+
+/// A message key retained for a message that has not arrived yet.
+#[derive(Clone)]
+struct SkippedKey {
+    /// DH ratchet public key of the chain the message belongs to.
+    dh_public: [u8; 32],
+    /// Message number within that chain.
+    counter: u32,
+    /// The derived message key.
+    message_key: [u8; 32],
+}
+
+/// An established session's Double Ratchet state.
 #[derive(Clone)]
 struct RatchetState {
     /// Root key — evolves with each DH ratchet step.
@@ -42,14 +127,99 @@ struct RatchetState {
     sending_chain_key: [u8; 32],
     /// Receiving chain key — evolves with each message received.
     receiving_chain_key: [u8; 32],
-    /// Our current DH ratchet keypair (X25519).
-    our_ratchet_secret: Vec<u8>,
+    /// Our current DH ratchet private key (X25519).
+    our_ratchet_secret: [u8; 32],
     /// Their current DH ratchet public key.
-    their_ratchet_public: Vec<u8>,
-    /// Send message counter.
-    send_counter: u64,
-    /// Receive message counter.
-    recv_counter: u64,
+    ///
+    /// `None` only for sessions migrated from the symmetric-only format,
+    /// until the first message from the peer reveals their ratchet key.
+    their_ratchet_public: Option<[u8; 32]>,
+    /// Messages sent in the current sending chain.
+    send_counter: u32,
+    /// Messages received in the current receiving chain.
+    recv_counter: u32,
+    /// Length of our previous sending chain (sent in headers as `pn`).
+    previous_send_counter: u32,
+    /// Whether our next send must start a fresh sending chain with a new
+    /// DH ratchet key before encrypting.
+    pending_send_ratchet: bool,
+    /// Keys for skipped (not yet received) messages, oldest first.
+    skipped_keys: Vec<SkippedKey>,
+}
+
+impl RatchetState {
+    /// Our current DH ratchet public key.
+    fn our_ratchet_public(&self) -> [u8; 32] {
+        X25519Public::from(&StaticSecret::from(self.our_ratchet_secret)).to_bytes()
+    }
+
+    /// Start a new sending chain with a fresh DH ratchet keypair.
+    fn ratchet_sending(&mut self, their_public: &[u8; 32]) -> Result<(), CryptoError> {
+        let new_secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let dh = new_secret.diffie_hellman(&X25519Public::from(*their_public));
+        let (root_key, chain_key) = kdf_root(&self.root_key, dh.as_bytes())?;
+        self.root_key = root_key;
+        self.sending_chain_key = chain_key;
+        self.our_ratchet_secret = new_secret.to_bytes();
+        self.previous_send_counter = self.send_counter;
+        self.send_counter = 0;
+        self.pending_send_ratchet = false;
+        Ok(())
+    }
+
+    /// Perform a full DH ratchet step on receiving a new ratchet public key.
+    fn ratchet_receiving(&mut self, their_public: [u8; 32]) -> Result<(), CryptoError> {
+        let dh = StaticSecret::from(self.our_ratchet_secret)
+            .diffie_hellman(&X25519Public::from(their_public));
+        let (root_key, chain_key) = kdf_root(&self.root_key, dh.as_bytes())?;
+        self.root_key = root_key;
+        self.receiving_chain_key = chain_key;
+        self.their_ratchet_public = Some(their_public);
+        self.recv_counter = 0;
+        self.ratchet_sending(&their_public)
+    }
+
+    /// Derive and store message keys for the current receiving chain up to
+    /// (but not including) message number `until`.
+    fn skip_message_keys(&mut self, until: u32) -> Result<(), CryptoError> {
+        if until <= self.recv_counter {
+            return Ok(());
+        }
+        if until - self.recv_counter > MAX_SKIP {
+            return Err(CryptoError::DecryptionError(format!(
+                "too many skipped messages ({})",
+                until - self.recv_counter
+            )));
+        }
+        let Some(dh_public) = self.their_ratchet_public else {
+            // Legacy chain with an unknown sender key: nothing to index by.
+            return Ok(());
+        };
+        while self.recv_counter < until {
+            let (message_key, next_chain_key) = kdf_chain(&self.receiving_chain_key)?;
+            self.receiving_chain_key = next_chain_key;
+            self.skipped_keys.push(SkippedKey {
+                dh_public,
+                counter: self.recv_counter,
+                message_key,
+            });
+            self.recv_counter += 1;
+        }
+        if self.skipped_keys.len() > MAX_SKIPPED_KEYS {
+            let excess = self.skipped_keys.len() - MAX_SKIPPED_KEYS;
+            self.skipped_keys.drain(..excess);
+        }
+        Ok(())
+    }
+
+    /// Remove and return a stored key for a previously skipped message.
+    fn take_skipped_key(&mut self, header: &MessageHeader) -> Option<[u8; 32]> {
+        let index = self
+            .skipped_keys
+            .iter()
+            .position(|k| k.dh_public == header.dh_public && k.counter == header.counter)?;
+        Some(self.skipped_keys.remove(index).message_key)
+    }
 }
 
 impl SignalSessionManager {
@@ -70,7 +240,13 @@ impl SignalSessionManager {
     ///
     /// This is the initiator side — called when we want to start a conversation
     /// with someone whose `PreKeyBundle` we fetched from DHT.
+    ///
+    /// Fails with [`CryptoError::IdentityKeyChanged`] (leaving any existing
+    /// session untouched) if the bundle's identity key differs from the one
+    /// recorded for this peer.
     pub fn establish_session(&self, peer_address: &str, bundle: &PreKeyBundle) -> Result<SessionInitInfo, CryptoError> {
+        self.check_identity(peer_address, &bundle.identity_key)?;
+
         // X3DH key agreement:
         // 1. Generate ephemeral X25519 keypair
         let ephemeral_secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
@@ -129,15 +305,20 @@ impl SignalSessionManager {
         sending_chain_key.copy_from_slice(&okm[32..64]);
         receiving_chain_key.copy_from_slice(&okm[64..96]);
 
-        // 7. Serialize and store the initial ratchet state
+        // 7. Serialize and store the initial ratchet state. Their signed prekey
+        //    doubles as their first DH ratchet key; our first send performs a
+        //    DH ratchet step against it so the responder ratchets on receipt.
         let ratchet = RatchetState {
             root_key,
             sending_chain_key,
             receiving_chain_key,
-            our_ratchet_secret: ephemeral_public.as_bytes().to_vec(),
-            their_ratchet_public: bundle.signed_prekey.clone(),
+            our_ratchet_secret: ephemeral_bytes,
+            their_ratchet_public: Some(their_signed_prekey.to_bytes()),
             send_counter: 0,
             recv_counter: 0,
+            previous_send_counter: 0,
+            pending_send_ratchet: true,
+            skipped_keys: Vec::new(),
         };
 
         let session_data = serialize_ratchet(&ratchet);
@@ -148,8 +329,8 @@ impl SignalSessionManager {
 
         Ok(SessionInitInfo {
             ephemeral_public_key: ephemeral_public.as_bytes().to_vec(),
-            signed_prekey_id: 1, // matches generate_prekey_bundle(1, ...)
-            one_time_prekey_id: if bundle.one_time_prekey.is_some() { Some(1) } else { None },
+            signed_prekey_id: bundle.signed_prekey_id,
+            one_time_prekey_id: bundle.effective_one_time_prekey_id(),
         })
     }
 
@@ -157,6 +338,12 @@ impl SignalSessionManager {
     ///
     /// Called when we receive a friend request or initial message containing
     /// the initiator's identity key and ephemeral public key.
+    ///
+    /// Fails with [`CryptoError::IdentityKeyChanged`] if `their_identity_key`
+    /// differs from the one recorded for this peer, and with
+    /// [`CryptoError::OneTimePreKeyConsumed`] if the one-time prekey was
+    /// already used by someone else (pool keys are fetched without being
+    /// claimed). No session is stored in either case.
     pub fn respond_to_session(
         &self,
         peer_address: &str,
@@ -165,6 +352,8 @@ impl SignalSessionManager {
         signed_prekey_id: u32,
         one_time_prekey_id: Option<u32>,
     ) -> Result<(), CryptoError> {
+        self.check_identity(peer_address, their_identity_key)?;
+
         // Load our identity keypair
         let (identity_private, _identity_public) = self.identity_store.get_identity_key_pair()?;
         let our_identity_x25519 = StaticSecret::from(
@@ -213,7 +402,7 @@ impl SignalSessionManager {
             let otpk_data = self
                 .prekey_store
                 .load_prekey(otpk_id)?
-                .ok_or_else(|| CryptoError::InvalidKey("one-time prekey not found".into()))?;
+                .ok_or(CryptoError::OneTimePreKeyConsumed(otpk_id))?;
             let otpk_secret = StaticSecret::from(
                 <[u8; 32]>::try_from(otpk_data.as_slice())
                     .map_err(|_| CryptoError::InvalidKey("one-time prekey wrong length".into()))?,
@@ -238,16 +427,19 @@ impl SignalSessionManager {
         receiving_chain_key.copy_from_slice(&okm[32..64]);
         sending_chain_key.copy_from_slice(&okm[64..96]);
 
+        // Our signed prekey is our first DH ratchet key; their ephemeral key
+        // is theirs until their first message advertises a new one.
         let ratchet = RatchetState {
             root_key,
             sending_chain_key,
             receiving_chain_key,
-            our_ratchet_secret: X25519Public::from(&StaticSecret::from(spk_bytes))
-                .as_bytes()
-                .to_vec(),
-            their_ratchet_public: their_ephemeral_key.to_vec(),
+            our_ratchet_secret: spk_bytes,
+            their_ratchet_public: Some(their_ephemeral.to_bytes()),
             send_counter: 0,
             recv_counter: 0,
+            previous_send_counter: 0,
+            pending_send_ratchet: false,
+            skipped_keys: Vec::new(),
         };
 
         let session_data = serialize_ratchet(&ratchet);
@@ -260,6 +452,10 @@ impl SignalSessionManager {
     }
 
     /// Encrypt a plaintext message for a peer.
+    ///
+    /// Output layout: `header(41) || AES-256-GCM ciphertext`, where the header
+    /// is `version || our DH ratchet public || pn || n` and is authenticated
+    /// as associated data.
     pub fn encrypt(&self, peer_address: &str, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
         let session_data = self
             .session_store
@@ -268,35 +464,32 @@ impl SignalSessionManager {
 
         let mut ratchet = deserialize_ratchet(&session_data)?;
 
-        // Derive message key from sending chain key via HKDF
-        let hk = Hkdf::<Sha256>::new(None, &ratchet.sending_chain_key);
-        let mut message_key = [0u8; 32];
-        let mut next_chain_key = [0u8; 32];
-        hk.expand(b"ReKindleMsgKey", &mut message_key)
-            .map_err(|e| CryptoError::EncryptionError(format!("HKDF: {e}")))?;
-        hk.expand(b"ReKindleChainKey", &mut next_chain_key)
-            .map_err(|e| CryptoError::EncryptionError(format!("HKDF: {e}")))?;
+        // Start a new sending chain if we owe the peer a DH ratchet step
+        if ratchet.pending_send_ratchet {
+            if let Some(their_public) = ratchet.their_ratchet_public {
+                ratchet.ratchet_sending(&their_public)?;
+            }
+        }
 
-        // Advance sending chain
+        // Derive message key and advance the sending chain
+        let (message_key, next_chain_key) = kdf_chain(&ratchet.sending_chain_key)?;
         ratchet.sending_chain_key = next_chain_key;
-        ratchet.send_counter += 1;
-
-        // Encrypt with AES-256-GCM
-        let cipher = Aes256Gcm::new_from_slice(&message_key)
-            .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
 
-        let mut nonce_bytes = [0u8; 12];
-        nonce_bytes[4..].copy_from_slice(&ratchet.send_counter.to_le_bytes());
-        let nonce = Nonce::from_slice(&nonce_bytes);
+        let header = MessageHeader {
+            dh_public: ratchet.our_ratchet_public(),
+            previous_chain_len: ratchet.previous_send_counter,
+            counter: ratchet.send_counter,
+        };
+        ratchet.send_counter = ratchet
+            .send_counter
+            .checked_add(1)
+            .ok_or_else(|| CryptoError::EncryptionError("sending chain exhausted".into()))?;
 
-        let ciphertext = cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
+        let header_bytes = header.to_bytes();
+        let ciphertext = seal(&message_key, header.counter, &header_bytes, plaintext)?;
 
-        // Prepend counter + nonce for the recipient
-        let mut output = Vec::with_capacity(8 + 12 + ciphertext.len());
-        output.extend_from_slice(&ratchet.send_counter.to_le_bytes());
-        output.extend_from_slice(&nonce_bytes);
+        let mut output = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        output.extend_from_slice(&header_bytes);
         output.extend_from_slice(&ciphertext);
 
         // Save updated ratchet state
@@ -307,8 +500,14 @@ impl SignalSessionManager {
     }
 
     /// Decrypt a ciphertext message from a peer.
+    ///
+    /// Handles out-of-order delivery: messages from an older chain or an
+    /// earlier position in the current chain are decrypted with stored
+    /// skipped-message keys. Session state is only persisted when
+    /// decryption succeeds, so a forged or corrupt message cannot
+    /// desynchronise the session.
     pub fn decrypt(&self, peer_address: &str, message: &[u8]) -> Result<Vec<u8>, CryptoError> {
-        if message.len() < 20 {
+        if message.len() < HEADER_LEN + TAG_LEN {
             return Err(CryptoError::DecryptionError("message too short".into()));
         }
 
@@ -319,38 +518,34 @@ impl SignalSessionManager {
 
         let mut ratchet = deserialize_ratchet(&session_data)?;
 
-        // Parse counter + nonce + ciphertext
-        let _counter = u64::from_le_bytes(
-            message[..8]
-                .try_into()
-                .map_err(|_| CryptoError::DecryptionError("invalid counter".into()))?,
-        );
-        let nonce_bytes: [u8; 12] = message[8..20]
-            .try_into()
-            .map_err(|_| CryptoError::DecryptionError("invalid nonce".into()))?;
-        let ciphertext = &message[20..];
-
-        // Derive message key from receiving chain key
-        let hk = Hkdf::<Sha256>::new(None, &ratchet.receiving_chain_key);
-        let mut message_key = [0u8; 32];
-        let mut next_chain_key = [0u8; 32];
-        hk.expand(b"ReKindleMsgKey", &mut message_key)
-            .map_err(|e| CryptoError::DecryptionError(format!("HKDF: {e}")))?;
-        hk.expand(b"ReKindleChainKey", &mut next_chain_key)
-            .map_err(|e| CryptoError::DecryptionError(format!("HKDF: {e}")))?;
-
-        // Advance receiving chain
-        ratchet.receiving_chain_key = next_chain_key;
-        ratchet.recv_counter += 1;
-
-        // Decrypt with AES-256-GCM
-        let cipher = Aes256Gcm::new_from_slice(&message_key)
-            .map_err(|e| CryptoError::DecryptionError(e.to_string()))?;
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| CryptoError::DecryptionError(e.to_string()))?;
+        let header = MessageHeader::from_bytes(message)?;
+        let header_bytes = &message[..HEADER_LEN];
+        let ciphertext = &message[HEADER_LEN..];
+
+        let message_key = if let Some(key) = ratchet.take_skipped_key(&header) {
+            key
+        } else {
+            match ratchet.their_ratchet_public {
+                None => return Err(CryptoError::SessionError("corrupt session".into())),
+                Some(current) if current != header.dh_public => {
+                    ratchet.skip_message_keys(header.previous_chain_len)?;
+                    ratchet.ratchet_receiving(header.dh_public)?;
+                }
+                Some(_) => {}
+            }
+            if header.counter < ratchet.recv_counter {
+                return Err(CryptoError::DecryptionError(
+                    "duplicate or expired message".into(),
+                ));
+            }
+            ratchet.skip_message_keys(header.counter)?;
+            let (message_key, next_chain_key) = kdf_chain(&ratchet.receiving_chain_key)?;
+            ratchet.receiving_chain_key = next_chain_key;
+            ratchet.recv_counter += 1;
+            message_key
+        };
+
+        let plaintext = open(&message_key, header.counter, header_bytes, ciphertext)?;
 
         // Save updated ratchet state
         let new_session_data = serialize_ratchet(&ratchet);
@@ -369,6 +564,59 @@ impl SignalSessionManager {
         self.session_store.delete_session(peer_address)
     }
 
+    /// The identity key recorded for a peer, if any.
+    pub fn peer_identity_key(&self, peer_address: &str) -> Result<Option<Vec<u8>>, CryptoError> {
+        self.identity_store.get_identity(peer_address)
+    }
+
+    /// Accept a changed identity key for a peer.
+    ///
+    /// Replaces the recorded key and clears the verified flag. Call after
+    /// [`CryptoError::IdentityKeyChanged`], then retry session establishment.
+    pub fn accept_identity_change(&self, peer_address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
+        self.identity_store.replace_identity(peer_address, identity_key)
+    }
+
+    /// Whether the peer's recorded identity key was verified out-of-band.
+    pub fn is_verified(&self, peer_address: &str) -> Result<bool, CryptoError> {
+        self.identity_store.is_verified(peer_address)
+    }
+
+    /// Mark the peer's recorded identity key as verified (or not).
+    pub fn set_verified(&self, peer_address: &str, verified: bool) -> Result<(), CryptoError> {
+        if self.identity_store.get_identity(peer_address)?.is_none() {
+            return Err(CryptoError::SessionError(format!(
+                "no identity key recorded for {peer_address}"
+            )));
+        }
+        self.identity_store.set_verified(peer_address, verified)
+    }
+
+    /// Compute the safety number between us and a peer.
+    ///
+    /// `our_address` and `peer_address` are the stable public identifiers
+    /// (Ed25519 public key hex) shown to users; the fingerprints cover the
+    /// X25519 identity keys actually used in X3DH.
+    pub fn safety_number(&self, our_address: &str, peer_address: &str) -> Result<SafetyNumber, CryptoError> {
+        let (_, our_identity) = self.identity_store.get_identity_key_pair()?;
+        let their_identity = self
+            .identity_store
+            .get_identity(peer_address)?
+            .ok_or_else(|| CryptoError::SessionError(format!(
+                "no identity key recorded for {peer_address}"
+            )))?;
+        Ok(SafetyNumber::new(our_address, &our_identity, peer_address, &their_identity))
+    }
+
+    /// Reject a session whose identity key differs from the recorded one.
+    fn check_identity(&self, peer_address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
+        if self.identity_store.is_trusted_identity(peer_address, identity_key)? {
+            Ok(())
+        } else {
+            Err(CryptoError::IdentityKeyChanged(peer_address.to_string()))
+        }
+    }
+
     /// Generate a `PreKeyBundle` for publication to DHT.
     ///
     /// Creates a signed prekey and optional one-time prekey, stores them
@@ -378,112 +626,323 @@ impl SignalSessionManager {
         signed_prekey_id: u32,
         one_time_prekey_id: Option<u32>,
     ) -> Result<PreKeyBundle, CryptoError> {
-        let (identity_private, identity_public) = self.identity_store.get_identity_key_pair()?;
-        let registration_id = self.identity_store.get_local_registration_id()?;
+        self.generate_signed_prekey(signed_prekey_id)?;
+        if let Some(otpk_id) = one_time_prekey_id {
+            self.generate_one_time_prekeys(&[otpk_id])?;
+        }
+        self.prekey_bundle(signed_prekey_id, one_time_prekey_id)
+    }
 
-        // Generate signed prekey (X25519)
+    /// Generate a new signed prekey and store its private half.
+    ///
+    /// Older signed prekeys are left in place so sessions initiated against
+    /// them can still be answered during the rotation grace window.
+    pub fn generate_signed_prekey(&self, signed_prekey_id: u32) -> Result<(), CryptoError> {
         let signed_prekey_secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
-        let signed_prekey_public = X25519Public::from(&signed_prekey_secret);
         self.prekey_store
-            .store_signed_prekey(signed_prekey_id, signed_prekey_secret.as_bytes())?;
+            .store_signed_prekey(signed_prekey_id, signed_prekey_secret.as_bytes())
+    }
+
+    /// Generate a batch of one-time prekeys and store their private halves.
+    ///
+    /// Returns the public halves for publication to the DHT prekey pool.
+    pub fn generate_one_time_prekeys(
+        &self,
+        prekey_ids: &[u32],
+    ) -> Result<Vec<OneTimePreKeyPublic>, CryptoError> {
+        let mut published = Vec::with_capacity(prekey_ids.len());
+        for &prekey_id in prekey_ids {
+            let otpk_secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
+            let otpk_public = X25519Public::from(&otpk_secret);
+            self.prekey_store
+                .store_prekey(prekey_id, otpk_secret.as_bytes())?;
+            published.push(OneTimePreKeyPublic {
+                id: prekey_id,
+                public_key: otpk_public.as_bytes().to_vec(),
+            });
+        }
+        Ok(published)
+    }
+
+    /// Public half of a stored one-time prekey, if it has not been consumed.
+    pub fn one_time_prekey_public(
+        &self,
+        prekey_id: u32,
+    ) -> Result<Option<OneTimePreKeyPublic>, CryptoError> {
+        let Some(secret) = self.prekey_store.load_prekey(prekey_id)? else {
+            return Ok(None);
+        };
+        Ok(Some(OneTimePreKeyPublic {
+            id: prekey_id,
+            public_key: public_from_secret(&secret, "one-time prekey")?.to_vec(),
+        }))
+    }
+
+    /// Build a `PreKeyBundle` from already-stored prekeys.
+    ///
+    /// Unlike [`generate_prekey_bundle`](Self::generate_prekey_bundle), this
+    /// never creates or overwrites keys, so bundles handed out earlier stay
+    /// valid.
+    pub fn prekey_bundle(
+        &self,
+        signed_prekey_id: u32,
+        one_time_prekey_id: Option<u32>,
+    ) -> Result<PreKeyBundle, CryptoError> {
+        let (identity_private, identity_public) = self.identity_store.get_identity_key_pair()?;
+        let registration_id = self.identity_store.get_local_registration_id()?;
+
+        let spk_secret = self
+            .prekey_store
+            .load_signed_prekey(signed_prekey_id)?
+            .ok_or_else(|| CryptoError::PreKeyError(format!("signed prekey {signed_prekey_id} not found")))?;
+        let signed_prekey_public = public_from_secret(&spk_secret, "signed prekey")?;
 
         // Sign the prekey public key bytes with our Ed25519 identity key
         let signing_key = SigningKey::from_bytes(
             &<[u8; 32]>::try_from(&identity_private[..32])
                 .map_err(|_| CryptoError::InvalidKey("identity key wrong length for signing".into()))?,
         );
-        let signature = signing_key.sign(signed_prekey_public.as_bytes());
+        let signature = signing_key.sign(&signed_prekey_public);
         let signed_prekey_signature = signature.to_bytes().to_vec();
 
-        // Optionally generate a one-time prekey
-        let one_time_prekey = if let Some(otpk_id) = one_time_prekey_id {
-            let otpk_secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
-            let otpk_public = X25519Public::from(&otpk_secret);
-            self.prekey_store
-                .store_prekey(otpk_id, otpk_secret.as_bytes())?;
-            Some(otpk_public.as_bytes().to_vec())
-        } else {
-            None
+        let one_time_prekey = match one_time_prekey_id {
+            Some(otpk_id) => Some(
+                self.one_time_prekey_public(otpk_id)?
+                    .ok_or_else(|| CryptoError::PreKeyError(format!("one-time prekey {otpk_id} not found")))?
+                    .public_key,
+            ),
+            None => None,
         };
 
         Ok(PreKeyBundle {
             identity_key: identity_public,
-            signed_prekey: signed_prekey_public.as_bytes().to_vec(),
+            signed_prekey: signed_prekey_public.to_vec(),
             signed_prekey_signature,
             one_time_prekey,
             registration_id,
+            signed_prekey_id,
+            one_time_prekey_id,
         })
     }
+
+    /// IDs of all unconsumed one-time prekeys.
+    pub fn one_time_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        self.prekey_store.list_prekey_ids()
+    }
+
+    /// IDs of all retained signed prekeys.
+    pub fn signed_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        self.prekey_store.list_signed_prekey_ids()
+    }
+
+    /// Discard a one-time prekey that was handed out but never used.
+    pub fn remove_one_time_prekey(&self, prekey_id: u32) -> Result<(), CryptoError> {
+        self.prekey_store.remove_prekey(prekey_id)
+    }
+
+    /// Discard a signed prekey once its rotation grace window has passed.
+    pub fn remove_signed_prekey(&self, signed_prekey_id: u32) -> Result<(), CryptoError> {
+        self.prekey_store.remove_signed_prekey(signed_prekey_id)
+    }
+}
+
+/// Derive an X25519 public key from stored private key bytes.
+fn public_from_secret(secret: &[u8], what: &str) -> Result<[u8; 32], CryptoError> {
+    let secret = <[u8; 32]>::try_from(secret)
+        .map_err(|_| CryptoError::InvalidKey(format!("{what} wrong length")))?;
+    Ok(X25519Public::from(&StaticSecret::from(secret)).to_bytes())
+}
+
+/// Root-key KDF: mix a DH output into the root key, yielding a new root key
+/// and a fresh chain key.
+fn kdf_root(root_key: &[u8; 32], dh_output: &[u8]) -> Result<([u8; 32], [u8; 32]), CryptoError> {
+    let hk = Hkdf::<Sha256>::new(Some(root_key), dh_output);
+    let mut okm = [0u8; 64];
+    hk.expand(b"ReKindleRatchet", &mut okm)
+        .map_err(|e| CryptoError::SessionError(format!("HKDF expand failed: {e}")))?;
+    let mut new_root = [0u8; 32];
+    let mut chain_key = [0u8; 32];
+    new_root.copy_from_slice(&okm[..32]);
+    chain_key.copy_from_slice(&okm[32..]);
+    Ok((new_root, chain_key))
 }
 
-// Simple binary serialization for ratchet state.
+/// Chain-key KDF: derive a message key and the next chain key.
+fn kdf_chain(chain_key: &[u8; 32]) -> Result<([u8; 32], [u8; 32]), CryptoError> {
+    let hk = Hkdf::<Sha256>::new(None, chain_key);
+    let mut message_key = [0u8; 32];
+    let mut next_chain_key = [0u8; 32];
+    hk.expand(b"ReKindleMsgKey", &mut message_key)
+        .map_err(|e| CryptoError::SessionError(format!("HKDF: {e}")))?;
+    hk.expand(b"ReKindleChainKey", &mut next_chain_key)
+        .map_err(|e| CryptoError::SessionError(format!("HKDF: {e}")))?;
+    Ok((message_key, next_chain_key))
+}
+
+/// Nonce for a message key. Each message key is used exactly once, so a
+/// counter-derived nonce is sufficient.
+fn message_nonce(counter: u32) -> [u8; 12] {
+    let mut nonce_bytes = [0u8; 12];
+    nonce_bytes[8..].copy_from_slice(&counter.to_le_bytes());
+    nonce_bytes
+}
+
+fn seal(
+    message_key: &[u8; 32],
+    counter: u32,
+    header: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let cipher = Aes256Gcm::new_from_slice(message_key)
+        .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
+    let nonce_bytes = message_nonce(counter);
+    cipher
+        .encrypt(
+            Nonce::from_slice(&nonce_bytes),
+            Payload {
+                msg: plaintext,
+                aad: header,
+            },
+        )
+        .map_err(|e| CryptoError::EncryptionError(e.to_string()))
+}
+
+fn open(
+    message_key: &[u8; 32],
+    counter: u32,
+    header: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let cipher = Aes256Gcm::new_from_slice(message_key)
+        .map_err(|e| CryptoError::DecryptionError(e.to_string()))?;
+    let nonce_bytes = message_nonce(counter);
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce_bytes),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|e| CryptoError::DecryptionError(e.to_string()))
+}
+
+fn read_array(data: &[u8], pos: usize) -> Result<[u8; 32], CryptoError> {
+    data.get(pos..pos + 32)
+        .and_then(|s| <[u8; 32]>::try_from(s).ok())
+        .ok_or_else(|| CryptoError::SessionError("corrupt session".into()))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, CryptoError> {
+    data.get(pos..pos + 4)
+        .and_then(|s| <[u8; 4]>::try_from(s).ok())
+        .map(u32::from_le_bytes)
+        .ok_or_else(|| CryptoError::SessionError("corrupt session".into()))
+}
+
+/// Serialize ratchet state (version 2).
+///
+/// Layout: `"RKDR" || version(1) || root(32) || sending(32) || receiving(32)
+/// || our_secret(32) || has_their(1) || their_public(32)? || ns(4) || nr(4)
+/// || pn(4) || pending(1) || skipped_count(4) || [dh(32) || n(4) || key(32)]*`.
 fn serialize_ratchet(state: &RatchetState) -> Vec<u8> {
-    let mut data = Vec::new();
+    let mut data = Vec::with_capacity(200 + state.skipped_keys.len() * 68);
+    data.extend_from_slice(RATCHET_MAGIC);
+    data.push(RATCHET_VERSION);
     data.extend_from_slice(&state.root_key);
     data.extend_from_slice(&state.sending_chain_key);
     data.extend_from_slice(&state.receiving_chain_key);
-    let our_len = u32::try_from(state.our_ratchet_secret.len())
-        .expect("ratchet secret length must fit in u32");
-    data.extend_from_slice(&our_len.to_le_bytes());
     data.extend_from_slice(&state.our_ratchet_secret);
-    let their_len = u32::try_from(state.their_ratchet_public.len())
-        .expect("ratchet public length must fit in u32");
-    data.extend_from_slice(&their_len.to_le_bytes());
-    data.extend_from_slice(&state.their_ratchet_public);
+    match state.their_ratchet_public {
+        Some(public) => {
+            data.push(1);
+            data.extend_from_slice(&public);
+        }
+        None => data.push(0),
+    }
     data.extend_from_slice(&state.send_counter.to_le_bytes());
     data.extend_from_slice(&state.recv_counter.to_le_bytes());
+    data.extend_from_slice(&state.previous_send_counter.to_le_bytes());
+    data.push(u8::from(state.pending_send_ratchet));
+    let skipped_len =
+        u32::try_from(state.skipped_keys.len()).expect("skipped key count is bounded");
+    data.extend_from_slice(&skipped_len.to_le_bytes());
+    for skipped in &state.skipped_keys {
+        data.extend_from_slice(&skipped.dh_public);
+        data.extend_from_slice(&skipped.counter.to_le_bytes());
+        data.extend_from_slice(&skipped.message_key);
+    }
     data
 }
 
 fn deserialize_ratchet(data: &[u8]) -> Result<RatchetState, CryptoError> {
-    if data.len() < 112 {
-        return Err(CryptoError::SessionError("invalid session data".into()));
+    if data.len() > RATCHET_MAGIC.len() && data.starts_with(RATCHET_MAGIC) {
+        match data[RATCHET_MAGIC.len()] {
+            RATCHET_VERSION => deserialize_ratchet_v2(&data[RATCHET_MAGIC.len() + 1..]),
+            v => Err(CryptoError::SessionError(format!(
+                "unsupported session version {v}"
+            ))),
+        }
+    } else {
+        Err(CryptoError::SessionError("unsupported session format".into()))
     }
+}
 
+fn deserialize_ratchet_v2(data: &[u8]) -> Result<RatchetState, CryptoError> {
     let mut pos = 0;
 
-    let mut root_key = [0u8; 32];
-    root_key.copy_from_slice(&data[pos..pos + 32]);
+    let root_key = read_array(data, pos)?;
     pos += 32;
-
-    let mut sending_chain_key = [0u8; 32];
-    sending_chain_key.copy_from_slice(&data[pos..pos + 32]);
+    let sending_chain_key = read_array(data, pos)?;
     pos += 32;
-
-    let mut receiving_chain_key = [0u8; 32];
-    receiving_chain_key.copy_from_slice(&data[pos..pos + 32]);
+    let receiving_chain_key = read_array(data, pos)?;
+    pos += 32;
+    let our_ratchet_secret = read_array(data, pos)?;
     pos += 32;
 
-    let our_len = usize::try_from(u32::from_le_bytes(
-        data[pos..pos + 4]
-            .try_into()
-            .map_err(|_| CryptoError::SessionError("corrupt session".into()))?,
-    )).map_err(|_| CryptoError::SessionError("ratchet secret length overflow".into()))?;
+    let has_their = *data
+        .get(pos)
+        .ok_or_else(|| CryptoError::SessionError("corrupt session".into()))?;
+    pos += 1;
+    let their_ratchet_public = if has_their == 1 {
+        let public = read_array(data, pos)?;
+        pos += 32;
+        Some(public)
+    } else {
+        None
+    };
+
+    let send_counter = read_u32(data, pos)?;
+    pos += 4;
+    let recv_counter = read_u32(data, pos)?;
     pos += 4;
-    let our_ratchet_secret = data[pos..pos + our_len].to_vec();
-    pos += our_len;
-
-    let their_len = usize::try_from(u32::from_le_bytes(
-        data[pos..pos + 4]
-            .try_into()
-            .map_err(|_| CryptoError::SessionError("corrupt session".into()))?,
-    )).map_err(|_| CryptoError::SessionError("ratchet public length overflow".into()))?;
+    let previous_send_counter = read_u32(data, pos)?;
     pos += 4;
-    let their_ratchet_public = data[pos..pos + their_len].to_vec();
-    pos += their_len;
-
-    let send_counter = u64::from_le_bytes(
-        data[pos..pos + 8]
-            .try_into()
-            .map_err(|_| CryptoError::SessionError("corrupt session".into()))?,
-    );
-    pos += 8;
-
-    let recv_counter = u64::from_le_bytes(
-        data[pos..pos + 8]
-            .try_into()
-            .map_err(|_| CryptoError::SessionError("corrupt session".into()))?,
-    );
+    let pending_send_ratchet = *data
+        .get(pos)
+        .ok_or_else(|| CryptoError::SessionError("corrupt session".into()))?
+        == 1;
+    pos += 1;
+
+    let skipped_len = read_u32(data, pos)? as usize;
+    pos += 4;
+    if skipped_len > MAX_SKIPPED_KEYS {
+        return Err(CryptoError::SessionError("corrupt session".into()));
+    }
+    let mut skipped_keys = Vec::with_capacity(skipped_len);
+    for _ in 0..skipped_len {
+        let dh_public = read_array(data, pos)?;
+        pos += 32;
+        let counter = read_u32(data, pos)?;
+        pos += 4;
+        let message_key = read_array(data, pos)?;
+        pos += 32;
+        skipped_keys.push(SkippedKey {
+            dh_public,
+            counter,
+            message_key,
+        });
+    }
 
     Ok(RatchetState {
         root_key,
@@ -493,5 +952,8 @@ fn deserialize_ratchet(data: &[u8]) -> Result<RatchetState, CryptoError> {
         their_ratchet_public,
         send_counter,
         recv_counter,
+        previous_send_counter,
+        pending_send_ratchet,
+        skipped_keys,
     })
 }
@@ -15,7 +15,25 @@ pub trait IdentityKeyStore: Send + Sync {
     fn is_trusted_identity(&self, address: &str, identity_key: &[u8]) -> Result<bool, CryptoError>;
 
     /// Save a remote identity key (TOFU — Trust On First Use).
+    ///
+    /// Fails with [`CryptoError::IdentityKeyChanged`] if a different key is
+    /// already stored for `address`; use [`replace_identity`](Self::replace_identity)
+    /// once the change has been accepted.
     fn save_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError>;
+
+    /// Get the stored identity key for a remote address.
+    fn get_identity(&self, address: &str) -> Result<Option<Vec<u8>>, CryptoError>;
+
+    /// Overwrite a remote identity key after a change was accepted.
+    ///
+    /// Clears the verified flag — the new key has not been compared out-of-band.
+    fn replace_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError>;
+
+    /// Check whether the stored identity key was verified out-of-band.
+    fn is_verified(&self, address: &str) -> Result<bool, CryptoError>;
+
+    /// Mark the stored identity key as verified (or unverified).
+    fn set_verified(&self, address: &str, verified: bool) -> Result<(), CryptoError>;
 }
 
 /// Storage trait for Signal Protocol prekeys.
@@ -40,6 +58,15 @@ pub trait PreKeyStore: Send + Sync {
         signed_prekey_id: u32,
         key_data: &[u8],
     ) -> Result<(), CryptoError>;
+
+    /// Remove a signed prekey whose grace window has expired.
+    fn remove_signed_prekey(&self, signed_prekey_id: u32) -> Result<(), CryptoError>;
+
+    /// IDs of all unconsumed one-time prekeys.
+    fn list_prekey_ids(&self) -> Result<Vec<u32>, CryptoError>;
+
+    /// IDs of all retained signed prekeys (current and within grace window).
+    fn list_signed_prekey_ids(&self) -> Result<Vec<u32>, CryptoError>;
 }
 
 /// Storage trait for Signal Protocol sessions.
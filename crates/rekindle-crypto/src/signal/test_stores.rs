@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 
 use crate::CryptoError;
@@ -10,6 +10,7 @@ pub struct MemoryIdentityStore {
     identity_public: Vec<u8>,
     registration_id: u32,
     trusted: Mutex<HashMap<String, Vec<u8>>>,
+    verified: Mutex<HashSet<String>>,
 }
 
 impl MemoryIdentityStore {
@@ -19,6 +20,7 @@ impl MemoryIdentityStore {
             identity_public,
             registration_id,
             trusted: Mutex::new(HashMap::new()),
+            verified: Mutex::new(HashSet::new()),
         }
     }
 }
@@ -41,7 +43,42 @@ impl IdentityKeyStore for MemoryIdentityStore {
     }
 
     fn save_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
-        self.trusted.lock().unwrap().insert(address.to_string(), identity_key.to_vec());
+        let mut trusted = self.trusted.lock().unwrap();
+        match trusted.get(address) {
+            Some(stored) if stored != identity_key => {
+                Err(CryptoError::IdentityKeyChanged(address.to_string()))
+            }
+            _ => {
+                trusted.insert(address.to_string(), identity_key.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    fn get_identity(&self, address: &str) -> Result<Option<Vec<u8>>, CryptoError> {
+        Ok(self.trusted.lock().unwrap().get(address).cloned())
+    }
+
+    fn replace_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
+        self.trusted
+            .lock()
+            .unwrap()
+            .insert(address.to_string(), identity_key.to_vec());
+        self.verified.lock().unwrap().remove(address);
+        Ok(())
+    }
+
+    fn is_verified(&self, address: &str) -> Result<bool, CryptoError> {
+        Ok(self.verified.lock().unwrap().contains(address))
+    }
+
+    fn set_verified(&self, address: &str, verified: bool) -> Result<(), CryptoError> {
+        let mut set = self.verified.lock().unwrap();
+        if verified {
+            set.insert(address.to_string());
+        } else {
+            set.remove(address);
+        }
         Ok(())
     }
 }
@@ -84,6 +121,19 @@ impl PreKeyStore for MemoryPreKeyStore {
         self.signed_prekeys.lock().unwrap().insert(signed_prekey_id, key_data.to_vec());
         Ok(())
     }
+
+    fn remove_signed_prekey(&self, signed_prekey_id: u32) -> Result<(), CryptoError> {
+        self.signed_prekeys.lock().unwrap().remove(&signed_prekey_id);
+        Ok(())
+    }
+
+    fn list_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        Ok(self.prekeys.lock().unwrap().keys().copied().collect())
+    }
+
+    fn list_signed_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        Ok(self.signed_prekeys.lock().unwrap().keys().copied().collect())
+    }
 }
 
 /// A SessionStore backed by a shared HashMap, allowing test code to inspect stored data.
@@ -113,17 +163,6 @@ impl SessionStore for SharedSessionStore {
     }
 }
 
-/// Extract the ephemeral public key from a serialized ratchet state.
-///
-/// Ratchet layout: root_key(32) + sending_chain(32) + receiving_chain(32)
-///   + our_ratchet_secret_len(4) + our_ratchet_secret(N) + ...
-/// After `establish_session`, `our_ratchet_secret` stores the ephemeral *public* key.
-fn extract_ephemeral_from_session(session_data: &[u8]) -> Vec<u8> {
-    let pos = 96; // skip root_key + sending + receiving chain keys
-    let len = u32::from_le_bytes(session_data[pos..pos + 4].try_into().unwrap()) as usize;
-    session_data[pos + 4..pos + 4 + len].to_vec()
-}
-
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -157,9 +196,6 @@ mod tests {
 
         let alice_x25519_pub = alice_id.to_x25519_public();
 
-        // Use shared session stores so we can inspect Alice's session data
-        let alice_sessions = Arc::new(Mutex::new(HashMap::<String, Vec<u8>>::new()));
-
         let alice_mgr = SignalSessionManager::new(
             Box::new(MemoryIdentityStore::new(
                 alice_id.to_x25519_secret().to_bytes().to_vec(),
@@ -167,7 +203,7 @@ mod tests {
                 1,
             )),
             Box::new(MemoryPreKeyStore::new()),
-            Box::new(SharedSessionStore(Arc::clone(&alice_sessions))),
+            Box::new(SharedSessionStore(Arc::new(Mutex::new(HashMap::new())))),
         );
 
         let bob_mgr = SignalSessionManager::new(
@@ -187,18 +223,14 @@ mod tests {
         let bob_bundle = bob_mgr.generate_prekey_bundle(1, Some(100)).unwrap();
 
         // Step 2: Alice establishes session as initiator
-        alice_mgr.establish_session(&bob_addr, &bob_bundle).unwrap();
+        let init = alice_mgr.establish_session(&bob_addr, &bob_bundle).unwrap();
 
-        // Step 3: Extract Alice's ephemeral key from her stored session
-        let alice_session_data = alice_sessions.lock().unwrap().get(&bob_addr).unwrap().clone();
-        let alice_ephemeral = extract_ephemeral_from_session(&alice_session_data);
-
-        // Step 4: Bob responds to session as responder
+        // Step 3: Bob responds to session as responder
         bob_mgr
             .respond_to_session(
                 &alice_addr,
                 alice_x25519_pub.as_bytes(),
-                &alice_ephemeral,
+                &init.ephemeral_public_key,
                 1,
                 Some(100),
             )
@@ -265,10 +297,10 @@ mod tests {
 
         let ciphertext = alice_mgr.encrypt(&bob_addr, b"don't tamper with me").unwrap();
 
-        // Flip a byte in the ciphertext portion (after 8-byte counter + 12-byte nonce)
+        // Flip a byte in the ciphertext portion (after the 41-byte header)
         let mut tampered = ciphertext.clone();
-        if tampered.len() > 20 {
-            tampered[20] ^= 0xFF;
+        if tampered.len() > 41 {
+            tampered[41] ^= 0xFF;
         }
 
         let result = bob_mgr.decrypt(&alice_addr, &tampered);
@@ -334,4 +366,279 @@ mod tests {
         let result = alice_mgr.encrypt("nonexistent_peer", b"hello");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn tampered_header_fails_without_desync() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let ciphertext = alice_mgr.encrypt(&bob_addr, b"header is authenticated").unwrap();
+
+        // Flip a byte of the DH ratchet key in the header
+        let mut tampered = ciphertext.clone();
+        tampered[5] ^= 0xFF;
+        assert!(bob_mgr.decrypt(&alice_addr, &tampered).is_err());
+
+        // The failed attempt must not have advanced Bob's state
+        assert_eq!(
+            bob_mgr.decrypt(&alice_addr, &ciphertext).unwrap(),
+            b"header is authenticated"
+        );
+    }
+
+    #[test]
+    fn out_of_order_messages_decrypt() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let msg1 = alice_mgr.encrypt(&bob_addr, b"one").unwrap();
+        let msg2 = alice_mgr.encrypt(&bob_addr, b"two").unwrap();
+        let msg3 = alice_mgr.encrypt(&bob_addr, b"three").unwrap();
+
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &msg3).unwrap(), b"three");
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &msg1).unwrap(), b"one");
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &msg2).unwrap(), b"two");
+    }
+
+    #[test]
+    fn dropped_message_does_not_break_session() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let _lost = alice_mgr.encrypt(&bob_addr, b"never delivered").unwrap();
+        let msg2 = alice_mgr.encrypt(&bob_addr, b"delivered").unwrap();
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &msg2).unwrap(), b"delivered");
+
+        let reply = bob_mgr.encrypt(&alice_addr, b"reply").unwrap();
+        assert_eq!(alice_mgr.decrypt(&bob_addr, &reply).unwrap(), b"reply");
+    }
+
+    #[test]
+    fn replayed_message_rejected() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let ct = alice_mgr.encrypt(&bob_addr, b"once only").unwrap();
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &ct).unwrap(), b"once only");
+        assert!(bob_mgr.decrypt(&alice_addr, &ct).is_err());
+    }
+
+    #[test]
+    fn dh_ratchet_rotates_keys_each_turn() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let a1 = alice_mgr.encrypt(&bob_addr, b"a1").unwrap();
+        bob_mgr.decrypt(&alice_addr, &a1).unwrap();
+        let b1 = bob_mgr.encrypt(&alice_addr, b"b1").unwrap();
+        alice_mgr.decrypt(&bob_addr, &b1).unwrap();
+        let a2 = alice_mgr.encrypt(&bob_addr, b"a2").unwrap();
+        bob_mgr.decrypt(&alice_addr, &a2).unwrap();
+
+        // Header bytes 1..33 carry the sender's DH ratchet public key
+        assert_ne!(a1[1..33], a2[1..33]);
+    }
+
+    #[test]
+    fn messages_from_previous_chain_decrypt_after_ratchet() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let a1 = alice_mgr.encrypt(&bob_addr, b"a1").unwrap();
+        let a2_delayed = alice_mgr.encrypt(&bob_addr, b"a2").unwrap();
+        bob_mgr.decrypt(&alice_addr, &a1).unwrap();
+
+        let b1 = bob_mgr.encrypt(&alice_addr, b"b1").unwrap();
+        alice_mgr.decrypt(&bob_addr, &b1).unwrap();
+        let a3 = alice_mgr.encrypt(&bob_addr, b"a3").unwrap();
+
+        // a3 starts a new chain; a2 from the old chain arrives afterwards
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &a3).unwrap(), b"a3");
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &a2_delayed).unwrap(), b"a2");
+    }
+
+    #[test]
+    fn responder_can_send_first() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let b1 = bob_mgr.encrypt(&alice_addr, b"b1").unwrap();
+        let a1 = alice_mgr.encrypt(&bob_addr, b"a1").unwrap();
+        assert_eq!(alice_mgr.decrypt(&bob_addr, &b1).unwrap(), b"b1");
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &a1).unwrap(), b"a1");
+
+        let b2 = bob_mgr.encrypt(&alice_addr, b"b2").unwrap();
+        assert_eq!(alice_mgr.decrypt(&bob_addr, &b2).unwrap(), b"b2");
+    }
+
+    #[test]
+    fn session_against_rotated_signed_prekey_within_grace() {
+        let alice_id = Identity::generate();
+        let bob_id = Identity::generate();
+        let alice_mgr = make_manager(&alice_id);
+        let bob_mgr = make_manager(&bob_id);
+        let alice_addr = hex::encode(alice_id.public_key_bytes());
+        let bob_addr = hex::encode(bob_id.public_key_bytes());
+
+        // Bob publishes signed prekey 1 with one-time prekey 10, Alice fetches it
+        bob_mgr.generate_signed_prekey(1).unwrap();
+        bob_mgr.generate_one_time_prekeys(&[10, 11]).unwrap();
+        let old_bundle = bob_mgr.prekey_bundle(1, Some(10)).unwrap();
+        assert_eq!(old_bundle.signed_prekey_id, 1);
+        assert_eq!(old_bundle.one_time_prekey_id, Some(10));
+
+        // Bob rotates before Alice's initiation reaches him
+        bob_mgr.generate_signed_prekey(2).unwrap();
+        let mut ids = bob_mgr.signed_prekey_ids().unwrap();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 2]);
+
+        let init = alice_mgr.establish_session(&bob_addr, &old_bundle).unwrap();
+        assert_eq!(init.signed_prekey_id, 1);
+        assert_eq!(init.one_time_prekey_id, Some(10));
+
+        bob_mgr
+            .respond_to_session(
+                &alice_addr,
+                alice_id.to_x25519_public().as_bytes(),
+                &init.ephemeral_public_key,
+                init.signed_prekey_id,
+                init.one_time_prekey_id,
+            )
+            .unwrap();
+
+        // The one-time prekey is consumed; the other remains in the pool
+        assert_eq!(bob_mgr.one_time_prekey_ids().unwrap(), vec![11]);
+
+        let ct = alice_mgr.encrypt(&bob_addr, b"after rotation").unwrap();
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &ct).unwrap(), b"after rotation");
+
+        // Once the grace window is over the old signed prekey is purged
+        bob_mgr.remove_signed_prekey(1).unwrap();
+        assert!(bob_mgr.prekey_bundle(1, None).is_err());
+    }
+
+    #[test]
+    fn second_initiator_on_same_one_time_prekey_is_told_to_retry() {
+        let alice_id = Identity::generate();
+        let carol_id = Identity::generate();
+        let bob_id = Identity::generate();
+        let alice_mgr = make_manager(&alice_id);
+        let carol_mgr = make_manager(&carol_id);
+        let bob_mgr = make_manager(&bob_id);
+        let alice_addr = hex::encode(alice_id.public_key_bytes());
+        let carol_addr = hex::encode(carol_id.public_key_bytes());
+        let bob_addr = hex::encode(bob_id.public_key_bytes());
+
+        // Alice and Carol both fetch Bob's bundle while pool key 10 is still published
+        bob_mgr.generate_signed_prekey(1).unwrap();
+        bob_mgr.generate_one_time_prekeys(&[10]).unwrap();
+        let bundle = bob_mgr.prekey_bundle(1, Some(10)).unwrap();
+        let alice_init = alice_mgr.establish_session(&bob_addr, &bundle).unwrap();
+        let carol_init = carol_mgr.establish_session(&bob_addr, &bundle).unwrap();
+
+        bob_mgr
+            .respond_to_session(
+                &alice_addr,
+                alice_id.to_x25519_public().as_bytes(),
+                &alice_init.ephemeral_public_key,
+                alice_init.signed_prekey_id,
+                alice_init.one_time_prekey_id,
+            )
+            .unwrap();
+
+        // Carol's init names the key Alice used up: distinct error, no session stored
+        let err = bob_mgr
+            .respond_to_session(
+                &carol_addr,
+                carol_id.to_x25519_public().as_bytes(),
+                &carol_init.ephemeral_public_key,
+                carol_init.signed_prekey_id,
+                carol_init.one_time_prekey_id,
+            )
+            .unwrap_err();
+        assert!(matches!(err, CryptoError::OneTimePreKeyConsumed(10)));
+        assert!(!bob_mgr.has_session(&carol_addr).unwrap());
+
+        // Carol retries from the signed prekey alone
+        let retry_bundle = bob_mgr.prekey_bundle(1, None).unwrap();
+        let retry = carol_mgr.establish_session(&bob_addr, &retry_bundle).unwrap();
+        assert_eq!(retry.one_time_prekey_id, None);
+        bob_mgr
+            .respond_to_session(
+                &carol_addr,
+                carol_id.to_x25519_public().as_bytes(),
+                &retry.ephemeral_public_key,
+                retry.signed_prekey_id,
+                retry.one_time_prekey_id,
+            )
+            .unwrap();
+
+        let ct = carol_mgr.encrypt(&bob_addr, b"second try").unwrap();
+        assert_eq!(bob_mgr.decrypt(&carol_addr, &ct).unwrap(), b"second try");
+        let ct = alice_mgr.encrypt(&bob_addr, b"first in").unwrap();
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &ct).unwrap(), b"first in");
+    }
+
+    #[test]
+    fn prekey_bundle_does_not_regenerate_keys() {
+        let identity = Identity::generate();
+        let mgr = make_manager(&identity);
+
+        mgr.generate_signed_prekey(5).unwrap();
+        let published = mgr.generate_one_time_prekeys(&[42]).unwrap();
+
+        let first = mgr.prekey_bundle(5, Some(42)).unwrap();
+        let second = mgr.prekey_bundle(5, Some(42)).unwrap();
+        assert_eq!(first.signed_prekey, second.signed_prekey);
+        assert_eq!(first.one_time_prekey, Some(published[0].public_key.clone()));
+        assert!(mgr.prekey_bundle(5, Some(43)).is_err());
+    }
+
+    #[test]
+    fn legacy_bundle_defaults_to_prekey_id_one() {
+        let json = br#"{"identity_key":[],"signed_prekey":[],"signed_prekey_signature":[],"one_time_prekey":[1],"registration_id":7}"#;
+        let bundle: crate::signal::PreKeyBundle = serde_json::from_slice(json).unwrap();
+        assert_eq!(bundle.signed_prekey_id, 1);
+        assert_eq!(bundle.effective_one_time_prekey_id(), Some(1));
+    }
+
+    #[test]
+    fn changed_identity_key_is_rejected() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        // An attacker publishes a bundle for Bob's address under their own identity
+        let mallory_mgr = make_manager(&Identity::generate());
+        let forged = mallory_mgr.generate_prekey_bundle(1, Some(1)).unwrap();
+        let result = alice_mgr.establish_session(&bob_addr, &forged);
+        assert!(matches!(result, Err(CryptoError::IdentityKeyChanged(ref addr)) if *addr == bob_addr));
+
+        // The existing session is untouched
+        let ct = alice_mgr.encrypt(&bob_addr, b"still bob").unwrap();
+        assert_eq!(bob_mgr.decrypt(&alice_addr, &ct).unwrap(), b"still bob");
+
+        // After explicitly accepting the change, establishment succeeds
+        alice_mgr
+            .accept_identity_change(&bob_addr, &forged.identity_key)
+            .unwrap();
+        assert!(alice_mgr.establish_session(&bob_addr, &forged).is_ok());
+    }
+
+    #[test]
+    fn accepting_identity_change_clears_verification() {
+        let (alice_mgr, _bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        assert!(!alice_mgr.is_verified(&bob_addr).unwrap());
+        alice_mgr.set_verified(&bob_addr, true).unwrap();
+        assert!(alice_mgr.is_verified(&bob_addr).unwrap());
+
+        alice_mgr.accept_identity_change(&bob_addr, &[7u8; 32]).unwrap();
+        assert!(!alice_mgr.is_verified(&bob_addr).unwrap());
+
+        // Verifying a peer we have never seen is an error
+        assert!(alice_mgr.set_verified(&alice_addr, true).is_err());
+    }
+
+    #[test]
+    fn safety_numbers_match_across_peers() {
+        let (alice_mgr, bob_mgr, alice_addr, bob_addr) = establish_session_pair();
+
+        let alice_view = alice_mgr.safety_number(&alice_addr, &bob_addr).unwrap();
+        let bob_view = bob_mgr.safety_number(&bob_addr, &alice_addr).unwrap();
+        assert_eq!(alice_view.displayable(), bob_view.displayable());
+        assert!(alice_view.matches_scanned(&bob_view.qr_payload()).unwrap());
+    }
 }
@@ -55,6 +55,18 @@ impl GameDatabase {
         self.by_process.get(&process_name.to_lowercase())
     }
 
+    /// Every game in the database once, sorted by name.
+    pub fn games(&self) -> Vec<&GameEntry> {
+        let mut games: Vec<&GameEntry> = Vec::new();
+        for entry in self.by_process.values() {
+            if !games.iter().any(|g| g.id == entry.id) {
+                games.push(entry);
+            }
+        }
+        games.sort_by(|a, b| a.name.cmp(&b.name));
+        games
+    }
+
     /// Get the number of games in the database.
     pub fn game_count(&self) -> usize {
         let unique: std::collections::HashSet<u32> = self.by_process.values().map(|e| e.id).collect();
@@ -94,4 +106,18 @@ mod tests {
         let entry = db.lookup_by_process("CS2.EXE").unwrap();
         assert_eq!(entry.id, 4181);
     }
+
+    #[test]
+    fn games_lists_each_game_once() {
+        let json = r#"{
+            "games": [
+                { "id": 2, "name": "Quake", "process_names": ["quake.exe", "quake"], "icon": null },
+                { "id": 1, "name": "Doom", "process_names": ["doom.exe"], "icon": null }
+            ]
+        }"#;
+
+        let db = GameDatabase::from_json(json).unwrap();
+        let names: Vec<&str> = db.games().iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["Doom", "Quake"]);
+    }
 }
@@ -2,7 +2,7 @@ use std::path::Path;
 
 const SCHEMAS: &[&str] = &[
     "message", "identity", "presence", "community", "friend", "voice",
-    "account", "conversation",
+    "account", "conversation", "group",
 ];
 
 /// Find the capnp binary, checking standard installation locations on Windows
@@ -89,6 +89,7 @@ fn main() {
                 .file("../../schemas/voice.capnp")
                 .file("../../schemas/account.capnp")
                 .file("../../schemas/conversation.capnp")
+                .file("../../schemas/group.capnp")
                 .run()
                 .expect("Cap'n Proto schema compilation failed");
 
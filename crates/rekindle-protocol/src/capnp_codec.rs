@@ -28,6 +28,7 @@ pub mod message {
     use super::{capnp_err, text_to_string, ProtocolError};
     use crate::message_capnp;
     use crate::messaging::envelope::{GameInfo, MessageEnvelope};
+    use crate::transfer::FileAttachment;
 
     /// Encode a `MessageEnvelope` into packed Cap'n Proto bytes.
     pub fn encode_envelope(env: &MessageEnvelope) -> Vec<u8> {
@@ -39,6 +40,9 @@ pub mod message {
             root.set_nonce(&env.nonce);
             root.set_payload(&env.payload);
             root.set_signature(&env.signature);
+            if !env.sender_device.is_empty() {
+                root.set_sender_device(&env.sender_device);
+            }
         }
         let mut output = Vec::new();
         capnp::serialize_packed::write_message(&mut output, &builder).expect("write to Vec never fails");
@@ -63,11 +67,20 @@ pub mod message {
             nonce: root.get_nonce().map_err(|e| capnp_err(&e))?.to_vec(),
             payload: root.get_payload().map_err(|e| capnp_err(&e))?.to_vec(),
             signature: root.get_signature().map_err(|e| capnp_err(&e))?.to_vec(),
+            sender_device: if root.has_sender_device() {
+                root.get_sender_device().map_err(|e| capnp_err(&e))?.to_vec()
+            } else {
+                Vec::new()
+            },
         })
     }
 
-    /// Encode a chat message body + optional reply-to nonce.
-    pub fn encode_chat_message(body: &str, reply_to: Option<&[u8]>) -> Vec<u8> {
+    /// Encode a chat message body, optional reply-to nonce and attachments.
+    pub fn encode_chat_message(
+        body: &str,
+        reply_to: Option<&[u8]>,
+        attachments: &[FileAttachment],
+    ) -> Vec<u8> {
         let mut builder = capnp::message::Builder::new_default();
         {
             let mut root = builder.init_root::<message_capnp::chat_message::Builder<'_>>();
@@ -75,15 +88,26 @@ pub mod message {
             if let Some(rt) = reply_to {
                 root.set_reply_to(rt);
             }
-            // attachments left empty for now
+            let mut list = root.init_attachments(u32::try_from(attachments.len()).unwrap_or(u32::MAX));
+            for (i, attachment) in attachments.iter().enumerate() {
+                let mut a = list.reborrow().get(u32::try_from(i).unwrap_or(u32::MAX));
+                a.set_name(attachment.name.as_str());
+                a.set_mime_type(attachment.mime_type.as_str());
+                a.set_size(attachment.size);
+                a.set_dht_key(attachment.dht_key.as_bytes());
+                a.set_checksum(&attachment.checksum);
+                a.set_file_key(&attachment.file_key);
+            }
         }
         let mut output = Vec::new();
         capnp::serialize_packed::write_message(&mut output, &builder).expect("write to Vec never fails");
         output
     }
 
-    /// Decode packed bytes into (body, optional `reply_to` nonce).
-    pub fn decode_chat_message(data: &[u8]) -> Result<(String, Option<Vec<u8>>), ProtocolError> {
+    /// Decode packed bytes into (body, optional `reply_to` nonce, attachments).
+    pub fn decode_chat_message(
+        data: &[u8],
+    ) -> Result<(String, Option<Vec<u8>>, Vec<FileAttachment>), ProtocolError> {
         let reader = capnp::serialize_packed::read_message(
             data,
             capnp::message::ReaderOptions::new(),
@@ -101,7 +125,23 @@ pub mod message {
             None
         };
 
-        Ok((body, reply_to))
+        let list = root.get_attachments().map_err(|e| capnp_err(&e))?;
+        let mut attachments = Vec::with_capacity(list.len() as usize);
+        for i in 0..list.len() {
+            let a = list.get(i);
+            let dht_key = String::from_utf8(a.get_dht_key().map_err(|e| capnp_err(&e))?.to_vec())
+                .map_err(|e| ProtocolError::Deserialization(format!("invalid UTF-8 DHT key: {e}")))?;
+            attachments.push(FileAttachment {
+                name: text_to_string(a.get_name().map_err(|e| capnp_err(&e))?)?,
+                mime_type: text_to_string(a.get_mime_type().map_err(|e| capnp_err(&e))?)?,
+                size: a.get_size(),
+                dht_key,
+                checksum: a.get_checksum().map_err(|e| capnp_err(&e))?.to_vec(),
+                file_key: a.get_file_key().map_err(|e| capnp_err(&e))?.to_vec(),
+            });
+        }
+
+        Ok((body, reply_to, attachments))
     }
 
     /// Encode a `GameInfo` into packed Cap'n Proto presence `GameStatus` bytes.
@@ -616,6 +656,7 @@ pub mod community {
             created_at: root.get_created_at(),
             owner_key: owner_key.to_string(),
             last_refreshed: 0,
+            listing_key: None,
         };
 
         // Channels
@@ -667,6 +708,7 @@ pub mod community {
                     None
                 },
                 permission_overwrites,
+                retention_secs: 0,
             });
         }
 
@@ -700,6 +742,7 @@ pub mod community {
             created_at: 0,
             owner_key: String::new(),
             last_refreshed: 0,
+            listing_key: None,
         };
         encode_community(&meta, channels, &[])
     }
@@ -713,6 +756,7 @@ pub mod community {
             created_at: 0,
             owner_key: String::new(),
             last_refreshed: 0,
+            listing_key: None,
         };
         encode_community(&meta, &[], roles)
     }
@@ -830,10 +874,39 @@ pub mod account {
         pub chat_list_keypair: Option<String>,
         /// Owner keypair string for the invitation list `DHTShortArray` (persisted for re-open).
         pub invitation_list_keypair: Option<String>,
+        /// Devices linked to this identity besides the primary one.
+        pub devices: Vec<DeviceEntry>,
+        /// Communities we joined or host.
+        pub communities: Vec<CommunityEntry>,
+        /// Friend group names in display order.
+        pub friend_groups: Vec<String>,
     }
 
-    /// Domain struct for a contact entry in the account's contact list.
+    /// Domain struct for a linked device listed in the account header.
     #[derive(Debug, Clone)]
+    pub struct DeviceEntry {
+        pub device_key: Vec<u8>,
+        pub name: String,
+        pub mailbox_key: String,
+        pub added_at: u64,
+        /// JSON-encoded `DeviceCertificate` signed by the identity key.
+        pub certificate: Vec<u8>,
+    }
+
+    /// Domain struct for a community membership listed in the account header.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct CommunityEntry {
+        /// Community DHT record key (doubles as the community ID).
+        pub community_id: String,
+        pub name: String,
+        pub joined_at: u64,
+        /// Owner keypair string of the community record; set only for
+        /// communities we host.
+        pub owner_keypair: Option<String>,
+    }
+
+    /// Domain struct for a contact entry in the account's contact list.
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct ContactEntry {
         pub public_key: Vec<u8>,
         pub display_name: String,
@@ -843,6 +916,12 @@ pub mod account {
         pub remote_conversation_key: String,
         pub added_at: u64,
         pub updated_at: u64,
+        /// Owner keypair string of our conversation record with this contact.
+        pub local_conversation_keypair: Option<String>,
+        /// The contact's profile DHT record key.
+        pub profile_key: String,
+        /// The contact's mailbox DHT record key.
+        pub mailbox_key: String,
     }
 
     /// Domain struct for a chat entry in the account's chat list.
@@ -879,6 +958,29 @@ pub mod account {
             if let Some(ref kp) = header.invitation_list_keypair {
                 root.set_invitation_list_keypair(kp);
             }
+            let mut list = root.reborrow().init_devices(u32::try_from(header.devices.len()).unwrap_or(u32::MAX));
+            for (i, device) in header.devices.iter().enumerate() {
+                let mut d = list.reborrow().get(u32::try_from(i).unwrap_or(u32::MAX));
+                d.set_device_key(&device.device_key);
+                d.set_name(device.name.as_str());
+                d.set_mailbox_key(device.mailbox_key.as_str());
+                d.set_added_at(device.added_at);
+                d.set_certificate(&device.certificate);
+            }
+            let mut list = root.reborrow().init_communities(u32::try_from(header.communities.len()).unwrap_or(u32::MAX));
+            for (i, community) in header.communities.iter().enumerate() {
+                let mut c = list.reborrow().get(u32::try_from(i).unwrap_or(u32::MAX));
+                c.set_community_id(community.community_id.as_str());
+                c.set_name(community.name.as_str());
+                c.set_joined_at(community.joined_at);
+                if let Some(ref kp) = community.owner_keypair {
+                    c.set_owner_keypair(kp);
+                }
+            }
+            let mut groups = root.init_friend_groups(u32::try_from(header.friend_groups.len()).unwrap_or(u32::MAX));
+            for (i, name) in header.friend_groups.iter().enumerate() {
+                groups.set(u32::try_from(i).unwrap_or(u32::MAX), name.as_str());
+            }
         }
         let mut output = Vec::new();
         capnp::serialize_packed::write_message(&mut output, &builder).expect("write to Vec never fails");
@@ -931,9 +1033,75 @@ pub mod account {
             } else {
                 None
             },
+            devices: decode_device_entries(&root)?,
+            communities: decode_community_entries(&root)?,
+            friend_groups: decode_friend_groups(&root)?,
         })
     }
 
+    /// Linked devices from a header; absent in headers written before devices existed.
+    fn decode_device_entries(
+        root: &account_capnp::account_header::Reader<'_>,
+    ) -> Result<Vec<DeviceEntry>, ProtocolError> {
+        if !root.has_devices() {
+            return Ok(Vec::new());
+        }
+        let list = root.get_devices().map_err(|e| capnp_err(&e))?;
+        let mut devices = Vec::with_capacity(list.len() as usize);
+        for i in 0..list.len() {
+            let d = list.get(i);
+            devices.push(DeviceEntry {
+                device_key: d.get_device_key().map_err(|e| capnp_err(&e))?.to_vec(),
+                name: text_to_string(d.get_name().map_err(|e| capnp_err(&e))?)?,
+                mailbox_key: text_to_string(d.get_mailbox_key().map_err(|e| capnp_err(&e))?)?,
+                added_at: d.get_added_at(),
+                certificate: d.get_certificate().map_err(|e| capnp_err(&e))?.to_vec(),
+            });
+        }
+        Ok(devices)
+    }
+
+    /// Community memberships from a header; absent in older headers.
+    fn decode_community_entries(
+        root: &account_capnp::account_header::Reader<'_>,
+    ) -> Result<Vec<CommunityEntry>, ProtocolError> {
+        if !root.has_communities() {
+            return Ok(Vec::new());
+        }
+        let list = root.get_communities().map_err(|e| capnp_err(&e))?;
+        let mut communities = Vec::with_capacity(list.len() as usize);
+        for i in 0..list.len() {
+            let c = list.get(i);
+            communities.push(CommunityEntry {
+                community_id: text_to_string(c.get_community_id().map_err(|e| capnp_err(&e))?)?,
+                name: text_to_string(c.get_name().map_err(|e| capnp_err(&e))?)?,
+                joined_at: c.get_joined_at(),
+                owner_keypair: if c.has_owner_keypair() {
+                    let s = text_to_string(c.get_owner_keypair().map_err(|e| capnp_err(&e))?)?;
+                    if s.is_empty() { None } else { Some(s) }
+                } else {
+                    None
+                },
+            });
+        }
+        Ok(communities)
+    }
+
+    /// Friend group names from a header; absent in older headers.
+    fn decode_friend_groups(
+        root: &account_capnp::account_header::Reader<'_>,
+    ) -> Result<Vec<String>, ProtocolError> {
+        if !root.has_friend_groups() {
+            return Ok(Vec::new());
+        }
+        let list = root.get_friend_groups().map_err(|e| capnp_err(&e))?;
+        let mut groups = Vec::with_capacity(list.len() as usize);
+        for i in 0..list.len() {
+            groups.push(text_to_string(list.get(i).map_err(|e| capnp_err(&e))?)?);
+        }
+        Ok(groups)
+    }
+
     pub fn encode_contact_entry(entry: &ContactEntry) -> Vec<u8> {
         let mut builder = capnp::message::Builder::new_default();
         {
@@ -946,6 +1114,11 @@ pub mod account {
             root.set_remote_conversation_key(&entry.remote_conversation_key);
             root.set_added_at(entry.added_at);
             root.set_updated_at(entry.updated_at);
+            if let Some(ref kp) = entry.local_conversation_keypair {
+                root.set_local_conversation_keypair(kp);
+            }
+            root.set_profile_key(&entry.profile_key);
+            root.set_mailbox_key(&entry.mailbox_key);
         }
         let mut output = Vec::new();
         capnp::serialize_packed::write_message(&mut output, &builder).expect("write to Vec never fails");
@@ -984,6 +1157,22 @@ pub mod account {
             },
             added_at: root.get_added_at(),
             updated_at: root.get_updated_at(),
+            local_conversation_keypair: if root.has_local_conversation_keypair() {
+                let s = text_to_string(root.get_local_conversation_keypair().map_err(|e| capnp_err(&e))?)?;
+                if s.is_empty() { None } else { Some(s) }
+            } else {
+                None
+            },
+            profile_key: if root.has_profile_key() {
+                text_to_string(root.get_profile_key().map_err(|e| capnp_err(&e))?)?
+            } else {
+                String::new()
+            },
+            mailbox_key: if root.has_mailbox_key() {
+                text_to_string(root.get_mailbox_key().map_err(|e| capnp_err(&e))?)?
+            } else {
+                String::new()
+            },
         })
     }
 
@@ -1184,12 +1373,99 @@ pub mod conversation {
     }
 }
 
+// ---------------------------------------------------------------------------
+// group.capnp — GroupHeader, GroupMemberEntry
+// ---------------------------------------------------------------------------
+pub mod group {
+    use super::{capnp_err, text_to_string, ProtocolError};
+    use crate::group_capnp;
+
+    /// Domain struct for the roster stored in a group conversation DHT record.
+    #[derive(Debug, Clone)]
+    pub struct GroupHeader {
+        pub group_id: String,
+        pub name: String,
+        pub created_by: Vec<u8>,
+        pub members: Vec<GroupMemberEntry>,
+        pub created_at: u64,
+        pub updated_at: u64,
+    }
+
+    /// Domain struct for one member listed in a group header.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct GroupMemberEntry {
+        pub public_key: Vec<u8>,
+        pub display_name: String,
+        pub profile_key: String,
+        pub mailbox_key: String,
+        pub joined_at: u64,
+    }
+
+    pub fn encode_group_header(header: &GroupHeader) -> Vec<u8> {
+        let mut builder = capnp::message::Builder::new_default();
+        {
+            let mut root = builder.init_root::<group_capnp::group_header::Builder<'_>>();
+            root.set_group_id(&header.group_id);
+            root.set_name(&header.name);
+            root.set_created_by(&header.created_by);
+            root.set_created_at(header.created_at);
+            root.set_updated_at(header.updated_at);
+            let mut list = root.init_members(u32::try_from(header.members.len()).unwrap_or(u32::MAX));
+            for (i, member) in header.members.iter().enumerate() {
+                let mut m = list.reborrow().get(u32::try_from(i).unwrap_or(u32::MAX));
+                m.set_public_key(&member.public_key);
+                m.set_display_name(member.display_name.as_str());
+                m.set_profile_key(member.profile_key.as_str());
+                m.set_mailbox_key(member.mailbox_key.as_str());
+                m.set_joined_at(member.joined_at);
+            }
+        }
+        let mut output = Vec::new();
+        capnp::serialize_packed::write_message(&mut output, &builder).expect("write to Vec never fails");
+        output
+    }
+
+    pub fn decode_group_header(data: &[u8]) -> Result<GroupHeader, ProtocolError> {
+        let reader = capnp::serialize_packed::read_message(
+            data,
+            capnp::message::ReaderOptions::new(),
+        )
+        .map_err(|e| capnp_err(&e))?;
+
+        let root = reader
+            .get_root::<group_capnp::group_header::Reader<'_>>()
+            .map_err(|e| capnp_err(&e))?;
+
+        let list = root.get_members().map_err(|e| capnp_err(&e))?;
+        let mut members = Vec::with_capacity(list.len() as usize);
+        for i in 0..list.len() {
+            let m = list.get(i);
+            members.push(GroupMemberEntry {
+                public_key: m.get_public_key().map_err(|e| capnp_err(&e))?.to_vec(),
+                display_name: text_to_string(m.get_display_name().map_err(|e| capnp_err(&e))?)?,
+                profile_key: text_to_string(m.get_profile_key().map_err(|e| capnp_err(&e))?)?,
+                mailbox_key: text_to_string(m.get_mailbox_key().map_err(|e| capnp_err(&e))?)?,
+                joined_at: m.get_joined_at(),
+            });
+        }
+
+        Ok(GroupHeader {
+            group_id: text_to_string(root.get_group_id().map_err(|e| capnp_err(&e))?)?,
+            name: text_to_string(root.get_name().map_err(|e| capnp_err(&e))?)?,
+            created_by: root.get_created_by().map_err(|e| capnp_err(&e))?.to_vec(),
+            members,
+            created_at: root.get_created_at(),
+            updated_at: root.get_updated_at(),
+        })
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
 #[cfg(test)]
 mod tests {
-    use super::{account, conversation, friend, identity, message, presence, voice};
+    use super::{account, conversation, friend, group, identity, message, presence, voice};
 
     #[test]
     fn round_trip_message_envelope() {
@@ -1201,6 +1477,7 @@ mod tests {
             nonce: vec![42u8; 16],
             payload: b"encrypted content".to_vec(),
             signature: vec![99u8; 64],
+            sender_device: Vec::new(),
         };
 
         let encoded = message::encode_envelope(&env);
@@ -1211,23 +1488,64 @@ mod tests {
         assert_eq!(env.nonce, decoded.nonce);
         assert_eq!(env.payload, decoded.payload);
         assert_eq!(env.signature, decoded.signature);
+        assert!(decoded.sender_device.is_empty());
+    }
+
+    #[test]
+    fn round_trip_message_envelope_from_linked_device() {
+        use crate::messaging::envelope::MessageEnvelope;
+
+        let env = MessageEnvelope {
+            sender_key: vec![1u8; 32],
+            timestamp: 1234567890,
+            nonce: vec![42u8; 16],
+            payload: b"encrypted content".to_vec(),
+            signature: vec![99u8; 64],
+            sender_device: vec![7u8; 32],
+        };
+
+        let decoded = message::decode_envelope(&message::encode_envelope(&env)).unwrap();
+        assert_eq!(decoded.sender_device, vec![7u8; 32]);
     }
 
     #[test]
     fn round_trip_chat_message() {
-        let (body, reply) = message::decode_chat_message(
-            &message::encode_chat_message("hello world", Some(&[1, 2, 3])),
+        let (body, reply, attachments) = message::decode_chat_message(
+            &message::encode_chat_message("hello world", Some(&[1, 2, 3]), &[]),
         )
         .unwrap();
         assert_eq!(body, "hello world");
         assert_eq!(reply, Some(vec![1, 2, 3]));
+        assert!(attachments.is_empty());
 
-        let (body2, reply2) =
-            message::decode_chat_message(&message::encode_chat_message("no reply", None)).unwrap();
+        let (body2, reply2, _) =
+            message::decode_chat_message(&message::encode_chat_message("no reply", None, &[])).unwrap();
         assert_eq!(body2, "no reply");
         assert_eq!(reply2, None);
     }
 
+    #[test]
+    fn round_trip_chat_message_attachments() {
+        use crate::transfer::FileAttachment;
+
+        let attachment = FileAttachment {
+            name: "notes.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            size: 70_000,
+            dht_key: "VLD0:abc".to_string(),
+            checksum: vec![7; 32],
+            file_key: vec![9; 32],
+        };
+        let (_, _, attachments) = message::decode_chat_message(&message::encode_chat_message(
+            "",
+            None,
+            std::slice::from_ref(&attachment),
+        ))
+        .unwrap();
+        assert_eq!(attachments, vec![attachment]);
+        assert_eq!(attachments[0].chunk_count(), 3);
+    }
+
     #[test]
     fn round_trip_presence_update() {
         use crate::messaging::envelope::GameInfo;
@@ -1411,6 +1729,28 @@ mod tests {
             contact_list_keypair: Some("VLD0:contacts_kp".to_string()),
             chat_list_keypair: Some("VLD0:chats_kp".to_string()),
             invitation_list_keypair: None,
+            devices: vec![account::DeviceEntry {
+                device_key: vec![0xBB; 32],
+                name: "Laptop".to_string(),
+                mailbox_key: "VLD0:mailbox".to_string(),
+                added_at: 1500,
+                certificate: b"{}".to_vec(),
+            }],
+            communities: vec![
+                account::CommunityEntry {
+                    community_id: "VLD0:community".to_string(),
+                    name: "LAN Party".to_string(),
+                    joined_at: 1200,
+                    owner_keypair: None,
+                },
+                account::CommunityEntry {
+                    community_id: "VLD0:hosted".to_string(),
+                    name: "Clan".to_string(),
+                    joined_at: 1300,
+                    owner_keypair: Some("VLD0:hosted_kp".to_string()),
+                },
+            ],
+            friend_groups: vec!["Gaming".to_string(), "Work".to_string()],
         };
 
         let encoded = account::encode_account_header(&header);
@@ -1427,6 +1767,19 @@ mod tests {
         assert_eq!(decoded.contact_list_keypair, Some("VLD0:contacts_kp".to_string()));
         assert_eq!(decoded.chat_list_keypair, Some("VLD0:chats_kp".to_string()));
         assert_eq!(decoded.invitation_list_keypair, None);
+        assert_eq!(decoded.devices.len(), 1);
+        assert_eq!(decoded.devices[0].device_key, vec![0xBB; 32]);
+        assert_eq!(decoded.devices[0].name, "Laptop");
+        assert_eq!(decoded.devices[0].mailbox_key, "VLD0:mailbox");
+        assert_eq!(decoded.devices[0].added_at, 1500);
+        assert_eq!(decoded.devices[0].certificate, b"{}".to_vec());
+        assert_eq!(decoded.communities.len(), 2);
+        assert_eq!(decoded.communities[0].community_id, "VLD0:community");
+        assert_eq!(decoded.communities[0].name, "LAN Party");
+        assert_eq!(decoded.communities[0].joined_at, 1200);
+        assert_eq!(decoded.communities[0].owner_keypair, None);
+        assert_eq!(decoded.communities[1].owner_keypair, Some("VLD0:hosted_kp".to_string()));
+        assert_eq!(decoded.friend_groups, vec!["Gaming".to_string(), "Work".to_string()]);
     }
 
     #[test]
@@ -1440,6 +1793,9 @@ mod tests {
             remote_conversation_key: "VLD0:remote456".to_string(),
             added_at: 1000,
             updated_at: 2000,
+            local_conversation_keypair: Some("VLD0:local_kp".to_string()),
+            profile_key: "VLD0:profile".to_string(),
+            mailbox_key: "VLD0:mailbox".to_string(),
         };
 
         let encoded = account::encode_contact_entry(&entry);
@@ -1453,6 +1809,9 @@ mod tests {
         assert_eq!(decoded.remote_conversation_key, "VLD0:remote456");
         assert_eq!(decoded.added_at, 1000);
         assert_eq!(decoded.updated_at, 2000);
+        assert_eq!(decoded.local_conversation_keypair, Some("VLD0:local_kp".to_string()));
+        assert_eq!(decoded.profile_key, "VLD0:profile");
+        assert_eq!(decoded.mailbox_key, "VLD0:mailbox");
     }
 
     #[test]
@@ -1526,4 +1885,33 @@ mod tests {
         assert_eq!(decoded.created_at, 5000);
         assert_eq!(decoded.updated_at, 6000);
     }
+
+    #[test]
+    fn round_trip_group_header() {
+        let member = |byte: u8, name: &str| group::GroupMemberEntry {
+            public_key: vec![byte; 32],
+            display_name: name.to_string(),
+            profile_key: format!("VLD0:profile_{name}"),
+            mailbox_key: format!("VLD0:mailbox_{name}"),
+            joined_at: u64::from(byte) * 1000,
+        };
+        let header = group::GroupHeader {
+            group_id: "6f0c1d2e".to_string(),
+            name: "Tonight's match".to_string(),
+            created_by: vec![0x01; 32],
+            members: vec![member(0x01, "Alice"), member(0x02, "Bob"), member(0x03, "Carol")],
+            created_at: 7000,
+            updated_at: 8000,
+        };
+
+        let encoded = group::encode_group_header(&header);
+        let decoded = group::decode_group_header(&encoded).unwrap();
+
+        assert_eq!(decoded.group_id, "6f0c1d2e");
+        assert_eq!(decoded.name, "Tonight's match");
+        assert_eq!(decoded.created_by, vec![0x01; 32]);
+        assert_eq!(decoded.members, header.members);
+        assert_eq!(decoded.created_at, 7000);
+        assert_eq!(decoded.updated_at, 8000);
+    }
 }
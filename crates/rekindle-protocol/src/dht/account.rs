@@ -30,31 +30,48 @@ pub struct AccountRecord {
     chat_list_keypair: Option<KeyPair>,
     /// Owner keypair for the invitation list `DHTShortArray` (unique per child).
     invitation_list_keypair: Option<KeyPair>,
+    /// Whether `create` found a header an earlier install had published.
+    recovered: bool,
 }
 
 impl AccountRecord {
-    /// Create a new account record with child `DHTShortArray`s.
+    /// Create the account record owned by `owner_keypair`, or pick up the one
+    /// an earlier install of the same identity already published.
     ///
-    /// Returns the record and the owner keypair (caller must persist both).
+    /// Veilid derives the record key from the owner keypair, so a keypair
+    /// derived from the identity secret makes the record findable from the
+    /// secret alone. If the network already holds a header under that key it
+    /// is adopted as-is and [`recovered`](Self::recovered) returns true;
+    /// otherwise fresh child `DHTShortArray`s are created.
     pub async fn create(
         rc: &RoutingContext,
+        owner_keypair: KeyPair,
         encryption_key: DhtRecordKey,
         display_name: &str,
         status_message: &str,
-    ) -> Result<(Self, KeyPair), ProtocolError> {
+    ) -> Result<Self, ProtocolError> {
         let schema = DHTSchema::dflt(1)
             .map_err(|e| ProtocolError::DhtError(format!("invalid schema: {e}")))?;
 
         let descriptor = rc
-            .create_dht_record(CRYPTO_KIND_VLD0, schema, None)
+            .create_dht_record(CRYPTO_KIND_VLD0, schema, Some(owner_keypair.clone()))
             .await
             .map_err(|e| ProtocolError::DhtError(format!("create account record: {e}")))?;
 
         let key = descriptor.key().clone();
-        let keypair = descriptor
-            .owner_secret()
-            .map(|secret| KeyPair::new_from_parts(descriptor.owner().clone(), secret.value()))
-            .ok_or_else(|| ProtocolError::DhtError("no owner secret after create".into()))?;
+
+        let existing = rc
+            .get_dht_value(key.clone(), 0, true)
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("read account header: {e}")))?;
+        if let Some(value) = existing {
+            let plaintext = encryption_key.decrypt(value.data())?;
+            let header = decode_account_header(&plaintext)?;
+            tracing::info!(key = %key, "recovered existing AccountRecord");
+            let mut record = Self::from_header(rc, key, owner_keypair, encryption_key, Some(header));
+            record.recovered = true;
+            return Ok(record);
+        }
 
         // Create child DHTShortArrays with unique random keypairs (None = random).
         // Passing the same owner keypair to all three would produce the same
@@ -63,10 +80,6 @@ impl AccountRecord {
         let (chats, chats_kp) = DHTShortArray::create(rc, 255, None).await?;
         let (invitations, invitations_kp) = DHTShortArray::create(rc, 255, None).await?;
 
-        let contact_list_key = contacts.record_key();
-        let chat_list_key = chats.record_key();
-        let invitation_list_key = invitations.record_key();
-
         let now = u64::try_from(
             std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -76,9 +89,9 @@ impl AccountRecord {
         .unwrap_or(u64::MAX);
 
         let header = AccountHeader {
-            contact_list_key: contact_list_key.clone(),
-            chat_list_key: chat_list_key.clone(),
-            invitation_list_key: invitation_list_key.clone(),
+            contact_list_key: contacts.record_key(),
+            chat_list_key: chats.record_key(),
+            invitation_list_key: invitations.record_key(),
             display_name: display_name.to_string(),
             status_message: status_message.to_string(),
             avatar_hash: Vec::new(),
@@ -88,6 +101,9 @@ impl AccountRecord {
             contact_list_keypair: Some(contacts_kp.to_string()),
             chat_list_keypair: Some(chats_kp.to_string()),
             invitation_list_keypair: Some(invitations_kp.to_string()),
+            devices: Vec::new(),
+            communities: Vec::new(),
+            friend_groups: Vec::new(),
         };
 
         // Encode, encrypt, and write to subkey 0
@@ -99,21 +115,7 @@ impl AccountRecord {
 
         tracing::debug!(key = %key, "AccountRecord created");
 
-        Ok((
-            Self {
-                routing_context: rc.clone(),
-                record_key: key,
-                owner_keypair: keypair.clone(),
-                encryption_key,
-                contact_list_key: Some(contact_list_key),
-                chat_list_key: Some(chat_list_key),
-                invitation_list_key: Some(invitation_list_key),
-                contact_list_keypair: Some(contacts_kp),
-                chat_list_keypair: Some(chats_kp),
-                invitation_list_keypair: Some(invitations_kp),
-            },
-            keypair,
-        ))
+        Ok(Self::from_header(rc, key, owner_keypair, encryption_key, Some(header)))
     }
 
     /// Open an existing account record with write access.
@@ -138,11 +140,30 @@ impl AccountRecord {
             .await
             .map_err(|e| ProtocolError::DhtError(format!("read account header: {e}")))?;
 
-        let (contact_list_key, chat_list_key, invitation_list_key,
-             contact_list_keypair, chat_list_keypair, invitation_list_keypair) = match value {
+        let header = match value {
             Some(v) => {
                 let plaintext = encryption_key.decrypt(v.data())?;
-                let header = decode_account_header(&plaintext)?;
+                Some(decode_account_header(&plaintext)?)
+            }
+            None => None,
+        };
+
+        tracing::debug!(key, "AccountRecord opened");
+
+        Ok(Self::from_header(rc, record_key, owner_keypair, encryption_key, header))
+    }
+
+    /// Build the handle from a decoded header's child key pointers and keypairs.
+    fn from_header(
+        rc: &RoutingContext,
+        record_key: RecordKey,
+        owner_keypair: KeyPair,
+        encryption_key: DhtRecordKey,
+        header: Option<AccountHeader>,
+    ) -> Self {
+        let (contact_list_key, chat_list_key, invitation_list_key,
+             contact_list_keypair, chat_list_keypair, invitation_list_keypair) = match header {
+            Some(header) => {
                 let clk = header.contact_list_keypair.and_then(|s| s.parse().ok());
                 let chk = header.chat_list_keypair.and_then(|s| s.parse().ok());
                 let ilk = header.invitation_list_keypair.and_then(|s| s.parse().ok());
@@ -158,9 +179,7 @@ impl AccountRecord {
             None => (None, None, None, None, None, None),
         };
 
-        tracing::debug!(key, "AccountRecord opened");
-
-        Ok(Self {
+        Self {
             routing_context: rc.clone(),
             record_key,
             owner_keypair,
@@ -171,7 +190,8 @@ impl AccountRecord {
             contact_list_keypair,
             chat_list_keypair,
             invitation_list_keypair,
-        })
+            recovered: false,
+        }
     }
 
     /// Read and decrypt the account header.
@@ -272,6 +292,27 @@ impl AccountRecord {
         ))
     }
 
+    /// Replace the whole contact list with `entries`.
+    pub async fn replace_contacts(&self, entries: &[ContactEntry]) -> Result<(), ProtocolError> {
+        let key = self
+            .contact_list_key
+            .as_ref()
+            .ok_or_else(|| ProtocolError::DhtError("contact list key not set".into()))?;
+
+        let arr = DHTShortArray::open(
+            &self.routing_context,
+            key,
+            self.contact_list_keypair.clone(),
+        )
+        .await?;
+
+        arr.clear().await?;
+        for entry in entries {
+            arr.add(&encode_contact_entry(entry)).await?;
+        }
+        Ok(())
+    }
+
     /// Add a chat entry to the chat list `DHTShortArray`.
     pub async fn add_chat(&self, entry: &ChatEntry) -> Result<u32, ProtocolError> {
         let key = self
@@ -368,6 +409,12 @@ impl AccountRecord {
         &self.owner_keypair
     }
 
+    /// Whether [`create`](Self::create) adopted a record an earlier install
+    /// had already published, rather than starting an empty one.
+    pub fn recovered(&self) -> bool {
+        self.recovered
+    }
+
     /// Get the invitation list `DHTShortArray` key (if loaded).
     pub fn invitation_list_key(&self) -> Option<&str> {
         self.invitation_list_key.as_deref()
@@ -1,9 +1,14 @@
 use crate::capnp_codec;
+use crate::dht::paged_list::{self, ListEntry, ListHead, ListWriter};
 use crate::dht::DHTManager;
 use crate::error::ProtocolError;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use veilid_core::KeyPair;
 
 // Community record subkey layout (SMPL schema, multi-writer).
+// Channels, members and roles are paged lists: the subkey holds a
+// `paged_list::ListHead` pointing at the pages and change log.
 pub const SUBKEY_METADATA: u32 = 0;
 pub const SUBKEY_CHANNELS: u32 = 1;
 pub const SUBKEY_MEMBERS: u32 = 2;
@@ -29,18 +34,27 @@ pub struct CommunityMetadata {
     /// Updated each keepalive cycle so the value actually changes, forcing a DHT write.
     #[serde(default)]
     pub last_refreshed: u64,
+    /// Key (hex) the community signs its directory listings with, set
+    /// while it is listed. See [`crate::dht::directory`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listing_key: Option<String>,
 }
 
 /// A channel entry stored in the channel list (subkey 1).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ChannelEntry {
     pub id: String,
     pub name: String,
     pub channel_type: String, // "text" or "voice"
     pub sort_order: u16,
+    #[serde(default)]
     pub latest_message_key: Option<String>,
     #[serde(default)]
     pub permission_overwrites: Vec<PermissionOverwrite>,
+    /// Message retention in seconds, 0 to keep messages.
+    #[serde(default)]
+    pub retention_secs: u32,
 }
 
 /// A member entry stored in the member list (subkey 2).
@@ -48,6 +62,8 @@ pub struct ChannelEntry {
 #[serde(rename_all = "camelCase")]
 pub struct MemberEntry {
     pub pseudonym_key: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub display_name: Option<String>,
     /// Legacy single-role field. Kept for migration compatibility.
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub role: Option<String>,
@@ -101,25 +117,26 @@ pub async fn create_community(
     metadata: &CommunityMetadata,
 ) -> Result<String, ProtocolError> {
     // TODO: Use DHTSchema::SMPL for multi-writer when veilid-core is available
-    let (key, _owner_keypair) = dht.create_record(COMMUNITY_SUBKEY_COUNT).await?;
+    let (key, owner_keypair) = dht.create_record(COMMUNITY_SUBKEY_COUNT).await?;
+    let owner = owner_keypair
+        .ok_or_else(|| ProtocolError::DhtError("no owner keypair for community record".into()))?;
 
     let meta_bytes = capnp_codec::community::encode_community(metadata, &[], &[]);
     dht.set_value(&key, SUBKEY_METADATA, meta_bytes).await?;
 
     // Initialize empty channel list
-    let ch_bytes = capnp_codec::community::encode_channels(&[]);
-    dht.set_value(&key, SUBKEY_CHANNELS, ch_bytes).await?;
+    publish_list::<ChannelEntry>(dht, &key, &owner, &[]).await?;
 
     // Initialize member list with owner (assign owner role + @everyone)
     let members = vec![MemberEntry {
         pseudonym_key: metadata.owner_key.clone(),
+        display_name: None,
         role: None,
         role_ids: vec![ROLE_EVERYONE_ID, 4], // @everyone + Owner role
         joined_at: metadata.created_at,
         timeout_until: None,
     }];
-    let mem_bytes = capnp_codec::community::encode_members(&members)?;
-    dht.set_value(&key, SUBKEY_MEMBERS, mem_bytes).await?;
+    publish_list(dht, &key, &owner, &members).await?;
 
     tracing::info!(key = %key, name = %metadata.name, "community record created");
     Ok(key)
@@ -139,18 +156,121 @@ pub async fn read_metadata(
     }
 }
 
+/// A list kept in one of the community record's subkeys.
+trait CommunityList: Serialize + DeserializeOwned + Sized {
+    const SUBKEY: u32;
+
+    /// The entry's ID within the list.
+    fn list_id(&self) -> String;
+
+    /// Decode a subkey that still holds the whole list in one value.
+    fn decode_legacy(data: &[u8]) -> Result<Vec<Self>, ProtocolError>;
+}
+
+impl CommunityList for ChannelEntry {
+    const SUBKEY: u32 = SUBKEY_CHANNELS;
+
+    fn list_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn decode_legacy(data: &[u8]) -> Result<Vec<Self>, ProtocolError> {
+        let (_, channels, _) = capnp_codec::community::decode_community(data, "")?;
+        Ok(channels)
+    }
+}
+
+impl CommunityList for MemberEntry {
+    const SUBKEY: u32 = SUBKEY_MEMBERS;
+
+    fn list_id(&self) -> String {
+        self.pseudonym_key.clone()
+    }
+
+    fn decode_legacy(data: &[u8]) -> Result<Vec<Self>, ProtocolError> {
+        capnp_codec::community::decode_members(data)
+    }
+}
+
+impl CommunityList for RoleDefinition {
+    const SUBKEY: u32 = SUBKEY_ROLES;
+
+    fn list_id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn decode_legacy(data: &[u8]) -> Result<Vec<Self>, ProtocolError> {
+        let (_, _, roles) = capnp_codec::community::decode_community(data, "")?;
+        Ok(roles)
+    }
+}
+
+/// Read a list page by page, or whole if it hasn't been migrated yet.
+async fn read_list<T: CommunityList>(dht: &DHTManager, key: &str) -> Result<Vec<T>, ProtocolError> {
+    let Some(data) = dht.get_value(key, T::SUBKEY).await? else {
+        return Ok(vec![]);
+    };
+    match ListHead::parse(&data) {
+        Some(head) => paged_list::read_entries(dht.routing_context(), &head)
+            .await?
+            .iter()
+            .map(ListEntry::decode)
+            .collect(),
+        None => T::decode_legacy(&data),
+    }
+}
+
+/// Publish `items` as the whole list.
+async fn publish_list<T: CommunityList>(
+    dht: &DHTManager,
+    key: &str,
+    owner: &KeyPair,
+    items: &[T],
+) -> Result<(), ProtocolError> {
+    let mut writer = ListWriter::open(dht.routing_context(), key, T::SUBKEY, owner.clone()).await?;
+    publish_with(&mut writer, items).await
+}
+
+async fn publish_with<T: CommunityList>(writer: &mut ListWriter, items: &[T]) -> Result<(), ProtocolError> {
+    let entries = items
+        .iter()
+        .map(|item| ListEntry::new(item.list_id(), item))
+        .collect::<Result<Vec<_>, _>>()?;
+    writer.publish(entries).await
+}
+
+/// Apply `change` to a list and publish the result, migrating it to the
+/// paged layout if it is still a single value. Only the pages holding
+/// changed entries are rewritten.
+async fn modify_list<T: CommunityList>(
+    dht: &DHTManager,
+    key: &str,
+    owner: &KeyPair,
+    change: impl FnOnce(&mut Vec<T>) -> Result<(), ProtocolError>,
+) -> Result<(), ProtocolError> {
+    let mut writer = ListWriter::open(dht.routing_context(), key, T::SUBKEY, owner.clone()).await?;
+    let mut items: Vec<T> = if writer.is_paged() {
+        writer
+            .entries()
+            .iter()
+            .map(ListEntry::decode)
+            .collect::<Result<_, _>>()?
+    } else {
+        match dht.get_value(key, T::SUBKEY).await? {
+            Some(data) => T::decode_legacy(&data)?,
+            None => vec![],
+        }
+    };
+    change(&mut items)?;
+    publish_with(&mut writer, &items).await
+}
+
 /// Read channel list from DHT.
 pub async fn read_channels(
     dht: &DHTManager,
     key: &str,
 ) -> Result<Vec<ChannelEntry>, ProtocolError> {
-    match dht.get_value(key, SUBKEY_CHANNELS).await? {
-        Some(data) => {
-            let (_, channels, _) = capnp_codec::community::decode_community(&data, "")?;
-            Ok(channels)
-        }
-        None => Ok(vec![]),
-    }
+    read_list(dht, key).await
 }
 
 /// Read member list from DHT.
@@ -158,91 +278,98 @@ pub async fn read_members(
     dht: &DHTManager,
     key: &str,
 ) -> Result<Vec<MemberEntry>, ProtocolError> {
-    match dht.get_value(key, SUBKEY_MEMBERS).await? {
-        Some(data) => capnp_codec::community::decode_members(&data),
-        None => Ok(vec![]),
-    }
+    read_list(dht, key).await
 }
 
 /// Add a channel to the community.
 pub async fn add_channel(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     channel: ChannelEntry,
 ) -> Result<(), ProtocolError> {
-    let mut channels = read_channels(dht, key).await?;
-    channels.push(channel);
-    let data = capnp_codec::community::encode_channels(&channels);
-    dht.set_value(key, SUBKEY_CHANNELS, data).await
+    modify_list(dht, key, owner, |channels| {
+        channels.push(channel);
+        Ok(())
+    })
+    .await
 }
 
 /// Remove a channel from the community by ID.
 pub async fn remove_channel(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     channel_id: &str,
 ) -> Result<(), ProtocolError> {
-    let mut channels = read_channels(dht, key).await?;
-    let before = channels.len();
-    channels.retain(|c| c.id != channel_id);
-    if channels.len() == before {
-        return Err(ProtocolError::DhtError(format!(
-            "channel {channel_id} not found"
-        )));
-    }
-    let data = capnp_codec::community::encode_channels(&channels);
-    dht.set_value(key, SUBKEY_CHANNELS, data).await
+    modify_list(dht, key, owner, |channels: &mut Vec<ChannelEntry>| {
+        let before = channels.len();
+        channels.retain(|c| c.id != channel_id);
+        if channels.len() == before {
+            return Err(ProtocolError::DhtError(format!(
+                "channel {channel_id} not found"
+            )));
+        }
+        Ok(())
+    })
+    .await
 }
 
 /// Add a member to the community.
 pub async fn add_member(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     member: MemberEntry,
 ) -> Result<(), ProtocolError> {
-    let mut members = read_members(dht, key).await?;
-    if members.iter().any(|m| m.pseudonym_key == member.pseudonym_key) {
-        return Err(ProtocolError::DhtError("member already exists".into()));
-    }
-    members.push(member);
-    let data = capnp_codec::community::encode_members(&members)?;
-    dht.set_value(key, SUBKEY_MEMBERS, data).await
+    modify_list(dht, key, owner, |members: &mut Vec<MemberEntry>| {
+        if members.iter().any(|m| m.pseudonym_key == member.pseudonym_key) {
+            return Err(ProtocolError::DhtError("member already exists".into()));
+        }
+        members.push(member);
+        Ok(())
+    })
+    .await
 }
 
 /// Remove a member from the community by pseudonym key.
 pub async fn remove_member(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     pseudonym_key: &str,
 ) -> Result<(), ProtocolError> {
-    let mut members = read_members(dht, key).await?;
-    let before = members.len();
-    members.retain(|m| m.pseudonym_key != pseudonym_key);
-    if members.len() == before {
-        return Err(ProtocolError::PeerNotFound(format!(
-            "member {pseudonym_key} not found"
-        )));
-    }
-    let data = capnp_codec::community::encode_members(&members)?;
-    dht.set_value(key, SUBKEY_MEMBERS, data).await
+    modify_list(dht, key, owner, |members: &mut Vec<MemberEntry>| {
+        let before = members.len();
+        members.retain(|m| m.pseudonym_key != pseudonym_key);
+        if members.len() == before {
+            return Err(ProtocolError::PeerNotFound(format!(
+                "member {pseudonym_key} not found"
+            )));
+        }
+        Ok(())
+    })
+    .await
 }
 
 /// Set a member's role IDs (replaces all role assignments).
 pub async fn set_member_roles(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     pseudonym_key: &str,
     role_ids: Vec<u32>,
 ) -> Result<(), ProtocolError> {
-    let mut members = read_members(dht, key).await?;
-    let member = members
-        .iter_mut()
-        .find(|m| m.pseudonym_key == pseudonym_key)
-        .ok_or_else(|| ProtocolError::PeerNotFound(format!("member {pseudonym_key} not found")))?;
-    member.role_ids = role_ids;
-    member.role = None; // clear legacy field
-    let data = capnp_codec::community::encode_members(&members)?;
-    dht.set_value(key, SUBKEY_MEMBERS, data).await
+    modify_list(dht, key, owner, |members: &mut Vec<MemberEntry>| {
+        let member = members
+            .iter_mut()
+            .find(|m| m.pseudonym_key == pseudonym_key)
+            .ok_or_else(|| ProtocolError::PeerNotFound(format!("member {pseudonym_key} not found")))?;
+        member.role_ids = role_ids;
+        member.role = None; // clear legacy field
+        Ok(())
+    })
+    .await
 }
 
 /// Read role definitions from DHT.
@@ -250,68 +377,68 @@ pub async fn read_roles(
     dht: &DHTManager,
     key: &str,
 ) -> Result<Vec<RoleDefinition>, ProtocolError> {
-    match dht.get_value(key, SUBKEY_ROLES).await? {
-        Some(data) => {
-            let (_, _, roles) = capnp_codec::community::decode_community(&data, "")?;
-            Ok(roles)
-        }
-        None => Ok(vec![]),
-    }
+    read_list(dht, key).await
 }
 
 /// Add a role definition to the community.
 pub async fn add_role(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     role: RoleDefinition,
 ) -> Result<(), ProtocolError> {
-    let mut roles = read_roles(dht, key).await?;
-    if roles.iter().any(|r| r.id == role.id) {
-        let id = role.id;
-        return Err(ProtocolError::DhtError(format!(
-            "role with id {id} already exists"
-        )));
-    }
-    roles.push(role);
-    let data = capnp_codec::community::encode_roles(&roles);
-    dht.set_value(key, SUBKEY_ROLES, data).await
+    modify_list(dht, key, owner, |roles: &mut Vec<RoleDefinition>| {
+        if roles.iter().any(|r| r.id == role.id) {
+            let id = role.id;
+            return Err(ProtocolError::DhtError(format!(
+                "role with id {id} already exists"
+            )));
+        }
+        roles.push(role);
+        Ok(())
+    })
+    .await
 }
 
 /// Remove a role definition by ID.
 pub async fn remove_role(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     role_id: u32,
 ) -> Result<(), ProtocolError> {
-    let mut roles = read_roles(dht, key).await?;
-    let before = roles.len();
-    roles.retain(|r| r.id != role_id);
-    if roles.len() == before {
-        return Err(ProtocolError::DhtError(format!(
-            "role {role_id} not found"
-        )));
-    }
-    let data = capnp_codec::community::encode_roles(&roles);
-    dht.set_value(key, SUBKEY_ROLES, data).await
+    modify_list(dht, key, owner, |roles: &mut Vec<RoleDefinition>| {
+        let before = roles.len();
+        roles.retain(|r| r.id != role_id);
+        if roles.len() == before {
+            return Err(ProtocolError::DhtError(format!(
+                "role {role_id} not found"
+            )));
+        }
+        Ok(())
+    })
+    .await
 }
 
 /// Update a role definition (replaces the role with matching ID).
 pub async fn update_role(
     dht: &DHTManager,
     key: &str,
+    owner: &KeyPair,
     role: RoleDefinition,
 ) -> Result<(), ProtocolError> {
-    let mut roles = read_roles(dht, key).await?;
-    let entry = roles
-        .iter_mut()
-        .find(|r| r.id == role.id)
-        .ok_or_else(|| {
-            let id = role.id;
-            ProtocolError::DhtError(format!("role {id} not found"))
-        })?;
-    *entry = role;
-    let data = capnp_codec::community::encode_roles(&roles);
-    dht.set_value(key, SUBKEY_ROLES, data).await
+    modify_list(dht, key, owner, |roles: &mut Vec<RoleDefinition>| {
+        let entry = roles
+            .iter_mut()
+            .find(|r| r.id == role.id)
+            .ok_or_else(|| {
+                let id = role.id;
+                ProtocolError::DhtError(format!("role {id} not found"))
+            })?;
+        *entry = role;
+        Ok(())
+    })
+    .await
 }
 
 /// Permission bit flags for role-based access control (Discord-aligned bit positions).
@@ -351,7 +478,7 @@ pub mod permissions {
     pub const MANAGE_NICKNAMES: u64 = 1 << 27;
     pub const MANAGE_ROLES: u64 = 1 << 28;
 
-    // ── Future ──
+    // ── Threads ──
     pub const MANAGE_THREADS: u64 = 1 << 34;
     pub const CREATE_PUBLIC_THREADS: u64 = 1 << 35;
     pub const CREATE_PRIVATE_THREADS: u64 = 1 << 36;
@@ -390,7 +517,10 @@ pub mod permissions {
 
     /// Default permissions for the Member role (id=1).
     pub fn member_permissions() -> u64 {
-        everyone_permissions() | CREATE_INSTANT_INVITE
+        everyone_permissions()
+            | CREATE_INSTANT_INVITE
+            | CREATE_PUBLIC_THREADS
+            | CREATE_PRIVATE_THREADS
     }
 
     /// Default permissions for the Moderator role (id=2).
@@ -398,6 +528,7 @@ pub mod permissions {
         member_permissions()
             | KICK_MEMBERS
             | MANAGE_MESSAGES
+            | MANAGE_THREADS
             | MUTE_MEMBERS
             | DEAFEN_MEMBERS
             | MODERATE_MEMBERS
@@ -551,7 +682,9 @@ pub mod permissions {
                     | ADD_REACTIONS
                     | SPEAK
                     | STREAM
-                    | CREATE_INSTANT_INVITE);
+                    | CREATE_INSTANT_INVITE
+                    | CREATE_PUBLIC_THREADS
+                    | CREATE_PRIVATE_THREADS);
             }
         }
 
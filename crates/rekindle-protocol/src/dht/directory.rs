@@ -0,0 +1,527 @@
+//! Public community directory.
+//!
+//! A community opts in to discovery by writing a signed
+//! [`DirectoryListing`] to its own listing record — a one-subkey DHT record
+//! owned by its listing key, so nobody else can change it — and pointing
+//! to that record from well-known append-only logs, or shards: the
+//! [`Shard::All`] log every listing goes to, plus one [`Shard::Game`] log
+//! per game it is tagged with. A shard's owner keypair is derived from its
+//! label, so anyone can find and append to it; a [`ListingPointer`] is
+//! signed by the listing key it names, and a listing is only tied to its
+//! community when the community's own metadata names the same key.
+//!
+//! Readers take a shard's tail and follow the newest valid pointer of each
+//! listing key, at most [`MAX_SHARD_LISTINGS`] of them. A host rewrites its
+//! record every [`LISTING_REFRESH_SECS`] and appends a pointer only when the
+//! tail no longer holds one; a listing older than [`LISTING_TTL_SECS`], one
+//! announcing a delisting, or one no longer tagged with a game shard's
+//! game, drops out.
+//!
+//! The shards are still writable by anyone. Rewriting one hides its
+//! pointers until hosts append them again, and a flood of pointers under
+//! fresh keys can push real ones out of the tail; neither can alter a
+//! listing, and one key gets one slot however often it appears.
+
+use std::collections::{HashMap, HashSet};
+
+use rekindle_crypto::Identity;
+use serde::{Deserialize, Serialize};
+use veilid_core::{
+    BarePublicKey, BareSecretKey, DHTSchema, KeyPair, PublicKey, RecordKey, RoutingContext,
+    CRYPTO_KIND_VLD0,
+};
+
+use crate::dht::community::CommunityMetadata;
+use crate::dht::log::DHTLog;
+use crate::error::ProtocolError;
+use crate::messaging::envelope::verify_signed_json;
+
+/// Keying material the shard owners are derived from.
+const DIRECTORY_SEED: &[u8] = b"rekindle-directory-v1";
+
+/// HKDF info for a community's listing key.
+const LISTING_KEY_INFO: &[u8] = b"rekindle-directory-listing-v1";
+
+/// Pointers per shard segment. A pointer is a few hundred bytes.
+const SHARD_SEGMENT_CAPACITY: u16 = 128;
+
+/// Entries read from the tail of a shard.
+const SHARD_TAIL: u32 = 1024;
+
+/// Most listing keys one shard read follows to their records.
+pub const MAX_SHARD_LISTINGS: usize = 256;
+
+/// How often a host republishes its listing.
+pub const LISTING_REFRESH_SECS: u64 = 6 * 60 * 60;
+
+/// How long a listing stays visible without a refresh.
+pub const LISTING_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Clock skew tolerated on a listing's `published_at`.
+const MAX_CLOCK_SKEW_SECS: u64 = 60 * 60;
+
+/// Longest listing name, in characters.
+pub const MAX_LISTING_NAME: usize = 100;
+
+/// Longest listing description, in characters.
+pub const MAX_LISTING_DESCRIPTION: usize = 500;
+
+/// Most games one listing may be tagged with.
+pub const MAX_LISTING_GAMES: usize = 8;
+
+/// One of the directory's logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shard {
+    /// Every listed community.
+    All,
+    /// Communities tagged with a game, by `GameEntry.id`.
+    Game(u32),
+}
+
+impl Shard {
+    /// The label the shard's owner is derived from.
+    pub fn label(self) -> String {
+        match self {
+            Self::All => "all".to_string(),
+            Self::Game(id) => format!("game:{id}"),
+        }
+    }
+
+    /// The shard log's owner keypair, the same for everyone.
+    pub fn owner(self) -> KeyPair {
+        keypair(&Identity::derive(DIRECTORY_SEED, self.label().as_bytes()))
+    }
+
+    /// Open the shard's log, creating it if nobody has listed there yet.
+    pub async fn open(self, rc: &RoutingContext) -> Result<DHTLog, ProtocolError> {
+        DHTLog::open_or_create_with_owner(rc, SHARD_SEGMENT_CAPACITY, self.owner()).await
+    }
+
+    /// Whether `listing` still belongs in this shard. Pointers outlive a
+    /// listing dropping a game, so game shards check its current tags.
+    pub fn includes(self, listing: &DirectoryListing) -> bool {
+        match self {
+            Self::All => true,
+            Self::Game(id) => listing.game_ids.contains(&id),
+        }
+    }
+}
+
+/// A shard entry: where a listing key keeps its community's listing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListingPointer {
+    /// Ed25519 key (hex) of the listing, which signs the pointer.
+    pub listing_key: String,
+    /// Key of the listing record.
+    pub record_key: String,
+    /// Signature over the JSON of the fields above.
+    pub signature: Vec<u8>,
+}
+
+impl ListingPointer {
+    /// A pointer to `record_key` signed by `key`.
+    pub fn signed(key: &Identity, record_key: &str) -> Self {
+        let mut pointer = Self {
+            listing_key: key.public_key_hex(),
+            record_key: record_key.to_string(),
+            signature: Vec::new(),
+        };
+        let signable = serde_json::to_vec(&pointer.signable()).unwrap_or_default();
+        pointer.signature = key.sign(&signable).to_bytes().to_vec();
+        pointer
+    }
+
+    /// Whether the listing key signed the pointer.
+    pub fn verify(&self) -> bool {
+        verify_signed_json(&self.listing_key, &self.signable(), &self.signature).is_ok()
+    }
+
+    fn signable(&self) -> serde_json::Value {
+        serde_json::json!({
+            "listingKey": self.listing_key,
+            "recordKey": self.record_key,
+        })
+    }
+}
+
+/// A community's entry in the directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryListing {
+    /// The community's DHT record key — the route to join it by, as an
+    /// invite code carries it.
+    pub community_id: String,
+    pub name: String,
+    pub description: String,
+    pub member_count: u32,
+    /// `GameEntry.id` of each game the community is about.
+    pub game_ids: Vec<u32>,
+    /// Ed25519 key (hex) the listing is signed with; see [`listing_identity`].
+    pub listing_key: String,
+    /// When the listing was signed (seconds since epoch).
+    pub published_at: u64,
+    /// Set when the community withdraws from the directory.
+    #[serde(default)]
+    pub delisted: bool,
+    /// Signature over the JSON of all fields above.
+    pub signature: Vec<u8>,
+}
+
+impl DirectoryListing {
+    /// Sign the listing with `key`, filling in `listing_key`.
+    pub fn signed(mut self, key: &Identity) -> Self {
+        self.listing_key = key.public_key_hex();
+        let signable = serde_json::to_vec(&self.signable()).unwrap_or_default();
+        self.signature = key.sign(&signable).to_bytes().to_vec();
+        self
+    }
+
+    /// Check the listing's limits and signature.
+    pub fn verify(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.chars().count() > MAX_LISTING_NAME {
+            return Err("invalid listing name".to_string());
+        }
+        if self.description.chars().count() > MAX_LISTING_DESCRIPTION {
+            return Err("listing description is too long".to_string());
+        }
+        if self.game_ids.len() > MAX_LISTING_GAMES {
+            return Err("listing has too many games".to_string());
+        }
+        verify_signed_json(&self.listing_key, &self.signable(), &self.signature)
+            .map_err(|e| format!("invalid listing signature: {e}"))
+    }
+
+    /// Whether `metadata` — the community's own subkey 0 — vouches for
+    /// this listing.
+    pub fn is_vouched_for(&self, metadata: &CommunityMetadata) -> bool {
+        metadata.listing_key.as_deref() == Some(self.listing_key.as_str())
+    }
+
+    /// Whether the listing matches a search: every word of `query` in its
+    /// name or description, and `game_id` among its games.
+    pub fn matches(&self, query: &str, game_id: Option<u32>) -> bool {
+        if game_id.is_some_and(|id| !self.game_ids.contains(&id)) {
+            return false;
+        }
+        let haystack = format!("{} {}", self.name, self.description).to_lowercase();
+        query
+            .split_whitespace()
+            .all(|word| haystack.contains(&word.to_lowercase()))
+    }
+
+    /// The shards the listing is published to.
+    pub fn shards(&self) -> Vec<Shard> {
+        let mut shards = vec![Shard::All];
+        for &id in &self.game_ids {
+            if !shards.contains(&Shard::Game(id)) {
+                shards.push(Shard::Game(id));
+            }
+        }
+        shards
+    }
+
+    fn signable(&self) -> serde_json::Value {
+        serde_json::json!({
+            "communityId": self.community_id,
+            "name": self.name,
+            "description": self.description,
+            "memberCount": self.member_count,
+            "gameIds": self.game_ids,
+            "listingKey": self.listing_key,
+            "publishedAt": self.published_at,
+            "delisted": self.delisted,
+        })
+    }
+}
+
+/// The key a community signs its listings with, derived from its DHT
+/// owner secret so it is stable across server restarts without storage.
+pub fn listing_identity(owner_secret: &[u8]) -> Identity {
+    Identity::derive(owner_secret, LISTING_KEY_INFO)
+}
+
+/// Write a listing signed by `key` to its record and, unless it is a
+/// delisting, make sure every shard it belongs in points to it.
+pub async fn publish_listing(
+    rc: &RoutingContext,
+    listing: &DirectoryListing,
+    key: &Identity,
+) -> Result<(), ProtocolError> {
+    let record_key = write_listing_record(rc, listing, key).await?;
+    if listing.delisted {
+        return Ok(());
+    }
+
+    let pointer = ListingPointer::signed(key, &record_key);
+    let data = serde_json::to_vec(&pointer).map_err(|e| ProtocolError::Serialization(e.to_string()))?;
+    for shard in listing.shards() {
+        let log = shard.open(rc).await?;
+        let appended = append_pointer(&log, &pointer, &data).await;
+        let _ = log.close().await;
+        appended?;
+    }
+    Ok(())
+}
+
+/// Overwrite the listing record owned by `key`, returning its key.
+async fn write_listing_record(
+    rc: &RoutingContext,
+    listing: &DirectoryListing,
+    key: &Identity,
+) -> Result<String, ProtocolError> {
+    let schema = DHTSchema::dflt(1).map_err(|e| ProtocolError::DhtError(format!("invalid schema: {e}")))?;
+    // Veilid derives the record's key from its owner, so this opens the
+    // record when it already exists
+    let descriptor = rc
+        .create_dht_record(CRYPTO_KIND_VLD0, schema, Some(keypair(key)))
+        .await
+        .map_err(|e| ProtocolError::DhtError(format!("open listing record: {e}")))?;
+    let record_key = descriptor.key().clone();
+    let data = serde_json::to_vec(listing).map_err(|e| ProtocolError::Serialization(e.to_string()))?;
+    let written = rc.set_dht_value(record_key.clone(), 0, data, None).await;
+    let _ = rc.close_dht_record(record_key.clone()).await;
+    written.map_err(|e| ProtocolError::DhtError(format!("write listing record: {e}")))?;
+    Ok(record_key.to_string())
+}
+
+/// Append `pointer` unless the shard's tail already holds it.
+async fn append_pointer(log: &DHTLog, pointer: &ListingPointer, data: &[u8]) -> Result<(), ProtocolError> {
+    let present = log
+        .tail(SHARD_TAIL)
+        .await?
+        .iter()
+        .filter_map(|entry| serde_json::from_slice::<ListingPointer>(entry).ok())
+        .any(|p| p == *pointer);
+    if !present {
+        log.append(data).await?;
+    }
+    Ok(())
+}
+
+/// Read the live listings in `shard`, newest per community.
+pub async fn read_shard(
+    rc: &RoutingContext,
+    shard: Shard,
+    now: u64,
+) -> Result<Vec<DirectoryListing>, ProtocolError> {
+    let log = shard.open(rc).await?;
+    let entries = log.tail(SHARD_TAIL).await;
+    let _ = log.close().await;
+    let pointers = newest_pointers(
+        entries?
+            .iter()
+            .filter_map(|data| serde_json::from_slice::<ListingPointer>(data).ok()),
+        MAX_SHARD_LISTINGS,
+    );
+
+    let listings = futures::future::join_all(pointers.iter().map(|p| read_listing(rc, p))).await;
+    Ok(live_listings(listings.into_iter().flatten(), now)
+        .into_iter()
+        .filter(|l| shard.includes(l))
+        .collect())
+}
+
+/// The listing a pointer leads to, if its record holds one under the
+/// pointer's key.
+async fn read_listing(rc: &RoutingContext, pointer: &ListingPointer) -> Option<DirectoryListing> {
+    let record_key: RecordKey = pointer.record_key.parse().ok()?;
+    if let Err(e) = rc.open_dht_record(record_key.clone(), None).await {
+        tracing::debug!(error = %e, record = %pointer.record_key, "failed to open listing record");
+        return None;
+    }
+    let value = rc.get_dht_value(record_key.clone(), 0, true).await;
+    let _ = rc.close_dht_record(record_key).await;
+    let listing: DirectoryListing = serde_json::from_slice(value.ok()??.data()).ok()?;
+    (listing.listing_key == pointer.listing_key).then_some(listing)
+}
+
+/// The newest validly signed pointer of each listing key in `entries`
+/// (oldest first), newest first, at most `cap` of them.
+///
+/// However many pointers a key appends, it takes one slot.
+pub fn newest_pointers(
+    entries: impl DoubleEndedIterator<Item = ListingPointer>,
+    cap: usize,
+) -> Vec<ListingPointer> {
+    let mut seen = HashSet::new();
+    entries
+        .rev()
+        .filter(|p| !seen.contains(&p.listing_key) && p.verify() && seen.insert(p.listing_key.clone()))
+        .take(cap)
+        .collect()
+}
+
+/// Fold a shard's entries into the listings still live at `now`.
+///
+/// Entries that fail [`DirectoryListing::verify`] are skipped. Each
+/// community and key keeps only its newest listing — the later entry on a
+/// tie — which is dropped if it is a delisting or older than
+/// [`LISTING_TTL_SECS`]. The rest come back largest community first.
+pub fn live_listings(
+    entries: impl IntoIterator<Item = DirectoryListing>,
+    now: u64,
+) -> Vec<DirectoryListing> {
+    let mut newest: HashMap<(String, String), DirectoryListing> = HashMap::new();
+    for listing in entries {
+        if listing.published_at > now + MAX_CLOCK_SKEW_SECS || listing.verify().is_err() {
+            continue;
+        }
+        let key = (listing.community_id.clone(), listing.listing_key.clone());
+        match newest.get(&key) {
+            Some(current) if current.published_at > listing.published_at => {}
+            _ => {
+                newest.insert(key, listing);
+            }
+        }
+    }
+
+    let mut live: Vec<DirectoryListing> = newest
+        .into_values()
+        .filter(|l| !l.delisted && l.published_at + LISTING_TTL_SECS >= now)
+        .collect();
+    live.sort_by(|a, b| {
+        b.member_count
+            .cmp(&a.member_count)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    live
+}
+
+/// Veilid keypair for an Ed25519 identity.
+fn keypair(identity: &Identity) -> KeyPair {
+    let bare_pub = BarePublicKey::new(&identity.public_key_bytes());
+    let bare_secret = BareSecretKey::new(identity.secret_key_bytes());
+    KeyPair::new_from_parts(PublicKey::new(CRYPTO_KIND_VLD0, bare_pub), bare_secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_800_000_000;
+
+    fn listing(community_id: &str, name: &str, published_at: u64) -> DirectoryListing {
+        DirectoryListing {
+            community_id: community_id.to_string(),
+            name: name.to_string(),
+            description: "Weekly raids and chill".to_string(),
+            member_count: 12,
+            game_ids: vec![42],
+            listing_key: String::new(),
+            published_at,
+            delisted: false,
+            signature: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn signed_listing_verifies() {
+        let key = Identity::generate();
+        let signed = listing("VLD0:abc", "Raiders", NOW).signed(&key);
+        assert_eq!(signed.listing_key, key.public_key_hex());
+        assert!(signed.verify().is_ok());
+
+        let mut tampered = signed.clone();
+        tampered.member_count = 9000;
+        assert!(tampered.verify().is_err());
+
+        let too_long = listing("VLD0:abc", &"x".repeat(MAX_LISTING_NAME + 1), NOW).signed(&key);
+        assert!(too_long.verify().is_err());
+    }
+
+    #[test]
+    fn live_listings_keep_the_newest_per_community() {
+        let key = Identity::generate();
+        let old = listing("VLD0:abc", "Old name", NOW - 100).signed(&key);
+        let new = listing("VLD0:abc", "New name", NOW - 10).signed(&key);
+        let other = listing("VLD0:def", "Other", NOW - 50).signed(&Identity::generate());
+
+        let live = live_listings(vec![new.clone(), old, other.clone()], NOW);
+        assert_eq!(live.len(), 2);
+        assert!(live.contains(&new));
+        assert!(live.contains(&other));
+    }
+
+    #[test]
+    fn live_listings_drop_delisted_stale_and_forged() {
+        let key = Identity::generate();
+        let listed = listing("VLD0:abc", "Raiders", NOW - 100).signed(&key);
+        let mut delisting = listing("VLD0:abc", "Raiders", NOW - 10);
+        delisting.delisted = true;
+        let delisting = delisting.signed(&key);
+        assert!(live_listings(vec![listed, delisting], NOW).is_empty());
+
+        let stale = listing("VLD0:def", "Stale", NOW - LISTING_TTL_SECS - 1).signed(&key);
+        assert!(live_listings(vec![stale], NOW).is_empty());
+
+        let mut forged = listing("VLD0:ghi", "Forged", NOW).signed(&key);
+        forged.name = "Renamed".to_string();
+        assert!(live_listings(vec![forged], NOW).is_empty());
+    }
+
+    #[test]
+    fn matches_words_and_games() {
+        let l = listing("VLD0:abc", "Night Raiders", NOW);
+        assert!(l.matches("", None));
+        assert!(l.matches("raiders chill", Some(42)));
+        assert!(!l.matches("raiders", Some(7)));
+        assert!(!l.matches("pvp", None));
+    }
+
+    #[test]
+    fn shards_include_each_game_once() {
+        let mut l = listing("VLD0:abc", "Raiders", NOW);
+        l.game_ids = vec![7, 42, 7];
+        assert_eq!(l.shards(), vec![Shard::All, Shard::Game(7), Shard::Game(42)]);
+        assert_eq!(Shard::Game(7).label(), "game:7");
+    }
+
+    #[test]
+    fn game_shards_drop_listings_no_longer_tagged() {
+        let l = listing("VLD0:abc", "Raiders", NOW);
+        assert!(Shard::All.includes(&l));
+        assert!(Shard::Game(42).includes(&l));
+        assert!(!Shard::Game(7).includes(&l));
+    }
+
+    #[test]
+    fn each_listing_key_takes_one_slot() {
+        let flooder = Identity::generate();
+        let a = Identity::generate();
+        let b = Identity::generate();
+        let mut entries = vec![
+            ListingPointer::signed(&a, "VLD0:rec-a-old"),
+            ListingPointer::signed(&a, "VLD0:rec-a"),
+            ListingPointer::signed(&b, "VLD0:rec-b"),
+        ];
+        entries.extend((0..100).map(|i| ListingPointer::signed(&flooder, &format!("VLD0:junk{i}"))));
+
+        let newest = newest_pointers(entries.into_iter(), 10);
+        assert_eq!(newest.len(), 3);
+        assert_eq!(newest[0].listing_key, flooder.public_key_hex());
+        assert_eq!(newest[1].record_key, "VLD0:rec-b");
+        assert_eq!(newest[2].record_key, "VLD0:rec-a");
+
+        let capped = newest_pointers(vec![ListingPointer::signed(&a, "VLD0:rec-a")].into_iter(), 0);
+        assert!(capped.is_empty());
+    }
+
+    #[test]
+    fn pointers_not_signed_by_their_key_are_skipped() {
+        let owner = Identity::generate();
+        let genuine = ListingPointer::signed(&owner, "VLD0:rec");
+
+        // Someone else's pointer relabelled, or a genuine one redirected,
+        // must not hide the genuine one
+        let mut relabelled = ListingPointer::signed(&Identity::generate(), "VLD0:elsewhere");
+        relabelled.listing_key = owner.public_key_hex();
+        let mut redirected = genuine.clone();
+        redirected.record_key = "VLD0:elsewhere".to_string();
+        assert!(!relabelled.verify());
+        assert!(!redirected.verify());
+
+        let newest = newest_pointers(vec![genuine.clone(), relabelled, redirected].into_iter(), 10);
+        assert_eq!(newest, vec![genuine]);
+    }
+}
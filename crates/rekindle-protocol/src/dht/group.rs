@@ -0,0 +1,131 @@
+use veilid_core::{
+    DHTSchema, KeyPair, RecordKey, RoutingContext, ValueSubkeyRangeSet, CRYPTO_KIND_VLD0,
+};
+
+use crate::capnp_codec::group::{decode_group_header, encode_group_header, GroupHeader};
+use crate::error::ProtocolError;
+use rekindle_crypto::DhtRecordKey;
+
+/// The shared DHT record of a group conversation.
+///
+/// Unlike a [`ConversationRecord`](super::conversation::ConversationRecord),
+/// which each party owns alone, one record serves the whole group: the
+/// creator hands its owner keypair and a random [`DhtRecordKey`] to every
+/// member, so whoever adds a member or leaves can rewrite the roster.
+/// Subkey 0 holds the encrypted [`GroupHeader`]; messages never touch it.
+pub struct GroupRecord {
+    routing_context: RoutingContext,
+    record_key: RecordKey,
+    encryption_key: DhtRecordKey,
+}
+
+impl GroupRecord {
+    /// Create the record and write the initial roster.
+    ///
+    /// Returns the record and the owner keypair to share with members.
+    pub async fn create(
+        rc: &RoutingContext,
+        encryption_key: DhtRecordKey,
+        header: &GroupHeader,
+    ) -> Result<(Self, KeyPair), ProtocolError> {
+        let schema = DHTSchema::dflt(1)
+            .map_err(|e| ProtocolError::DhtError(format!("invalid schema: {e}")))?;
+
+        let descriptor = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, schema, None)
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("create group record: {e}")))?;
+
+        let key = descriptor.key().clone();
+        let keypair = descriptor
+            .owner_secret()
+            .map(|secret| KeyPair::new_from_parts(descriptor.owner().clone(), secret.value()))
+            .ok_or_else(|| ProtocolError::DhtError("no owner secret after create".into()))?;
+
+        let record = Self {
+            routing_context: rc.clone(),
+            record_key: key,
+            encryption_key,
+        };
+        record.write_header(header).await?;
+
+        tracing::debug!(key = %record.record_key, "GroupRecord created");
+        Ok((record, keypair))
+    }
+
+    /// Open an existing group record with write access.
+    pub async fn open(
+        rc: &RoutingContext,
+        key: &str,
+        owner_keypair: KeyPair,
+        encryption_key: DhtRecordKey,
+    ) -> Result<Self, ProtocolError> {
+        let record_key: RecordKey = key
+            .parse()
+            .map_err(|e| ProtocolError::DhtError(format!("invalid key '{key}': {e}")))?;
+
+        let _ = rc
+            .open_dht_record(record_key.clone(), Some(owner_keypair))
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("open group record: {e}")))?;
+
+        tracing::debug!(key, "GroupRecord opened");
+
+        Ok(Self {
+            routing_context: rc.clone(),
+            record_key,
+            encryption_key,
+        })
+    }
+
+    /// Read and decrypt the roster, fetching the latest copy from the network.
+    pub async fn read_header(&self) -> Result<GroupHeader, ProtocolError> {
+        let value = self
+            .routing_context
+            .get_dht_value(self.record_key.clone(), 0, true)
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("read group header: {e}")))?
+            .ok_or_else(|| ProtocolError::DhtError("group header not set".into()))?;
+
+        let plaintext = self.encryption_key.decrypt(value.data())?;
+        decode_group_header(&plaintext)
+    }
+
+    /// Encrypt and write a new roster.
+    pub async fn write_header(&self, header: &GroupHeader) -> Result<(), ProtocolError> {
+        let plaintext = encode_group_header(header);
+        let ciphertext = self.encryption_key.encrypt(&plaintext)?;
+        self.routing_context
+            .set_dht_value(self.record_key.clone(), 0, ciphertext, None)
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("write group header: {e}")))?;
+        Ok(())
+    }
+
+    /// Watch the roster for changes made by other members.
+    pub async fn watch(&self) -> Result<bool, ProtocolError> {
+        let subkeys: ValueSubkeyRangeSet = [0u32].iter().copied().collect();
+        let active = self
+            .routing_context
+            .watch_dht_values(self.record_key.clone(), Some(subkeys), None, None)
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("watch group record: {e}")))?;
+
+        tracing::debug!(key = %self.record_key, "GroupRecord watch requested");
+        Ok(active)
+    }
+
+    /// Close the underlying DHT record.
+    pub async fn close(&self) -> Result<(), ProtocolError> {
+        self.routing_context
+            .close_dht_record(self.record_key.clone())
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("close group record: {e}")))?;
+        Ok(())
+    }
+
+    /// Get the record key as a string.
+    pub fn record_key(&self) -> String {
+        self.record_key.to_string()
+    }
+}
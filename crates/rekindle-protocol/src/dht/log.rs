@@ -46,13 +46,96 @@ impl DHTLog {
     pub async fn create(
         rc: &RoutingContext,
     ) -> Result<(Self, KeyPair), ProtocolError> {
+        Self::create_with_capacity(rc, DEFAULT_SEGMENT_CAPACITY).await
+    }
+
+    /// Create a new empty `DHTLog` whose segments hold `segment_capacity`
+    /// entries each.
+    ///
+    /// Use a smaller capacity for large entries: each segment is one DHT
+    /// record, and Veilid caps a record's total size.
+    pub async fn create_with_capacity(
+        rc: &RoutingContext,
+        segment_capacity: u16,
+    ) -> Result<(Self, KeyPair), ProtocolError> {
+        Self::create_inner(rc, segment_capacity, None).await
+    }
+
+    /// Create a new empty `DHTLog` owned by an existing keypair, so a
+    /// writer that already persists `owner` need not persist another.
+    pub async fn create_with_owner(
+        rc: &RoutingContext,
+        segment_capacity: u16,
+        owner: KeyPair,
+    ) -> Result<Self, ProtocolError> {
+        let (log, _) = Self::create_inner(rc, segment_capacity, Some(owner)).await?;
+        Ok(log)
+    }
+
+    /// Open the log owned by `owner`, creating it if nobody has yet.
+    ///
+    /// Veilid derives the spine's key from its owner, so a log whose owner
+    /// is derived from a well-known label can be found — and written — by
+    /// anyone who knows the label, without sharing its key.
+    pub async fn open_or_create_with_owner(
+        rc: &RoutingContext,
+        segment_capacity: u16,
+        owner: KeyPair,
+    ) -> Result<Self, ProtocolError> {
         let schema = DHTSchema::dflt(1)
             .map_err(|e| {
                 ProtocolError::DhtError(format!("invalid schema: {e}"))
             })?;
 
         let descriptor = rc
-            .create_dht_record(CRYPTO_KIND_VLD0, schema, None)
+            .create_dht_record(CRYPTO_KIND_VLD0, schema, Some(owner.clone()))
+            .await
+            .map_err(|e| {
+                ProtocolError::DhtError(format!("create log spine: {e}"))
+            })?;
+        let key = descriptor.key().clone();
+
+        let existing = rc
+            .get_dht_value(key.clone(), 0, true)
+            .await
+            .map_err(|e| {
+                ProtocolError::DhtError(format!("read spine: {e}"))
+            })?;
+        if existing.is_none() {
+            let spine = LogSpine {
+                total_count: 0,
+                segment_capacity,
+                segments: Vec::new(),
+            };
+            let spine_bytes = serde_json::to_vec(&spine)
+                .map_err(|e| ProtocolError::Serialization(e.to_string()))?;
+            rc.set_dht_value(key.clone(), 0, spine_bytes, None)
+                .await
+                .map_err(|e| {
+                    ProtocolError::DhtError(format!("write spine: {e}"))
+                })?;
+            tracing::debug!(key = %key, "DHTLog created");
+        }
+
+        Ok(Self {
+            routing_context: rc.clone(),
+            spine_key: key,
+            owner_keypair: Some(owner),
+        })
+    }
+
+    async fn create_inner(
+        rc: &RoutingContext,
+        segment_capacity: u16,
+        owner: Option<KeyPair>,
+    ) -> Result<(Self, KeyPair), ProtocolError> {
+        let schema = DHTSchema::dflt(1)
+            .map_err(|e| {
+                ProtocolError::DhtError(format!("invalid schema: {e}"))
+            })?;
+
+        let descriptor = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, schema, owner)
             .await
             .map_err(|e| {
                 ProtocolError::DhtError(format!("create log spine: {e}"))
@@ -75,7 +158,7 @@ impl DHTLog {
 
         let spine = LogSpine {
             total_count: 0,
-            segment_capacity: DEFAULT_SEGMENT_CAPACITY,
+            segment_capacity,
             segments: Vec::new(),
         };
         let spine_bytes = serde_json::to_vec(&spine)
@@ -269,15 +352,26 @@ impl DHTLog {
     pub async fn tail(
         &self,
         count: u32,
+    ) -> Result<Vec<Vec<u8>>, ProtocolError> {
+        let total = self.len().await?;
+        self.range(total.saturating_sub(u64::from(count)), total).await
+    }
+
+    /// Read the entries at positions `start..end`, oldest first.
+    ///
+    /// Stops early at the end of the log.
+    pub async fn range(
+        &self,
+        start: u64,
+        end: u64,
     ) -> Result<Vec<Vec<u8>>, ProtocolError> {
         let spine = self.read_spine().await?;
-        let total = spine.total_count;
+        let total = spine.total_count.min(end);
 
-        if total == 0 || count == 0 {
+        if start >= total {
             return Ok(Vec::new());
         }
 
-        let start = total.saturating_sub(u64::from(count));
         let result_count = usize::try_from(total - start).unwrap_or(usize::MAX);
         let mut results = Vec::with_capacity(result_count);
         let cap = u64::from(spine.segment_capacity);
@@ -8,6 +8,12 @@ pub const MAILBOX_SUBKEY_ROUTE_BLOB: u32 = 0;
 /// Total subkey count for a mailbox record.
 pub const MAILBOX_SUBKEY_COUNT: u16 = 1;
 
+/// Subkey index for the prekey bundle in a linked device's mailbox.
+pub const DEVICE_MAILBOX_SUBKEY_PREKEY_BUNDLE: u32 = 1;
+
+/// Total subkey count for a linked device's mailbox record.
+pub const DEVICE_MAILBOX_SUBKEY_COUNT: u16 = 2;
+
 /// Create the mailbox DHT record using the identity keypair as owner.
 ///
 /// The mailbox record key is deterministic for a given identity keypair because
@@ -32,6 +38,28 @@ pub async fn create_mailbox(
     Ok(key_string)
 }
 
+/// Create the mailbox DHT record of a linked device, owned by the device key.
+///
+/// Besides the route blob it carries the device's own prekey bundle, since
+/// a linked device can't publish into the identity's profile record without
+/// clobbering the primary device's.
+pub async fn create_device_mailbox(
+    rc: &RoutingContext,
+    device_keypair: veilid_core::KeyPair,
+) -> Result<String, ProtocolError> {
+    let schema = DHTSchema::dflt(DEVICE_MAILBOX_SUBKEY_COUNT)
+        .map_err(|e| ProtocolError::DhtError(format!("invalid device mailbox schema: {e}")))?;
+
+    let descriptor = rc
+        .create_dht_record(CRYPTO_KIND_VLD0, schema, Some(device_keypair))
+        .await
+        .map_err(|e| ProtocolError::DhtError(format!("create_device_mailbox: {e}")))?;
+
+    let key_string = descriptor.key().to_string();
+    tracing::info!(key = %key_string, "created device mailbox DHT record");
+    Ok(key_string)
+}
+
 /// Open an existing mailbox for writing (our own).
 ///
 /// Must be called on each login to regain write access to the mailbox record.
@@ -98,3 +126,50 @@ pub async fn update_mailbox_route(
     tracing::debug!(key = %mailbox_key, "updated mailbox route blob");
     Ok(())
 }
+
+/// Publish our prekey bundle in a linked device's mailbox.
+pub async fn update_device_mailbox_prekey_bundle(
+    rc: &RoutingContext,
+    mailbox_key: &str,
+    prekey_bundle: &[u8],
+) -> Result<(), ProtocolError> {
+    let record_key = mailbox_key.parse().map_err(|e| {
+        ProtocolError::DhtError(format!("invalid mailbox key '{mailbox_key}': {e}"))
+    })?;
+
+    rc.set_dht_value(
+        record_key,
+        DEVICE_MAILBOX_SUBKEY_PREKEY_BUNDLE,
+        prekey_bundle.to_vec(),
+        None,
+    )
+    .await
+    .map_err(|e| ProtocolError::DhtError(format!("update_device_mailbox_prekey_bundle: {e}")))?;
+
+    tracing::debug!(key = %mailbox_key, "updated device mailbox prekey bundle");
+    Ok(())
+}
+
+/// Read the prekey bundle from a peer device's mailbox.
+///
+/// Returns `None` if the device hasn't published one yet.
+pub async fn read_device_mailbox_prekey_bundle(
+    rc: &RoutingContext,
+    mailbox_key: &str,
+) -> Result<Option<Vec<u8>>, ProtocolError> {
+    let record_key: veilid_core::RecordKey = mailbox_key.parse().map_err(|e| {
+        ProtocolError::DhtError(format!("invalid mailbox key '{mailbox_key}': {e}"))
+    })?;
+
+    let _ = rc
+        .open_dht_record(record_key.clone(), None)
+        .await
+        .map_err(|e| ProtocolError::DhtError(format!("open device mailbox: {e}")))?;
+
+    let value = rc
+        .get_dht_value(record_key, DEVICE_MAILBOX_SUBKEY_PREKEY_BUNDLE, true)
+        .await
+        .map_err(|e| ProtocolError::DhtError(format!("read device mailbox prekey bundle: {e}")))?;
+
+    Ok(value.map(|v| v.data().to_vec()).filter(|b| !b.is_empty()))
+}
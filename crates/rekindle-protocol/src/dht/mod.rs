@@ -2,9 +2,12 @@ pub mod account;
 pub mod channel;
 pub mod community;
 pub mod conversation;
+pub mod directory;
 pub mod friends;
+pub mod group;
 pub mod log;
 pub mod mailbox;
+pub mod paged_list;
 pub mod presence;
 pub mod profile;
 pub mod short_array;
@@ -62,6 +65,11 @@ impl DHTManager {
         }
     }
 
+    /// The routing context DHT operations go through.
+    pub fn routing_context(&self) -> &RoutingContext {
+        &self.routing_context
+    }
+
     /// Create a new DHT record with DFLT schema (single owner).
     ///
     /// Returns `(record_key, owner_keypair)`. The `owner_keypair` is the randomly
@@ -148,18 +156,32 @@ impl DHTManager {
         key: &str,
         writer: veilid_core::KeyPair,
     ) -> Result<(), ProtocolError> {
+        self.open_record_writable_subkeys(key, writer).await.map(|_| ())
+    }
+
+    /// Open a record we own, like [`open_record_writable`], and return the
+    /// number of subkeys in its schema.
+    ///
+    /// A record keeps the subkey count it was created with, so this is how
+    /// callers spot records that predate a layout change.
+    pub async fn open_record_writable_subkeys(
+        &self,
+        key: &str,
+        writer: veilid_core::KeyPair,
+    ) -> Result<u32, ProtocolError> {
         let record_key = key
             .parse()
             .map_err(|e| ProtocolError::DhtError(format!("invalid record key '{key}': {e}")))?;
 
-        let _descriptor = self
+        let descriptor = self
             .routing_context
             .open_dht_record(record_key, Some(writer))
             .await
             .map_err(|e| ProtocolError::DhtError(format!("open_dht_record (writable): {e}")))?;
+        let subkey_count = descriptor.schema().max_subkey().saturating_add(1);
 
-        tracing::debug!(key, "opened DHT record (writable)");
-        Ok(())
+        tracing::debug!(key, subkey_count, "opened DHT record (writable)");
+        Ok(subkey_count)
     }
 
     /// Close a DHT record.
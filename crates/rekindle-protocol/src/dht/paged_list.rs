@@ -0,0 +1,793 @@
+//! Lists too large for one DHT value: pages plus a change log.
+//!
+//! The list's own subkey holds only a [`ListHead`]. The entries are packed
+//! into pages of at most [`MAX_PAGE_BYTES`], kept in a chain of
+//! [`DHTShortArray`] records, and every change since the pages were last
+//! rebuilt is appended to a [`DHTLog`]. Each change bumps the head's
+//! revision, so watchers of the subkey still hear about it; a reader that
+//! holds revision `r` reads only the changes after `r`, anyone else reads
+//! the pages.
+//!
+//! A community's channels, members and roles are stored this way. A subkey
+//! that does not parse as a head still holds its whole list in one value,
+//! the layout from before [`LIST_LAYOUT_VERSION`]; [`ListWriter`] replaces
+//! it with a head the first time it publishes.
+//!
+//! Veilid derives a record's key from its owner and schema, so the page
+//! records and change logs cannot share the list owner's keypair: every
+//! list on the record would get the same keys. Each gets an owner of its
+//! own instead, derived from the list owner and a random seed kept in the
+//! head (see [`record_owner`]), so only the list owner can write them.
+
+use std::collections::{BTreeSet, HashMap};
+
+use rekindle_crypto::Identity;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use veilid_core::{
+    BarePublicKey, BareSecretKey, KeyPair, PublicKey, RecordKey, RoutingContext, CRYPTO_KIND_VLD0,
+};
+
+use crate::dht::log::DHTLog;
+use crate::dht::short_array::DHTShortArray;
+use crate::error::ProtocolError;
+
+/// Layout version in [`ListHead::version`]. The single-value layout it
+/// replaces was version 1.
+pub const LIST_LAYOUT_VERSION: u32 = 2;
+
+/// Largest encoded page, well under Veilid's 32 KiB subkey limit.
+pub const MAX_PAGE_BYTES: usize = 16 * 1024;
+
+/// Pages per page record, keeping each record under Veilid's 1 MiB limit.
+const PAGES_PER_RECORD: u16 = 60;
+
+/// Page records one list may chain.
+const MAX_PAGE_RECORDS: usize = 64;
+
+/// Changes per change log segment. Changes carry whole entries, so this is
+/// sized like the pages.
+const CHANGES_PER_SEGMENT: u16 = 32;
+
+/// Changes logged before the writer starts a fresh log, after which
+/// readers re-read the pages once.
+const MAX_LOGGED_CHANGES: u64 = 4096;
+
+/// Changes a reader applies before re-reading the pages is cheaper.
+const MAX_CATCH_UP: u64 = 256;
+
+/// What a list subkey holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListHead {
+    pub version: u32,
+    /// Changes made to the list since it was created.
+    pub revision: u64,
+    /// Number of entries.
+    pub count: u32,
+    /// Number of pages.
+    pub page_count: u32,
+    /// `DHTShortArray` keys holding the pages, in order.
+    pub page_records: Vec<String>,
+    /// Owner seed of each page record, in the same order.
+    #[serde(default)]
+    pub page_seeds: Vec<String>,
+    /// Spine key of the change log.
+    pub changes_key: String,
+    /// Owner seed of the change log.
+    #[serde(default)]
+    pub changes_seed: String,
+    /// Revision the change log starts at: its entry `i` takes the list
+    /// from revision `changes_base + i` to the next.
+    pub changes_base: u64,
+    /// When the head was last written, so a keepalive write changes it.
+    #[serde(default)]
+    pub last_refreshed: u64,
+}
+
+impl ListHead {
+    /// Parse a list subkey. `None` means it holds a single-value list.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice::<Self>(data)
+            .ok()
+            .filter(|head| head.version == LIST_LAYOUT_VERSION)
+    }
+
+    fn empty() -> Self {
+        Self {
+            version: LIST_LAYOUT_VERSION,
+            revision: 0,
+            count: 0,
+            page_count: 0,
+            page_records: Vec::new(),
+            page_seeds: Vec::new(),
+            changes_key: String::new(),
+            changes_seed: String::new(),
+            changes_base: 0,
+            last_refreshed: 0,
+        }
+    }
+
+    /// Whether every page record and the change log have an owner seed.
+    /// Heads written before seeds were kept had all of them owned by the
+    /// list owner.
+    fn has_seeds(&self) -> bool {
+        self.page_seeds.len() == self.page_records.len()
+            && (self.changes_key.is_empty() || !self.changes_seed.is_empty())
+    }
+}
+
+/// HKDF info label for [`record_owner`].
+const RECORD_OWNER_INFO: &[u8] = b"rekindle-list-record-v2";
+
+/// Owner of a page record or change log of the list in `subkey`, derived
+/// from the list owner's secret key and the record's seed.
+pub fn record_owner(list_owner: &KeyPair, subkey: u32, seed: &str) -> KeyPair {
+    // Secret and subkey have fixed lengths, so the seed can simply follow
+    let secret = list_owner.bare_secret();
+    let mut ikm = Vec::with_capacity(secret.len() + 4 + seed.len());
+    ikm.extend_from_slice(&secret);
+    ikm.extend_from_slice(&subkey.to_be_bytes());
+    ikm.extend_from_slice(seed.as_bytes());
+    let identity = Identity::derive(&ikm, RECORD_OWNER_INFO);
+    let bare_pub = BarePublicKey::new(&identity.public_key_bytes());
+    let bare_secret = BareSecretKey::new(identity.secret_key_bytes());
+    KeyPair::new_from_parts(PublicKey::new(CRYPTO_KIND_VLD0, bare_pub), bare_secret)
+}
+
+/// A random seed for a new record's owner.
+fn new_seed() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+/// One entry of a list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListEntry {
+    /// Identifies the entry within its list, e.g. a channel ID.
+    pub id: String,
+    pub entry: serde_json::Value,
+}
+
+impl ListEntry {
+    pub fn new<T: Serialize>(id: impl Into<String>, value: &T) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            id: id.into(),
+            entry: serde_json::to_value(value)
+                .map_err(|e| ProtocolError::Serialization(e.to_string()))?,
+        })
+    }
+
+    /// Deserialize the entry as `T`.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, ProtocolError> {
+        T::deserialize(&self.entry).map_err(|e| ProtocolError::Deserialization(e.to_string()))
+    }
+}
+
+/// One change in the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum ListChange {
+    /// Add the entry, or replace the one with its ID.
+    Upsert(ListEntry),
+    /// Remove the entry with this ID.
+    Remove { id: String },
+}
+
+/// Apply `changes` to `entries`, in order.
+pub fn apply_changes(entries: &mut Vec<ListEntry>, changes: &[ListChange]) {
+    for change in changes {
+        match change {
+            ListChange::Upsert(new) => match entries.iter_mut().find(|e| e.id == new.id) {
+                Some(existing) => existing.entry.clone_from(&new.entry),
+                None => entries.push(new.clone()),
+            },
+            ListChange::Remove { id } => entries.retain(|e| &e.id != id),
+        }
+    }
+}
+
+/// How the writer's entries are packed into pages.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListLayout {
+    pages: Vec<Vec<ListEntry>>,
+}
+
+/// What [`ListLayout::update`] changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct LayoutUpdate {
+    /// The changes, in the order they go in the log.
+    pub changes: Vec<ListChange>,
+    /// Pages whose contents changed, ascending.
+    pub dirty: Vec<usize>,
+}
+
+impl ListLayout {
+    pub fn pages(&self) -> &[Vec<ListEntry>] {
+        &self.pages
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.pages.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.iter().all(Vec::is_empty)
+    }
+
+    /// Every entry, page by page.
+    pub fn entries(&self) -> Vec<ListEntry> {
+        self.pages.iter().flatten().cloned().collect()
+    }
+
+    /// Make the list hold exactly `entries`.
+    ///
+    /// Entries stay on the page they are on unless they outgrow it, so
+    /// only pages that gained, lost or changed an entry are dirty. New
+    /// entries go on the first page with room; trailing empty pages are
+    /// dropped.
+    pub fn update(&mut self, entries: Vec<ListEntry>) -> Result<LayoutUpdate, ProtocolError> {
+        let mut order = Vec::with_capacity(entries.len());
+        let mut wanted = HashMap::with_capacity(entries.len());
+        for ListEntry { id, entry } in entries {
+            order.push(id.clone());
+            wanted.insert(id, entry);
+        }
+
+        let mut pages = self.pages.clone();
+        let mut changes = Vec::new();
+        let mut dirty = BTreeSet::new();
+        let mut displaced = Vec::new();
+        for (index, page) in pages.iter_mut().enumerate() {
+            let before = page.len();
+            let mut changed = false;
+            page.retain_mut(|e| match wanted.remove(&e.id) {
+                Some(value) => {
+                    if value != e.entry {
+                        e.entry = value;
+                        changes.push(ListChange::Upsert(e.clone()));
+                        changed = true;
+                    }
+                    true
+                }
+                None => {
+                    changes.push(ListChange::Remove { id: e.id.clone() });
+                    false
+                }
+            });
+            if changed || page.len() != before {
+                dirty.insert(index);
+            }
+            // An entry that grew may push the page over the limit
+            while page_len(page) > MAX_PAGE_BYTES {
+                let Some(entry) = page.pop() else { break };
+                displaced.push(entry);
+                dirty.insert(index);
+            }
+        }
+
+        for id in order {
+            if let Some(entry) = wanted.remove(&id) {
+                let entry = ListEntry { id, entry };
+                changes.push(ListChange::Upsert(entry.clone()));
+                displaced.push(entry);
+            }
+        }
+        for entry in displaced {
+            let len = entry_len(&entry);
+            if len + 2 > MAX_PAGE_BYTES {
+                return Err(ProtocolError::Serialization(format!(
+                    "list entry {} is {len} bytes, pages hold {MAX_PAGE_BYTES}",
+                    entry.id
+                )));
+            }
+            let index = if let Some(i) = pages.iter().position(|p| page_len(p) + len <= MAX_PAGE_BYTES) {
+                i
+            } else {
+                pages.push(Vec::new());
+                pages.len() - 1
+            };
+            pages[index].push(entry);
+            dirty.insert(index);
+        }
+
+        while pages.last().is_some_and(Vec::is_empty) {
+            pages.pop();
+        }
+        let max_pages = usize::from(PAGES_PER_RECORD) * MAX_PAGE_RECORDS;
+        if pages.len() > max_pages {
+            return Err(ProtocolError::DhtError(format!(
+                "list needs {} pages, the limit is {max_pages}",
+                pages.len()
+            )));
+        }
+        dirty.retain(|&i| i < pages.len());
+
+        self.pages = pages;
+        Ok(LayoutUpdate {
+            changes,
+            dirty: dirty.into_iter().collect(),
+        })
+    }
+}
+
+/// Encoded size of an entry within a page, counting its separator.
+fn entry_len(entry: &ListEntry) -> usize {
+    serde_json::to_vec(entry).map_or(0, |b| b.len()) + 1
+}
+
+/// Encoded size of a page.
+fn page_len(page: &[ListEntry]) -> usize {
+    2 + page.iter().map(entry_len).sum::<usize>()
+}
+
+fn decode_page(data: &[u8]) -> Result<Vec<ListEntry>, ProtocolError> {
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_slice(data).map_err(|e| ProtocolError::Deserialization(format!("list page: {e}")))
+}
+
+/// Read every page of the list.
+async fn read_pages(rc: &RoutingContext, head: &ListHead) -> Result<Vec<Vec<ListEntry>>, ProtocolError> {
+    let page_count = head.page_count as usize;
+    let mut pages = Vec::with_capacity(page_count);
+    for key in &head.page_records {
+        let record = DHTShortArray::open(rc, key, None).await?;
+        for data in record.get_all().await? {
+            if pages.len() == page_count {
+                break;
+            }
+            pages.push(decode_page(&data)?);
+        }
+    }
+    if pages.len() != page_count {
+        return Err(ProtocolError::DhtError(format!(
+            "list has {} of {page_count} pages",
+            pages.len()
+        )));
+    }
+    Ok(pages)
+}
+
+/// Read every entry of the list, page by page.
+pub async fn read_entries(rc: &RoutingContext, head: &ListHead) -> Result<Vec<ListEntry>, ProtocolError> {
+    Ok(read_pages(rc, head).await?.into_iter().flatten().collect())
+}
+
+/// Read the changes that take the list from revision `since` to the head's.
+///
+/// `None` when they are no longer logged, too many to be worth applying,
+/// or not all readable yet — read the pages instead.
+pub async fn read_changes(
+    rc: &RoutingContext,
+    head: &ListHead,
+    since: u64,
+) -> Result<Option<Vec<ListChange>>, ProtocolError> {
+    if since < head.changes_base || since > head.revision || head.revision - since > MAX_CATCH_UP {
+        return Ok(None);
+    }
+    if since == head.revision {
+        return Ok(Some(Vec::new()));
+    }
+    let log = DHTLog::open_read(rc, &head.changes_key).await?;
+    let entries = log
+        .range(since - head.changes_base, head.revision - head.changes_base)
+        .await?;
+    if entries.len() as u64 != head.revision - since {
+        return Ok(None);
+    }
+    entries
+        .iter()
+        .map(|data| {
+            serde_json::from_slice(data)
+                .map_err(|e| ProtocolError::Deserialization(format!("list change: {e}")))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// What a reader learned from a list subkey.
+#[derive(Debug)]
+pub enum ListSync {
+    /// The subkey holds the whole list in one value, in the layout from
+    /// before [`LIST_LAYOUT_VERSION`].
+    Legacy(Vec<u8>),
+    /// The list is still at the reader's revision.
+    Unchanged,
+    /// The changes since the reader's revision.
+    Changes { revision: u64, changes: Vec<ListChange> },
+    /// Every entry.
+    Full { revision: u64, entries: Vec<ListEntry> },
+}
+
+/// Bring a reader up to date from the value of a list subkey, given the
+/// revision it last applied.
+pub async fn sync(rc: &RoutingContext, value: &[u8], since: Option<u64>) -> Result<ListSync, ProtocolError> {
+    let Some(head) = ListHead::parse(value) else {
+        return Ok(ListSync::Legacy(value.to_vec()));
+    };
+    if let Some(since) = since {
+        if since == head.revision {
+            return Ok(ListSync::Unchanged);
+        }
+        if let Some(changes) = read_changes(rc, &head, since).await? {
+            return Ok(ListSync::Changes {
+                revision: head.revision,
+                changes,
+            });
+        }
+    }
+    Ok(ListSync::Full {
+        revision: head.revision,
+        entries: read_entries(rc, &head).await?,
+    })
+}
+
+/// Publishes a list to one subkey of a record we own.
+///
+/// Keep the writer for as long as the list is published: it remembers what
+/// the pages hold, so each [`publish`](Self::publish) writes only what
+/// changed.
+pub struct ListWriter {
+    routing_context: RoutingContext,
+    record_key: RecordKey,
+    subkey: u32,
+    owner: KeyPair,
+    head: Option<ListHead>,
+    layout: ListLayout,
+    /// Whether `layout` matches the published pages. Until it does, the
+    /// next publish rewrites every page and starts a fresh change log.
+    in_sync: bool,
+}
+
+impl ListWriter {
+    /// Pick up the list in `subkey` of `record_key`. The record must be
+    /// open for writing with `owner`, from which the page and change
+    /// records' owners are derived.
+    ///
+    /// An existing head's pages are read back so the next publish can be
+    /// a diff. A head without owner seeds gets fresh records instead.
+    pub async fn open(
+        rc: &RoutingContext,
+        record_key: &str,
+        subkey: u32,
+        owner: KeyPair,
+    ) -> Result<Self, ProtocolError> {
+        let record: RecordKey = record_key
+            .parse()
+            .map_err(|e| ProtocolError::DhtError(format!("invalid key '{record_key}': {e}")))?;
+        let value = rc
+            .get_dht_value(record.clone(), subkey, false)
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("read list head: {e}")))?;
+        let mut head = value.and_then(|v| ListHead::parse(v.data()));
+
+        let mut layout = ListLayout::default();
+        let mut in_sync = false;
+        match head.as_mut() {
+            Some(head) if !head.has_seeds() => {
+                // Its records may be another list's too, so none are reused
+                head.page_records.clear();
+                head.page_seeds.clear();
+                head.page_count = 0;
+                head.changes_key.clear();
+                head.changes_seed.clear();
+            }
+            Some(head) => match read_pages(rc, head).await {
+                Ok(pages) => {
+                    layout.pages = pages;
+                    in_sync = true;
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, key = record_key, subkey, "failed to read list pages — will rewrite them");
+                }
+            },
+            None => {}
+        }
+
+        Ok(Self {
+            routing_context: rc.clone(),
+            record_key: record,
+            subkey,
+            owner,
+            head,
+            layout,
+            in_sync,
+        })
+    }
+
+    /// Whether the subkey holds a head, rather than a single-value list
+    /// still to be migrated.
+    pub fn is_paged(&self) -> bool {
+        self.head.is_some()
+    }
+
+    /// The entries as last published.
+    pub fn entries(&self) -> Vec<ListEntry> {
+        self.layout.entries()
+    }
+
+    /// Publish `entries` as the whole list.
+    ///
+    /// Writes the pages that changed, logs the changes and rewrites the
+    /// head — even when nothing changed, so this doubles as a keepalive.
+    pub async fn publish(&mut self, entries: Vec<ListEntry>) -> Result<(), ProtocolError> {
+        let rebuild = !self.in_sync;
+        let mut layout = if rebuild { ListLayout::default() } else { self.layout.clone() };
+        let update = layout.update(entries)?;
+        let mut head = self.head.clone().unwrap_or_else(ListHead::empty);
+
+        // Whatever fails from here leaves pages we can't vouch for
+        self.in_sync = false;
+
+        let dirty: Vec<usize> = if rebuild { (0..layout.pages.len()).collect() } else { update.dirty };
+        for index in dirty {
+            self.write_page(&mut head, index, &layout.pages[index]).await?;
+        }
+        self.truncate_pages(&mut head, layout.pages.len()).await?;
+        self.log_changes(&mut head, &update.changes, rebuild).await?;
+
+        head.count = u32::try_from(layout.len()).unwrap_or(u32::MAX);
+        head.page_count = u32::try_from(layout.pages.len()).unwrap_or(u32::MAX);
+        head.last_refreshed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let head_bytes = serde_json::to_vec(&head).map_err(|e| ProtocolError::Serialization(e.to_string()))?;
+        self.routing_context
+            .set_dht_value(self.record_key.clone(), self.subkey, head_bytes, None)
+            .await
+            .map_err(|e| ProtocolError::DhtError(format!("write list head: {e}")))?;
+
+        self.head = Some(head);
+        self.layout = layout;
+        self.in_sync = true;
+        Ok(())
+    }
+
+    fn owner_for(&self, seed: &str) -> KeyPair {
+        record_owner(&self.owner, self.subkey, seed)
+    }
+
+    async fn write_page(&self, head: &mut ListHead, index: usize, page: &[ListEntry]) -> Result<(), ProtocolError> {
+        let per_record = usize::from(PAGES_PER_RECORD);
+        let record_index = index / per_record;
+        let slot = u32::try_from(index % per_record).unwrap_or(u32::MAX);
+        let data = serde_json::to_vec(page).map_err(|e| ProtocolError::Serialization(e.to_string()))?;
+
+        // Pages are written in ascending order, so a new page is always
+        // the next slot and a new record the next one in the chain
+        let record = if record_index < head.page_records.len() {
+            let owner = self.owner_for(&head.page_seeds[record_index]);
+            DHTShortArray::open(&self.routing_context, &head.page_records[record_index], Some(owner)).await?
+        } else if record_index == head.page_records.len() {
+            let seed = new_seed();
+            let (record, _) =
+                DHTShortArray::create(&self.routing_context, PAGES_PER_RECORD, Some(self.owner_for(&seed))).await?;
+            head.page_records.push(record.record_key());
+            head.page_seeds.push(seed);
+            record
+        } else {
+            return Err(ProtocolError::DhtError(format!("list page {index} is past the end")));
+        };
+
+        if slot < record.len().await? {
+            record.set(slot, &data).await?;
+        } else {
+            record.add(&data).await?;
+        }
+        Ok(())
+    }
+
+    /// Drop pages past `page_count`, and records left with none.
+    async fn truncate_pages(&self, head: &mut ListHead, page_count: usize) -> Result<(), ProtocolError> {
+        let per_record = usize::from(PAGES_PER_RECORD);
+        if page_count < head.page_count as usize {
+            for record_index in page_count / per_record..head.page_records.len() {
+                let keep = page_count.saturating_sub(record_index * per_record).min(per_record);
+                let record = DHTShortArray::open(
+                    &self.routing_context,
+                    &head.page_records[record_index],
+                    Some(self.owner_for(&head.page_seeds[record_index])),
+                )
+                .await?;
+                let keep = u32::try_from(keep).unwrap_or(u32::MAX);
+                for slot in (keep..record.len().await?).rev() {
+                    record.remove(slot).await?;
+                }
+            }
+        }
+        head.page_records.truncate(page_count.div_ceil(per_record));
+        head.page_seeds.truncate(page_count.div_ceil(per_record));
+        Ok(())
+    }
+
+    /// Append `changes` to the log and advance the revision. Starts a
+    /// fresh log instead when the pages were rebuilt, the log is full, or
+    /// it no longer lines up with the head.
+    async fn log_changes(&self, head: &mut ListHead, changes: &[ListChange], rebuild: bool) -> Result<(), ProtocolError> {
+        let count = changes.len() as u64;
+        if count == 0 && !rebuild && !head.changes_key.is_empty() {
+            return Ok(());
+        }
+        let logged = head.revision - head.changes_base;
+        let log = if rebuild || head.changes_key.is_empty() || logged + count > MAX_LOGGED_CHANGES {
+            None
+        } else {
+            let owner = self.owner_for(&head.changes_seed);
+            let log = DHTLog::open_write(&self.routing_context, &head.changes_key, owner).await?;
+            // A publish that failed after logging leaves entries the head never counted
+            (log.len().await? == logged).then_some(log)
+        };
+
+        let Some(log) = log else {
+            let seed = new_seed();
+            let log =
+                DHTLog::create_with_owner(&self.routing_context, CHANGES_PER_SEGMENT, self.owner_for(&seed)).await?;
+            head.revision += count.max(1);
+            head.changes_base = head.revision;
+            head.changes_key = log.spine_key();
+            head.changes_seed = seed;
+            return Ok(());
+        };
+        for change in changes {
+            let data = serde_json::to_vec(change).map_err(|e| ProtocolError::Serialization(e.to_string()))?;
+            log.append(&data).await?;
+            head.revision += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, name: &str) -> ListEntry {
+        ListEntry {
+            id: id.to_string(),
+            entry: serde_json::json!({ "name": name }),
+        }
+    }
+
+    #[test]
+    fn update_reports_only_what_changed() {
+        let mut layout = ListLayout::default();
+        let first = layout.update(vec![entry("a", "A"), entry("b", "B")]).unwrap();
+        assert_eq!(first.changes.len(), 2);
+        assert_eq!(first.dirty, vec![0]);
+
+        let same = layout.update(vec![entry("a", "A"), entry("b", "B")]).unwrap();
+        assert_eq!(same, LayoutUpdate::default());
+
+        let update = layout.update(vec![entry("b", "Bee"), entry("c", "C")]).unwrap();
+        assert_eq!(
+            update.changes,
+            vec![
+                ListChange::Remove { id: "a".into() },
+                ListChange::Upsert(entry("b", "Bee")),
+                ListChange::Upsert(entry("c", "C")),
+            ]
+        );
+        assert_eq!(layout.entries(), vec![entry("b", "Bee"), entry("c", "C")]);
+    }
+
+    #[test]
+    fn large_lists_span_pages_and_touch_one_page_per_change() {
+        let big = "x".repeat(1000);
+        let entries: Vec<ListEntry> = (0..100).map(|i| entry(&format!("m{i}"), &big)).collect();
+        let mut layout = ListLayout::default();
+        layout.update(entries.clone()).unwrap();
+        assert!(layout.pages().len() > 1);
+        assert!(layout.pages().iter().all(|p| page_len(p) <= MAX_PAGE_BYTES));
+        assert_eq!(layout.len(), 100);
+
+        // Dropping one member rewrites only its page
+        let mut fewer = entries.clone();
+        fewer.remove(50);
+        let update = layout.update(fewer).unwrap();
+        assert_eq!(update.changes, vec![ListChange::Remove { id: "m50".into() }]);
+        assert_eq!(update.dirty.len(), 1);
+
+        // A newcomer fills the gap rather than starting a page
+        let pages = layout.pages().len();
+        let mut more = layout.entries();
+        more.push(entry("new", &big));
+        let update = layout.update(more).unwrap();
+        assert_eq!(update.dirty.len(), 1);
+        assert_eq!(layout.pages().len(), pages);
+    }
+
+    #[test]
+    fn emptied_trailing_pages_are_dropped() {
+        let big = "x".repeat(1000);
+        let entries: Vec<ListEntry> = (0..40).map(|i| entry(&format!("m{i}"), &big)).collect();
+        let mut layout = ListLayout::default();
+        layout.update(entries.clone()).unwrap();
+        assert!(layout.pages().len() > 1);
+
+        layout.update(entries[..1].to_vec()).unwrap();
+        assert_eq!(layout.pages().len(), 1);
+    }
+
+    #[test]
+    fn oversized_entry_is_rejected_without_touching_the_layout() {
+        let mut layout = ListLayout::default();
+        layout.update(vec![entry("a", "A")]).unwrap();
+        let before = layout.clone();
+        assert!(layout.update(vec![entry("a", "A"), entry("huge", &"x".repeat(MAX_PAGE_BYTES))]).is_err());
+        assert_eq!(layout, before);
+    }
+
+    #[test]
+    fn logged_changes_replay_to_the_same_list() {
+        let mut layout = ListLayout::default();
+        layout.update(vec![entry("a", "A"), entry("b", "B")]).unwrap();
+        let mut reader = layout.entries();
+
+        let update = layout.update(vec![entry("b", "Bee"), entry("c", "C")]).unwrap();
+        let encoded: Vec<Vec<u8>> = update.changes.iter().map(|c| serde_json::to_vec(c).unwrap()).collect();
+        let decoded: Vec<ListChange> = encoded.iter().map(|d| serde_json::from_slice(d).unwrap()).collect();
+        apply_changes(&mut reader, &decoded);
+
+        let mut expected = layout.entries();
+        expected.sort_by(|a, b| a.id.cmp(&b.id));
+        reader.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(reader, expected);
+    }
+
+    fn test_owner() -> KeyPair {
+        let identity = Identity::derive(b"test community", b"owner");
+        KeyPair::new_from_parts(
+            PublicKey::new(CRYPTO_KIND_VLD0, BarePublicKey::new(&identity.public_key_bytes())),
+            BareSecretKey::new(identity.secret_key_bytes()),
+        )
+    }
+
+    #[test]
+    fn lists_on_one_record_get_their_own_record_owners() {
+        let owner = test_owner();
+        let channels_seed = new_seed();
+        let members_seed = new_seed();
+        let channels = record_owner(&owner, 2, &channels_seed).to_string();
+        let members = record_owner(&owner, 3, &members_seed).to_string();
+        assert_ne!(channels, members);
+        assert_ne!(channels, owner.to_string());
+        // Even a reused seed gives each list its own page records
+        assert_ne!(
+            record_owner(&owner, 2, "seed").to_string(),
+            record_owner(&owner, 3, "seed").to_string()
+        );
+        // The writer derives the same owner again from the head's seed
+        assert_eq!(record_owner(&owner, 2, &channels_seed).to_string(), channels);
+        // Another list owner's secret gives other record owners
+        let other = Identity::derive(b"other community", b"owner");
+        let other = KeyPair::new_from_parts(
+            PublicKey::new(CRYPTO_KIND_VLD0, BarePublicKey::new(&other.public_key_bytes())),
+            BareSecretKey::new(other.secret_key_bytes()),
+        );
+        assert_ne!(record_owner(&other, 2, &channels_seed).to_string(), channels);
+    }
+
+    #[test]
+    fn heads_without_owner_seeds_are_detected() {
+        let mut head = ListHead::empty();
+        assert!(head.has_seeds());
+        head.page_records.push("VLD0:page".into());
+        head.changes_key = "VLD0:log".into();
+        assert!(!head.has_seeds());
+        head.page_seeds.push(new_seed());
+        head.changes_seed = new_seed();
+        assert!(head.has_seeds());
+    }
+
+    #[test]
+    fn single_value_lists_are_not_heads() {
+        assert!(ListHead::parse(br#"{"channels":[],"lastRefreshed":0}"#).is_none());
+        assert!(ListHead::parse(b"[]").is_none());
+        let mut head = ListHead::empty();
+        head.revision = 3;
+        assert_eq!(ListHead::parse(&serde_json::to_vec(&head).unwrap()), Some(head));
+    }
+}
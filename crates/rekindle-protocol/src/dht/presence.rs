@@ -1,9 +1,10 @@
 use crate::dht::DHTManager;
-use crate::dht::profile::{SUBKEY_GAME_INFO, SUBKEY_ROUTE_BLOB, SUBKEY_STATUS};
+use crate::dht::profile::{SUBKEY_DEVICES, SUBKEY_GAME_INFO, SUBKEY_ROUTE_BLOB, SUBKEY_STATUS};
 use crate::error::ProtocolError;
 
 /// The subkeys we watch for friend presence updates.
-pub const PRESENCE_WATCH_SUBKEYS: &[u32] = &[SUBKEY_STATUS, SUBKEY_GAME_INFO, SUBKEY_ROUTE_BLOB];
+pub const PRESENCE_WATCH_SUBKEYS: &[u32] =
+    &[SUBKEY_STATUS, SUBKEY_GAME_INFO, SUBKEY_ROUTE_BLOB, SUBKEY_DEVICES];
 
 /// Start watching a friend's profile DHT record for presence changes.
 ///
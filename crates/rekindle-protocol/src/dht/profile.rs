@@ -9,9 +9,17 @@ pub const SUBKEY_AVATAR: u32 = 3;
 pub const SUBKEY_GAME_INFO: u32 = 4;
 pub const SUBKEY_PREKEY_BUNDLE: u32 = 5;
 pub const SUBKEY_ROUTE_BLOB: u32 = 6;
-pub const SUBKEY_METADATA: u32 = 7;
+/// JSON list of the identity's linked-device `DeviceCertificate`s.
+pub const SUBKEY_DEVICES: u32 = 7;
+/// First subkey of the one-time prekey pool (one prekey per subkey).
+pub const SUBKEY_ONE_TIME_PREKEYS_START: u32 = 8;
+/// Number of subkeys reserved for the one-time prekey pool.
+pub const ONE_TIME_PREKEY_SLOTS: u32 = 16;
 
-pub const PROFILE_SUBKEY_COUNT: u32 = 8;
+/// Subkeys in a profile record. Records created before the one-time prekey
+/// pool existed stop at `SUBKEY_ONE_TIME_PREKEYS_START` and must be replaced
+/// before a pool can be published.
+pub const PROFILE_SUBKEY_COUNT: u32 = SUBKEY_ONE_TIME_PREKEYS_START + ONE_TIME_PREKEY_SLOTS;
 
 /// Create a new profile DHT record and initialize subkeys.
 ///
@@ -105,3 +113,43 @@ pub async fn read_prekey_bundle(
 ) -> Result<Option<Vec<u8>>, ProtocolError> {
     dht.get_value(profile_key, SUBKEY_PREKEY_BUNDLE).await
 }
+
+/// Publish the one-time prekey pool, one serialized prekey per slot.
+///
+/// Slots beyond `prekeys.len()` are cleared so consumed keys stop being
+/// handed out. Extra prekeys beyond [`ONE_TIME_PREKEY_SLOTS`] are ignored.
+pub async fn publish_one_time_prekeys(
+    dht: &DHTManager,
+    profile_key: &str,
+    prekeys: &[Vec<u8>],
+) -> Result<(), ProtocolError> {
+    for slot in 0..ONE_TIME_PREKEY_SLOTS {
+        let value = usize::try_from(slot)
+            .ok()
+            .and_then(|i| prekeys.get(i))
+            .cloned()
+            .unwrap_or_default();
+        dht.set_value(profile_key, SUBKEY_ONE_TIME_PREKEYS_START + slot, value)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Read one slot of a peer's one-time prekey pool.
+///
+/// Returns `None` for an empty (consumed) slot.
+pub async fn read_one_time_prekey(
+    dht: &DHTManager,
+    profile_key: &str,
+    slot: u32,
+) -> Result<Option<Vec<u8>>, ProtocolError> {
+    if slot >= ONE_TIME_PREKEY_SLOTS {
+        return Err(ProtocolError::DhtError(format!(
+            "one-time prekey slot {slot} out of range"
+        )));
+    }
+    Ok(dht
+        .get_value(profile_key, SUBKEY_ONE_TIME_PREKEYS_START + slot)
+        .await?
+        .filter(|v| !v.is_empty()))
+}
@@ -181,6 +181,30 @@ impl DHTShortArray {
         Ok(value.map(|v| v.data().to_vec()))
     }
 
+    /// Replace the element at the given logical index in place.
+    pub async fn set(&self, index: u32, data: &[u8]) -> Result<(), ProtocolError> {
+        let head = self.read_head().await?;
+        let idx = index as usize;
+
+        if idx >= head.slots.len() {
+            return Err(ProtocolError::DhtError(format!(
+                "index {index} out of bounds (len={})",
+                head.slots.len()
+            )));
+        }
+
+        let slot = head.slots[idx];
+        let subkey = u32::from(slot) + 1;
+        self.routing_context
+            .set_dht_value(self.record_key.clone(), subkey, data.to_vec(), None)
+            .await
+            .map_err(|e| {
+                ProtocolError::DhtError(format!("write slot {slot}: {e}"))
+            })?;
+
+        Ok(())
+    }
+
     /// Remove the element at the given logical index.
     ///
     /// Subsequent elements shift down by one logical index.
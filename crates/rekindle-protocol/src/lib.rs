@@ -5,6 +5,7 @@ pub mod messaging;
 pub mod node;
 pub mod peer;
 pub mod routing;
+pub mod transfer;
 
 pub use dht::log::DHTLog;
 pub use dht::short_array::DHTShortArray;
@@ -52,3 +53,8 @@ pub mod account_capnp {
 pub mod conversation_capnp {
     include!(concat!(env!("OUT_DIR"), "/conversation_capnp.rs"));
 }
+
+#[allow(unused, clippy::all, clippy::pedantic)]
+pub mod group_capnp {
+    include!(concat!(env!("OUT_DIR"), "/group_capnp.rs"));
+}
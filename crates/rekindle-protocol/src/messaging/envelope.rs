@@ -1,6 +1,9 @@
 use base64::Engine as _;
+use rekindle_crypto::group::member_vouch::MemberVouch;
 use serde::{Deserialize, Serialize};
 
+use crate::transfer::FileAttachment;
+
 /// Wire format envelope wrapping all messages sent over Veilid.
 ///
 /// The payload is E2E encrypted (Signal Protocol for DMs, MEK for channels).
@@ -15,8 +18,14 @@ pub struct MessageEnvelope {
     pub nonce: Vec<u8>,
     /// Encrypted payload (ciphertext).
     pub payload: Vec<u8>,
-    /// Ed25519 signature over (timestamp || nonce || payload).
+    /// Ed25519 signature over (timestamp || nonce || payload), followed by
+    /// `sender_device` when that is set.
     pub signature: Vec<u8>,
+    /// Ed25519 key of the linked device that sent this, so the receiver can
+    /// pick that device's Signal session. Empty from an identity's primary
+    /// device and from older clients.
+    #[serde(default)]
+    pub sender_device: Vec<u8>,
 }
 
 /// The type of message contained in the envelope payload (after decryption).
@@ -25,8 +34,16 @@ pub struct MessageEnvelope {
 pub enum MessagePayload {
     /// Direct 1:1 chat message.
     DirectMessage {
+        /// Globally unique ID (see [`new_message_id`]) that later edits,
+        /// deletions and reactions refer to. Empty from older clients.
+        #[serde(default)]
+        message_id: String,
         body: String,
         reply_to: Option<Vec<u8>>,
+        /// Files shared with this message. The chunks live in DHT logs;
+        /// the keys to read them are only ever sent here.
+        #[serde(default)]
+        attachments: Vec<FileAttachment>,
     },
     /// Channel message (community text channel).
     ChannelMessage {
@@ -34,6 +51,19 @@ pub enum MessagePayload {
         body: String,
         reply_to: Option<Vec<u8>>,
     },
+    /// Replace the body of one of our earlier direct messages.
+    EditMessage { message_id: String, body: String },
+    /// Delete one of our earlier direct messages on both sides.
+    DeleteMessage { message_id: String },
+    /// React to a direct message, ours or the peer's.
+    AddReaction { message_id: String, emoji: String },
+    /// Take back one of our reactions.
+    RemoveReaction { message_id: String, emoji: String },
+    /// Our `DirectMessage`s that reached the peer. At most
+    /// [`MAX_RECEIPT_BATCH`] IDs per receipt.
+    DeliveryReceipt { message_ids: Vec<String> },
+    /// Our `DirectMessage`s the peer has read.
+    ReadReceipt { message_ids: Vec<String> },
     /// Typing indicator.
     TypingIndicator { typing: bool },
     /// Friend request.
@@ -82,6 +112,97 @@ pub enum MessagePayload {
     Unfriended,
     /// ACK confirming an `Unfriended` message was received and processed.
     UnfriendedAck,
+    /// Frame key for our side of a 1:1 voice call. Only ever sent
+    /// Signal-encrypted; a plaintext copy is dropped.
+    CallKey {
+        key_id: u64,
+        key: Vec<u8>,
+    },
+    /// First message on a Signal session with one of the recipient's linked
+    /// devices. Carries the X3DH parameters the device needs to answer the
+    /// session, next to the Signal-encrypted payload itself.
+    SessionInit {
+        ephemeral_key: Vec<u8>,
+        signed_prekey_id: u32,
+        one_time_prekey_id: Option<u32>,
+        /// Signal ciphertext of the wrapped `MessagePayload`.
+        ciphertext: Vec<u8>,
+    },
+    /// Answer to a `SessionInit` naming a one-time prekey another initiator
+    /// already used. The initiator resends its payload on a session from the
+    /// signed prekey alone. Sent in plaintext, since no session exists.
+    SessionInitRejected {
+        one_time_prekey_id: u32,
+    },
+    /// Copy of a payload we sent to `peer`, mirrored to our other devices so
+    /// every device shows the same conversation. Only accepted from our own
+    /// identity and only Signal-encrypted.
+    SentTranscript {
+        /// Public key (hex) of the peer the payload went to.
+        peer: String,
+        payload: Box<MessagePayload>,
+    },
+    /// Approval of a [`DeviceLinkRequest`]: the new device's certificate and
+    /// the account material sealed to its device key.
+    DeviceLinkGrant {
+        certificate: DeviceCertificate,
+        /// Account material sealed with `rekindle_crypto::device_link`.
+        sealed: Vec<u8>,
+    },
+    /// The primary device's friend list, sent to our linked devices whenever
+    /// it changes. A full snapshot, so a lost update heals with the next one.
+    /// Only accepted from our own identity and only Signal-encrypted.
+    ContactSync { contacts: Vec<SyncedContact> },
+    /// Message in a group conversation. Sent separately to every member over
+    /// their own Signal session, so there is no group key to rotate.
+    GroupMessage {
+        group_id: String,
+        /// See [`new_message_id`]; every member stores the same ID.
+        message_id: String,
+        body: String,
+    },
+    /// Invitation into a group conversation: the roster and the keys to the
+    /// shared group record. Only accepted from friends.
+    GroupInvite { group: GroupInvite },
+    /// The sender added `member` to the group. Every member sends the new
+    /// member's messages from then on.
+    GroupMemberAdded { group_id: String, member: GroupMember },
+    /// The sender left the group.
+    GroupMemberLeft { group_id: String },
+    /// The sender set the conversation's disappearing-message timer. Both
+    /// sides delete messages `seconds` after they arrive; 0 turns it off.
+    DisappearingTimer { seconds: u32 },
+}
+
+/// Most members a group conversation can have, ourselves included.
+pub const MAX_GROUP_MEMBERS: usize = 10;
+
+/// Longest disappearing-message timer or channel retention, in seconds
+/// (one year).
+pub const MAX_DISAPPEARING_SECS: u32 = 365 * 24 * 60 * 60;
+
+/// Longest reaction accepted, in bytes — room for any emoji sequence.
+pub const MAX_REACTION_LEN: usize = 32;
+
+/// Most message IDs carried by one receipt.
+pub const MAX_RECEIPT_BATCH: usize = 100;
+
+/// A fresh, globally unique message ID (16 random bytes, hex).
+///
+/// Chosen by the sender so both DMs and channel messages can be referred to
+/// by edits, deletions and reactions before any server has seen them.
+pub fn new_message_id() -> String {
+    use rand::RngCore as _;
+    let mut id = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut id);
+    hex::encode(id)
+}
+
+/// Whether `emoji` is acceptable as a reaction.
+pub fn is_valid_reaction(emoji: &str) -> bool {
+    !emoji.is_empty()
+        && emoji.len() <= MAX_REACTION_LEN
+        && !emoji.chars().any(|c| c.is_control() || c.is_whitespace())
 }
 
 /// Game information for rich presence.
@@ -214,6 +335,263 @@ pub fn decode_invite_url(url: &str) -> Result<InviteBlob, String> {
     Ok(blob)
 }
 
+/// A link to a server-managed community invite (`CommunityRequest::CreateInvite`).
+///
+/// Unsigned: the community server checks `code` when it is redeemed with
+/// `CommunityRequest::Join`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommunityInviteLink {
+    pub community_id: String,
+    pub code: String,
+    /// Community name when the invite was made, for display before joining.
+    pub community_name: String,
+    /// The owner's pseudonym (hex) as the inviter knows it. Key holders
+    /// wrap the MEK only for members vouched for from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Seed of the invite key (hex, `group::member_vouch::invite_key`) the
+    /// joiner signs their pseudonym with. Never sent to the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite_secret: Option<String>,
+}
+
+/// Encode a community invite as a `rekindle://community/` URL.
+pub fn encode_community_invite_url(link: &CommunityInviteLink) -> String {
+    let json = serde_json::to_vec(link).unwrap_or_default();
+    let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&json);
+    format!("rekindle://community/{encoded}")
+}
+
+/// Decode a community invite from a `rekindle://community/` URL.
+pub fn decode_community_invite_url(url: &str) -> Result<CommunityInviteLink, String> {
+    let data = url
+        .strip_prefix("rekindle://community/")
+        .ok_or_else(|| "not a community invite link".to_string())?;
+    let json_bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|e| format!("invalid base64: {e}"))?;
+    serde_json::from_slice(&json_bytes).map_err(|e| format!("invalid invite JSON: {e}"))
+}
+
+// ---------------------------------------------------------------------------
+// Device linking
+// ---------------------------------------------------------------------------
+
+/// An identity's signed statement that a device may act for it.
+///
+/// Published in the identity's profile (subkey 7) and account record so
+/// friends and the identity's other devices know where to fan messages out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCertificate {
+    /// Identity Ed25519 public key (hex).
+    pub identity_key: String,
+    /// Device Ed25519 public key (hex).
+    pub device_key: String,
+    /// Name the user gave the device.
+    pub device_name: String,
+    /// Device mailbox DHT record key: route blob in subkey 0, prekey bundle
+    /// in subkey 1.
+    pub mailbox_dht_key: String,
+    /// Unix timestamp in milliseconds.
+    pub created_at: u64,
+    /// Identity-key signature over the JSON of all fields above.
+    pub signature: Vec<u8>,
+}
+
+/// A friend as the primary device hands it to a linked device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncedContact {
+    /// Friend Ed25519 public key (hex).
+    pub public_key: String,
+    pub display_name: String,
+    pub nickname: Option<String>,
+    /// Friend's profile DHT record key.
+    pub profile_dht_key: Option<String>,
+    /// Friend's mailbox DHT record key.
+    pub mailbox_dht_key: Option<String>,
+}
+
+/// One member of a group conversation as the other members see them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMember {
+    /// Member Ed25519 public key (hex).
+    pub public_key: String,
+    pub display_name: String,
+    /// Member's profile DHT record key, for their route and prekeys.
+    pub profile_dht_key: Option<String>,
+    /// Member's mailbox DHT record key.
+    pub mailbox_dht_key: Option<String>,
+}
+
+/// Everything a new member needs to join a group conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupInvite {
+    pub group_id: String,
+    pub name: String,
+    /// Creator Ed25519 public key (hex).
+    pub created_by: String,
+    /// Every member, the recipient included.
+    pub members: Vec<GroupMember>,
+    /// Shared group DHT record; absent when the inviter could not reach the
+    /// DHT to create it.
+    pub record_key: Option<String>,
+    /// Owner keypair of the group record, so any member can update the roster.
+    pub record_keypair: Option<String>,
+    /// Key the group record is encrypted with (32 bytes).
+    #[serde(default)]
+    pub record_secret: Vec<u8>,
+}
+
+/// A new device asking an existing one to link it to the identity.
+///
+/// Signed with the new device's own key, then base64url-encoded for sharing
+/// as a `rekindle-link://` URL or QR code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceLinkRequest {
+    /// Device Ed25519 public key (hex).
+    pub device_key: String,
+    pub device_name: String,
+    /// Device mailbox DHT record key.
+    pub mailbox_dht_key: String,
+    /// The device's current route blob, for delivering the grant.
+    pub route_blob: Vec<u8>,
+    /// Unix timestamp in milliseconds.
+    pub created_at: u64,
+    /// Device-key signature over the JSON of all fields above.
+    pub signature: Vec<u8>,
+}
+
+/// Sign a certificate linking `device_key` to the identity holding `identity_secret`.
+pub fn sign_device_certificate(
+    identity_secret: &[u8; 32],
+    device_key: &str,
+    device_name: &str,
+    mailbox_dht_key: &str,
+    created_at: u64,
+) -> DeviceCertificate {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let signing_key = SigningKey::from_bytes(identity_secret);
+    let identity_key = hex::encode(signing_key.verifying_key().to_bytes());
+    let signable = serde_json::json!({
+        "identity_key": identity_key,
+        "device_key": device_key,
+        "device_name": device_name,
+        "mailbox_dht_key": mailbox_dht_key,
+        "created_at": created_at,
+    });
+    let signable_bytes = serde_json::to_vec(&signable).unwrap_or_default();
+    let signature = signing_key.sign(&signable_bytes);
+
+    DeviceCertificate {
+        identity_key,
+        device_key: device_key.to_string(),
+        device_name: device_name.to_string(),
+        mailbox_dht_key: mailbox_dht_key.to_string(),
+        created_at,
+        signature: signature.to_bytes().to_vec(),
+    }
+}
+
+/// Verify that a certificate was signed by the identity it names.
+pub fn verify_device_certificate(cert: &DeviceCertificate) -> Result<(), String> {
+    let signable = serde_json::json!({
+        "identity_key": cert.identity_key,
+        "device_key": cert.device_key,
+        "device_name": cert.device_name,
+        "mailbox_dht_key": cert.mailbox_dht_key,
+        "created_at": cert.created_at,
+    });
+    verify_signed_json(&cert.identity_key, &signable, &cert.signature)
+        .map_err(|e| format!("invalid device certificate: {e}"))
+}
+
+/// Create a link request signed with the new device's key.
+pub fn create_link_request(
+    device_secret: &[u8; 32],
+    device_name: &str,
+    mailbox_dht_key: &str,
+    route_blob: &[u8],
+    created_at: u64,
+) -> DeviceLinkRequest {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let signing_key = SigningKey::from_bytes(device_secret);
+    let device_key = hex::encode(signing_key.verifying_key().to_bytes());
+    let signable = serde_json::json!({
+        "device_key": device_key,
+        "device_name": device_name,
+        "mailbox_dht_key": mailbox_dht_key,
+        "route_blob": route_blob,
+        "created_at": created_at,
+    });
+    let signable_bytes = serde_json::to_vec(&signable).unwrap_or_default();
+    let signature = signing_key.sign(&signable_bytes);
+
+    DeviceLinkRequest {
+        device_key,
+        device_name: device_name.to_string(),
+        mailbox_dht_key: mailbox_dht_key.to_string(),
+        route_blob: route_blob.to_vec(),
+        created_at,
+        signature: signature.to_bytes().to_vec(),
+    }
+}
+
+/// Verify that a link request was signed by the device it names.
+pub fn verify_link_request(request: &DeviceLinkRequest) -> Result<(), String> {
+    let signable = serde_json::json!({
+        "device_key": request.device_key,
+        "device_name": request.device_name,
+        "mailbox_dht_key": request.mailbox_dht_key,
+        "route_blob": request.route_blob,
+        "created_at": request.created_at,
+    });
+    verify_signed_json(&request.device_key, &signable, &request.signature)
+        .map_err(|e| format!("invalid link request: {e}"))
+}
+
+/// Encode a link request as a `rekindle-link://` URL.
+pub fn encode_link_request_url(request: &DeviceLinkRequest) -> String {
+    let json = serde_json::to_vec(request).unwrap_or_default();
+    let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&json);
+    format!("rekindle-link://{encoded}")
+}
+
+/// Decode a link request from a `rekindle-link://` URL or raw base64 string.
+pub fn decode_link_request_url(url: &str) -> Result<DeviceLinkRequest, String> {
+    let data = url.trim();
+    let data = data.strip_prefix("rekindle-link://").unwrap_or(data);
+    let json_bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|e| format!("invalid base64: {e}"))?;
+    serde_json::from_slice(&json_bytes).map_err(|e| format!("invalid link request JSON: {e}"))
+}
+
+/// Check an Ed25519 signature by `public_key_hex` over the JSON of `signable`.
+pub(crate) fn verify_signed_json(
+    public_key_hex: &str,
+    signable: &serde_json::Value,
+    signature: &[u8],
+) -> Result<(), String> {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    let pub_array: [u8; 32] = hex::decode(public_key_hex)
+        .map_err(|e| format!("invalid public key hex: {e}"))?
+        .try_into()
+        .map_err(|_| "public key must be 32 bytes".to_string())?;
+    let verifying_key =
+        VerifyingKey::from_bytes(&pub_array).map_err(|e| format!("invalid public key: {e}"))?;
+    let sig_array: [u8; 64] = signature
+        .try_into()
+        .map_err(|_| "signature must be 64 bytes".to_string())?;
+    let signable_bytes = serde_json::to_vec(signable).unwrap_or_default();
+    verifying_key
+        .verify(&signable_bytes, &Signature::from_bytes(&sig_array))
+        .map_err(|e| e.to_string())
+}
+
 // ---------------------------------------------------------------------------
 // Community server RPC types
 // ---------------------------------------------------------------------------
@@ -234,21 +612,66 @@ pub enum CommunityRequest {
         prekey_bundle: Vec<u8>,
         /// The member's private route blob so the server can broadcast to them.
         route_blob: Option<Vec<u8>>,
+        /// The invite key's vouch for `pseudonym_pubkey`, when the invite
+        /// link carried one.
+        #[serde(default)]
+        vouch: Option<MemberVouchDto>,
     },
-    /// Send a message to a channel.
+    /// Send a message to a channel or thread.
     SendMessage {
         channel_id: String,
+        /// Sender-chosen ID (see [`new_message_id`]); must be unique in
+        /// the community.
+        message_id: String,
         ciphertext: Vec<u8>,
         mek_generation: u64,
+        /// The message this one replies to, in the same channel or thread.
+        #[serde(default)]
+        reply_to: Option<String>,
     },
-    /// Fetch message history for a channel.
+    /// Fetch message history for a channel or thread.
     GetMessages {
         channel_id: String,
         before_timestamp: Option<u64>,
         limit: u32,
     },
-    /// Request current MEK (e.g., after reconnect).
-    RequestMEK,
+    /// Replace the body of one of our messages. `ciphertext` is the new
+    /// body under the current MEK generation.
+    EditMessage {
+        channel_id: String,
+        message_id: String,
+        ciphertext: Vec<u8>,
+        mek_generation: u64,
+    },
+    /// Delete a message for everyone: our own, or anyone's with
+    /// `MANAGE_MESSAGES`.
+    DeleteMessage {
+        channel_id: String,
+        message_id: String,
+    },
+    /// React to a message (needs `ADD_REACTIONS`).
+    AddReaction {
+        channel_id: String,
+        message_id: String,
+        emoji: String,
+    },
+    /// Take back one of our reactions.
+    RemoveReaction {
+        channel_id: String,
+        message_id: String,
+        emoji: String,
+    },
+    /// Request current MEK (e.g., after reconnect), or an older
+    /// `generation` to read history.
+    ///
+    /// Attach a prekey bundle (as in `Join`) when no MEK delivery session
+    /// with the server exists yet; the response then carries `session_init`.
+    /// Older generations are subject to the community's `HistoryVisibility`.
+    RequestMEK {
+        prekey_bundle: Option<Vec<u8>>,
+        #[serde(default)]
+        generation: Option<u64>,
+    },
     /// Leave the community.
     Leave,
     /// Admin: kick a member.
@@ -271,11 +694,50 @@ pub enum CommunityRequest {
         channel_id: String,
         new_name: String,
     },
-    /// Admin: update community metadata (name, description).
+    /// Admin: keep a channel's messages for `seconds` only, on the server
+    /// and on every member's device. 0 keeps them forever.
+    SetChannelRetention {
+        channel_id: String,
+        seconds: u32,
+    },
+    /// Admin: update community metadata (name, description) and settings.
     UpdateCommunity {
         name: Option<String>,
         description: Option<String>,
+        #[serde(default)]
+        history_visibility: Option<HistoryVisibility>,
+        /// Whether `Join` needs a valid invite code from newcomers.
+        #[serde(default)]
+        invite_only: Option<bool>,
+        /// How long audit log entries are kept, in seconds; 0 keeps them
+        /// forever.
+        #[serde(default)]
+        audit_retention_secs: Option<u32>,
+    },
+    /// Create an invite code (needs `CREATE_INSTANT_INVITE`). It stops
+    /// working after `max_uses` joins or at `expires_at` (Unix seconds);
+    /// whoever joins with it also gets `grant_role_ids`, which needs
+    /// `MANAGE_ROLES` and roles below our own.
+    CreateInvite {
+        max_uses: Option<u32>,
+        expires_at: Option<u64>,
+        grant_role_ids: Vec<u32>,
+    },
+    /// List invites: all of them with `MANAGE_COMMUNITY`, otherwise the
+    /// ones we created.
+    ListInvites,
+    /// Revoke an invite we created, or any with `MANAGE_COMMUNITY`.
+    RevokeInvite {
+        code: String,
     },
+    /// Admin: list the community in the public directory
+    /// (`crate::dht::directory`) under `game_ids`, or withdraw it.
+    SetListing {
+        listed: bool,
+        game_ids: Vec<u32>,
+    },
+    /// Get the community's directory listing settings.
+    GetListing,
     /// Admin: ban a member (kick + prevent rejoin).
     Ban {
         target_pseudonym: String,
@@ -286,6 +748,14 @@ pub enum CommunityRequest {
     },
     /// Admin: get ban list.
     GetBanList,
+    /// Page through the audit log (needs `VIEW_AUDIT_LOG`), newest first:
+    /// up to `limit` entries with IDs below `before`, optionally only one
+    /// kind of action.
+    GetAuditLog {
+        before: Option<u64>,
+        limit: u32,
+        action_filter: Option<AuditAction>,
+    },
 
     // ── New role & permission management ──
 
@@ -347,6 +817,105 @@ pub enum CommunityRequest {
     },
     /// Get all role definitions.
     GetRoles,
+    /// Owner: switch the community to zero-knowledge MEK custody.
+    ///
+    /// The server discards its MEK and from then on only relays wrapped
+    /// keys published by privileged members. One-way.
+    EnableZeroKnowledge,
+    /// Privileged member: publish a MEK generation wrapped to each member's
+    /// pseudonym (zero-knowledge communities only).
+    PublishMEK {
+        generation: u64,
+        wrapped_keys: Vec<WrappedMekDto>,
+    },
+    /// Member: store vouches made by us or by an invite key we vouched
+    /// for, so key holders know who to wrap the MEK for.
+    VouchMembers {
+        vouches: Vec<MemberVouchDto>,
+    },
+    /// Owner: switch the community to a `TreeKEM` group
+    /// (`rekindle_crypto::group::tree`).
+    ///
+    /// The server discards its MEK and from then on only orders commits.
+    /// The response is `TreeEpoch`; the owner creates the tree at that
+    /// epoch. One-way.
+    EnableTreeKem,
+    /// Publish a serialized `KeyPackage` so a privileged member adds us to
+    /// the tree (tree communities only).
+    PublishKeyPackage {
+        key_package: Vec<u8>,
+    },
+    /// Submit a serialized `Commit` made at `epoch`, with a `Welcome` for
+    /// every member it adds. Only the first commit for an epoch is
+    /// accepted; later ones get a 409.
+    SubmitCommit {
+        epoch: u64,
+        commit: Vec<u8>,
+        welcomes: Vec<TreeWelcomeDto>,
+    },
+    /// Fetch accepted commits from `since_epoch` on, plus our latest
+    /// welcome.
+    GetCommits {
+        since_epoch: u64,
+    },
+    /// Join a voice channel on the server's voice relay, leaving any other
+    /// voice channel in the community. The relay forwards frames to
+    /// `route_blob`, or to the route we joined the community with.
+    JoinVoice {
+        channel_id: String,
+        route_blob: Option<Vec<u8>>,
+    },
+    /// Leave the voice channel we are in.
+    LeaveVoice,
+
+    // ── Threads ──
+    //
+    // A thread is a sub-channel started from a message in a text channel.
+    // Its ID goes wherever a `channel_id` does for messages, edits,
+    // deletions and reactions, under the parent channel's permissions.
+
+    /// Start a thread from `parent_message_id` in `channel_id` (needs
+    /// `CREATE_PUBLIC_THREADS`, or `CREATE_PRIVATE_THREADS` for a
+    /// `private` one). Private threads are seen only by their members and
+    /// by `MANAGE_THREADS` holders.
+    CreateThread {
+        channel_id: String,
+        parent_message_id: String,
+        name: String,
+        private: bool,
+    },
+    /// List a channel's threads visible to us, archived ones only when
+    /// asked.
+    GetThreads {
+        channel_id: String,
+        include_archived: bool,
+    },
+    /// Archive or reopen a thread: its creator, or anyone with
+    /// `MANAGE_THREADS`. Archived threads take no new messages.
+    ArchiveThread {
+        thread_id: String,
+        archived: bool,
+    },
+    /// Join a public thread.
+    JoinThread {
+        thread_id: String,
+    },
+    /// Leave a thread; leaving a private one takes away access to it.
+    LeaveThread {
+        thread_id: String,
+    },
+    /// Add a member to a thread we are in, or to any thread with
+    /// `MANAGE_THREADS`.
+    AddThreadMember {
+        thread_id: String,
+        target_pseudonym: String,
+    },
+    /// Remove a member from a thread: its creator, or anyone with
+    /// `MANAGE_THREADS`.
+    RemoveThreadMember {
+        thread_id: String,
+        target_pseudonym: String,
+    },
 }
 
 /// Response from the community server to a member.
@@ -356,21 +925,41 @@ pub enum CommunityResponse {
     /// Generic success.
     Ok,
     /// Join succeeded — includes encrypted MEK and channel list.
+    ///
+    /// `mek_encrypted` is a Signal message on the server↔member session.
+    /// With `zero_knowledge`, it is instead a wrapped MEK (see `WrappedMEK`),
+    /// or empty until a privileged member publishes one for us.
     Joined {
         mek_encrypted: Vec<u8>,
         mek_generation: u64,
+        session_init: Option<MekSessionInit>,
         channels: Vec<ChannelInfoDto>,
         role_ids: Vec<u32>,
         roles: Vec<RoleDto>,
+        #[serde(default)]
+        zero_knowledge: bool,
+        #[serde(default)]
+        history_visibility: HistoryVisibility,
+        /// MEKs come from a `TreeKEM` group; publish a key package to be
+        /// added to it.
+        #[serde(default)]
+        tree_kem: bool,
     },
     /// Message history.
     Messages {
         messages: Vec<ChannelMessageDto>,
     },
-    /// MEK delivery.
+    /// MEK delivery, encrypted like `Joined::mek_encrypted`.
     MEK {
         mek_encrypted: Vec<u8>,
         mek_generation: u64,
+        session_init: Option<MekSessionInit>,
+    },
+    /// MEK wrapped to our pseudonym by a privileged member
+    /// (zero-knowledge communities).
+    WrappedMEK {
+        mek_generation: u64,
+        wrapped_key: Vec<u8>,
     },
     /// Channel created.
     ChannelCreated {
@@ -378,10 +967,29 @@ pub enum CommunityResponse {
     },
     /// Community metadata updated.
     CommunityUpdated,
+    /// Invite created.
+    InviteCreated {
+        invite: InviteDto,
+    },
+    /// Invites visible to the requester, and whether joining needs one.
+    Invites {
+        invite_only: bool,
+        invites: Vec<InviteDto>,
+    },
+    /// Directory listing settings.
+    Listing {
+        listed: bool,
+        game_ids: Vec<u32>,
+    },
     /// Ban list response.
     BanList {
         banned: Vec<BannedMemberDto>,
     },
+    /// A page of the audit log, and how long entries are kept.
+    AuditLog {
+        entries: Vec<AuditEntryDto>,
+        retention_secs: u32,
+    },
     /// Role created successfully.
     RoleCreated {
         role_id: u32,
@@ -390,6 +998,32 @@ pub enum CommunityResponse {
     RolesList {
         roles: Vec<RoleDto>,
     },
+    /// The community's `TreeKEM` group is at `epoch`.
+    TreeEpoch {
+        epoch: u64,
+    },
+    /// Accepted commits in epoch order (at most a page; ask again from the
+    /// last epoch + 1 for more), and the requester's latest welcome.
+    Commits {
+        commits: Vec<TreeCommitDto>,
+        welcome: Option<Vec<u8>>,
+    },
+    /// Joined a voice channel; `participants` were already in it.
+    VoiceJoined {
+        participants: Vec<VoiceParticipantDto>,
+        /// Sent between the tag and the body of every frame and probe so
+        /// the relay knows who sent it.
+        #[serde(default)]
+        relay_token: Vec<u8>,
+    },
+    /// Thread created.
+    ThreadCreated {
+        thread: ThreadDto,
+    },
+    /// A channel's threads, newest first.
+    Threads {
+        threads: Vec<ThreadDto>,
+    },
     /// Error.
     Error {
         code: u32,
@@ -397,6 +1031,231 @@ pub enum CommunityResponse {
     },
 }
 
+/// X3DH parameters for a server↔member MEK delivery session.
+///
+/// Sent alongside the first MEK encrypted on a new session so the member
+/// can call `respond_to_session()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MekSessionInit {
+    /// The server's X25519 Signal identity key.
+    pub identity_key: Vec<u8>,
+    /// The server's X3DH ephemeral public key.
+    pub ephemeral_key: Vec<u8>,
+    /// Which of the member's signed prekeys was used.
+    pub signed_prekey_id: u32,
+    /// Which of the member's one-time prekeys was consumed (if any).
+    pub one_time_prekey_id: Option<u32>,
+}
+
+/// Which MEK generations a member may fetch besides the current one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryVisibility {
+    /// Every generation, so newcomers can read the full channel history.
+    #[default]
+    Full,
+    /// Only generations from the member's join onwards; history sent
+    /// before they joined is not served to them.
+    SinceJoin,
+}
+
+impl HistoryVisibility {
+    /// Stable string form used in database columns.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::SinceJoin => "since_join",
+        }
+    }
+
+    /// Parse the database form; unknown values fall back to `Full`.
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "since_join" => Self::SinceJoin,
+            _ => Self::Full,
+        }
+    }
+}
+
+/// A moderation or configuration action recorded in a community's audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    CommunityUpdate,
+    ListingUpdate,
+    ChannelCreate,
+    ChannelUpdate,
+    ChannelDelete,
+    ChannelOverwriteUpdate,
+    ChannelOverwriteDelete,
+    RoleCreate,
+    RoleUpdate,
+    RoleDelete,
+    MemberRoleAdd,
+    MemberRoleRemove,
+    MemberKick,
+    MemberBan,
+    MemberUnban,
+    MemberTimeout,
+    MemberTimeoutRemove,
+    MessageDelete,
+    InviteCreate,
+    InviteRevoke,
+    MekRotate,
+    ZeroKnowledgeEnable,
+    TreeKemEnable,
+    ThreadUpdate,
+    ThreadMemberRemove,
+}
+
+impl AuditAction {
+    /// Stable string form used in database columns.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::CommunityUpdate => "community_update",
+            Self::ListingUpdate => "listing_update",
+            Self::ChannelCreate => "channel_create",
+            Self::ChannelUpdate => "channel_update",
+            Self::ChannelDelete => "channel_delete",
+            Self::ChannelOverwriteUpdate => "channel_overwrite_update",
+            Self::ChannelOverwriteDelete => "channel_overwrite_delete",
+            Self::RoleCreate => "role_create",
+            Self::RoleUpdate => "role_update",
+            Self::RoleDelete => "role_delete",
+            Self::MemberRoleAdd => "member_role_add",
+            Self::MemberRoleRemove => "member_role_remove",
+            Self::MemberKick => "member_kick",
+            Self::MemberBan => "member_ban",
+            Self::MemberUnban => "member_unban",
+            Self::MemberTimeout => "member_timeout",
+            Self::MemberTimeoutRemove => "member_timeout_remove",
+            Self::MessageDelete => "message_delete",
+            Self::InviteCreate => "invite_create",
+            Self::InviteRevoke => "invite_revoke",
+            Self::MekRotate => "mek_rotate",
+            Self::ZeroKnowledgeEnable => "zero_knowledge_enable",
+            Self::TreeKemEnable => "tree_kem_enable",
+            Self::ThreadUpdate => "thread_update",
+            Self::ThreadMemberRemove => "thread_member_remove",
+        }
+    }
+
+    /// Parse the database form; `None` for actions this build doesn't know.
+    pub fn from_db(value: &str) -> Option<Self> {
+        Some(match value {
+            "community_update" => Self::CommunityUpdate,
+            "listing_update" => Self::ListingUpdate,
+            "channel_create" => Self::ChannelCreate,
+            "channel_update" => Self::ChannelUpdate,
+            "channel_delete" => Self::ChannelDelete,
+            "channel_overwrite_update" => Self::ChannelOverwriteUpdate,
+            "channel_overwrite_delete" => Self::ChannelOverwriteDelete,
+            "role_create" => Self::RoleCreate,
+            "role_update" => Self::RoleUpdate,
+            "role_delete" => Self::RoleDelete,
+            "member_role_add" => Self::MemberRoleAdd,
+            "member_role_remove" => Self::MemberRoleRemove,
+            "member_kick" => Self::MemberKick,
+            "member_ban" => Self::MemberBan,
+            "member_unban" => Self::MemberUnban,
+            "member_timeout" => Self::MemberTimeout,
+            "member_timeout_remove" => Self::MemberTimeoutRemove,
+            "message_delete" => Self::MessageDelete,
+            "invite_create" => Self::InviteCreate,
+            "invite_revoke" => Self::InviteRevoke,
+            "mek_rotate" => Self::MekRotate,
+            "zero_knowledge_enable" => Self::ZeroKnowledgeEnable,
+            "tree_kem_enable" => Self::TreeKemEnable,
+            "thread_update" => Self::ThreadUpdate,
+            "thread_member_remove" => Self::ThreadMemberRemove,
+            _ => return None,
+        })
+    }
+}
+
+/// An audit log entry, as returned by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntryDto {
+    pub id: u64,
+    pub actor_pseudonym: String,
+    /// Actor's display name, empty once they left the community.
+    pub actor_name: String,
+    pub action: AuditAction,
+    /// What the action applied to: a pseudonym, channel or thread ID, role
+    /// ID or invite code.
+    pub target: Option<String>,
+    /// JSON of the target's state before and after the action.
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: u64,
+}
+
+/// One member's copy of a MEK generation in a zero-knowledge community.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WrappedMekDto {
+    /// Recipient pseudonym public key (hex).
+    pub pseudonym_key: String,
+    /// MEK sealed to that pseudonym; opaque to the server.
+    pub wrapped_key: Vec<u8>,
+}
+
+/// One `rekindle_crypto::group::member_vouch::MemberVouch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberVouchDto {
+    /// Vouching pseudonym or invite key (hex).
+    pub voucher: String,
+    /// Pseudonym vouched for (hex).
+    pub member: String,
+    pub signature: Vec<u8>,
+}
+
+impl From<&MemberVouch> for MemberVouchDto {
+    fn from(v: &MemberVouch) -> Self {
+        Self {
+            voucher: hex::encode(v.voucher),
+            member: hex::encode(v.member),
+            signature: v.signature.clone(),
+        }
+    }
+}
+
+impl MemberVouchDto {
+    /// The vouch, or `None` if either key is not 32 bytes of hex. The
+    /// signature is not checked here.
+    pub fn to_vouch(&self) -> Option<MemberVouch> {
+        let key = |s: &str| <[u8; 32]>::try_from(hex::decode(s).ok()?).ok();
+        Some(MemberVouch {
+            voucher: key(&self.voucher)?,
+            member: key(&self.member)?,
+            signature: self.signature.clone(),
+        })
+    }
+}
+
+/// A `TreeKEM` commit accepted by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeCommitDto {
+    /// Epoch the commit was made in.
+    pub epoch: u64,
+    /// Serialized `Commit`; opaque to the server beyond basic checks.
+    pub commit: Vec<u8>,
+}
+
+/// A `TreeKEM` welcome for one member added by a commit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeWelcomeDto {
+    /// Recipient pseudonym public key (hex).
+    pub pseudonym_key: String,
+    /// Serialized `Welcome`.
+    pub welcome: Vec<u8>,
+}
+
 /// A role definition as returned by the server over RPC.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -419,14 +1278,75 @@ pub struct BannedMemberDto {
     pub banned_at: u64,
 }
 
+/// A server-managed invite code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteDto {
+    pub code: String,
+    pub creator_pseudonym: String,
+    /// `None` for unlimited uses.
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    /// Unix seconds; `None` never expires.
+    pub expires_at: Option<u64>,
+    pub grant_role_ids: Vec<u32>,
+    pub created_at: u64,
+}
+
+/// A member in a voice channel, as returned by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceParticipantDto {
+    pub pseudonym_key: String,
+    pub display_name: String,
+}
+
 /// A channel message as returned by the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChannelMessageDto {
+    pub message_id: String,
     pub sender_pseudonym: String,
+    /// The latest body; replaced in place by edits.
     pub ciphertext: Vec<u8>,
     pub mek_generation: u64,
     pub timestamp: u64,
+    /// When the body was last edited.
+    #[serde(default)]
+    pub edited_at: Option<u64>,
+    #[serde(default)]
+    pub reactions: Vec<ReactionDto>,
+    /// The message this one replies to.
+    #[serde(default)]
+    pub reply_to: Option<String>,
+}
+
+/// A thread started from a message in a text channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadDto {
+    pub id: String,
+    /// The text channel the thread belongs to.
+    pub channel_id: String,
+    pub parent_message_id: String,
+    pub name: String,
+    pub creator_pseudonym: String,
+    pub private: bool,
+    pub archived: bool,
+    /// Pseudonyms of the members who joined or were added.
+    pub members: Vec<String>,
+    pub message_count: u32,
+    /// Unix seconds of the newest message, if any.
+    pub last_message_at: Option<u64>,
+    pub created_at: u64,
+}
+
+/// Everyone who reacted to a message with one emoji.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionDto {
+    pub emoji: String,
+    pub pseudonym_keys: Vec<String>,
 }
 
 /// Channel info as returned by the server.
@@ -436,6 +1356,9 @@ pub struct ChannelInfoDto {
     pub id: String,
     pub name: String,
     pub channel_type: String,
+    /// Seconds messages are kept for; 0 keeps them forever.
+    #[serde(default)]
+    pub retention_secs: u32,
 }
 
 /// Broadcast from the community server to members via `app_message`.
@@ -444,20 +1367,99 @@ pub struct ChannelInfoDto {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum CommunityBroadcast {
-    /// A new message was posted in a channel.
+    /// A new message was posted in a channel or thread.
     NewMessage {
         community_id: String,
         channel_id: String,
+        message_id: String,
         sender_pseudonym: String,
         ciphertext: Vec<u8>,
         mek_generation: u64,
         timestamp: u64,
+        #[serde(default)]
+        reply_to: Option<String>,
+    },
+    /// A message's author replaced its body.
+    MessageEdited {
+        community_id: String,
+        channel_id: String,
+        message_id: String,
+        ciphertext: Vec<u8>,
+        mek_generation: u64,
+        edited_at: u64,
+    },
+    /// A message was deleted for everyone.
+    MessageDeleted {
+        community_id: String,
+        channel_id: String,
+        message_id: String,
+    },
+    /// A thread was started. Private threads are announced only to their
+    /// members and to `MANAGE_THREADS` holders.
+    ThreadCreated {
+        community_id: String,
+        thread: ThreadDto,
+    },
+    /// A thread was archived or reopened, or its members changed. Also
+    /// sent to a member removed from a private thread, without them in
+    /// `members`.
+    ThreadUpdated {
+        community_id: String,
+        thread: ThreadDto,
+    },
+    /// A member reacted to a message.
+    ReactionAdded {
+        community_id: String,
+        channel_id: String,
+        message_id: String,
+        pseudonym_key: String,
+        emoji: String,
+    },
+    /// A member took back a reaction.
+    ReactionRemoved {
+        community_id: String,
+        channel_id: String,
+        message_id: String,
+        pseudonym_key: String,
+        emoji: String,
     },
     /// MEK has been rotated — fetch your new copy via `RequestMEK`.
     MEKRotated {
         community_id: String,
         new_generation: u64,
     },
+    /// Zero-knowledge communities: `members` hold no copy of MEK
+    /// `generation`. Privileged members respond with `PublishMEK`; when
+    /// `generation` is newer than the current one, a fresh key is expected.
+    MEKKeysNeeded {
+        community_id: String,
+        generation: u64,
+        members: Vec<String>,
+        /// Every vouch stored for the community, for key holders to check
+        /// `members` against before wrapping.
+        #[serde(default)]
+        vouches: Vec<MemberVouchDto>,
+    },
+    /// Tree communities: membership changed. A privileged member in the
+    /// tree commits at `epoch`, removing leaves whose credential is not in
+    /// `members` and adding the published `key_packages`.
+    TreeCommitNeeded {
+        community_id: String,
+        epoch: u64,
+        members: Vec<String>,
+        key_packages: Vec<Vec<u8>>,
+    },
+    /// A commit moved the tree to `epoch` — fetch it with `GetCommits`.
+    TreeCommitted {
+        community_id: String,
+        epoch: u64,
+    },
+    /// The community switched to a `TreeKEM` group starting at `epoch`;
+    /// publish a key package to be added.
+    TreeKemEnabled {
+        community_id: String,
+        epoch: u64,
+    },
     /// A member joined the community.
     MemberJoined {
         community_id: String,
@@ -492,4 +1494,26 @@ pub enum CommunityBroadcast {
         community_id: String,
         channel_id: String,
     },
+    /// A member joined a voice channel.
+    VoiceJoined {
+        community_id: String,
+        channel_id: String,
+        pseudonym_key: String,
+        display_name: String,
+    },
+    /// A member left a voice channel (or was removed from it).
+    VoiceLeft {
+        community_id: String,
+        channel_id: String,
+        pseudonym_key: String,
+    },
+    /// A member in our voice channel started or stopped talking. Sent only
+    /// to the channel's other participants; the relay infers it from
+    /// whether frames are arriving.
+    VoiceSpeaking {
+        community_id: String,
+        channel_id: String,
+        pseudonym_key: String,
+        speaking: bool,
+    },
 }
@@ -3,8 +3,14 @@ pub mod receiver;
 pub mod sender;
 
 pub use envelope::{
-    BannedMemberDto, ChannelInfoDto, ChannelMessageDto, CommunityBroadcast, CommunityRequest,
-    CommunityResponse, InviteBlob, MessageEnvelope, MessagePayload, RoleDto,
-    create_invite_blob, decode_invite_url, encode_invite_url, verify_invite_blob,
+    AuditAction, AuditEntryDto, BannedMemberDto, ChannelInfoDto, ChannelMessageDto,
+    CommunityBroadcast, CommunityInviteLink, CommunityRequest, CommunityResponse,
+    DeviceCertificate, DeviceLinkRequest, GroupInvite, GroupMember, HistoryVisibility,
+    InviteBlob, InviteDto, MekSessionInit, MemberVouchDto, MessageEnvelope, MessagePayload,
+    ReactionDto, RoleDto, SyncedContact, ThreadDto, TreeCommitDto, TreeWelcomeDto,
+    VoiceParticipantDto, WrappedMekDto,
+    create_invite_blob, decode_community_invite_url, decode_invite_url,
+    encode_community_invite_url, encode_invite_url, is_valid_reaction, new_message_id,
+    verify_invite_blob, MAX_DISAPPEARING_SECS, MAX_GROUP_MEMBERS,
 };
 pub use receiver::process_incoming;
@@ -3,6 +3,7 @@ use ed25519_dalek::{Signature, Verifier, VerifyingKey};
 use crate::capnp_codec;
 use crate::error::ProtocolError;
 use crate::messaging::envelope::{MessageEnvelope, MessagePayload};
+use crate::messaging::sender::signed_data;
 
 /// Parse a raw incoming message into a `MessageEnvelope`.
 pub fn parse_envelope(data: &[u8]) -> Result<MessageEnvelope, ProtocolError> {
@@ -11,11 +12,13 @@ pub fn parse_envelope(data: &[u8]) -> Result<MessageEnvelope, ProtocolError> {
 
 /// Verify the Ed25519 signature on a message envelope.
 pub fn verify_envelope(envelope: &MessageEnvelope) -> Result<bool, ProtocolError> {
-    // Reconstruct the signed data: timestamp || nonce || payload
-    let mut signed_data = Vec::new();
-    signed_data.extend_from_slice(&envelope.timestamp.to_le_bytes());
-    signed_data.extend_from_slice(&envelope.nonce);
-    signed_data.extend_from_slice(&envelope.payload);
+    // Reconstruct the signed data: timestamp || nonce || payload [|| sender_device]
+    let signed_data = signed_data(
+        envelope.timestamp,
+        &envelope.nonce,
+        &envelope.payload,
+        &envelope.sender_device,
+    );
 
     // Verify with ed25519-dalek
     let key_bytes: [u8; 32] = envelope
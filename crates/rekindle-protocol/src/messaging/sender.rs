@@ -27,6 +27,23 @@ pub fn build_envelope_from_secret(
     build_envelope(&signing_key, timestamp, nonce, payload)
 }
 
+/// Build and sign a `MessageEnvelope` sent from one of the identity's linked
+/// devices.
+///
+/// `device_key` is the linked device's Ed25519 public key; it is covered by
+/// the signature so it can't be swapped in transit. Pass an empty slice from
+/// the primary device.
+pub fn build_device_envelope_from_secret(
+    secret_key_bytes: &[u8; 32],
+    device_key: &[u8],
+    timestamp: u64,
+    nonce: Vec<u8>,
+    payload: Vec<u8>,
+) -> MessageEnvelope {
+    let signing_key = SigningKey::from_bytes(secret_key_bytes);
+    sign_envelope(&signing_key, device_key.to_vec(), timestamp, nonce, payload)
+}
+
 /// Build and sign a `MessageEnvelope` from raw components.
 ///
 /// Signs (timestamp || nonce || payload) with the sender's Ed25519 key.
@@ -36,14 +53,18 @@ pub fn build_envelope(
     nonce: Vec<u8>,
     payload: Vec<u8>,
 ) -> MessageEnvelope {
-    let sender_key = signing_key.verifying_key().to_bytes().to_vec();
-
-    // Sign: timestamp || nonce || payload
-    let mut signed_data = Vec::new();
-    signed_data.extend_from_slice(&timestamp.to_le_bytes());
-    signed_data.extend_from_slice(&nonce);
-    signed_data.extend_from_slice(&payload);
+    sign_envelope(signing_key, Vec::new(), timestamp, nonce, payload)
+}
 
+fn sign_envelope(
+    signing_key: &SigningKey,
+    sender_device: Vec<u8>,
+    timestamp: u64,
+    nonce: Vec<u8>,
+    payload: Vec<u8>,
+) -> MessageEnvelope {
+    let sender_key = signing_key.verifying_key().to_bytes().to_vec();
+    let signed_data = signed_data(timestamp, &nonce, &payload, &sender_device);
     let signature = signing_key.sign(&signed_data);
 
     MessageEnvelope {
@@ -52,9 +73,22 @@ pub fn build_envelope(
         nonce,
         payload,
         signature: signature.to_bytes().to_vec(),
+        sender_device,
     }
 }
 
+/// The bytes an envelope signature covers: timestamp || nonce || payload,
+/// then the sender device only when there is one, so envelopes from primary
+/// devices verify on clients that predate linked devices.
+pub(crate) fn signed_data(timestamp: u64, nonce: &[u8], payload: &[u8], sender_device: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + nonce.len() + payload.len() + sender_device.len());
+    data.extend_from_slice(&timestamp.to_le_bytes());
+    data.extend_from_slice(nonce);
+    data.extend_from_slice(payload);
+    data.extend_from_slice(sender_device);
+    data
+}
+
 /// Send a message envelope to a peer via a pre-imported `RouteId`.
 ///
 /// The message is already encrypted and wrapped in an envelope.
@@ -0,0 +1,191 @@
+//! Chunked, encrypted file transfer over DHT logs.
+//!
+//! The sender splits a file into [`CHUNK_SIZE`] pieces, seals each with a
+//! random per-file [`FileKey`], and appends them in order to a fresh
+//! [`DHTLog`]. The log's key, the file key and the SHA-256 of the plaintext
+//! travel to the recipient as a [`FileAttachment`] inside the
+//! Signal-encrypted chat message, so the DHT only ever holds ciphertext.
+//!
+//! Both sides can resume: the log's length is the upload's progress, and a
+//! download only needs the index of the next chunk it is missing.
+
+use rekindle_crypto::file_key::FileKey;
+use serde::{Deserialize, Serialize};
+use veilid_core::{KeyPair, RoutingContext};
+
+use crate::dht::log::DHTLog;
+use crate::error::ProtocolError;
+
+/// Plaintext bytes per chunk. Sealed chunks (plus the 16-byte tag) stay
+/// under Veilid's 32 KiB subkey limit.
+pub const CHUNK_SIZE: usize = 30 * 1024;
+
+/// Chunks per log segment, keeping each segment record under Veilid's
+/// 1 MiB record limit.
+const CHUNKS_PER_SEGMENT: u16 = 32;
+
+/// Largest file that can be shared.
+pub const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// A shared file, as announced in a chat message.
+///
+/// Mirrors `Attachment` in `message.capnp`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileAttachment {
+    /// File name, without any directory.
+    pub name: String,
+    pub mime_type: String,
+    /// Plaintext size in bytes.
+    pub size: u64,
+    /// Spine key of the `DHTLog` holding the sealed chunks.
+    pub dht_key: String,
+    /// SHA-256 of the plaintext.
+    pub checksum: Vec<u8>,
+    /// Per-file AES-256-GCM key.
+    pub file_key: Vec<u8>,
+}
+
+impl FileAttachment {
+    /// Number of chunks the file is split into.
+    pub fn chunk_count(&self) -> u32 {
+        chunk_count(self.size)
+    }
+}
+
+/// Number of [`CHUNK_SIZE`] chunks in a file of `size` bytes.
+pub fn chunk_count(size: u64) -> u32 {
+    u32::try_from(size.div_ceil(CHUNK_SIZE as u64)).unwrap_or(u32::MAX)
+}
+
+/// Writing side of a transfer.
+pub struct FileUpload {
+    log: DHTLog,
+    key: FileKey,
+    chunk_count: u32,
+}
+
+impl FileUpload {
+    /// Start a new upload for a file of `size` bytes.
+    ///
+    /// Returns the upload and the log's owner keypair, which must be
+    /// persisted alongside the file key to resume later.
+    pub async fn create(rc: &RoutingContext, size: u64) -> Result<(Self, KeyPair), ProtocolError> {
+        if size > MAX_FILE_SIZE {
+            return Err(ProtocolError::Internal(format!(
+                "file is {size} bytes, the limit is {MAX_FILE_SIZE}"
+            )));
+        }
+        let (log, owner) = DHTLog::create_with_capacity(rc, CHUNKS_PER_SEGMENT).await?;
+        Ok((
+            Self {
+                log,
+                key: FileKey::generate(),
+                chunk_count: chunk_count(size),
+            },
+            owner,
+        ))
+    }
+
+    /// Reopen an interrupted upload.
+    pub async fn resume(
+        rc: &RoutingContext,
+        dht_key: &str,
+        owner: KeyPair,
+        file_key: &[u8],
+        size: u64,
+    ) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            log: DHTLog::open_write(rc, dht_key, owner).await?,
+            key: FileKey::from_bytes(file_key)?,
+            chunk_count: chunk_count(size),
+        })
+    }
+
+    /// Spine key of the chunk log.
+    pub fn dht_key(&self) -> String {
+        self.log.spine_key()
+    }
+
+    /// The per-file key.
+    pub fn file_key(&self) -> &FileKey {
+        &self.key
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        self.chunk_count
+    }
+
+    /// Chunks already in the log — the index of the next one to put.
+    pub async fn uploaded(&self) -> Result<u32, ProtocolError> {
+        Ok(u32::try_from(self.log.len().await?).unwrap_or(u32::MAX))
+    }
+
+    /// Seal and append chunk `index`.
+    ///
+    /// Chunks must go in order. Putting a chunk that is already in the log
+    /// is a no-op, so a resumed upload can safely repeat its last chunk.
+    pub async fn put_chunk(&self, index: u32, plaintext: &[u8]) -> Result<(), ProtocolError> {
+        let uploaded = self.uploaded().await?;
+        if index < uploaded {
+            return Ok(());
+        }
+        if index > uploaded {
+            return Err(ProtocolError::Internal(format!(
+                "chunk {index} out of order, log has {uploaded}"
+            )));
+        }
+        let sealed = self.key.seal_chunk(index, self.chunk_count, plaintext)?;
+        self.log.append(&sealed).await?;
+        Ok(())
+    }
+
+    pub async fn close(&self) -> Result<(), ProtocolError> {
+        self.log.close().await
+    }
+}
+
+/// Reading side of a transfer.
+pub struct FileDownload {
+    log: DHTLog,
+    key: FileKey,
+    chunk_count: u32,
+}
+
+impl FileDownload {
+    /// Open the chunk log an attachment points at.
+    pub async fn open(
+        rc: &RoutingContext,
+        attachment: &FileAttachment,
+    ) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            log: DHTLog::open_read(rc, &attachment.dht_key).await?,
+            key: FileKey::from_bytes(&attachment.file_key)?,
+            chunk_count: attachment.chunk_count(),
+        })
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        self.chunk_count
+    }
+
+    /// Chunks the sender has uploaded so far.
+    pub async fn available(&self) -> Result<u32, ProtocolError> {
+        Ok(u32::try_from(self.log.len().await?).unwrap_or(u32::MAX))
+    }
+
+    /// Fetch and open chunk `index`. `None` if it hasn't been uploaded yet.
+    pub async fn get_chunk(&self, index: u32) -> Result<Option<Vec<u8>>, ProtocolError> {
+        let Some(sealed) = self.log.get(u64::from(index)).await? else {
+            return Ok(None);
+        };
+        Ok(Some(self.key.open_chunk(
+            index,
+            self.chunk_count,
+            &sealed,
+        )?))
+    }
+
+    pub async fn close(&self) -> Result<(), ProtocolError> {
+        self.log.close().await
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use rekindle_protocol::messaging::envelope::{AuditAction, AuditEntryDto};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::server_state::ServerState;
+
+/// Audit log retention for newly hosted communities: 90 days.
+pub const DEFAULT_RETENTION_SECS: u32 = 90 * 24 * 60 * 60;
+
+/// Most entries one `GetAuditLog` page returns.
+pub const MAX_PAGE: u32 = 100;
+
+/// One audited action, built by the handler that performed it.
+pub struct Entry<'a> {
+    action: AuditAction,
+    target: Option<&'a str>,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl<'a> Entry<'a> {
+    pub fn new(action: AuditAction, target: Option<&'a str>) -> Self {
+        Self {
+            action,
+            target,
+            before: None,
+            after: None,
+        }
+    }
+
+    /// The target's state before the action.
+    pub fn before(mut self, value: Value) -> Self {
+        self.before = Some(value);
+        self
+    }
+
+    /// The target's state after the action.
+    pub fn after(mut self, value: Value) -> Self {
+        self.after = Some(value);
+        self
+    }
+}
+
+/// A value's JSON for an entry's before or after state.
+pub fn json(value: &impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// Append an entry to a community's audit log.
+///
+/// Locks `state.db` itself, so callers must have released it. A failed
+/// write is logged rather than failing the action it records.
+pub fn record(state: &Arc<ServerState>, community_id: &str, actor_pseudonym: &str, entry: Entry<'_>) {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    if let Err(e) = db.execute(
+        "INSERT INTO server_audit_log (community_id, actor_pseudonym, action, target, before_json, after_json, created_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        params![
+            community_id,
+            actor_pseudonym,
+            entry.action.as_str(),
+            entry.target,
+            entry.before.map(|v| v.to_string()),
+            entry.after.map(|v| v.to_string()),
+            timestamp_now_secs(),
+        ],
+    ) {
+        tracing::warn!(error = %e, community = %community_id, action = entry.action.as_str(), "failed to write audit log entry");
+    }
+}
+
+/// A page of a community's audit log, newest first: up to `limit` entries
+/// (capped at [`MAX_PAGE`]) older than entry `before`, optionally only
+/// those of one action.
+pub fn page(
+    conn: &Connection,
+    community_id: &str,
+    before: Option<u64>,
+    limit: u32,
+    action: Option<AuditAction>,
+) -> Vec<AuditEntryDto> {
+    let before = before.map_or(i64::MAX, |id| id.try_into().unwrap_or(i64::MAX));
+    conn.prepare(
+        "SELECT a.id, a.actor_pseudonym, m.display_name, a.action, a.target, a.before_json, a.after_json, a.created_at \
+         FROM server_audit_log a \
+         LEFT JOIN server_members m \
+           ON m.community_id = a.community_id AND m.pseudonym_key_hex = a.actor_pseudonym \
+         WHERE a.community_id = ?1 AND a.id < ?2 AND (?3 IS NULL OR a.action = ?3) \
+         ORDER BY a.id DESC LIMIT ?4",
+    )
+    .and_then(|mut stmt| {
+        let rows = stmt.query_map(
+            params![community_id, before, action.map(AuditAction::as_str), limit.min(MAX_PAGE)],
+            |row| {
+                let id: i64 = row.get(0)?;
+                let action: String = row.get(3)?;
+                let created_at: i64 = row.get(7)?;
+                // Entries of actions this build doesn't know are skipped
+                let Some(action) = AuditAction::from_db(&action) else {
+                    return Ok(None);
+                };
+                Ok(Some(AuditEntryDto {
+                    id: id.try_into().unwrap_or(0u64),
+                    actor_pseudonym: row.get(1)?,
+                    actor_name: row.get(2)?,
+                    action,
+                    target: row.get(4)?,
+                    before: row.get(5)?,
+                    after: row.get(6)?,
+                    created_at: created_at.try_into().unwrap_or(0u64),
+                }))
+            },
+        )?;
+        rows.collect::<Result<Vec<_>, _>>()
+    })
+    .unwrap_or_default()
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Delete audit log entries older than their community's retention.
+pub fn purge_expired(state: &Arc<ServerState>) {
+    let retained: Vec<(String, u32)> = {
+        let hosted = state.hosted.read();
+        hosted
+            .values()
+            .filter(|c| c.audit_retention_secs > 0)
+            .map(|c| (c.community_id.clone(), c.audit_retention_secs))
+            .collect()
+    };
+    if retained.is_empty() {
+        return;
+    }
+
+    let now = timestamp_now_secs();
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    for (community_id, secs) in retained {
+        match db.execute(
+            "DELETE FROM server_audit_log WHERE community_id = ? AND created_at < ?",
+            params![community_id, now - i64::from(secs)],
+        ) {
+            Ok(0) => {}
+            Ok(n) => {
+                tracing::debug!(community = %community_id, count = n, "expired audit log entries");
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, community = %community_id, "failed to purge expired audit log entries");
+            }
+        }
+    }
+}
+
+fn timestamp_now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .try_into()
+        .unwrap_or(i64::MAX)
+}
@@ -1,17 +1,22 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
 use rekindle_protocol::dht::community::{
-    OverwriteType, PermissionOverwrite, RoleDefinition,
-    SUBKEY_CHANNELS, SUBKEY_MEK, SUBKEY_MEMBERS, SUBKEY_METADATA, SUBKEY_SERVER_ROUTE,
-    ROLE_EVERYONE_ID, permissions,
+    ChannelEntry, MemberEntry, OverwriteType, PermissionOverwrite, RoleDefinition,
+    SUBKEY_CHANNELS, SUBKEY_MEK, SUBKEY_MEMBERS, SUBKEY_METADATA, SUBKEY_ROLES,
+    SUBKEY_SERVER_ROUTE, ROLE_EVERYONE_ID, permissions,
 };
+use rekindle_protocol::dht::directory::{self, DirectoryListing};
+use rekindle_protocol::dht::paged_list::{ListEntry, ListWriter};
 use rekindle_protocol::dht::DHTManager;
+use rekindle_protocol::messaging::envelope::HistoryVisibility;
 use rusqlite::params;
 use tokio::sync::mpsc;
 
+use crate::audit;
 use crate::mek;
-use crate::server_state::{HostedCommunity, ServerChannel, ServerMember, ServerState};
+use crate::server_state::{HostedCommunity, ServerChannel, ServerMember, ServerState, ServerThread};
 
 /// Load members for a community from the server database.
 fn load_members_from_db(
@@ -21,7 +26,7 @@ fn load_members_from_db(
     let db = state.db.lock().map_err(|e| e.to_string())?;
     let mut stmt = db
         .prepare(
-            "SELECT pseudonym_key_hex, display_name, joined_at, route_blob FROM server_members WHERE community_id = ?",
+            "SELECT pseudonym_key_hex, display_name, joined_at, route_blob, joined_generation FROM server_members WHERE community_id = ?",
         )
         .map_err(|e| e.to_string())?;
     let rows = stmt
@@ -31,6 +36,7 @@ fn load_members_from_db(
                 display_name: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
                 role_ids: Vec::new(), // filled below
                 joined_at: row.get(2)?,
+                joined_generation: row.get::<_, i64>(4)?.try_into().unwrap_or(0u64),
                 route_blob: row.get(3)?,
                 timeout_until: None,  // filled below
             })
@@ -86,7 +92,7 @@ fn load_channels_from_db(
     let db = state.db.lock().map_err(|e| e.to_string())?;
     let mut stmt = db
         .prepare(
-            "SELECT id, name, channel_type, sort_order FROM server_channels WHERE community_id = ?",
+            "SELECT id, name, channel_type, sort_order, retention_secs FROM server_channels WHERE community_id = ?",
         )
         .map_err(|e| e.to_string())?;
     let rows = stmt
@@ -96,6 +102,7 @@ fn load_channels_from_db(
                 name: row.get(1)?,
                 channel_type: row.get(2)?,
                 sort_order: row.get(3)?,
+                retention_secs: row.get(4)?,
                 permission_overwrites: Vec::new(), // filled below
             })
         })
@@ -140,6 +147,55 @@ fn load_channels_from_db(
     Ok(channels)
 }
 
+/// Load threads, and their members, for a community from the server database.
+fn load_threads_from_db(
+    state: &Arc<ServerState>,
+    community_id: &str,
+) -> Result<Vec<ServerThread>, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = db
+        .prepare(
+            "SELECT id, channel_id, parent_message_id, name, creator_pseudonym, private, archived, created_at \
+             FROM server_threads WHERE community_id = ? ORDER BY created_at",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![community_id], |row| {
+            Ok(ServerThread {
+                id: row.get(0)?,
+                channel_id: row.get(1)?,
+                parent_message_id: row.get(2)?,
+                name: row.get(3)?,
+                creator_pseudonym_hex: row.get(4)?,
+                private: row.get(5)?,
+                archived: row.get(6)?,
+                members: Vec::new(), // filled below
+                created_at: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    let mut threads: Vec<ServerThread> = rows.filter_map(Result::ok).collect();
+
+    let mut member_stmt = db
+        .prepare(
+            "SELECT thread_id, pseudonym_key_hex FROM server_thread_members \
+             WHERE community_id = ? ORDER BY joined_at",
+        )
+        .map_err(|e| e.to_string())?;
+    let member_rows = member_stmt
+        .query_map(params![community_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    for (thread_id, pseudonym) in member_rows.flatten() {
+        if let Some(thread) = threads.iter_mut().find(|t| t.id == thread_id) {
+            thread.members.push(pseudonym);
+        }
+    }
+
+    Ok(threads)
+}
+
 /// Load role definitions for a community from the server database.
 fn load_roles_from_db(
     state: &Arc<ServerState>,
@@ -379,10 +435,10 @@ pub async fn host_community(
         }
     }
 
-    let mek_val = mek::load_latest_mek(state, community_id)
-        .unwrap_or_else(|| mek::create_initial_mek(state, community_id));
+    let mek_val = mek::load_custody(state, community_id);
 
     let channels = load_channels_from_db(state, community_id)?;
+    let threads = load_threads_from_db(state, community_id)?;
 
     // Load or create default roles
     let mut roles = load_roles_from_db(state, community_id)?;
@@ -395,8 +451,15 @@ pub async fn host_community(
     let (route_id, route_blob, dht_opened) =
         setup_dht_and_route(state, community_id, dht_record_key, owner_keypair_hex).await?;
 
-    // Load description and creator_pseudonym from DB
-    let (description, mut creator_pseudonym_hex) = {
+    // Load description, creator_pseudonym, history visibility, the
+    // directory listing, the invite-only flag and audit log retention from DB
+    let (
+        description,
+        mut creator_pseudonym_hex,
+        history_visibility,
+        (listed, listing_game_ids),
+        (invite_only, audit_retention_secs),
+    ) = {
         let db = state.db.lock().map_err(|e| e.to_string())?;
         let desc = db
             .query_row(
@@ -412,7 +475,29 @@ pub async fn host_community(
                 |row| row.get::<_, String>(0),
             )
             .unwrap_or_default();
-        (desc, creator)
+        let visibility = db
+            .query_row(
+                "SELECT history_visibility FROM hosted_communities WHERE id = ?",
+                params![community_id],
+                |row| row.get::<_, String>(0),
+            )
+            .map_or(HistoryVisibility::Full, |v| HistoryVisibility::from_db(&v));
+        let listing = db
+            .query_row(
+                "SELECT listed, listing_game_ids FROM hosted_communities WHERE id = ?",
+                params![community_id],
+                |row| Ok((row.get::<_, bool>(0)?, row.get::<_, String>(1)?)),
+            )
+            .map(|(listed, games)| (listed, serde_json::from_str(&games).unwrap_or_default()))
+            .unwrap_or_default();
+        let settings = db
+            .query_row(
+                "SELECT invite_only, audit_retention_secs FROM hosted_communities WHERE id = ?",
+                params![community_id],
+                |row| Ok((row.get::<_, bool>(0)?, row.get::<_, u32>(1)?)),
+            )
+            .unwrap_or((false, audit::DEFAULT_RETENTION_SECS));
+        (desc, creator, visibility, listing, settings)
     };
 
     // If a creator pseudonym key was provided and the creator isn't already
@@ -432,8 +517,8 @@ pub async fn host_community(
         {
             let db = state.db.lock().map_err(|e| e.to_string())?;
             let _ = db.execute(
-                "INSERT OR IGNORE INTO server_members (community_id, pseudonym_key_hex, display_name, joined_at) VALUES (?,?,?,?)",
-                params![community_id, creator_pseudonym_key, creator_display_name, now],
+                "INSERT OR IGNORE INTO server_members (community_id, pseudonym_key_hex, display_name, joined_at, joined_generation) VALUES (?,?,?,?,?)",
+                params![community_id, creator_pseudonym_key, creator_display_name, now, i64::try_from(mek_val.generation()).unwrap_or(i64::MAX)],
             );
             for role_id in &owner_role_ids {
                 let _ = db.execute(
@@ -453,6 +538,7 @@ pub async fn host_community(
             display_name: creator_display_name.to_string(),
             role_ids: owner_role_ids,
             joined_at: now,
+            joined_generation: mek_val.generation(),
             route_blob: None,
             timeout_until: None,
         });
@@ -475,8 +561,15 @@ pub async fn host_community(
         mek: mek_val,
         members,
         channels,
+        threads,
         roles,
         creator_pseudonym_hex,
+        history_visibility,
+        listed,
+        listing_game_ids,
+        invite_only,
+        audit_retention_secs,
+        voice: HashMap::new(),
     };
 
     state
@@ -490,7 +583,15 @@ pub async fn host_community(
         publish_metadata(state, community_id, name).await;
         publish_channels(state, community_id).await;
         publish_member_roster(state, community_id).await;
+        publish_roles(state, community_id).await;
         publish_mek_bundle(state, community_id).await;
+        if listed {
+            let st = Arc::clone(state);
+            let cid = community_id.to_string();
+            tokio::spawn(async move {
+                publish_listing(&st, &cid).await;
+            });
+        }
     } else {
         tracing::warn!(
             community = %community_id,
@@ -514,6 +615,7 @@ pub fn unhost_community(state: &Arc<ServerState>, community_id: &str) {
         if let Some(route_id) = community.route_id {
             let _ = state.api.release_private_route(route_id);
         }
+        state.dht_lists.lock().retain(|(id, _), _| id != community_id);
         // Remove from DB so it's not re-loaded on restart
         // CASCADE FKs clean up server_members, server_channels, server_mek
         if let Ok(db) = state.db.lock() {
@@ -530,6 +632,9 @@ pub fn unhost_community(state: &Arc<ServerState>, community_id: &str) {
 
 /// DHT keep-alive loop: re-writes all subkeys every 2 minutes to prevent expiration.
 ///
+/// The channel, member and role lists are paged, so a keepalive rewrites
+/// only their heads unless something changed that wasn't published yet.
+///
 /// Veilid private routes have a TTL of ~5 minutes. By re-allocating every 2 minutes
 /// we ensure routes are always fresh before they expire.
 pub async fn dht_keepalive_loop(
@@ -562,6 +667,133 @@ pub async fn dht_keepalive_loop(
     }
 }
 
+/// How often `retention_sweep_loop` deletes expired channel messages.
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Delete channel messages older than their channel's retention, and audit
+/// log entries older than their community's, every minute. `GetMessages`
+/// already hides expired messages in between.
+pub async fn retention_sweep_loop(state: Arc<ServerState>) {
+    let mut tick = tokio::time::interval(RETENTION_SWEEP_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tick.tick().await;
+        purge_expired_messages(&state);
+        audit::purge_expired(&state);
+    }
+}
+
+/// Delete every message that outlived its channel's retention, threads
+/// following their parent channel's. Reactions go with them
+/// (`ON DELETE CASCADE`).
+pub fn purge_expired_messages(state: &Arc<ServerState>) {
+    let retained: Vec<(String, String, u32)> = {
+        let hosted = state.hosted.read();
+        hosted
+            .values()
+            .flat_map(|c| {
+                c.channels
+                    .iter()
+                    .filter(|ch| ch.retention_secs > 0)
+                    .flat_map(move |ch| {
+                        let threads = c
+                            .threads
+                            .iter()
+                            .filter(move |t| t.channel_id == ch.id)
+                            .map(|t| t.id.clone());
+                        std::iter::once(ch.id.clone())
+                            .chain(threads)
+                            .map(move |id| (c.community_id.clone(), id, ch.retention_secs))
+                    })
+            })
+            .collect()
+    };
+    if retained.is_empty() {
+        return;
+    }
+
+    let now = timestamp_now_secs().cast_signed();
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    for (community_id, channel_id, secs) in retained {
+        match db.execute(
+            "DELETE FROM server_messages WHERE community_id = ? AND channel_id = ? AND timestamp < ?",
+            params![community_id, channel_id, now - i64::from(secs)],
+        ) {
+            Ok(0) => {}
+            Ok(n) => {
+                tracing::debug!(community = %community_id, channel = %channel_id, count = n, "expired channel messages");
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, community = %community_id, "failed to purge expired messages");
+            }
+        }
+    }
+}
+
+/// Republish every listed community's directory listing before it
+/// expires. Each is first published when it is listed or hosted.
+pub async fn directory_refresh_loop(state: Arc<ServerState>) {
+    let period = Duration::from_secs(directory::LISTING_REFRESH_SECS);
+    let mut tick = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tick.tick().await;
+        let listed: Vec<String> = {
+            let hosted = state.hosted.read();
+            hosted
+                .values()
+                .filter(|c| c.listed)
+                .map(|c| c.community_id.clone())
+                .collect()
+        };
+        for community_id in listed {
+            publish_listing(&state, &community_id).await;
+        }
+    }
+}
+
+/// Write a community's directory listing to its listing record.
+///
+/// A delisted community's record announces the delisting, so it drops out
+/// before its last listing expires; games dropped from a listing drop out
+/// of their shards the same way.
+pub async fn publish_listing(state: &Arc<ServerState>, community_id: &str) {
+    let (listing, owner_keypair_hex) = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return;
+        };
+        let listing = DirectoryListing {
+            community_id: community.dht_record_key.clone(),
+            name: community.name.chars().take(directory::MAX_LISTING_NAME).collect(),
+            description: community
+                .description
+                .chars()
+                .take(directory::MAX_LISTING_DESCRIPTION)
+                .collect(),
+            member_count: u32::try_from(community.members.len()).unwrap_or(u32::MAX),
+            game_ids: community.listing_game_ids.clone(),
+            listing_key: String::new(),
+            published_at: timestamp_now_secs(),
+            delisted: !community.listed,
+            signature: Vec::new(),
+        };
+        (listing, community.owner_keypair_hex.clone())
+    };
+    let key = directory::listing_identity(owner_keypair_hex.as_bytes());
+    let listing = listing.signed(&key);
+
+    match directory::publish_listing(&state.routing_context, &listing, &key).await {
+        Ok(()) => tracing::debug!(community = %community_id, delisted = listing.delisted, "published directory listing"),
+        Err(e) => {
+            tracing::warn!(error = %e, community = %community_id, "failed to publish directory listing");
+        }
+    }
+}
+
 /// Data needed per-community during a keepalive cycle.
 struct KeepaliveData {
     community_id: String,
@@ -683,6 +915,7 @@ async fn rewrite_all_communities(state: &Arc<ServerState>) {
         publish_metadata(state, &entry.community_id, &entry.name).await;
         publish_channels(state, &entry.community_id).await;
         publish_member_roster(state, &entry.community_id).await;
+        publish_roles(state, &entry.community_id).await;
 
         tracing::debug!(community = %entry.community_id, "DHT keep-alive refresh done");
     }
@@ -783,7 +1016,7 @@ pub fn clear_dead_member_routes(
 
 /// Publish community metadata (name, description) to DHT subkey 0.
 pub async fn publish_metadata(state: &Arc<ServerState>, community_id: &str, name: &str) {
-    let (dht_key, owner_public_key) = {
+    let (dht_key, owner_public_key, listing_key) = {
         let hosted = state.hosted.read();
         let Some(community) = hosted.get(community_id) else {
             return;
@@ -792,7 +1025,11 @@ pub async fn publish_metadata(state: &Arc<ServerState>, community_id: &str, name
         let owner_key = parse_owner_keypair(&community.owner_keypair_hex)
             .map(|kp| kp.key().to_string())
             .unwrap_or_default();
-        (community.dht_record_key.clone(), owner_key)
+        // Vouch for our directory listing while we have one
+        let listing_key = community.listed.then(|| {
+            directory::listing_identity(community.owner_keypair_hex.as_bytes()).public_key_hex()
+        });
+        (community.dht_record_key.clone(), owner_key, listing_key)
     };
 
     let now = timestamp_now_secs();
@@ -803,6 +1040,7 @@ pub async fn publish_metadata(state: &Arc<ServerState>, community_id: &str, name
         created_at: now,
         owner_key: owner_public_key,
         last_refreshed: now,
+        listing_key,
     };
     let data = serde_json::to_vec(&metadata).unwrap_or_default();
 
@@ -812,72 +1050,120 @@ pub async fn publish_metadata(state: &Arc<ServerState>, community_id: &str, name
     }
 }
 
+/// Publish one of a community's paged lists to its subkey.
+///
+/// The first publish opens a [`ListWriter`], migrating a subkey that still
+/// holds the whole list; it is kept in `state.dht_lists` so later publishes
+/// write only the pages that changed.
+async fn publish_list(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    dht_key: &str,
+    owner_keypair_hex: &str,
+    subkey: u32,
+    entries: Vec<ListEntry>,
+) -> Result<(), String> {
+    let slot = Arc::clone(
+        state
+            .dht_lists
+            .lock()
+            .entry((community_id.to_string(), subkey))
+            .or_default(),
+    );
+    let mut writer = slot.lock().await;
+    if writer.is_none() {
+        let owner = parse_owner_keypair(owner_keypair_hex)?;
+        let opened = ListWriter::open(&state.routing_context, dht_key, subkey, owner)
+            .await
+            .map_err(|e| e.to_string())?;
+        *writer = Some(opened);
+    }
+    match writer.as_mut() {
+        Some(writer) => writer.publish(entries).await.map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
 /// Publish the channel list to DHT subkey 1.
 pub async fn publish_channels(state: &Arc<ServerState>, community_id: &str) {
-    let (dht_key, channels_json) = {
+    let (dht_key, owner_keypair_hex, entries) = {
         let hosted = state.hosted.read();
         let Some(community) = hosted.get(community_id) else {
             return;
         };
-        let wrapper = serde_json::json!({
-            "channels": community.channels.iter().map(|ch| {
-                serde_json::json!({
-                    "id": ch.id,
-                    "name": ch.name,
-                    "channelType": ch.channel_type,
-                    "sortOrder": ch.sort_order,
-                })
-            }).collect::<Vec<_>>(),
-            "lastRefreshed": timestamp_now_secs(),
-        });
+        let entries = community
+            .channels
+            .iter()
+            .map(|ch| {
+                let entry = ChannelEntry {
+                    id: ch.id.clone(),
+                    name: ch.name.clone(),
+                    channel_type: ch.channel_type.clone(),
+                    sort_order: u16::try_from(ch.sort_order).unwrap_or(0),
+                    latest_message_key: None,
+                    permission_overwrites: ch.permission_overwrites.clone(),
+                    retention_secs: ch.retention_secs,
+                };
+                ListEntry::new(&ch.id, &entry)
+            })
+            .collect::<Result<Vec<_>, _>>();
         (
             community.dht_record_key.clone(),
-            serde_json::to_vec(&wrapper).unwrap_or_default(),
+            community.owner_keypair_hex.clone(),
+            entries,
         )
     };
 
-    let mgr = DHTManager::new(state.routing_context.clone());
-    if let Err(e) = mgr
-        .set_value(&dht_key, SUBKEY_CHANNELS, channels_json)
-        .await
-    {
+    let result = match entries {
+        Ok(entries) => {
+            publish_list(state, community_id, &dht_key, &owner_keypair_hex, SUBKEY_CHANNELS, entries).await
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
         tracing::warn!(error = %e, community = %community_id, "failed to publish channels to DHT");
     }
 }
 
 /// Publish the current member roster to DHT subkey 2.
 ///
-/// Serializes all members with their pseudonym key, role, display name, and join time.
+/// Serializes all members with their pseudonym key, roles, display name,
+/// join time and timeout.
 pub async fn publish_member_roster(state: &Arc<ServerState>, community_id: &str) {
-    let (dht_key, roster_json) = {
+    let (dht_key, owner_keypair_hex, entries) = {
         let hosted = state.hosted.read();
         let Some(community) = hosted.get(community_id) else {
             return;
         };
-
-        let wrapper = serde_json::json!({
-            "members": community.members.iter().map(|m| {
-                serde_json::json!({
-                    "pseudonymKey": m.pseudonym_key_hex,
-                    "roleIds": m.role_ids,
-                    "displayName": m.display_name,
-                    "joinedAt": m.joined_at,
-                })
-            }).collect::<Vec<_>>(),
-            "lastRefreshed": timestamp_now_secs(),
-        });
-
+        let entries = community
+            .members
+            .iter()
+            .map(|m| {
+                let entry = MemberEntry {
+                    pseudonym_key: m.pseudonym_key_hex.clone(),
+                    display_name: Some(m.display_name.clone()),
+                    role: None,
+                    role_ids: m.role_ids.clone(),
+                    joined_at: u64::try_from(m.joined_at).unwrap_or(0),
+                    timeout_until: m.timeout_until,
+                };
+                ListEntry::new(&m.pseudonym_key_hex, &entry)
+            })
+            .collect::<Result<Vec<_>, _>>();
         (
             community.dht_record_key.clone(),
-            serde_json::to_vec(&wrapper).unwrap_or_default(),
+            community.owner_keypair_hex.clone(),
+            entries,
         )
     };
 
-    let mgr = DHTManager::new(state.routing_context.clone());
-    if let Err(e) = mgr
-        .set_value(&dht_key, SUBKEY_MEMBERS, roster_json)
-        .await
-    {
+    let result = match entries {
+        Ok(entries) => {
+            publish_list(state, community_id, &dht_key, &owner_keypair_hex, SUBKEY_MEMBERS, entries).await
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
         tracing::warn!(
             error = %e,
             community = %community_id,
@@ -886,6 +1172,36 @@ pub async fn publish_member_roster(state: &Arc<ServerState>, community_id: &str)
     }
 }
 
+/// Publish the role definitions to DHT subkey 3.
+pub async fn publish_roles(state: &Arc<ServerState>, community_id: &str) {
+    let (dht_key, owner_keypair_hex, entries) = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return;
+        };
+        let entries = community
+            .roles
+            .iter()
+            .map(|role| ListEntry::new(role.id.to_string(), role))
+            .collect::<Result<Vec<_>, _>>();
+        (
+            community.dht_record_key.clone(),
+            community.owner_keypair_hex.clone(),
+            entries,
+        )
+    };
+
+    let result = match entries {
+        Ok(entries) => {
+            publish_list(state, community_id, &dht_key, &owner_keypair_hex, SUBKEY_ROLES, entries).await
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        tracing::warn!(error = %e, community = %community_id, "failed to publish roles to DHT");
+    }
+}
+
 /// Publish MEK generation metadata to DHT subkey 5.
 ///
 /// Only the generation number and a refresh timestamp are written (no key material).
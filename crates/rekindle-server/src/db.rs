@@ -3,7 +3,7 @@ use std::sync::{Arc, Mutex};
 use rusqlite::Connection;
 
 /// Server-side schema version. Bump when the schema changes.
-const SERVER_SCHEMA_VERSION: i64 = 4;
+const SERVER_SCHEMA_VERSION: i64 = 14;
 
 /// Open (or create) the server `SQLite` database and run migrations.
 pub fn open_server_db(path: &str) -> Result<Arc<Mutex<Connection>>, String> {
@@ -71,7 +71,39 @@ CREATE TABLE IF NOT EXISTS hosted_communities (
     name TEXT NOT NULL,
     description TEXT NOT NULL DEFAULT '',
     creator_pseudonym TEXT NOT NULL DEFAULT '',
-    created_at INTEGER NOT NULL
+    created_at INTEGER NOT NULL,
+    -- 1 once MEK custody moved to members (see server_mek_wrapped)
+    zero_knowledge INTEGER NOT NULL DEFAULT 0,
+    -- Current MEK generation while zero_knowledge = 1
+    mek_generation INTEGER NOT NULL DEFAULT 0,
+    -- 1 once MEKs come from a TreeKEM group (implies zero_knowledge = 1);
+    -- mek_generation is then the tree epoch
+    tree_kem INTEGER NOT NULL DEFAULT 0,
+    history_visibility TEXT NOT NULL DEFAULT 'full' CHECK(history_visibility IN ('full','since_join')),
+    -- 1 while the community is listed in the public directory
+    listed INTEGER NOT NULL DEFAULT 0,
+    -- JSON array of GameEntry ids the listing is tagged with
+    listing_game_ids TEXT NOT NULL DEFAULT '[]',
+    -- 1 when newcomers must redeem a server_invites code to join
+    invite_only INTEGER NOT NULL DEFAULT 0,
+    -- Seconds server_audit_log entries are kept for (default 90 days); 0 keeps them forever
+    audit_retention_secs INTEGER NOT NULL DEFAULT 7776000
+);
+
+-- Invite codes; each join that redeems one counts as a use
+CREATE TABLE IF NOT EXISTS server_invites (
+    community_id TEXT NOT NULL REFERENCES hosted_communities(id) ON DELETE CASCADE,
+    code TEXT NOT NULL,
+    creator_pseudonym TEXT NOT NULL,
+    -- NULL = unlimited
+    max_uses INTEGER,
+    uses INTEGER NOT NULL DEFAULT 0,
+    -- Unix seconds; NULL = never expires
+    expires_at INTEGER,
+    -- JSON array of role ids given to whoever joins with the code
+    grant_role_ids TEXT NOT NULL DEFAULT '[]',
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (community_id, code)
 );
 
 CREATE TABLE IF NOT EXISTS server_members (
@@ -79,6 +111,8 @@ CREATE TABLE IF NOT EXISTS server_members (
     pseudonym_key_hex TEXT NOT NULL,
     display_name TEXT,
     joined_at INTEGER NOT NULL,
+    -- MEK generation current when the member joined (history_visibility = 'since_join')
+    joined_generation INTEGER NOT NULL DEFAULT 0,
     signal_session_data BLOB,
     route_blob BLOB,
     PRIMARY KEY (community_id, pseudonym_key_hex)
@@ -92,12 +126,68 @@ CREATE TABLE IF NOT EXISTS server_mek (
     PRIMARY KEY (community_id, generation)
 );
 
+-- Zero-knowledge MEK copies, each wrapped to one member's pseudonym
+CREATE TABLE IF NOT EXISTS server_mek_wrapped (
+    community_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    pseudonym_key_hex TEXT NOT NULL,
+    wrapped_key BLOB NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (community_id, generation, pseudonym_key_hex),
+    FOREIGN KEY (community_id, pseudonym_key_hex)
+        REFERENCES server_members(community_id, pseudonym_key_hex) ON DELETE CASCADE
+);
+
+-- Signed statements that `member` belongs to the community, made by a
+-- member's pseudonym or an invite key (rekindle_crypto::group::member_vouch)
+CREATE TABLE IF NOT EXISTS server_member_vouches (
+    community_id TEXT NOT NULL REFERENCES hosted_communities(id) ON DELETE CASCADE,
+    voucher TEXT NOT NULL,
+    member TEXT NOT NULL,
+    signature BLOB NOT NULL,
+    PRIMARY KEY (community_id, voucher, member)
+);
+
+-- Accepted TreeKEM commits, one per epoch (the first one submitted wins)
+CREATE TABLE IF NOT EXISTS server_tree_commits (
+    community_id TEXT NOT NULL REFERENCES hosted_communities(id) ON DELETE CASCADE,
+    epoch INTEGER NOT NULL,
+    sender_pseudonym TEXT NOT NULL,
+    commit_data BLOB NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (community_id, epoch)
+);
+
+-- Latest TreeKEM welcome for each member added by a commit
+CREATE TABLE IF NOT EXISTS server_tree_welcomes (
+    community_id TEXT NOT NULL,
+    pseudonym_key_hex TEXT NOT NULL,
+    epoch INTEGER NOT NULL,
+    welcome_data BLOB NOT NULL,
+    PRIMARY KEY (community_id, pseudonym_key_hex),
+    FOREIGN KEY (community_id, pseudonym_key_hex)
+        REFERENCES server_members(community_id, pseudonym_key_hex) ON DELETE CASCADE
+);
+
+-- Key packages members published to be added to the tree
+CREATE TABLE IF NOT EXISTS server_tree_key_packages (
+    community_id TEXT NOT NULL,
+    pseudonym_key_hex TEXT NOT NULL,
+    key_package BLOB NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (community_id, pseudonym_key_hex),
+    FOREIGN KEY (community_id, pseudonym_key_hex)
+        REFERENCES server_members(community_id, pseudonym_key_hex) ON DELETE CASCADE
+);
+
 CREATE TABLE IF NOT EXISTS server_channels (
     community_id TEXT NOT NULL REFERENCES hosted_communities(id) ON DELETE CASCADE,
     id TEXT NOT NULL,
     name TEXT NOT NULL,
     channel_type TEXT NOT NULL CHECK(channel_type IN ('text','voice')),
     sort_order INTEGER NOT NULL DEFAULT 0,
+    -- Seconds messages are kept for; 0 keeps them forever
+    retention_secs INTEGER NOT NULL DEFAULT 0,
     PRIMARY KEY (community_id, id)
 );
 
@@ -105,15 +195,62 @@ CREATE TABLE IF NOT EXISTS server_messages (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     community_id TEXT NOT NULL,
     channel_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
     sender_pseudonym TEXT NOT NULL,
     ciphertext BLOB NOT NULL,
     mek_generation INTEGER NOT NULL,
-    timestamp INTEGER NOT NULL
+    timestamp INTEGER NOT NULL,
+    edited_at INTEGER,
+    -- Message ID this one replies to, in the same channel or thread
+    reply_to TEXT,
+    UNIQUE (community_id, message_id)
 );
 
 CREATE INDEX IF NOT EXISTS idx_server_messages
     ON server_messages(community_id, channel_id, timestamp);
 
+-- Threads started from a message; their messages use the thread ID as
+-- server_messages.channel_id
+CREATE TABLE IF NOT EXISTS server_threads (
+    community_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    parent_message_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    creator_pseudonym TEXT NOT NULL,
+    private INTEGER NOT NULL DEFAULT 0,
+    archived INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (community_id, id),
+    UNIQUE (community_id, parent_message_id),
+    FOREIGN KEY (community_id, channel_id)
+        REFERENCES server_channels(community_id, id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS server_thread_members (
+    community_id TEXT NOT NULL,
+    thread_id TEXT NOT NULL,
+    pseudonym_key_hex TEXT NOT NULL,
+    joined_at INTEGER NOT NULL,
+    PRIMARY KEY (community_id, thread_id, pseudonym_key_hex),
+    FOREIGN KEY (community_id, thread_id)
+        REFERENCES server_threads(community_id, id) ON DELETE CASCADE,
+    FOREIGN KEY (community_id, pseudonym_key_hex)
+        REFERENCES server_members(community_id, pseudonym_key_hex) ON DELETE CASCADE
+);
+
+-- One row per (member, emoji) on a message
+CREATE TABLE IF NOT EXISTS server_reactions (
+    community_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    pseudonym_key_hex TEXT NOT NULL,
+    emoji TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (community_id, message_id, pseudonym_key_hex, emoji),
+    FOREIGN KEY (community_id, message_id)
+        REFERENCES server_messages(community_id, message_id) ON DELETE CASCADE
+);
+
 CREATE TABLE IF NOT EXISTS banned_members (
     community_id TEXT NOT NULL REFERENCES hosted_communities(id) ON DELETE CASCADE,
     pseudonym_key_hex TEXT NOT NULL,
@@ -145,6 +282,24 @@ CREATE TABLE IF NOT EXISTS server_member_roles (
         REFERENCES server_members(community_id, pseudonym_key_hex) ON DELETE CASCADE
 );
 
+-- Moderation and configuration actions, newest last
+CREATE TABLE IF NOT EXISTS server_audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    community_id TEXT NOT NULL REFERENCES hosted_communities(id) ON DELETE CASCADE,
+    actor_pseudonym TEXT NOT NULL,
+    -- AuditAction::as_str
+    action TEXT NOT NULL,
+    -- Pseudonym, channel ID, role ID or invite code the action applied to
+    target TEXT,
+    -- JSON of the target before and after the action
+    before_json TEXT,
+    after_json TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_server_audit_log
+    ON server_audit_log(community_id, id);
+
 -- Per-channel permission overwrites
 CREATE TABLE IF NOT EXISTS server_channel_overwrites (
     community_id TEXT NOT NULL,
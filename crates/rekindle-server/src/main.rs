@@ -1,17 +1,19 @@
 #![recursion_limit = "512"]
 
+mod audit;
 mod community_host;
 mod db;
 mod ipc;
 mod mek;
 mod rpc;
 mod server_state;
+mod voice_relay;
 
 use std::sync::Arc;
 
 use parking_lot::RwLock;
 use tokio::sync::mpsc;
-use veilid_core::VeilidUpdate;
+use veilid_core::{SafetySelection, Sequencing, VeilidUpdate};
 
 use server_state::ServerState;
 
@@ -81,11 +83,19 @@ async fn main() {
         .routing_context()
         .expect("failed to create routing context");
 
+    // Relayed voice trades sender privacy for latency, like the clients' own voice transport
+    let voice_routing_context = routing_context
+        .clone()
+        .with_safety(SafetySelection::Unsafe(Sequencing::NoPreference))
+        .expect("failed to create voice routing context");
+
     let state = Arc::new(ServerState {
         api: veilid_api,
         routing_context,
+        voice_routing_context,
         db,
         hosted: RwLock::new(std::collections::HashMap::new()),
+        dht_lists: parking_lot::Mutex::new(std::collections::HashMap::new()),
         started_at: timestamp_now_secs(),
     });
 
@@ -97,6 +107,15 @@ async fn main() {
         keepalive_shutdown_rx,
     ));
 
+    // Start the voice relay's speaking detection
+    tokio::spawn(voice_relay::speaking_sweep_loop(Arc::clone(&state)));
+
+    // Start deleting channel messages past their retention
+    tokio::spawn(community_host::retention_sweep_loop(Arc::clone(&state)));
+
+    // Start refreshing listed communities' directory listings
+    tokio::spawn(community_host::directory_refresh_loop(Arc::clone(&state)));
+
     // Start the IPC listener
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
     let ipc_state = Arc::clone(&state);
@@ -304,8 +323,12 @@ async fn server_dispatch_loop(
                 });
             }
             VeilidUpdate::AppMessage(msg) => {
-                let _msg = *msg;
-                // Future: handle member route updates, broadcast receipts
+                // Voice frames and probes carry the same tags as between clients
+                match msg.message().first() {
+                    Some(b'V') => voice_relay::forward_frame(&state, msg.route_id(), msg.message()),
+                    Some(b'E') => voice_relay::answer_probe(&state, msg.route_id(), msg.message()),
+                    _ => {}
+                }
             }
             VeilidUpdate::RouteChange(change) => {
                 let dead_routes: Vec<veilid_core::RouteId> = change.dead_routes;
@@ -313,6 +336,7 @@ async fn server_dispatch_loop(
                 community_host::handle_server_route_change(&state, &dead_routes).await;
                 if !dead_remote_routes.is_empty() {
                     community_host::clear_dead_member_routes(&state, &dead_remote_routes);
+                    voice_relay::drop_dead_routes(&state, &dead_remote_routes);
                 }
             }
             VeilidUpdate::Attachment(att) => {
@@ -1,9 +1,55 @@
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 
 use rekindle_crypto::group::media_key::MediaEncryptionKey;
-use rusqlite::params;
+use rekindle_crypto::group::{mek_delivery, member_vouch};
+use rekindle_crypto::signal::{
+    MemoryIdentityStore, MemoryPreKeyStore, PreKeyBundle, SessionStore, SignalSessionManager,
+};
+use rekindle_crypto::CryptoError;
+use rekindle_protocol::messaging::envelope::{
+    MekSessionInit, MemberVouchDto, TreeCommitDto, TreeWelcomeDto, WrappedMekDto,
+};
+use rusqlite::{params, Connection, OptionalExtension};
 
-use crate::server_state::ServerState;
+use crate::server_state::{HostedCommunity, MekCustody, ServerState};
+
+/// Most commits returned by one `GetCommits`; clients page by epoch.
+const MAX_COMMITS_PER_FETCH: i64 = 64;
+
+/// Load a community's MEK custody when it is (re)hosted.
+///
+/// Server-custody communities get their latest MEK from `server_mek`, or a
+/// fresh generation 1 if none exists yet. Zero-knowledge and tree
+/// communities only get their current generation (epoch) back.
+pub fn load_custody(state: &Arc<ServerState>, community_id: &str) -> MekCustody {
+    let zero_knowledge = {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        db.query_row(
+            "SELECT mek_generation, tree_kem FROM hosted_communities WHERE id = ? AND zero_knowledge = 1",
+            params![community_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?)),
+        )
+        .optional()
+        .unwrap_or(None)
+    };
+
+    match zero_knowledge {
+        Some((epoch, true)) => MekCustody::Tree {
+            epoch: epoch.try_into().unwrap_or(0u64),
+        },
+        Some((generation, false)) => MekCustody::Members {
+            generation: generation.try_into().unwrap_or(0u64),
+        },
+        None => MekCustody::Server(
+            load_latest_mek(state, community_id)
+                .unwrap_or_else(|| create_initial_mek(state, community_id)),
+        ),
+    }
+}
 
 /// Generate the initial MEK when a community is first hosted.
 pub fn create_initial_mek(
@@ -66,20 +112,55 @@ pub fn rotate_mek(
 pub fn load_latest_mek(
     state: &Arc<ServerState>,
     community_id: &str,
+) -> Option<MediaEncryptionKey> {
+    query_mek(
+        state,
+        community_id,
+        "SELECT generation, key_bytes FROM server_mek WHERE community_id = ?1 ORDER BY generation DESC LIMIT 1",
+        None,
+    )
+}
+
+/// Load one MEK generation for a community from the server database.
+///
+/// Older generations are kept after a rotation so remaining members can
+/// still fetch keys for history they missed.
+pub fn load_mek(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    generation: u64,
+) -> Option<MediaEncryptionKey> {
+    query_mek(
+        state,
+        community_id,
+        "SELECT generation, key_bytes FROM server_mek WHERE community_id = ?1 AND generation = ?2",
+        Some(generation),
+    )
+}
+
+fn query_mek(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sql: &str,
+    generation: Option<u64>,
 ) -> Option<MediaEncryptionKey> {
     let db = state.db.lock().unwrap_or_else(|e| {
         tracing::error!(error = %e, "server db mutex poisoned — recovering");
         e.into_inner()
     });
-    let result = db.query_row(
-        "SELECT generation, key_bytes FROM server_mek WHERE community_id = ? ORDER BY generation DESC LIMIT 1",
-        params![community_id],
-        |row| {
-            let gen: i64 = row.get(0)?;
-            let bytes: Vec<u8> = row.get(1)?;
-            Ok((gen, bytes))
-        },
-    );
+    let map_row = |row: &rusqlite::Row<'_>| {
+        let gen: i64 = row.get(0)?;
+        let bytes: Vec<u8> = row.get(1)?;
+        Ok((gen, bytes))
+    };
+    let result = match generation {
+        Some(g) => db.query_row(
+            sql,
+            params![community_id, i64::try_from(g).unwrap_or(i64::MAX)],
+            map_row,
+        ),
+        None => db.query_row(sql, params![community_id], map_row),
+    };
 
     match result {
         Ok((gen, bytes)) => {
@@ -96,6 +177,541 @@ pub fn load_latest_mek(
     }
 }
 
+/// Result of a MEK rotation request.
+pub enum Rotation {
+    /// Server custody: the new generation is already live.
+    Rotated(u64),
+    /// Zero-knowledge: a privileged member has to publish this generation.
+    KeysNeeded(u64),
+    /// Tree: a privileged member has to commit the membership change at
+    /// this epoch.
+    CommitNeeded(u64),
+}
+
+/// Rotate a community's MEK, or work out which generation members must
+/// publish when the server doesn't hold it.
+pub fn rotate(state: &Arc<ServerState>, community: &mut HostedCommunity) -> Rotation {
+    let new_generation = community.mek.generation() + 1;
+    match community.mek {
+        MekCustody::Server(_) => {
+            community.mek = MekCustody::Server(rotate_mek(
+                state,
+                &community.community_id,
+                new_generation,
+            ));
+            Rotation::Rotated(new_generation)
+        }
+        MekCustody::Members { .. } => Rotation::KeysNeeded(new_generation),
+        MekCustody::Tree { epoch } => Rotation::CommitNeeded(epoch),
+    }
+}
+
+/// Hand MEK custody to the community's privileged members, starting at the
+/// next generation.
+///
+/// Deletes every server-held generation and the members' delivery
+/// sessions. Every generation so far was known to the server, so none of
+/// them stays current: messages are refused until a key holder publishes
+/// the returned generation.
+pub fn enable_zero_knowledge(state: &Arc<ServerState>, community: &mut HostedCommunity) -> u64 {
+    let generation = community.mek.generation() + 1;
+    community.mek = MekCustody::Members { generation };
+    discard_server_keys(state, &community.community_id, generation, false);
+    tracing::info!(community = %community.community_id, generation, "MEK custody handed to members");
+    generation
+}
+
+/// Switch a community to a `TreeKEM` group starting at the next generation.
+///
+/// Like [`enable_zero_knowledge`] the server drops every key it holds; the
+/// owner then creates the tree at the returned epoch and adds members as
+/// they publish key packages.
+pub fn enable_tree_kem(state: &Arc<ServerState>, community: &mut HostedCommunity) -> u64 {
+    let epoch = community.mek.generation() + 1;
+    community.mek = MekCustody::Tree { epoch };
+    discard_server_keys(state, &community.community_id, epoch, true);
+    tracing::info!(community = %community.community_id, epoch, "MEK custody handed to a TreeKEM group");
+    epoch
+}
+
+fn discard_server_keys(state: &Arc<ServerState>, community_id: &str, generation: u64, tree_kem: bool) {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let result = db
+        .execute(
+            "UPDATE hosted_communities SET zero_knowledge = 1, tree_kem = ?, mek_generation = ? WHERE id = ?",
+            params![tree_kem, i64::try_from(generation).unwrap_or(i64::MAX), community_id],
+        )
+        .and_then(|_| {
+            db.execute(
+                "DELETE FROM server_mek WHERE community_id = ?",
+                params![community_id],
+            )
+        })
+        .and_then(|_| {
+            db.execute(
+                "UPDATE server_members SET signal_session_data = NULL WHERE community_id = ?",
+                params![community_id],
+            )
+        });
+    if let Err(e) = result {
+        tracing::error!(error = %e, community = %community_id, "failed to persist zero-knowledge MEK custody");
+    }
+}
+
+/// Store member-published wrapped MEKs and, when `generation` is new, make
+/// it the community's current generation.
+///
+/// Existing copies are never replaced, so when two key holders race to
+/// publish a generation the first one wins. Returns how many copies were
+/// stored.
+pub fn store_wrapped(
+    state: &Arc<ServerState>,
+    community: &mut HostedCommunity,
+    generation: u64,
+    wrapped_keys: &[WrappedMekDto],
+) -> Result<usize, String> {
+    let generation_i64 = i64::try_from(generation).unwrap_or(i64::MAX);
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let now = timestamp_now();
+    let mut stored = 0;
+    for wrapped in wrapped_keys {
+        stored += db.execute(
+            "INSERT OR IGNORE INTO server_mek_wrapped \
+             (community_id, generation, pseudonym_key_hex, wrapped_key, created_at) VALUES (?,?,?,?,?)",
+            params![community.community_id, generation_i64, wrapped.pseudonym_key, wrapped.wrapped_key, now],
+        )
+        .map_err(|e| format!("failed to store wrapped MEK: {e}"))?;
+    }
+
+    if stored > 0 && generation > community.mek.generation() {
+        db.execute(
+            "UPDATE hosted_communities SET mek_generation = ? WHERE id = ?",
+            params![generation_i64, community.community_id],
+        )
+        .map_err(|e| format!("failed to update MEK generation: {e}"))?;
+        community.mek = MekCustody::Members { generation };
+        tracing::info!(community = %community.community_id, generation, "member-published MEK is now current");
+    }
+    Ok(stored)
+}
+
+/// Whether any member published copies of `generation`. The generation a
+/// community switched to zero-knowledge custody at has none at first.
+pub fn generation_published(state: &Arc<ServerState>, community_id: &str, generation: u64) -> bool {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM server_mek_wrapped WHERE community_id = ? AND generation = ?)",
+        params![community_id, i64::try_from(generation).unwrap_or(i64::MAX)],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
+
+/// A member's wrapped copy of one MEK generation, if published.
+pub fn load_wrapped(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    generation: u64,
+    pseudonym_hex: &str,
+) -> Option<Vec<u8>> {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    db.query_row(
+        "SELECT wrapped_key FROM server_mek_wrapped \
+         WHERE community_id = ? AND generation = ? AND pseudonym_key_hex = ?",
+        params![community_id, i64::try_from(generation).unwrap_or(i64::MAX), pseudonym_hex],
+        |row| row.get(0),
+    )
+    .optional()
+    .unwrap_or(None)
+}
+
+/// Members with no wrapped copy of the current generation.
+///
+/// Timed-out members are left out — they get their copy once the timeout
+/// ends.
+pub fn members_missing_wraps(state: &Arc<ServerState>, community: &HostedCommunity) -> Vec<String> {
+    let generation = community.mek.generation();
+    community
+        .members
+        .iter()
+        .filter(|m| !m.is_timed_out())
+        .filter(|m| load_wrapped(state, &community.community_id, generation, &m.pseudonym_key_hex).is_none())
+        .map(|m| m.pseudonym_key_hex.clone())
+        .collect()
+}
+
+/// Store vouches after checking their signatures. Returns how many were
+/// new, or an error naming the first vouch that does not verify.
+pub fn store_vouches(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    vouches: &[MemberVouchDto],
+) -> Result<usize, String> {
+    if let Some(bad) = vouches
+        .iter()
+        .find(|v| !v.to_vouch().is_some_and(|v| v.verify(community_id)))
+    {
+        return Err(format!("invalid vouch for {}", bad.member));
+    }
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let mut stored = 0;
+    for vouch in vouches {
+        stored += db
+            .execute(
+                "INSERT OR IGNORE INTO server_member_vouches (community_id, voucher, member, signature) \
+                 VALUES (?,?,?,?)",
+                params![community_id, vouch.voucher, vouch.member, vouch.signature],
+            )
+            .map_err(|e| format!("failed to store vouch: {e}"))?;
+    }
+    Ok(stored)
+}
+
+/// Every vouch stored for a community.
+pub fn load_vouches(state: &Arc<ServerState>, community_id: &str) -> Vec<MemberVouchDto> {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let Ok(mut stmt) = db.prepare(
+        "SELECT voucher, member, signature FROM server_member_vouches WHERE community_id = ?",
+    ) else {
+        return Vec::new();
+    };
+    stmt.query_map(params![community_id], |row| {
+        Ok(MemberVouchDto {
+            voucher: row.get(0)?,
+            member: row.get(1)?,
+            signature: row.get(2)?,
+        })
+    })
+    .map(|rows| rows.filter_map(Result::ok).collect())
+    .unwrap_or_default()
+}
+
+/// Members key holders will wrap the MEK for: those vouched for, directly
+/// or through invite keys, from the owner. Others are left out of the
+/// every-member check on a new generation.
+pub fn vouched_members(
+    state: &Arc<ServerState>,
+    community: &HostedCommunity,
+) -> HashSet<String> {
+    let Ok(owner) = <[u8; 32]>::try_from(
+        hex::decode(&community.creator_pseudonym_hex).unwrap_or_default(),
+    ) else {
+        return HashSet::new();
+    };
+    let vouches: Vec<_> = load_vouches(state, &community.community_id)
+        .iter()
+        .filter_map(MemberVouchDto::to_vouch)
+        .collect();
+    member_vouch::vouched_members(&community.community_id, &[owner], &vouches)
+        .iter()
+        .map(hex::encode)
+        .collect()
+}
+
+/// Store (or replace) the key package a member published.
+pub fn store_key_package(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    pseudonym_hex: &str,
+    key_package: &[u8],
+) -> Result<(), String> {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    db.execute(
+        "INSERT OR REPLACE INTO server_tree_key_packages \
+         (community_id, pseudonym_key_hex, key_package, created_at) VALUES (?,?,?,?)",
+        params![community_id, pseudonym_hex, key_package, timestamp_now()],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("failed to store key package: {e}"))
+}
+
+/// Key packages of members waiting to be added, skipping timed-out members.
+pub fn pending_key_packages(state: &Arc<ServerState>, community: &HostedCommunity) -> Vec<Vec<u8>> {
+    let rows: Vec<(String, Vec<u8>)> = {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        let Ok(mut stmt) = db.prepare(
+            "SELECT pseudonym_key_hex, key_package FROM server_tree_key_packages WHERE community_id = ?",
+        ) else {
+            return Vec::new();
+        };
+        stmt.query_map(params![community.community_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map(|rows| rows.filter_map(Result::ok).collect())
+            .unwrap_or_default()
+    };
+    rows.into_iter()
+        .filter(|(pseudonym, _)| {
+            community
+                .members
+                .iter()
+                .any(|m| m.pseudonym_key_hex == *pseudonym && !m.is_timed_out())
+        })
+        .map(|(_, key_package)| key_package)
+        .collect()
+}
+
+/// Record the commit that moves a tree community from `epoch` to the next.
+///
+/// Welcomed members' key packages are consumed. Fails if a commit for
+/// `epoch` was already stored.
+pub fn store_commit(
+    state: &Arc<ServerState>,
+    community: &mut HostedCommunity,
+    epoch: u64,
+    sender_pseudonym: &str,
+    commit: &[u8],
+    welcomes: &[TreeWelcomeDto],
+) -> Result<(), String> {
+    let next = epoch + 1;
+    let epoch_i64 = i64::try_from(epoch).unwrap_or(i64::MAX);
+    let next_i64 = i64::try_from(next).unwrap_or(i64::MAX);
+    let community_id = &community.community_id;
+    {
+        let mut db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        let tx = db
+            .transaction()
+            .map_err(|e| format!("failed to start transaction: {e}"))?;
+        tx.execute(
+            "INSERT INTO server_tree_commits \
+             (community_id, epoch, sender_pseudonym, commit_data, created_at) VALUES (?,?,?,?,?)",
+            params![community_id, epoch_i64, sender_pseudonym, commit, timestamp_now()],
+        )
+        .map_err(|e| format!("failed to store commit: {e}"))?;
+        for welcome in welcomes {
+            tx.execute(
+                "INSERT OR REPLACE INTO server_tree_welcomes \
+                 (community_id, pseudonym_key_hex, epoch, welcome_data) VALUES (?,?,?,?)",
+                params![community_id, welcome.pseudonym_key, next_i64, welcome.welcome],
+            )
+            .map_err(|e| format!("failed to store welcome: {e}"))?;
+            tx.execute(
+                "DELETE FROM server_tree_key_packages WHERE community_id = ? AND pseudonym_key_hex = ?",
+                params![community_id, welcome.pseudonym_key],
+            )
+            .map_err(|e| format!("failed to consume key package: {e}"))?;
+        }
+        tx.execute(
+            "UPDATE hosted_communities SET mek_generation = ? WHERE id = ?",
+            params![next_i64, community_id],
+        )
+        .map_err(|e| format!("failed to update tree epoch: {e}"))?;
+        tx.commit()
+            .map_err(|e| format!("failed to commit transaction: {e}"))?;
+    }
+
+    community.mek = MekCustody::Tree { epoch: next };
+    tracing::info!(community = %community_id, epoch = next, sender = %sender_pseudonym, "tree commit accepted");
+    Ok(())
+}
+
+/// Accepted commits from `since_epoch` on, in epoch order.
+pub fn load_commits(state: &Arc<ServerState>, community_id: &str, since_epoch: u64) -> Vec<TreeCommitDto> {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let Ok(mut stmt) = db.prepare(
+        "SELECT epoch, commit_data FROM server_tree_commits \
+         WHERE community_id = ? AND epoch >= ? ORDER BY epoch LIMIT ?",
+    ) else {
+        return Vec::new();
+    };
+    stmt.query_map(
+        params![community_id, i64::try_from(since_epoch).unwrap_or(i64::MAX), MAX_COMMITS_PER_FETCH],
+        |row| {
+            Ok(TreeCommitDto {
+                epoch: row.get::<_, i64>(0)?.try_into().unwrap_or(0u64),
+                commit: row.get(1)?,
+            })
+        },
+    )
+    .map(|rows| rows.filter_map(Result::ok).collect())
+    .unwrap_or_default()
+}
+
+/// The latest welcome stored for a member, if any.
+pub fn load_welcome(state: &Arc<ServerState>, community_id: &str, pseudonym_hex: &str) -> Option<Vec<u8>> {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    db.query_row(
+        "SELECT welcome_data FROM server_tree_welcomes WHERE community_id = ? AND pseudonym_key_hex = ?",
+        params![community_id, pseudonym_hex],
+        |row| row.get(0),
+    )
+    .optional()
+    .unwrap_or(None)
+}
+
+/// A MEK generation encrypted for one member.
+pub struct SealedMek {
+    /// Signal message carrying `generation || key`, or in zero-knowledge
+    /// mode the member's wrapped copy (empty if none was published).
+    pub ciphertext: Vec<u8>,
+    /// Generation of the sealed MEK.
+    pub generation: u64,
+    /// Present when this delivery started a new session.
+    pub session_init: Option<MekSessionInit>,
+}
+
+/// Encrypt a server-held MEK for one member over their Signal session.
+///
+/// A non-empty `prekey_bundle` (a serialized `PreKeyBundle` whose identity
+/// key must be the member's pseudonym) starts a fresh session; otherwise
+/// the member's stored session is used. Takes the DB lock, so callers must
+/// not hold it.
+pub fn seal_for_member(
+    state: &Arc<ServerState>,
+    community: &HostedCommunity,
+    pseudonym_hex: &str,
+    prekey_bundle: Option<&[u8]>,
+    mek: &MediaEncryptionKey,
+) -> Result<SealedMek, String> {
+    let bundle = match prekey_bundle.filter(|b| !b.is_empty()) {
+        Some(bytes) => {
+            let bundle: PreKeyBundle = serde_json::from_slice(bytes)
+                .map_err(|e| format!("invalid prekey bundle: {e}"))?;
+            let pseudonym_bytes = hex::decode(pseudonym_hex).unwrap_or_default();
+            if !mek_delivery::bundle_matches_pseudonym(&bundle, &pseudonym_bytes) {
+                return Err("prekey bundle identity does not match pseudonym".into());
+            }
+            Some(bundle)
+        }
+        None => None,
+    };
+
+    let (identity_private, identity_public) = mek_delivery::server_signal_identity(
+        community.owner_keypair_hex.as_bytes(),
+        &community.community_id,
+    );
+    let manager = SignalSessionManager::new(
+        Box::new(MemoryIdentityStore::new(identity_private, identity_public.clone(), 0)),
+        Box::new(MemoryPreKeyStore::new()),
+        Box::new(MemberSessionStore {
+            db: Arc::clone(&state.db),
+            community_id: community.community_id.clone(),
+        }),
+    );
+
+    if bundle.is_none() && !manager.has_session(pseudonym_hex).map_err(|e| e.to_string())? {
+        return Err("no MEK delivery session — resend with a prekey bundle".into());
+    }
+
+    let (ciphertext, init) = mek_delivery::seal_mek(&manager, pseudonym_hex, bundle.as_ref(), mek)
+        .map_err(|e| format!("failed to seal MEK: {e}"))?;
+
+    Ok(SealedMek {
+        ciphertext,
+        generation: mek.generation(),
+        session_init: init.map(|init| MekSessionInit {
+            identity_key: identity_public,
+            ephemeral_key: init.ephemeral_public_key,
+            signed_prekey_id: init.signed_prekey_id,
+            one_time_prekey_id: init.one_time_prekey_id,
+        }),
+    })
+}
+
+/// Signal session store backed by `server_members.signal_session_data`.
+///
+/// Sessions live on the member row, so they are dropped with the member.
+struct MemberSessionStore {
+    db: Arc<Mutex<Connection>>,
+    community_id: String,
+}
+
+impl MemberSessionStore {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>, CryptoError> {
+        self.db
+            .lock()
+            .map_err(|e| CryptoError::StorageError(e.to_string()))
+    }
+}
+
+impl SessionStore for MemberSessionStore {
+    fn load_session(&self, address: &str) -> Result<Option<Vec<u8>>, CryptoError> {
+        let db = self.lock()?;
+        db.query_row(
+            "SELECT signal_session_data FROM server_members WHERE community_id = ? AND pseudonym_key_hex = ?",
+            params![self.community_id, address],
+            |row| row.get::<_, Option<Vec<u8>>>(0),
+        )
+        .optional()
+        .map(Option::flatten)
+        .map_err(|e| CryptoError::StorageError(e.to_string()))
+    }
+
+    fn store_session(&self, address: &str, session_data: &[u8]) -> Result<(), CryptoError> {
+        let db = self.lock()?;
+        let updated = db
+            .execute(
+                "UPDATE server_members SET signal_session_data = ? WHERE community_id = ? AND pseudonym_key_hex = ?",
+                params![session_data, self.community_id, address],
+            )
+            .map_err(|e| CryptoError::StorageError(e.to_string()))?;
+        if updated == 0 {
+            return Err(CryptoError::StorageError(format!("{address} is not a member")));
+        }
+        Ok(())
+    }
+
+    fn has_session(&self, address: &str) -> Result<bool, CryptoError> {
+        Ok(self.load_session(address)?.is_some())
+    }
+
+    fn delete_session(&self, address: &str) -> Result<(), CryptoError> {
+        let db = self.lock()?;
+        db.execute(
+            "UPDATE server_members SET signal_session_data = NULL WHERE community_id = ? AND pseudonym_key_hex = ?",
+            params![self.community_id, address],
+        )
+        .map_err(|e| CryptoError::StorageError(e.to_string()))?;
+        Ok(())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>, CryptoError> {
+        let db = self.lock()?;
+        let mut stmt = db
+            .prepare(
+                "SELECT pseudonym_key_hex FROM server_members \
+                 WHERE community_id = ? AND signal_session_data IS NOT NULL",
+            )
+            .map_err(|e| CryptoError::StorageError(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![self.community_id], |row| row.get(0))
+            .map_err(|e| CryptoError::StorageError(e.to_string()))?;
+        rows.collect::<Result<Vec<String>, _>>()
+            .map_err(|e| CryptoError::StorageError(e.to_string()))
+    }
+}
+
 fn timestamp_now() -> i64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -1,21 +1,30 @@
 use std::sync::Arc;
 
+use rekindle_crypto::group::mek_delivery::WRAPPED_MEK_LEN;
+use rekindle_crypto::group::tree;
 use rekindle_protocol::dht::community::{
     permissions, OverwriteType, PermissionOverwrite, RoleDefinition, ROLE_EVERYONE_ID,
 };
+use rekindle_protocol::dht::directory;
 use rekindle_protocol::messaging::envelope::{
-    ChannelInfoDto, ChannelMessageDto, CommunityBroadcast, CommunityRequest, CommunityResponse,
-    RoleDto,
+    is_valid_reaction, AuditAction, ChannelInfoDto, ChannelMessageDto, CommunityBroadcast,
+    CommunityRequest, CommunityResponse, HistoryVisibility, InviteDto, MemberVouchDto,
+    ReactionDto, RoleDto, ThreadDto, TreeWelcomeDto, WrappedMekDto, MAX_DISAPPEARING_SECS,
 };
 use rekindle_protocol::messaging::receiver::process_incoming;
 use rusqlite::params;
+use serde_json::json;
 
+use crate::audit;
 use crate::community_host;
 use crate::mek;
-use crate::server_state::{HostedCommunity, ServerChannel, ServerMember, ServerState};
+use crate::voice_relay;
+use crate::server_state::{
+    HostedCommunity, MekCustody, ServerChannel, ServerMember, ServerState, ServerThread,
+};
 
 /// Result tuple returned by `add_new_member` on successful join.
-type JoinResult = (Vec<u8>, u64, Vec<ChannelInfoDto>, Vec<u32>, Vec<RoleDto>);
+type JoinResult = (Vec<ChannelInfoDto>, Vec<u32>, Vec<RoleDto>);
 
 // ---------------------------------------------------------------------------
 // Permission helpers
@@ -106,6 +115,71 @@ fn check_hierarchy(
     }
 }
 
+/// Check that a sender has a required permission in one channel, taking the
+/// channel's overwrites and any timeout into account. A thread uses its
+/// parent channel's overwrites. The community creator always passes.
+fn check_channel_permission(
+    community: &HostedCommunity,
+    sender_pseudonym: &str,
+    channel_id: &str,
+    required: u64,
+) -> Result<(), CommunityResponse> {
+    if !community.creator_pseudonym_hex.is_empty()
+        && community.creator_pseudonym_hex == sender_pseudonym
+    {
+        return Ok(());
+    }
+    let perms = community
+        .members
+        .iter()
+        .find(|m| m.pseudonym_key_hex == sender_pseudonym)
+        .map_or(0, |member| {
+            let channel_id = community.permission_channel_id(channel_id);
+            let overwrites = community
+                .channels
+                .iter()
+                .find(|ch| ch.id == channel_id)
+                .map_or(&[][..], |ch| &ch.permission_overwrites);
+            permissions::calculate_permissions(
+                &member.role_ids,
+                &community.roles,
+                overwrites,
+                sender_pseudonym,
+                member.timeout_until,
+            )
+        });
+    if permissions::has_permission(perms, required) {
+        Ok(())
+    } else {
+        Err(CommunityResponse::Error {
+            code: 403,
+            message: "insufficient permissions in this channel".into(),
+        })
+    }
+}
+
+/// Whether a member can see a thread: any public one, but a private one
+/// only as its member or with `MANAGE_THREADS`.
+fn can_see_thread(community: &HostedCommunity, pseudonym: &str, thread: &ServerThread) -> bool {
+    !thread.private
+        || thread.members.iter().any(|m| m == pseudonym)
+        || check_channel_permission(community, pseudonym, &thread.channel_id, permissions::MANAGE_THREADS)
+            .is_ok()
+}
+
+/// Check that a sender can see the messages in `channel_id`, which only
+/// private threads restrict.
+fn check_thread_access(
+    community: &HostedCommunity,
+    sender_pseudonym: &str,
+    channel_id: &str,
+) -> Result<(), CommunityResponse> {
+    match community.thread(channel_id) {
+        Some(thread) if !can_see_thread(community, sender_pseudonym, thread) => Err(thread_not_found()),
+        _ => Ok(()),
+    }
+}
+
 /// Build `RoleDto` vec from community roles.
 fn roles_to_dto(community: &HostedCommunity) -> Vec<RoleDto> {
     community
@@ -287,9 +361,11 @@ async fn dispatch_request(
     // resolution works differently (via IPC hint or route lookup).
     if let CommunityRequest::Join {
         pseudonym_pubkey,
+        invite_code,
         display_name,
+        prekey_bundle,
         route_blob,
-        ..
+        vouch,
     } = request
     {
         if pseudonym_pubkey != sender_pseudonym {
@@ -302,8 +378,11 @@ async fn dispatch_request(
         return handle_join(
             state,
             sender_pseudonym,
+            invite_code.as_deref(),
             &display_name,
+            &prekey_bundle,
             route_blob,
+            vouch.as_ref(),
             incoming_route_id,
             ipc_community_id,
         )
@@ -328,15 +407,19 @@ async fn dispatch_request(
 
         CommunityRequest::SendMessage {
             channel_id,
+            message_id,
             ciphertext,
             mek_generation,
+            reply_to,
         } => handle_send_message(
             state,
             &community_id,
             sender_pseudonym,
             &channel_id,
+            &message_id,
             ciphertext,
             mek_generation,
+            reply_to,
         ),
 
         CommunityRequest::GetMessages {
@@ -352,7 +435,70 @@ async fn dispatch_request(
             limit,
         ),
 
-        CommunityRequest::RequestMEK => handle_request_mek(state, &community_id, sender_pseudonym),
+        CommunityRequest::EditMessage {
+            channel_id,
+            message_id,
+            ciphertext,
+            mek_generation,
+        } => handle_edit_message(
+            state,
+            &community_id,
+            sender_pseudonym,
+            &channel_id,
+            &message_id,
+            ciphertext,
+            mek_generation,
+        ),
+
+        CommunityRequest::DeleteMessage {
+            channel_id,
+            message_id,
+        } => handle_delete_message(
+            state,
+            &community_id,
+            sender_pseudonym,
+            &channel_id,
+            &message_id,
+        ),
+
+        CommunityRequest::AddReaction {
+            channel_id,
+            message_id,
+            emoji,
+        } => handle_reaction(
+            state,
+            &community_id,
+            sender_pseudonym,
+            &channel_id,
+            &message_id,
+            &emoji,
+            true,
+        ),
+
+        CommunityRequest::RemoveReaction {
+            channel_id,
+            message_id,
+            emoji,
+        } => handle_reaction(
+            state,
+            &community_id,
+            sender_pseudonym,
+            &channel_id,
+            &message_id,
+            &emoji,
+            false,
+        ),
+
+        CommunityRequest::RequestMEK {
+            prekey_bundle,
+            generation,
+        } => handle_request_mek(
+            state,
+            &community_id,
+            sender_pseudonym,
+            prekey_bundle.as_deref(),
+            generation,
+        ),
         CommunityRequest::Leave => handle_leave(state, &community_id, sender_pseudonym).await,
 
         CommunityRequest::Kick { target_pseudonym } => {
@@ -374,6 +520,7 @@ async fn dispatch_request(
         CommunityRequest::DeleteChannel { channel_id } => {
             let resp = handle_delete_channel(state, &community_id, sender_pseudonym, &channel_id);
             if matches!(resp, CommunityResponse::Ok) {
+                voice_relay::close_channel(state, &community_id, &channel_id);
                 let st = Arc::clone(state);
                 let cid = community_id.clone();
                 tokio::spawn(async move {
@@ -400,17 +547,68 @@ async fn dispatch_request(
             resp
         }
 
-        CommunityRequest::UpdateCommunity { name, description } => {
-            handle_update_community(
+        CommunityRequest::SetChannelRetention { channel_id, seconds } => {
+            let resp = handle_set_channel_retention(state, &community_id, sender_pseudonym, &channel_id, seconds);
+            if matches!(resp, CommunityResponse::Ok) {
+                community_host::purge_expired_messages(state);
+                let st = Arc::clone(state);
+                let cid = community_id.clone();
+                tokio::spawn(async move {
+                    community_host::publish_channels(&st, &cid).await;
+                });
+            }
+            resp
+        }
+
+        CommunityRequest::UpdateCommunity {
+            name,
+            description,
+            history_visibility,
+            invite_only,
+            audit_retention_secs,
+        } => {
+            let resp = handle_update_community(
                 state,
                 &community_id,
                 sender_pseudonym,
                 name.as_deref(),
                 description.as_deref(),
+                history_visibility,
+                invite_only,
+                audit_retention_secs,
             )
-            .await
+            .await;
+            if matches!(resp, CommunityResponse::CommunityUpdated) && audit_retention_secs.is_some() {
+                audit::purge_expired(state);
+            }
+            resp
+        }
+
+        CommunityRequest::CreateInvite {
+            max_uses,
+            expires_at,
+            grant_role_ids,
+        } => handle_create_invite(
+            state,
+            &community_id,
+            sender_pseudonym,
+            max_uses,
+            expires_at,
+            grant_role_ids,
+        ),
+
+        CommunityRequest::ListInvites => handle_list_invites(state, &community_id, sender_pseudonym),
+
+        CommunityRequest::RevokeInvite { code } => {
+            handle_revoke_invite(state, &community_id, sender_pseudonym, &code)
         }
 
+        CommunityRequest::SetListing { listed, game_ids } => {
+            handle_set_listing(state, &community_id, sender_pseudonym, listed, game_ids)
+        }
+
+        CommunityRequest::GetListing => handle_get_listing(state, &community_id, sender_pseudonym),
+
         CommunityRequest::Ban { target_pseudonym } => {
             handle_ban(state, &community_id, sender_pseudonym, &target_pseudonym).await
         }
@@ -421,6 +619,12 @@ async fn dispatch_request(
 
         CommunityRequest::GetBanList => handle_get_ban_list(state, &community_id, sender_pseudonym),
 
+        CommunityRequest::GetAuditLog {
+            before,
+            limit,
+            action_filter,
+        } => handle_get_audit_log(state, &community_id, sender_pseudonym, before, limit, action_filter),
+
         // ── Role & permission management ──
 
         CommunityRequest::CreateRole {
@@ -531,20 +735,109 @@ async fn dispatch_request(
             target_pseudonym,
             duration_seconds,
             reason,
-        } => handle_timeout_member(
+        } => {
+            handle_timeout_member(
+                state,
+                &community_id,
+                sender_pseudonym,
+                &target_pseudonym,
+                duration_seconds,
+                reason.as_ref(),
+            )
+            .await
+        }
+
+        CommunityRequest::RemoveTimeout { target_pseudonym } => {
+            handle_remove_timeout(state, &community_id, sender_pseudonym, &target_pseudonym)
+        }
+
+        CommunityRequest::GetRoles => handle_get_roles(state, &community_id, sender_pseudonym),
+
+        CommunityRequest::EnableZeroKnowledge => {
+            handle_enable_zero_knowledge(state, &community_id, sender_pseudonym)
+        }
+
+        CommunityRequest::PublishMEK {
+            generation,
+            wrapped_keys,
+        } => handle_publish_mek(state, &community_id, sender_pseudonym, generation, &wrapped_keys).await,
+
+        CommunityRequest::VouchMembers { vouches } => {
+            handle_vouch_members(state, &community_id, sender_pseudonym, &vouches)
+        }
+
+        CommunityRequest::EnableTreeKem => {
+            handle_enable_tree_kem(state, &community_id, sender_pseudonym)
+        }
+
+        CommunityRequest::PublishKeyPackage { key_package } => {
+            handle_publish_key_package(state, &community_id, sender_pseudonym, &key_package)
+        }
+
+        CommunityRequest::SubmitCommit {
+            epoch,
+            commit,
+            welcomes,
+        } => handle_submit_commit(state, &community_id, sender_pseudonym, epoch, &commit, &welcomes).await,
+
+        CommunityRequest::GetCommits { since_epoch } => {
+            handle_get_commits(state, &community_id, sender_pseudonym, since_epoch)
+        }
+
+        CommunityRequest::JoinVoice {
+            channel_id,
+            route_blob,
+        } => handle_join_voice(state, &community_id, sender_pseudonym, &channel_id, route_blob),
+
+        CommunityRequest::LeaveVoice => {
+            voice_relay::leave(state, &community_id, sender_pseudonym);
+            CommunityResponse::Ok
+        }
+
+        // ── Threads ──
+
+        CommunityRequest::CreateThread {
+            channel_id,
+            parent_message_id,
+            name,
+            private,
+        } => handle_create_thread(
             state,
             &community_id,
             sender_pseudonym,
-            &target_pseudonym,
-            duration_seconds,
-            reason.as_ref(),
+            &channel_id,
+            &parent_message_id,
+            &name,
+            private,
         ),
 
-        CommunityRequest::RemoveTimeout { target_pseudonym } => {
-            handle_remove_timeout(state, &community_id, sender_pseudonym, &target_pseudonym)
+        CommunityRequest::GetThreads {
+            channel_id,
+            include_archived,
+        } => handle_get_threads(state, &community_id, sender_pseudonym, &channel_id, include_archived),
+
+        CommunityRequest::ArchiveThread {
+            thread_id,
+            archived,
+        } => handle_archive_thread(state, &community_id, sender_pseudonym, &thread_id, archived),
+
+        CommunityRequest::JoinThread { thread_id } => {
+            handle_join_thread(state, &community_id, sender_pseudonym, &thread_id, sender_pseudonym)
         }
 
-        CommunityRequest::GetRoles => handle_get_roles(state, &community_id, sender_pseudonym),
+        CommunityRequest::LeaveThread { thread_id } => {
+            handle_leave_thread(state, &community_id, sender_pseudonym, &thread_id, sender_pseudonym)
+        }
+
+        CommunityRequest::AddThreadMember {
+            thread_id,
+            target_pseudonym,
+        } => handle_join_thread(state, &community_id, sender_pseudonym, &thread_id, &target_pseudonym),
+
+        CommunityRequest::RemoveThreadMember {
+            thread_id,
+            target_pseudonym,
+        } => handle_leave_thread(state, &community_id, sender_pseudonym, &thread_id, &target_pseudonym),
     }
 }
 
@@ -552,7 +845,11 @@ async fn dispatch_request(
 // Join / Rejoin
 // ---------------------------------------------------------------------------
 
-fn build_rejoin_response(community: &HostedCommunity, pseudonym_pubkey: &str) -> CommunityResponse {
+fn build_rejoin_response(
+    community: &HostedCommunity,
+    pseudonym_pubkey: &str,
+    sealed: mek::SealedMek,
+) -> CommunityResponse {
     let channels = community
         .channels
         .iter()
@@ -560,6 +857,7 @@ fn build_rejoin_response(community: &HostedCommunity, pseudonym_pubkey: &str) ->
             id: ch.id.clone(),
             name: ch.name.clone(),
             channel_type: ch.channel_type.clone(),
+            retention_secs: ch.retention_secs,
         })
         .collect();
 
@@ -569,16 +867,16 @@ fn build_rejoin_response(community: &HostedCommunity, pseudonym_pubkey: &str) ->
         .find(|m| m.pseudonym_key_hex == pseudonym_pubkey)
         .map_or_else(Vec::new, |m| m.role_ids.clone());
 
-    let mut mek_payload = Vec::with_capacity(40);
-    mek_payload.extend_from_slice(&community.mek.generation().to_le_bytes());
-    mek_payload.extend_from_slice(community.mek.as_bytes());
-
     CommunityResponse::Joined {
-        mek_encrypted: mek_payload,
-        mek_generation: community.mek.generation(),
+        mek_encrypted: sealed.ciphertext,
+        mek_generation: sealed.generation,
+        session_init: sealed.session_init,
         channels,
         role_ids,
         roles: roles_to_dto(community),
+        zero_knowledge: community.mek.is_zero_knowledge(),
+        history_visibility: community.history_visibility,
+        tree_kem: matches!(community.mek, MekCustody::Tree { .. }),
     }
 }
 
@@ -586,6 +884,7 @@ fn handle_rejoin(
     state: &Arc<ServerState>,
     community_id: &str,
     pseudonym_pubkey: &str,
+    prekey_bundle: &[u8],
     member_route_blob: Option<&[u8]>,
 ) -> Option<CommunityResponse> {
     let mut hosted = state.hosted.write();
@@ -604,7 +903,26 @@ fn handle_rejoin(
             );
         }
     }
-    Some(build_rejoin_response(community, pseudonym_pubkey))
+    // A timed-out member keeps their seat but gets no key until it ends
+    let sealed = if member.is_timed_out() {
+        Ok(mek::SealedMek {
+            ciphertext: Vec::new(),
+            generation: community.mek.generation(),
+            session_init: None,
+        })
+    } else {
+        mek_copy_for(
+            state,
+            community,
+            pseudonym_pubkey,
+            Some(prekey_bundle),
+            community.mek.generation(),
+        )
+    };
+    Some(match sealed {
+        Ok(sealed) => build_rejoin_response(community, pseudonym_pubkey, sealed),
+        Err(e) => mek_delivery_error(&e),
+    })
 }
 
 fn add_new_member(
@@ -613,19 +931,37 @@ fn add_new_member(
     pseudonym_pubkey: &str,
     display_name: &str,
     member_route_blob: Option<&[u8]>,
+    granted_role_ids: &[u32],
 ) -> Option<JoinResult> {
     let now = timestamp_now();
 
     // Determine role IDs — first member is the creator (gets Owner + Admin + Mod + Member + @everyone)
-    let is_first_member = {
+    let (is_first_member, joined_generation, granted_role_ids) = {
         let hosted = state.hosted.read();
-        hosted.get(community_id).is_some_and(|c| c.members.is_empty())
+        let community = hosted.get(community_id);
+        (
+            community.is_some_and(|c| c.members.is_empty()),
+            community.map_or(0, |c| c.mek.generation()),
+            // Roles the invite grants that still exist
+            community.map_or_else(Vec::new, |c| {
+                granted_role_ids
+                    .iter()
+                    .copied()
+                    .filter(|id| c.roles.iter().any(|r| r.id == *id))
+                    .collect::<Vec<u32>>()
+            }),
+        )
     };
-    let default_role_ids = if is_first_member {
+    let mut default_role_ids = if is_first_member {
         vec![ROLE_EVERYONE_ID, 1, 2, 3, 4] // @everyone, Member, Moderator, Admin, Owner
     } else {
         vec![ROLE_EVERYONE_ID, 1] // @everyone + Member
     };
+    for role_id in granted_role_ids {
+        if !default_role_ids.contains(&role_id) {
+            default_role_ids.push(role_id);
+        }
+    }
 
     // Insert member into DB
     {
@@ -634,8 +970,14 @@ fn add_new_member(
             e.into_inner()
         });
         if let Err(e) = db.execute(
-            "INSERT OR IGNORE INTO server_members (community_id, pseudonym_key_hex, display_name, joined_at) VALUES (?,?,?,?)",
-            params![community_id, pseudonym_pubkey, display_name, now],
+            "INSERT OR IGNORE INTO server_members (community_id, pseudonym_key_hex, display_name, joined_at, joined_generation) VALUES (?,?,?,?,?)",
+            params![
+                community_id,
+                pseudonym_pubkey,
+                display_name,
+                now,
+                i64::try_from(joined_generation).unwrap_or(i64::MAX)
+            ],
         ) {
             tracing::error!(error = %e, "failed to insert member into DB");
         }
@@ -669,6 +1011,7 @@ fn add_new_member(
         display_name: display_name.to_string(),
         role_ids: default_role_ids.clone(),
         joined_at: now,
+        joined_generation,
         route_blob: member_route_blob.map(<[u8]>::to_vec),
         timeout_until: None,
     });
@@ -691,24 +1034,24 @@ fn add_new_member(
             id: ch.id.clone(),
             name: ch.name.clone(),
             channel_type: ch.channel_type.clone(),
+            retention_secs: ch.retention_secs,
         })
         .collect();
 
     let roles_dto = roles_to_dto(community);
 
-    let mek_generation = community.mek.generation();
-    let mut mek_payload = Vec::with_capacity(40);
-    mek_payload.extend_from_slice(&mek_generation.to_le_bytes());
-    mek_payload.extend_from_slice(community.mek.as_bytes());
-
-    Some((mek_payload, mek_generation, channels, default_role_ids, roles_dto))
+    Some((channels, default_role_ids, roles_dto))
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_join(
     state: &Arc<ServerState>,
     pseudonym_pubkey: &str,
+    invite_code: Option<&str>,
     display_name: &str,
+    prekey_bundle: &[u8],
     member_route_blob: Option<Vec<u8>>,
+    vouch: Option<&MemberVouchDto>,
     incoming_route_id: Option<&veilid_core::RouteId>,
     ipc_community_id: Option<&str>,
 ) -> CommunityResponse {
@@ -755,21 +1098,44 @@ async fn handle_join(
         }
     }
 
+    // Key holders in zero-knowledge communities wrap the MEK only for
+    // members someone they trust vouched for
+    if let Some(vouch) = vouch {
+        if vouch.member != pseudonym_pubkey {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "vouch is for another pseudonym".into(),
+            };
+        }
+        if let Err(e) = mek::store_vouches(state, &community_id, std::slice::from_ref(vouch)) {
+            return CommunityResponse::Error { code: 400, message: e };
+        }
+    }
+
     if let Some(resp) = handle_rejoin(
         state,
         &community_id,
         pseudonym_pubkey,
+        prekey_bundle,
         member_route_blob.as_deref(),
     ) {
+        request_missing_keys(state, &community_id);
         return resp;
     }
 
-    let Some((mek_payload, mek_generation, channels, role_ids, roles)) = add_new_member(
+    // Members rejoin freely; newcomers may need an invite
+    let granted_role_ids = match redeem_invite(state, &community_id, invite_code) {
+        Ok(role_ids) => role_ids,
+        Err(e) => return e,
+    };
+
+    let Some((channels, role_ids, roles)) = add_new_member(
         state,
         &community_id,
         pseudonym_pubkey,
         display_name,
         member_route_blob.as_deref(),
+        &granted_role_ids,
     ) else {
         return CommunityResponse::Error {
             code: 404,
@@ -777,6 +1143,29 @@ async fn handle_join(
         };
     };
 
+    // The member row exists now, so the new session has somewhere to live
+    let sealed = {
+        let hosted = state.hosted.read();
+        match hosted.get(&community_id) {
+            Some(community) => mek_copy_for(
+                state,
+                community,
+                pseudonym_pubkey,
+                Some(prekey_bundle),
+                community.mek.generation(),
+            )
+            .map(|sealed| {
+                let tree_kem = matches!(community.mek, MekCustody::Tree { .. });
+                (sealed, community.mek.is_zero_knowledge(), community.history_visibility, tree_kem)
+            }),
+            None => Err("community not found".into()),
+        }
+    };
+    let (sealed, zero_knowledge, history_visibility, tree_kem) = match sealed {
+        Ok(sealed) => sealed,
+        Err(e) => return mek_delivery_error(&e),
+    };
+
     community_host::publish_member_roster(state, &community_id).await;
 
     broadcast_to_members(
@@ -790,29 +1179,101 @@ async fn handle_join(
             role_ids: role_ids.clone(),
         },
     );
+    request_missing_keys(state, &community_id);
 
     CommunityResponse::Joined {
-        mek_encrypted: mek_payload,
-        mek_generation,
+        mek_encrypted: sealed.ciphertext,
+        mek_generation: sealed.generation,
+        session_init: sealed.session_init,
         channels,
         role_ids,
         roles,
+        zero_knowledge,
+        history_visibility,
+        tree_kem,
+    }
+}
+
+/// Redeem `invite_code` for a newcomer, returning the roles it grants.
+///
+/// A code is required in invite-only communities (except for the creator,
+/// who joins an empty one). A code that is given must be valid either way,
+/// so a stale link fails instead of joining without its roles.
+fn redeem_invite(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    invite_code: Option<&str>,
+) -> Result<Vec<u32>, CommunityResponse> {
+    let Some(code) = invite_code else {
+        let invite_only = state
+            .hosted
+            .read()
+            .get(community_id)
+            .is_some_and(|c| c.invite_only && !c.members.is_empty());
+        if invite_only {
+            return Err(CommunityResponse::Error {
+                code: 403,
+                message: "this community is invite-only".into(),
+            });
+        }
+        return Ok(Vec::new());
+    };
+
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    // Take a use in the same statement that checks the limits, so two
+    // joins can't both take the last one
+    let claimed = db
+        .execute(
+            "UPDATE server_invites SET uses = uses + 1 \
+             WHERE community_id = ? AND code = ? \
+             AND (max_uses IS NULL OR uses < max_uses) \
+             AND (expires_at IS NULL OR expires_at > ?)",
+            params![community_id, code, timestamp_now()],
+        )
+        .unwrap_or(0);
+    if claimed == 0 {
+        return Err(CommunityResponse::Error {
+            code: 410,
+            message: "invite is invalid, expired or used up".into(),
+        });
     }
+    let grant_role_ids: String = db
+        .query_row(
+            "SELECT grant_role_ids FROM server_invites WHERE community_id = ? AND code = ?",
+            params![community_id, code],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+    Ok(serde_json::from_str(&grant_role_ids).unwrap_or_default())
 }
 
 // ---------------------------------------------------------------------------
 // Message handlers
 // ---------------------------------------------------------------------------
 
+#[allow(clippy::too_many_arguments)]
 fn handle_send_message(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
     channel_id: &str,
+    message_id: &str,
     ciphertext: Vec<u8>,
     mek_generation: u64,
+    reply_to: Option<String>,
 ) -> CommunityResponse {
-    // Check SEND_MESSAGES permission (with channel overwrites)
+    if !is_valid_message_id(message_id) {
+        return CommunityResponse::Error {
+            code: 400,
+            message: "invalid message id".into(),
+        };
+    }
+
+    // Check SEND_MESSAGES permission (with channel overwrites; a thread
+    // uses its parent channel's)
     {
         let hosted = state.hosted.read();
         let Some(community) = hosted.get(community_id) else {
@@ -824,15 +1285,25 @@ fn handle_send_message(
         if let Err(e) = verify_membership(community, sender_pseudonym) {
             return e;
         }
+        if let Err(e) = check_thread_access(community, sender_pseudonym, channel_id) {
+            return e;
+        }
+        if community.thread(channel_id).is_some_and(|t| t.archived) {
+            return CommunityResponse::Error {
+                code: 409,
+                message: "thread is archived".into(),
+            };
+        }
         if let Some(member) = community
             .members
             .iter()
             .find(|m| m.pseudonym_key_hex == sender_pseudonym)
         {
+            let permission_channel_id = community.permission_channel_id(channel_id);
             let ch_overwrites = community
                 .channels
                 .iter()
-                .find(|ch| ch.id == channel_id)
+                .find(|ch| ch.id == permission_channel_id)
                 .map_or(&[][..], |ch| &ch.permission_overwrites);
             let perms = permissions::calculate_permissions(
                 &member.role_ids,
@@ -861,6 +1332,20 @@ fn handle_send_message(
         }
     }
 
+    // Replies stay within one channel or thread
+    if let Some(reply_to) = &reply_to {
+        match message_author(state, community_id, channel_id, reply_to) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return CommunityResponse::Error {
+                    code: 400,
+                    message: "replied-to message is not in this channel".into(),
+                };
+            }
+            Err(e) => return e,
+        }
+    }
+
     let now = timestamp_now();
 
     {
@@ -869,30 +1354,56 @@ fn handle_send_message(
             e.into_inner()
         });
         let mek_gen_i64 = i64::try_from(mek_generation).unwrap_or(i64::MAX);
-        if let Err(e) = db.execute(
-            "INSERT INTO server_messages (community_id, channel_id, sender_pseudonym, ciphertext, mek_generation, timestamp) VALUES (?,?,?,?,?,?)",
-            params![community_id, channel_id, sender_pseudonym, ciphertext, mek_gen_i64, now],
+        match db.execute(
+            "INSERT INTO server_messages (community_id, channel_id, message_id, sender_pseudonym, ciphertext, mek_generation, timestamp, reply_to) VALUES (?,?,?,?,?,?,?,?)",
+            params![community_id, channel_id, message_id, sender_pseudonym, ciphertext, mek_gen_i64, now, reply_to],
         ) {
-            tracing::error!(error = %e, "failed to store message in DB");
-            return CommunityResponse::Error {
-                code: 500,
-                message: "failed to store message".into(),
-            };
+            Ok(_) => {}
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                // A retry of a message we already stored (the client never saw
+                // our reply) succeeds without broadcasting it again.
+                let resent = db
+                    .query_row(
+                        "SELECT 1 FROM server_messages WHERE community_id = ? AND message_id = ? AND channel_id = ? AND sender_pseudonym = ?",
+                        params![community_id, message_id, channel_id, sender_pseudonym],
+                        |_| Ok(()),
+                    )
+                    .is_ok();
+                if resent {
+                    return CommunityResponse::Ok;
+                }
+                return CommunityResponse::Error {
+                    code: 409,
+                    message: "message id already in use".into(),
+                };
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to store message in DB");
+                return CommunityResponse::Error {
+                    code: 500,
+                    message: "failed to store message".into(),
+                };
+            }
         }
     }
 
     let now_u64: u64 = now.try_into().unwrap_or(0u64);
-    broadcast_to_members(
+    broadcast_to_channel(
         state,
         community_id,
         sender_pseudonym,
+        channel_id,
         &CommunityBroadcast::NewMessage {
             community_id: community_id.to_string(),
             channel_id: channel_id.to_string(),
+            message_id: message_id.to_string(),
             sender_pseudonym: sender_pseudonym.to_string(),
             ciphertext,
             mek_generation,
             timestamp: now_u64,
+            reply_to,
         },
     );
 
@@ -909,14 +1420,35 @@ fn handle_get_messages(
 ) -> CommunityResponse {
     let limit = limit.min(500);
 
-    {
+    // Under `SinceJoin` history, members only see messages from after they
+    // joined; expired messages the sweep hasn't reached yet stay hidden too
+    let since = {
         let hosted = state.hosted.read();
-        if let Some(community) = hosted.get(community_id) {
-            if let Err(e) = verify_membership(community, sender_pseudonym) {
-                return e;
+        match hosted.get(community_id) {
+            Some(community) => {
+                if let Err(e) = verify_membership(community, sender_pseudonym) {
+                    return e;
+                }
+                if let Err(e) = check_thread_access(community, sender_pseudonym, channel_id) {
+                    return e;
+                }
+                let retention_channel_id = community.permission_channel_id(channel_id);
+                let joined = community
+                    .members
+                    .iter()
+                    .find(|m| m.pseudonym_key_hex == sender_pseudonym)
+                    .filter(|_| community.history_visibility == HistoryVisibility::SinceJoin)
+                    .map_or(0, |m| m.joined_at);
+                let retained = community
+                    .channels
+                    .iter()
+                    .find(|ch| ch.id == retention_channel_id && ch.retention_secs > 0)
+                    .map_or(0, |ch| timestamp_now() - i64::from(ch.retention_secs));
+                joined.max(retained)
             }
+            None => 0,
         }
-    }
+    };
 
     let db = state.db.lock().unwrap_or_else(|e| {
         tracing::error!(error = %e, "server db mutex poisoned — recovering");
@@ -926,43 +1458,30 @@ fn handle_get_messages(
     let query_result: Result<Vec<ChannelMessageDto>, _> = if let Some(before) = before_timestamp {
         let before_i64: i64 = before.try_into().unwrap_or(i64::MAX);
         db.prepare(
-            "SELECT sender_pseudonym, ciphertext, mek_generation, timestamp FROM server_messages \
-             WHERE community_id = ? AND channel_id = ? AND timestamp < ? \
+            "SELECT message_id, sender_pseudonym, ciphertext, mek_generation, timestamp, edited_at, reply_to \
+             FROM server_messages \
+             WHERE community_id = ? AND channel_id = ? AND timestamp < ? AND timestamp >= ? \
              ORDER BY timestamp DESC LIMIT ?",
         )
         .and_then(|mut stmt| {
             let rows = stmt.query_map(
-                params![community_id, channel_id, before_i64, limit],
-                |row| {
-                    let mek_gen: i64 = row.get(2)?;
-                    let ts: i64 = row.get(3)?;
-                    Ok(ChannelMessageDto {
-                        sender_pseudonym: row.get(0)?,
-                        ciphertext: row.get(1)?,
-                        mek_generation: mek_gen.try_into().unwrap_or(0u64),
-                        timestamp: ts.try_into().unwrap_or(0u64),
-                    })
-                },
+                params![community_id, channel_id, before_i64, since, limit],
+                message_from_row,
             )?;
             rows.collect()
         })
     } else {
         db.prepare(
-            "SELECT sender_pseudonym, ciphertext, mek_generation, timestamp FROM server_messages \
-             WHERE community_id = ? AND channel_id = ? \
+            "SELECT message_id, sender_pseudonym, ciphertext, mek_generation, timestamp, edited_at, reply_to \
+             FROM server_messages \
+             WHERE community_id = ? AND channel_id = ? AND timestamp >= ? \
              ORDER BY timestamp DESC LIMIT ?",
         )
         .and_then(|mut stmt| {
-            let rows = stmt.query_map(params![community_id, channel_id, limit], |row| {
-                let mek_gen: i64 = row.get(2)?;
-                let ts: i64 = row.get(3)?;
-                Ok(ChannelMessageDto {
-                    sender_pseudonym: row.get(0)?,
-                    ciphertext: row.get(1)?,
-                    mek_generation: mek_gen.try_into().unwrap_or(0u64),
-                    timestamp: ts.try_into().unwrap_or(0u64),
-                })
-            })?;
+            let rows = stmt.query_map(
+                params![community_id, channel_id, since, limit],
+                message_from_row,
+            )?;
             rows.collect()
         })
     };
@@ -978,410 +1497,601 @@ fn handle_get_messages(
         }
     };
 
+    for message in &mut messages {
+        match load_reactions(&db, community_id, &message.message_id) {
+            Ok(reactions) => message.reactions = reactions,
+            Err(e) => tracing::warn!(error = %e, "failed to load message reactions"),
+        }
+    }
+
     messages.reverse();
     CommunityResponse::Messages { messages }
 }
 
-fn handle_request_mek(state: &Arc<ServerState>, community_id: &str, sender_pseudonym: &str) -> CommunityResponse {
-    let hosted = state.hosted.read();
-    let Some(community) = hosted.get(community_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
-        };
-    };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
+fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ChannelMessageDto> {
+    let mek_gen: i64 = row.get(3)?;
+    let ts: i64 = row.get(4)?;
+    let edited_at: Option<i64> = row.get(5)?;
+    Ok(ChannelMessageDto {
+        message_id: row.get(0)?,
+        sender_pseudonym: row.get(1)?,
+        ciphertext: row.get(2)?,
+        mek_generation: mek_gen.try_into().unwrap_or(0u64),
+        timestamp: ts.try_into().unwrap_or(0u64),
+        edited_at: edited_at.and_then(|t| t.try_into().ok()),
+        reactions: Vec::new(),
+        reply_to: row.get(6)?,
+    })
+}
+
+/// Reactions on a message, grouped by emoji in the order they were first used.
+fn load_reactions(
+    db: &rusqlite::Connection,
+    community_id: &str,
+    message_id: &str,
+) -> rusqlite::Result<Vec<ReactionDto>> {
+    let mut stmt = db.prepare(
+        "SELECT emoji, pseudonym_key_hex FROM server_reactions \
+         WHERE community_id = ? AND message_id = ? ORDER BY created_at, rowid",
+    )?;
+    let rows = stmt.query_map(params![community_id, message_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    let mut reactions: Vec<ReactionDto> = Vec::new();
+    for row in rows {
+        let (emoji, pseudonym) = row?;
+        match reactions.iter_mut().find(|r| r.emoji == emoji) {
+            Some(reaction) => reaction.pseudonym_keys.push(pseudonym),
+            None => reactions.push(ReactionDto {
+                emoji,
+                pseudonym_keys: vec![pseudonym],
+            }),
+        }
     }
+    Ok(reactions)
+}
 
-    let mut mek_payload = Vec::with_capacity(40);
-    mek_payload.extend_from_slice(&community.mek.generation().to_le_bytes());
-    mek_payload.extend_from_slice(community.mek.as_bytes());
+/// Message IDs are sender-chosen, so only accept the shape
+/// `new_message_id` produces.
+fn is_valid_message_id(message_id: &str) -> bool {
+    message_id.len() == 32 && message_id.bytes().all(|b| b.is_ascii_hexdigit())
+}
 
-    CommunityResponse::MEK {
-        mek_encrypted: mek_payload,
-        mek_generation: community.mek.generation(),
+/// Author of a message in a channel, if it exists.
+fn message_author(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    channel_id: &str,
+    message_id: &str,
+) -> Result<Option<String>, CommunityResponse> {
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    match db.query_row(
+        "SELECT sender_pseudonym FROM server_messages \
+         WHERE community_id = ? AND channel_id = ? AND message_id = ?",
+        params![community_id, channel_id, message_id],
+        |row| row.get(0),
+    ) {
+        Ok(author) => Ok(Some(author)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to look up message");
+            Err(CommunityResponse::Error {
+                code: 500,
+                message: "failed to look up message".into(),
+            })
+        }
     }
 }
 
-// ---------------------------------------------------------------------------
-// Leave / Kick
-// ---------------------------------------------------------------------------
+fn message_not_found() -> CommunityResponse {
+    CommunityResponse::Error {
+        code: 404,
+        message: "message not found".into(),
+    }
+}
 
-async fn handle_leave(state: &Arc<ServerState>, community_id: &str, sender_pseudonym: &str) -> CommunityResponse {
+/// Also returned for private threads the sender can't see.
+fn thread_not_found() -> CommunityResponse {
+    CommunityResponse::Error {
+        code: 404,
+        message: "thread not found".into(),
+    }
+}
+
+/// Replace the body of a message. Only its author may, and only while they
+/// could still send in the channel.
+fn handle_edit_message(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    channel_id: &str,
+    message_id: &str,
+    ciphertext: Vec<u8>,
+    mek_generation: u64,
+) -> CommunityResponse {
     {
         let hosted = state.hosted.read();
-        if let Some(community) = hosted.get(community_id) {
-            if let Err(e) = verify_membership(community, sender_pseudonym) {
-                return e;
-            }
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        if let Err(e) = check_thread_access(community, sender_pseudonym, channel_id) {
+            return e;
+        }
+        if let Err(e) = check_channel_permission(
+            community,
+            sender_pseudonym,
+            channel_id,
+            permissions::SEND_MESSAGES,
+        ) {
+            return e;
+        }
+        let current_gen = community.mek.generation();
+        if mek_generation != current_gen {
+            return CommunityResponse::Error {
+                code: 409,
+                message: format!(
+                    "MEK generation mismatch: sent {mek_generation}, current is {current_gen}. Request new MEK."
+                ),
+            };
+        }
+    }
+
+    match message_author(state, community_id, channel_id, message_id) {
+        Ok(Some(author)) if author == sender_pseudonym => {}
+        Ok(Some(_)) => {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "only the author can edit a message".into(),
+            };
         }
+        Ok(None) => return message_not_found(),
+        Err(e) => return e,
     }
 
+    let now = timestamp_now();
     {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
-        // CASCADE will also delete from server_member_roles and server_member_timeouts
+        let mek_gen_i64 = i64::try_from(mek_generation).unwrap_or(i64::MAX);
         if let Err(e) = db.execute(
-            "DELETE FROM server_members WHERE community_id = ? AND pseudonym_key_hex = ?",
-            params![community_id, sender_pseudonym],
+            "UPDATE server_messages SET ciphertext = ?, mek_generation = ?, edited_at = ? \
+             WHERE community_id = ? AND message_id = ?",
+            params![ciphertext, mek_gen_i64, now, community_id, message_id],
         ) {
-            tracing::error!(error = %e, "failed to delete member from DB");
-        }
-    }
-
-    {
-        let mut hosted = state.hosted.write();
-        if let Some(community) = hosted.get_mut(community_id) {
-            community
-                .members
-                .retain(|m| m.pseudonym_key_hex != sender_pseudonym);
-            let new_gen = community.mek.generation() + 1;
-            community.mek = mek::rotate_mek(state, community_id, new_gen);
+            tracing::error!(error = %e, "failed to store message edit");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to store edit".into(),
+            };
         }
     }
 
-    community_host::publish_member_roster(state, community_id).await;
-    community_host::publish_mek_bundle(state, community_id).await;
-
-    broadcast_to_members(
+    broadcast_to_channel(
         state,
         community_id,
         sender_pseudonym,
-        &CommunityBroadcast::MemberRemoved {
+        channel_id,
+        &CommunityBroadcast::MessageEdited {
             community_id: community_id.to_string(),
-            pseudonym_key: sender_pseudonym.to_string(),
+            channel_id: channel_id.to_string(),
+            message_id: message_id.to_string(),
+            ciphertext,
+            mek_generation,
+            edited_at: now.try_into().unwrap_or(0u64),
         },
     );
 
-    let new_gen = {
-        let hosted = state.hosted.read();
-        hosted.get(community_id).map(|c| c.mek.generation())
-    };
-    if let Some(gen) = new_gen {
-        broadcast_to_members(
-            state,
-            community_id,
-            sender_pseudonym,
-            &CommunityBroadcast::MEKRotated {
-                community_id: community_id.to_string(),
-                new_generation: gen,
-            },
-        );
-    }
-
-    tracing::info!(community = %community_id, member = %sender_pseudonym, "member left community");
     CommunityResponse::Ok
 }
 
-async fn handle_kick(
+/// Delete a message for everyone. Authors can always delete their own;
+/// anyone else's needs `MANAGE_MESSAGES` in the channel.
+fn handle_delete_message(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
-    target_pseudonym: &str,
+    channel_id: &str,
+    message_id: &str,
 ) -> CommunityResponse {
+    let author = match message_author(state, community_id, channel_id, message_id) {
+        Ok(Some(author)) => author,
+        Ok(None) => return message_not_found(),
+        Err(e) => return e,
+    };
+
     {
         let hosted = state.hosted.read();
         let Some(community) = hosted.get(community_id) else {
             return CommunityResponse::Error {
-                code: 403,
-                message: "not a member".into(),
+                code: 404,
+                message: "community not found".into(),
             };
         };
-
-        if let Err(e) = check_permission(community, sender_pseudonym, permissions::KICK_MEMBERS) {
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
             return e;
         }
-
-        if sender_pseudonym == target_pseudonym {
-            return CommunityResponse::Error {
-                code: 400,
-                message: "cannot kick yourself — use Leave instead".into(),
-            };
-        }
-
-        if let Err(e) = check_hierarchy(community, sender_pseudonym, target_pseudonym) {
+        if let Err(e) = check_thread_access(community, sender_pseudonym, channel_id) {
             return e;
         }
+        if author != sender_pseudonym {
+            if let Err(e) = check_channel_permission(
+                community,
+                sender_pseudonym,
+                channel_id,
+                permissions::MANAGE_MESSAGES,
+            ) {
+                return e;
+            }
+        }
     }
 
-    handle_leave(state, community_id, target_pseudonym).await
-}
-
-// ---------------------------------------------------------------------------
-// Channel management
-// ---------------------------------------------------------------------------
-
-fn handle_create_channel(
-    state: &Arc<ServerState>,
-    community_id: &str,
-    sender_pseudonym: &str,
-    name: &str,
-    channel_type: &str,
-) -> CommunityResponse {
-    let mut hosted = state.hosted.write();
-
-    let Some(community) = hosted.get_mut(community_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
-        };
-    };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
-    }
-
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_CHANNELS) {
-        return e;
-    }
-
-    if channel_type != "text" && channel_type != "voice" {
-        return CommunityResponse::Error {
-            code: 400,
-            message: format!("invalid channel type '{channel_type}': must be 'text' or 'voice'"),
-        };
-    }
-
-    let channel_id = format!("channel_{}", hex::encode(rand_bytes(8)));
-    let sort_order = i32::try_from(community.channels.len()).unwrap_or(i32::MAX);
-    let channel = ServerChannel {
-        id: channel_id.clone(),
-        name: name.to_string(),
-        channel_type: channel_type.to_string(),
-        sort_order,
-        permission_overwrites: Vec::new(),
-    };
-
     {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
         if let Err(e) = db.execute(
-            "INSERT INTO server_channels (community_id, id, name, channel_type, sort_order) VALUES (?,?,?,?,?)",
-            params![community.community_id, channel.id, channel.name, channel.channel_type, channel.sort_order],
+            "DELETE FROM server_messages WHERE community_id = ? AND message_id = ?",
+            params![community_id, message_id],
         ) {
-            tracing::error!(error = %e, "failed to insert channel into DB");
+            tracing::error!(error = %e, "failed to delete message");
             return CommunityResponse::Error {
                 code: 500,
-                message: "failed to create channel".into(),
+                message: "failed to delete message".into(),
             };
         }
     }
 
-    community.channels.push(channel);
-    CommunityResponse::ChannelCreated { channel_id }
+    if author != sender_pseudonym {
+        tracing::info!(
+            community = %community_id,
+            channel = %channel_id,
+            moderator = %sender_pseudonym,
+            author = %author,
+            "message deleted by moderator"
+        );
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::MessageDelete, Some(message_id))
+                .before(json!({ "channelId": channel_id, "author": author })),
+        );
+    }
+    broadcast_to_channel(
+        state,
+        community_id,
+        sender_pseudonym,
+        channel_id,
+        &CommunityBroadcast::MessageDeleted {
+            community_id: community_id.to_string(),
+            channel_id: channel_id.to_string(),
+            message_id: message_id.to_string(),
+        },
+    );
+
+    CommunityResponse::Ok
 }
 
-fn handle_delete_channel(
+/// Add (`add`) or take back one of the sender's reactions. Adding needs
+/// `ADD_REACTIONS` in the channel; taking back never does. Both are no-ops
+/// when there is nothing to change.
+fn handle_reaction(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
     channel_id: &str,
+    message_id: &str,
+    emoji: &str,
+    add: bool,
 ) -> CommunityResponse {
-    let mut hosted = state.hosted.write();
-
-    let Some(community) = hosted.get_mut(community_id) else {
+    if !is_valid_reaction(emoji) {
         return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
+            code: 400,
+            message: "invalid reaction".into(),
         };
-    };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_CHANNELS) {
-        return e;
+    {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        if let Err(e) = check_thread_access(community, sender_pseudonym, channel_id) {
+            return e;
+        }
+        if add {
+            if let Err(e) = check_channel_permission(
+                community,
+                sender_pseudonym,
+                channel_id,
+                permissions::ADD_REACTIONS,
+            ) {
+                return e;
+            }
+        }
     }
 
-    community.channels.retain(|ch| ch.id != channel_id);
+    match message_author(state, community_id, channel_id, message_id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return message_not_found(),
+        Err(e) => return e,
+    }
 
-    {
+    let changed = {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
-        if let Err(e) = db.execute(
-            "DELETE FROM server_channels WHERE community_id = ? AND id = ?",
-            params![community.community_id, channel_id],
-        ) {
-            tracing::error!(error = %e, "failed to delete channel from DB");
+        let result = if add {
+            db.execute(
+                "INSERT OR IGNORE INTO server_reactions (community_id, message_id, pseudonym_key_hex, emoji, created_at) \
+                 VALUES (?,?,?,?,?)",
+                params![community_id, message_id, sender_pseudonym, emoji, timestamp_now()],
+            )
+        } else {
+            db.execute(
+                "DELETE FROM server_reactions \
+                 WHERE community_id = ? AND message_id = ? AND pseudonym_key_hex = ? AND emoji = ?",
+                params![community_id, message_id, sender_pseudonym, emoji],
+            )
+        };
+        match result {
+            Ok(rows) => rows > 0,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to store reaction");
+                return CommunityResponse::Error {
+                    code: 500,
+                    message: "failed to store reaction".into(),
+                };
+            }
         }
+    };
+
+    if changed {
+        let community_id = community_id.to_string();
+        let message_id = message_id.to_string();
+        let pseudonym_key = sender_pseudonym.to_string();
+        let emoji = emoji.to_string();
+        let broadcast = if add {
+            CommunityBroadcast::ReactionAdded {
+                community_id: community_id.clone(),
+                channel_id: channel_id.to_string(),
+                message_id,
+                pseudonym_key,
+                emoji,
+            }
+        } else {
+            CommunityBroadcast::ReactionRemoved {
+                community_id: community_id.clone(),
+                channel_id: channel_id.to_string(),
+                message_id,
+                pseudonym_key,
+                emoji,
+            }
+        };
+        broadcast_to_channel(state, &community_id, sender_pseudonym, channel_id, &broadcast);
     }
 
     CommunityResponse::Ok
 }
 
-fn handle_rename_channel(
+fn handle_request_mek(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
-    channel_id: &str,
-    new_name: &str,
+    prekey_bundle: Option<&[u8]>,
+    generation: Option<u64>,
 ) -> CommunityResponse {
-    let mut hosted = state.hosted.write();
+    let (response, request_keys) = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        let Some(member) = community
+            .members
+            .iter()
+            .find(|m| m.pseudonym_key_hex == sender_pseudonym)
+        else {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "not a member".into(),
+            };
+        };
+        if member.is_timed_out() {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "you are timed out".into(),
+            };
+        }
 
-    let Some(community) = hosted.get_mut(community_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
+        let current = community.mek.generation();
+        let wanted = generation.unwrap_or(current);
+        if wanted == 0 || wanted > current {
+            return CommunityResponse::Error {
+                code: 404,
+                message: format!("unknown MEK generation {wanted}"),
+            };
+        }
+        if wanted < member.joined_generation
+            && community.history_visibility == HistoryVisibility::SinceJoin
+        {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "history from before you joined is not shared in this community".into(),
+            };
+        }
+
+        let response = match mek_copy_for(state, community, sender_pseudonym, prekey_bundle, wanted) {
+            Ok(sealed) if sealed.ciphertext.is_empty() => CommunityResponse::Error {
+                code: 404,
+                message: format!("no copy of MEK generation {wanted} is available for you"),
+            },
+            Ok(sealed) if community.mek.is_zero_knowledge() => CommunityResponse::WrappedMEK {
+                mek_generation: sealed.generation,
+                wrapped_key: sealed.ciphertext,
+            },
+            Ok(sealed) => CommunityResponse::MEK {
+                mek_encrypted: sealed.ciphertext,
+                mek_generation: sealed.generation,
+                session_init: sealed.session_init,
+            },
+            Err(e) => mek_delivery_error(&e),
         };
+        // A key holder just came online, or a member is still missing the
+        // current generation — let key holders fill in the gaps
+        let key_holder = check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY).is_ok();
+        let missing = wanted == current && matches!(response, CommunityResponse::Error { code: 404, .. });
+        (response, community.mek.is_zero_knowledge() && (key_holder || missing))
     };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
-    }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_CHANNELS) {
-        return e;
+    if request_keys {
+        request_missing_keys(state, community_id);
     }
+    response
+}
 
-    let Some(channel) = community.channels.iter_mut().find(|ch| ch.id == channel_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "channel not found".into(),
-        };
+/// The requester's copy of one MEK generation.
+///
+/// Under server custody this is sealed on the member's Signal session;
+/// generations older than the current one come from `server_mek`. In
+/// zero-knowledge mode it is the wrapped copy a privileged member
+/// published; tree communities have none, since members derive each
+/// epoch's MEK themselves. The ciphertext is empty when there is no copy
+/// to give.
+fn mek_copy_for(
+    state: &Arc<ServerState>,
+    community: &HostedCommunity,
+    pseudonym_hex: &str,
+    prekey_bundle: Option<&[u8]>,
+    generation: u64,
+) -> Result<mek::SealedMek, String> {
+    let unavailable = || mek::SealedMek {
+        ciphertext: Vec::new(),
+        generation,
+        session_init: None,
     };
+    match &community.mek {
+        MekCustody::Server(current) if current.generation() == generation => {
+            mek::seal_for_member(state, community, pseudonym_hex, prekey_bundle, current)
+        }
+        MekCustody::Server(_) => match mek::load_mek(state, &community.community_id, generation) {
+            Some(older) => mek::seal_for_member(state, community, pseudonym_hex, prekey_bundle, &older),
+            None => Ok(unavailable()),
+        },
+        MekCustody::Members { .. } | MekCustody::Tree { .. } => Ok(
+            mek::load_wrapped(state, &community.community_id, generation, pseudonym_hex)
+                .map_or_else(unavailable, |ciphertext| mek::SealedMek {
+                    ciphertext,
+                    generation,
+                    session_init: None,
+                }),
+        ),
+    }
+}
 
-    channel.name = new_name.to_string();
+/// Error response for a MEK that could not be sealed for the requester.
+///
+/// Code 409 tells the client to retry with a fresh prekey bundle.
+fn mek_delivery_error(reason: &str) -> CommunityResponse {
+    tracing::warn!(error = %reason, "MEK delivery failed");
+    CommunityResponse::Error {
+        code: 409,
+        message: format!("MEK delivery failed: {reason}"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Leave / Kick
+// ---------------------------------------------------------------------------
+
+async fn handle_leave(state: &Arc<ServerState>, community_id: &str, sender_pseudonym: &str) -> CommunityResponse {
+    {
+        let hosted = state.hosted.read();
+        if let Some(community) = hosted.get(community_id) {
+            if let Err(e) = verify_membership(community, sender_pseudonym) {
+                return e;
+            }
+        }
+    }
 
     {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
+        // CASCADE will also delete from server_member_roles, server_member_timeouts
+        // and server_thread_members
         if let Err(e) = db.execute(
-            "UPDATE server_channels SET name = ? WHERE community_id = ? AND id = ?",
-            params![new_name, community.community_id, channel_id],
+            "DELETE FROM server_members WHERE community_id = ? AND pseudonym_key_hex = ?",
+            params![community_id, sender_pseudonym],
         ) {
-            tracing::error!(error = %e, "failed to rename channel in DB");
+            tracing::error!(error = %e, "failed to delete member from DB");
+        }
+        // Coming back takes a fresh invite
+        if let Err(e) = db.execute(
+            "DELETE FROM server_member_vouches WHERE community_id = ? AND member = ?",
+            params![community_id, sender_pseudonym],
+        ) {
+            tracing::error!(error = %e, "failed to delete member's vouches from DB");
         }
     }
 
-    CommunityResponse::Ok
-}
-
-// ---------------------------------------------------------------------------
-// MEK rotation
-// ---------------------------------------------------------------------------
-
-async fn handle_rotate_mek(
-    state: &Arc<ServerState>,
-    community_id: &str,
-    sender_pseudonym: &str,
-) -> CommunityResponse {
-    let new_gen = {
+    let rotation = {
         let mut hosted = state.hosted.write();
-        let Some(community) = hosted.get_mut(community_id) else {
-            return CommunityResponse::Error {
-                code: 404,
-                message: "community not found".into(),
-            };
-        };
-        if let Err(e) = verify_membership(community, sender_pseudonym) {
-            return e;
-        }
-
-        if let Err(e) =
-            check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY)
-        {
-            return e;
-        }
-
-        let new_gen = community.mek.generation() + 1;
-        community.mek = mek::rotate_mek(state, community_id, new_gen);
-        new_gen
+        hosted.get_mut(community_id).map(|community| {
+            community
+                .members
+                .retain(|m| m.pseudonym_key_hex != sender_pseudonym);
+            for thread in &mut community.threads {
+                thread.members.retain(|m| m != sender_pseudonym);
+            }
+            mek::rotate(state, community)
+        })
     };
 
-    community_host::publish_mek_bundle(state, community_id).await;
+    voice_relay::leave(state, community_id, sender_pseudonym);
+    community_host::publish_member_roster(state, community_id).await;
 
     broadcast_to_members(
         state,
         community_id,
-        "",
-        &CommunityBroadcast::MEKRotated {
+        sender_pseudonym,
+        &CommunityBroadcast::MemberRemoved {
             community_id: community_id.to_string(),
-            new_generation: new_gen,
+            pseudonym_key: sender_pseudonym.to_string(),
         },
     );
 
-    CommunityResponse::Ok
-}
-
-// ---------------------------------------------------------------------------
-// Community metadata update
-// ---------------------------------------------------------------------------
-
-async fn handle_update_community(
-    state: &Arc<ServerState>,
-    community_id: &str,
-    sender_pseudonym: &str,
-    new_name: Option<&str>,
-    new_description: Option<&str>,
-) -> CommunityResponse {
-    {
-        let mut hosted = state.hosted.write();
-        let Some(community) = hosted.get_mut(community_id) else {
-            return CommunityResponse::Error {
-                code: 404,
-                message: "community not found".into(),
-            };
-        };
-        if let Err(e) = verify_membership(community, sender_pseudonym) {
-            return e;
-        }
-
-        if let Err(e) =
-            check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY)
-        {
-            return e;
-        }
-
-        if let Some(n) = new_name {
-            community.name = n.to_string();
-        }
-        if let Some(d) = new_description {
-            community.description = d.to_string();
-        }
-
-        {
-            let db = state.db.lock().unwrap_or_else(|e| {
-                tracing::error!(error = %e, "server db mutex poisoned — recovering");
-                e.into_inner()
-            });
-            if let Some(n) = new_name {
-                let _ = db.execute(
-                    "UPDATE hosted_communities SET name = ? WHERE id = ?",
-                    params![n, community_id],
-                );
-            }
-            if let Some(d) = new_description {
-                let _ = db.execute(
-                    "UPDATE hosted_communities SET description = ? WHERE id = ?",
-                    params![d, community_id],
-                );
-            }
-        }
-    }
-
-    let name = {
-        let hosted = state.hosted.read();
-        hosted.get(community_id).map(|c| c.name.clone())
-    };
-    if let Some(name) = name {
-        community_host::publish_metadata(state, community_id, &name).await;
+    if let Some(rotation) = rotation {
+        announce_rotation(state, community_id, rotation).await;
     }
 
-    CommunityResponse::CommunityUpdated
+    tracing::info!(community = %community_id, member = %sender_pseudonym, "member left community");
+    CommunityResponse::Ok
 }
 
-// ---------------------------------------------------------------------------
-// Ban / Unban
-// ---------------------------------------------------------------------------
-
-async fn handle_ban(
+async fn handle_kick(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
@@ -1391,22 +2101,19 @@ async fn handle_ban(
         let hosted = state.hosted.read();
         let Some(community) = hosted.get(community_id) else {
             return CommunityResponse::Error {
-                code: 404,
-                message: "community not found".into(),
+                code: 403,
+                message: "not a member".into(),
             };
         };
-        if let Err(e) = verify_membership(community, sender_pseudonym) {
-            return e;
-        }
 
-        if let Err(e) = check_permission(community, sender_pseudonym, permissions::BAN_MEMBERS) {
+        if let Err(e) = check_permission(community, sender_pseudonym, permissions::KICK_MEMBERS) {
             return e;
         }
 
         if sender_pseudonym == target_pseudonym {
             return CommunityResponse::Error {
                 code: 400,
-                message: "cannot ban yourself".into(),
+                message: "cannot kick yourself — use Leave instead".into(),
             };
         }
 
@@ -1421,31 +2128,33 @@ async fn handle_ban(
             .map_or_else(String::new, |m| m.display_name.clone())
     };
 
-    {
-        let now = timestamp_now();
-        let db = state.db.lock().unwrap_or_else(|e| {
-            tracing::error!(error = %e, "server db mutex poisoned — recovering");
-            e.into_inner()
-        });
-        if let Err(e) = db.execute(
-            "INSERT OR REPLACE INTO banned_members (community_id, pseudonym_key_hex, display_name, banned_at) VALUES (?,?,?,?)",
-            params![community_id, target_pseudonym, display_name, now],
-        ) {
-            tracing::error!(error = %e, "failed to insert ban record");
-        }
+    let resp = handle_leave(state, community_id, target_pseudonym).await;
+    if matches!(resp, CommunityResponse::Ok) {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::MemberKick, Some(target_pseudonym))
+                .before(json!({ "displayName": display_name })),
+        );
     }
-
-    handle_leave(state, community_id, target_pseudonym).await
+    resp
 }
 
-fn handle_unban(
+// ---------------------------------------------------------------------------
+// Channel management
+// ---------------------------------------------------------------------------
+
+fn handle_create_channel(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
-    target_pseudonym: &str,
+    name: &str,
+    channel_type: &str,
 ) -> CommunityResponse {
-    let hosted = state.hosted.read();
-    let Some(community) = hosted.get(community_id) else {
+    let mut hosted = state.hosted.write();
+
+    let Some(community) = hosted.get_mut(community_id) else {
         return CommunityResponse::Error {
             code: 404,
             message: "community not found".into(),
@@ -1455,84 +2164,64 @@ fn handle_unban(
         return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::BAN_MEMBERS) {
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_CHANNELS) {
         return e;
     }
 
-    {
-        let db = state.db.lock().unwrap_or_else(|e| {
-            tracing::error!(error = %e, "server db mutex poisoned — recovering");
-            e.into_inner()
-        });
-        if let Err(e) = db.execute(
-            "DELETE FROM banned_members WHERE community_id = ? AND pseudonym_key_hex = ?",
-            params![community.community_id, target_pseudonym],
-        ) {
-            tracing::error!(error = %e, "failed to remove ban record");
-        }
-    }
-
-    tracing::info!(community = %community.community_id, member = %target_pseudonym, "member unbanned");
-    CommunityResponse::Ok
-}
-
-fn handle_get_ban_list(state: &Arc<ServerState>, community_id: &str, sender_pseudonym: &str) -> CommunityResponse {
-    let hosted = state.hosted.read();
-    let Some(community) = hosted.get(community_id) else {
+    if channel_type != "text" && channel_type != "voice" {
         return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
+            code: 400,
+            message: format!("invalid channel type '{channel_type}': must be 'text' or 'voice'"),
         };
-    };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
-    }
-
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::BAN_MEMBERS) {
-        return e;
     }
 
-    let db = state.db.lock().unwrap_or_else(|e| {
-        tracing::error!(error = %e, "server db mutex poisoned — recovering");
-        e.into_inner()
-    });
+    let channel_id = format!("channel_{}", hex::encode(rand_bytes(8)));
+    let sort_order = i32::try_from(community.channels.len()).unwrap_or(i32::MAX);
+    let channel = ServerChannel {
+        id: channel_id.clone(),
+        name: name.to_string(),
+        channel_type: channel_type.to_string(),
+        sort_order,
+        retention_secs: 0,
+        permission_overwrites: Vec::new(),
+    };
 
-    let banned = db
-        .prepare(
-            "SELECT pseudonym_key_hex, display_name, banned_at FROM banned_members WHERE community_id = ? ORDER BY banned_at DESC",
-        )
-        .and_then(|mut stmt| {
-            let rows = stmt.query_map(params![community.community_id], |row| {
-                let banned_at: i64 = row.get(2)?;
-                Ok(rekindle_protocol::messaging::envelope::BannedMemberDto {
-                    pseudonym_key: row.get(0)?,
-                    display_name: row.get(1)?,
-                    banned_at: banned_at.try_into().unwrap_or(0u64),
-                })
-            })?;
-            rows.collect::<Result<Vec<_>, _>>()
-        })
-        .unwrap_or_default();
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        if let Err(e) = db.execute(
+            "INSERT INTO server_channels (community_id, id, name, channel_type, sort_order) VALUES (?,?,?,?,?)",
+            params![community.community_id, channel.id, channel.name, channel.channel_type, channel.sort_order],
+        ) {
+            tracing::error!(error = %e, "failed to insert channel into DB");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to create channel".into(),
+            };
+        }
+    }
 
-    CommunityResponse::BanList { banned }
+    let after = json!({ "name": channel.name, "channelType": channel.channel_type });
+    community.channels.push(channel);
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::ChannelCreate, Some(channel_id.as_str())).after(after),
+    );
+    CommunityResponse::ChannelCreated { channel_id }
 }
 
-// ---------------------------------------------------------------------------
-// Role management
-// ---------------------------------------------------------------------------
-
-#[allow(clippy::too_many_arguments)]
-fn handle_create_role(
+fn handle_delete_channel(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
-    name: &str,
-    color: u32,
-    perms: u64,
-    hoist: bool,
-    mentionable: bool,
+    channel_id: &str,
 ) -> CommunityResponse {
     let mut hosted = state.hosted.write();
+
     let Some(community) = hosted.get_mut(community_id) else {
         return CommunityResponse::Error {
             code: 404,
@@ -1543,70 +2232,64 @@ fn handle_create_role(
         return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_CHANNELS) {
         return e;
     }
 
-    // Generate next role ID
-    let next_id = community.roles.iter().map(|r| r.id).max().unwrap_or(0) + 1;
-    // Position: one below sender's highest (new roles start below the creator)
-    let sender_pos = highest_role_position(community, sender_pseudonym);
-    let position = sender_pos.saturating_sub(1).max(1); // at least 1, never 0 (@everyone)
-
-    let role = RoleDefinition {
-        id: next_id,
-        name: name.to_string(),
-        color,
-        permissions: perms,
-        position,
-        hoist,
-        mentionable,
-    };
+    let before = community
+        .channels
+        .iter()
+        .find(|ch| ch.id == channel_id)
+        .map(|ch| json!({ "name": ch.name, "channelType": ch.channel_type }));
+    community.channels.retain(|ch| ch.id != channel_id);
+    let (threads, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut community.threads)
+        .into_iter()
+        .partition(|t| t.channel_id == channel_id);
+    community.threads = kept;
 
     {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
+        // Its threads go with it (`ON DELETE CASCADE`), and so do their
+        // messages, which private threads kept from most members
         if let Err(e) = db.execute(
-            "INSERT INTO server_roles (community_id, id, name, color, permissions, position, hoist, mentionable) VALUES (?,?,?,?,?,?,?,?)",
-            params![
-                community.community_id,
-                role.id,
-                role.name,
-                role.color,
-                role.permissions.cast_signed(),
-                role.position,
-                i32::from(role.hoist),
-                i32::from(role.mentionable),
-            ],
+            "DELETE FROM server_channels WHERE community_id = ? AND id = ?",
+            params![community.community_id, channel_id],
         ) {
-            tracing::error!(error = %e, "failed to insert role into DB");
-            return CommunityResponse::Error {
-                code: 500,
-                message: "failed to create role".into(),
-            };
+            tracing::error!(error = %e, "failed to delete channel from DB");
+        }
+        for thread in &threads {
+            if let Err(e) = db.execute(
+                "DELETE FROM server_messages WHERE community_id = ? AND channel_id = ?",
+                params![community.community_id, thread.id],
+            ) {
+                tracing::error!(error = %e, thread = %thread.id, "failed to delete thread messages from DB");
+            }
         }
     }
 
-    community.roles.push(role);
-    CommunityResponse::RoleCreated { role_id: next_id }
+    if let Some(before) = before {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::ChannelDelete, Some(channel_id)).before(before),
+        );
+    }
+    CommunityResponse::Ok
 }
 
-#[allow(clippy::too_many_arguments)]
-fn handle_edit_role(
+fn handle_rename_channel(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
-    role_id: u32,
-    name: Option<&String>,
-    color: Option<u32>,
-    perms: Option<u64>,
-    position: Option<i32>,
-    hoist: Option<bool>,
-    mentionable: Option<bool>,
+    channel_id: &str,
+    new_name: &str,
 ) -> CommunityResponse {
     let mut hosted = state.hosted.write();
+
     let Some(community) = hosted.get_mut(community_id) else {
         return CommunityResponse::Error {
             code: 404,
@@ -1617,96 +2300,60 @@ fn handle_edit_role(
         return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_CHANNELS) {
         return e;
     }
 
-    let sender_pos = highest_role_position(community, sender_pseudonym);
-    let sender_is_admin = permissions::is_administrator(member_base_permissions(community, sender_pseudonym));
-
-    // Check role exists and position constraint before mutating
-    let target_pos = community.roles.iter().find(|r| r.id == role_id).map(|r| r.position);
-    let Some(target_position) = target_pos else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "role not found".into(),
-        };
-    };
-
-    // Can only edit roles below own position (unless admin)
-    if target_position >= sender_pos && !sender_is_admin {
-        return CommunityResponse::Error {
-            code: 403,
-            message: "cannot edit a role at or above your position".into(),
-        };
-    }
-
-    let Some(role) = community.roles.iter_mut().find(|r| r.id == role_id) else {
+    let Some(channel) = community.channels.iter_mut().find(|ch| ch.id == channel_id) else {
         return CommunityResponse::Error {
             code: 404,
-            message: "role not found".into(),
+            message: "channel not found".into(),
         };
     };
 
-    if let Some(n) = &name {
-        role.name.clone_from(n);
-    }
-    if let Some(c) = color {
-        role.color = c;
-    }
-    if let Some(p) = perms {
-        role.permissions = p;
-    }
-    if let Some(pos) = position {
-        role.position = pos;
-    }
-    if let Some(h) = hoist {
-        role.hoist = h;
-    }
-    if let Some(m) = mentionable {
-        role.mentionable = m;
-    }
+    let before = json!({ "name": channel.name });
+    channel.name = new_name.to_string();
 
-    // Persist
     {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
         if let Err(e) = db.execute(
-            "UPDATE server_roles SET name=?, color=?, permissions=?, position=?, hoist=?, mentionable=? WHERE community_id=? AND id=?",
-            params![
-                role.name,
-                role.color,
-                role.permissions.cast_signed(),
-                role.position,
-                i32::from(role.hoist),
-                i32::from(role.mentionable),
-                community.community_id,
-                role_id,
-            ],
+            "UPDATE server_channels SET name = ? WHERE community_id = ? AND id = ?",
+            params![new_name, community.community_id, channel_id],
         ) {
-            tracing::error!(error = %e, "failed to update role in DB");
+            tracing::error!(error = %e, "failed to rename channel in DB");
         }
     }
 
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::ChannelUpdate, Some(channel_id))
+            .before(before)
+            .after(json!({ "name": new_name })),
+    );
     CommunityResponse::Ok
 }
 
-fn handle_delete_role(
+fn handle_set_channel_retention(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
-    role_id: u32,
+    channel_id: &str,
+    seconds: u32,
 ) -> CommunityResponse {
-    if role_id == ROLE_EVERYONE_ID {
+    if seconds > MAX_DISAPPEARING_SECS {
         return CommunityResponse::Error {
             code: 400,
-            message: "cannot delete the @everyone role".into(),
+            message: format!("retention may be at most {MAX_DISAPPEARING_SECS} seconds"),
         };
     }
 
     let mut hosted = state.hosted.write();
+
     let Some(community) = hosted.get_mut(community_id) else {
         return CommunityResponse::Error {
             code: 404,
@@ -1717,54 +2364,1664 @@ fn handle_delete_role(
         return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_CHANNELS) {
         return e;
     }
 
-    let sender_pos = highest_role_position(community, sender_pseudonym);
-    let role_pos = community
-        .roles
-        .iter()
-        .find(|r| r.id == role_id)
-        .map_or(i32::MAX, |r| r.position);
-    if role_pos >= sender_pos {
+    let Some(channel) = community.channels.iter_mut().find(|ch| ch.id == channel_id) else {
         return CommunityResponse::Error {
-            code: 403,
-            message: "cannot delete a role at or above your position".into(),
+            code: 404,
+            message: "channel not found".into(),
+        };
+    };
+    if channel.channel_type != "text" {
+        return CommunityResponse::Error {
+            code: 400,
+            message: "only text channels keep messages".into(),
         };
     }
 
-    community.roles.retain(|r| r.id != role_id);
-    // Remove from all members
-    for member in &mut community.members {
-        member.role_ids.retain(|rid| *rid != role_id);
-    }
+    let before = json!({ "retentionSecs": channel.retention_secs });
+    channel.retention_secs = seconds;
 
     {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
-        let _ = db.execute(
-            "DELETE FROM server_roles WHERE community_id = ? AND id = ?",
-            params![community.community_id, role_id],
-        );
-        let _ = db.execute(
-            "DELETE FROM server_member_roles WHERE community_id = ? AND role_id = ?",
-            params![community.community_id, role_id],
-        );
+        if let Err(e) = db.execute(
+            "UPDATE server_channels SET retention_secs = ? WHERE community_id = ? AND id = ?",
+            params![seconds, community.community_id, channel_id],
+        ) {
+            tracing::error!(error = %e, "failed to set channel retention in DB");
+        }
     }
 
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::ChannelUpdate, Some(channel_id))
+            .before(before)
+            .after(json!({ "retentionSecs": seconds })),
+    );
     CommunityResponse::Ok
 }
 
-fn handle_assign_role(
+// ---------------------------------------------------------------------------
+// MEK rotation
+// ---------------------------------------------------------------------------
+
+async fn handle_rotate_mek(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+) -> CommunityResponse {
+    let rotation = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+
+        if let Err(e) =
+            check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY)
+        {
+            return e;
+        }
+
+        mek::rotate(state, community)
+    };
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::MekRotate, None),
+    );
+    announce_rotation(state, community_id, rotation).await;
+
+    CommunityResponse::Ok
+}
+
+/// Tell members about a rotation: fetch the new MEK if it is live, or
+/// (zero-knowledge) publish it if they are privileged, or (tree) commit
+/// the membership change.
+async fn announce_rotation(state: &Arc<ServerState>, community_id: &str, rotation: mek::Rotation) {
+    let broadcast = match rotation {
+        mek::Rotation::CommitNeeded(_) => {
+            request_tree_commit(state, community_id);
+            return;
+        }
+        mek::Rotation::Rotated(new_generation) => {
+            community_host::publish_mek_bundle(state, community_id).await;
+            CommunityBroadcast::MEKRotated {
+                community_id: community_id.to_string(),
+                new_generation,
+            }
+        }
+        mek::Rotation::KeysNeeded(generation) => {
+            let members = {
+                let hosted = state.hosted.read();
+                hosted.get(community_id).map_or_else(Vec::new, |c| {
+                    c.members
+                        .iter()
+                        .filter(|m| !m.is_timed_out())
+                        .map(|m| m.pseudonym_key_hex.clone())
+                        .collect()
+                })
+            };
+            CommunityBroadcast::MEKKeysNeeded {
+                community_id: community_id.to_string(),
+                generation,
+                members,
+                vouches: mek::load_vouches(state, community_id),
+            }
+        }
+    };
+    broadcast_to_members(state, community_id, "", &broadcast);
+}
+
+// ---------------------------------------------------------------------------
+// Zero-knowledge MEK custody
+// ---------------------------------------------------------------------------
+
+fn handle_enable_zero_knowledge(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+) -> CommunityResponse {
+    let (generation, members) = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if community.creator_pseudonym_hex != sender_pseudonym {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "only the community owner can change MEK custody".into(),
+            };
+        }
+        if community.mek.is_zero_knowledge() {
+            return CommunityResponse::Ok;
+        }
+
+        let generation = mek::enable_zero_knowledge(state, community);
+        let members = community
+            .members
+            .iter()
+            .filter(|m| !m.is_timed_out())
+            .map(|m| m.pseudonym_key_hex.clone())
+            .collect();
+        (generation, members)
+    };
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::ZeroKnowledgeEnable, None),
+    );
+
+    // The server saw every generation so far; members need a fresh one
+    // before they can send again
+    broadcast_to_members(
+        state,
+        community_id,
+        "",
+        &CommunityBroadcast::MEKKeysNeeded {
+            community_id: community_id.to_string(),
+            generation,
+            members,
+            vouches: mek::load_vouches(state, community_id),
+        },
+    );
+
+    CommunityResponse::Ok
+}
+
+async fn handle_publish_mek(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    generation: u64,
+    wrapped_keys: &[WrappedMekDto],
+) -> CommunityResponse {
+    let advanced = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        if let Err(e) =
+            check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY)
+        {
+            return e;
+        }
+        if community
+            .members
+            .iter()
+            .any(|m| m.pseudonym_key_hex == sender_pseudonym && m.is_timed_out())
+        {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "you are timed out".into(),
+            };
+        }
+
+        let MekCustody::Members { generation: current } = community.mek else {
+            return CommunityResponse::Error {
+                code: 409,
+                message: "the community server holds this community's MEK".into(),
+            };
+        };
+        // The generation custody switched at has no copies until the first
+        // key holder answers; that first publish starts it like a rotation
+        let first_copies = generation == current && !mek::generation_published(state, community_id, current);
+        let advanced = generation == current + 1 || first_copies;
+        if !advanced && generation != current {
+            return CommunityResponse::Error {
+                code: 409,
+                message: format!("cannot publish MEK generation {generation}: current is {current}"),
+            };
+        }
+
+        if let Some(bad) = wrapped_keys.iter().find(|w| w.wrapped_key.len() != WRAPPED_MEK_LEN) {
+            return CommunityResponse::Error {
+                code: 400,
+                message: format!("invalid wrapped MEK for {}", bad.pseudonym_key),
+            };
+        }
+        // Copies for members who left or were timed out since the request
+        // was broadcast are dropped
+        let wrapped_keys: Vec<WrappedMekDto> = wrapped_keys
+            .iter()
+            .filter(|w| {
+                community
+                    .members
+                    .iter()
+                    .any(|m| m.pseudonym_key_hex == w.pseudonym_key && !m.is_timed_out())
+            })
+            .cloned()
+            .collect();
+        // A new generation replaces the old one for everybody at once.
+        // Members nobody vouched for get no copy from an honest key holder
+        if advanced {
+            let vouched = mek::vouched_members(state, community);
+            if community
+                .members
+                .iter()
+                .filter(|m| !m.is_timed_out() && vouched.contains(&m.pseudonym_key_hex))
+                .any(|m| !wrapped_keys.iter().any(|w| w.pseudonym_key == m.pseudonym_key_hex))
+            {
+                return CommunityResponse::Error {
+                    code: 400,
+                    message: "a new MEK generation must be wrapped for every vouched member".into(),
+                };
+            }
+        }
+
+        match mek::store_wrapped(state, community, generation, &wrapped_keys) {
+            Ok(0) => {
+                return CommunityResponse::Error {
+                    code: 409,
+                    message: format!("MEK generation {generation} was already published"),
+                };
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!(error = %e, community = %community_id, "failed to store published MEK");
+                return CommunityResponse::Error {
+                    code: 500,
+                    message: "failed to store MEK".into(),
+                };
+            }
+        }
+        advanced
+    };
+
+    if advanced {
+        community_host::publish_mek_bundle(state, community_id).await;
+    }
+    broadcast_to_members(
+        state,
+        community_id,
+        sender_pseudonym,
+        &CommunityBroadcast::MEKRotated {
+            community_id: community_id.to_string(),
+            new_generation: generation,
+        },
+    );
+
+    CommunityResponse::Ok
+}
+
+/// Store vouches a member made with their own pseudonym, then ask key
+/// holders for any copies the newly vouched members are missing.
+fn handle_vouch_members(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    vouches: &[MemberVouchDto],
+) -> CommunityResponse {
+    {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+    }
+    if vouches.iter().any(|v| v.voucher != sender_pseudonym) {
+        return CommunityResponse::Error {
+            code: 403,
+            message: "vouches must be made with your own pseudonym".into(),
+        };
+    }
+    match mek::store_vouches(state, community_id, vouches) {
+        Ok(0) => CommunityResponse::Ok,
+        Ok(_) => {
+            request_missing_keys(state, community_id);
+            CommunityResponse::Ok
+        }
+        Err(e) => CommunityResponse::Error { code: 400, message: e },
+    }
+}
+
+/// Zero-knowledge communities: ask privileged members to publish the
+/// current generation for members who have no copy of it yet.
+fn request_missing_keys(state: &Arc<ServerState>, community_id: &str) {
+    let (generation, members) = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return;
+        };
+        if !matches!(community.mek, MekCustody::Members { .. }) {
+            return;
+        }
+        (community.mek.generation(), mek::members_missing_wraps(state, community))
+    };
+    if members.is_empty() {
+        return;
+    }
+
+    broadcast_to_members(
+        state,
+        community_id,
+        "",
+        &CommunityBroadcast::MEKKeysNeeded {
+            community_id: community_id.to_string(),
+            generation,
+            members,
+            vouches: mek::load_vouches(state, community_id),
+        },
+    );
+}
+
+// ---------------------------------------------------------------------------
+// TreeKEM delivery service
+// ---------------------------------------------------------------------------
+
+fn handle_enable_tree_kem(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+) -> CommunityResponse {
+    let epoch = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if community.creator_pseudonym_hex != sender_pseudonym {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "only the community owner can change MEK custody".into(),
+            };
+        }
+        if let MekCustody::Tree { epoch } = community.mek {
+            return CommunityResponse::TreeEpoch { epoch };
+        }
+        mek::enable_tree_kem(state, community)
+    };
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::TreeKemEnable, None).after(json!({ "epoch": epoch })),
+    );
+
+    broadcast_to_members(
+        state,
+        community_id,
+        sender_pseudonym,
+        &CommunityBroadcast::TreeKemEnabled {
+            community_id: community_id.to_string(),
+            epoch,
+        },
+    );
+
+    CommunityResponse::TreeEpoch { epoch }
+}
+
+fn handle_publish_key_package(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    key_package: &[u8],
+) -> CommunityResponse {
+    {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_active_tree_member(community, sender_pseudonym) {
+            return e;
+        }
+
+        let valid = serde_json::from_slice::<tree::KeyPackage>(key_package).is_ok_and(|kp| {
+            hex::encode(kp.leaf.credential) == sender_pseudonym && kp.verify().is_ok()
+        });
+        if !valid {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "invalid key package".into(),
+            };
+        }
+    }
+
+    if let Err(e) = mek::store_key_package(state, community_id, sender_pseudonym, key_package) {
+        tracing::error!(error = %e, community = %community_id, "failed to store key package");
+        return CommunityResponse::Error {
+            code: 500,
+            message: "failed to store key package".into(),
+        };
+    }
+
+    request_tree_commit(state, community_id);
+    CommunityResponse::Ok
+}
+
+async fn handle_submit_commit(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    epoch: u64,
+    commit_bytes: &[u8],
+    welcomes: &[TreeWelcomeDto],
+) -> CommunityResponse {
+    {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_active_tree_member(community, sender_pseudonym) {
+            return e;
+        }
+        let current = community.mek.generation();
+        if epoch != current {
+            return CommunityResponse::Error {
+                code: 409,
+                message: format!("commit is for epoch {epoch} but the tree is at {current}"),
+            };
+        }
+
+        let Ok(commit) = serde_json::from_slice::<tree::Commit>(commit_bytes) else {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "invalid commit".into(),
+            };
+        };
+        if commit.epoch != epoch {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "commit epoch does not match the request".into(),
+            };
+        }
+        // Anyone in the tree may refresh their own path; changing who is
+        // in it takes the same permission as publishing a MEK
+        if !commit.proposals.is_empty() {
+            if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY) {
+                return e;
+            }
+        }
+        let mut added: Vec<String> = commit
+            .proposals
+            .iter()
+            .filter_map(|p| match p {
+                tree::Proposal::Add(kp) => Some(hex::encode(kp.leaf.credential)),
+                tree::Proposal::Remove { .. } => None,
+            })
+            .collect();
+        let active = |pseudonym: &str| {
+            community
+                .members
+                .iter()
+                .any(|m| m.pseudonym_key_hex == pseudonym && !m.is_timed_out())
+        };
+        if !added.iter().all(|p| active(p.as_str())) {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "commit adds someone who is not an active member".into(),
+            };
+        }
+        let mut welcomed: Vec<&String> = welcomes.iter().map(|w| &w.pseudonym_key).collect();
+        welcomed.sort();
+        added.sort();
+        if !welcomed.into_iter().eq(added.iter()) {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "every added member needs exactly one welcome".into(),
+            };
+        }
+
+        if let Err(e) = mek::store_commit(state, community, epoch, sender_pseudonym, commit_bytes, welcomes) {
+            tracing::error!(error = %e, community = %community_id, "failed to store commit");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to store commit".into(),
+            };
+        }
+    }
+
+    community_host::publish_mek_bundle(state, community_id).await;
+    broadcast_to_members(
+        state,
+        community_id,
+        sender_pseudonym,
+        &CommunityBroadcast::TreeCommitted {
+            community_id: community_id.to_string(),
+            epoch: epoch + 1,
+        },
+    );
+
+    CommunityResponse::Ok
+}
+
+fn handle_get_commits(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    since_epoch: u64,
+) -> CommunityResponse {
+    {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_active_tree_member(community, sender_pseudonym) {
+            return e;
+        }
+    }
+
+    CommunityResponse::Commits {
+        commits: mek::load_commits(state, community_id, since_epoch),
+        welcome: mek::load_welcome(state, community_id, sender_pseudonym),
+    }
+}
+
+/// A tree community member who is not timed out.
+fn verify_active_tree_member(community: &HostedCommunity, pseudonym: &str) -> Result<(), CommunityResponse> {
+    verify_membership(community, pseudonym)?;
+    if !matches!(community.mek, MekCustody::Tree { .. }) {
+        return Err(CommunityResponse::Error {
+            code: 409,
+            message: "this community does not use a TreeKEM group".into(),
+        });
+    }
+    if community
+        .members
+        .iter()
+        .any(|m| m.pseudonym_key_hex == pseudonym && m.is_timed_out())
+    {
+        return Err(CommunityResponse::Error {
+            code: 403,
+            message: "you are timed out".into(),
+        });
+    }
+    Ok(())
+}
+
+/// Tree communities: ask privileged members to commit the current roster
+/// and pending key packages.
+fn request_tree_commit(state: &Arc<ServerState>, community_id: &str) {
+    let broadcast = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return;
+        };
+        let MekCustody::Tree { epoch } = community.mek else {
+            return;
+        };
+        CommunityBroadcast::TreeCommitNeeded {
+            community_id: community_id.to_string(),
+            epoch,
+            members: community
+                .members
+                .iter()
+                .filter(|m| !m.is_timed_out())
+                .map(|m| m.pseudonym_key_hex.clone())
+                .collect(),
+            key_packages: mek::pending_key_packages(state, community),
+        }
+    };
+    broadcast_to_members(state, community_id, "", &broadcast);
+}
+
+// ---------------------------------------------------------------------------
+// Community metadata update
+// ---------------------------------------------------------------------------
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_update_community(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    new_name: Option<&str>,
+    new_description: Option<&str>,
+    history_visibility: Option<HistoryVisibility>,
+    invite_only: Option<bool>,
+    audit_retention_secs: Option<u32>,
+) -> CommunityResponse {
+    let (before, after) = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+
+        if let Err(e) =
+            check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY)
+        {
+            return e;
+        }
+
+        let before = community_settings_json(community);
+        if let Some(n) = new_name {
+            community.name = n.to_string();
+        }
+        if let Some(d) = new_description {
+            community.description = d.to_string();
+        }
+        if let Some(v) = history_visibility {
+            community.history_visibility = v;
+        }
+        if let Some(v) = invite_only {
+            community.invite_only = v;
+        }
+        if let Some(secs) = audit_retention_secs {
+            community.audit_retention_secs = secs;
+        }
+
+        {
+            let db = state.db.lock().unwrap_or_else(|e| {
+                tracing::error!(error = %e, "server db mutex poisoned — recovering");
+                e.into_inner()
+            });
+            if let Some(n) = new_name {
+                let _ = db.execute(
+                    "UPDATE hosted_communities SET name = ? WHERE id = ?",
+                    params![n, community_id],
+                );
+            }
+            if let Some(d) = new_description {
+                let _ = db.execute(
+                    "UPDATE hosted_communities SET description = ? WHERE id = ?",
+                    params![d, community_id],
+                );
+            }
+            if let Some(v) = history_visibility {
+                let _ = db.execute(
+                    "UPDATE hosted_communities SET history_visibility = ? WHERE id = ?",
+                    params![v.as_str(), community_id],
+                );
+            }
+            if let Some(v) = invite_only {
+                let _ = db.execute(
+                    "UPDATE hosted_communities SET invite_only = ? WHERE id = ?",
+                    params![v, community_id],
+                );
+            }
+            if let Some(secs) = audit_retention_secs {
+                let _ = db.execute(
+                    "UPDATE hosted_communities SET audit_retention_secs = ? WHERE id = ?",
+                    params![secs, community_id],
+                );
+            }
+        }
+        (before, community_settings_json(community))
+    };
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::CommunityUpdate, None)
+            .before(before)
+            .after(after),
+    );
+
+    let (name, listed) = {
+        let hosted = state.hosted.read();
+        hosted
+            .get(community_id)
+            .map(|c| (c.name.clone(), c.listed))
+            .unzip()
+    };
+    if let Some(name) = name {
+        community_host::publish_metadata(state, community_id, &name).await;
+    }
+    if listed == Some(true) && (new_name.is_some() || new_description.is_some()) {
+        let st = Arc::clone(state);
+        let cid = community_id.to_string();
+        tokio::spawn(async move {
+            community_host::publish_listing(&st, &cid).await;
+        });
+    }
+
+    CommunityResponse::CommunityUpdated
+}
+
+/// The settings `UpdateCommunity` changes, for the audit log.
+fn community_settings_json(community: &HostedCommunity) -> serde_json::Value {
+    json!({
+        "name": community.name,
+        "description": community.description,
+        "historyVisibility": community.history_visibility.as_str(),
+        "inviteOnly": community.invite_only,
+        "auditRetentionSecs": community.audit_retention_secs,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Invites
+// ---------------------------------------------------------------------------
+
+fn handle_create_invite(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    max_uses: Option<u32>,
+    expires_at: Option<u64>,
+    mut grant_role_ids: Vec<u32>,
+) -> CommunityResponse {
+    let now = timestamp_now();
+    if max_uses == Some(0) {
+        return CommunityResponse::Error {
+            code: 400,
+            message: "an invite needs at least one use".into(),
+        };
+    }
+    if expires_at.is_some_and(|t| i64::try_from(t).unwrap_or(i64::MAX) <= now) {
+        return CommunityResponse::Error {
+            code: 400,
+            message: "invite would already be expired".into(),
+        };
+    }
+    grant_role_ids.retain(|id| *id != ROLE_EVERYONE_ID);
+    grant_role_ids.sort_unstable();
+    grant_role_ids.dedup();
+
+    {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+
+        if let Err(e) =
+            check_permission(community, sender_pseudonym, permissions::CREATE_INSTANT_INVITE)
+        {
+            return e;
+        }
+
+        // Granting roles is assigning them, ahead of time
+        if !grant_role_ids.is_empty() {
+            if let Err(e) =
+                check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES)
+            {
+                return e;
+            }
+            let sender_pos = highest_role_position(community, sender_pseudonym);
+            for role_id in &grant_role_ids {
+                let Some(role) = community.roles.iter().find(|r| r.id == *role_id) else {
+                    return CommunityResponse::Error {
+                        code: 404,
+                        message: format!("role {role_id} not found"),
+                    };
+                };
+                if role.position >= sender_pos {
+                    return CommunityResponse::Error {
+                        code: 403,
+                        message: "cannot grant a role at or above your position".into(),
+                    };
+                }
+            }
+        }
+    }
+
+    let invite = InviteDto {
+        code: hex::encode(rand_bytes(8)),
+        creator_pseudonym: sender_pseudonym.to_string(),
+        max_uses,
+        uses: 0,
+        expires_at,
+        grant_role_ids,
+        created_at: now.try_into().unwrap_or(0u64),
+    };
+
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    if let Err(e) = db.execute(
+        "INSERT INTO server_invites (community_id, code, creator_pseudonym, max_uses, expires_at, grant_role_ids, created_at) \
+         VALUES (?,?,?,?,?,?,?)",
+        params![
+            community_id,
+            invite.code,
+            sender_pseudonym,
+            invite.max_uses,
+            invite.expires_at.map(|t| i64::try_from(t).unwrap_or(i64::MAX)),
+            serde_json::to_string(&invite.grant_role_ids).unwrap_or_default(),
+            now
+        ],
+    ) {
+        tracing::error!(error = %e, "failed to save invite in DB");
+        return CommunityResponse::Error {
+            code: 500,
+            message: "failed to save invite".into(),
+        };
+    }
+
+    drop(db);
+
+    tracing::info!(community = %community_id, code = %invite.code, "invite created");
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::InviteCreate, Some(invite.code.as_str())).after(json!({
+            "maxUses": invite.max_uses,
+            "expiresAt": invite.expires_at,
+            "grantRoleIds": invite.grant_role_ids,
+        })),
+    );
+    CommunityResponse::InviteCreated { invite }
+}
+
+fn handle_list_invites(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+) -> CommunityResponse {
+    let (invite_only, see_all) = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+
+        let see_all =
+            check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY).is_ok();
+        if !see_all {
+            if let Err(e) =
+                check_permission(community, sender_pseudonym, permissions::CREATE_INSTANT_INVITE)
+            {
+                return e;
+            }
+        }
+        (community.invite_only, see_all)
+    };
+
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let invites = db
+        .prepare(
+            "SELECT code, creator_pseudonym, max_uses, uses, expires_at, grant_role_ids, created_at \
+             FROM server_invites WHERE community_id = ? AND (? OR creator_pseudonym = ?) \
+             ORDER BY created_at DESC",
+        )
+        .and_then(|mut stmt| {
+            let rows = stmt.query_map(params![community_id, see_all, sender_pseudonym], |row| {
+                let expires_at: Option<i64> = row.get(4)?;
+                let grant_role_ids: String = row.get(5)?;
+                let created_at: i64 = row.get(6)?;
+                Ok(InviteDto {
+                    code: row.get(0)?,
+                    creator_pseudonym: row.get(1)?,
+                    max_uses: row.get(2)?,
+                    uses: row.get(3)?,
+                    expires_at: expires_at.map(|t| t.try_into().unwrap_or(0u64)),
+                    grant_role_ids: serde_json::from_str(&grant_role_ids).unwrap_or_default(),
+                    created_at: created_at.try_into().unwrap_or(0u64),
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or_default();
+
+    CommunityResponse::Invites {
+        invite_only,
+        invites,
+    }
+}
+
+fn handle_revoke_invite(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    code: &str,
+) -> CommunityResponse {
+    let can_manage = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY).is_ok()
+    };
+
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let creator: Option<String> = db
+        .query_row(
+            "SELECT creator_pseudonym FROM server_invites WHERE community_id = ? AND code = ?",
+            params![community_id, code],
+            |row| row.get(0),
+        )
+        .ok();
+    let Some(creator) = creator else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "invite not found".into(),
+        };
+    };
+    if !can_manage && creator != sender_pseudonym {
+        return CommunityResponse::Error {
+            code: 403,
+            message: "insufficient permissions".into(),
+        };
+    }
+    let _ = db.execute(
+        "DELETE FROM server_invites WHERE community_id = ? AND code = ?",
+        params![community_id, code],
+    );
+    drop(db);
+
+    tracing::info!(community = %community_id, code, "invite revoked");
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::InviteRevoke, Some(code))
+            .before(json!({ "creatorPseudonym": creator })),
+    );
+    CommunityResponse::Ok
+}
+
+// ---------------------------------------------------------------------------
+// Directory listing
+// ---------------------------------------------------------------------------
+
+fn handle_set_listing(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    listed: bool,
+    mut game_ids: Vec<u32>,
+) -> CommunityResponse {
+    game_ids.sort_unstable();
+    game_ids.dedup();
+    if game_ids.len() > directory::MAX_LISTING_GAMES {
+        return CommunityResponse::Error {
+            code: 400,
+            message: format!("a listing may name at most {} games", directory::MAX_LISTING_GAMES),
+        };
+    }
+
+    let (was_listed, name, before) = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+
+        if let Err(e) =
+            check_permission(community, sender_pseudonym, permissions::MANAGE_COMMUNITY)
+        {
+            return e;
+        }
+
+        let was_listed = community.listed;
+        let before = json!({ "listed": community.listed, "gameIds": community.listing_game_ids });
+        community.listed = listed;
+        community.listing_game_ids.clone_from(&game_ids);
+
+        {
+            let db = state.db.lock().unwrap_or_else(|e| {
+                tracing::error!(error = %e, "server db mutex poisoned — recovering");
+                e.into_inner()
+            });
+            if let Err(e) = db.execute(
+                "UPDATE hosted_communities SET listed = ?, listing_game_ids = ? WHERE id = ?",
+                params![
+                    listed,
+                    serde_json::to_string(&game_ids).unwrap_or_default(),
+                    community_id
+                ],
+            ) {
+                tracing::error!(error = %e, "failed to save directory listing in DB");
+            }
+        }
+        (was_listed, community.name.clone(), before)
+    };
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::ListingUpdate, None)
+            .before(before)
+            .after(json!({ "listed": listed, "gameIds": game_ids })),
+    );
+
+    // Metadata carries the listing key that vouches for the listing
+    let st = Arc::clone(state);
+    let cid = community_id.to_string();
+    tokio::spawn(async move {
+        community_host::publish_metadata(&st, &cid, &name).await;
+        // A delisting overwrites the listing record so it drops out at once
+        if listed || was_listed {
+            community_host::publish_listing(&st, &cid).await;
+        }
+    });
+
+    CommunityResponse::Listing { listed, game_ids }
+}
+
+fn handle_get_listing(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+) -> CommunityResponse {
+    let hosted = state.hosted.read();
+    let Some(community) = hosted.get(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    CommunityResponse::Listing {
+        listed: community.listed,
+        game_ids: community.listing_game_ids.clone(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Ban / Unban
+// ---------------------------------------------------------------------------
+
+async fn handle_ban(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    target_pseudonym: &str,
+) -> CommunityResponse {
+    let display_name = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+
+        if let Err(e) = check_permission(community, sender_pseudonym, permissions::BAN_MEMBERS) {
+            return e;
+        }
+
+        if sender_pseudonym == target_pseudonym {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "cannot ban yourself".into(),
+            };
+        }
+
+        if let Err(e) = check_hierarchy(community, sender_pseudonym, target_pseudonym) {
+            return e;
+        }
+
+        community
+            .members
+            .iter()
+            .find(|m| m.pseudonym_key_hex == target_pseudonym)
+            .map_or_else(String::new, |m| m.display_name.clone())
+    };
+
+    {
+        let now = timestamp_now();
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        if let Err(e) = db.execute(
+            "INSERT OR REPLACE INTO banned_members (community_id, pseudonym_key_hex, display_name, banned_at) VALUES (?,?,?,?)",
+            params![community_id, target_pseudonym, display_name, now],
+        ) {
+            tracing::error!(error = %e, "failed to insert ban record");
+        }
+    }
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::MemberBan, Some(target_pseudonym))
+            .before(json!({ "displayName": display_name })),
+    );
+    handle_leave(state, community_id, target_pseudonym).await
+}
+
+fn handle_unban(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    target_pseudonym: &str,
+) -> CommunityResponse {
+    let hosted = state.hosted.read();
+    let Some(community) = hosted.get(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::BAN_MEMBERS) {
+        return e;
+    }
+
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        if let Err(e) = db.execute(
+            "DELETE FROM banned_members WHERE community_id = ? AND pseudonym_key_hex = ?",
+            params![community.community_id, target_pseudonym],
+        ) {
+            tracing::error!(error = %e, "failed to remove ban record");
+        }
+    }
+
+    tracing::info!(community = %community.community_id, member = %target_pseudonym, "member unbanned");
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::MemberUnban, Some(target_pseudonym)),
+    );
+    CommunityResponse::Ok
+}
+
+fn handle_get_ban_list(state: &Arc<ServerState>, community_id: &str, sender_pseudonym: &str) -> CommunityResponse {
+    let hosted = state.hosted.read();
+    let Some(community) = hosted.get(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::BAN_MEMBERS) {
+        return e;
+    }
+
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+
+    let banned = db
+        .prepare(
+            "SELECT pseudonym_key_hex, display_name, banned_at FROM banned_members WHERE community_id = ? ORDER BY banned_at DESC",
+        )
+        .and_then(|mut stmt| {
+            let rows = stmt.query_map(params![community.community_id], |row| {
+                let banned_at: i64 = row.get(2)?;
+                Ok(rekindle_protocol::messaging::envelope::BannedMemberDto {
+                    pseudonym_key: row.get(0)?,
+                    display_name: row.get(1)?,
+                    banned_at: banned_at.try_into().unwrap_or(0u64),
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or_default();
+
+    CommunityResponse::BanList { banned }
+}
+
+fn handle_get_audit_log(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    before: Option<u64>,
+    limit: u32,
+    action_filter: Option<AuditAction>,
+) -> CommunityResponse {
+    let hosted = state.hosted.read();
+    let Some(community) = hosted.get(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::VIEW_AUDIT_LOG) {
+        return e;
+    }
+
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+
+    CommunityResponse::AuditLog {
+        entries: audit::page(&db, community_id, before, limit, action_filter),
+        retention_secs: community.audit_retention_secs,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Role management
+// ---------------------------------------------------------------------------
+
+#[allow(clippy::too_many_arguments)]
+fn handle_create_role(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    name: &str,
+    color: u32,
+    perms: u64,
+    hoist: bool,
+    mentionable: bool,
+) -> CommunityResponse {
+    let mut hosted = state.hosted.write();
+    let Some(community) = hosted.get_mut(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+        return e;
+    }
+
+    // Generate next role ID
+    let next_id = community.roles.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+    // Position: one below sender's highest (new roles start below the creator)
+    let sender_pos = highest_role_position(community, sender_pseudonym);
+    let position = sender_pos.saturating_sub(1).max(1); // at least 1, never 0 (@everyone)
+
+    let role = RoleDefinition {
+        id: next_id,
+        name: name.to_string(),
+        color,
+        permissions: perms,
+        position,
+        hoist,
+        mentionable,
+    };
+
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        if let Err(e) = db.execute(
+            "INSERT INTO server_roles (community_id, id, name, color, permissions, position, hoist, mentionable) VALUES (?,?,?,?,?,?,?,?)",
+            params![
+                community.community_id,
+                role.id,
+                role.name,
+                role.color,
+                role.permissions.cast_signed(),
+                role.position,
+                i32::from(role.hoist),
+                i32::from(role.mentionable),
+            ],
+        ) {
+            tracing::error!(error = %e, "failed to insert role into DB");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to create role".into(),
+            };
+        }
+    }
+
+    let after = audit::json(&role);
+    community.roles.push(role);
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::RoleCreate, Some(next_id.to_string().as_str())).after(after),
+    );
+    CommunityResponse::RoleCreated { role_id: next_id }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_edit_role(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    role_id: u32,
+    name: Option<&String>,
+    color: Option<u32>,
+    perms: Option<u64>,
+    position: Option<i32>,
+    hoist: Option<bool>,
+    mentionable: Option<bool>,
+) -> CommunityResponse {
+    let mut hosted = state.hosted.write();
+    let Some(community) = hosted.get_mut(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+        return e;
+    }
+
+    let sender_pos = highest_role_position(community, sender_pseudonym);
+    let sender_is_admin = permissions::is_administrator(member_base_permissions(community, sender_pseudonym));
+
+    // Check role exists and position constraint before mutating
+    let target_pos = community.roles.iter().find(|r| r.id == role_id).map(|r| r.position);
+    let Some(target_position) = target_pos else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "role not found".into(),
+        };
+    };
+
+    // Can only edit roles below own position (unless admin)
+    if target_position >= sender_pos && !sender_is_admin {
+        return CommunityResponse::Error {
+            code: 403,
+            message: "cannot edit a role at or above your position".into(),
+        };
+    }
+
+    let Some(role) = community.roles.iter_mut().find(|r| r.id == role_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "role not found".into(),
+        };
+    };
+
+    let before = audit::json(&*role);
+    if let Some(n) = &name {
+        role.name.clone_from(n);
+    }
+    if let Some(c) = color {
+        role.color = c;
+    }
+    if let Some(p) = perms {
+        role.permissions = p;
+    }
+    if let Some(pos) = position {
+        role.position = pos;
+    }
+    if let Some(h) = hoist {
+        role.hoist = h;
+    }
+    if let Some(m) = mentionable {
+        role.mentionable = m;
+    }
+
+    // Persist
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        if let Err(e) = db.execute(
+            "UPDATE server_roles SET name=?, color=?, permissions=?, position=?, hoist=?, mentionable=? WHERE community_id=? AND id=?",
+            params![
+                role.name,
+                role.color,
+                role.permissions.cast_signed(),
+                role.position,
+                i32::from(role.hoist),
+                i32::from(role.mentionable),
+                community.community_id,
+                role_id,
+            ],
+        ) {
+            tracing::error!(error = %e, "failed to update role in DB");
+        }
+    }
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::RoleUpdate, Some(role_id.to_string().as_str()))
+            .before(before)
+            .after(audit::json(&*role)),
+    );
+    CommunityResponse::Ok
+}
+
+fn handle_delete_role(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    role_id: u32,
+) -> CommunityResponse {
+    if role_id == ROLE_EVERYONE_ID {
+        return CommunityResponse::Error {
+            code: 400,
+            message: "cannot delete the @everyone role".into(),
+        };
+    }
+
+    let mut hosted = state.hosted.write();
+    let Some(community) = hosted.get_mut(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+        return e;
+    }
+
+    let sender_pos = highest_role_position(community, sender_pseudonym);
+    let role_pos = community
+        .roles
+        .iter()
+        .find(|r| r.id == role_id)
+        .map_or(i32::MAX, |r| r.position);
+    if role_pos >= sender_pos {
+        return CommunityResponse::Error {
+            code: 403,
+            message: "cannot delete a role at or above your position".into(),
+        };
+    }
+
+    let before = community.roles.iter().find(|r| r.id == role_id).map(audit::json);
+    community.roles.retain(|r| r.id != role_id);
+    // Remove from all members
+    for member in &mut community.members {
+        member.role_ids.retain(|rid| *rid != role_id);
+    }
+
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        let _ = db.execute(
+            "DELETE FROM server_roles WHERE community_id = ? AND id = ?",
+            params![community.community_id, role_id],
+        );
+        let _ = db.execute(
+            "DELETE FROM server_member_roles WHERE community_id = ? AND role_id = ?",
+            params![community.community_id, role_id],
+        );
+    }
+
+    if let Some(before) = before {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::RoleDelete, Some(role_id.to_string().as_str())).before(before),
+        );
+    }
+    CommunityResponse::Ok
+}
+
+fn handle_assign_role(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    target_pseudonym: &str,
+    role_id: u32,
+) -> CommunityResponse {
+    let mut hosted = state.hosted.write();
+    let Some(community) = hosted.get_mut(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+        return e;
+    }
+
+    if let Err(e) = check_hierarchy(community, sender_pseudonym, target_pseudonym) {
+        return e;
+    }
+
+    // Can only assign roles below own position
+    let sender_pos = highest_role_position(community, sender_pseudonym);
+    let role_pos = community
+        .roles
+        .iter()
+        .find(|r| r.id == role_id)
+        .map_or(i32::MAX, |r| r.position);
+    if role_pos >= sender_pos {
+        return CommunityResponse::Error {
+            code: 403,
+            message: "cannot assign a role at or above your position".into(),
+        };
+    }
+
+    let Some(member) = community
+        .members
+        .iter_mut()
+        .find(|m| m.pseudonym_key_hex == target_pseudonym)
+    else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "target member not found".into(),
+        };
+    };
+
+    if !member.role_ids.contains(&role_id) {
+        member.role_ids.push(role_id);
+        {
+            let db = state.db.lock().unwrap_or_else(|e| {
+                tracing::error!(error = %e, "server db mutex poisoned — recovering");
+                e.into_inner()
+            });
+            let _ = db.execute(
+                "INSERT OR IGNORE INTO server_member_roles (community_id, pseudonym_key_hex, role_id) VALUES (?,?,?)",
+                params![community.community_id, target_pseudonym, role_id],
+            );
+        }
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::MemberRoleAdd, Some(target_pseudonym))
+                .after(json!({ "roleId": role_id })),
+        );
+    }
+
+    CommunityResponse::Ok
+}
+
+fn handle_unassign_role(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
     target_pseudonym: &str,
     role_id: u32,
 ) -> CommunityResponse {
+    if role_id == ROLE_EVERYONE_ID {
+        return CommunityResponse::Error {
+            code: 400,
+            message: "cannot remove the @everyone role".into(),
+        };
+    }
+
     let mut hosted = state.hosted.write();
     let Some(community) = hosted.get_mut(community_id) else {
         return CommunityResponse::Error {
@@ -1784,20 +4041,6 @@ fn handle_assign_role(
         return e;
     }
 
-    // Can only assign roles below own position
-    let sender_pos = highest_role_position(community, sender_pseudonym);
-    let role_pos = community
-        .roles
-        .iter()
-        .find(|r| r.id == role_id)
-        .map_or(i32::MAX, |r| r.position);
-    if role_pos >= sender_pos {
-        return CommunityResponse::Error {
-            code: 403,
-            message: "cannot assign a role at or above your position".into(),
-        };
-    }
-
     let Some(member) = community
         .members
         .iter_mut()
@@ -1807,37 +4050,315 @@ fn handle_assign_role(
             code: 404,
             message: "target member not found".into(),
         };
-    };
+    };
+
+    let had_role = member.role_ids.contains(&role_id);
+    member.role_ids.retain(|rid| *rid != role_id);
+
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        let _ = db.execute(
+            "DELETE FROM server_member_roles WHERE community_id = ? AND pseudonym_key_hex = ? AND role_id = ?",
+            params![community.community_id, target_pseudonym, role_id],
+        );
+    }
+
+    if had_role {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::MemberRoleRemove, Some(target_pseudonym))
+                .before(json!({ "roleId": role_id })),
+        );
+    }
+    CommunityResponse::Ok
+}
+
+fn handle_get_roles(state: &Arc<ServerState>, community_id: &str, sender_pseudonym: &str) -> CommunityResponse {
+    let hosted = state.hosted.read();
+    let Some(community) = hosted.get(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    CommunityResponse::RolesList {
+        roles: roles_to_dto(community),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Channel permission overwrites
+// ---------------------------------------------------------------------------
+
+#[allow(clippy::too_many_arguments)]
+fn handle_set_channel_overwrite(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    channel_id: &str,
+    target_type: &str,
+    target_id: &str,
+    allow: u64,
+    deny: u64,
+) -> CommunityResponse {
+    let mut hosted = state.hosted.write();
+    let Some(community) = hosted.get_mut(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+        return e;
+    }
+
+    let ow_type = match target_type {
+        "member" => OverwriteType::Member,
+        _ => OverwriteType::Role,
+    };
+
+    let Some(channel) = community.channels.iter_mut().find(|ch| ch.id == channel_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "channel not found".into(),
+        };
+    };
+
+    let before = channel
+        .permission_overwrites
+        .iter()
+        .find(|o| o.target_type == ow_type && o.target_id == target_id)
+        .map(audit::json);
+
+    // Upsert
+    if let Some(existing) = channel
+        .permission_overwrites
+        .iter_mut()
+        .find(|o| o.target_type == ow_type && o.target_id == target_id)
+    {
+        existing.allow = allow;
+        existing.deny = deny;
+    } else {
+        channel.permission_overwrites.push(PermissionOverwrite {
+            target_type: ow_type,
+            target_id: target_id.to_string(),
+            allow,
+            deny,
+        });
+    }
+
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        let _ = db.execute(
+            "INSERT OR REPLACE INTO server_channel_overwrites (community_id, channel_id, target_type, target_id, allow_bits, deny_bits) VALUES (?,?,?,?,?,?)",
+            params![community.community_id, channel_id, target_type, target_id, allow.cast_signed(), deny.cast_signed()],
+        );
+    }
+
+    drop(hosted); // Release write lock before broadcasting
+
+    let mut entry = audit::Entry::new(AuditAction::ChannelOverwriteUpdate, Some(channel_id)).after(json!({
+        "targetType": target_type,
+        "targetId": target_id,
+        "allow": allow,
+        "deny": deny,
+    }));
+    if let Some(before) = before {
+        entry = entry.before(before);
+    }
+    audit::record(state, community_id, sender_pseudonym, entry);
+
+    broadcast_to_members(
+        state,
+        community_id,
+        "",
+        &CommunityBroadcast::ChannelOverwriteChanged {
+            community_id: community_id.to_string(),
+            channel_id: channel_id.to_string(),
+        },
+    );
+
+    CommunityResponse::Ok
+}
+
+fn handle_delete_channel_overwrite(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    channel_id: &str,
+    target_type: &str,
+    target_id: &str,
+) -> CommunityResponse {
+    let mut hosted = state.hosted.write();
+    let Some(community) = hosted.get_mut(community_id) else {
+        return CommunityResponse::Error {
+            code: 404,
+            message: "community not found".into(),
+        };
+    };
+    if let Err(e) = verify_membership(community, sender_pseudonym) {
+        return e;
+    }
+
+    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
+        return e;
+    }
+
+    let ow_type = match target_type {
+        "member" => OverwriteType::Member,
+        _ => OverwriteType::Role,
+    };
+
+    let mut before = None;
+    if let Some(channel) = community.channels.iter_mut().find(|ch| ch.id == channel_id) {
+        before = channel
+            .permission_overwrites
+            .iter()
+            .find(|o| o.target_type == ow_type && o.target_id == target_id)
+            .map(audit::json);
+        channel
+            .permission_overwrites
+            .retain(|o| !(o.target_type == ow_type && o.target_id == target_id));
+    }
+
+    {
+        let db = state.db.lock().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "server db mutex poisoned — recovering");
+            e.into_inner()
+        });
+        let _ = db.execute(
+            "DELETE FROM server_channel_overwrites WHERE community_id = ? AND channel_id = ? AND target_type = ? AND target_id = ?",
+            params![community.community_id, channel_id, target_type, target_id],
+        );
+    }
+
+    if let Some(before) = before {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::ChannelOverwriteDelete, Some(channel_id)).before(before),
+        );
+    }
+    CommunityResponse::Ok
+}
+
+// ---------------------------------------------------------------------------
+// Timeouts
+// ---------------------------------------------------------------------------
+
+async fn handle_timeout_member(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    target_pseudonym: &str,
+    duration_seconds: u64,
+    reason: Option<&String>,
+) -> CommunityResponse {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let timeout_until = now + duration_seconds;
+
+    let rotation = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+
+        if let Err(e) =
+            check_permission(community, sender_pseudonym, permissions::MODERATE_MEMBERS)
+        {
+            return e;
+        }
+
+        if let Err(e) = check_hierarchy(community, sender_pseudonym, target_pseudonym) {
+            return e;
+        }
+
+        let Some(member) = community
+            .members
+            .iter_mut()
+            .find(|m| m.pseudonym_key_hex == target_pseudonym)
+        else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "target member not found".into(),
+            };
+        };
 
-    if !member.role_ids.contains(&role_id) {
-        member.role_ids.push(role_id);
-        let db = state.db.lock().unwrap_or_else(|e| {
-            tracing::error!(error = %e, "server db mutex poisoned — recovering");
-            e.into_inner()
-        });
-        let _ = db.execute(
-            "INSERT OR IGNORE INTO server_member_roles (community_id, pseudonym_key_hex, role_id) VALUES (?,?,?)",
-            params![community.community_id, target_pseudonym, role_id],
-        );
-    }
+        member.timeout_until = Some(timeout_until);
+
+        {
+            let db = state.db.lock().unwrap_or_else(|e| {
+                tracing::error!(error = %e, "server db mutex poisoned — recovering");
+                e.into_inner()
+            });
+            let _ = db.execute(
+                "INSERT OR REPLACE INTO server_member_timeouts (community_id, pseudonym_key_hex, timeout_until, reason) VALUES (?,?,?,?)",
+                params![community.community_id, target_pseudonym, timeout_until.cast_signed(), reason],
+            );
+        }
+
+        // The timed-out member must not read what is said while they are muted
+        mek::rotate(state, community)
+    }; // Release write lock before broadcasting
+
+    audit::record(
+        state,
+        community_id,
+        sender_pseudonym,
+        audit::Entry::new(AuditAction::MemberTimeout, Some(target_pseudonym))
+            .after(json!({ "timeoutUntil": timeout_until, "reason": reason })),
+    );
+
+    // Timed-out members may not speak or listen in
+    voice_relay::leave(state, community_id, target_pseudonym);
+
+    broadcast_to_members(
+        state,
+        community_id,
+        "",
+        &CommunityBroadcast::MemberTimedOut {
+            community_id: community_id.to_string(),
+            pseudonym_key: target_pseudonym.to_string(),
+            timeout_until: Some(timeout_until),
+        },
+    );
+    announce_rotation(state, community_id, rotation).await;
 
     CommunityResponse::Ok
 }
 
-fn handle_unassign_role(
+fn handle_remove_timeout(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
     target_pseudonym: &str,
-    role_id: u32,
 ) -> CommunityResponse {
-    if role_id == ROLE_EVERYONE_ID {
-        return CommunityResponse::Error {
-            code: 400,
-            message: "cannot remove the @everyone role".into(),
-        };
-    }
-
     let mut hosted = state.hosted.write();
     let Some(community) = hosted.get_mut(community_id) else {
         return CommunityResponse::Error {
@@ -1849,26 +4370,20 @@ fn handle_unassign_role(
         return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
-        return e;
-    }
-
-    if let Err(e) = check_hierarchy(community, sender_pseudonym, target_pseudonym) {
+    if let Err(e) =
+        check_permission(community, sender_pseudonym, permissions::MODERATE_MEMBERS)
+    {
         return e;
     }
 
-    let Some(member) = community
+    let mut before = None;
+    if let Some(member) = community
         .members
         .iter_mut()
         .find(|m| m.pseudonym_key_hex == target_pseudonym)
-    else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "target member not found".into(),
-        };
-    };
-
-    member.role_ids.retain(|rid| *rid != role_id);
+    {
+        before = member.timeout_until.take();
+    }
 
     {
         let db = state.db.lock().unwrap_or_else(|e| {
@@ -1876,126 +4391,304 @@ fn handle_unassign_role(
             e.into_inner()
         });
         let _ = db.execute(
-            "DELETE FROM server_member_roles WHERE community_id = ? AND pseudonym_key_hex = ? AND role_id = ?",
-            params![community.community_id, target_pseudonym, role_id],
+            "DELETE FROM server_member_timeouts WHERE community_id = ? AND pseudonym_key_hex = ?",
+            params![community.community_id, target_pseudonym],
+        );
+    }
+
+    drop(hosted); // Release write lock before broadcasting
+
+    if let Some(timeout_until) = before {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::MemberTimeoutRemove, Some(target_pseudonym))
+                .before(json!({ "timeoutUntil": timeout_until })),
         );
     }
 
+    broadcast_to_members(
+        state,
+        community_id,
+        "",
+        &CommunityBroadcast::MemberTimedOut {
+            community_id: community_id.to_string(),
+            pseudonym_key: target_pseudonym.to_string(),
+            timeout_until: None,
+        },
+    );
+    request_missing_keys(state, community_id);
+
     CommunityResponse::Ok
 }
 
-fn handle_get_roles(state: &Arc<ServerState>, community_id: &str, sender_pseudonym: &str) -> CommunityResponse {
-    let hosted = state.hosted.read();
-    let Some(community) = hosted.get(community_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
+// ---------------------------------------------------------------------------
+// Voice relay
+// ---------------------------------------------------------------------------
+
+/// Join a voice channel on the relay. Requires `CONNECT` in the channel;
+/// frames are only relayed for members who also have `SPEAK`.
+fn handle_join_voice(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    channel_id: &str,
+    route_blob: Option<Vec<u8>>,
+) -> CommunityResponse {
+    let (route_blob, can_speak) = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        let Some(channel) = community.channels.iter().find(|ch| ch.id == channel_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "channel not found".into(),
+            };
+        };
+        if channel.channel_type != "voice" {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "not a voice channel".into(),
+            };
+        }
+        let Some(member) = community
+            .members
+            .iter()
+            .find(|m| m.pseudonym_key_hex == sender_pseudonym)
+        else {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "not a member".into(),
+            };
+        };
+        let perms = permissions::calculate_permissions(
+            &member.role_ids,
+            &community.roles,
+            &channel.permission_overwrites,
+            sender_pseudonym,
+            member.timeout_until,
+        );
+        if !permissions::has_permission(perms, permissions::CONNECT) {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "you do not have permission to join this voice channel".into(),
+            };
+        }
+        let Some(route_blob) = route_blob.or_else(|| member.route_blob.clone()) else {
+            return CommunityResponse::Error {
+                code: 409,
+                message: "no route to relay voice to".into(),
+            };
         };
+        (route_blob, permissions::has_permission(perms, permissions::SPEAK))
     };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
-    }
 
-    CommunityResponse::RolesList {
-        roles: roles_to_dto(community),
+    let route_id = match state.api.import_remote_private_route(route_blob) {
+        Ok(route_id) => route_id,
+        Err(e) => {
+            return CommunityResponse::Error {
+                code: 400,
+                message: format!("invalid route blob: {e}"),
+            };
+        }
+    };
+
+    let (participants, relay_token) = voice_relay::join(
+        state,
+        community_id,
+        sender_pseudonym,
+        channel_id,
+        route_id,
+        can_speak,
+    );
+    CommunityResponse::VoiceJoined {
+        participants,
+        relay_token: relay_token.to_vec(),
     }
 }
 
 // ---------------------------------------------------------------------------
-// Channel permission overwrites
+// Threads
 // ---------------------------------------------------------------------------
 
-#[allow(clippy::too_many_arguments)]
-fn handle_set_channel_overwrite(
+/// Longest thread name accepted, in characters.
+const MAX_THREAD_NAME_LEN: usize = 100;
+
+/// A thread as sent to members, with its message count and latest activity.
+fn thread_to_dto(db: &rusqlite::Connection, community_id: &str, thread: &ServerThread) -> ThreadDto {
+    let (message_count, last_message_at) = db
+        .query_row(
+            "SELECT COUNT(*), MAX(timestamp) FROM server_messages WHERE community_id = ? AND channel_id = ?",
+            params![community_id, thread.id],
+            |row| Ok((row.get::<_, u32>(0)?, row.get::<_, Option<i64>>(1)?)),
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!(error = %e, thread = %thread.id, "failed to count thread messages");
+            (0, None)
+        });
+    ThreadDto {
+        id: thread.id.clone(),
+        channel_id: thread.channel_id.clone(),
+        parent_message_id: thread.parent_message_id.clone(),
+        name: thread.name.clone(),
+        creator_pseudonym: thread.creator_pseudonym_hex.clone(),
+        private: thread.private,
+        archived: thread.archived,
+        members: thread.members.clone(),
+        message_count,
+        last_message_at: last_message_at.and_then(|t| t.try_into().ok()),
+        created_at: thread.created_at.try_into().unwrap_or(0u64),
+    }
+}
+
+/// Start a thread from a message in a text channel. The creator is its
+/// first member.
+fn handle_create_thread(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
     channel_id: &str,
-    target_type: &str,
-    target_id: &str,
-    allow: u64,
-    deny: u64,
+    parent_message_id: &str,
+    name: &str,
+    private: bool,
 ) -> CommunityResponse {
-    let mut hosted = state.hosted.write();
-    let Some(community) = hosted.get_mut(community_id) else {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_THREAD_NAME_LEN {
         return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
+            code: 400,
+            message: format!("thread name must be 1-{MAX_THREAD_NAME_LEN} characters"),
         };
-    };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
-        return e;
+    match message_author(state, community_id, channel_id, parent_message_id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return message_not_found(),
+        Err(e) => return e,
     }
 
-    let ow_type = match target_type {
-        "member" => OverwriteType::Member,
-        _ => OverwriteType::Role,
-    };
-
-    let Some(channel) = community.channels.iter_mut().find(|ch| ch.id == channel_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "channel not found".into(),
+    let thread = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
         };
-    };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        let Some(channel) = community.channels.iter().find(|ch| ch.id == channel_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "channel not found".into(),
+            };
+        };
+        if channel.channel_type != "text" {
+            return CommunityResponse::Error {
+                code: 400,
+                message: "threads can only be started in text channels".into(),
+            };
+        }
+        let required = if private {
+            permissions::CREATE_PRIVATE_THREADS
+        } else {
+            permissions::CREATE_PUBLIC_THREADS
+        };
+        if let Err(e) = check_channel_permission(community, sender_pseudonym, channel_id, required) {
+            return e;
+        }
+        if community
+            .threads
+            .iter()
+            .any(|t| t.parent_message_id == parent_message_id)
+        {
+            return CommunityResponse::Error {
+                code: 409,
+                message: "message already has a thread".into(),
+            };
+        }
 
-    // Upsert
-    if let Some(existing) = channel
-        .permission_overwrites
-        .iter_mut()
-        .find(|o| o.target_type == ow_type && o.target_id == target_id)
-    {
-        existing.allow = allow;
-        existing.deny = deny;
-    } else {
-        channel.permission_overwrites.push(PermissionOverwrite {
-            target_type: ow_type,
-            target_id: target_id.to_string(),
-            allow,
-            deny,
-        });
-    }
+        let thread = ServerThread {
+            id: format!("thread_{}", hex::encode(rand_bytes(8))),
+            channel_id: channel_id.to_string(),
+            parent_message_id: parent_message_id.to_string(),
+            name: name.to_string(),
+            creator_pseudonym_hex: sender_pseudonym.to_string(),
+            private,
+            archived: false,
+            members: vec![sender_pseudonym.to_string()],
+            created_at: timestamp_now(),
+        };
 
-    {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
-        let _ = db.execute(
-            "INSERT OR REPLACE INTO server_channel_overwrites (community_id, channel_id, target_type, target_id, allow_bits, deny_bits) VALUES (?,?,?,?,?,?)",
-            params![community.community_id, channel_id, target_type, target_id, allow.cast_signed(), deny.cast_signed()],
-        );
-    }
+        let stored = db
+            .execute(
+                "INSERT INTO server_threads (community_id, id, channel_id, parent_message_id, name, creator_pseudonym, private, created_at) \
+                 VALUES (?,?,?,?,?,?,?,?)",
+                params![
+                    community_id,
+                    thread.id,
+                    thread.channel_id,
+                    thread.parent_message_id,
+                    thread.name,
+                    thread.creator_pseudonym_hex,
+                    thread.private,
+                    thread.created_at,
+                ],
+            )
+            .and_then(|_| {
+                db.execute(
+                    "INSERT INTO server_thread_members (community_id, thread_id, pseudonym_key_hex, joined_at) VALUES (?,?,?,?)",
+                    params![community_id, thread.id, sender_pseudonym, thread.created_at],
+                )
+            });
+        if let Err(e) = stored {
+            tracing::error!(error = %e, "failed to insert thread into DB");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to create thread".into(),
+            };
+        }
 
-    drop(hosted); // Release write lock before broadcasting
+        let dto = thread_to_dto(&db, community_id, &thread);
+        community.threads.push(thread);
+        dto
+    };
 
-    broadcast_to_members(
+    broadcast_to_channel(
         state,
         community_id,
-        "",
-        &CommunityBroadcast::ChannelOverwriteChanged {
+        sender_pseudonym,
+        &thread.id,
+        &CommunityBroadcast::ThreadCreated {
             community_id: community_id.to_string(),
-            channel_id: channel_id.to_string(),
+            thread: thread.clone(),
         },
     );
-
-    CommunityResponse::Ok
+    CommunityResponse::ThreadCreated { thread }
 }
 
-fn handle_delete_channel_overwrite(
+/// A channel's threads the sender can see, most recently active first.
+fn handle_get_threads(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
     channel_id: &str,
-    target_type: &str,
-    target_id: &str,
+    include_archived: bool,
 ) -> CommunityResponse {
-    let mut hosted = state.hosted.write();
-    let Some(community) = hosted.get_mut(community_id) else {
+    let hosted = state.hosted.read();
+    let Some(community) = hosted.get(community_id) else {
         return CommunityResponse::Error {
             code: 404,
             message: "community not found".into(),
@@ -2005,169 +4698,258 @@ fn handle_delete_channel_overwrite(
         return e;
     }
 
-    if let Err(e) = check_permission(community, sender_pseudonym, permissions::MANAGE_ROLES) {
-        return e;
-    }
-
-    let ow_type = match target_type {
-        "member" => OverwriteType::Member,
-        _ => OverwriteType::Role,
-    };
+    let db = state.db.lock().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "server db mutex poisoned — recovering");
+        e.into_inner()
+    });
+    let mut threads: Vec<ThreadDto> = community
+        .threads
+        .iter()
+        .filter(|t| t.channel_id == channel_id && (include_archived || !t.archived))
+        .filter(|t| can_see_thread(community, sender_pseudonym, t))
+        .map(|t| thread_to_dto(&db, community_id, t))
+        .collect();
+    threads.sort_by_key(|t| std::cmp::Reverse(t.last_message_at.unwrap_or(t.created_at)));
+    CommunityResponse::Threads { threads }
+}
 
-    if let Some(channel) = community.channels.iter_mut().find(|ch| ch.id == channel_id) {
-        channel
-            .permission_overwrites
-            .retain(|o| !(o.target_type == ow_type && o.target_id == target_id));
-    }
+/// Archive or reopen a thread: its creator, or anyone with `MANAGE_THREADS`
+/// in its channel.
+fn handle_archive_thread(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    sender_pseudonym: &str,
+    thread_id: &str,
+    archived: bool,
+) -> CommunityResponse {
+    let (thread, moderated) = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
+        };
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        let Some(thread) = community
+            .thread(thread_id)
+            .filter(|t| can_see_thread(community, sender_pseudonym, t))
+        else {
+            return thread_not_found();
+        };
+        let moderated = thread.creator_pseudonym_hex != sender_pseudonym;
+        if moderated {
+            if let Err(e) = check_channel_permission(
+                community,
+                sender_pseudonym,
+                thread_id,
+                permissions::MANAGE_THREADS,
+            ) {
+                return e;
+            }
+        }
+        if thread.archived == archived {
+            return CommunityResponse::Ok;
+        }
 
-    {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
-        let _ = db.execute(
-            "DELETE FROM server_channel_overwrites WHERE community_id = ? AND channel_id = ? AND target_type = ? AND target_id = ?",
-            params![community.community_id, channel_id, target_type, target_id],
+        if let Err(e) = db.execute(
+            "UPDATE server_threads SET archived = ? WHERE community_id = ? AND id = ?",
+            params![archived, community_id, thread_id],
+        ) {
+            tracing::error!(error = %e, "failed to update thread in DB");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to update thread".into(),
+            };
+        }
+        let Some(thread) = community.threads.iter_mut().find(|t| t.id == thread_id) else {
+            return thread_not_found();
+        };
+        thread.archived = archived;
+        (thread_to_dto(&db, community_id, thread), moderated)
+    };
+
+    if moderated {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::ThreadUpdate, Some(thread_id))
+                .before(json!({ "name": thread.name, "archived": !archived }))
+                .after(json!({ "name": thread.name, "archived": archived })),
         );
     }
-
+    broadcast_thread_updated(state, community_id, thread, None);
     CommunityResponse::Ok
 }
 
-// ---------------------------------------------------------------------------
-// Timeouts
-// ---------------------------------------------------------------------------
-
-fn handle_timeout_member(
+/// Add `target_pseudonym` to a thread. Members join public threads
+/// themselves; adding anyone else takes being in the thread, or
+/// `MANAGE_THREADS` in its channel. Either way the target must be able to
+/// see the channel.
+fn handle_join_thread(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
+    thread_id: &str,
     target_pseudonym: &str,
-    duration_seconds: u64,
-    reason: Option<&String>,
 ) -> CommunityResponse {
-    let mut hosted = state.hosted.write();
-    let Some(community) = hosted.get_mut(community_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
+    let thread = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
         };
-    };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
-    }
-
-    if let Err(e) =
-        check_permission(community, sender_pseudonym, permissions::MODERATE_MEMBERS)
-    {
-        return e;
-    }
-
-    if let Err(e) = check_hierarchy(community, sender_pseudonym, target_pseudonym) {
-        return e;
-    }
-
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    let timeout_until = now + duration_seconds;
-
-    let Some(member) = community
-        .members
-        .iter_mut()
-        .find(|m| m.pseudonym_key_hex == target_pseudonym)
-    else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "target member not found".into(),
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        let Some(thread) = community
+            .thread(thread_id)
+            .filter(|t| can_see_thread(community, sender_pseudonym, t))
+        else {
+            return thread_not_found();
         };
-    };
-
-    member.timeout_until = Some(timeout_until);
+        if thread.members.iter().any(|m| m == target_pseudonym) {
+            return CommunityResponse::Ok;
+        }
+        if target_pseudonym != sender_pseudonym {
+            if !community
+                .members
+                .iter()
+                .any(|m| m.pseudonym_key_hex == target_pseudonym)
+            {
+                return CommunityResponse::Error {
+                    code: 404,
+                    message: "target is not a member".into(),
+                };
+            }
+            if !thread.members.iter().any(|m| m == sender_pseudonym) {
+                if let Err(e) = check_channel_permission(
+                    community,
+                    sender_pseudonym,
+                    thread_id,
+                    permissions::MANAGE_THREADS,
+                ) {
+                    return e;
+                }
+            }
+        }
+        if check_channel_permission(community, target_pseudonym, thread_id, permissions::VIEW_CHANNEL)
+            .is_err()
+        {
+            return CommunityResponse::Error {
+                code: 403,
+                message: "member cannot see this channel".into(),
+            };
+        }
 
-    {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
-        let _ = db.execute(
-            "INSERT OR REPLACE INTO server_member_timeouts (community_id, pseudonym_key_hex, timeout_until, reason) VALUES (?,?,?,?)",
-            params![community.community_id, target_pseudonym, timeout_until.cast_signed(), reason],
-        );
-    }
-
-    drop(hosted); // Release write lock before broadcasting
-
-    broadcast_to_members(
-        state,
-        community_id,
-        "",
-        &CommunityBroadcast::MemberTimedOut {
-            community_id: community_id.to_string(),
-            pseudonym_key: target_pseudonym.to_string(),
-            timeout_until: Some(timeout_until),
-        },
-    );
+        if let Err(e) = db.execute(
+            "INSERT OR IGNORE INTO server_thread_members (community_id, thread_id, pseudonym_key_hex, joined_at) VALUES (?,?,?,?)",
+            params![community_id, thread_id, target_pseudonym, timestamp_now()],
+        ) {
+            tracing::error!(error = %e, "failed to add thread member to DB");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to add thread member".into(),
+            };
+        }
+        let Some(thread) = community.threads.iter_mut().find(|t| t.id == thread_id) else {
+            return thread_not_found();
+        };
+        thread.members.push(target_pseudonym.to_string());
+        thread_to_dto(&db, community_id, thread)
+    };
 
+    broadcast_thread_updated(state, community_id, thread, None);
     CommunityResponse::Ok
 }
 
-fn handle_remove_timeout(
+/// Take `target_pseudonym` out of a thread. Members leave themselves;
+/// removing anyone else takes being the thread's creator, or
+/// `MANAGE_THREADS` in its channel.
+fn handle_leave_thread(
     state: &Arc<ServerState>,
     community_id: &str,
     sender_pseudonym: &str,
+    thread_id: &str,
     target_pseudonym: &str,
 ) -> CommunityResponse {
-    let mut hosted = state.hosted.write();
-    let Some(community) = hosted.get_mut(community_id) else {
-        return CommunityResponse::Error {
-            code: 404,
-            message: "community not found".into(),
+    let (thread, moderated) = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return CommunityResponse::Error {
+                code: 404,
+                message: "community not found".into(),
+            };
         };
-    };
-    if let Err(e) = verify_membership(community, sender_pseudonym) {
-        return e;
-    }
-
-    if let Err(e) =
-        check_permission(community, sender_pseudonym, permissions::MODERATE_MEMBERS)
-    {
-        return e;
-    }
-
-    if let Some(member) = community
-        .members
-        .iter_mut()
-        .find(|m| m.pseudonym_key_hex == target_pseudonym)
-    {
-        member.timeout_until = None;
-    }
+        if let Err(e) = verify_membership(community, sender_pseudonym) {
+            return e;
+        }
+        let Some(thread) = community
+            .thread(thread_id)
+            .filter(|t| can_see_thread(community, sender_pseudonym, t))
+        else {
+            return thread_not_found();
+        };
+        let moderated = target_pseudonym != sender_pseudonym
+            && thread.creator_pseudonym_hex != sender_pseudonym;
+        if moderated {
+            if let Err(e) = check_channel_permission(
+                community,
+                sender_pseudonym,
+                thread_id,
+                permissions::MANAGE_THREADS,
+            ) {
+                return e;
+            }
+        }
+        if !thread.members.iter().any(|m| m == target_pseudonym) {
+            return CommunityResponse::Ok;
+        }
 
-    {
         let db = state.db.lock().unwrap_or_else(|e| {
             tracing::error!(error = %e, "server db mutex poisoned — recovering");
             e.into_inner()
         });
-        let _ = db.execute(
-            "DELETE FROM server_member_timeouts WHERE community_id = ? AND pseudonym_key_hex = ?",
-            params![community.community_id, target_pseudonym],
+        if let Err(e) = db.execute(
+            "DELETE FROM server_thread_members WHERE community_id = ? AND thread_id = ? AND pseudonym_key_hex = ?",
+            params![community_id, thread_id, target_pseudonym],
+        ) {
+            tracing::error!(error = %e, "failed to remove thread member from DB");
+            return CommunityResponse::Error {
+                code: 500,
+                message: "failed to remove thread member".into(),
+            };
+        }
+        let Some(thread) = community.threads.iter_mut().find(|t| t.id == thread_id) else {
+            return thread_not_found();
+        };
+        thread.members.retain(|m| m != target_pseudonym);
+        (thread_to_dto(&db, community_id, thread), moderated)
+    };
+
+    if moderated {
+        audit::record(
+            state,
+            community_id,
+            sender_pseudonym,
+            audit::Entry::new(AuditAction::ThreadMemberRemove, Some(thread_id))
+                .before(json!({ "name": thread.name, "member": target_pseudonym })),
         );
     }
-
-    drop(hosted); // Release write lock before broadcasting
-
-    broadcast_to_members(
-        state,
-        community_id,
-        "",
-        &CommunityBroadcast::MemberTimedOut {
-            community_id: community_id.to_string(),
-            pseudonym_key: target_pseudonym.to_string(),
-            timeout_until: None,
-        },
-    );
-
+    broadcast_thread_updated(state, community_id, thread, Some(target_pseudonym));
     CommunityResponse::Ok
 }
 
@@ -2193,6 +4975,12 @@ fn broadcast_roles_changed(state: &Arc<ServerState>, community_id: &str) {
             roles,
         },
     );
+
+    let st = Arc::clone(state);
+    let cid = community_id.to_string();
+    tokio::spawn(async move {
+        community_host::publish_roles(&st, &cid).await;
+    });
 }
 
 fn broadcast_member_roles_changed(
@@ -2222,13 +5010,71 @@ fn broadcast_member_roles_changed(
             role_ids,
         },
     );
+
+    let st = Arc::clone(state);
+    let cid = community_id.to_string();
+    tokio::spawn(async move {
+        community_host::publish_member_roster(&st, &cid).await;
+    });
+}
+
+pub fn broadcast_to_members(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    exclude_pseudonym: &str,
+    broadcast: &CommunityBroadcast,
+) {
+    broadcast_where(state, community_id, broadcast, |_, m| {
+        m.pseudonym_key_hex != exclude_pseudonym
+    });
 }
 
-fn broadcast_to_members(
+/// Broadcast about messages in `channel_id` to the members who can see
+/// them: everyone, unless it is a private thread.
+fn broadcast_to_channel(
     state: &Arc<ServerState>,
     community_id: &str,
     exclude_pseudonym: &str,
+    channel_id: &str,
+    broadcast: &CommunityBroadcast,
+) {
+    broadcast_where(state, community_id, broadcast, |c, m| {
+        m.pseudonym_key_hex != exclude_pseudonym
+            && c.thread(channel_id)
+                .is_none_or(|t| can_see_thread(c, &m.pseudonym_key_hex, t))
+    });
+}
+
+/// Broadcast a thread's new state to everyone who can see it, and to
+/// `removed` — a member who just lost sight of a private thread.
+fn broadcast_thread_updated(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    thread: ThreadDto,
+    removed: Option<&str>,
+) {
+    let thread_id = thread.id.clone();
+    broadcast_where(
+        state,
+        community_id,
+        &CommunityBroadcast::ThreadUpdated {
+            community_id: community_id.to_string(),
+            thread,
+        },
+        |c, m| {
+            removed == Some(m.pseudonym_key_hex.as_str())
+                || c.thread(&thread_id)
+                    .is_some_and(|t| can_see_thread(c, &m.pseudonym_key_hex, t))
+        },
+    );
+}
+
+/// Send a broadcast to each member `include` picks.
+fn broadcast_where(
+    state: &Arc<ServerState>,
+    community_id: &str,
     broadcast: &CommunityBroadcast,
+    include: impl Fn(&HostedCommunity, &ServerMember) -> bool,
 ) {
     let broadcast_bytes = serde_json::to_vec(broadcast).unwrap_or_default();
 
@@ -2239,7 +5085,7 @@ fn broadcast_to_members(
             .map(|c| {
                 c.members
                     .iter()
-                    .filter(|m| m.pseudonym_key_hex != exclude_pseudonym)
+                    .filter(|m| include(c, m))
                     .filter_map(|m| m.route_blob.clone())
                     .collect()
             })
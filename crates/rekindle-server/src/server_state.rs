@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use parking_lot::RwLock;
 use rusqlite::Connection;
@@ -7,6 +8,8 @@ use veilid_core::{RoutingContext, VeilidAPI};
 
 use rekindle_crypto::group::media_key::MediaEncryptionKey;
 use rekindle_protocol::dht::community::{PermissionOverwrite, RoleDefinition};
+use rekindle_protocol::dht::paged_list::ListWriter;
+use rekindle_protocol::messaging::envelope::HistoryVisibility;
 
 /// Central state for the community server daemon.
 pub struct ServerState {
@@ -14,10 +17,16 @@ pub struct ServerState {
     pub api: VeilidAPI,
     /// Routing context cloned from the API.
     pub routing_context: RoutingContext,
+    /// Unsafe (direct) routing context for relayed voice frames.
+    pub voice_routing_context: RoutingContext,
     /// Server's own `SQLite` database.
     pub db: Arc<Mutex<Connection>>,
     /// Hosted communities: `community_id` -> state.
     pub hosted: RwLock<HashMap<String, HostedCommunity>>,
+    /// Writers for hosted communities' paged DHT lists, by
+    /// `(community_id, subkey)`. Opened on first publish and kept so later
+    /// publishes only write what changed.
+    pub dht_lists: parking_lot::Mutex<HashMap<(String, u32), Arc<tokio::sync::Mutex<Option<ListWriter>>>>>,
     /// Unix timestamp when the server started.
     pub started_at: u64,
 }
@@ -38,16 +47,74 @@ pub struct HostedCommunity {
     pub route_id: Option<veilid_core::RouteId>,
     /// Our private route blob (published to DHT subkey 6).
     pub route_blob: Option<Vec<u8>>,
-    /// Current MEK (plaintext on server side).
-    pub mek: MediaEncryptionKey,
+    /// Current MEK, or just its generation in zero-knowledge mode.
+    pub mek: MekCustody,
     /// In-memory roster of members.
     pub members: Vec<ServerMember>,
     /// Channels in this community.
     pub channels: Vec<ServerChannel>,
+    /// Threads started in its text channels, archived ones included.
+    pub threads: Vec<ServerThread>,
     /// Role definitions for this community.
     pub roles: Vec<RoleDefinition>,
     /// Hex-encoded pseudonym key of the community creator (inherent full permissions).
     pub creator_pseudonym_hex: String,
+    /// Which older MEK generations (and messages) newcomers may fetch.
+    pub history_visibility: HistoryVisibility,
+    /// Whether the community is listed in the public directory.
+    pub listed: bool,
+    /// `GameEntry.id`s the directory listing is tagged with.
+    pub listing_game_ids: Vec<u32>,
+    /// Whether newcomers need an invite code to join.
+    pub invite_only: bool,
+    /// Seconds audit log entries are kept for; 0 keeps them forever.
+    pub audit_retention_secs: u32,
+    /// Members connected to a voice channel: pseudonym -> participant.
+    pub voice: HashMap<String, VoiceParticipant>,
+}
+
+impl HostedCommunity {
+    /// A thread by ID.
+    pub fn thread(&self, thread_id: &str) -> Option<&ServerThread> {
+        self.threads.iter().find(|t| t.id == thread_id)
+    }
+
+    /// The channel whose permissions apply to messages in `channel_id`:
+    /// the thread's parent channel when it names a thread, otherwise
+    /// `channel_id` itself.
+    pub fn permission_channel_id<'a>(&'a self, channel_id: &'a str) -> &'a str {
+        self.thread(channel_id).map_or(channel_id, |t| t.channel_id.as_str())
+    }
+}
+
+/// Who holds a community's MEK.
+pub enum MekCustody {
+    /// The server generates each MEK and delivers it over Signal sessions.
+    Server(MediaEncryptionKey),
+    /// Zero-knowledge: privileged members generate MEKs and publish a copy
+    /// wrapped to each member's pseudonym. The server relays the opaque
+    /// copies and only tracks the current generation.
+    Members { generation: u64 },
+    /// Zero-knowledge with a `TreeKEM` group (`rekindle_crypto::group::tree`):
+    /// each epoch's MEK comes out of the members' key schedule and the
+    /// server only orders commits. The epoch doubles as the MEK generation.
+    Tree { epoch: u64 },
+}
+
+impl MekCustody {
+    /// Current MEK generation.
+    pub fn generation(&self) -> u64 {
+        match self {
+            Self::Server(mek) => mek.generation(),
+            Self::Members { generation } => *generation,
+            Self::Tree { epoch } => *epoch,
+        }
+    }
+
+    /// Whether the server never sees MEK plaintext.
+    pub fn is_zero_knowledge(&self) -> bool {
+        matches!(self, Self::Members { .. } | Self::Tree { .. })
+    }
 }
 
 /// A member in the server's roster.
@@ -60,12 +127,25 @@ pub struct ServerMember {
     pub role_ids: Vec<u32>,
     /// When the member joined (unix timestamp ms).
     pub joined_at: i64,
+    /// MEK generation that was current when the member joined.
+    pub joined_generation: u64,
     /// The member's private route blob (for broadcasting messages).
     pub route_blob: Option<Vec<u8>>,
     /// If set, the member is timed out until this unix timestamp (seconds).
     pub timeout_until: Option<u64>,
 }
 
+impl ServerMember {
+    /// Whether the member is currently timed out.
+    pub fn is_timed_out(&self) -> bool {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.timeout_until.is_some_and(|until| until > now)
+    }
+}
+
 /// A channel in a hosted community.
 pub struct ServerChannel {
     /// Unique channel ID.
@@ -76,6 +156,101 @@ pub struct ServerChannel {
     pub channel_type: String,
     /// Sort order for display.
     pub sort_order: i32,
+    /// Seconds messages are kept for; 0 keeps them forever.
+    pub retention_secs: u32,
     /// Per-channel permission overwrites.
     pub permission_overwrites: Vec<PermissionOverwrite>,
 }
+
+/// A thread: a sub-channel started from a message in a text channel.
+///
+/// Its messages are stored under its ID as their `channel_id`, and it
+/// uses its parent channel's permission overwrites.
+pub struct ServerThread {
+    /// Unique thread ID.
+    pub id: String,
+    /// The text channel it was started in.
+    pub channel_id: String,
+    /// The message it was started from.
+    pub parent_message_id: String,
+    /// Thread display name.
+    pub name: String,
+    /// Hex-encoded pseudonym key of whoever started it.
+    pub creator_pseudonym_hex: String,
+    /// Only members, and `MANAGE_THREADS` holders, can see it.
+    pub private: bool,
+    /// Archived threads take no new messages.
+    pub archived: bool,
+    /// Pseudonyms of the members who joined or were added.
+    pub members: Vec<String>,
+    /// When it was started (unix timestamp seconds).
+    pub created_at: i64,
+}
+
+/// A member connected to one of a community's voice channels.
+pub struct VoiceParticipant {
+    /// The voice channel they are in.
+    pub channel_id: String,
+    /// Their route, imported once on join so relaying a frame doesn't
+    /// import it again.
+    pub route_id: veilid_core::RouteId,
+    /// Random token handed to them in `VoiceJoined`. Their frames and
+    /// probes must lead with it; the sender key inside a frame is only
+    /// what the frame claims.
+    pub relay_token: [u8; crate::voice_relay::RELAY_TOKEN_LEN],
+    /// Whether the channel's permissions let them speak. Frames from
+    /// listen-only participants are not relayed.
+    pub can_speak: bool,
+    /// Whether the rest of the channel was last told they are speaking.
+    pub speaking: bool,
+    /// When their last frame arrived.
+    pub last_frame: Option<Instant>,
+    /// Frames from them since their last probe, for the loss we echo back.
+    pub uplink: UplinkCounter,
+}
+
+/// Counts a participant's frames between probes so the relay can tell them
+/// how much of their stream it lost on the way in.
+#[derive(Default)]
+pub struct UplinkCounter {
+    /// Highest sequence number seen.
+    highest_seq: Option<u32>,
+    /// `highest_seq` at the previous probe.
+    start_seq: Option<u32>,
+    /// Frames received since the previous probe.
+    received: u32,
+}
+
+impl UplinkCounter {
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn on_frame(&mut self, sequence: u32) {
+        self.received += 1;
+        match self.highest_seq {
+            None => {
+                self.highest_seq = Some(sequence);
+                self.start_seq = Some(sequence.wrapping_sub(1));
+            }
+            Some(highest) if (sequence.wrapping_sub(highest) as i32) > 0 => {
+                self.highest_seq = Some(sequence);
+            }
+            Some(_) => {}
+        }
+    }
+
+    /// Loss since the previous call, in percent; starts a new interval.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn take_loss_pct(&mut self) -> f32 {
+        let (Some(highest), Some(start)) = (self.highest_seq, self.start_seq) else {
+            return 0.0;
+        };
+        let expected = highest.wrapping_sub(start);
+        let lost = expected.saturating_sub(self.received);
+        self.start_seq = Some(highest);
+        self.received = 0;
+        if expected == 0 {
+            0.0
+        } else {
+            lost as f32 * 100.0 / expected as f32
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rekindle_protocol::messaging::envelope::{CommunityBroadcast, VoiceParticipantDto};
+use rand::RngCore as _;
+use serde::{Deserialize, Serialize};
+use veilid_core::{RouteId, Target};
+
+use crate::rpc::broadcast_to_members;
+use crate::server_state::{HostedCommunity, ServerState, UplinkCounter, VoiceParticipant};
+
+/// Clients only send frames while their VAD hears speech, so a participant
+/// whose frames stop for this long has stopped talking.
+const SPEAKING_HOLD: Duration = Duration::from_millis(400);
+
+/// How often `speaking_sweep_loop` looks for participants who went quiet.
+const SWEEP_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Length of the relay token a participant puts between the message tag
+/// and the frame or probe.
+pub const RELAY_TOKEN_LEN: usize = 16;
+
+/// The leading fields of `rekindle_voice::transport::VoicePacket`.
+///
+/// The relay only needs the sender and sequence number; declaring them
+/// here keeps the audio stack out of the server. bincode reads fields in
+/// order and ignores the rest, so these must stay the packet's first fields.
+#[derive(Deserialize)]
+struct FrameHeader {
+    sender_key: Vec<u8>,
+    sequence: u32,
+}
+
+/// `rekindle_voice::transport::VoiceProbe`, field for field — the relay
+/// answers probes itself rather than forwarding them.
+#[derive(Serialize, Deserialize)]
+struct VoiceProbe {
+    sender_key: Vec<u8>,
+    sent_at_ms: u64,
+    echo: bool,
+    held_ms: u32,
+    loss_pct: f32,
+}
+
+/// Put a member in a voice channel, moving them out of any other channel
+/// in the community, and tell the community. Returns who was already there
+/// and the relay token the member must send with every frame.
+pub fn join(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    pseudonym: &str,
+    channel_id: &str,
+    route_id: RouteId,
+    can_speak: bool,
+) -> (Vec<VoiceParticipantDto>, [u8; RELAY_TOKEN_LEN]) {
+    let mut relay_token = [0u8; RELAY_TOKEN_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut relay_token);
+
+    let (participants, display_name, previous) = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return (Vec::new(), relay_token);
+        };
+        let previous = community
+            .voice
+            .remove(pseudonym)
+            .map(|p| p.channel_id)
+            .filter(|ch| ch != channel_id);
+        let participants = community
+            .voice
+            .iter()
+            .filter(|(_, p)| p.channel_id == channel_id)
+            .map(|(key, _)| VoiceParticipantDto {
+                pseudonym_key: key.clone(),
+                display_name: display_name_of(community, key),
+            })
+            .collect();
+        community.voice.insert(
+            pseudonym.to_string(),
+            VoiceParticipant {
+                channel_id: channel_id.to_string(),
+                route_id,
+                relay_token,
+                can_speak,
+                speaking: false,
+                last_frame: None,
+                uplink: UplinkCounter::default(),
+            },
+        );
+        (
+            participants,
+            display_name_of(community, pseudonym),
+            previous,
+        )
+    };
+
+    if let Some(previous) = previous {
+        announce_left(state, community_id, &previous, pseudonym);
+    }
+    broadcast_to_members(
+        state,
+        community_id,
+        pseudonym,
+        &CommunityBroadcast::VoiceJoined {
+            community_id: community_id.to_string(),
+            channel_id: channel_id.to_string(),
+            pseudonym_key: pseudonym.to_string(),
+            display_name,
+        },
+    );
+    tracing::info!(community = %community_id, channel = %channel_id, member = %pseudonym, "member joined voice");
+    (participants, relay_token)
+}
+
+/// Take a member out of whichever voice channel they are in, if any.
+pub fn leave(state: &Arc<ServerState>, community_id: &str, pseudonym: &str) {
+    let left = {
+        let mut hosted = state.hosted.write();
+        hosted
+            .get_mut(community_id)
+            .and_then(|c| c.voice.remove(pseudonym))
+    };
+    if let Some(participant) = left {
+        announce_left(state, community_id, &participant.channel_id, pseudonym);
+        tracing::info!(community = %community_id, member = %pseudonym, "member left voice");
+    }
+}
+
+/// Disconnect everyone from a deleted channel.
+pub fn close_channel(state: &Arc<ServerState>, community_id: &str, channel_id: &str) {
+    let dropped: Vec<String> = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted.get_mut(community_id) else {
+            return;
+        };
+        let dropped = community
+            .voice
+            .iter()
+            .filter(|(_, p)| p.channel_id == channel_id)
+            .map(|(key, _)| key.clone())
+            .collect();
+        community.voice.retain(|_, p| p.channel_id != channel_id);
+        dropped
+    };
+    for pseudonym in dropped {
+        announce_left(state, community_id, channel_id, &pseudonym);
+    }
+}
+
+/// Drop participants whose routes Veilid reports as dead — they can no
+/// longer hear the channel.
+pub fn drop_dead_routes(state: &Arc<ServerState>, dead_remote_routes: &[RouteId]) {
+    let dropped: Vec<(String, String, String)> = {
+        let mut hosted = state.hosted.write();
+        let mut dropped = Vec::new();
+        for community in hosted.values_mut() {
+            community.voice.retain(|pseudonym, p| {
+                let dead = dead_remote_routes.contains(&p.route_id);
+                if dead {
+                    dropped.push((
+                        community.community_id.clone(),
+                        p.channel_id.clone(),
+                        pseudonym.clone(),
+                    ));
+                }
+                !dead
+            });
+        }
+        dropped
+    };
+    for (community_id, channel_id, pseudonym) in dropped {
+        tracing::info!(community = %community_id, member = %pseudonym, "voice route died — dropping participant");
+        announce_left(state, &community_id, &channel_id, &pseudonym);
+    }
+}
+
+/// Relay a voice frame that arrived on a community's route to everyone else
+/// in the sender's channel.
+///
+/// `message` is the `b'V'` tag, the sender's relay token, then the frame.
+/// The sender is whoever holds the token; a frame whose declared sender key
+/// disagrees is dropped. The token is stripped and the tagged frame is
+/// forwarded otherwise unchanged: frames are end-to-end encrypted, and the
+/// relay reads nothing but the header.
+pub fn forward_frame(state: &Arc<ServerState>, route_id: Option<&RouteId>, message: &[u8]) {
+    let Some(route_id) = route_id else {
+        return;
+    };
+    let Some((token, frame)) = split_token(message) else {
+        tracing::trace!("dropping voice frame without a relay token");
+        return;
+    };
+    let Ok(header) = bincode::deserialize::<FrameHeader>(frame) else {
+        tracing::trace!("dropping malformed voice frame");
+        return;
+    };
+
+    let (sender, targets, started) = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted
+            .values_mut()
+            .find(|c| c.route_id.as_ref() == Some(route_id))
+        else {
+            return;
+        };
+        let Some((sender, participant)) = participant_by_token(community, token) else {
+            tracing::trace!("dropping voice frame with an unknown relay token");
+            return;
+        };
+        if hex::encode(&header.sender_key) != *sender {
+            tracing::debug!(member = %sender, "dropping voice frame with a spoofed sender key");
+            return;
+        }
+        if !participant.can_speak {
+            return;
+        }
+        let sender = sender.clone();
+        participant.uplink.on_frame(header.sequence);
+        participant.last_frame = Some(Instant::now());
+        let started = !participant.speaking;
+        participant.speaking = true;
+        let channel_id = participant.channel_id.clone();
+        let targets = channel_routes(community, &channel_id, &sender);
+        (
+            sender,
+            targets,
+            started.then(|| (community.community_id.clone(), channel_id)),
+        )
+    };
+
+    let mut data = Vec::with_capacity(1 + frame.len());
+    data.push(b'V');
+    data.extend_from_slice(frame);
+    send_to_routes(state, targets, &data);
+    if let Some((community_id, channel_id)) = started {
+        announce_speaking(state, &community_id, &channel_id, &sender, true);
+    }
+}
+
+/// Echo a participant's round-trip probe straight back, with the share of
+/// their frames that never reached us since their last one. Probes carry
+/// the relay token like frames do.
+pub fn answer_probe(state: &Arc<ServerState>, route_id: Option<&RouteId>, message: &[u8]) {
+    let Some(route_id) = route_id else {
+        return;
+    };
+    let Some((token, body)) = split_token(message) else {
+        tracing::trace!("dropping voice probe without a relay token");
+        return;
+    };
+    let Ok(mut probe) = bincode::deserialize::<VoiceProbe>(body) else {
+        tracing::trace!("dropping malformed voice probe");
+        return;
+    };
+    if probe.echo {
+        return;
+    }
+
+    let target = {
+        let mut hosted = state.hosted.write();
+        let Some(community) = hosted
+            .values_mut()
+            .find(|c| c.route_id.as_ref() == Some(route_id))
+        else {
+            return;
+        };
+        let Some((sender, participant)) = participant_by_token(community, token) else {
+            return;
+        };
+        if hex::encode(&probe.sender_key) != *sender {
+            return;
+        }
+        probe.loss_pct = participant.uplink.take_loss_pct();
+        participant.route_id.clone()
+    };
+    probe.echo = true;
+    probe.held_ms = 0;
+
+    let Ok(payload) = bincode::serialize(&probe) else {
+        return;
+    };
+    let mut data = Vec::with_capacity(1 + payload.len());
+    data.push(b'E');
+    data.extend_from_slice(&payload);
+    send_to_routes(state, vec![target], &data);
+}
+
+/// Tell channels when a participant's frames have stopped.
+pub async fn speaking_sweep_loop(state: Arc<ServerState>) {
+    let mut tick = tokio::time::interval(SWEEP_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tick.tick().await;
+        let quiet: Vec<(String, String, String)> = {
+            let mut hosted = state.hosted.write();
+            let mut quiet = Vec::new();
+            for community in hosted.values_mut() {
+                for (pseudonym, p) in &mut community.voice {
+                    if p.speaking && p.last_frame.is_none_or(|t| t.elapsed() > SPEAKING_HOLD) {
+                        p.speaking = false;
+                        quiet.push((
+                            community.community_id.clone(),
+                            p.channel_id.clone(),
+                            pseudonym.clone(),
+                        ));
+                    }
+                }
+            }
+            quiet
+        };
+        for (community_id, channel_id, pseudonym) in quiet {
+            announce_speaking(&state, &community_id, &channel_id, &pseudonym, false);
+        }
+    }
+}
+
+fn announce_left(state: &Arc<ServerState>, community_id: &str, channel_id: &str, pseudonym: &str) {
+    broadcast_to_members(
+        state,
+        community_id,
+        "",
+        &CommunityBroadcast::VoiceLeft {
+            community_id: community_id.to_string(),
+            channel_id: channel_id.to_string(),
+            pseudonym_key: pseudonym.to_string(),
+        },
+    );
+}
+
+/// Speaking changes go only to the speaker's channel, over the same direct
+/// routes as the frames so the indicator keeps up with the audio.
+fn announce_speaking(
+    state: &Arc<ServerState>,
+    community_id: &str,
+    channel_id: &str,
+    pseudonym: &str,
+    speaking: bool,
+) {
+    let targets = {
+        let hosted = state.hosted.read();
+        let Some(community) = hosted.get(community_id) else {
+            return;
+        };
+        channel_routes(community, channel_id, pseudonym)
+    };
+    let broadcast = CommunityBroadcast::VoiceSpeaking {
+        community_id: community_id.to_string(),
+        channel_id: channel_id.to_string(),
+        pseudonym_key: pseudonym.to_string(),
+        speaking,
+    };
+    send_to_routes(
+        state,
+        targets,
+        &serde_json::to_vec(&broadcast).unwrap_or_default(),
+    );
+}
+
+/// Routes of everyone in `channel_id` except `exclude`.
+fn channel_routes(community: &HostedCommunity, channel_id: &str, exclude: &str) -> Vec<RouteId> {
+    community
+        .voice
+        .iter()
+        .filter(|(key, p)| p.channel_id == channel_id && key.as_str() != exclude)
+        .map(|(_, p)| p.route_id.clone())
+        .collect()
+}
+
+fn send_to_routes(state: &Arc<ServerState>, routes: Vec<RouteId>, data: &[u8]) {
+    for route_id in routes {
+        let rc = state.voice_routing_context.clone();
+        let data = data.to_vec();
+        tokio::spawn(async move {
+            if let Err(e) = rc.app_message(Target::RouteId(route_id), data).await {
+                tracing::trace!(error = %e, "failed to relay to voice participant");
+            }
+        });
+    }
+}
+
+/// Split a tagged relay message into its relay token and the body after it.
+fn split_token(message: &[u8]) -> Option<(&[u8], &[u8])> {
+    let rest = message.get(1..)?;
+    (rest.len() >= RELAY_TOKEN_LEN).then(|| rest.split_at(RELAY_TOKEN_LEN))
+}
+
+/// The participant `token` was issued to, with their pseudonym.
+fn participant_by_token<'a>(
+    community: &'a mut HostedCommunity,
+    token: &[u8],
+) -> Option<(&'a String, &'a mut VoiceParticipant)> {
+    community
+        .voice
+        .iter_mut()
+        .find(|(_, p)| p.relay_token.as_slice() == token)
+}
+
+fn display_name_of(community: &HostedCommunity, pseudonym: &str) -> String {
+    community
+        .members
+        .iter()
+        .find(|m| m.pseudonym_key_hex == pseudonym)
+        .map_or_else(String::new, |m| m.display_name.clone())
+}
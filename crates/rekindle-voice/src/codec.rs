@@ -175,6 +175,20 @@ impl OpusCodec {
             .set_packet_loss_perc(percent)
             .map_err(|e| VoiceError::Codec(format!("set packet loss percent failed: {e}")))
     }
+
+    /// Set the encoder's target bitrate in bits per second.
+    pub fn set_bitrate(&mut self, bits_per_second: i32) -> Result<(), VoiceError> {
+        self.encoder
+            .set_bitrate(opus::Bitrate::Bits(bits_per_second))
+            .map_err(|e| VoiceError::Codec(format!("set bitrate failed: {e}")))
+    }
+
+    /// Turn in-band FEC on or off.
+    pub fn set_inband_fec(&mut self, enabled: bool) -> Result<(), VoiceError> {
+        self.encoder
+            .set_inband_fec(enabled)
+            .map_err(|e| VoiceError::Codec(format!("set FEC failed: {e}")))
+    }
 }
 
 #[cfg(test)]
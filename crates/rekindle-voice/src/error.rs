@@ -13,4 +13,7 @@ pub enum VoiceError {
 
     #[error("not connected to voice channel")]
     NotConnected,
+
+    #[error("frame encryption error: {0}")]
+    Crypto(String),
 }
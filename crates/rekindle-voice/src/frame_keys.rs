@@ -0,0 +1,126 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use rekindle_crypto::sframe::{EncryptedFrame, FrameDecryptor, FrameEncryptor, FrameKey};
+
+use crate::error::VoiceError;
+use crate::transport::VoicePacket;
+
+/// How long a sender keeps using its old key after learning a new one, so
+/// every receiver has the new key before the first frame sealed with it.
+pub const KEY_SWITCH_DELAY: Duration = Duration::from_secs(3);
+
+/// Frame keys for one voice session.
+///
+/// Shared between the send loop (through `VoiceTransport`), the receive
+/// loop, and whatever learns about new keys — a MEK rotation for channels,
+/// a call key from the peer for 1:1 calls.
+pub struct VoiceKeys {
+    encryptor: Mutex<FrameEncryptor>,
+    decryptor: Mutex<FrameDecryptor>,
+}
+
+impl VoiceKeys {
+    /// Keys for frames we send under `sender_key` (our public key).
+    pub fn new(sender_key: Vec<u8>) -> Self {
+        Self {
+            encryptor: Mutex::new(FrameEncryptor::new(sender_key)),
+            decryptor: Mutex::new(FrameDecryptor::new()),
+        }
+    }
+
+    /// Seal our frames with `key` from `KEY_SWITCH_DELAY` from now on (right
+    /// away if we have no key yet).
+    pub fn set_send_key(&self, key: FrameKey) {
+        self.encryptor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_key(key, Instant::now() + KEY_SWITCH_DELAY);
+    }
+
+    /// Id of the key our frames are sealed with right now.
+    pub fn send_key_id(&self) -> Option<u64> {
+        self.encryptor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .key_id()
+    }
+
+    /// Accept frames sealed with `key`. Older keys stay usable until evicted.
+    pub fn add_receive_key(&self, key: FrameKey) {
+        self.decryptor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .add_key(key);
+    }
+
+    /// Whether frames sealed with `key_id` can be opened.
+    pub fn has_receive_key(&self, key_id: u64) -> bool {
+        self.decryptor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .has_key(key_id)
+    }
+
+    /// Seal one Opus frame.
+    pub fn seal(&self, audio: &[u8]) -> Result<EncryptedFrame, VoiceError> {
+        self.encryptor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .encrypt(Instant::now(), audio)
+            .map_err(|e| VoiceError::Crypto(e.to_string()))
+    }
+
+    /// Open the Opus frame in a received packet.
+    pub fn open(&self, packet: &VoicePacket) -> Result<Vec<u8>, VoiceError> {
+        let frame = EncryptedFrame {
+            key_id: packet.key_id,
+            counter: packet.counter,
+            ciphertext: packet.audio_data.clone(),
+        };
+        self.decryptor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .decrypt(&packet.sender_key, &frame)
+            .map_err(|e| VoiceError::Crypto(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(sender: &[u8], frame: EncryptedFrame) -> VoicePacket {
+        VoicePacket {
+            sender_key: sender.to_vec(),
+            sequence: 0,
+            timestamp: 0,
+            key_id: frame.key_id,
+            counter: frame.counter,
+            audio_data: frame.ciphertext,
+        }
+    }
+
+    #[test]
+    fn test_sealed_packet_opens() {
+        let key = FrameKey::generate(7);
+        let alice = VoiceKeys::new(vec![1; 32]);
+        alice.set_send_key(key.clone());
+        let bob = VoiceKeys::new(vec![2; 32]);
+        bob.add_receive_key(key);
+
+        let sealed = alice.seal(b"opus").unwrap();
+        assert_eq!(
+            bob.open(&packet(&[1; 32], sealed.clone())).unwrap(),
+            b"opus"
+        );
+        // Claiming another sender breaks the AAD
+        assert!(bob.open(&packet(&[3; 32], sealed)).is_err());
+    }
+
+    #[test]
+    fn test_no_send_key_no_frame() {
+        let alice = VoiceKeys::new(vec![1; 32]);
+        assert!(alice.seal(b"opus").is_err());
+    }
+}
@@ -6,7 +6,9 @@ use std::collections::BTreeMap;
 /// Buffers incoming voice packets and releases them at a steady rate
 /// to compensate for variable network latency. Includes an initial
 /// buffering phase that waits until enough packets have accumulated
-/// before allowing playback to start.
+/// before allowing playback to start. The buffer refills the same way
+/// whenever it runs dry — normally between talk spurts — so a new target
+/// set by `set_target_delay_ms` takes effect at the next spurt.
 pub struct JitterBuffer {
     /// Buffered packets indexed by sequence number.
     buffer: BTreeMap<u32, VoicePacket>,
@@ -20,6 +22,11 @@ pub struct JitterBuffer {
     initial_fill_done: bool,
     /// Timestamp of the first packet arrival (for initial fill timing).
     first_packet_time: Option<std::time::Instant>,
+    /// Whether anything has been played yet (from then on, packets behind
+    /// `next_playback_seq` are late).
+    has_played: bool,
+    /// Packets that arrived after their slot had been played or concealed.
+    late_drops: u64,
 }
 
 impl JitterBuffer {
@@ -32,6 +39,8 @@ impl JitterBuffer {
             max_packets: 50,
             initial_fill_done: false,
             first_packet_time: None,
+            has_played: false,
+            late_drops: 0,
         }
     }
 
@@ -45,8 +54,9 @@ impl JitterBuffer {
         }
 
         // Drop packets that are too old (already played)
-        if self.initial_fill_done && seq < self.next_playback_seq {
+        if self.has_played && seq < self.next_playback_seq {
             tracing::trace!(seq, expected = self.next_playback_seq, "dropping late packet");
+            self.late_drops += 1;
             return;
         }
 
@@ -65,6 +75,10 @@ impl JitterBuffer {
     ///
     /// Returns `None` if the initial fill phase hasn't completed yet
     /// or the next expected packet hasn't arrived (packet loss / buffering).
+    /// A missing packet with later ones already buffered is given up on:
+    /// its slot is skipped, and `peek_next_audio_data` then offers the
+    /// packet after it for FEC. With nothing buffered at all the buffer
+    /// refills before playing again.
     pub fn pop(&mut self) -> Option<VoicePacket> {
         // Don't start playback until initial fill is complete
         if !self.initial_fill_done {
@@ -80,6 +94,12 @@ impl JitterBuffer {
         let packet = self.buffer.remove(&self.next_playback_seq);
         if packet.is_some() {
             self.next_playback_seq += 1;
+            self.has_played = true;
+        } else if self.buffer.is_empty() {
+            self.initial_fill_done = false;
+            self.first_packet_time = None;
+        } else {
+            self.next_playback_seq += 1;
         }
         packet
     }
@@ -107,17 +127,19 @@ impl JitterBuffer {
         false
     }
 
-    /// Peek at the next buffered packet after the expected one (for FEC recovery).
+    /// Peek at the packet after a missing one (for FEC recovery).
     ///
-    /// When `pop()` returns `None` (current packet missing), this peeks at
-    /// `next_playback_seq + 1` to check if FEC recovery is possible.
-    /// Returns the audio data of the next packet if available.
+    /// When `pop()` returns `None` because it skipped a missing packet,
+    /// this peeks at the packet that followed it — now `next_playback_seq`
+    /// — to check if FEC recovery is possible. Returns its audio data if
+    /// available.
     pub fn peek_next_audio_data(&self) -> Option<&[u8]> {
         if !self.initial_fill_done {
             return None;
         }
-        let next_seq = self.next_playback_seq.wrapping_add(1);
-        self.buffer.get(&next_seq).map(|p| p.audio_data.as_slice())
+        self.buffer
+            .get(&self.next_playback_seq)
+            .map(|p| p.audio_data.as_slice())
     }
 
     /// Get the current buffer depth (number of buffered packets).
@@ -130,17 +152,24 @@ impl JitterBuffer {
         self.target_delay_ms
     }
 
-    /// Set a new target delay.
+    /// Set a new target delay, used from the next refill on.
     pub fn set_target_delay_ms(&mut self, ms: u32) {
         self.target_delay_ms = ms;
     }
 
+    /// Packets dropped for arriving too late, since creation or `reset`.
+    pub fn late_drops(&self) -> u64 {
+        self.late_drops
+    }
+
     /// Reset the buffer (e.g., on reconnect).
     pub fn reset(&mut self) {
         self.buffer.clear();
         self.next_playback_seq = 0;
         self.initial_fill_done = false;
         self.first_packet_time = None;
+        self.has_played = false;
+        self.late_drops = 0;
     }
 }
 
@@ -153,6 +182,8 @@ mod tests {
             sender_key: vec![0; 32],
             sequence: seq,
             timestamp: u64::from(seq) * 20,
+            key_id: 0,
+            counter: u64::from(seq),
             audio_data: vec![0; 160],
         }
     }
@@ -191,5 +222,41 @@ mod tests {
 
         jb.push(make_packet(0)); // late, should be dropped
         assert_eq!(jb.depth(), 0);
+        assert_eq!(jb.late_drops(), 1);
+    }
+
+    #[test]
+    fn test_gap_skipped_with_fec_peek() {
+        let mut jb = JitterBuffer::new(0);
+        jb.push(make_packet(0));
+        jb.push(make_packet(2));
+        jb.push(make_packet(3));
+
+        assert_eq!(jb.pop().unwrap().sequence, 0);
+        // 1 is missing: skip it and offer 2 for FEC
+        assert!(jb.pop().is_none());
+        assert!(jb.peek_next_audio_data().is_some());
+        assert_eq!(jb.pop().unwrap().sequence, 2);
+
+        // 1 turning up now is too late
+        jb.push(make_packet(1));
+        assert_eq!(jb.late_drops(), 1);
+        assert_eq!(jb.pop().unwrap().sequence, 3);
+    }
+
+    #[test]
+    fn test_refills_after_running_dry() {
+        let mut jb = JitterBuffer::new(0);
+        jb.push(make_packet(0));
+        assert_eq!(jb.pop().unwrap().sequence, 0);
+        assert!(jb.pop().is_none());
+
+        // Next spurt, buffered against the new target before playing
+        jb.set_target_delay_ms(60);
+        jb.push(make_packet(1));
+        jb.push(make_packet(2));
+        assert!(jb.pop().is_none());
+        jb.push(make_packet(3));
+        assert_eq!(jb.pop().unwrap().sequence, 1);
     }
 }
@@ -2,9 +2,11 @@ pub mod audio_processing;
 pub mod capture;
 pub mod codec;
 pub mod error;
+pub mod frame_keys;
 pub mod jitter;
 pub mod mixer;
 pub mod playback;
+pub mod stats;
 pub mod transport;
 
 pub use error::VoiceError;
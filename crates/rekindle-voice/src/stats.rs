@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+use crate::transport::VoiceProbe;
+
+/// Lowest bitrate the encoder is pushed down to on a bad link.
+pub const MIN_BITRATE: i32 = 12_000;
+/// Bitrate a session starts at (the codec's default).
+pub const START_BITRATE: i32 = 32_000;
+/// Highest bitrate a clean link is allowed to climb to.
+pub const MAX_BITRATE: i32 = 40_000;
+
+/// Receive-side statistics for one participant's stream, in the manner of
+/// RTCP receiver reports (RFC 3550 §6.4.1): loss from gaps in `sequence`,
+/// interarrival jitter from `timestamp` against arrival time.
+#[derive(Default)]
+pub struct StreamStats {
+    /// Highest sequence number seen so far.
+    highest_seq: Option<u32>,
+    /// `highest_seq` when the current report interval began.
+    interval_start_seq: Option<u32>,
+    /// Packets received in the current report interval.
+    interval_received: u32,
+    /// Smoothed interarrival jitter in milliseconds.
+    jitter_ms: f64,
+    /// Transit time (arrival minus send timestamp) of the previous packet.
+    last_transit_ms: Option<i64>,
+}
+
+/// One report interval's worth of `StreamStats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StreamReport {
+    /// Percentage of the packets sent during the interval that never arrived.
+    pub loss_pct: f32,
+    /// Current interarrival jitter in milliseconds.
+    pub jitter_ms: f32,
+}
+
+impl StreamStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a packet sent at `timestamp_ms` (sender's clock) that arrived
+    /// at `arrival_ms` (ours). The clocks need not agree: only the change in
+    /// transit time between packets counts.
+    #[allow(clippy::cast_possible_wrap, clippy::cast_precision_loss)]
+    pub fn on_packet(&mut self, sequence: u32, timestamp_ms: u64, arrival_ms: u64) {
+        self.interval_received += 1;
+        match self.highest_seq {
+            None => {
+                self.highest_seq = Some(sequence);
+                self.interval_start_seq = Some(sequence.wrapping_sub(1));
+            }
+            Some(highest) if (sequence.wrapping_sub(highest) as i32) > 0 => {
+                self.highest_seq = Some(sequence);
+            }
+            Some(_) => {}
+        }
+
+        let transit = arrival_ms as i64 - timestamp_ms as i64;
+        if let Some(last) = self.last_transit_ms {
+            let d = (transit - last).unsigned_abs() as f64;
+            self.jitter_ms += (d - self.jitter_ms) / 16.0;
+        }
+        self.last_transit_ms = Some(transit);
+    }
+
+    /// Close the current report interval and start the next.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub fn take_report(&mut self) -> StreamReport {
+        let (Some(highest), Some(start)) = (self.highest_seq, self.interval_start_seq) else {
+            return StreamReport::default();
+        };
+        let expected = highest.wrapping_sub(start);
+        // Packets reordered across the interval boundary can push received
+        // past expected; that is no loss, not negative loss.
+        let lost = expected.saturating_sub(self.interval_received);
+        let loss_pct = if expected == 0 {
+            0.0
+        } else {
+            lost as f32 * 100.0 / expected as f32
+        };
+
+        self.interval_start_seq = Some(highest);
+        self.interval_received = 0;
+        StreamReport {
+            loss_pct,
+            jitter_ms: self.jitter_ms as f32,
+        }
+    }
+}
+
+/// What we know about how our own frames fare, from echoed probes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Uplink {
+    /// Smoothed round-trip time to whoever receives our frames.
+    pub rtt_ms: Option<u32>,
+    /// Percentage of our frames they lost, as of their latest echo.
+    pub loss_pct: Option<f32>,
+}
+
+/// Link statistics for one voice session.
+///
+/// Shared between the receive loop, which measures incoming streams and
+/// collects probes, and the send loop, which answers probes and tunes the
+/// encoder from what the echoes report.
+#[derive(Default)]
+pub struct LinkStats {
+    inner: Mutex<LinkInner>,
+}
+
+#[derive(Default)]
+struct LinkInner {
+    /// Latest loss we measured on each sender's stream.
+    received_loss: HashMap<Vec<u8>, f32>,
+    /// Probes from peers waiting for the send loop, with when they arrived.
+    pending_echoes: Vec<(VoiceProbe, Instant)>,
+    /// Smoothed round-trip time.
+    srtt_ms: Option<f64>,
+    /// Loss the other end reported in its latest echo.
+    remote_loss_pct: Option<f32>,
+}
+
+impl LinkStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LinkInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Remember the loss we measured on `sender`'s stream, to report back
+    /// when they probe us.
+    pub fn record_received(&self, sender: &[u8], report: &StreamReport) {
+        self.lock()
+            .received_loss
+            .insert(sender.to_vec(), report.loss_pct);
+    }
+
+    /// Stop tracking a sender who went away.
+    pub fn forget(&self, sender: &[u8]) {
+        self.lock().received_loss.remove(sender);
+    }
+
+    /// Queue a peer's probe for the send loop to echo.
+    pub fn queue_echo(&self, probe: VoiceProbe) {
+        self.lock().pending_echoes.push((probe, Instant::now()));
+    }
+
+    /// Echoes for every queued probe, each carrying how long it waited
+    /// here and how much of the prober's stream we lost.
+    pub fn take_echoes(&self) -> Vec<VoiceProbe> {
+        let mut inner = self.lock();
+        let pending = std::mem::take(&mut inner.pending_echoes);
+        pending
+            .into_iter()
+            .map(|(probe, arrived)| {
+                let held_ms = u32::try_from(arrived.elapsed().as_millis()).unwrap_or(u32::MAX);
+                let loss_pct = inner
+                    .received_loss
+                    .get(&probe.sender_key)
+                    .copied()
+                    .unwrap_or(0.0);
+                probe.into_echo(held_ms, loss_pct)
+            })
+            .collect()
+    }
+
+    /// Take in the echo of one of our probes, received at `now_ms` (the
+    /// clock `sent_at_ms` was read from).
+    #[allow(clippy::cast_precision_loss)]
+    pub fn on_echo(&self, echo: &VoiceProbe, now_ms: u64) {
+        let sample = now_ms
+            .saturating_sub(echo.sent_at_ms)
+            .saturating_sub(u64::from(echo.held_ms)) as f64;
+        let mut inner = self.lock();
+        // Same smoothing as TCP's SRTT (RFC 6298)
+        inner.srtt_ms = Some(
+            inner
+                .srtt_ms
+                .map_or(sample, |srtt| srtt + (sample - srtt) / 8.0),
+        );
+        inner.remote_loss_pct = Some(echo.loss_pct);
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn uplink(&self) -> Uplink {
+        let inner = self.lock();
+        Uplink {
+            rtt_ms: inner.srtt_ms.map(|ms| ms.round() as u32),
+            loss_pct: inner.remote_loss_pct,
+        }
+    }
+}
+
+/// Opus settings chosen for the current link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderSettings {
+    pub bitrate: i32,
+    pub inband_fec: bool,
+    /// Loss percentage the encoder should provision FEC for.
+    pub expected_loss_pct: i32,
+}
+
+/// Steers the encoder from the loss and RTT our receivers report: back off
+/// multiplicatively on a congested link, climb additively on a clean one.
+pub struct BitrateController {
+    bitrate: i32,
+}
+
+impl Default for BitrateController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitrateController {
+    pub fn new() -> Self {
+        Self {
+            bitrate: START_BITRATE,
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn update(&mut self, loss_pct: f32, rtt_ms: Option<u32>) -> EncoderSettings {
+        let rtt = rtt_ms.unwrap_or(0);
+        if loss_pct >= 10.0 || rtt > 600 {
+            self.bitrate = (self.bitrate * 3 / 4).max(MIN_BITRATE);
+        } else if loss_pct < 2.0 && rtt < 400 {
+            self.bitrate = (self.bitrate + 4_000).min(MAX_BITRATE);
+        }
+
+        // Opus only spends bits on FEC when told to expect loss
+        let inband_fec = loss_pct >= 1.0;
+        let expected_loss_pct = if inband_fec {
+            (loss_pct.ceil() as i32).clamp(1, 30)
+        } else {
+            0
+        };
+        EncoderSettings {
+            bitrate: self.bitrate,
+            inband_fec,
+            expected_loss_pct,
+        }
+    }
+
+    pub fn bitrate(&self) -> i32 {
+        self.bitrate
+    }
+}
+
+/// Jitter-buffer target for a stream with `jitter_ms` of interarrival
+/// jitter: enough to ride out a few jitter periods, in whole frames.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn recommended_buffer_ms(jitter_ms: f32) -> u32 {
+    let ms = (40.0 + 4.0 * jitter_ms.max(0.0)).min(500.0) as u32;
+    ms.div_ceil(20).saturating_mul(20).clamp(60, 500)
+}
+
+/// Connection bars (0–4) for the UI.
+pub fn connection_bars(loss_pct: f32, jitter_ms: f32, rtt_ms: Option<u32>) -> u8 {
+    // (max loss %, max jitter ms, max RTT ms) for four bars, three, two, one
+    const TIERS: [(f32, f32, u32); 4] = [
+        (2.0, 30.0, 250),
+        (5.0, 60.0, 400),
+        (10.0, 100.0, 600),
+        (20.0, f32::INFINITY, u32::MAX),
+    ];
+    let rtt = rtt_ms.unwrap_or(0);
+    TIERS
+        .iter()
+        .position(|&(loss, jitter, max_rtt)| loss_pct < loss && jitter_ms < jitter && rtt < max_rtt)
+        .map_or(0, |tier| 4 - u8::try_from(tier).unwrap_or(4))
+}
+
+/// The coarse label `VoiceEvent::ConnectionQuality` has always carried.
+pub fn quality_label(bars: u8) -> &'static str {
+    match bars {
+        3.. => "good",
+        2 => "fair",
+        _ => "poor",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loss_from_sequence_gaps() {
+        let mut stats = StreamStats::new();
+        for seq in (0..10).filter(|s| *s != 3 && *s != 7) {
+            stats.on_packet(seq, u64::from(seq) * 20, u64::from(seq) * 20 + 100);
+        }
+        let report = stats.take_report();
+        assert!((report.loss_pct - 20.0).abs() < 0.01);
+        assert!(report.jitter_ms.abs() < 0.01);
+
+        // Next interval starts clean
+        stats.on_packet(10, 200, 300);
+        assert!(stats.take_report().loss_pct.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_jitter_tracks_transit_variation() {
+        let mut stats = StreamStats::new();
+        for seq in 0..50u32 {
+            let delay = if seq % 2 == 0 { 100 } else { 160 };
+            stats.on_packet(seq, u64::from(seq) * 20, u64::from(seq) * 20 + delay);
+        }
+        let jitter = stats.take_report().jitter_ms;
+        assert!(jitter > 40.0 && jitter <= 60.0, "jitter {jitter}");
+    }
+
+    #[test]
+    fn test_echo_rtt_excludes_hold_time() {
+        let link = LinkStats::new();
+        link.record_received(
+            &[1; 32],
+            &StreamReport {
+                loss_pct: 5.0,
+                jitter_ms: 0.0,
+            },
+        );
+        link.queue_echo(VoiceProbe::new(vec![1; 32], 1_000));
+        let echoes = link.take_echoes();
+        assert_eq!(echoes.len(), 1);
+        assert!(echoes[0].echo);
+        assert!((echoes[0].loss_pct - 5.0).abs() < 0.01);
+        assert!(link.take_echoes().is_empty());
+
+        let echo = VoiceProbe::new(vec![2; 32], 1_000).into_echo(30, 2.5);
+        link.on_echo(&echo, 1_150);
+        let uplink = link.uplink();
+        assert_eq!(uplink.rtt_ms, Some(120));
+        assert_eq!(uplink.loss_pct, Some(2.5));
+    }
+
+    #[test]
+    fn test_bitrate_backs_off_and_recovers() {
+        let mut ctl = BitrateController::new();
+        let lossy = ctl.update(15.0, Some(100));
+        assert_eq!(lossy.bitrate, START_BITRATE * 3 / 4);
+        assert!(lossy.inband_fec);
+        assert_eq!(lossy.expected_loss_pct, 15);
+
+        for _ in 0..20 {
+            ctl.update(40.0, None);
+        }
+        assert_eq!(ctl.bitrate(), MIN_BITRATE);
+
+        for _ in 0..20 {
+            let clean = ctl.update(0.0, Some(80));
+            assert!(!clean.inband_fec);
+        }
+        assert_eq!(ctl.bitrate(), MAX_BITRATE);
+    }
+
+    #[test]
+    fn test_buffer_target_follows_jitter() {
+        assert_eq!(recommended_buffer_ms(0.0), 60);
+        assert_eq!(recommended_buffer_ms(40.0), 200);
+        assert_eq!(recommended_buffer_ms(45.0), 220);
+        assert_eq!(recommended_buffer_ms(1_000.0), 500);
+    }
+
+    #[test]
+    fn test_connection_bars() {
+        assert_eq!(connection_bars(0.0, 5.0, Some(50)), 4);
+        assert_eq!(connection_bars(3.0, 5.0, Some(50)), 3);
+        assert_eq!(connection_bars(0.0, 5.0, Some(500)), 2);
+        assert_eq!(connection_bars(15.0, 5.0, None), 1);
+        assert_eq!(connection_bars(50.0, 5.0, None), 0);
+        assert_eq!(quality_label(4), "good");
+        assert_eq!(quality_label(0), "poor");
+    }
+}
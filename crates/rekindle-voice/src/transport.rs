@@ -1,8 +1,11 @@
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
 use veilid_core::{RoutingContext, SafetySelection, Sequencing, Target, VeilidAPI};
 
 use crate::codec::EncodedFrame;
 use crate::error::VoiceError;
+use crate::frame_keys::VoiceKeys;
 
 /// Voice packet for network transmission.
 #[derive(Clone, Serialize, Deserialize)]
@@ -13,14 +16,69 @@ pub struct VoicePacket {
     pub sequence: u32,
     /// Timestamp in milliseconds.
     pub timestamp: u64,
-    /// Opus-encoded audio data.
+    /// Id of the frame key `audio_data` is sealed with.
+    pub key_id: u64,
+    /// Frame counter under that key (part of the nonce and AAD).
+    pub counter: u64,
+    /// Opus frame, encrypted per frame (see `frame_keys`). Plaintext once
+    /// the receive side has opened it.
     pub audio_data: Vec<u8>,
 }
 
+/// Round-trip probe. A participant sends one every few seconds to whoever
+/// receives its frames — the peer in a 1:1 call, the community's relay in
+/// a voice channel — which sends it back as an echo.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VoiceProbe {
+    /// Public key of the participant measuring. Kept on the echo.
+    pub sender_key: Vec<u8>,
+    /// Prober's clock in milliseconds when the probe left, returned unchanged.
+    pub sent_at_ms: u64,
+    /// Whether this is the echo rather than the probe.
+    pub echo: bool,
+    /// On an echo: how long the responder held the probe before answering.
+    pub held_ms: u32,
+    /// On an echo: percentage of the prober's frames the responder lost
+    /// in its latest report interval.
+    pub loss_pct: f32,
+}
+
+impl VoiceProbe {
+    pub fn new(sender_key: Vec<u8>, sent_at_ms: u64) -> Self {
+        Self {
+            sender_key,
+            sent_at_ms,
+            echo: false,
+            held_ms: 0,
+            loss_pct: 0.0,
+        }
+    }
+
+    /// Turn a received probe into its echo.
+    pub fn into_echo(self, held_ms: u32, loss_pct: f32) -> Self {
+        Self {
+            echo: true,
+            held_ms,
+            loss_pct,
+            ..self
+        }
+    }
+}
+
+/// What the dispatch loop hands the receive loop.
+pub enum VoiceMessage {
+    /// A voice frame (`b'V'`).
+    Frame(VoicePacket),
+    /// A probe or echo (`b'E'`).
+    Probe(VoiceProbe),
+}
+
 /// Voice transport over the Veilid network.
 ///
 /// Uses `SafetySelection::Unsafe` for voice to minimize latency,
-/// trading sender privacy for acceptable voice quality.
+/// trading sender privacy for acceptable voice quality. Relaying nodes
+/// only ever see encrypted frames: `send` refuses to transmit without a
+/// frame key.
 pub struct VoiceTransport {
     channel_id: String,
     is_connected: bool,
@@ -28,11 +86,15 @@ pub struct VoiceTransport {
     routing_context: Option<RoutingContext>,
     route_id: Option<veilid_core::RouteId>,
     sender_key: Vec<u8>,
+    /// Token a community relay issued on join, sent after the tag of every
+    /// message so the relay can tell who sent it. Empty for 1:1 calls.
+    relay_token: Vec<u8>,
+    keys: Arc<VoiceKeys>,
 }
 
 impl VoiceTransport {
-    /// Create a new transport for a voice channel.
-    pub fn new(channel_id: String) -> Self {
+    /// Create a new transport for a voice channel, sealing frames with `keys`.
+    pub fn new(channel_id: String, keys: Arc<VoiceKeys>) -> Self {
         Self {
             channel_id,
             is_connected: false,
@@ -40,9 +102,16 @@ impl VoiceTransport {
             routing_context: None,
             route_id: None,
             sender_key: Vec::new(),
+            relay_token: Vec::new(),
+            keys,
         }
     }
 
+    /// Lead every frame and probe with `token`, as a community relay requires.
+    pub fn set_relay_token(&mut self, token: Vec<u8>) {
+        self.relay_token = token;
+    }
+
     /// Connect to the voice channel's routing context.
     pub fn connect(
         &mut self,
@@ -85,21 +154,14 @@ impl VoiceTransport {
             return Err(VoiceError::NotConnected);
         }
 
-        let routing_context = self
-            .routing_context
-            .as_ref()
-            .ok_or(VoiceError::NotConnected)?;
-
-        let route_id = self
-            .route_id
-            .clone()
-            .ok_or(VoiceError::NotConnected)?;
-
+        let sealed = self.keys.seal(&frame.data)?;
         let packet = VoicePacket {
             sender_key: self.sender_key.clone(),
             sequence: frame.sequence,
             timestamp: frame.timestamp,
-            audio_data: frame.data.clone(),
+            key_id: sealed.key_id,
+            counter: sealed.counter,
+            audio_data: sealed.ciphertext,
         };
 
         let payload =
@@ -107,9 +169,35 @@ impl VoiceTransport {
 
         // Prepend voice type tag (b'V') so the dispatch loop can distinguish
         // voice packets from chat messages and community broadcasts.
-        let mut data = Vec::with_capacity(1 + payload.len());
-        data.push(b'V');
-        data.extend_from_slice(&payload);
+        self.send_tagged(b'V', &payload).await
+    }
+
+    /// Send a probe or echo to the remote participant. Probes carry no
+    /// audio and go out unsealed.
+    pub async fn send_probe(&self, probe: &VoiceProbe) -> Result<(), VoiceError> {
+        if !self.is_connected {
+            return Err(VoiceError::NotConnected);
+        }
+        let payload =
+            bincode::serialize(probe).map_err(|e| VoiceError::Transport(format!("{e}")))?;
+        self.send_tagged(b'E', &payload).await
+    }
+
+    async fn send_tagged(&self, tag: u8, payload: &[u8]) -> Result<(), VoiceError> {
+        let routing_context = self
+            .routing_context
+            .as_ref()
+            .ok_or(VoiceError::NotConnected)?;
+
+        let route_id = self
+            .route_id
+            .clone()
+            .ok_or(VoiceError::NotConnected)?;
+
+        let mut data = Vec::with_capacity(1 + self.relay_token.len() + payload.len());
+        data.push(tag);
+        data.extend_from_slice(&self.relay_token);
+        data.extend_from_slice(payload);
 
         routing_context
             .app_message(Target::RouteId(route_id), data)
@@ -127,6 +215,11 @@ impl VoiceTransport {
         bincode::deserialize(data).map_err(|e| VoiceError::Transport(format!("{e}")))
     }
 
+    /// Deserialize an incoming probe, again without its `b'E'` tag.
+    pub fn receive_probe(data: &[u8]) -> Result<VoiceProbe, VoiceError> {
+        bincode::deserialize(data).map_err(|e| VoiceError::Transport(format!("{e}")))
+    }
+
     /// Whether the transport is connected.
     pub fn is_connected(&self) -> bool {
         self.is_connected
@@ -13,7 +13,11 @@ CREATE TABLE IF NOT EXISTS identity (
     avatar_webp BLOB,
     account_dht_key TEXT,
     account_owner_keypair TEXT,
-    mailbox_dht_key TEXT
+    mailbox_dht_key TEXT,
+    next_prekey_id INTEGER NOT NULL DEFAULT 1,
+    -- Set only on a linked device: its own Ed25519 key (hex) and mailbox.
+    device_key TEXT,
+    device_mailbox_dht_key TEXT
 );
 
 CREATE TABLE IF NOT EXISTS friend_groups (
@@ -39,6 +43,8 @@ CREATE TABLE IF NOT EXISTS friends (
     remote_conversation_key TEXT,
     mailbox_dht_key TEXT,
     friendship_state TEXT NOT NULL DEFAULT 'accepted',
+    -- Disappearing-message timer in seconds; NULL when off.
+    disappearing_secs INTEGER,
     PRIMARY KEY (owner_key, public_key)
 );
 
@@ -46,20 +52,107 @@ CREATE TABLE IF NOT EXISTS messages (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     owner_key TEXT NOT NULL REFERENCES identity(public_key) ON DELETE CASCADE,
     conversation_id TEXT NOT NULL,
-    conversation_type TEXT NOT NULL CHECK(conversation_type IN ('dm', 'channel')),
+    conversation_type TEXT NOT NULL CHECK(conversation_type IN ('dm', 'channel', 'group')),
     sender_key TEXT NOT NULL,
     body TEXT NOT NULL,
     timestamp INTEGER NOT NULL,
     is_read INTEGER NOT NULL DEFAULT 0,
     reply_to_id INTEGER REFERENCES messages(id) ON DELETE SET NULL,
     attachment_json TEXT,
-    mek_generation INTEGER
+    mek_generation INTEGER,
+    -- Globally unique ID chosen by the sender; edits, deletions and
+    -- reactions refer to it. NULL for messages from older clients.
+    message_id TEXT,
+    edited_at INTEGER,
+    -- Our DMs only: queued, sent, delivered, read or failed.
+    delivery_state TEXT,
+    -- Unix ms after which the purge deletes the message (disappearing
+    -- DMs, channels with a retention).
+    expires_at INTEGER
 );
 
 CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(owner_key, conversation_id, timestamp);
 CREATE INDEX IF NOT EXISTS idx_messages_unread ON messages(owner_key, conversation_id, is_read) WHERE is_read = 0;
 CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_dedup
   ON messages(owner_key, conversation_id, conversation_type, sender_key, timestamp);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_message_id
+  ON messages(owner_key, conversation_id, message_id) WHERE message_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_messages_expiry ON messages(expires_at) WHERE expires_at IS NOT NULL;
+
+-- Stamp each new message with its conversation's timer, counted from when
+-- it reached this device. Here rather than in every insert path; a timer
+-- only applies to messages that arrive after it is set.
+CREATE TRIGGER IF NOT EXISTS messages_expiry AFTER INSERT ON messages
+WHEN new.expires_at IS NULL AND new.conversation_type IN ('dm', 'channel') BEGIN
+    UPDATE messages SET expires_at = CAST(strftime('%s', 'now') AS INTEGER) * 1000 + 1000 * (
+        CASE new.conversation_type
+            WHEN 'dm' THEN (SELECT disappearing_secs FROM friends
+                            WHERE owner_key = new.owner_key AND public_key = new.conversation_id)
+            ELSE (SELECT retention_secs FROM channels
+                  WHERE owner_key = new.owner_key AND id = new.conversation_id)
+        END)
+    WHERE id = new.id;
+END;
+
+-- Full-text index over decrypted message bodies. External-content table:
+-- the text lives only in `messages`, the triggers keep the index in step.
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+    body,
+    content='messages',
+    content_rowid='id',
+    tokenize='unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts(rowid, body) VALUES (new.id, new.body);
+END;
+
+CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, body) VALUES ('delete', old.id, old.body);
+END;
+
+CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF body ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, body) VALUES ('delete', old.id, old.body);
+    INSERT INTO messages_fts(rowid, body) VALUES (new.id, new.body);
+END;
+
+-- Bodies an edit replaced, so a message's history is kept.
+CREATE TABLE IF NOT EXISTS message_edits (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_row_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    body TEXT NOT NULL,
+    replaced_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_edits_message ON message_edits(message_row_id);
+
+-- One row per (reactor, emoji) on a message; tallies are grouped from here.
+CREATE TABLE IF NOT EXISTS message_reactions (
+    message_row_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    reactor_key TEXT NOT NULL,
+    emoji TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (message_row_id, reactor_key, emoji)
+);
+
+-- Chunked file transfers. Each shared file is a DHT log of sealed chunks;
+-- `attachment_json` is the `FileAttachment` (with its key) from the message.
+-- Uploads resume from the log's length, downloads from `next_chunk`.
+CREATE TABLE IF NOT EXISTS file_transfers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_key TEXT NOT NULL REFERENCES identity(public_key) ON DELETE CASCADE,
+    message_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    attachment_index INTEGER NOT NULL DEFAULT 0,
+    direction TEXT NOT NULL CHECK(direction IN ('upload', 'download')),
+    peer_key TEXT NOT NULL,
+    local_path TEXT NOT NULL,
+    attachment_json TEXT NOT NULL,
+    dht_owner_keypair TEXT,
+    next_chunk INTEGER NOT NULL DEFAULT 0,
+    state TEXT NOT NULL DEFAULT 'active' CHECK(state IN ('active', 'complete', 'failed')),
+    updated_at INTEGER NOT NULL,
+    UNIQUE(owner_key, message_id, attachment_index)
+);
 
 CREATE TABLE IF NOT EXISTS communities (
     owner_key TEXT NOT NULL REFERENCES identity(public_key) ON DELETE CASCADE,
@@ -74,9 +167,13 @@ CREATE TABLE IF NOT EXISTS communities (
     dht_record_key TEXT,
     dht_owner_keypair TEXT,
     my_pseudonym_key TEXT,
+    -- Owner's pseudonym from the invite link we joined with (ours if we own
+    -- it); member vouches are traced from it before wrapping a MEK
+    owner_pseudonym TEXT,
     mek_generation INTEGER NOT NULL DEFAULT 0,
     server_route_blob BLOB,
     is_hosted INTEGER NOT NULL DEFAULT 0,
+    history_visibility TEXT NOT NULL DEFAULT 'full',
     PRIMARY KEY (owner_key, id)
 );
 
@@ -87,6 +184,8 @@ CREATE TABLE IF NOT EXISTS channels (
     name TEXT NOT NULL,
     channel_type TEXT NOT NULL CHECK(channel_type IN ('text', 'voice')),
     sort_order INTEGER NOT NULL DEFAULT 0,
+    -- Retention set by the community in seconds; NULL keeps messages.
+    retention_secs INTEGER,
     PRIMARY KEY (owner_key, id),
     FOREIGN KEY (owner_key, community_id) REFERENCES communities(owner_key, id) ON DELETE CASCADE
 );
@@ -132,6 +231,37 @@ CREATE INDEX IF NOT EXISTS idx_friends_group_id ON friends(owner_key, group_id);
 CREATE INDEX IF NOT EXISTS idx_channels_community_id ON channels(owner_key, community_id);
 CREATE INDEX IF NOT EXISTS idx_community_members_community ON community_members(owner_key, community_id, pseudonym_key);
 
+-- Group conversations: small sets of friends talking without a community
+-- server. Messages go pairwise over Signal and live in `messages` with
+-- conversation_type 'group'. The DHT record holds the shared roster.
+CREATE TABLE IF NOT EXISTS group_chats (
+    owner_key TEXT NOT NULL REFERENCES identity(public_key) ON DELETE CASCADE,
+    id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    dht_record_key TEXT,
+    dht_owner_keypair TEXT,
+    -- Hex of the key the DHT record is encrypted with.
+    record_secret TEXT,
+    -- When our copy of the roster last changed; a newer roster in the DHT
+    -- record replaces it.
+    roster_updated_at INTEGER NOT NULL,
+    PRIMARY KEY (owner_key, id)
+);
+
+CREATE TABLE IF NOT EXISTS group_chat_members (
+    owner_key TEXT NOT NULL,
+    group_id TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    profile_dht_key TEXT,
+    mailbox_dht_key TEXT,
+    joined_at INTEGER NOT NULL,
+    PRIMARY KEY (owner_key, group_id, public_key),
+    FOREIGN KEY (owner_key, group_id) REFERENCES group_chats(owner_key, id) ON DELETE CASCADE
+);
+
 CREATE TABLE IF NOT EXISTS trusted_identities (
     owner_key TEXT NOT NULL REFERENCES identity(public_key) ON DELETE CASCADE,
     public_key TEXT NOT NULL,
@@ -149,13 +279,18 @@ CREATE TABLE IF NOT EXISTS signal_sessions (
     PRIMARY KEY (owner_key, recipient_key)
 );
 
+-- Signed and one-time prekeys. One-time prekeys are deleted when consumed;
+-- `handed_out_at` marks keys embedded directly in a friend request or invite
+-- (never published to the DHT pool). Signed prekeys are retained for a grace
+-- window after rotation.
 CREATE TABLE IF NOT EXISTS prekeys (
     owner_key TEXT NOT NULL REFERENCES identity(public_key) ON DELETE CASCADE,
     id INTEGER NOT NULL,
     key_data BLOB NOT NULL,
     is_signed INTEGER NOT NULL DEFAULT 0,
     created_at INTEGER NOT NULL,
-    PRIMARY KEY (owner_key, id)
+    handed_out_at INTEGER,
+    PRIMARY KEY (owner_key, is_signed, id)
 );
 
 CREATE TABLE IF NOT EXISTS pending_messages (
@@ -164,7 +299,9 @@ CREATE TABLE IF NOT EXISTS pending_messages (
     recipient_key TEXT NOT NULL,
     body TEXT NOT NULL,
     created_at INTEGER NOT NULL,
-    retry_count INTEGER NOT NULL DEFAULT 0
+    retry_count INTEGER NOT NULL DEFAULT 0,
+    -- Global ID of the DM this envelope carries, so delivery can update it.
+    message_id TEXT
 );
 
 CREATE INDEX IF NOT EXISTS idx_pending_recipient ON pending_messages (owner_key, recipient_key);
@@ -189,3 +326,16 @@ CREATE TABLE IF NOT EXISTS blocked_users (
     blocked_at INTEGER NOT NULL,
     PRIMARY KEY (owner_key, public_key)
 );
+
+-- Linked devices of our own identity and of our friends, from certificates
+-- signed by the identity key.
+CREATE TABLE IF NOT EXISTS devices (
+    owner_key TEXT NOT NULL REFERENCES identity(public_key) ON DELETE CASCADE,
+    identity_key TEXT NOT NULL,
+    device_key TEXT NOT NULL,
+    name TEXT NOT NULL,
+    mailbox_dht_key TEXT NOT NULL,
+    certificate TEXT NOT NULL,
+    added_at INTEGER NOT NULL,
+    PRIMARY KEY (owner_key, identity_key, device_key)
+);
@@ -10,6 +10,42 @@ pub enum ChatEvent {
         body: String,
         timestamp: u64,
         conversation_id: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attachments: Vec<crate::commands::chat::AttachmentInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message_id: Option<String>,
+        /// Global ID of the message this one replies to.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reply_to: Option<String>,
+    },
+    /// A message's body was replaced by its author.
+    #[serde(rename_all = "camelCase")]
+    MessageEdited {
+        conversation_id: String,
+        message_id: String,
+        body: String,
+        edited_at: u64,
+    },
+    /// A message was deleted for everyone.
+    #[serde(rename_all = "camelCase")]
+    MessageDeleted {
+        conversation_id: String,
+        message_id: String,
+    },
+    /// The reactions on a message changed; `reactions` is the new tally.
+    #[serde(rename_all = "camelCase")]
+    ReactionsChanged {
+        conversation_id: String,
+        message_id: String,
+        reactions: Vec<crate::commands::chat::ReactionInfo>,
+    },
+    /// Some of our DMs to a peer reached a new delivery state
+    /// (`queued`, `sent`, `delivered`, `read` or `failed`).
+    #[serde(rename_all = "camelCase")]
+    DeliveryStateChanged {
+        conversation_id: String,
+        message_ids: Vec<String>,
+        state: String,
     },
     TypingIndicator {
         from: String,
@@ -50,10 +86,68 @@ pub enum ChatEvent {
     FriendRequestDelivered {
         to: String,
     },
+    /// Emitted when a contact's identity key differs from the one we recorded.
+    ///
+    /// `verified` contacts are blocked until re-verified; unverified contacts
+    /// have the new key accepted and their safety number changes.
+    #[serde(rename_all = "camelCase")]
+    IdentityKeyChanged {
+        public_key: String,
+        verified: bool,
+    },
+    /// Emitted as a file upload or download moves along, and when it ends.
+    #[serde(rename_all = "camelCase")]
+    TransferProgress {
+        conversation_id: String,
+        attachment: crate::commands::chat::AttachmentInfo,
+    },
     /// Emitted when background server fetch completes with channel history.
     #[serde(rename_all = "camelCase")]
     ChannelHistoryLoaded {
         channel_id: String,
         messages: Vec<crate::commands::chat::Message>,
     },
+    /// A member posted in one of our group conversations.
+    #[serde(rename_all = "camelCase")]
+    GroupMessageReceived {
+        group_id: String,
+        from: String,
+        body: String,
+        timestamp: u64,
+        message_id: String,
+    },
+    /// We joined a group conversation or its roster changed.
+    #[serde(rename_all = "camelCase")]
+    GroupUpdated {
+        group: crate::state::GroupChatState,
+    },
+    /// We left a group conversation.
+    #[serde(rename_all = "camelCase")]
+    GroupRemoved {
+        group_id: String,
+    },
+    /// Someone joined or left a group conversation; `by` added them, or is
+    /// the member who left.
+    #[serde(rename_all = "camelCase")]
+    GroupMembershipChanged {
+        group_id: String,
+        public_key: String,
+        display_name: String,
+        joined: bool,
+        by: String,
+    },
+    /// The disappearing-message timer of the DM with `peer_id` changed;
+    /// `by` is whoever set it (us or the peer).
+    #[serde(rename_all = "camelCase")]
+    DisappearingTimerChanged {
+        peer_id: String,
+        seconds: u32,
+        by: String,
+    },
+    /// Messages outlived their conversation's timer and were deleted.
+    #[serde(rename_all = "camelCase")]
+    MessagesExpired {
+        conversation_id: String,
+        message_ids: Vec<String>,
+    },
 }
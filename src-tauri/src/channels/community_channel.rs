@@ -52,6 +52,12 @@ pub enum CommunityEvent {
         community_id: String,
         channel_id: String,
     },
+    /// A thread was started, archived or reopened, or its members changed.
+    #[serde(rename_all = "camelCase")]
+    ThreadUpdated {
+        community_id: String,
+        thread: rekindle_protocol::messaging::ThreadDto,
+    },
 }
 
 /// Role DTO for frontend consumption (mirrors protocol's `RoleDto`).
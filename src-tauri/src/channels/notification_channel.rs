@@ -6,6 +6,19 @@ use serde::Serialize;
 pub enum NotificationEvent {
     SystemAlert { title: String, body: String },
     UpdateAvailable { version: String },
+    /// Our link request was approved; the device can now finish linking.
+    #[serde(rename_all = "camelCase")]
+    DeviceLinkGranted { public_key: String, display_name: String },
+    /// Login found our account record on the network and restored friends,
+    /// groups and communities from it; `failed` lists what it could not.
+    #[serde(rename_all = "camelCase")]
+    SocialGraphRestored {
+        friends: u32,
+        groups: u32,
+        communities: u32,
+        sessions_pending: u32,
+        failed: Vec<String>,
+    },
 }
 
 /// Pushed to the frontend whenever network-relevant state changes
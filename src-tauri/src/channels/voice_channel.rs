@@ -23,8 +23,28 @@ pub enum VoiceEvent {
         public_key: String,
         muted: bool,
     },
+    /// Our own link, from echoed probes: how the people (or relay) receiving
+    /// our frames see them, and the bitrate the encoder settled on.
+    #[serde(rename_all = "camelCase")]
     ConnectionQuality {
         quality: String,
+        /// Connection bars, 0–4.
+        bars: u8,
+        rtt_ms: Option<u32>,
+        loss_pct: f32,
+        bitrate: i32,
+    },
+    /// How one participant's stream is arriving.
+    #[serde(rename_all = "camelCase")]
+    ParticipantStats {
+        public_key: String,
+        loss_pct: f32,
+        jitter_ms: f32,
+        late_drops: u64,
+        /// Audio currently buffered for them.
+        buffer_ms: u32,
+        /// Connection bars, 0–4.
+        bars: u8,
     },
     #[serde(rename_all = "camelCase")]
     DeviceChanged {
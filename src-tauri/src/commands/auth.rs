@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
-use rekindle_crypto::keychain::{KEY_ED25519_PRIVATE, VAULT_IDENTITY};
+use rekindle_crypto::keychain::{
+    KEY_DATABASE, KEY_DATABASE_NEXT, KEY_DEVICE_PRIVATE, KEY_ED25519_PRIVATE, VAULT_IDENTITY,
+};
 use rekindle_crypto::Keychain as _;
+use rekindle_protocol::messaging::HistoryVisibility;
 use rusqlite::OptionalExtension as _;
 use serde::{Deserialize, Serialize};
 use tauri::{Manager as _, State};
@@ -11,7 +14,7 @@ use crate::db::{self, DbPool};
 use crate::keystore::{KeystoreHandle, StrongholdKeystore};
 use crate::services;
 use crate::state::{
-    ChannelInfo, ChannelType, CommunityState, FriendState, IdentityState,
+    ChannelInfo, ChannelType, CommunityState, FriendState, IdentityState, LocalDevice,
     SharedState, SignalManagerHandle, UserStatus,
 };
 
@@ -52,7 +55,7 @@ pub fn current_owner_key(state: &SharedState) -> Result<String, String> {
 /// whether to spawn background services.
 ///
 /// Multiple identities can coexist — each gets its own Stronghold file
-/// and `owner_key`-scoped rows. Only one is active at a time.
+/// and encrypted database. Only one is active at a time.
 pub async fn create_identity_core(
     config_dir: &std::path::Path,
     passphrase: &str,
@@ -60,17 +63,33 @@ pub async fn create_identity_core(
     state: &SharedState,
     pool: &DbPool,
     keystore_handle: &KeystoreHandle,
+) -> Result<(LoginResult, [u8; 32]), String> {
+    let identity = rekindle_crypto::Identity::generate();
+    store_new_identity(config_dir, passphrase, display_name, &identity, state, pool, keystore_handle)
+        .await
+}
+
+/// Store `identity` in a new Stronghold and database and make it the active
+/// identity. Shared by identity creation and recovery-phrase restore.
+async fn store_new_identity(
+    config_dir: &std::path::Path,
+    passphrase: &str,
+    display_name: Option<String>,
+    identity: &rekindle_crypto::Identity,
+    state: &SharedState,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
 ) -> Result<(LoginResult, [u8; 32]), String> {
     // Clear in-memory state from any previous session
     *state.identity.write() = None;
     state.friends.write().clear();
     state.communities.write().clear();
+    state.group_chats.write().clear();
 
     // Ensure config directory exists for Stronghold snapshot
     std::fs::create_dir_all(config_dir)
         .map_err(|e| format!("failed to create config dir: {e}"))?;
 
-    let identity = rekindle_crypto::Identity::generate();
     let public_key = identity.public_key_hex();
     let secret_bytes = *identity.secret_key_bytes();
     let display_name = display_name
@@ -85,23 +104,28 @@ pub async fn create_identity_core(
     keystore
         .store_key(VAULT_IDENTITY, KEY_ED25519_PRIVATE, &secret_bytes)
         .map_err(|e| e.to_string())?;
-    keystore.save().map_err(|e| e.to_string())?;
+    // Generates the database key and saves the snapshot
+    let db_key = keystore.database_key().map_err(|e| e.to_string())?;
 
     // Keep the keystore unlocked for the session
     *keystore_handle.lock() = Some(keystore);
 
-    // Persist identity to SQLite (alongside any existing identities)
+    // List the identity in the directory (alongside any existing identities);
+    // opening its database moves the row into it.
     let db = pool.clone();
+    let dir = config_dir.to_path_buf();
     let pk = public_key.clone();
     let dn = display_name.clone();
     tokio::task::spawn_blocking(move || {
-        let conn = db.lock().map_err(|e| e.to_string())?;
-        conn.execute(
-            "INSERT INTO identity (public_key, display_name, created_at) VALUES (?, ?, ?)",
-            rusqlite::params![pk, dn, now],
-        )
-        .map_err(|e| e.to_string())?;
-        Ok::<(), String>(())
+        db.with_directory(|conn| {
+            conn.execute(
+                "INSERT INTO identity (public_key, display_name, created_at) VALUES (?, ?, ?)",
+                rusqlite::params![pk, dn, now],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(())
+        })?;
+        db.open_identity(&dir, &pk, &db_key)
     })
     .await
     .map_err(|e| e.to_string())??;
@@ -168,8 +192,9 @@ pub async fn create_identity(
 
 /// Core login logic, separated from `AppHandle` for testability.
 ///
-/// Loads identity from `SQLite` by `public_key`, unlocks its per-identity
-/// Stronghold, verifies keypair, restores friends + communities.
+/// Looks up the identity in the directory, unlocks its per-identity
+/// Stronghold, verifies keypair, opens its encrypted database and restores
+/// friends + communities.
 /// Returns `(LoginResult, secret_key, dht_keys)`.
 /// Columns loaded from the identity table during login.
 #[derive(Debug)]
@@ -181,6 +206,9 @@ pub struct IdentityDhtColumns {
     pub account_dht_key: Option<String>,
     pub account_owner_keypair: Option<String>,
     pub mailbox_dht_key: Option<String>,
+    /// Set when this machine is a linked device of the identity.
+    pub device_key: Option<String>,
+    pub device_mailbox_dht_key: Option<String>,
 }
 
 pub async fn login_core(
@@ -195,8 +223,33 @@ pub async fn login_core(
     *state.identity.write() = None;
     state.friends.write().clear();
     state.communities.write().clear();
+    state.group_chats.write().clear();
+    state.devices.write().clear();
+    *state.local_device.write() = None;
+
+    // Make sure the identity exists before touching Stronghold
+    let db = pool.clone();
+    let pk_query = public_key.to_string();
+    tokio::task::spawn_blocking(move || {
+        db.with_directory(|conn| {
+            conn.query_row(
+                "SELECT 1 FROM identity WHERE public_key = ?1",
+                rusqlite::params![pk_query],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "no identity found — please create one first".to_string())
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let keystore = unlock_identity_keystore(config_dir, public_key, passphrase)?;
+    let key_array = load_identity_secret(&keystore, public_key)?;
+    let keystore = open_identity_database(config_dir, public_key, keystore, pool).await?;
 
-    // Load identity metadata from SQLite by public key
+    // Load identity metadata from its database
     let db = pool.clone();
     let pk_query = public_key.to_string();
     let (display_name, dht_cols) =
@@ -206,7 +259,8 @@ pub async fn login_core(
                 .prepare(
                     "SELECT display_name, dht_record_key, friend_list_dht_key, \
                      dht_owner_keypair, friend_list_owner_keypair, \
-                     account_dht_key, account_owner_keypair, mailbox_dht_key \
+                     account_dht_key, account_owner_keypair, mailbox_dht_key, \
+                     device_key, device_mailbox_dht_key \
                      FROM identity WHERE public_key = ?1",
                 )
                 .map_err(|e| e.to_string())?;
@@ -222,6 +276,8 @@ pub async fn login_core(
                             account_dht_key: row.get::<_, Option<String>>("account_dht_key")?,
                             account_owner_keypair: row.get::<_, Option<String>>("account_owner_keypair")?,
                             mailbox_dht_key: row.get::<_, Option<String>>("mailbox_dht_key")?,
+                            device_key: row.get::<_, Option<String>>("device_key")?,
+                            device_mailbox_dht_key: row.get::<_, Option<String>>("device_mailbox_dht_key")?,
                         },
                     ))
                 })
@@ -233,42 +289,18 @@ pub async fn login_core(
         .await
         .map_err(|e| e.to_string())??;
 
-    // Unlock per-identity Stronghold with passphrase and load private key
-    let keystore =
-        StrongholdKeystore::initialize_for_identity(config_dir, public_key, passphrase)
-            .map_err(|e| {
-                let msg = e.to_string();
-                tracing::warn!(
-                    public_key = %public_key,
-                    error = %msg,
-                    "Stronghold unlock failed"
-                );
-                if msg.contains("snapshot") || msg.contains("decrypt") {
-                    "Wrong passphrase — unable to unlock keystore".to_string()
-                } else {
-                    msg
-                }
-            })?;
-
-    let secret_bytes = keystore
-        .load_key(VAULT_IDENTITY, KEY_ED25519_PRIVATE)
-        .map_err(|e| e.to_string())?;
-
-    // Verify the private key exists and matches the stored public key
-    let secret = secret_bytes.ok_or_else(|| {
-        "No private key found in keystore — identity may be corrupted. \
-         Please create a new identity."
-            .to_string()
-    })?;
-    let key_array: [u8; 32] = secret
-        .as_slice()
-        .try_into()
-        .map_err(|_| "invalid secret key length in Stronghold")?;
-    let restored = rekindle_crypto::Identity::from_secret_bytes(&key_array);
-    let restored_pub = restored.public_key_hex();
-    if restored_pub != public_key {
-        return Err(
-            "Wrong passphrase — decrypted key does not match stored identity".to_string(),
+    // A linked device also holds the key of its own mailbox
+    if let (Some(device_key), Some(mailbox_dht_key)) =
+        (dht_cols.device_key.clone(), dht_cols.device_mailbox_dht_key.clone())
+    {
+        let secret: [u8; 32] = keystore
+            .load_key(VAULT_IDENTITY, KEY_DEVICE_PRIVATE)
+            .map_err(|e| e.to_string())?
+            .and_then(|s| <[u8; 32]>::try_from(s.as_slice()).ok())
+            .ok_or("device key missing from keystore")?;
+        services::device_service::set_local_device(
+            state,
+            LocalDevice { device_key, secret, mailbox_dht_key },
         );
     }
 
@@ -286,6 +318,8 @@ pub async fn login_core(
     // Restore friends and communities from SQLite into AppState (scoped to this identity)
     load_friends_from_db(pool, state, public_key).await?;
     load_communities_from_db(pool, state, public_key).await?;
+    services::device_service::load_devices(pool, state, public_key).await?;
+    services::group_service::load_groups(pool, state, public_key).await?;
 
     // Derive pseudonyms for each community and load MEKs from Stronghold
     restore_community_pseudonyms_and_meks(state, keystore_handle, &key_array);
@@ -297,6 +331,123 @@ pub async fn login_core(
     Ok((result, key_array, dht_cols))
 }
 
+/// Whether `public_key` is already listed in the identity directory.
+async fn identity_exists(pool: &DbPool, public_key: &str) -> Result<bool, String> {
+    let db = pool.clone();
+    let pk_query = public_key.to_string();
+    tokio::task::spawn_blocking(move || {
+        db.with_directory(|conn| {
+            conn.query_row(
+                "SELECT 1 FROM identity WHERE public_key = ?1",
+                rusqlite::params![pk_query],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(|e| e.to_string())
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Unlock the Stronghold snapshot of `public_key` with `passphrase`.
+fn unlock_identity_keystore(
+    config_dir: &std::path::Path,
+    public_key: &str,
+    passphrase: &str,
+) -> Result<StrongholdKeystore, String> {
+    StrongholdKeystore::initialize_for_identity(config_dir, public_key, passphrase).map_err(|e| {
+        let msg = e.to_string();
+        tracing::warn!(
+            public_key = %public_key,
+            error = %msg,
+            "Stronghold unlock failed"
+        );
+        if msg.contains("snapshot") || msg.contains("decrypt") {
+            "Wrong passphrase — unable to unlock keystore".to_string()
+        } else {
+            msg
+        }
+    })
+}
+
+/// Load the private key from an unlocked keystore and check it belongs to
+/// `public_key`.
+fn load_identity_secret(
+    keystore: &StrongholdKeystore,
+    public_key: &str,
+) -> Result<[u8; 32], String> {
+    let secret_bytes = keystore
+        .load_key(VAULT_IDENTITY, KEY_ED25519_PRIVATE)
+        .map_err(|e| e.to_string())?;
+
+    // Verify the private key exists and matches the stored public key
+    let secret = secret_bytes.ok_or_else(|| {
+        "No private key found in keystore — identity may be corrupted. \
+         Please create a new identity."
+            .to_string()
+    })?;
+    let key_array: [u8; 32] = secret
+        .as_slice()
+        .try_into()
+        .map_err(|_| "invalid secret key length in Stronghold")?;
+    let restored = rekindle_crypto::Identity::from_secret_bytes(&key_array);
+    let restored_pub = restored.public_key_hex();
+    if restored_pub != public_key {
+        return Err(
+            "Wrong passphrase — decrypted key does not match stored identity".to_string(),
+        );
+    }
+    Ok(key_array)
+}
+
+/// Open the encrypted database of `public_key` with the key held in its
+/// keystore, migrating plaintext rows on first use.
+///
+/// If a passphrase change was interrupted after the database was re-keyed
+/// but before the keystore caught up, the pending key opens it and is
+/// promoted here.
+async fn open_identity_database(
+    config_dir: &std::path::Path,
+    public_key: &str,
+    keystore: StrongholdKeystore,
+    pool: &DbPool,
+) -> Result<StrongholdKeystore, String> {
+    let key = keystore.database_key().map_err(|e| e.to_string())?;
+    let next = keystore
+        .load_database_key(KEY_DATABASE_NEXT)
+        .map_err(|e| e.to_string())?;
+
+    let db = pool.clone();
+    let dir = config_dir.to_path_buf();
+    let pk = public_key.to_string();
+    let opened_next = next.clone();
+    let used_next = tokio::task::spawn_blocking(move || match db.open_identity(&dir, &pk, &key) {
+        Ok(()) => Ok(false),
+        Err(e) => match opened_next {
+            Some(next) => db.open_identity(&dir, &pk, &next).map(|()| true),
+            None => Err(e),
+        },
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if let Some(next) = next {
+        if used_next {
+            tracing::info!(public_key = %public_key, "finishing interrupted database re-key");
+            keystore
+                .store_key(VAULT_IDENTITY, KEY_DATABASE, next.as_slice())
+                .map_err(|e| e.to_string())?;
+        }
+        keystore
+            .delete_key(VAULT_IDENTITY, KEY_DATABASE_NEXT)
+            .map_err(|e| e.to_string())?;
+        keystore.save().map_err(|e| e.to_string())?;
+    }
+    Ok(keystore)
+}
+
 /// Unlock existing identity with passphrase.
 ///
 /// Opens the per-identity Stronghold snapshot with the passphrase, loads the
@@ -361,16 +512,507 @@ pub async fn login(
         .await
         .unwrap_or(false);
 
-        if ready {
-            if let Err(e) =
-                services::sync_service::sync_friends_now(state.inner(), &app).await
-            {
-                tracing::warn!(error = %e, "login-time friend sync failed");
-            }
-        } else {
-            tracing::warn!("network not ready within 20s — buddy list will use fallback sync");
-        }
-    }
+        if ready {
+            if let Err(e) =
+                services::sync_service::sync_friends_now(state.inner(), &app).await
+            {
+                tracing::warn!(error = %e, "login-time friend sync failed");
+            }
+        } else {
+            tracing::warn!("network not ready within 20s — buddy list will use fallback sync");
+        }
+    }
+
+    Ok(result)
+}
+
+/// Core of [`complete_device_link`], separated from `AppHandle` for testability.
+///
+/// Turns an approved link request into a local copy of the identity: the
+/// granted secret and this device's key go into a new Stronghold, the account
+/// pointers, friends and sibling devices into a new database.
+/// Returns `(LoginResult, secret_key, dht_keys)` like [`login_core`].
+pub async fn link_device_core(
+    config_dir: &std::path::Path,
+    passphrase: &str,
+    state: &SharedState,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+) -> Result<(LoginResult, [u8; 32], IdentityDhtColumns), String> {
+    let (device_secret, device_mailbox_dht_key, account) = {
+        let pending = state.pending_link.lock();
+        let link = pending.as_ref().ok_or("no device link in progress")?;
+        let grant = link
+            .grant
+            .clone()
+            .ok_or("the link request has not been approved yet")?;
+        (link.secret, link.mailbox_dht_key.clone(), grant)
+    };
+    let secret_bytes: [u8; 32] = account
+        .identity_secret
+        .as_slice()
+        .try_into()
+        .map_err(|_| "invalid identity key in link grant")?;
+    let public_key = rekindle_crypto::Identity::from_secret_bytes(&secret_bytes).public_key_hex();
+    let device_key = rekindle_crypto::Identity::from_secret_bytes(&device_secret).public_key_hex();
+
+    if identity_exists(pool, &public_key).await? {
+        return Err("This identity is already on this device — log in instead".to_string());
+    }
+
+    // Clear in-memory state from any previous session
+    *state.identity.write() = None;
+    state.friends.write().clear();
+    state.communities.write().clear();
+    state.group_chats.write().clear();
+    state.devices.write().clear();
+
+    std::fs::create_dir_all(config_dir)
+        .map_err(|e| format!("failed to create config dir: {e}"))?;
+
+    let keystore =
+        StrongholdKeystore::initialize_for_identity(config_dir, &public_key, passphrase)
+            .map_err(|e| e.to_string())?;
+    keystore
+        .store_key(VAULT_IDENTITY, KEY_ED25519_PRIVATE, &secret_bytes)
+        .map_err(|e| e.to_string())?;
+    keystore
+        .store_key(VAULT_IDENTITY, KEY_DEVICE_PRIVATE, &device_secret)
+        .map_err(|e| e.to_string())?;
+    // Generates the database key and saves the snapshot
+    let db_key = keystore.database_key().map_err(|e| e.to_string())?;
+    *keystore_handle.lock() = Some(keystore);
+
+    let dht_cols = IdentityDhtColumns {
+        existing_dht_key: account.profile_dht_key.clone(),
+        existing_friend_list_key: account.friend_list_dht_key.clone(),
+        dht_owner_keypair: None,
+        friend_list_owner_keypair: None,
+        account_dht_key: account.account_dht_key.clone(),
+        account_owner_keypair: account.account_owner_keypair.clone(),
+        mailbox_dht_key: account.mailbox_dht_key.clone(),
+        device_key: Some(device_key.clone()),
+        device_mailbox_dht_key: Some(device_mailbox_dht_key.clone()),
+    };
+
+    let now = db::timestamp_now();
+    let db = pool.clone();
+    let dir = config_dir.to_path_buf();
+    let pk = public_key.clone();
+    let grant = account.clone();
+    let dk = device_key.clone();
+    let dmk = device_mailbox_dht_key.clone();
+    tokio::task::spawn_blocking(move || {
+        db.with_directory(|conn| {
+            conn.execute(
+                "INSERT INTO identity (public_key, display_name, created_at) VALUES (?, ?, ?)",
+                rusqlite::params![pk, grant.display_name, now],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(())
+        })?;
+        db.open_identity(&dir, &pk, &db_key)?;
+
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE identity SET dht_record_key = ?2, friend_list_dht_key = ?3, account_dht_key = ?4, \
+             account_owner_keypair = ?5, mailbox_dht_key = ?6, device_key = ?7, \
+             device_mailbox_dht_key = ?8 WHERE public_key = ?1",
+            rusqlite::params![
+                pk, grant.profile_dht_key, grant.friend_list_dht_key, grant.account_dht_key,
+                grant.account_owner_keypair, grant.mailbox_dht_key, dk, dmk,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        for contact in &grant.contacts {
+            conn.execute(
+                "INSERT OR IGNORE INTO friends (owner_key, public_key, display_name, nickname, added_at, \
+                 dht_record_key, mailbox_dht_key, friendship_state) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'accepted')",
+                rusqlite::params![
+                    pk, contact.public_key, contact.display_name, contact.nickname, now,
+                    contact.profile_dht_key, contact.mailbox_dht_key,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        for cert in &grant.devices {
+            if cert.identity_key != pk
+                || rekindle_protocol::messaging::envelope::verify_device_certificate(cert).is_err()
+            {
+                continue;
+            }
+            let json = serde_json::to_string(cert).map_err(|e| e.to_string())?;
+            conn.execute(
+                "INSERT OR REPLACE INTO devices (owner_key, identity_key, device_key, name, \
+                 mailbox_dht_key, certificate, added_at) VALUES (?1, ?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![
+                    pk, cert.device_key, cert.device_name, cert.mailbox_dht_key, json,
+                    i64::try_from(cert.created_at).unwrap_or(now),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    services::device_service::set_local_device(
+        state,
+        LocalDevice {
+            device_key,
+            secret: device_secret,
+            mailbox_dht_key: device_mailbox_dht_key,
+        },
+    );
+    *state.identity.write() = Some(IdentityState {
+        public_key: public_key.clone(),
+        display_name: account.display_name.clone(),
+        status: UserStatus::Online,
+        status_message: String::new(),
+    });
+    load_friends_from_db(pool, state, &public_key).await?;
+    services::device_service::load_devices(pool, state, &public_key).await?;
+    *state.pending_link.lock() = None;
+
+    tracing::info!(public_key = %public_key, "linked this device to an existing identity");
+    let result = LoginResult {
+        public_key,
+        display_name: account.display_name.clone(),
+    };
+    Ok((result, secret_bytes, dht_cols))
+}
+
+/// Finish linking this device once the other device approved the request,
+/// protecting the local copy with `passphrase`, and log in.
+#[tauri::command]
+pub async fn complete_device_link(
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+    keystore_handle: State<'_, KeystoreHandle>,
+) -> Result<LoginResult, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?;
+
+    let (result, secret_key, dht_cols) = link_device_core(
+        &config_dir,
+        &passphrase,
+        state.inner(),
+        pool.inner(),
+        keystore_handle.inner(),
+    )
+    .await?;
+
+    start_background_services(
+        &app,
+        state.inner(),
+        pool.inner(),
+        &secret_key,
+        DhtKeysConfig {
+            existing_dht_key: dht_cols.existing_dht_key,
+            existing_friend_list_key: dht_cols.existing_friend_list_key,
+            dht_owner_keypair: None,
+            friend_list_owner_keypair: None,
+            account_dht_key: dht_cols.account_dht_key,
+            account_owner_keypair: dht_cols.account_owner_keypair,
+            mailbox_dht_key: dht_cols.mailbox_dht_key,
+        },
+    );
+
+    Ok(result)
+}
+
+/// Core of [`export_identity_backup`]: the active identity as an archive
+/// sealed under `backup_passphrase`.
+pub async fn export_backup_core(
+    state: &SharedState,
+    pool: &DbPool,
+    backup_passphrase: &str,
+    include_history: bool,
+) -> Result<Vec<u8>, String> {
+    let owner_key = current_owner_key(state)?;
+    if backup_passphrase.is_empty() {
+        return Err("backup passphrase must not be empty".to_string());
+    }
+    // A linked device doesn't hold the owner keypairs of the profile and
+    // friend list, so a restore from it would have to start those over
+    if services::device_service::is_linked_device(state) {
+        return Err("Export backups from your primary device".to_string());
+    }
+    let secret = {
+        let sk = state.identity_secret.lock();
+        *sk.as_ref().ok_or("identity secret not available")?
+    };
+
+    let db = pool.clone();
+    let backup = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        services::backup_service::collect(&conn, &owner_key, &secret, include_history)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    backup.seal(backup_passphrase)
+}
+
+/// Core of [`import_identity_backup`], separated from `AppHandle` for testability.
+///
+/// Opens the archive with `backup_passphrase`, stores the identity under a
+/// new local `passphrase` and writes the backed-up rows into its database.
+/// Returns `(LoginResult, secret_key, dht_keys)` like [`login_core`].
+pub async fn import_backup_core(
+    config_dir: &std::path::Path,
+    archive: &[u8],
+    backup_passphrase: &str,
+    passphrase: &str,
+    state: &SharedState,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+) -> Result<(LoginResult, [u8; 32], IdentityDhtColumns), String> {
+    let backup = services::backup_service::IdentityBackup::open(archive, backup_passphrase)?;
+    let secret_bytes = backup.secret()?;
+    let public_key = rekindle_crypto::Identity::from_secret_bytes(&secret_bytes).public_key_hex();
+    if identity_exists(pool, &public_key).await? {
+        return Err("This identity is already on this device — log in instead".to_string());
+    }
+
+    // Clear in-memory state from any previous session
+    *state.identity.write() = None;
+    state.friends.write().clear();
+    state.communities.write().clear();
+    state.group_chats.write().clear();
+    state.devices.write().clear();
+    *state.local_device.write() = None;
+
+    std::fs::create_dir_all(config_dir)
+        .map_err(|e| format!("failed to create config dir: {e}"))?;
+
+    let keystore =
+        StrongholdKeystore::initialize_for_identity(config_dir, &public_key, passphrase)
+            .map_err(|e| e.to_string())?;
+    keystore
+        .store_key(VAULT_IDENTITY, KEY_ED25519_PRIVATE, &secret_bytes)
+        .map_err(|e| e.to_string())?;
+    // Generates the database key and saves the snapshot
+    let db_key = keystore.database_key().map_err(|e| e.to_string())?;
+    *keystore_handle.lock() = Some(keystore);
+
+    let dht_cols = IdentityDhtColumns {
+        existing_dht_key: backup.profile_dht_key.clone(),
+        existing_friend_list_key: backup.friend_list_dht_key.clone(),
+        dht_owner_keypair: backup.profile_owner_keypair.clone(),
+        friend_list_owner_keypair: backup.friend_list_owner_keypair.clone(),
+        account_dht_key: backup.account_dht_key.clone(),
+        account_owner_keypair: backup.account_owner_keypair.clone(),
+        mailbox_dht_key: backup.mailbox_dht_key.clone(),
+        device_key: None,
+        device_mailbox_dht_key: None,
+    };
+    let display_name = backup.display_name.clone();
+
+    let db = pool.clone();
+    let dir = config_dir.to_path_buf();
+    let pk = public_key.clone();
+    tokio::task::spawn_blocking(move || {
+        db.with_directory(|conn| {
+            conn.execute(
+                "INSERT INTO identity (public_key, display_name, created_at) VALUES (?, ?, ?)",
+                rusqlite::params![pk, backup.display_name, backup.created_at],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(())
+        })?;
+        db.open_identity(&dir, &pk, &db_key)?;
+        {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            services::backup_service::restore(&conn, &pk, &backup)?;
+        }
+        // The directory row carries the avatar for the account picker
+        db::sync_directory_entry(&db, &pk)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    *state.identity.write() = Some(IdentityState {
+        public_key: public_key.clone(),
+        display_name: display_name.clone(),
+        status: UserStatus::Online,
+        status_message: String::new(),
+    });
+    load_friends_from_db(pool, state, &public_key).await?;
+
+    tracing::info!(public_key = %public_key, "restored identity from backup");
+    let result = LoginResult {
+        public_key,
+        display_name,
+    };
+    Ok((result, secret_bytes, dht_cols))
+}
+
+/// Core of [`restore_from_recovery_phrase`], separated from `AppHandle` for
+/// testability.
+///
+/// Only the identity key comes back: friends and history need a backup
+/// archive, and the DHT records are published afresh.
+pub async fn restore_from_phrase_core(
+    config_dir: &std::path::Path,
+    phrase: &str,
+    passphrase: &str,
+    display_name: Option<String>,
+    state: &SharedState,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+) -> Result<(LoginResult, [u8; 32]), String> {
+    let identity =
+        rekindle_crypto::Identity::from_recovery_phrase(phrase).map_err(|e| e.to_string())?;
+    if identity_exists(pool, &identity.public_key_hex()).await? {
+        return Err("This identity is already on this device — log in instead".to_string());
+    }
+    store_new_identity(config_dir, passphrase, display_name, &identity, state, pool, keystore_handle)
+        .await
+}
+
+/// Export the active identity as an encrypted backup in the downloads folder.
+///
+/// `passphrase` must be the identity's current passphrase; the archive itself
+/// is protected by `backup_passphrase`. Returns the path written.
+#[tauri::command]
+pub async fn export_identity_backup(
+    passphrase: String,
+    backup_passphrase: String,
+    include_history: bool,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<String, String> {
+    let public_key = current_owner_key(state.inner())?;
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?;
+    unlock_identity_keystore(&config_dir, &public_key, &passphrase)?;
+
+    let archive =
+        export_backup_core(state.inner(), pool.inner(), &backup_passphrase, include_history)
+            .await?;
+
+    let downloads = app.path().download_dir().map_err(|e| e.to_string())?;
+    let path = downloads.join(format!(
+        "rekindle-{}-{}.rkbackup",
+        &public_key[..8],
+        db::timestamp_now()
+    ));
+    std::fs::write(&path, archive).map_err(|e| format!("failed to write backup: {e}"))?;
+    tracing::info!(path = %path.display(), include_history, "identity backup exported");
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Restore an identity from a backup archive, protect it with `passphrase`
+/// and log in. Routes and records are republished like on a normal login.
+#[tauri::command]
+pub async fn import_identity_backup(
+    data: Vec<u8>,
+    backup_passphrase: String,
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+    keystore_handle: State<'_, KeystoreHandle>,
+) -> Result<LoginResult, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?;
+
+    let (result, secret_key, dht_cols) = import_backup_core(
+        &config_dir,
+        &data,
+        &backup_passphrase,
+        &passphrase,
+        state.inner(),
+        pool.inner(),
+        keystore_handle.inner(),
+    )
+    .await?;
+
+    start_background_services(
+        &app,
+        state.inner(),
+        pool.inner(),
+        &secret_key,
+        DhtKeysConfig {
+            existing_dht_key: dht_cols.existing_dht_key,
+            existing_friend_list_key: dht_cols.existing_friend_list_key,
+            dht_owner_keypair: dht_cols.dht_owner_keypair,
+            friend_list_owner_keypair: dht_cols.friend_list_owner_keypair,
+            account_dht_key: dht_cols.account_dht_key,
+            account_owner_keypair: dht_cols.account_owner_keypair,
+            mailbox_dht_key: dht_cols.mailbox_dht_key,
+        },
+    );
+
+    Ok(result)
+}
+
+/// The 24-word recovery phrase of the active identity, after checking
+/// `passphrase`.
+#[tauri::command]
+pub async fn get_recovery_phrase(
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+) -> Result<String, String> {
+    let public_key = current_owner_key(state.inner())?;
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?;
+    let keystore = unlock_identity_keystore(&config_dir, &public_key, &passphrase)?;
+    let secret = zeroize::Zeroizing::new(load_identity_secret(&keystore, &public_key)?);
+    let phrase = rekindle_crypto::Identity::from_secret_bytes(&secret).recovery_phrase();
+    Ok(phrase.as_str().to_string())
+}
+
+/// Recreate an identity from its recovery phrase and log in.
+#[tauri::command]
+pub async fn restore_from_recovery_phrase(
+    phrase: String,
+    passphrase: String,
+    display_name: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+    keystore_handle: State<'_, KeystoreHandle>,
+) -> Result<LoginResult, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?;
+
+    let (result, secret_bytes) = restore_from_phrase_core(
+        &config_dir,
+        &phrase,
+        &passphrase,
+        display_name,
+        state.inner(),
+        pool.inner(),
+        keystore_handle.inner(),
+    )
+    .await?;
+
+    start_background_services(&app, state.inner(), pool.inner(), &secret_bytes, DhtKeysConfig {
+        existing_dht_key: None,
+        existing_friend_list_key: None,
+        dht_owner_keypair: None,
+        friend_list_owner_keypair: None,
+        account_dht_key: None,
+        account_owner_keypair: None,
+        mailbox_dht_key: None,
+    });
 
     Ok(result)
 }
@@ -390,11 +1032,13 @@ pub async fn get_identity(
     }))
 }
 
-/// Log out: save and lock Stronghold, clean up user state, keep node alive.
+/// Log out: lock Stronghold and the identity database, clean up user state,
+/// keep node alive.
 #[tauri::command]
 pub async fn logout(
     app: tauri::AppHandle,
     state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
     keystore_handle: State<'_, KeystoreHandle>,
 ) -> Result<(), String> {
     // Drop the Stronghold keystore handle — no save needed since private keys
@@ -477,6 +1121,14 @@ pub async fn logout(
         }
     }
 
+    // Shut down prekey maintenance loop
+    {
+        let tx = state.prekey_shutdown_tx.write().take();
+        if let Some(tx) = tx {
+            let _ = tx.send(()).await;
+        }
+    }
+
     // Publish Offline to DHT BEFORE logout_cleanup clears the owner keypair
     {
         let current_status = state.identity.read().as_ref().map(|id| id.status);
@@ -503,6 +1155,9 @@ pub async fn logout(
     // but keep the Veilid node alive for re-login
     services::veilid_service::logout_cleanup(Some(&app), &state).await;
 
+    // Close the encrypted database; only the directory stays open
+    pool.close_identity()?;
+
     // Re-open the login window (it was closed during show_buddy_list)
     crate::windows::open_login(&app, active_key.as_deref())?;
 
@@ -524,15 +1179,15 @@ pub async fn logout(
 
 /// List all persisted identities (for the account picker).
 ///
-/// Returns summaries of every identity in `SQLite`, ordered by creation date.
-/// No authentication needed — this is called by the login window on mount.
+/// Returns summaries of every identity in the plaintext directory, ordered by
+/// creation date. No authentication needed — this is called by the login
+/// window on mount.
 #[tauri::command]
 pub async fn list_identities(
     pool: State<'_, DbPool>,
 ) -> Result<Vec<IdentitySummary>, String> {
     let db = pool.inner().clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db.lock().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || db.with_directory(|conn| {
         let mut stmt = conn
             .prepare(
                 "SELECT public_key, display_name, created_at, avatar_webp \
@@ -559,8 +1214,8 @@ pub async fn list_identities(
             .map_err(|e| e.to_string())?
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| e.to_string())?;
-        Ok::<Vec<IdentitySummary>, String>(rows)
-    })
+        Ok(rows)
+    }))
     .await
     .map_err(|e| e.to_string())?
 }
@@ -568,7 +1223,8 @@ pub async fn list_identities(
 /// Delete a specific identity after verifying the passphrase.
 ///
 /// Opens the identity's Stronghold to verify the passphrase, then deletes:
-/// - The identity row (CASCADE deletes all scoped data)
+/// - The directory row (CASCADE deletes any not-yet-migrated data)
+/// - The encrypted identity database
 /// - The Stronghold snapshot file
 ///
 /// If deleting the currently active identity, performs logout first.
@@ -688,6 +1344,7 @@ pub async fn delete_identity(
 
         // Clean up user-specific DHT state (node stays alive)
         services::veilid_service::logout_cleanup(Some(&app), state.inner()).await;
+        pool.close_identity()?;
 
         // Destroy all windows except login so labels are immediately freed
         for (label, window) in app.webview_windows() {
@@ -701,21 +1358,25 @@ pub async fn delete_identity(
         *state.game_detector.lock() = None;
     }
 
-    // Delete from SQLite (CASCADE deletes all scoped data)
+    // Delete from the directory (CASCADE deletes all scoped data)
     let db = pool.inner().clone();
     let pk = public_key.clone();
     tokio::task::spawn_blocking(move || {
-        let conn = db.lock().map_err(|e| e.to_string())?;
-        conn.execute(
-            "DELETE FROM identity WHERE public_key = ?1",
-            rusqlite::params![pk],
-        )
-        .map_err(|e| e.to_string())?;
-        Ok::<(), String>(())
+        db.with_directory(|conn| {
+            conn.execute(
+                "DELETE FROM identity WHERE public_key = ?1",
+                rusqlite::params![pk],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(())
+        })
     })
     .await
     .map_err(|e| e.to_string())??;
 
+    db::delete_identity_db(&config_dir, &public_key)
+        .map_err(|e| format!("failed to delete identity database: {e}"))?;
+
     // Delete the Stronghold snapshot file
     StrongholdKeystore::delete_snapshot(&config_dir, &public_key)
         .map_err(|e| format!("failed to delete keystore: {e}"))?;
@@ -724,6 +1385,73 @@ pub async fn delete_identity(
     Ok(())
 }
 
+/// Change the active identity's passphrase.
+///
+/// The Stronghold snapshot is re-encrypted under the new passphrase and the
+/// local database is re-keyed with a fresh key. The new database key is
+/// saved as pending before the re-key so an interruption at any point
+/// leaves a key that opens the database (see `open_identity_database`).
+#[tauri::command]
+pub async fn change_passphrase(
+    current_passphrase: String,
+    new_passphrase: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+    keystore_handle: State<'_, KeystoreHandle>,
+) -> Result<(), String> {
+    let public_key = current_owner_key(state.inner())?;
+    if new_passphrase.is_empty() {
+        return Err("new passphrase must not be empty".to_string());
+    }
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?;
+
+    // Verify the current passphrase against the snapshot on disk
+    StrongholdKeystore::initialize_for_identity(&config_dir, &public_key, &current_passphrase)
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("snapshot") || msg.contains("decrypt") {
+                "Wrong passphrase".to_string()
+            } else {
+                msg
+            }
+        })?;
+
+    let next = crate::keystore::generate_database_key();
+    {
+        let guard = keystore_handle.lock();
+        let keystore = guard.as_ref().ok_or("keystore is locked")?;
+        keystore
+            .store_key(VAULT_IDENTITY, KEY_DATABASE_NEXT, next.as_slice())
+            .map_err(|e| e.to_string())?;
+        keystore.save().map_err(|e| e.to_string())?;
+    }
+
+    let db = pool.inner().clone();
+    let rekey = next.clone();
+    tokio::task::spawn_blocking(move || db.rekey(&rekey))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let mut guard = keystore_handle.lock();
+    let keystore = guard.as_mut().ok_or("keystore is locked")?;
+    keystore
+        .store_key(VAULT_IDENTITY, KEY_DATABASE, next.as_slice())
+        .map_err(|e| e.to_string())?;
+    keystore
+        .delete_key(VAULT_IDENTITY, KEY_DATABASE_NEXT)
+        .map_err(|e| e.to_string())?;
+    keystore
+        .change_passphrase(&new_passphrase)
+        .map_err(|e| e.to_string())?;
+
+    tracing::info!(public_key = %public_key, "passphrase changed");
+    Ok(())
+}
+
 /// Load friends from `SQLite` into `AppState`, scoped to the given identity.
 async fn load_friends_from_db(
     pool: &DbPool,
@@ -738,7 +1466,7 @@ async fn load_friends_from_db(
             .prepare(
                 "SELECT f.public_key, f.display_name, f.nickname, f.dht_record_key, \
                  f.last_seen_at, f.local_conversation_key, f.remote_conversation_key, \
-                 f.mailbox_dht_key, f.friendship_state, g.name AS group_name \
+                 f.mailbox_dht_key, f.friendship_state, f.disappearing_secs, g.name AS group_name \
                  FROM friends f LEFT JOIN friend_groups g ON f.group_id = g.id \
                  WHERE f.owner_key = ?1",
             )
@@ -766,6 +1494,9 @@ async fn load_friends_from_db(
                     mailbox_dht_key: db::get_str_opt(row, "mailbox_dht_key"),
                     last_heartbeat_at: None,
                     friendship_state,
+                    disappearing_secs: db::get_i64_opt(row, "disappearing_secs")
+                        .and_then(|s| u32::try_from(s).ok())
+                        .unwrap_or(0),
                 })
             })
             .map_err(|e| e.to_string())?
@@ -801,7 +1532,7 @@ async fn load_communities_from_db(
         let mut comm_stmt = conn
             .prepare(
                 "SELECT id, name, description, my_role, my_role_ids, dht_record_key, dht_owner_keypair, \
-                 my_pseudonym_key, mek_generation, server_route_blob, is_hosted \
+                 my_pseudonym_key, owner_pseudonym, mek_generation, server_route_blob, is_hosted, history_visibility \
                  FROM communities WHERE owner_key = ?1",
             )
             .map_err(|e| e.to_string())?;
@@ -816,9 +1547,11 @@ async fn load_communities_from_db(
                     db::get_str_opt(row, "dht_record_key"),
                     db::get_str_opt(row, "dht_owner_keypair"),
                     db::get_str_opt(row, "my_pseudonym_key"),
+                    db::get_str_opt(row, "owner_pseudonym"),
                     row.get::<_, i64>("mek_generation").unwrap_or(0).cast_unsigned(),
                     row.get::<_, Option<Vec<u8>>>("server_route_blob").unwrap_or(None),
                     row.get::<_, i64>("is_hosted").unwrap_or(0) != 0,
+                    HistoryVisibility::from_db(&db::get_str(row, "history_visibility")),
                 ))
             })
             .map_err(|e| e.to_string())?
@@ -850,7 +1583,7 @@ async fn load_communities_from_db(
             .map_err(|e| e.to_string())?;
 
         let mut chan_stmt = conn
-            .prepare("SELECT id, community_id, name, channel_type FROM channels WHERE owner_key = ?1 ORDER BY sort_order")
+            .prepare("SELECT id, community_id, name, channel_type, retention_secs FROM channels WHERE owner_key = ?1 ORDER BY sort_order")
             .map_err(|e| e.to_string())?;
         let channels = chan_stmt
             .query_map(rusqlite::params![ok], |row| {
@@ -859,6 +1592,7 @@ async fn load_communities_from_db(
                     db::get_str(row, "community_id"),
                     db::get_str(row, "name"),
                     db::get_str(row, "channel_type"),
+                    db::get_i64_opt(row, "retention_secs").and_then(|s| u32::try_from(s).ok()).unwrap_or(0),
                 ))
             })
             .map_err(|e| e.to_string())?
@@ -871,11 +1605,11 @@ async fn load_communities_from_db(
     .map_err(|e| e.to_string())??;
 
     let mut communities = state.communities.write();
-    for (community_id, name, description, my_role, my_role_ids_json, dht_record_key, dht_owner_keypair, my_pseudonym_key, mek_generation, server_route_blob, is_hosted) in &community_rows {
+    for (community_id, name, description, my_role, my_role_ids_json, dht_record_key, dht_owner_keypair, my_pseudonym_key, owner_pseudonym, mek_generation, server_route_blob, is_hosted, history_visibility) in &community_rows {
         let channels: Vec<ChannelInfo> = channel_rows
             .iter()
-            .filter(|(_, cid, _, _)| cid == community_id)
-            .map(|(id, _, ch_name, ch_type)| {
+            .filter(|(_, cid, ..)| cid == community_id)
+            .map(|(id, _, ch_name, ch_type, retention_secs)| {
                 let channel_type = match ch_type.as_str() {
                     "voice" => ChannelType::Voice,
                     _ => ChannelType::Text,
@@ -885,6 +1619,7 @@ async fn load_communities_from_db(
                     name: ch_name.clone(),
                     channel_type,
                     unread_count: 0,
+                    retention_secs: *retention_secs,
                 }
             })
             .collect();
@@ -919,9 +1654,12 @@ async fn load_communities_from_db(
             dht_record_key: dht_record_key.clone(),
             dht_owner_keypair: dht_owner_keypair.clone(),
             my_pseudonym_key: my_pseudonym_key.clone(),
+            owner_pseudonym: owner_pseudonym.clone(),
             mek_generation: *mek_generation,
             server_route_blob: server_route_blob.clone(),
             is_hosted: *is_hosted,
+            history_visibility: *history_visibility,
+            threads: Vec::new(),
         };
         // Recalculate display role from role definitions (DB value may be stale)
         community.my_role = Some(crate::state::display_role_name(&community.my_role_ids, &community.roles));
@@ -944,10 +1682,8 @@ fn restore_community_pseudonyms_and_meks(
     keystore_handle: &KeystoreHandle,
     secret_key: &[u8; 32],
 ) {
-    use rekindle_crypto::group::media_key::MediaEncryptionKey;
+    use rekindle_crypto::group::media_key::{MediaEncryptionKey, MediaKeyRing};
     use rekindle_crypto::group::pseudonym::derive_community_pseudonym;
-    use rekindle_crypto::keychain::{mek_key_name, VAULT_COMMUNITIES};
-    use rekindle_crypto::Keychain as _;
 
     // Collect community IDs and is_hosted flags
     let community_info: Vec<(String, bool)> = {
@@ -959,7 +1695,7 @@ fn restore_community_pseudonyms_and_meks(
     };
 
     let mut pseudonym_updates: Vec<(String, String)> = Vec::new();
-    let mut mek_updates: Vec<(String, MediaEncryptionKey)> = Vec::new();
+    let mut mek_updates: Vec<(String, MediaKeyRing)> = Vec::new();
     let mut regenerated_community_ids: Vec<String> = Vec::new();
 
     for (community_id, is_hosted) in &community_info {
@@ -968,60 +1704,36 @@ fn restore_community_pseudonyms_and_meks(
         let pseudonym_hex = hex::encode(signing_key.verifying_key().as_bytes());
         pseudonym_updates.push((community_id.clone(), pseudonym_hex));
 
-        // Try to load MEK from Stronghold
-        let keystore = keystore_handle.lock();
-        if let Some(ref ks) = *keystore {
-            let key_name = mek_key_name(community_id);
-            match ks.load_key(VAULT_COMMUNITIES, &key_name) {
-                Ok(Some(mek_bytes)) if mek_bytes.len() >= 40 => {
-                    // MEK payload: generation (8 bytes LE) + key (32 bytes)
-                    let generation =
-                        u64::from_le_bytes(mek_bytes[..8].try_into().unwrap_or_default());
-                    let key_bytes: [u8; 32] =
-                        mek_bytes[8..40].try_into().unwrap_or_default();
-                    let mek = MediaEncryptionKey::from_bytes(key_bytes, generation);
-                    mek_updates.push((community_id.clone(), mek));
-                }
-                Ok(_) if *is_hosted => {
-                    // Owned community with no MEK in Stronghold — regenerate.
-                    // This handles communities created before MEK persistence was added.
-                    tracing::warn!(
-                        community = %community_id,
-                        "MEK missing from Stronghold for hosted community — regenerating"
-                    );
-                    let mek = MediaEncryptionKey::generate(1);
-
-                    // Persist immediately so the next restart finds it
-                    let mut mek_payload = Vec::with_capacity(40);
-                    mek_payload.extend_from_slice(&mek.generation().to_le_bytes());
-                    mek_payload.extend_from_slice(mek.as_bytes());
-                    if let Err(e) = ks.store_key(VAULT_COMMUNITIES, &key_name, &mek_payload) {
-                        tracing::warn!(error = %e, community = %community_id, "failed to persist regenerated MEK");
-                    } else if let Err(e) = ks.save() {
-                        tracing::warn!(error = %e, community = %community_id, "failed to save Stronghold after MEK regeneration");
-                    } else {
-                        tracing::info!(community = %community_id, "regenerated MEK persisted to Stronghold");
-                    }
-
-                    mek_updates.push((community_id.clone(), mek));
-                    regenerated_community_ids.push(community_id.clone());
-                }
-                Ok(_) => {
-                    // Non-hosted community with missing MEK — user needs to
-                    // re-join or wait for MEK delivery from the community server.
-                    tracing::warn!(
-                        community = %community_id,
-                        "MEK missing from Stronghold for joined community — \
-                         will be delivered when connecting to community server"
-                    );
-                }
-                Err(e) => {
-                    tracing::warn!(
-                        community = %community_id,
-                        error = %e,
-                        "failed to load MEK from Stronghold"
-                    );
-                }
+        // Try to load every MEK generation from Stronghold
+        match services::mek_service::load_keyring(keystore_handle, community_id) {
+            Ok(Some(ring)) => mek_updates.push((community_id.clone(), ring)),
+            Ok(None) if *is_hosted => {
+                // Owned community with no MEK in Stronghold — regenerate.
+                // This handles communities created before MEK persistence was added.
+                tracing::warn!(
+                    community = %community_id,
+                    "MEK missing from Stronghold for hosted community — regenerating"
+                );
+                let mut ring = MediaKeyRing::new();
+                ring.insert(MediaEncryptionKey::generate(1));
+                mek_updates.push((community_id.clone(), ring));
+                regenerated_community_ids.push(community_id.clone());
+            }
+            Ok(None) => {
+                // Non-hosted community with missing MEK — user needs to
+                // re-join or wait for MEK delivery from the community server.
+                tracing::warn!(
+                    community = %community_id,
+                    "MEK missing from Stronghold for joined community — \
+                     will be delivered when connecting to community server"
+                );
+            }
+            Err(e) => {
+                tracing::warn!(
+                    community = %community_id,
+                    error = %e,
+                    "failed to load MEK from Stronghold"
+                );
             }
         }
     }
@@ -1046,18 +1758,23 @@ fn restore_community_pseudonyms_and_meks(
         }
     }
 
-    // Load MEKs into cache
+    // Load MEK keyrings into cache
     {
         let mut mek_cache = state.mek_cache.lock();
-        for (community_id, mek) in mek_updates {
+        for (community_id, ring) in mek_updates {
             tracing::debug!(
                 community = %community_id,
-                generation = mek.generation(),
-                "restored MEK from Stronghold"
+                generation = ?ring.current_generation(),
+                "restored MEK keyring from Stronghold"
             );
-            mek_cache.insert(community_id, mek);
+            mek_cache.insert(community_id, ring);
         }
     }
+
+    // Persist regenerated keys so the next restart finds them
+    for community_id in &regenerated_community_ids {
+        services::mek_service::persist_keyring(state, keystore_handle, community_id);
+    }
 }
 
 /// Stored DHT keys and owner keypairs loaded from `SQLite` during login.
@@ -1088,7 +1805,7 @@ fn start_background_services(
     dht_keys: DhtKeysConfig,
 ) {
     // Initialize Signal Protocol session manager (returns serialized PreKeyBundle)
-    let prekey_bundle_bytes = initialize_signal_manager(state, secret_key);
+    let prekey_bundle_bytes = initialize_signal_manager(state, pool, secret_key);
 
     // Clear any stale background handles from a previous session
     state.background_handles.lock().clear();
@@ -1164,7 +1881,7 @@ fn spawn_login_services(
     let dht_handle = tauri::async_runtime::spawn(spawn_dht_publish(
         app.clone(),
         state.clone(),
-        pool,
+        pool.clone(),
         prekey_bundle_bytes,
         dht_keys,
     ));
@@ -1208,6 +1925,16 @@ fn spawn_login_services(
     *state.heartbeat_shutdown_tx.write() = Some(heartbeat_tx);
     state.background_handles.lock().push(heartbeat_handle);
 
+    // Start prekey maintenance loop (signed-prekey rotation + one-time prekey replenishment)
+    let (prekey_tx, prekey_rx) = mpsc::channel::<()>(1);
+    let prekey_state = Arc::clone(state);
+    let prekey_pool = pool.clone();
+    let prekey_handle = tauri::async_runtime::spawn(
+        services::prekey_service::prekey_maintenance_loop(prekey_state, prekey_pool, prekey_rx),
+    );
+    *state.prekey_shutdown_tx.write() = Some(prekey_tx);
+    state.background_handles.lock().push(prekey_handle);
+
     // Spawn community server process if user owns any communities
     maybe_spawn_server(app, state);
 
@@ -1407,7 +2134,7 @@ fn kill_stale_server(state: &SharedState, socket_path: &std::path::Path) {
 /// Route allocation can fail transiently after the network becomes ready because
 /// peerinfo may not have been published yet. We retry up to `max_attempts` times
 /// with a 3-second delay between attempts.
-async fn allocate_route_with_retry(app_handle: &tauri::AppHandle, state: &SharedState, max_attempts: u32) -> Option<Vec<u8>> {
+pub(crate) async fn allocate_route_with_retry(app_handle: &tauri::AppHandle, state: &SharedState, max_attempts: u32) -> Option<Vec<u8>> {
     for attempt in 1..=max_attempts {
         let api = {
             let node = state.node.read();
@@ -1498,6 +2225,11 @@ async fn spawn_dht_publish(
         tracing::warn!("failed to allocate private route after retries — peers won't be able to message us");
     }
 
+    if services::device_service::is_linked_device(&state) {
+        publish_as_linked_device(&app_handle, &state, &pool, dht_keys, route_blob, prekey_bundle_bytes).await;
+        return;
+    }
+
     // Create or open mailbox DHT record
     if let Err(e) = publish_mailbox(&state, &pool, dht_keys.mailbox_dht_key.as_ref(), route_blob.as_deref()).await {
         tracing::warn!(error = %e, "mailbox publish failed");
@@ -1529,16 +2261,60 @@ async fn spawn_dht_publish(
         tracing::warn!(error = %e, "immediate friend sync failed");
     }
 
-    // Publish account record (Phase 3)
-    if let Err(e) = publish_account_to_dht(
-        &state,
-        &pool,
-        dht_keys.account_dht_key,
-        dht_keys.account_owner_keypair,
+    // Publish account record (Phase 3); restores the social graph if the
+    // network already held one for this identity
+    if let Err(e) = services::account_service::publish_account(&app_handle, &state, &pool).await {
+        tracing::warn!(error = %e, "DHT account publish failed — will retry on next sync");
+    } else if let Err(e) = services::account_service::publish_social_graph(&state, &pool).await {
+        tracing::warn!(error = %e, "account record update failed");
+    }
+
+    // Keep linked devices' view of the device and friend lists current
+    if !services::device_service::own_devices(&state).is_empty() {
+        if let Err(e) = services::device_service::publish_own_devices(&state, &pool).await {
+            tracing::warn!(error = %e, "device list publish failed");
+        }
+        services::device_service::share_contacts(&state, &pool);
+    }
+}
+
+/// Linked-device counterpart of [`spawn_dht_publish`]'s publish steps.
+///
+/// The profile, friend list, identity mailbox and account record belong to
+/// the primary device; a linked device only publishes its own mailbox and
+/// reads the rest.
+async fn publish_as_linked_device(
+    app_handle: &tauri::AppHandle,
+    state: &SharedState,
+    pool: &DbPool,
+    dht_keys: DhtKeysConfig,
+    route_blob: Option<Vec<u8>>,
+    prekey_bundle_bytes: Option<Vec<u8>>,
+) {
+    {
+        let mut node = state.node.write();
+        if let Some(ref mut nh) = *node {
+            nh.mailbox_dht_key = dht_keys.mailbox_dht_key;
+            nh.account_dht_key = dht_keys.account_dht_key;
+        }
+    }
+
+    if let Err(e) = services::device_service::publish_device_mailbox(
+        state,
+        route_blob.as_deref(),
+        prekey_bundle_bytes.as_deref(),
     )
     .await
     {
-        tracing::warn!(error = %e, "DHT account publish failed — will retry on next sync");
+        tracing::warn!(error = %e, "device mailbox publish failed");
+    }
+
+    if let Err(e) = services::sync_service::sync_friends_now(state, app_handle).await {
+        tracing::warn!(error = %e, "immediate friend sync failed");
+    }
+
+    if let Err(e) = services::device_service::refresh_own_devices(state, pool).await {
+        tracing::warn!(error = %e, "failed to refresh our linked devices");
     }
 }
 
@@ -1645,13 +2421,23 @@ async fn publish_mailbox(
     Ok(())
 }
 
+/// Outcome of reusing an existing profile DHT record.
+enum ProfileReuse {
+    /// The record was opened and all subkeys were written.
+    Updated,
+    /// The record predates the one-time prekey pool and has too few subkeys
+    /// to hold it. Nothing was written.
+    TooSmall,
+}
+
 /// Try to open an existing profile DHT record and update all subkeys.
 ///
 /// If `owner_keypair` is provided, the record is opened with write access.
-/// Returns `Ok(())` if the record was opened and ALL subkeys were written successfully.
-/// Returns `Err` if the open failed OR any write failed (e.g. "value is not writable"
-/// when the owner keypair is missing or incorrect). The caller should fall back to
-/// creating a new record in the `Err` case.
+/// Returns `Ok(ProfileReuse::Updated)` if the record was opened and ALL subkeys were
+/// written successfully, and `Ok(ProfileReuse::TooSmall)` if the record must be
+/// migrated to a new one. Returns `Err` if the open failed OR any write failed (e.g.
+/// "value is not writable" when the owner keypair is missing or incorrect). The caller
+/// should fall back to creating a new record in the `Err` case.
 async fn try_update_existing_profile(
     dht: &rekindle_protocol::dht::DHTManager,
     existing_key: &str,
@@ -1660,12 +2446,16 @@ async fn try_update_existing_profile(
     prekey_bundle: &[u8],
     route_blob: &[u8],
     owner_keypair: Option<veilid_core::KeyPair>,
-) -> Result<(), String> {
+) -> Result<ProfileReuse, String> {
     let has_keypair = owner_keypair.is_some();
     if let Some(keypair) = owner_keypair {
-        dht.open_record_writable(existing_key, keypair)
+        let subkey_count = dht
+            .open_record_writable_subkeys(existing_key, keypair)
             .await
             .map_err(|e| format!("open writable: {e}"))?;
+        if subkey_count < rekindle_protocol::dht::profile::PROFILE_SUBKEY_COUNT {
+            return Ok(ProfileReuse::TooSmall);
+        }
     } else {
         return Err("no owner keypair — cannot write to existing record".to_string());
     }
@@ -1695,7 +2485,37 @@ async fn try_update_existing_profile(
     )
     .await
     .map_err(|e| format!("route blob: {e}"))?;
-    Ok(())
+    Ok(ProfileReuse::Updated)
+}
+
+/// Move a profile record that predates the one-time prekey pool to a new,
+/// full-size record. Linked-device certificates are copied over; the caller
+/// tells friends the new key once it is stored.
+async fn migrate_profile_record(
+    dht: &rekindle_protocol::dht::DHTManager,
+    old_key: &str,
+    display_name: &str,
+    status_message: &str,
+    prekey_bundle: &[u8],
+    route_blob: &[u8],
+) -> Result<(String, Option<veilid_core::KeyPair>), String> {
+    use rekindle_protocol::dht::profile;
+
+    let (new_key, new_keypair) =
+        profile::create_profile(dht, display_name, status_message, prekey_bundle, route_blob)
+            .await
+            .map_err(|e| format!("failed to create DHT profile: {e}"))?;
+    match profile::read_subkey(dht, old_key, profile::SUBKEY_DEVICES).await {
+        Ok(Some(devices)) => {
+            if let Err(e) = profile::update_subkey(dht, &new_key, profile::SUBKEY_DEVICES, devices).await {
+                tracing::warn!(error = %e, "failed to copy device list to migrated profile");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!(error = %e, "failed to read device list from old profile"),
+    }
+    tracing::info!(old_key = %old_key, new_key = %new_key, "migrated profile record to the current layout");
+    Ok((new_key, new_keypair))
 }
 
 /// Create (or reuse) a DHT profile record and publish identity data after login.
@@ -1707,7 +2527,8 @@ async fn try_update_existing_profile(
 /// If `existing_dht_key` is provided (from a previous session stored in `SQLite`),
 /// attempts to open and reuse that record. Falls back to creating a new one if the
 /// open or any write fails (e.g., record expired, or "value is not writable" when
-/// the owner keypair isn't available).
+/// the owner keypair isn't available). A record too small for the one-time prekey
+/// pool is migrated to a new one and friends are sent `ProfileKeyRotated`.
 async fn publish_profile_to_dht(
     state: &SharedState,
     pool: &DbPool,
@@ -1750,6 +2571,7 @@ async fn publish_profile_to_dht(
 
     // Try to reuse existing DHT record with the owner keypair for write access.
     // If no keypair is stored, writes will fail and we fall back to creating fresh.
+    let mut migrated = false;
     let (profile_key, new_keypair) = if let Some(ref existing_key) = existing_dht_key {
         match try_update_existing_profile(
             &temp_dht,
@@ -1762,7 +2584,14 @@ async fn publish_profile_to_dht(
         )
         .await
         {
-            Ok(()) => (existing_key.clone(), None),
+            Ok(ProfileReuse::Updated) => (existing_key.clone(), None),
+            Ok(ProfileReuse::TooSmall) => {
+                migrated = true;
+                migrate_profile_record(
+                    &temp_dht, existing_key, &display_name, &status_message, bundle, &route_blob,
+                )
+                .await?
+            }
             Err(e) => {
                 tracing::warn!(
                     key = %existing_key, error = %e,
@@ -1830,6 +2659,11 @@ async fn publish_profile_to_dht(
         "published profile to DHT"
     );
 
+    if migrated {
+        let notified = crate::commands::friends::notify_profile_key_rotated(state, pool, &profile_key).await;
+        tracing::info!(notified, "told friends about the migrated profile record");
+    }
+
     Ok(())
 }
 
@@ -1943,138 +2777,6 @@ async fn publish_friend_list_to_dht(
     Ok(())
 }
 
-/// Create a fresh account DHT record (helper to reduce duplication).
-async fn create_fresh_account_record(
-    routing_context: &veilid_core::RoutingContext,
-    encryption_key: rekindle_crypto::DhtRecordKey,
-    display_name: &str,
-    status_message: &str,
-) -> Result<(String, Option<veilid_core::KeyPair>), String> {
-    let (record, kp) = rekindle_protocol::dht::account::AccountRecord::create(
-        routing_context,
-        encryption_key,
-        display_name,
-        status_message,
-    )
-    .await
-    .map_err(|e| format!("create account record: {e}"))?;
-    Ok((record.record_key(), Some(kp)))
-}
-
-/// Store an account DHT key on `NodeHandle` and track records in `DHTManagerHandle`.
-fn store_account_key_on_handles(state: &SharedState, account_key: &str, record_keys: Vec<String>) {
-    if let Some(ref mut nh) = *state.node.write() {
-        nh.account_dht_key = Some(account_key.to_string());
-    }
-    let mut dht_mgr = state.dht_manager.write();
-    if let Some(ref mut mgr) = *dht_mgr {
-        for k in record_keys {
-            mgr.track_open_record(k);
-        }
-    }
-}
-
-/// Persist account DHT key and owner keypair to `SQLite`.
-async fn persist_account_key_to_db(
-    pool: &DbPool,
-    public_key: &str,
-    account_key: &str,
-    new_keypair: Option<veilid_core::KeyPair>,
-) -> Result<(), String> {
-    let keypair_str = new_keypair.map(|kp| kp.to_string());
-    let db = pool.clone();
-    let pk = public_key.to_string();
-    let ak = account_key.to_string();
-    tokio::task::spawn_blocking(move || {
-        let conn = db.lock().map_err(|e| e.to_string())?;
-        conn.execute(
-            "UPDATE identity SET account_dht_key = ?1, \
-             account_owner_keypair = COALESCE(?3, account_owner_keypair) \
-             WHERE public_key = ?2",
-            rusqlite::params![ak, pk, keypair_str],
-        )
-        .map_err(|e| e.to_string())?;
-        Ok::<(), String>(())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-/// Create (or reopen) the private Account DHT record after login.
-///
-/// The account record is encrypted with a key derived from the identity's Ed25519 secret.
-/// It holds pointers to contact list, chat list, and invitation list `DHTShortArray`s.
-async fn publish_account_to_dht(
-    state: &SharedState,
-    pool: &DbPool,
-    existing_account_key: Option<String>,
-    account_owner_keypair_str: Option<String>,
-) -> Result<(), String> {
-    let (public_key, display_name, status_message) = {
-        let identity = state.identity.read();
-        let id = identity.as_ref().ok_or("identity not set before account publish")?;
-        (id.public_key.clone(), id.display_name.clone(), id.status_message.clone())
-    };
-
-    let secret_bytes = state
-        .identity_secret
-        .lock()
-        .ok_or("identity secret not available for account key derivation")?;
-    let encryption_key = rekindle_crypto::DhtRecordKey::derive_account_key(&secret_bytes);
-
-    let routing_context = {
-        let node = state.node.read();
-        let nh = node.as_ref().ok_or("node not initialized before account publish")?;
-        nh.routing_context.clone()
-    };
-
-    let owner_keypair: Option<veilid_core::KeyPair> = account_owner_keypair_str.as_ref().and_then(|s| {
-        s.parse().map_err(|e| {
-            tracing::warn!(error = %e, "failed to parse stored account owner keypair");
-            e
-        }).ok()
-    });
-
-    let (account_key, new_keypair) = if let Some(ref existing_key) = existing_account_key {
-        if let Some(keypair) = owner_keypair {
-            match rekindle_protocol::dht::account::AccountRecord::open(
-                &routing_context,
-                existing_key,
-                keypair,
-                encryption_key,
-            )
-            .await
-            {
-                Ok(record) => {
-                    tracing::info!(key = %existing_key, "reusing existing account DHT record");
-                    store_account_key_on_handles(state, &record.record_key(), record.all_record_keys());
-                    return Ok(());
-                }
-                Err(e) => {
-                    tracing::warn!(
-                        key = %existing_key, error = %e,
-                        "failed to open existing account record — creating new one"
-                    );
-                    let enc_key = rekindle_crypto::DhtRecordKey::derive_account_key(&secret_bytes);
-                    create_fresh_account_record(&routing_context, enc_key, &display_name, &status_message).await?
-                }
-            }
-        } else {
-            tracing::warn!("no account owner keypair — creating new account record");
-            let enc_key = rekindle_crypto::DhtRecordKey::derive_account_key(&secret_bytes);
-            create_fresh_account_record(&routing_context, enc_key, &display_name, &status_message).await?
-        }
-    } else {
-        create_fresh_account_record(&routing_context, encryption_key, &display_name, &status_message).await?
-    };
-
-    store_account_key_on_handles(state, &account_key, vec![account_key.clone()]);
-    persist_account_key_to_db(pool, &public_key, &account_key, new_keypair).await?;
-
-    tracing::info!(account_key = %account_key, "published account record to DHT");
-    Ok(())
-}
-
 /// Create a conversation record for a specific friend.
 ///
 /// Called when establishing a new contact. Creates a `ConversationRecord`,
@@ -2197,18 +2899,20 @@ pub async fn create_conversation_for_friend(
 
 /// Initialize the Signal Protocol session manager with the identity key.
 ///
-/// Creates in-memory stores for identity, prekeys, and sessions, then
-/// generates an initial `PreKeyBundle` for DHT publication.
+/// The session store is in-memory; prekeys and trusted remote identity keys
+/// are persisted in `SQLite` so that bundles handed out in earlier sessions
+/// stay usable and identity-key changes are detected across restarts.
+/// Runs an initial prekey maintenance pass (creating or rotating the signed
+/// prekey and filling the one-time prekey pool).
 ///
 /// Returns the serialized `PreKeyBundle` bytes if generation succeeded,
 /// so the caller can publish them to DHT profile subkey 5.
 fn initialize_signal_manager(
     state: &SharedState,
+    pool: &DbPool,
     secret_key: &[u8; 32],
 ) -> Option<Vec<u8>> {
-    use rekindle_crypto::signal::{
-        MemoryIdentityStore, MemoryPreKeyStore, MemorySessionStore, SignalSessionManager,
-    };
+    use rekindle_crypto::signal::{MemorySessionStore, SignalSessionManager};
 
     // Derive the X25519 key pair from the Ed25519 secret key for X3DH
     let identity = rekindle_crypto::Identity::from_secret_bytes(secret_key);
@@ -2223,8 +2927,15 @@ fn initialize_signal_manager(
     let pub_bytes = identity.public_key_bytes();
     let registration_id = u32::from_le_bytes([pub_bytes[0], pub_bytes[1], pub_bytes[2], pub_bytes[3]]);
 
-    let identity_store = MemoryIdentityStore::new(identity_private, identity_public, registration_id);
-    let prekey_store = MemoryPreKeyStore::new();
+    let owner_key = identity.public_key_hex();
+    let identity_store = crate::signal_store::SqliteIdentityStore::new(
+        pool.clone(),
+        owner_key.clone(),
+        identity_private,
+        identity_public,
+        registration_id,
+    );
+    let prekey_store = crate::signal_store::SqlitePreKeyStore::new(pool.clone(), owner_key);
     let session_store = MemorySessionStore::new();
 
     let manager = SignalSessionManager::new(
@@ -2232,30 +2943,24 @@ fn initialize_signal_manager(
         Box::new(prekey_store),
         Box::new(session_store),
     );
+    *state.signal_manager.lock() = Some(SignalManagerHandle { manager });
 
-    // Generate initial PreKeyBundle (signed prekey #1, one-time prekey #1)
-    let bundle_bytes = match manager.generate_prekey_bundle(1, Some(1)) {
-        Ok(bundle) => {
-            tracing::info!(
-                registration_id = bundle.registration_id,
-                "Signal session manager initialized with PreKeyBundle"
-            );
-            match serde_json::to_vec(&bundle) {
-                Ok(bytes) => Some(bytes),
-                Err(e) => {
-                    tracing::warn!(error = %e, "failed to serialize PreKeyBundle for DHT publication");
-                    None
-                }
-            }
+    // Ensure a current signed prekey and a full one-time prekey pool exist
+    if let Err(e) = services::prekey_service::run_maintenance(state, pool) {
+        tracing::warn!(error = %e, "initial prekey maintenance failed — sessions will still work via respond_to_session");
+    }
+
+    let bundle_bytes = match services::prekey_service::published_bundle(state) {
+        Ok(bytes) => {
+            tracing::info!(registration_id, "Signal session manager initialized with PreKeyBundle");
+            Some(bytes)
         }
         Err(e) => {
-            tracing::warn!(error = %e, "failed to generate initial PreKeyBundle — sessions will still work via respond_to_session");
+            tracing::warn!(error = %e, "failed to build PreKeyBundle for DHT publication");
             None
         }
     };
 
-    *state.signal_manager.lock() = Some(SignalManagerHandle { manager });
-
     // Store the Ed25519 secret key bytes so message_service can sign envelopes
     *state.identity_secret.lock() = Some(*secret_key);
 
@@ -1,3 +1,5 @@
+use rekindle_protocol::messaging::envelope::{MessagePayload, MAX_RECEIPT_BATCH};
+use rekindle_protocol::messaging::new_message_id;
 use serde::{Deserialize, Serialize};
 use tauri::{Emitter, State};
 
@@ -5,6 +7,8 @@ use crate::channels::ChatEvent;
 use crate::commands::auth::current_owner_key;
 use crate::db::{self, DbPool};
 use crate::services;
+use crate::services::message_service::Delivery;
+use crate::services::receipt_service::{self, DeliveryState};
 use crate::state::SharedState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,9 +19,68 @@ pub struct Message {
     pub body: String,
     pub timestamp: i64,
     pub is_own: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<AttachmentInfo>,
+    /// Global ID shared with the peer or server (`id` is our row).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<ReactionInfo>,
+    /// Our DMs only: `queued`, `sent`, `delivered`, `read` or `failed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery_state: Option<String>,
+    /// Global ID of the message this one replies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+}
+
+/// A DM we just sent.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SentMessage {
+    pub message_id: String,
+    /// `sent` if it went straight out, `queued` if it is waiting for the
+    /// peer to come online, `failed` if it couldn't even be queued.
+    pub delivery_state: String,
+}
+
+/// One emoji's reactions on a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionInfo {
+    pub emoji: String,
+    pub count: u32,
+    /// Whether we are one of the reactors.
+    pub mine: bool,
+}
+
+/// A file shared in a message, as shown in the chat window.
+///
+/// Never carries the file key — the frontend only needs enough to show the
+/// file and ask for it by `(message_id, index)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentInfo {
+    pub message_id: i64,
+    pub index: u32,
+    pub name: String,
+    pub mime_type: String,
+    pub size: u64,
+    /// `available` (not downloaded), `active`, `complete` or `failed`.
+    pub state: String,
+    /// Chunks transferred so far, out of `total`.
+    pub done: u32,
+    pub total: u32,
+    /// Where the file is on disk, once it is there.
+    pub local_path: Option<String>,
 }
 
 /// Send a message to a friend (1:1 DM).
+///
+/// Returns the message's global ID, for later edits, deletion and reactions,
+/// and where its delivery stands.
 #[tauri::command]
 pub async fn send_message(
     to: String,
@@ -25,10 +88,11 @@ pub async fn send_message(
     app: tauri::AppHandle,
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
-) -> Result<(), String> {
+) -> Result<SentMessage, String> {
     let owner_key = current_owner_key(state.inner())?;
     let sender_key = owner_key.clone();
     let timestamp = db::timestamp_now();
+    let message_id = new_message_id();
 
     tracing::info!(to = %to, from = %sender_key, len = body.len(), "sending message");
 
@@ -38,12 +102,13 @@ pub async fn send_message(
     let sender_key_clone = sender_key.clone();
     let body_clone = body.clone();
     let ok = owner_key.clone();
+    let id = message_id.clone();
     tokio::task::spawn_blocking(move || {
         let conn = pool_clone.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read) \
-             VALUES (?, ?, 'dm', ?, ?, ?, 1)",
-            rusqlite::params![ok, to_clone, sender_key_clone, body_clone, timestamp],
+            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, message_id, delivery_state) \
+             VALUES (?, ?, 'dm', ?, ?, ?, 1, ?, 'queued')",
+            rusqlite::params![ok, to_clone, sender_key_clone, body_clone, timestamp, id],
         )
         .map_err(|e| e.to_string())?;
         Ok::<_, String>(())
@@ -52,8 +117,27 @@ pub async fn send_message(
     .map_err(|e| e.to_string())??;
 
     // Step 2: Send via Veilid (best-effort — queues on failure internally)
-    if let Err(e) = services::message_service::send_message(state.inner(), pool.inner(), &to, &body).await {
-        tracing::warn!(error = %e, "DM send failed — message persisted locally");
+    let delivery_state =
+        match services::message_service::send_message(state.inner(), pool.inner(), &to, &message_id, &body)
+            .await
+        {
+            Ok(Delivery::Sent) => DeliveryState::Sent,
+            Ok(_) => DeliveryState::Queued,
+            Err(e) => {
+                tracing::warn!(error = %e, "DM send failed — message persisted locally");
+                DeliveryState::Failed
+            }
+        };
+    if delivery_state != DeliveryState::Queued {
+        receipt_service::set_delivery_state(
+            &app,
+            state.inner(),
+            pool.inner(),
+            &to,
+            std::slice::from_ref(&message_id),
+            delivery_state,
+        )
+        .await;
     }
 
     // Step 3: Emit ack
@@ -62,7 +146,146 @@ pub async fn send_message(
     };
     let _ = app.emit("chat-event", &ack);
 
-    Ok(())
+    Ok(SentMessage {
+        message_id,
+        delivery_state: delivery_state.as_str().to_string(),
+    })
+}
+
+/// Replace the body of one of our messages, in a DM or a channel.
+#[tauri::command]
+pub async fn edit_message(
+    conversation_id: String,
+    message_id: String,
+    body: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    services::message_edit_service::edit_message(
+        &app,
+        state.inner(),
+        pool.inner(),
+        &conversation_id,
+        &message_id,
+        &body,
+    )
+    .await
+}
+
+/// Delete a message for everyone in the conversation.
+///
+/// Our own messages anywhere; in channels, others' too with `MANAGE_MESSAGES`.
+#[tauri::command]
+pub async fn delete_message(
+    conversation_id: String,
+    message_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    services::message_edit_service::delete_message(
+        &app,
+        state.inner(),
+        pool.inner(),
+        &conversation_id,
+        &message_id,
+    )
+    .await
+}
+
+/// Add an emoji reaction to a message.
+#[tauri::command]
+pub async fn add_reaction(
+    conversation_id: String,
+    message_id: String,
+    emoji: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    services::message_edit_service::set_reaction(
+        &app,
+        state.inner(),
+        pool.inner(),
+        &conversation_id,
+        &message_id,
+        &emoji,
+        true,
+    )
+    .await
+}
+
+/// Take back one of our reactions.
+#[tauri::command]
+pub async fn remove_reaction(
+    conversation_id: String,
+    message_id: String,
+    emoji: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    services::message_edit_service::set_reaction(
+        &app,
+        state.inner(),
+        pool.inner(),
+        &conversation_id,
+        &message_id,
+        &emoji,
+        false,
+    )
+    .await
+}
+
+/// Set the disappearing-message timer of our DM with a friend, for both
+/// sides. `seconds` of 0 turns it off; messages already sent keep theirs.
+#[tauri::command]
+pub async fn set_disappearing_timer(
+    peer_id: String,
+    seconds: u32,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    services::disappearing_service::set_timer(&app, state.inner(), pool.inner(), &peer_id, seconds).await
+}
+
+/// Share a file with a friend.
+///
+/// The file is chunked, encrypted and uploaded in the background; the
+/// message announcing it is sent once every chunk is in the DHT. Returns
+/// our copy of the message so the chat window can show upload progress.
+#[tauri::command]
+pub async fn send_file(
+    to: String,
+    path: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<Message, String> {
+    services::file_transfer_service::send_file(&app, state.inner(), pool.inner(), &to, &path).await
+}
+
+/// Download a file someone shared with us into the downloads folder.
+///
+/// Picks up where it left off if an earlier download was interrupted.
+#[tauri::command]
+pub async fn download_attachment(
+    message_id: i64,
+    index: u32,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<AttachmentInfo, String> {
+    services::file_transfer_service::download_attachment(
+        &app,
+        state.inner(),
+        pool.inner(),
+        message_id,
+        index,
+    )
+    .await
 }
 
 /// Send a typing indicator to a peer.
@@ -103,7 +326,7 @@ pub async fn get_message_history(
         let conn = pool.lock().map_err(|e| e.to_string())?;
         let mut stmt = conn
             .prepare(
-                "SELECT id, sender_key, body, timestamp FROM messages \
+                "SELECT id, sender_key, body, timestamp, attachment_json, message_id, edited_at, delivery_state FROM messages \
                  WHERE owner_key = ? AND conversation_id = ? AND conversation_type = 'dm' \
                  ORDER BY timestamp ASC LIMIT ?",
             )
@@ -113,19 +336,34 @@ pub async fn get_message_history(
             .query_map(rusqlite::params![ok, peer_id_clone, limit], |row| {
                 let sender = db::get_str(row, "sender_key");
                 let is_own = sender == our_key;
-                Ok(Message {
-                    id: db::get_i64(row, "id"),
-                    sender_id: sender,
-                    body: db::get_str(row, "body"),
-                    timestamp: db::get_i64(row, "timestamp"),
-                    is_own,
-                })
+                Ok((
+                    Message {
+                        id: db::get_i64(row, "id"),
+                        sender_id: sender,
+                        body: db::get_str(row, "body"),
+                        timestamp: db::get_i64(row, "timestamp"),
+                        is_own,
+                        attachments: Vec::new(),
+                        message_id: db::get_str_opt(row, "message_id"),
+                        edited_at: db::get_i64_opt(row, "edited_at"),
+                        reactions: Vec::new(),
+                        delivery_state: db::get_str_opt(row, "delivery_state"),
+                        reply_to: None,
+                    },
+                    db::get_str_opt(row, "attachment_json"),
+                ))
             })
             .map_err(|e| e.to_string())?;
 
         let mut messages = Vec::new();
         for row in rows {
-            messages.push(row.map_err(|e| e.to_string())?);
+            let (mut message, attachment_json) = row.map_err(|e| e.to_string())?;
+            if let Some(json) = attachment_json {
+                message.attachments =
+                    services::file_transfer_service::attachment_infos(&conn, &ok, message.id, &json);
+            }
+            message.reactions = services::message_edit_service::reactions(&conn, message.id, &ok);
+            messages.push(message);
         }
         Ok::<_, String>(messages)
     })
@@ -186,9 +424,13 @@ pub async fn prepare_chat_session(
 }
 
 /// Mark messages as read.
+///
+/// Tells the peer which of their DMs we just read, unless the user has
+/// turned read receipts off.
 #[tauri::command]
 pub async fn mark_read(
     peer_id: String,
+    app: tauri::AppHandle,
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
@@ -198,19 +440,55 @@ pub async fn mark_read(
         friend.unread_count = 0;
     }
 
-    let pool = pool.inner().clone();
+    let pool_clone = pool.inner().clone();
     let peer_id_clone = peer_id.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = pool.lock().map_err(|e| e.to_string())?;
+    let newly_read = tokio::task::spawn_blocking(move || {
+        let conn = pool_clone.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT message_id FROM messages WHERE owner_key = ? AND conversation_id = ? \
+                 AND conversation_type = 'dm' AND sender_key = conversation_id AND is_read = 0 \
+                 AND message_id IS NOT NULL",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(rusqlite::params![owner_key, peer_id_clone], |row| {
+                Ok(db::get_str(row, "message_id"))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut ids = Vec::new();
+        for row in rows {
+            ids.push(row.map_err(|e| e.to_string())?);
+        }
         conn.execute(
             "UPDATE messages SET is_read = 1 WHERE owner_key = ? AND conversation_id = ? AND is_read = 0",
             rusqlite::params![owner_key, peer_id_clone],
         )
         .map_err(|e| e.to_string())?;
-        Ok::<_, String>(())
+        Ok::<_, String>(ids)
     })
     .await
     .map_err(|e| e.to_string())??;
 
+    if receipt_service::read_receipts_enabled(&app) {
+        receipt_service::queue_receipts(
+            state.inner(),
+            pool.inner(),
+            &peer_id,
+            &newly_read,
+            receipt_service::ReceiptKind::Read,
+        );
+    }
+
+    // Our other devices clear the same messages
+    for chunk in newly_read.chunks(MAX_RECEIPT_BATCH) {
+        services::device_service::mirror_to_siblings(
+            state.inner(),
+            pool.inner(),
+            &peer_id,
+            MessagePayload::ReadReceipt { message_ids: chunk.to_vec() },
+        );
+    }
+
     Ok(())
 }
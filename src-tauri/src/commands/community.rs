@@ -1,3 +1,9 @@
+use rekindle_crypto::group::member_vouch::{self, MemberVouch};
+use rekindle_crypto::group::pseudonym::derive_community_pseudonym;
+use rekindle_protocol::messaging::{
+    decode_community_invite_url, encode_community_invite_url, new_message_id, AuditAction,
+    AuditEntryDto, CommunityInviteLink, HistoryVisibility, InviteDto, MemberVouchDto, ThreadDto,
+};
 use serde::{Deserialize, Serialize};
 use tauri::{Emitter, State};
 
@@ -59,6 +65,7 @@ pub struct ChannelInfoDto {
     pub name: String,
     pub channel_type: String,
     pub unread_count: u32,
+    pub retention_secs: u32,
 }
 
 /// Role DTO for frontend consumption (re-exports the channel's `RoleDto`).
@@ -78,6 +85,7 @@ pub struct CommunityDetail {
     pub my_pseudonym_key: Option<String>,
     pub mek_generation: u64,
     pub is_hosted: bool,
+    pub history_visibility: HistoryVisibility,
 }
 
 /// Get all joined communities with full channel details.
@@ -103,6 +111,7 @@ pub async fn get_community_details(
                         ChannelType::Voice => "voice".to_string(),
                     },
                     unread_count: ch.unread_count,
+                    retention_secs: ch.retention_secs,
                 })
                 .collect(),
             my_role: c.my_role.clone(),
@@ -111,6 +120,7 @@ pub async fn get_community_details(
             my_pseudonym_key: c.my_pseudonym_key.clone(),
             mek_generation: c.mek_generation,
             is_hosted: c.is_hosted,
+            history_visibility: c.history_visibility,
         })
         .collect();
     Ok(list)
@@ -125,41 +135,51 @@ pub async fn create_community(
     pool: State<'_, DbPool>,
     keystore_handle: State<'_, KeystoreHandle>,
 ) -> Result<String, String> {
-    let owner_key = current_owner_key(state.inner())?;
     let community_id =
         services::community_service::create_community(state.inner(), &name).await?;
 
-    // Persist MEK to Stronghold for login restoration
-    {
-        use rekindle_crypto::keychain::{mek_key_name, VAULT_COMMUNITIES};
-        use rekindle_crypto::Keychain as _;
+    // Persist MEK keyring to Stronghold for login restoration
+    services::mek_service::persist_keyring(state.inner(), keystore_handle.inner(), &community_id);
 
-        let mek_cache = state.mek_cache.lock();
-        if let Some(mek) = mek_cache.get(&community_id) {
-            let mut mek_payload = Vec::with_capacity(40);
-            mek_payload.extend_from_slice(&mek.generation().to_le_bytes());
-            mek_payload.extend_from_slice(mek.as_bytes());
-
-            let ks = keystore_handle.lock();
-            if let Some(ref keystore) = *ks {
-                let key_name = mek_key_name(&community_id);
-                if let Err(e) = keystore.store_key(VAULT_COMMUNITIES, &key_name, &mek_payload) {
-                    tracing::warn!(error = %e, "failed to persist MEK to Stronghold for new community");
-                } else {
-                    if let Err(e) = keystore.save() {
-                        tracing::warn!(error = %e, "failed to save Stronghold snapshot after MEK persist");
-                    }
-                    tracing::debug!(community = %community_id, "MEK persisted to Stronghold");
-                }
-            }
-        }
-    }
+    persist_hosted_community(state.inner(), pool.inner(), &community_id).await?;
+    ensure_community_hosted(&app, state.inner(), &community_id).await;
+    services::account_service::publish_in_background(state.inner(), pool.inner());
+
+    Ok(community_id)
+}
+
+/// Take back a community we host after a reinstall, with the owner keypair
+/// our account record kept for it.
+pub(crate) async fn restore_hosted_community(
+    app: &tauri::AppHandle,
+    state: &SharedState,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+    name: &str,
+    owner_keypair: &str,
+) -> Result<(), String> {
+    services::community_service::restore_hosted_community(state, community_id, name, owner_keypair)
+        .await?;
+    services::mek_service::persist_keyring(state, keystore_handle, community_id);
+    persist_hosted_community(state, pool, community_id).await?;
+    ensure_community_hosted(app, state, community_id).await;
+    Ok(())
+}
+
+/// Persist a community we host, with ourselves as its owner and first member.
+async fn persist_hosted_community(
+    state: &SharedState,
+    pool: &DbPool,
+    community_id: &str,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
 
     // Read back the community to get default channel info
     let community = {
         let communities = state.communities.read();
         communities
-            .get(&community_id)
+            .get(community_id)
             .cloned()
             .ok_or("community not found after creation")?
     };
@@ -176,13 +196,13 @@ pub async fn create_community(
     // Get pseudonym key for this community
     let my_pseudonym_key = {
         let communities = state.communities.read();
-        communities.get(&community_id).and_then(|c| c.my_pseudonym_key.clone())
+        communities.get(community_id).and_then(|c| c.my_pseudonym_key.clone())
     };
 
     let now = db::timestamp_now();
-    let pool = pool.inner().clone();
-    let community_id_clone = community_id.clone();
-    let name_clone = name.clone();
+    let pool = pool.clone();
+    let community_id_clone = community_id.to_string();
+    let name_clone = community.name.clone();
     let dht_record_key = community.dht_record_key.clone();
     let dht_owner_keypair = community.dht_owner_keypair.clone();
     let pseudonym_key = my_pseudonym_key.clone().unwrap_or_else(|| creator_key.clone());
@@ -195,9 +215,9 @@ pub async fn create_community(
         // Owner gets all default role IDs: @everyone(0), members(1), moderator(2), admin(3), owner(4)
         let owner_role_ids = serde_json::to_string(&[0u32, 1, 2, 3, 4]).unwrap_or_default();
         conn.execute(
-            "INSERT INTO communities (owner_key, id, name, my_role, my_role_ids, joined_at, dht_record_key, dht_owner_keypair, my_pseudonym_key, is_hosted, mek_generation) \
-             VALUES (?, ?, ?, 'owner', ?, ?, ?, ?, ?, 1, ?)",
-            rusqlite::params![ok, community_id_clone, name_clone, owner_role_ids, now, dht_record_key, dht_owner_keypair, pseudonym_key, mek_gen],
+            "INSERT INTO communities (owner_key, id, name, my_role, my_role_ids, joined_at, dht_record_key, dht_owner_keypair, my_pseudonym_key, owner_pseudonym, is_hosted, mek_generation) \
+             VALUES (?, ?, ?, 'owner', ?, ?, ?, ?, ?, ?, 1, ?)",
+            rusqlite::params![ok, community_id_clone, name_clone, owner_role_ids, now, dht_record_key, dht_owner_keypair, pseudonym_key, pseudonym_key, mek_gen],
         )
         .map_err(|e| e.to_string())?;
 
@@ -238,29 +258,55 @@ pub async fn create_community(
         Ok::<_, String>(())
     })
     .await
-    .map_err(|e| e.to_string())??;
-
-    ensure_community_hosted(&app, state.inner(), &community_id).await;
-
-    Ok(community_id)
+    .map_err(|e| e.to_string())?
 }
 
-/// Join an existing community by ID.
+/// Join an existing community by ID or `rekindle://community/` invite link.
+///
+/// Returns the community ID.
 #[tauri::command]
-#[allow(clippy::too_many_lines)]
 pub async fn join_community(
-    community_id: String,
+    invite: String,
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
     keystore_handle: State<'_, KeystoreHandle>,
+) -> Result<String, String> {
+    let invite = invite.trim();
+    let (community_id, link) = if invite.starts_with("rekindle://") {
+        let link = decode_community_invite_url(invite)?;
+        (link.community_id.clone(), Some(link))
+    } else {
+        (invite.to_string(), None)
+    };
+    join_and_persist(
+        state.inner(),
+        pool.inner(),
+        keystore_handle.inner(),
+        &community_id,
+        link.as_ref(),
+    )
+    .await?;
+    services::account_service::publish_in_background(state.inner(), pool.inner());
+    Ok(community_id)
+}
+
+/// Join a community and persist it with its channels and roles.
+#[allow(clippy::too_many_lines)]
+pub(crate) async fn join_and_persist(
+    state: &SharedState,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+    invite: Option<&CommunityInviteLink>,
 ) -> Result<(), String> {
-    let owner_key = current_owner_key(state.inner())?;
-    services::community_service::join_community(state.inner(), &community_id).await?;
+    let owner_key = current_owner_key(state)?;
+    let tree_kem =
+        services::community_service::join_community(state, community_id, invite).await?;
 
     let (name, dht_record_key) = {
         let communities = state.communities.read();
         communities
-            .get(&community_id)
+            .get(community_id)
             .map(|c| (c.name.clone(), c.dht_record_key.clone()))
             .unwrap_or_default()
     };
@@ -274,50 +320,38 @@ pub async fn join_community(
         .unwrap_or_default();
 
     // Get pseudonym key, server_route_blob, mek_generation, and channels from the community state
-    let (my_pseudonym_key, server_route_blob, mek_generation, channels) = {
+    let (my_pseudonym_key, owner_pseudonym, server_route_blob, mek_generation, channels, history_visibility) = {
         let communities = state.communities.read();
         communities
-            .get(&community_id)
+            .get(community_id)
             .map(|c| (
                 c.my_pseudonym_key.clone(),
+                c.owner_pseudonym.clone(),
                 c.server_route_blob.clone(),
                 c.mek_generation,
                 c.channels.clone(),
+                c.history_visibility,
             ))
             .unwrap_or_default()
     };
     let pseudonym_key = my_pseudonym_key.unwrap_or_else(|| owner_key.clone());
 
-    // Persist MEK to Stronghold for login restoration
-    {
-        use rekindle_crypto::keychain::{mek_key_name, VAULT_COMMUNITIES};
-        use rekindle_crypto::Keychain as _;
+    // Persist MEK keyring to Stronghold for login restoration
+    services::mek_service::persist_keyring(state, keystore_handle, community_id);
 
-        let mek_cache = state.mek_cache.lock();
-        if let Some(mek) = mek_cache.get(&community_id) {
-            let mut mek_payload = Vec::with_capacity(40);
-            mek_payload.extend_from_slice(&mek.generation().to_le_bytes());
-            mek_payload.extend_from_slice(mek.as_bytes());
-
-            let ks = keystore_handle.lock();
-            if let Some(ref keystore) = *ks {
-                let key_name = mek_key_name(&community_id);
-                if let Err(e) = keystore.store_key(VAULT_COMMUNITIES, &key_name, &mek_payload) {
-                    tracing::warn!(error = %e, "failed to persist MEK to Stronghold");
-                } else {
-                    if let Err(e) = keystore.save() {
-                        tracing::warn!(error = %e, "failed to save Stronghold snapshot after MEK persist");
-                    }
-                    tracing::debug!(community = %community_id, "MEK persisted to Stronghold");
-                }
-            }
+    // TreeKEM communities add us to the tree once a key holder sees our key package
+    if tree_kem {
+        if let Err(e) = services::tree_service::publish_key_package(
+            state, pool, keystore_handle, community_id,
+        ).await {
+            tracing::warn!(community = %community_id, error = %e, "failed to publish TreeKEM key package");
         }
     }
 
     // Get role_ids and roles from community state (set by join RPC response)
     let (my_role_ids, roles_to_persist) = {
         let communities = state.communities.read();
-        match communities.get(&community_id) {
+        match communities.get(community_id) {
             Some(c) => (c.my_role_ids.clone(), c.roles.clone()),
             None => (vec![0, 1], Vec::new()),
         }
@@ -325,8 +359,8 @@ pub async fn join_community(
     let role_ids_json = serde_json::to_string(&my_role_ids).unwrap_or_else(|_| "[0,1]".to_string());
 
     let now = db::timestamp_now();
-    let pool = pool.inner().clone();
-    let community_id_clone = community_id.clone();
+    let pool = pool.clone();
+    let community_id_clone = community_id.to_string();
     let ok = owner_key;
     let pk = pseudonym_key.clone();
     let srb = server_route_blob.clone();
@@ -335,9 +369,9 @@ pub async fn join_community(
     tokio::task::spawn_blocking(move || {
         let conn = pool.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT OR IGNORE INTO communities (owner_key, id, name, my_role, my_role_ids, joined_at, dht_record_key, my_pseudonym_key, server_route_blob, mek_generation) \
-             VALUES (?, ?, ?, 'member', ?, ?, ?, ?, ?, ?)",
-            rusqlite::params![ok, community_id_clone, name, rij, now, dht_record_key, pk, srb, mg],
+            "INSERT OR IGNORE INTO communities (owner_key, id, name, my_role, my_role_ids, joined_at, dht_record_key, my_pseudonym_key, owner_pseudonym, server_route_blob, mek_generation, history_visibility) \
+             VALUES (?, ?, ?, 'member', ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![ok, community_id_clone, name, rij, now, dht_record_key, pk, owner_pseudonym, srb, mg, history_visibility.as_str()],
         )
         .map_err(|e| e.to_string())?;
 
@@ -356,8 +390,9 @@ pub async fn join_community(
                 crate::state::ChannelType::Voice => "voice",
             };
             conn.execute(
-                "INSERT OR IGNORE INTO channels (owner_key, id, community_id, name, channel_type) VALUES (?, ?, ?, ?, ?)",
-                rusqlite::params![ok, channel.id, community_id_clone, channel.name, ch_type],
+                "INSERT OR IGNORE INTO channels (owner_key, id, community_id, name, channel_type, retention_secs) \
+                 VALUES (?, ?, ?, ?, ?, NULLIF(?, 0))",
+                rusqlite::params![ok, channel.id, community_id_clone, channel.name, ch_type, channel.retention_secs],
             )
             .map_err(|e| e.to_string())?;
         }
@@ -431,6 +466,7 @@ pub async fn create_channel(
                             name: name.clone(),
                             channel_type: ch_type,
                             unread_count: 0,
+                            retention_secs: 0,
                         });
                     }
                 }
@@ -500,35 +536,35 @@ pub async fn create_channel(
     Ok(channel_id)
 }
 
-/// Send a message in a community channel.
+/// Send a message in a community channel or thread, optionally as a reply
+/// to `reply_to` (a message's global ID) in the same one.
 ///
-/// Encrypts the message body with the community's MEK, then sends a
+/// Encrypts the message body with the community's current MEK generation, then sends a
 /// `CommunityRequest::SendMessage` to the community server via `app_call`.
 /// Falls back to local-only storage if the server is unreachable.
+/// Returns the message's global ID.
 #[tauri::command]
 pub async fn send_channel_message(
     channel_id: String,
     body: String,
+    reply_to: Option<String>,
     app: tauri::AppHandle,
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
-) -> Result<(), String> {
+) -> Result<String, String> {
     let owner_key = current_owner_key(state.inner())?;
 
     let timestamp = db::timestamp_now();
+    let message_id = new_message_id();
 
-    // --- Step 1: Find the community and get MEK + server route + pseudonym ---
-    let (community_id, mek_generation, server_route_blob) = {
+    // --- Step 1: Find the community and get server route + pseudonym ---
+    let (community_id, server_route_blob) = {
         let communities = state.communities.read();
         let community = communities
             .values()
-            .find(|c| c.channels.iter().any(|ch| ch.id == channel_id))
+            .find(|c| c.has_conversation(&channel_id))
             .ok_or("channel not found in any community")?;
-        (
-            community.id.clone(),
-            community.mek_generation,
-            community.server_route_blob.clone(),
-        )
+        (community.id.clone(), community.server_route_blob.clone())
     };
 
     // Use pseudonym key as sender for channel messages (matches what the
@@ -541,15 +577,8 @@ pub async fn send_channel_message(
             .unwrap_or_else(|| owner_key.clone())
     };
 
-    // --- Step 2: Encrypt with MEK ---
-    let ciphertext = {
-        let mek_cache = state.mek_cache.lock();
-        let mek = mek_cache.get(&community_id).ok_or_else(|| {
-            "MEK not available — rejoin the community or wait for MEK delivery".to_string()
-        })?;
-        mek.encrypt(body.as_bytes())
-            .map_err(|e| format!("MEK encryption failed: {e}"))?
-    };
+    // --- Step 2: Encrypt with the newest MEK generation ---
+    let (ciphertext, mek_generation) = encrypt_with_current_mek(state.inner(), &community_id, &body)?;
 
     // --- Step 3: Store plaintext in local SQLite FIRST (persist before send) ---
     let pool_for_queue = pool.inner().clone();
@@ -557,13 +586,16 @@ pub async fn send_channel_message(
     let channel_id_clone = channel_id.clone();
     let sender_key_clone = sender_key.clone();
     let body_clone = body.clone();
+    let message_id_clone = message_id.clone();
+    let reply_to_clone = reply_to.clone();
     let ok = owner_key;
     tokio::task::spawn_blocking(move || {
         let conn = pool.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, mek_generation) \
-             VALUES (?, ?, 'channel', ?, ?, ?, 1, ?)",
-            rusqlite::params![ok, channel_id_clone, sender_key_clone, body_clone, timestamp, mek_generation.cast_signed()],
+            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, mek_generation, message_id, reply_to_id) \
+             VALUES (?1, ?2, 'channel', ?3, ?4, ?5, 1, ?6, ?7, \
+                     (SELECT id FROM messages WHERE owner_key = ?1 AND conversation_id = ?2 AND message_id = ?8))",
+            rusqlite::params![ok, channel_id_clone, sender_key_clone, body_clone, timestamp, mek_generation.cast_signed(), message_id_clone, reply_to_clone],
         )
         .map_err(|e| e.to_string())?;
         Ok::<_, String>(())
@@ -572,24 +604,23 @@ pub async fn send_channel_message(
     .map_err(|e| e.to_string())??;
 
     // --- Step 4: Send to community server (best-effort — message already persisted) ---
+    let pending = PendingChannelMessage {
+        community_id,
+        channel_id: channel_id.clone(),
+        message_id: message_id.clone(),
+        ciphertext,
+        mek_generation,
+        timestamp,
+        reply_to: reply_to.clone(),
+    };
     if let Some(route_blob) = server_route_blob {
-        if let Err(e) = send_encrypted_to_server(
-            &state, &channel_id, &community_id, ciphertext.clone(), mek_generation, timestamp, route_blob,
-        )
-        .await
-        {
+        if let Err(e) = send_encrypted_to_server(&state, &pending, route_blob).await {
             tracing::warn!(error = %e, "server delivery failed — queuing for retry");
-            queue_pending_channel_message(
-                &state, &pool_for_queue, &community_id, &channel_id, &ciphertext, mek_generation, timestamp,
-            )
-            .await;
+            queue_pending_channel_message(&state, &pool_for_queue, &pending).await;
         }
     } else {
         tracing::warn!("no server route — message stored locally, queuing for retry");
-        queue_pending_channel_message(
-            &state, &pool_for_queue, &community_id, &channel_id, &ciphertext, mek_generation, timestamp,
-        )
-        .await;
+        queue_pending_channel_message(&state, &pool_for_queue, &pending).await;
     }
 
     // --- Step 5: Emit local echo to frontend ---
@@ -598,11 +629,32 @@ pub async fn send_channel_message(
         body,
         timestamp: timestamp.cast_unsigned(),
         conversation_id: channel_id,
+        attachments: Vec::new(),
+        message_id: Some(message_id.clone()),
+        reply_to,
     };
     let _ = app.emit("chat-event", &event);
 
     tracing::info!("channel message sent");
-    Ok(())
+    Ok(message_id)
+}
+
+/// Encrypt `body` with a community's newest MEK generation.
+///
+/// Returns the ciphertext and the generation it was sealed with.
+pub(crate) fn encrypt_with_current_mek(
+    state: &SharedState,
+    community_id: &str,
+    body: &str,
+) -> Result<(Vec<u8>, u64), String> {
+    let mek_cache = state.mek_cache.lock();
+    let mek = mek_cache.get(community_id).and_then(|ring| ring.current()).ok_or_else(|| {
+        "MEK not available — rejoin the community or wait for MEK delivery".to_string()
+    })?;
+    let ciphertext = mek
+        .encrypt(body.as_bytes())
+        .map_err(|e| format!("MEK encryption failed: {e}"))?;
+    Ok((ciphertext, mek.generation()))
 }
 
 /// Pending channel message queued for retry delivery to the community server.
@@ -611,9 +663,12 @@ pub async fn send_channel_message(
 pub(crate) struct PendingChannelMessage {
     pub community_id: String,
     pub channel_id: String,
+    pub message_id: String,
     pub ciphertext: Vec<u8>,
     pub mek_generation: u64,
     pub timestamp: i64,
+    #[serde(default)]
+    pub reply_to: Option<String>,
 }
 
 /// Queue a failed channel message for retry via `pending_messages` table.
@@ -623,20 +678,9 @@ pub(crate) struct PendingChannelMessage {
 async fn queue_pending_channel_message(
     state: &SharedState,
     pool: &DbPool,
-    community_id: &str,
-    channel_id: &str,
-    ciphertext: &[u8],
-    mek_generation: u64,
-    timestamp: i64,
+    pending: &PendingChannelMessage,
 ) {
-    let pending = PendingChannelMessage {
-        community_id: community_id.to_string(),
-        channel_id: channel_id.to_string(),
-        ciphertext: ciphertext.to_vec(),
-        mek_generation,
-        timestamp,
-    };
-    let body = match serde_json::to_string(&pending) {
+    let body = match serde_json::to_string(pending) {
         Ok(b) => b,
         Err(e) => {
             tracing::error!(error = %e, "failed to serialize pending channel message");
@@ -651,7 +695,7 @@ async fn queue_pending_channel_message(
         .map(|id| id.public_key.clone())
         .unwrap_or_default();
     let pool = pool.clone();
-    let recipient = community_id.to_string();
+    let recipient = pending.community_id.clone();
     let now = crate::db::timestamp_now();
     if let Err(e) = tokio::task::spawn_blocking(move || {
         let conn = pool.lock().map_err(|e| e.to_string())?;
@@ -684,13 +728,11 @@ fn rand_nonce() -> Vec<u8> {
 /// before this is called.
 pub(crate) async fn send_encrypted_to_server(
     state: &SharedState,
-    channel_id: &str,
-    community_id: &str,
-    ciphertext: Vec<u8>,
-    mek_generation: u64,
-    timestamp: i64,
+    message: &PendingChannelMessage,
     route_blob: Vec<u8>,
 ) -> Result<(), String> {
+    let channel_id = message.channel_id.as_str();
+    let community_id = message.community_id.as_str();
     let routing_context = {
         let node = state.node.read();
         node.as_ref()
@@ -704,8 +746,10 @@ pub(crate) async fn send_encrypted_to_server(
 
     let request = rekindle_protocol::messaging::CommunityRequest::SendMessage {
         channel_id: channel_id.to_string(),
-        ciphertext,
-        mek_generation,
+        message_id: message.message_id.clone(),
+        ciphertext: message.ciphertext.clone(),
+        mek_generation: message.mek_generation,
+        reply_to: message.reply_to.clone(),
     };
     let request_bytes = serde_json::to_vec(&request)
         .map_err(|e| format!("failed to serialize request: {e}"))?;
@@ -722,7 +766,7 @@ pub(crate) async fn send_encrypted_to_server(
         rekindle_crypto::group::pseudonym::derive_community_pseudonym(&secret, community_id);
     let envelope = rekindle_protocol::messaging::sender::build_envelope(
         &pseudonym_key,
-        timestamp.cast_unsigned(),
+        message.timestamp.cast_unsigned(),
         rand_nonce(),
         request_bytes,
     );
@@ -798,7 +842,7 @@ fn check_server_response(channel_id: &str, response_bytes: &[u8]) -> Result<(),
 ///
 /// For **remote** communities, signs the request with the user's pseudonym key,
 /// wraps it in a `MessageEnvelope`, and sends it via Veilid `app_call`.
-async fn send_community_rpc(
+pub(crate) async fn send_community_rpc(
     state: &SharedState,
     pool: &DbPool,
     community_id: &str,
@@ -1129,35 +1173,22 @@ pub async fn leave_community(
     )
     .await;
 
-    // Remove MEK from cache
-    state.mek_cache.lock().remove(&community_id);
-
-    // Remove MEK from Stronghold
-    {
-        use rekindle_crypto::keychain::{mek_key_name, VAULT_COMMUNITIES};
-        use rekindle_crypto::Keychain as _;
-
-        let ks = keystore_handle.lock();
-        if let Some(ref keystore) = *ks {
-            let key_name = mek_key_name(&community_id);
-            if let Err(e) = keystore.delete_key(VAULT_COMMUNITIES, &key_name) {
-                tracing::warn!(error = %e, "failed to remove MEK from Stronghold");
-            }
-        }
-    }
+    // Remove every MEK generation from cache and Stronghold
+    services::mek_service::forget_community(state.inner(), keystore_handle.inner(), &community_id);
 
-    // Remove cached server route
+    // Remove cached server route and list revisions
     state.community_routes.write().remove(&community_id);
+    state.community_lists.write().retain(|(key, _), _| key != &community_id);
 
     // Remove from local state
     state.communities.write().remove(&community_id);
 
     // Remove from SQLite (CASCADE on communities handles channels)
     let owner_key = current_owner_key(state.inner())?;
-    let pool = pool.inner().clone();
+    let pool_clone = pool.inner().clone();
     let community_id_clone = community_id.clone();
     tokio::task::spawn_blocking(move || {
-        let conn = pool.lock().map_err(|e| e.to_string())?;
+        let conn = pool_clone.lock().map_err(|e| e.to_string())?;
         conn.execute(
             "DELETE FROM communities WHERE owner_key = ? AND id = ?",
             rusqlite::params![owner_key, community_id_clone],
@@ -1168,6 +1199,7 @@ pub async fn leave_community(
     .await
     .map_err(|e| e.to_string())??;
 
+    services::account_service::publish_in_background(state.inner(), pool.inner());
     tracing::info!(community = %community_id, "left community");
     Ok(())
 }
@@ -1184,6 +1216,7 @@ pub async fn get_channel_messages(
     app: tauri::AppHandle,
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
+    keystore_handle: State<'_, KeystoreHandle>,
 ) -> Result<Vec<Message>, String> {
     let our_key = current_owner_key(state.inner()).unwrap_or_default();
 
@@ -1192,7 +1225,7 @@ pub async fn get_channel_messages(
         let communities = state.communities.read();
         let community = communities
             .values()
-            .find(|c| c.channels.iter().any(|ch| ch.id == channel_id));
+            .find(|c| c.has_conversation(&channel_id));
         match community {
             Some(c) => (
                 Some(c.id.clone()),
@@ -1211,9 +1244,11 @@ pub async fn get_channel_messages(
         let conn = pool_clone.lock().map_err(|e| e.to_string())?;
         let mut stmt = conn
             .prepare(
-                "SELECT id, sender_key, body, timestamp FROM messages \
-                 WHERE owner_key = ? AND conversation_id = ? AND conversation_type = 'channel' \
-                 ORDER BY timestamp DESC LIMIT ?",
+                "SELECT m.id, m.sender_key, m.body, m.timestamp, m.message_id, m.edited_at, \
+                        r.message_id AS reply_to \
+                 FROM messages m LEFT JOIN messages r ON r.id = m.reply_to_id \
+                 WHERE m.owner_key = ? AND m.conversation_id = ? AND m.conversation_type = 'channel' \
+                 ORDER BY m.timestamp DESC LIMIT ?",
             )
             .map_err(|e| e.to_string())?;
 
@@ -1228,13 +1263,21 @@ pub async fn get_channel_messages(
                     body: db::get_str(row, "body"),
                     timestamp: db::get_i64(row, "timestamp"),
                     is_own,
+                    attachments: Vec::new(),
+                    message_id: db::get_str_opt(row, "message_id"),
+                    edited_at: db::get_i64_opt(row, "edited_at"),
+                    reactions: Vec::new(),
+                    delivery_state: None,
+                    reply_to: db::get_str_opt(row, "reply_to"),
                 })
             })
             .map_err(|e| e.to_string())?;
 
         let mut messages = Vec::new();
         for row in rows {
-            messages.push(row.map_err(|e| e.to_string())?);
+            let mut message = row.map_err(|e| e.to_string())?;
+            message.reactions = services::message_edit_service::reactions(&conn, message.id, &mpk);
+            messages.push(message);
         }
         Ok::<_, String>(messages)
     })
@@ -1256,6 +1299,7 @@ pub async fn get_channel_messages(
     if let Some(cid) = community_id {
         let state = state.inner().clone();
         let pool = pool.inner().clone();
+        let keystore_handle = keystore_handle.inner().clone();
         let channel_id = channel_id.clone();
         let our_key = our_key.clone();
         let my_pseudonym_key = my_pseudonym_key.clone();
@@ -1263,6 +1307,7 @@ pub async fn get_channel_messages(
             let server_messages = fetch_channel_history_from_server(
                 &state,
                 &pool,
+                &keystore_handle,
                 &cid,
                 &channel_id,
                 &our_key,
@@ -1294,6 +1339,7 @@ pub async fn get_channel_messages(
 async fn fetch_channel_history_from_server(
     state: &SharedState,
     pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
     community_id: &str,
     channel_id: &str,
     owner_key: &str,
@@ -1320,33 +1366,57 @@ async fn fetch_channel_history_from_server(
         return Vec::new();
     }
 
-    // Decrypt with cached MEK — scope the guard so it's dropped before any .await
-    let decrypted: Vec<(String, String, i64, i64)> = {
+    // Ask for any generations we missed (e.g. rotations while we were offline)
+    let missing: std::collections::BTreeSet<u64> = {
+        let mek_cache = state.mek_cache.lock();
+        let ring = mek_cache.get(community_id);
+        server_messages
+            .iter()
+            .map(|msg| msg.mek_generation)
+            .filter(|generation| ring.is_none_or(|r| r.get(*generation).is_none()))
+            .collect()
+    };
+    for generation in missing {
+        match services::mek_service::fetch_generation(state, pool, community_id, generation).await {
+            Ok(Some(mek)) => {
+                services::mek_service::store_mek(state, keystore_handle, community_id, mek);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::debug!(community = %community_id, generation, error = %e, "failed to fetch missing MEK generation");
+            }
+        }
+    }
+
+    // Decrypt with the matching MEK generation — scope the guard so it's dropped before any .await
+    let decrypted: Vec<HistoryMessage> = {
         let mek_cache = state.mek_cache.lock();
-        let Some(mek) = mek_cache.get(community_id) else {
+        let Some(ring) = mek_cache.get(community_id) else {
             tracing::warn!(community = %community_id, "no MEK to decrypt server history");
             return Vec::new();
         };
 
         let mut result = Vec::new();
         for msg in &server_messages {
-            if msg.mek_generation != mek.generation() {
+            let Some(mek) = ring.get(msg.mek_generation) else {
                 tracing::debug!(
-                    have = mek.generation(),
                     need = msg.mek_generation,
-                    "skipping message with different MEK generation"
+                    "skipping message from an MEK generation we don't hold"
                 );
                 continue;
-            }
+            };
             match mek.decrypt(&msg.ciphertext) {
                 Ok(plaintext) => {
-                    let body = String::from_utf8(plaintext).unwrap_or_default();
-                    result.push((
-                        msg.sender_pseudonym.clone(),
-                        body,
-                        msg.timestamp.cast_signed(),
-                        msg.mek_generation.cast_signed(),
-                    ));
+                    result.push(HistoryMessage {
+                        message_id: msg.message_id.clone(),
+                        sender: msg.sender_pseudonym.clone(),
+                        body: String::from_utf8(plaintext).unwrap_or_default(),
+                        timestamp: msg.timestamp.cast_signed(),
+                        mek_generation: msg.mek_generation.cast_signed(),
+                        edited_at: msg.edited_at.map(u64::cast_signed),
+                        reactions: msg.reactions.clone(),
+                        reply_to: msg.reply_to.clone(),
+                    });
                 }
                 Err(e) => {
                     tracing::debug!(error = %e, "failed to decrypt historical message");
@@ -1361,34 +1431,92 @@ async fn fetch_channel_history_from_server(
     let ok = owner_key.to_string();
     let cid = channel_id.to_string();
     let mpk = my_pseudonym_key.to_string();
-    let decrypted_clone = decrypted.clone();
-    let _ = tokio::task::spawn_blocking(move || {
+    let messages = tokio::task::spawn_blocking(move || {
         let conn = pool.lock().map_err(|e| e.to_string())?;
-        for (sender, body, ts, mg) in &decrypted_clone {
+        let mut messages = Vec::with_capacity(decrypted.len());
+        for msg in decrypted {
+            // Known messages pick up any edits made while we were away
             let _ = conn.execute(
-                "INSERT OR IGNORE INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, mek_generation) \
-                 VALUES (?, ?, 'channel', ?, ?, ?, 0, ?)",
-                rusqlite::params![ok, cid, sender, body, ts, mg],
+                "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, mek_generation, message_id, edited_at, reply_to_id) \
+                 VALUES (?1, ?2, 'channel', ?3, ?4, ?5, 0, ?6, ?7, ?8, \
+                         (SELECT id FROM messages WHERE owner_key = ?1 AND conversation_id = ?2 AND message_id = ?9)) \
+                 ON CONFLICT (owner_key, conversation_id, message_id) WHERE message_id IS NOT NULL \
+                 DO UPDATE SET body = excluded.body, edited_at = excluded.edited_at",
+                rusqlite::params![ok, cid, msg.sender, msg.body, msg.timestamp, msg.mek_generation, msg.message_id, msg.edited_at, msg.reply_to],
             );
+            let row_id = conn
+                .query_row(
+                    "SELECT id FROM messages WHERE owner_key = ? AND conversation_id = ? AND message_id = ?",
+                    rusqlite::params![ok, cid, msg.message_id],
+                    |row| row.get::<_, i64>(0),
+                )
+                .unwrap_or(0);
+            if row_id != 0 {
+                store_history_reactions(&conn, row_id, &msg.reactions);
+            }
+            messages.push(Message {
+                id: row_id,
+                is_own: msg.sender == mpk,
+                sender_id: msg.sender,
+                body: msg.body,
+                timestamp: msg.timestamp,
+                attachments: Vec::new(),
+                message_id: Some(msg.message_id),
+                edited_at: msg.edited_at,
+                reactions: services::message_edit_service::reactions(&conn, row_id, &mpk),
+                delivery_state: None,
+                reply_to: msg.reply_to,
+            });
         }
-        Ok::<_, String>(())
+        Ok::<_, String>(messages)
     })
     .await;
 
-    // Build Message structs for the frontend
-    decrypted
-        .into_iter()
-        .map(|(sender, body, ts, _mg)| {
-            let is_own = sender == mpk;
-            Message {
-                id: 0, // temporary — will get real IDs on next query from SQLite
-                sender_id: sender,
-                body,
-                timestamp: ts,
-                is_own,
-            }
-        })
-        .collect()
+    match messages {
+        Ok(Ok(messages)) => messages,
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "failed to store channel history");
+            Vec::new()
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to store channel history");
+            Vec::new()
+        }
+    }
+}
+
+/// A channel message from the server's history, decrypted.
+struct HistoryMessage {
+    message_id: String,
+    sender: String,
+    body: String,
+    timestamp: i64,
+    mek_generation: i64,
+    edited_at: Option<i64>,
+    reactions: Vec<rekindle_protocol::messaging::ReactionDto>,
+    reply_to: Option<String>,
+}
+
+/// Replace a message's stored reactions with the server's.
+fn store_history_reactions(
+    conn: &rusqlite::Connection,
+    row_id: i64,
+    reactions: &[rekindle_protocol::messaging::ReactionDto],
+) {
+    let _ = conn.execute(
+        "DELETE FROM message_reactions WHERE message_row_id = ?",
+        rusqlite::params![row_id],
+    );
+    let now = db::timestamp_now();
+    for reaction in reactions {
+        for reactor in &reaction.pseudonym_keys {
+            let _ = conn.execute(
+                "INSERT OR IGNORE INTO message_reactions (message_row_id, reactor_key, emoji, created_at) \
+                 VALUES (?, ?, ?, ?)",
+                rusqlite::params![row_id, reactor, reaction.emoji, now],
+            );
+        }
+    }
 }
 
 /// Remove a member from a community.
@@ -2210,12 +2338,75 @@ pub async fn rename_channel(
     Ok(())
 }
 
-/// Update community metadata (name, description).
+/// Keep a channel's messages for `seconds` only (0 keeps them forever).
+///
+/// The server enforces it on its copy; every member's device purges its
+/// own copy of messages that arrive from then on.
+#[tauri::command]
+pub async fn set_channel_retention(
+    community_id: String,
+    channel_id: String,
+    seconds: u32,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state.inner())?;
+
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::SetChannelRetention {
+            channel_id: channel_id.clone(),
+            seconds,
+        },
+    )
+    .await?;
+
+    if let rekindle_protocol::messaging::CommunityResponse::Error { message, .. } = response {
+        return Err(format!("server rejected channel retention: {message}"));
+    }
+
+    // Update local state
+    {
+        let mut communities = state.communities.write();
+        if let Some(community) = communities.get_mut(&community_id) {
+            if let Some(ch) = community.channels.iter_mut().find(|ch| ch.id == channel_id) {
+                ch.retention_secs = seconds;
+            }
+        }
+    }
+
+    // Update local SQLite
+    let pool = pool.inner().clone();
+    let community_id_clone = community_id.clone();
+    let channel_id_clone = channel_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE channels SET retention_secs = NULLIF(?, 0) WHERE owner_key = ? AND id = ? AND community_id = ?",
+            rusqlite::params![seconds, owner_key, channel_id_clone, community_id_clone],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<_, String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    tracing::info!(community = %community_id, channel = %channel_id, seconds, "channel retention set");
+    Ok(())
+}
+
+/// Update community metadata (name, description) and settings.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn update_community_info(
     community_id: String,
     name: Option<String>,
     description: Option<String>,
+    history_visibility: Option<HistoryVisibility>,
+    invite_only: Option<bool>,
+    audit_retention_secs: Option<u32>,
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
@@ -2228,6 +2419,9 @@ pub async fn update_community_info(
         rekindle_protocol::messaging::CommunityRequest::UpdateCommunity {
             name: name.clone(),
             description: description.clone(),
+            history_visibility,
+            invite_only,
+            audit_retention_secs,
         },
     )
     .await;
@@ -2255,6 +2449,9 @@ pub async fn update_community_info(
             if let Some(ref d) = description {
                 community.description = Some(d.clone());
             }
+            if let Some(v) = history_visibility {
+                community.history_visibility = v;
+            }
         }
     }
 
@@ -2277,6 +2474,13 @@ pub async fn update_community_info(
             )
             .map_err(|e| e.to_string())?;
         }
+        if let Some(v) = history_visibility {
+            conn.execute(
+                "UPDATE communities SET history_visibility = ? WHERE owner_key = ? AND id = ?",
+                rusqlite::params![v.as_str(), owner_key, cid],
+            )
+            .map_err(|e| e.to_string())?;
+        }
         Ok::<_, String>(())
     })
     .await
@@ -2392,27 +2596,650 @@ pub async fn get_ban_list(
     }
 }
 
-/// Force MEK rotation for a community.
+/// A page of a community's audit log for the frontend.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommunityAuditLog {
+    /// Newest first.
+    pub entries: Vec<AuditEntryDto>,
+    /// Seconds entries are kept for; 0 keeps them forever.
+    pub retention_secs: u32,
+}
+
+/// Get a page of a community's audit log, older than entry `before` when
+/// loading more. Needs `VIEW_AUDIT_LOG`.
 #[tauri::command]
-pub async fn rotate_mek(
+pub async fn get_audit_log(
     community_id: String,
+    before: Option<u64>,
+    limit: u32,
+    action_filter: Option<AuditAction>,
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
-) -> Result<(), String> {
+) -> Result<CommunityAuditLog, String> {
     let response = send_community_rpc(
         state.inner(),
         pool.inner(),
         &community_id,
-        rekindle_protocol::messaging::CommunityRequest::RotateMEK,
+        rekindle_protocol::messaging::CommunityRequest::GetAuditLog {
+            before,
+            limit,
+            action_filter,
+        },
     )
-    .await?;
+    .await;
 
-    if let rekindle_protocol::messaging::CommunityResponse::Error { message, .. } = response {
-        return Err(format!("server rejected MEK rotation: {message}"));
+    match response {
+        Ok(rekindle_protocol::messaging::CommunityResponse::AuditLog {
+            entries,
+            retention_secs,
+        }) => Ok(CommunityAuditLog {
+            entries,
+            retention_secs,
+        }),
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
+            Err(format!("server rejected audit log request: {message}"))
+        }
+        Ok(_) => Err("unexpected response from server".into()),
+        Err(e) => Err(e),
     }
+}
 
-    tracing::info!(community = %community_id, "MEK rotation requested");
-    Ok(())
+/// Get a channel's threads we can see, most recently active first, and
+/// remember them so messages can be sent to and fetched from them.
+#[tauri::command]
+pub async fn get_channel_threads(
+    community_id: String,
+    channel_id: String,
+    include_archived: bool,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<Vec<ThreadDto>, String> {
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::GetThreads {
+            channel_id,
+            include_archived,
+        },
+    )
+    .await;
+
+    match response {
+        Ok(rekindle_protocol::messaging::CommunityResponse::Threads { threads }) => {
+            if let Some(community) = state.communities.write().get_mut(&community_id) {
+                for thread in &threads {
+                    community.upsert_thread(thread.clone());
+                }
+            }
+            Ok(threads)
+        }
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
+            Err(format!("server rejected thread list request: {message}"))
+        }
+        Ok(_) => Err("unexpected response from server".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Start a thread from a message in a text channel. Needs
+/// `CREATE_PUBLIC_THREADS`, or `CREATE_PRIVATE_THREADS` for a private one.
+#[tauri::command]
+pub async fn create_thread(
+    community_id: String,
+    channel_id: String,
+    parent_message_id: String,
+    name: String,
+    private: bool,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<ThreadDto, String> {
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::CreateThread {
+            channel_id,
+            parent_message_id,
+            name,
+            private,
+        },
+    )
+    .await;
+
+    match response {
+        Ok(rekindle_protocol::messaging::CommunityResponse::ThreadCreated { thread }) => {
+            if let Some(community) = state.communities.write().get_mut(&community_id) {
+                community.upsert_thread(thread.clone());
+            }
+            tracing::info!(community = %community_id, thread = %thread.id, "thread created");
+            Ok(thread)
+        }
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
+            Err(format!("server rejected thread creation: {message}"))
+        }
+        Ok(_) => Err("unexpected response from server".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Archive or reopen a thread: our own, or any with `MANAGE_THREADS`.
+#[tauri::command]
+pub async fn archive_thread(
+    community_id: String,
+    thread_id: String,
+    archived: bool,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let request = rekindle_protocol::messaging::CommunityRequest::ArchiveThread {
+        thread_id,
+        archived,
+    };
+    send_thread_request(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        request,
+        "thread archive",
+    )
+    .await
+}
+
+/// Join a public thread.
+#[tauri::command]
+pub async fn join_thread(
+    community_id: String,
+    thread_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let request = rekindle_protocol::messaging::CommunityRequest::JoinThread { thread_id };
+    send_thread_request(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        request,
+        "thread join",
+    )
+    .await
+}
+
+/// Leave a thread.
+#[tauri::command]
+pub async fn leave_thread(
+    community_id: String,
+    thread_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let request = rekindle_protocol::messaging::CommunityRequest::LeaveThread { thread_id };
+    send_thread_request(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        request,
+        "thread leave",
+    )
+    .await
+}
+
+/// Add a member to a thread we are in, or to any with `MANAGE_THREADS`.
+#[tauri::command]
+pub async fn add_thread_member(
+    community_id: String,
+    thread_id: String,
+    pseudonym_key: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let request = rekindle_protocol::messaging::CommunityRequest::AddThreadMember {
+        thread_id,
+        target_pseudonym: pseudonym_key,
+    };
+    send_thread_request(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        request,
+        "thread member add",
+    )
+    .await
+}
+
+/// Remove a member from a thread we started, or from any with
+/// `MANAGE_THREADS`.
+#[tauri::command]
+pub async fn remove_thread_member(
+    community_id: String,
+    thread_id: String,
+    pseudonym_key: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let request = rekindle_protocol::messaging::CommunityRequest::RemoveThreadMember {
+        thread_id,
+        target_pseudonym: pseudonym_key,
+    };
+    send_thread_request(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        request,
+        "thread member removal",
+    )
+    .await
+}
+
+/// Send a thread request answered with `Ok`. The thread's new state
+/// arrives as a `ThreadUpdated` broadcast.
+async fn send_thread_request(
+    state: &SharedState,
+    pool: &DbPool,
+    community_id: &str,
+    request: rekindle_protocol::messaging::CommunityRequest,
+    what: &str,
+) -> Result<(), String> {
+    match send_community_rpc(state, pool, community_id, request).await? {
+        rekindle_protocol::messaging::CommunityResponse::Error { message, .. } => {
+            Err(format!("server rejected {what}: {message}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A community's directory listing settings for the frontend.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommunityListing {
+    pub listed: bool,
+    pub game_ids: Vec<u32>,
+}
+
+/// Get whether a community is listed in the public directory.
+#[tauri::command]
+pub async fn get_community_listing(
+    community_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<CommunityListing, String> {
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::GetListing,
+    )
+    .await;
+
+    match response {
+        Ok(rekindle_protocol::messaging::CommunityResponse::Listing { listed, game_ids }) => {
+            Ok(CommunityListing { listed, game_ids })
+        }
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
+            Err(format!("server rejected listing request: {message}"))
+        }
+        Ok(_) => Err("unexpected response from server".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// List a community in the public directory under `game_ids`, or take it
+/// out. Its server publishes the listing and keeps it fresh.
+#[tauri::command]
+pub async fn set_community_listing(
+    community_id: String,
+    listed: bool,
+    game_ids: Vec<u32>,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<CommunityListing, String> {
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::SetListing { listed, game_ids },
+    )
+    .await;
+
+    match response {
+        Ok(rekindle_protocol::messaging::CommunityResponse::Listing { listed, game_ids }) => {
+            tracing::info!(community = %community_id, listed, "directory listing updated");
+            Ok(CommunityListing { listed, game_ids })
+        }
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
+            Err(format!("server rejected listing change: {message}"))
+        }
+        Ok(_) => Err("unexpected response from server".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// An invite code for the frontend, with its shareable link.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommunityInviteInfo {
+    pub code: String,
+    pub link: String,
+    pub creator_pseudonym: String,
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    pub expires_at: Option<u64>,
+    pub grant_role_ids: Vec<u32>,
+    pub created_at: u64,
+}
+
+impl CommunityInviteInfo {
+    /// Links to our own invites carry the invite key and the owner's
+    /// pseudonym, so whoever joins with one can be vouched for.
+    fn new(state: &SharedState, community_id: &str, invite: InviteDto) -> Self {
+        let (community_name, my_pseudonym, owner) = state
+            .communities
+            .read()
+            .get(community_id)
+            .map(|c| (c.name.clone(), c.my_pseudonym_key.clone(), c.owner_pseudonym.clone()))
+            .unwrap_or_default();
+        let invite_secret = (my_pseudonym.as_ref() == Some(&invite.creator_pseudonym))
+            .then(|| invite_key(state, community_id, &invite.code))
+            .flatten()
+            .map(|(_, key)| hex::encode(key.to_bytes()));
+        let link = encode_community_invite_url(&CommunityInviteLink {
+            community_id: community_id.to_string(),
+            code: invite.code.clone(),
+            community_name,
+            owner: invite_secret.is_some().then_some(owner).flatten(),
+            invite_secret,
+        });
+        Self {
+            code: invite.code,
+            link,
+            creator_pseudonym: invite.creator_pseudonym,
+            max_uses: invite.max_uses,
+            uses: invite.uses,
+            expires_at: invite.expires_at,
+            grant_role_ids: invite.grant_role_ids,
+            created_at: invite.created_at,
+        }
+    }
+}
+
+/// A community's invites visible to us, and whether joining needs one.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommunityInvites {
+    pub invite_only: bool,
+    pub invites: Vec<CommunityInviteInfo>,
+}
+
+/// Our pseudonym in `community_id` and the key for our invite `code`.
+fn invite_key(
+    state: &SharedState,
+    community_id: &str,
+    code: &str,
+) -> Option<(ed25519_dalek::SigningKey, ed25519_dalek::SigningKey)> {
+    let secret = (*state.identity_secret.lock())?;
+    let pseudonym = derive_community_pseudonym(&secret, community_id);
+    let key = member_vouch::invite_key(&pseudonym, code);
+    Some((pseudonym, key))
+}
+
+/// Create an invite that stops working after `max_uses` joins or
+/// `expires_in_secs` from now, and grants `grant_role_ids` to whoever
+/// joins with it.
+#[tauri::command]
+pub async fn create_community_invite(
+    community_id: String,
+    max_uses: Option<u32>,
+    expires_in_secs: Option<u64>,
+    grant_role_ids: Vec<u32>,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<CommunityInviteInfo, String> {
+    let now_secs = (db::timestamp_now() / 1000).cast_unsigned();
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::CreateInvite {
+            max_uses,
+            expires_at: expires_in_secs.map(|secs| now_secs.saturating_add(secs)),
+            grant_role_ids,
+        },
+    )
+    .await;
+
+    match response {
+        Ok(rekindle_protocol::messaging::CommunityResponse::InviteCreated { invite }) => {
+            tracing::info!(community = %community_id, "invite created");
+            // Vouch for the invite key, which vouches for whoever joins with it
+            if let Some((pseudonym, key)) = invite_key(state.inner(), &community_id, &invite.code) {
+                let vouch = member_vouch::vouch(&pseudonym, &community_id, &key.verifying_key().to_bytes());
+                if let Err(e) = vouch_members(state.inner(), pool.inner(), &community_id, vec![vouch]).await {
+                    tracing::warn!(community = %community_id, error = %e, "failed to vouch for invite key");
+                }
+            }
+            Ok(CommunityInviteInfo::new(state.inner(), &community_id, invite))
+        }
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
+            Err(format!("server rejected invite: {message}"))
+        }
+        Ok(_) => Err("unexpected response from server".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// List a community's invites.
+#[tauri::command]
+pub async fn list_community_invites(
+    community_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<CommunityInvites, String> {
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::ListInvites,
+    )
+    .await;
+
+    match response {
+        Ok(rekindle_protocol::messaging::CommunityResponse::Invites { invite_only, invites }) => {
+            Ok(CommunityInvites {
+                invite_only,
+                invites: invites
+                    .into_iter()
+                    .map(|invite| CommunityInviteInfo::new(state.inner(), &community_id, invite))
+                    .collect(),
+            })
+        }
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
+            Err(format!("server rejected invite list request: {message}"))
+        }
+        Ok(_) => Err("unexpected response from server".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Revoke an invite so nobody else can join with it.
+#[tauri::command]
+pub async fn revoke_community_invite(
+    community_id: String,
+    code: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::RevokeInvite { code },
+    )
+    .await?;
+
+    if let rekindle_protocol::messaging::CommunityResponse::Error { message, .. } = response {
+        return Err(format!("server rejected invite revocation: {message}"));
+    }
+
+    tracing::info!(community = %community_id, "invite revoked");
+    Ok(())
+}
+
+/// Search the public community directory, optionally by game.
+#[tauri::command]
+pub async fn search_directory(
+    query: String,
+    game_id: Option<u32>,
+    state: State<'_, SharedState>,
+) -> Result<Vec<services::directory_service::DirectoryEntry>, String> {
+    services::directory_service::search(state.inner(), &query, game_id).await
+}
+
+/// Preview a community found in the directory before joining it.
+#[tauri::command]
+pub async fn preview_directory_listing(
+    community_id: String,
+    state: State<'_, SharedState>,
+) -> Result<services::directory_service::DirectoryPreview, String> {
+    services::directory_service::preview(state.inner(), &community_id).await
+}
+
+/// Force MEK rotation for a community.
+#[tauri::command]
+pub async fn rotate_mek(
+    community_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::RotateMEK,
+    )
+    .await?;
+
+    if let rekindle_protocol::messaging::CommunityResponse::Error { message, .. } = response {
+        return Err(format!("server rejected MEK rotation: {message}"));
+    }
+
+    tracing::info!(community = %community_id, "MEK rotation requested");
+    Ok(())
+}
+
+/// Store `vouches` made with our pseudonym on the community server.
+async fn vouch_members(
+    state: &SharedState,
+    pool: &DbPool,
+    community_id: &str,
+    vouches: Vec<MemberVouch>,
+) -> Result<(), String> {
+    let response = send_community_rpc(
+        state,
+        pool,
+        community_id,
+        rekindle_protocol::messaging::CommunityRequest::VouchMembers {
+            vouches: vouches.iter().map(MemberVouchDto::from).collect(),
+        },
+    )
+    .await?;
+
+    if let rekindle_protocol::messaging::CommunityResponse::Error { message, .. } = response {
+        return Err(format!("server rejected vouch: {message}"));
+    }
+    Ok(())
+}
+
+/// Vouch for a member who joined without one of our invites, so key
+/// holders in a zero-knowledge community wrap the MEK for them too.
+#[tauri::command]
+pub async fn vouch_community_member(
+    community_id: String,
+    pseudonym_key: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let member = hex::decode(&pseudonym_key)
+        .ok()
+        .and_then(|key| <[u8; 32]>::try_from(key).ok())
+        .ok_or("invalid pseudonym key")?;
+    let secret = (*state.identity_secret.lock()).ok_or("identity not unlocked")?;
+    let pseudonym = derive_community_pseudonym(&secret, &community_id);
+    let vouch = member_vouch::vouch(&pseudonym, &community_id, &member);
+    vouch_members(state.inner(), pool.inner(), &community_id, vec![vouch]).await?;
+    tracing::info!(community = %community_id, member = %pseudonym_key, "vouched for member");
+    Ok(())
+}
+
+/// Hand a community's MEK custody to its privileged members (owner only).
+///
+/// Everyone in our member list is vouched for first: the server already
+/// held the key for all of them. The server then deletes its copy of
+/// every generation and asks key holders to publish a fresh one. This
+/// cannot be undone.
+#[tauri::command]
+pub async fn enable_zero_knowledge(
+    community_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let secret = (*state.identity_secret.lock()).ok_or("identity not unlocked")?;
+    let pseudonym = derive_community_pseudonym(&secret, &community_id);
+    let owner_key = current_owner_key(state.inner())?;
+    let db = pool.inner().clone();
+    let cid = community_id.clone();
+    let members: Vec<String> = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT pseudonym_key FROM community_members WHERE owner_key = ? AND community_id = ?")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(rusqlite::params![owner_key, cid], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let vouches: Vec<MemberVouch> = members
+        .iter()
+        .filter_map(|m| <[u8; 32]>::try_from(hex::decode(m).ok()?).ok())
+        .filter(|m| *m != pseudonym.verifying_key().to_bytes())
+        .map(|m| member_vouch::vouch(&pseudonym, &community_id, &m))
+        .collect();
+    if !vouches.is_empty() {
+        vouch_members(state.inner(), pool.inner(), &community_id, vouches).await?;
+    }
+
+    let response = send_community_rpc(
+        state.inner(),
+        pool.inner(),
+        &community_id,
+        rekindle_protocol::messaging::CommunityRequest::EnableZeroKnowledge,
+    )
+    .await?;
+
+    if let rekindle_protocol::messaging::CommunityResponse::Error { message, .. } = response {
+        return Err(format!("server rejected zero-knowledge mode: {message}"));
+    }
+
+    tracing::info!(community = %community_id, "zero-knowledge MEK custody enabled");
+    Ok(())
+}
+
+/// Switch a community's key agreement to a `TreeKEM` group (owner only).
+///
+/// Suited to large communities: a membership change costs one commit of
+/// `O(log n)` ciphertexts instead of a wrapped copy per member. The server
+/// discards its MEKs and other members join the tree through key packages.
+/// This cannot be undone.
+#[tauri::command]
+pub async fn enable_tree_kem(
+    community_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+    keystore_handle: State<'_, KeystoreHandle>,
+) -> Result<(), String> {
+    services::tree_service::enable(state.inner(), pool.inner(), keystore_handle.inner(), &community_id).await
 }
 
 /// Get members of a community from the local cache.
@@ -0,0 +1,63 @@
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::DbPool;
+use crate::services::device_service;
+use crate::state::{DeviceInfo, SharedState};
+
+/// Start linking this machine to an identity that lives on another device.
+///
+/// Returns the `rekindle-link://` URL to approve there. Runs before login.
+#[tauri::command]
+pub async fn create_device_link_request(
+    device_name: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+) -> Result<String, String> {
+    device_service::create_link_request(&app, state.inner(), &device_name).await
+}
+
+/// Abandon a pending link request.
+#[tauri::command]
+pub async fn cancel_device_link(state: State<'_, SharedState>) -> Result<(), String> {
+    *state.pending_link.lock() = None;
+    Ok(())
+}
+
+/// Approve a link request shown by a new device.
+#[tauri::command]
+pub async fn approve_device_link(
+    url: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<DeviceInfo, String> {
+    device_service::approve_link(state.inner(), pool.inner(), &url).await
+}
+
+/// Devices linked to our identity, and which one this is.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceList {
+    pub devices: Vec<DeviceInfo>,
+    /// This device's key, or `None` on the primary device.
+    pub local_device_key: Option<String>,
+}
+
+/// List the devices linked to our identity.
+#[tauri::command]
+pub async fn get_devices(state: State<'_, SharedState>) -> Result<DeviceList, String> {
+    Ok(DeviceList {
+        devices: device_service::own_devices(state.inner()),
+        local_device_key: device_service::local_device(state.inner()),
+    })
+}
+
+/// Unlink one of our devices.
+#[tauri::command]
+pub async fn remove_device(
+    device_key: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    device_service::remove_device(state.inner(), pool.inner(), &device_key).await
+}
@@ -21,6 +21,7 @@ pub struct FriendResponse {
     pub unread_count: u32,
     pub last_seen_at: Option<i64>,
     pub friendship_state: FriendshipState,
+    pub disappearing_secs: u32,
 }
 
 /// A pending friend request stored in `SQLite`.
@@ -79,6 +80,8 @@ pub async fn add_friend(
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
+    services::device_service::require_primary(state.inner())?;
+
     // Validate public key format (64 hex chars = 32 bytes)
     if public_key.len() != 64 || hex::decode(&public_key).is_err() {
         return Err("Invalid public key — must be a 64-character hex string".to_string());
@@ -132,6 +135,7 @@ pub async fn add_friend(
         mailbox_dht_key: None,
         last_heartbeat_at: None,
         friendship_state: FriendshipState::PendingOut,
+        disappearing_secs: 0,
     };
     state.friends.write().insert(public_key.clone(), friend);
 
@@ -168,6 +172,7 @@ pub async fn remove_friend(
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
+    services::device_service::require_primary(state.inner())?;
     let owner_key = current_owner_key(state.inner())?;
 
     // Notify the peer BEFORE local cleanup so we still have their route info.
@@ -273,6 +278,8 @@ pub async fn remove_friend(
         }
     });
 
+    services::device_service::share_contacts(state.inner(), pool.inner());
+
     tracing::info!(public_key = %public_key, "friend removed");
     Ok(())
 }
@@ -287,6 +294,7 @@ pub async fn accept_request(
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
+    services::device_service::require_primary(state.inner())?;
     let owner_key = current_owner_key(state.inner())?;
     let timestamp = db::timestamp_now();
 
@@ -346,6 +354,7 @@ pub async fn accept_request(
         mailbox_dht_key: pending_mailbox_key.clone(),
         last_heartbeat_at: None,
         friendship_state: FriendshipState::Accepted,
+        disappearing_secs: 0,
     };
     state.friends.write().insert(public_key.clone(), friend);
 
@@ -373,13 +382,13 @@ pub async fn accept_request(
 
     // Establish initiator-side Signal session using the requester's stored prekey bundle.
     // We (the acceptor) are the Signal initiator; the requester will be the responder.
-    // Clear any stale session first (e.g., from a previous friendship that was removed).
     let session_init = if let Some(ref prekey_bytes) = pending_prekey_bundle {
         let signal = state.signal_manager.lock();
         if let Some(handle) = signal.as_ref() {
-            let _ = handle.manager.delete_session(&public_key);
             if let Ok(bundle) = serde_json::from_slice::<rekindle_crypto::signal::PreKeyBundle>(prekey_bytes) {
-                match handle.manager.establish_session(&public_key, &bundle) {
+                match services::message_service::with_identity_policy(&app, &handle.manager, &public_key, &bundle.identity_key, || {
+                    handle.manager.establish_session(&public_key, &bundle)
+                }) {
                     Ok(info) => {
                         tracing::info!(peer = %public_key, "established initiator Signal session on accept");
                         Some(info)
@@ -422,6 +431,8 @@ pub async fn accept_request(
         }
     }
 
+    services::device_service::share_contacts(state.inner(), pool.inner());
+
     let _ = app.emit(
         "chat-event",
         &ChatEvent::FriendRequestAccepted {
@@ -481,6 +492,7 @@ pub async fn get_friends(state: State<'_, SharedState>) -> Result<Vec<FriendResp
                 unread_count: f.unread_count,
                 last_seen_at: f.last_seen_at,
                 friendship_state: f.friendship_state,
+                disappearing_secs: f.disappearing_secs,
             }
         })
         .collect();
@@ -591,7 +603,7 @@ pub async fn create_friend_group(
 ) -> Result<i64, String> {
     let owner_key = current_owner_key(state.inner())?;
     let pool_clone = pool.inner().clone();
-    tokio::task::spawn_blocking(move || {
+    let group_id = tokio::task::spawn_blocking(move || {
         let conn = pool_clone.lock().map_err(|e| e.to_string())?;
         conn.execute(
             "INSERT INTO friend_groups (owner_key, name) VALUES (?1, ?2)",
@@ -601,7 +613,9 @@ pub async fn create_friend_group(
         Ok::<i64, String>(conn.last_insert_rowid())
     })
     .await
-    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())??;
+    services::account_service::publish_in_background(state.inner(), pool.inner());
+    Ok(group_id)
 }
 
 /// Rename a friend group.
@@ -609,6 +623,7 @@ pub async fn create_friend_group(
 pub async fn rename_friend_group(
     group_id: i64,
     name: String,
+    state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
     let pool_clone = pool.inner().clone();
@@ -622,7 +637,9 @@ pub async fn rename_friend_group(
         Ok::<(), String>(())
     })
     .await
-    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())??;
+    services::account_service::publish_in_background(state.inner(), pool.inner());
+    Ok(())
 }
 
 /// Move a friend into a group (or remove from group with `group_id` = null).
@@ -676,6 +693,7 @@ pub async fn move_friend_to_group(
         }
     }
 
+    services::account_service::publish_in_background(state.inner(), pool.inner());
     Ok(())
 }
 
@@ -683,6 +701,7 @@ pub async fn move_friend_to_group(
 #[tauri::command]
 pub async fn generate_invite(
     state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
 ) -> Result<String, String> {
     // Gather identity info
     let (public_key, display_name, secret_key) = {
@@ -704,15 +723,15 @@ pub async fn generate_invite(
         (mdk, pdk, rb)
     };
 
-    // Generate a PreKeyBundle
+    // Generate a PreKeyBundle with a one-time prekey reserved for this invite
     let prekey_bundle = {
-        let signal = state.signal_manager.lock();
-        let handle = signal.as_ref().ok_or("signal manager not initialized")?;
-        let bundle = handle
-            .manager
-            .generate_prekey_bundle(1, Some(1))
-            .map_err(|e| format!("generate prekey bundle: {e}"))?;
-        serde_json::to_vec(&bundle).map_err(|e| format!("serialize prekey bundle: {e}"))?
+        let st = state.inner().clone();
+        let db = pool.inner().clone();
+        tokio::task::spawn_blocking(move || {
+            crate::services::prekey_service::bundle_for_peer(&st, &db)
+        })
+        .await
+        .map_err(|e| e.to_string())??
     };
 
     let blob = rekindle_protocol::messaging::create_invite_blob(
@@ -735,6 +754,8 @@ pub async fn add_friend_from_invite(
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
+    services::device_service::require_primary(state.inner())?;
+
     // Decode and verify the invite
     let blob = rekindle_protocol::messaging::decode_invite_url(&invite_string)?;
     rekindle_protocol::messaging::verify_invite_blob(&blob)?;
@@ -816,6 +837,7 @@ pub async fn add_friend_from_invite(
         mailbox_dht_key: Some(blob.mailbox_dht_key.clone()),
         last_heartbeat_at: None,
         friendship_state: FriendshipState::PendingOut,
+        disappearing_secs: 0,
     };
     state.friends.write().insert(blob.public_key.clone(), friend);
 
@@ -874,14 +896,14 @@ async fn setup_invite_contact(
     }
 
     // Establish Signal session from invite's PreKeyBundle
-    // Clear any stale session first (e.g., from a previous friendship that was removed)
     if let Ok(bundle) =
         serde_json::from_slice::<rekindle_crypto::signal::PreKeyBundle>(&blob.prekey_bundle)
     {
         let signal = state.signal_manager.lock();
         if let Some(handle) = signal.as_ref() {
-            let _ = handle.manager.delete_session(&blob.public_key);
-            match handle.manager.establish_session(&blob.public_key, &bundle) {
+            match services::message_service::with_identity_policy(&app, &handle.manager, &blob.public_key, &bundle.identity_key, || {
+                handle.manager.establish_session(&blob.public_key, &bundle)
+            }) {
                 Ok(_init_info) => {
                     tracing::info!(peer = %blob.public_key, "established Signal session from invite");
                 }
@@ -952,6 +974,7 @@ pub async fn block_user(
     state: State<'_, SharedState>,
     pool: State<'_, DbPool>,
 ) -> Result<(), String> {
+    services::device_service::require_primary(state.inner())?;
     let owner_key = current_owner_key(state.inner())?;
     let timestamp = db::timestamp_now();
 
@@ -1039,6 +1062,8 @@ pub async fn block_user(
         },
     );
 
+    services::device_service::share_contacts(state.inner(), pool.inner());
+
     tracing::info!(public_key = %public_key, "user blocked and profile key rotated");
     Ok(())
 }
@@ -1107,6 +1132,84 @@ pub async fn get_blocked_users(
     .map_err(|e| e.to_string())?
 }
 
+/// Safety number for out-of-band verification of a friend's identity key.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyNumberInfo {
+    /// 60 digits, identical on both devices.
+    pub safety_number: String,
+    /// Hex-encoded payload for the QR code the friend scans.
+    pub qr_payload: String,
+    pub verified: bool,
+}
+
+/// Get the safety number for a friend we have a Signal identity key for.
+#[tauri::command]
+pub async fn get_safety_number(
+    public_key: String,
+    state: State<'_, SharedState>,
+) -> Result<SafetyNumberInfo, String> {
+    let owner_key = current_owner_key(state.inner())?;
+    let signal = state.signal_manager.lock();
+    let handle = signal.as_ref().ok_or("signal manager not initialized")?;
+    let number = handle
+        .manager
+        .safety_number(&owner_key, &public_key)
+        .map_err(|e| e.to_string())?;
+    let verified = handle.manager.is_verified(&public_key).map_err(|e| e.to_string())?;
+    Ok(SafetyNumberInfo {
+        safety_number: number.displayable(),
+        qr_payload: hex::encode(number.qr_payload()),
+        verified,
+    })
+}
+
+/// Mark a friend's identity key as verified (or clear verification).
+///
+/// Verified friends are never messaged in plaintext, and a later identity
+/// key change blocks the session instead of being accepted silently.
+#[tauri::command]
+pub async fn set_friend_verified(
+    public_key: String,
+    verified: bool,
+    state: State<'_, SharedState>,
+) -> Result<(), String> {
+    let signal = state.signal_manager.lock();
+    let handle = signal.as_ref().ok_or("signal manager not initialized")?;
+    handle
+        .manager
+        .set_verified(&public_key, verified)
+        .map_err(|e| e.to_string())
+}
+
+/// Verify a friend by scanning their safety-number QR code.
+///
+/// Marks the friend verified and returns `true` if the scanned payload
+/// matches; returns `false` (leaving verification unchanged) otherwise.
+#[tauri::command]
+pub async fn verify_safety_number_qr(
+    public_key: String,
+    qr_payload: String,
+    state: State<'_, SharedState>,
+) -> Result<bool, String> {
+    let owner_key = current_owner_key(state.inner())?;
+    let scanned = hex::decode(&qr_payload).map_err(|e| format!("invalid QR payload: {e}"))?;
+    let signal = state.signal_manager.lock();
+    let handle = signal.as_ref().ok_or("signal manager not initialized")?;
+    let number = handle
+        .manager
+        .safety_number(&owner_key, &public_key)
+        .map_err(|e| e.to_string())?;
+    let matches = number.matches_scanned(&scanned).map_err(|e| e.to_string())?;
+    if matches {
+        handle
+            .manager
+            .set_verified(&public_key, true)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(matches)
+}
+
 /// Rotate the profile DHT key: create a new profile record, copy data,
 /// update state/DB, and notify all remaining friends via `ProfileKeyRotated`.
 async fn rotate_profile_key(
@@ -1122,7 +1225,7 @@ async fn rotate_profile_key(
     };
     let temp_mgr = rekindle_protocol::dht::DHTManager::new(routing_context.clone());
     let (new_key, new_keypair) = temp_mgr
-        .create_record(8)
+        .create_record(rekindle_protocol::dht::profile::PROFILE_SUBKEY_COUNT)
         .await
         .map_err(|e| format!("create new profile record: {e}"))?;
 
@@ -1139,25 +1242,16 @@ async fn rotate_profile_key(
         (ok, dn, vec![status], rb)
     };
 
-    // Read prekey from signal manager
-    let prekey_bytes = {
-        let signal = state.signal_manager.lock();
-        if let Some(handle) = signal.as_ref() {
-            match handle.manager.generate_prekey_bundle(1, Some(1)) {
-                Ok(bundle) => serde_json::to_vec(&bundle).unwrap_or_default(),
-                Err(_) => Vec::new(),
-            }
-        } else {
-            Vec::new()
-        }
-    };
+    // Current signed prekey bundle (the one-time prekey pool is republished
+    // to the new record by the prekey maintenance loop)
+    let prekey_bytes = crate::services::prekey_service::published_bundle(state).unwrap_or_default();
 
     let record_key: veilid_core::RecordKey = new_key
         .parse()
         .map_err(|e| format!("invalid new profile key: {e}"))?;
 
     // Write profile subkeys to new record
-    // Subkey 0: display name, 1: status, 5: prekey, 6: route blob
+    // Subkey 0: display name, 1: status, 5: prekey, 6: route blob, 7: devices
     let _ = routing_context
         .set_dht_value(record_key.clone(), 0, display_name.into_bytes(), None)
         .await;
@@ -1170,6 +1264,25 @@ async fn rotate_profile_key(
     let _ = routing_context
         .set_dht_value(record_key.clone(), 6, route_blob, None)
         .await;
+    // Subkey 7: linked-device certificates, copied from the old record
+    let devices = match old_key_str.parse::<veilid_core::RecordKey>() {
+        Ok(old_record_key) => routing_context
+            .get_dht_value(old_record_key, rekindle_protocol::dht::profile::SUBKEY_DEVICES, false)
+            .await
+            .ok()
+            .flatten(),
+        Err(_) => None,
+    };
+    if let Some(devices) = devices {
+        let _ = routing_context
+            .set_dht_value(
+                record_key.clone(),
+                rekindle_protocol::dht::profile::SUBKEY_DEVICES,
+                devices.data().to_vec(),
+                None,
+            )
+            .await;
+    }
 
     // Update NodeHandle
     {
@@ -1203,12 +1316,29 @@ async fn rotate_profile_key(
     .map_err(|e| e.to_string())??;
 
     // Notify all remaining friends about the new profile key
+    let notified = notify_profile_key_rotated(state, pool, &new_key).await;
+
+    tracing::info!(
+        old_key = %old_key_str,
+        new_key = %new_key,
+        "profile DHT key rotated — {notified} friends notified"
+    );
+    Ok(())
+}
+
+/// Send `ProfileKeyRotated` for `new_key` to every friend. Returns how many
+/// friends were sent it.
+pub(crate) async fn notify_profile_key_rotated(
+    state: &std::sync::Arc<crate::state::AppState>,
+    pool: &DbPool,
+    new_key: &str,
+) -> usize {
     let friend_keys: Vec<String> = {
         let friends = state.friends.read();
         friends.keys().cloned().collect()
     };
     let payload = rekindle_protocol::messaging::envelope::MessagePayload::ProfileKeyRotated {
-        new_profile_dht_key: new_key.clone(),
+        new_profile_dht_key: new_key.to_string(),
     };
     for fk in &friend_keys {
         if let Err(e) =
@@ -1217,14 +1347,7 @@ async fn rotate_profile_key(
             tracing::warn!(to = %fk, error = %e, "failed to send ProfileKeyRotated");
         }
     }
-
-    tracing::info!(
-        old_key = %old_key_str,
-        new_key = %new_key,
-        "profile DHT key rotated — {} friends notified",
-        friend_keys.len()
-    );
-    Ok(())
+    friend_keys.len()
 }
 
 /// Re-emit presence events for all non-offline friends.
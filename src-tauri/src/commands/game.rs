@@ -25,3 +25,25 @@ pub async fn get_game_status(state: State<'_, SharedState>) -> Result<Option<Gam
     }
     Ok(None)
 }
+
+/// A game from the bundled game database, for picking directory tags.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownGame {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Every game in the bundled database, sorted by name.
+#[tauri::command]
+pub async fn get_known_games() -> Result<Vec<KnownGame>, String> {
+    let database = rekindle_game_detect::GameDatabase::bundled();
+    Ok(database
+        .games()
+        .into_iter()
+        .map(|g| KnownGame {
+            id: g.id,
+            name: g.name.clone(),
+        })
+        .collect())
+}
@@ -0,0 +1,152 @@
+use tauri::State;
+
+use crate::commands::auth::current_owner_key;
+use crate::commands::chat::Message;
+use crate::db::{self, DbPool};
+use crate::services::group_service;
+use crate::state::{GroupChatState, SharedState};
+
+/// Start a group conversation with some of our friends.
+#[tauri::command]
+pub async fn create_group_chat(
+    name: String,
+    members: Vec<String>,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<GroupChatState, String> {
+    group_service::create_group(&app, state.inner(), pool.inner(), &name, &members).await
+}
+
+/// Get the group conversations we are in.
+#[tauri::command]
+pub async fn get_group_chats(state: State<'_, SharedState>) -> Result<Vec<GroupChatState>, String> {
+    let mut groups: Vec<GroupChatState> = state.group_chats.read().values().cloned().collect();
+    groups.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    Ok(groups)
+}
+
+/// Get the message history of a group conversation, oldest first.
+#[tauri::command]
+pub async fn get_group_messages(
+    group_id: String,
+    limit: u32,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<Vec<Message>, String> {
+    let our_key = current_owner_key(state.inner())?;
+    let pool = pool.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, sender_key, body, timestamp, message_id, edited_at FROM messages \
+                 WHERE owner_key = ? AND conversation_id = ? AND conversation_type = 'group' \
+                 ORDER BY timestamp ASC LIMIT ?",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(rusqlite::params![our_key, group_id, limit], |row| {
+                let sender = db::get_str(row, "sender_key");
+                Ok(Message {
+                    id: db::get_i64(row, "id"),
+                    is_own: sender == our_key,
+                    sender_id: sender,
+                    body: db::get_str(row, "body"),
+                    timestamp: db::get_i64(row, "timestamp"),
+                    attachments: Vec::new(),
+                    message_id: db::get_str_opt(row, "message_id"),
+                    edited_at: db::get_i64_opt(row, "edited_at"),
+                    reactions: Vec::new(),
+                    delivery_state: None,
+                    reply_to: None,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        let mut messages = Vec::new();
+        for row in rows {
+            messages.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok::<_, String>(messages)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Send a message to a group conversation.
+///
+/// Returns the message's global ID.
+#[tauri::command]
+pub async fn send_group_message(
+    group_id: String,
+    body: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<String, String> {
+    if body.trim().is_empty() {
+        return Err("message cannot be empty".to_string());
+    }
+    group_service::send_message(state.inner(), pool.inner(), &group_id, &body).await
+}
+
+/// Add one of our friends to a group conversation.
+#[tauri::command]
+pub async fn add_group_member(
+    group_id: String,
+    public_key: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<GroupChatState, String> {
+    group_service::add_member(&app, state.inner(), pool.inner(), &group_id, &public_key).await
+}
+
+/// Leave a group conversation and delete its history.
+#[tauri::command]
+pub async fn leave_group_chat(
+    group_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    group_service::leave_group(&app, state.inner(), pool.inner(), &group_id).await
+}
+
+/// Mark a group conversation's messages as read.
+#[tauri::command]
+pub async fn mark_group_read(
+    group_id: String,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state.inner())?;
+    if let Some(group) = state.group_chats.write().get_mut(&group_id) {
+        group.unread_count = 0;
+    }
+    let pool = pool.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE messages SET is_read = 1 WHERE owner_key = ? AND conversation_id = ? \
+             AND conversation_type = 'group' AND is_read = 0",
+            rusqlite::params![owner_key, group_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Pick up roster changes from the group's DHT record that we missed.
+///
+/// Called when a group window opens.
+#[tauri::command]
+pub async fn refresh_group_chat(
+    group_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<(), String> {
+    group_service::refresh_roster(&app, state.inner(), pool.inner(), &group_id).await
+}
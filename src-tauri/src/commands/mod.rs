@@ -1,8 +1,11 @@
 pub mod auth;
 pub mod chat;
 pub mod community;
+pub mod devices;
 pub mod friends;
 pub mod game;
+pub mod groups;
+pub mod search;
 pub mod settings;
 pub mod status;
 pub mod voice;
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::auth::current_owner_key;
+use crate::db::{self, DbPool};
+use crate::state::SharedState;
+
+/// Results returned when the caller doesn't ask for a number.
+const DEFAULT_LIMIT: u32 = 50;
+/// Most results returned by one search.
+const MAX_LIMIT: u32 = 200;
+
+/// Optional narrowing of a message search. All given filters must match.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchFilters {
+    /// Peer public key (DM), channel ID or group ID.
+    pub conversation_id: Option<String>,
+    pub sender_key: Option<String>,
+    /// Inclusive lower bound on the stored timestamp.
+    pub since: Option<i64>,
+    /// Exclusive upper bound on the stored timestamp.
+    pub until: Option<i64>,
+    pub has_attachment: Option<bool>,
+}
+
+/// A message matching a search.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    /// Row ID in `messages`.
+    pub id: i64,
+    pub message_id: Option<String>,
+    pub conversation_id: String,
+    /// `dm`, `channel` or `group`.
+    pub conversation_type: String,
+    /// Friend's nickname or display name, or the channel's or group's name.
+    pub conversation_name: Option<String>,
+    /// Set for channel messages.
+    pub community_id: Option<String>,
+    pub sender_id: String,
+    pub timestamp: i64,
+    /// Excerpt of the body around the match. Matched terms are wrapped in
+    /// `\u{2}` … `\u{3}` so the frontend can highlight them without
+    /// interpreting the body as markup.
+    pub snippet: String,
+    pub has_attachment: bool,
+}
+
+/// Search decrypted message bodies across every DM, channel and group, newest first.
+///
+/// Each word of `query` is matched as a prefix, and all words must appear.
+#[tauri::command]
+pub async fn search_messages(
+    query: String,
+    filters: Option<SearchFilters>,
+    limit: Option<u32>,
+    state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
+) -> Result<Vec<SearchResult>, String> {
+    let owner_key = current_owner_key(state.inner())?;
+    let Some(fts_query) = fts_query(&query) else {
+        return Ok(Vec::new());
+    };
+    let filters = filters.unwrap_or_default();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    let pool = pool.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT m.id, m.message_id, m.conversation_id, m.conversation_type, m.sender_key, m.timestamp, \
+                        m.attachment_json IS NOT NULL AS has_attachment, \
+                        COALESCE(f.nickname, f.display_name, c.name, g.name) AS conversation_name, \
+                        c.community_id, \
+                        snippet(messages_fts, 0, char(2), char(3), '…', 12) AS snippet \
+                 FROM messages_fts \
+                 JOIN messages m ON m.id = messages_fts.rowid \
+                 LEFT JOIN friends f ON m.conversation_type = 'dm' \
+                      AND f.owner_key = m.owner_key AND f.public_key = m.conversation_id \
+                 LEFT JOIN channels c ON m.conversation_type = 'channel' \
+                      AND c.owner_key = m.owner_key AND c.id = m.conversation_id \
+                 LEFT JOIN group_chats g ON m.conversation_type = 'group' \
+                      AND g.owner_key = m.owner_key AND g.id = m.conversation_id \
+                 WHERE messages_fts MATCH ?1 AND m.owner_key = ?2 \
+                   AND (?3 IS NULL OR m.conversation_id = ?3) \
+                   AND (?4 IS NULL OR m.sender_key = ?4) \
+                   AND (?5 IS NULL OR m.timestamp >= ?5) \
+                   AND (?6 IS NULL OR m.timestamp < ?6) \
+                   AND (?7 IS NULL OR (m.attachment_json IS NOT NULL) = ?7) \
+                 ORDER BY m.timestamp DESC LIMIT ?8",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![
+                    fts_query,
+                    owner_key,
+                    filters.conversation_id,
+                    filters.sender_key,
+                    filters.since,
+                    filters.until,
+                    filters.has_attachment,
+                    limit,
+                ],
+                |row| {
+                    Ok(SearchResult {
+                        id: db::get_i64(row, "id"),
+                        message_id: db::get_str_opt(row, "message_id"),
+                        conversation_id: db::get_str(row, "conversation_id"),
+                        conversation_type: db::get_str(row, "conversation_type"),
+                        conversation_name: db::get_str_opt(row, "conversation_name"),
+                        community_id: db::get_str_opt(row, "community_id"),
+                        sender_id: db::get_str(row, "sender_key"),
+                        timestamp: db::get_i64(row, "timestamp"),
+                        snippet: db::get_str(row, "snippet"),
+                        has_attachment: db::get_i64(row, "has_attachment") != 0,
+                    })
+                },
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok::<_, String>(results)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Re-index every stored message body.
+///
+/// The index normally maintains itself; this is the manual repair path.
+#[tauri::command]
+pub async fn rebuild_search_index(pool: State<'_, DbPool>) -> Result<(), String> {
+    let pool = pool.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        db::rebuild_search_index(&conn)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Turn free text into an FTS5 query: every word quoted (so punctuation and
+/// FTS operators are taken literally) and matched as a prefix.
+fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
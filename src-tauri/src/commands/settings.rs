@@ -34,6 +34,9 @@ pub struct Preferences {
     /// Minutes of inactivity before auto-away (0 = disabled).
     #[serde(default = "default_auto_away")]
     pub auto_away_minutes: u32,
+    /// Whether to tell friends when we've read their messages.
+    #[serde(default = "default_true")]
+    pub send_read_receipts: bool,
 }
 
 fn default_volume() -> f32 {
@@ -64,6 +67,7 @@ impl Default for Preferences {
             noise_suppression: true,
             echo_cancellation: true,
             auto_away_minutes: 10,
+            send_read_receipts: true,
         }
     }
 }
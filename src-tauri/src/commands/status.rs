@@ -4,7 +4,7 @@ use image::ImageReader;
 use tauri::{Emitter as _, State};
 
 use crate::commands::auth::current_owner_key;
-use crate::db::DbPool;
+use crate::db::{self, DbPool};
 use crate::services;
 use crate::state::{SharedState, UserStatus};
 
@@ -66,7 +66,9 @@ pub async fn set_nickname(
             rusqlite::params![nickname_clone, pk_clone],
         )
         .map_err(|e| e.to_string())?;
-        Ok::<_, String>(())
+        drop(conn);
+        // The login screen reads names from the directory
+        db::sync_directory_entry(&pool, &pk_clone)
     })
     .await
     .map_err(|e| e.to_string())??;
@@ -153,7 +155,8 @@ pub async fn set_avatar(
             rusqlite::params![webp_for_db, pk_clone],
         )
         .map_err(|e| e.to_string())?;
-        Ok::<_, String>(())
+        drop(conn);
+        db::sync_directory_entry(&pool_clone, &pk_clone)
     })
     .await
     .map_err(|e| e.to_string())??;
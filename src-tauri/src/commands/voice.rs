@@ -3,12 +3,31 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use tauri::{Emitter, State};
+use rekindle_crypto::group::media_key::MediaKeyRing;
+use rekindle_crypto::sframe::FrameKey;
+use rekindle_protocol::messaging::{CommunityRequest, CommunityResponse, VoiceParticipantDto};
+use rekindle_voice::frame_keys::VoiceKeys;
+use rekindle_voice::stats::{BitrateController, LinkStats, StreamStats};
+use rekindle_voice::transport::{VoiceMessage, VoiceProbe};
+use tauri::{Emitter, Manager, State};
 use tauri_plugin_store::StoreExt;
 use tokio::sync::{broadcast, mpsc};
 
 use crate::channels::{NotificationEvent, VoiceEvent};
-use crate::state::{SharedState, VoiceEngineHandle};
+use crate::db::DbPool;
+use crate::state::{SharedState, VoiceEngineHandle, VoiceKeySource};
+
+/// How often each side of a 1:1 call moves to a fresh frame key.
+const CALL_REKEY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How often the send loop probes the round trip and answers peers' probes.
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often the send loop retunes the encoder from the latest echoes.
+const ENCODER_ADAPT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the receive loop reports each participant's stream.
+const STREAM_REPORT_INTERVAL: Duration = Duration::from_secs(2);
 
 /// Join a voice channel — initialize the voice engine and emit join event.
 #[allow(clippy::too_many_lines)]
@@ -17,6 +36,7 @@ pub async fn join_voice_channel(
     channel_id: String,
     app: tauri::AppHandle,
     state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
 ) -> Result<(), String> {
     // Check if already in a call
     {
@@ -35,6 +55,21 @@ pub async fn join_voice_channel(
         .clone()
         .ok_or("not logged in")?;
 
+    // Frames never leave unencrypted, so we need keys before any audio flows
+    let key_source = voice_key_source(state.inner(), &channel_id);
+    let self_key = voice_self_key(state.inner(), &identity.public_key, &key_source)?;
+    let frame_keys = Arc::new(VoiceKeys::new(hex::decode(&self_key).unwrap_or_default()));
+    install_frame_keys(state.inner(), pool.inner(), &key_source, &frame_keys).await?;
+
+    // Community channels run through the server's voice relay
+    let (participants, relay_token) = match &key_source {
+        VoiceKeySource::Channel { community_id } => {
+            join_relay(state.inner(), pool.inner(), community_id, &channel_id).await?
+        }
+        VoiceKeySource::Call { .. } => (Vec::new(), Vec::new()),
+    };
+    let relayed = matches!(key_source, VoiceKeySource::Channel { .. });
+
     // Load audio device preferences from persistent store
     let prefs: crate::commands::settings::Preferences = app
         .store("preferences.json")
@@ -69,6 +104,9 @@ pub async fn join_voice_channel(
             channel_id: channel_id.clone(),
             muted_flag: Arc::clone(&muted_flag),
             deafened_flag: Arc::clone(&deafened_flag),
+            frame_keys: Arc::clone(&frame_keys),
+            key_source,
+            relay_token: relay_token.clone(),
         });
     }
 
@@ -88,15 +126,12 @@ pub async fn join_voice_channel(
     }
 
     // Create voice transport for this channel and attempt to connect.
-    let mut transport = rekindle_voice::transport::VoiceTransport::new(channel_id.clone());
+    let mut transport =
+        rekindle_voice::transport::VoiceTransport::new(channel_id.clone(), Arc::clone(&frame_keys));
+    transport.set_relay_token(relay_token);
 
     // Try to look up a route blob for this channel
-    let route_blob = {
-        let dht_mgr = state.dht_manager.read();
-        dht_mgr
-            .as_ref()
-            .and_then(|mgr| mgr.manager.get_cached_route(&channel_id).cloned())
-    };
+    let route_blob = voice_route_blob(state.inner(), &key_source, &channel_id);
 
     // Clone API handle out before await
     let api = {
@@ -105,7 +140,7 @@ pub async fn join_voice_channel(
     };
 
     if let (Some(blob), Some(api)) = (route_blob, api) {
-        let sender_key = hex::decode(&identity.public_key).unwrap_or_default();
+        let sender_key = hex::decode(&self_key).unwrap_or_default();
         if let Err(e) = transport.connect(api, &blob, sender_key) {
             tracing::warn!(error = %e, channel = %channel_id, "voice transport connect failed — audio only local");
         }
@@ -136,10 +171,13 @@ pub async fn join_voice_channel(
     // send loop receives it to feed the echo canceller.
     let (speaker_ref_tx, speaker_ref_rx) = broadcast::channel::<Vec<f32>>(50);
 
+    // Link statistics: the receive loop measures, the send loop adapts
+    let link_stats = Arc::new(LinkStats::new());
+
     // Spawn voice send loop
     let (send_shutdown_tx, send_shutdown_rx) = mpsc::channel::<()>(1);
     let send_app = app.clone();
-    let send_public_key = identity.public_key.clone();
+    let send_public_key = self_key.clone();
     let send_muted = Arc::clone(&muted_flag);
     let send_handle = tokio::spawn(voice_send_loop(
         capture_rx,
@@ -151,12 +189,13 @@ pub async fn join_voice_channel(
         echo_cancellation,
         send_muted,
         speaker_ref_rx,
+        Arc::clone(&link_stats),
     ));
 
     // Spawn voice receive loop
     let (recv_shutdown_tx, recv_shutdown_rx) = mpsc::channel::<()>(1);
     let recv_app = app.clone();
-    let recv_public_key = identity.public_key.clone();
+    let recv_public_key = self_key.clone();
     let recv_deafened = Arc::clone(&deafened_flag);
     let recv_handle = tokio::spawn(voice_receive_loop(
         voice_packet_rx,
@@ -166,6 +205,9 @@ pub async fn join_voice_channel(
         recv_public_key,
         recv_deafened,
         speaker_ref_tx,
+        frame_keys,
+        link_stats,
+        relayed,
     ));
 
     // Take device error receiver and spawn device monitor loop
@@ -202,18 +244,29 @@ pub async fn join_voice_channel(
 
     // Emit voice events to frontend
     let event = VoiceEvent::UserJoined {
-        public_key: identity.public_key.clone(),
+        public_key: self_key.clone(),
         display_name: identity.display_name,
     };
     let _ = app.emit("voice-event", &event);
+    for participant in participants {
+        let event = VoiceEvent::UserJoined {
+            public_key: participant.pseudonym_key,
+            display_name: participant.display_name,
+        };
+        let _ = app.emit("voice-event", &event);
+    }
 
     let quality_event = VoiceEvent::ConnectionQuality {
         quality: "good".to_string(),
+        bars: 4,
+        rtt_ms: None,
+        loss_pct: 0.0,
+        bitrate: rekindle_voice::stats::START_BITRATE,
     };
     let _ = app.emit("voice-event", &quality_event);
 
     let speaking_event = VoiceEvent::UserSpeaking {
-        public_key: identity.public_key,
+        public_key: self_key,
         speaking: false,
     };
     let _ = app.emit("voice-event", &speaking_event);
@@ -226,16 +279,31 @@ pub async fn join_voice_channel(
 pub async fn leave_voice(
     app: tauri::AppHandle,
     state: State<'_, SharedState>,
+    pool: State<'_, DbPool>,
 ) -> Result<(), String> {
-    let public_key = state
+    let identity_key = state
         .identity
         .read()
         .as_ref()
         .map(|id| id.public_key.clone())
         .unwrap_or_default();
+    let key_source = state.voice_engine.lock().as_ref().map(|h| h.key_source.clone());
+    let public_key = key_source
+        .as_ref()
+        .and_then(|source| voice_self_key(state.inner(), &identity_key, source).ok())
+        .unwrap_or(identity_key);
 
     shutdown_voice_loops(&state).await;
 
+    if let Some(VoiceKeySource::Channel { community_id }) = key_source {
+        let request = CommunityRequest::LeaveVoice;
+        if let Err(e) =
+            crate::commands::community::send_community_rpc(state.inner(), pool.inner(), &community_id, request).await
+        {
+            tracing::warn!(community = %community_id, error = %e, "failed to leave voice relay");
+        }
+    }
+
     // Emit leave event
     let event = VoiceEvent::UserLeft { public_key };
     let _ = app.emit("voice-event", &event);
@@ -462,7 +530,7 @@ fn restart_voice_loops(
         .ok_or("not logged in")?;
 
     // Restart capture and playback, take channels
-    let (capture_rx, playback_tx, channel_id, muted_flag, deafened_flag, noise_suppression, echo_cancellation) = {
+    let (capture_rx, playback_tx, channel_id, muted_flag, deafened_flag, frame_keys, key_source, relay_token, noise_suppression, echo_cancellation) = {
         let mut ve = state.voice_engine.lock();
         let handle = ve.as_mut().ok_or("no active voice engine")?;
 
@@ -483,25 +551,27 @@ fn restart_voice_loops(
             handle.channel_id.clone(),
             Arc::clone(&handle.muted_flag),
             Arc::clone(&handle.deafened_flag),
+            Arc::clone(&handle.frame_keys),
+            handle.key_source.clone(),
+            handle.relay_token.clone(),
             ns,
             ec,
         )
     };
+    let self_key = voice_self_key(state, &identity.public_key, &key_source)?;
+    let relayed = matches!(key_source, VoiceKeySource::Channel { .. });
 
     // Create new transport and try to connect
-    let mut transport = rekindle_voice::transport::VoiceTransport::new(channel_id.clone());
-    let route_blob = {
-        let dht_mgr = state.dht_manager.read();
-        dht_mgr
-            .as_ref()
-            .and_then(|mgr| mgr.manager.get_cached_route(&channel_id).cloned())
-    };
+    let mut transport =
+        rekindle_voice::transport::VoiceTransport::new(channel_id.clone(), Arc::clone(&frame_keys));
+    transport.set_relay_token(relay_token);
+    let route_blob = voice_route_blob(state, &key_source, &channel_id);
     let api = {
         let node = state.node.read();
         node.as_ref().map(|nh| nh.api.clone())
     };
     if let (Some(blob), Some(api)) = (route_blob, api) {
-        let sender_key = hex::decode(&identity.public_key).unwrap_or_default();
+        let sender_key = hex::decode(&self_key).unwrap_or_default();
         if let Err(e) = transport.connect(api, &blob, sender_key) {
             tracing::warn!(error = %e, "hot-swap: voice transport reconnect failed");
         }
@@ -514,6 +584,9 @@ fn restart_voice_loops(
     // New speaker reference broadcast channel
     let (speaker_ref_tx, speaker_ref_rx) = broadcast::channel::<Vec<f32>>(50);
 
+    // Fresh link statistics — the new transport starts measuring from scratch
+    let link_stats = Arc::new(LinkStats::new());
+
     // Spawn send loop
     let (send_shutdown_tx, send_shutdown_rx) = mpsc::channel::<()>(1);
     let send_handle = tokio::spawn(voice_send_loop(
@@ -521,11 +594,12 @@ fn restart_voice_loops(
         transport,
         send_shutdown_rx,
         app.clone(),
-        identity.public_key.clone(),
+        self_key.clone(),
         noise_suppression,
         echo_cancellation,
         Arc::clone(&muted_flag),
         speaker_ref_rx,
+        Arc::clone(&link_stats),
     ));
 
     // Spawn receive loop
@@ -535,9 +609,12 @@ fn restart_voice_loops(
         playback_tx,
         recv_shutdown_rx,
         app.clone(),
-        identity.public_key.clone(),
+        self_key,
         Arc::clone(&deafened_flag),
         speaker_ref_tx,
+        frame_keys,
+        link_stats,
+        relayed,
     ));
 
     // Respawn device monitor with fresh error channel
@@ -825,12 +902,218 @@ async fn handle_device_swap(
     Ok(())
 }
 
+// ── Voice Relay ──────────────────────────────────────────────────────────
+
+/// The key we appear under in a voice session: our pseudonym in a community
+/// channel (the relay and other members know us by it), our identity key
+/// in a 1:1 call.
+fn voice_self_key(
+    state: &SharedState,
+    identity_key: &str,
+    source: &VoiceKeySource,
+) -> Result<String, String> {
+    match source {
+        VoiceKeySource::Channel { community_id } => state
+            .communities
+            .read()
+            .get(community_id)
+            .and_then(|c| c.my_pseudonym_key.clone())
+            .ok_or_else(|| "no pseudonym key for this community".to_string()),
+        VoiceKeySource::Call { .. } => Ok(identity_key.to_string()),
+    }
+}
+
+/// Where our frames go: the community server's relay for a channel, the
+/// peer's route for a call.
+fn voice_route_blob(state: &SharedState, source: &VoiceKeySource, channel_id: &str) -> Option<Vec<u8>> {
+    match source {
+        VoiceKeySource::Channel { community_id } => state
+            .communities
+            .read()
+            .get(community_id)
+            .and_then(|c| c.server_route_blob.clone()),
+        VoiceKeySource::Call { .. } => {
+            let dht_mgr = state.dht_manager.read();
+            dht_mgr
+                .as_ref()
+                .and_then(|mgr| mgr.manager.get_cached_route(channel_id).cloned())
+        }
+    }
+}
+
+/// Join a channel on the community server's voice relay, which forwards
+/// frames to our current route. Returns who is already in the channel and
+/// the relay token our frames must carry.
+async fn join_relay(
+    state: &SharedState,
+    pool: &DbPool,
+    community_id: &str,
+    channel_id: &str,
+) -> Result<(Vec<VoiceParticipantDto>, Vec<u8>), String> {
+    let route_blob = state.node.read().as_ref().and_then(|nh| nh.route_blob.clone());
+    let request = CommunityRequest::JoinVoice {
+        channel_id: channel_id.to_string(),
+        route_blob,
+    };
+    match crate::commands::community::send_community_rpc(state, pool, community_id, request).await? {
+        CommunityResponse::VoiceJoined { participants, relay_token } => Ok((participants, relay_token)),
+        CommunityResponse::Error { message, .. } => Err(format!("server rejected voice join: {message}")),
+        other => Err(format!("unexpected response to voice join: {other:?}")),
+    }
+}
+
+/// Pass a relay broadcast about `channel_id` on to the voice panel if that
+/// is the channel we are in. Being dropped from it (kicked, timed out, or
+/// the channel deleted) ends our session.
+pub(crate) async fn relay_event(
+    app: &tauri::AppHandle,
+    state: &SharedState,
+    community_id: &str,
+    channel_id: &str,
+    event: VoiceEvent,
+) {
+    let in_channel = state.voice_engine.lock().as_ref().is_some_and(|handle| {
+        handle.channel_id == channel_id
+            && handle.key_source == (VoiceKeySource::Channel { community_id: community_id.to_string() })
+    });
+    if !in_channel {
+        return;
+    }
+    let self_key = state
+        .communities
+        .read()
+        .get(community_id)
+        .and_then(|c| c.my_pseudonym_key.clone());
+
+    if let VoiceEvent::UserLeft { public_key } = &event {
+        if self_key.as_deref() == Some(public_key.as_str()) {
+            tracing::info!(community = %community_id, channel = %channel_id, "removed from voice channel by the relay");
+            shutdown_voice_loops(state).await;
+            let _ = app.emit("voice-event", &event);
+            return;
+        }
+    }
+    let _ = app.emit("voice-event", &event);
+}
+
+// ── Frame Keys ───────────────────────────────────────────────────────────
+
+/// A community voice channel if `channel_id` is one of ours, otherwise a
+/// 1:1 call with the peer whose public key it is.
+fn voice_key_source(state: &SharedState, channel_id: &str) -> VoiceKeySource {
+    let communities = state.communities.read();
+    communities
+        .values()
+        .find(|c| c.channels.iter().any(|ch| ch.id == channel_id))
+        .map_or_else(
+            || VoiceKeySource::Call { peer: channel_id.to_string() },
+            |c| VoiceKeySource::Channel { community_id: c.id.clone() },
+        )
+}
+
+/// Give a new voice session its first frame keys.
+///
+/// Channels use the community's two newest MEK generations (the older one
+/// for members who haven't switched yet). For a call we pick our own key
+/// and send it over the Signal session, and use the peer's key if it has
+/// already arrived.
+async fn install_frame_keys(
+    state: &SharedState,
+    pool: &DbPool,
+    source: &VoiceKeySource,
+    keys: &VoiceKeys,
+) -> Result<(), String> {
+    match source {
+        VoiceKeySource::Channel { community_id } => {
+            let (previous, current) = {
+                let mek_cache = state.mek_cache.lock();
+                let ring = mek_cache.get(community_id);
+                let current = ring
+                    .and_then(MediaKeyRing::current)
+                    .map(FrameKey::from_mek)
+                    .ok_or("no encryption key for this community yet")?;
+                let previous = current
+                    .id()
+                    .checked_sub(1)
+                    .and_then(|g| ring.and_then(|r| r.get(g)))
+                    .map(FrameKey::from_mek);
+                (previous, current)
+            };
+            if let Some(previous) = previous {
+                keys.add_receive_key(previous);
+            }
+            keys.add_receive_key(current.clone());
+            keys.set_send_key(current);
+        }
+        VoiceKeySource::Call { peer } => {
+            let peer_key = state.call_keys.lock().get(peer).cloned();
+            if let Some(peer_key) = peer_key {
+                keys.add_receive_key(peer_key);
+            }
+            let key = FrameKey::generate(rand::random());
+            crate::services::message_service::send_call_key(state, pool, peer, &key)
+                .await
+                .map_err(|e| format!("cannot share call key: {e}"))?;
+            keys.set_send_key(key);
+        }
+    }
+    Ok(())
+}
+
+/// A community stored a new MEK generation. If we are in one of its voice
+/// channels, accept frames under it now and, if it is the newest, seal ours
+/// with it once the switch delay has passed.
+pub(crate) fn channel_key_stored(state: &SharedState, community_id: &str, key: FrameKey, is_current: bool) {
+    let ve = state.voice_engine.lock();
+    let Some(handle) = ve.as_ref() else {
+        return;
+    };
+    if handle.key_source != (VoiceKeySource::Channel { community_id: community_id.to_string() }) {
+        return;
+    }
+    tracing::debug!(community = %community_id, key_id = key.id(), "voice frame key added");
+    handle.frame_keys.add_receive_key(key.clone());
+    if is_current {
+        handle.frame_keys.set_send_key(key);
+    }
+}
+
+/// Move our side of a 1:1 call to a fresh frame key.
+///
+/// The key is sent before we switch, and the switch itself is delayed, so
+/// the peer never gets a frame it can't open.
+async fn rotate_call_key(app: tauri::AppHandle) {
+    let state = app.state::<SharedState>().inner().clone();
+    let pool = app.state::<DbPool>().inner().clone();
+    let (peer, keys) = {
+        let ve = state.voice_engine.lock();
+        match ve.as_ref() {
+            Some(VoiceEngineHandle { key_source: VoiceKeySource::Call { peer }, frame_keys, .. }) => {
+                (peer.clone(), Arc::clone(frame_keys))
+            }
+            _ => return,
+        }
+    };
+
+    let key_id = keys.send_key_id().map_or_else(rand::random, |id| id.wrapping_add(1));
+    let key = FrameKey::generate(key_id);
+    match crate::services::message_service::send_call_key(&state, &pool, &peer, &key).await {
+        Ok(()) => {
+            keys.set_send_key(key);
+            tracing::debug!(peer = %peer, key_id, "call frame key rotated");
+        }
+        Err(e) => tracing::warn!(peer = %peer, error = %e, "failed to rotate call frame key"),
+    }
+}
+
 // ── Send Loop ────────────────────────────────────────────────────────────
 
 /// Voice send loop: drains `capture_rx`, runs `AudioProcessor`, encodes with Opus, sends via transport.
 ///
 /// This task owns the `VoiceTransport` and runs until a shutdown signal is received
-/// or the capture channel closes. On exit it disconnects the transport.
+/// or the capture channel closes. On exit it disconnects the transport. It
+/// also sends our round-trip probes, echoes the ones peers send us, and
+/// retunes the encoder from what the echoes report (see `LinkStats`).
 #[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 async fn voice_send_loop(
     capture_rx: Option<mpsc::Receiver<Vec<f32>>>,
@@ -842,6 +1125,7 @@ async fn voice_send_loop(
     echo_cancellation: bool,
     muted_flag: Arc<AtomicBool>,
     mut speaker_ref_rx: broadcast::Receiver<Vec<f32>>,
+    link_stats: Arc<LinkStats>,
 ) {
     let Some(mut capture_rx) = capture_rx else {
         tracing::warn!("voice send loop started without capture_rx — exiting");
@@ -876,10 +1160,24 @@ async fn voice_send_loop(
     // Opus frame boundaries, so we accumulate samples here.
     let mut pcm_buffer: Vec<f32> = Vec::with_capacity(frame_size * 2);
 
-    // Connection quality tracking
+    // Connection quality tracking. Local send failures stand in for loss
+    // until the other end echoes a probe.
     let mut packets_sent: u64 = 0;
     let mut send_failures: u64 = 0;
-    let mut last_quality_report = Instant::now();
+    let mut bitrate = BitrateController::new();
+    let our_key_bytes = hex::decode(&public_key).unwrap_or_default();
+    let mut probe_tick = tokio::time::interval(PROBE_INTERVAL);
+    probe_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut adapt_tick = tokio::time::interval_at(
+        tokio::time::Instant::now() + ENCODER_ADAPT_INTERVAL,
+        ENCODER_ADAPT_INTERVAL,
+    );
+
+    // 1:1 calls move to a fresh frame key periodically (no-op for channels)
+    let mut rekey = tokio::time::interval_at(
+        tokio::time::Instant::now() + CALL_REKEY_INTERVAL,
+        CALL_REKEY_INTERVAL,
+    );
 
     tracing::info!("voice send loop started");
 
@@ -892,6 +1190,55 @@ async fn voice_send_loop(
                 break;
             }
 
+            _ = rekey.tick() => {
+                tokio::spawn(rotate_call_key(app.clone()));
+            }
+
+            _ = probe_tick.tick() => {
+                if !transport.is_connected() {
+                    continue;
+                }
+                let probe = VoiceProbe::new(our_key_bytes.clone(), unix_millis());
+                let echoes = link_stats.take_echoes();
+                for probe in std::iter::once(probe).chain(echoes) {
+                    if let Err(e) = transport.send_probe(&probe).await {
+                        tracing::trace!(error = %e, "voice send loop: probe send failed");
+                    }
+                }
+            }
+
+            _ = adapt_tick.tick() => {
+                let uplink = link_stats.uplink();
+                let loss_pct = uplink.loss_pct.unwrap_or_else(|| {
+                    if packets_sent > 0 {
+                        #[allow(clippy::cast_precision_loss)]
+                        let pct = (send_failures as f32 / packets_sent as f32) * 100.0;
+                        pct
+                    } else {
+                        0.0
+                    }
+                });
+                packets_sent = 0;
+                send_failures = 0;
+
+                let settings = bitrate.update(loss_pct, uplink.rtt_ms);
+                if let Err(e) = codec.set_bitrate(settings.bitrate) {
+                    tracing::debug!(error = %e, "voice send loop: failed to set bitrate");
+                }
+                let _ = codec.set_inband_fec(settings.inband_fec);
+                let _ = codec.set_packet_loss_perc(settings.expected_loss_pct);
+
+                let bars = rekindle_voice::stats::connection_bars(loss_pct, 0.0, uplink.rtt_ms);
+                let event = VoiceEvent::ConnectionQuality {
+                    quality: rekindle_voice::stats::quality_label(bars).to_string(),
+                    bars,
+                    rtt_ms: uplink.rtt_ms,
+                    loss_pct,
+                    bitrate: settings.bitrate,
+                };
+                let _ = app.emit("voice-event", &event);
+            }
+
             maybe_samples = capture_rx.recv() => {
                 let Some(samples) = maybe_samples else {
                     tracing::info!("voice send loop: capture channel closed");
@@ -955,10 +1302,7 @@ async fn voice_send_loop(
                     };
 
                     encoded.sequence = sequence;
-                    encoded.timestamp = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
-                        .unwrap_or(0);
+                    encoded.timestamp = unix_millis();
                     sequence = sequence.wrapping_add(1);
 
                     if transport.is_connected() {
@@ -968,37 +1312,6 @@ async fn voice_send_loop(
                         }
                         packets_sent += 1;
                     }
-
-                    // Periodic connection quality report (every 5s)
-                    if last_quality_report.elapsed() >= Duration::from_secs(5) {
-                        let loss_pct = if packets_sent > 0 {
-                            #[allow(clippy::cast_precision_loss)]
-                            let pct = (send_failures as f64 / packets_sent as f64) * 100.0;
-                            pct
-                        } else {
-                            0.0
-                        };
-                        let quality = if loss_pct < 5.0 {
-                            "good"
-                        } else if loss_pct < 15.0 {
-                            "fair"
-                        } else {
-                            "poor"
-                        };
-                        let event = VoiceEvent::ConnectionQuality {
-                            quality: quality.to_string(),
-                        };
-                        let _ = app.emit("voice-event", &event);
-
-                        // Update Opus FEC based on measured loss
-                        #[allow(clippy::cast_possible_truncation)]
-                        let loss_i32 = (loss_pct as i32).clamp(0, 100);
-                        let _ = codec.set_packet_loss_perc(loss_i32);
-
-                        packets_sent = 0;
-                        send_failures = 0;
-                        last_quality_report = Instant::now();
-                    }
                 }
             }
         }
@@ -1020,12 +1333,21 @@ async fn voice_send_loop(
     tracing::info!("voice send loop exited");
 }
 
+/// Wall-clock milliseconds, for frame timestamps and probes.
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+        .unwrap_or(0)
+}
+
 // ── Receive Loop ─────────────────────────────────────────────────────────
 
 /// Per-participant decoder state in the receive loop.
 struct ParticipantDecoder {
     codec: rekindle_voice::codec::OpusCodec,
     jitter_buffer: rekindle_voice::jitter::JitterBuffer,
+    stats: StreamStats,
     is_speaking: bool,
     last_packet_time: Instant,
 }
@@ -1033,16 +1355,26 @@ struct ParticipantDecoder {
 /// Voice receive loop: receives `VoicePacket`s, decodes per-participant, mixes, sends to playback.
 ///
 /// Runs on a 20ms tick (50Hz) cadence that drives decode/mix/playback independently
-/// of packet arrival timing.
-#[allow(clippy::too_many_lines)]
+/// of packet arrival timing. When `relayed`, the community's voice relay
+/// reports who is in the channel and who is speaking, so the loop emits no
+/// participant events of its own.
+///
+/// Each participant's stream is measured as it arrives; every
+/// `STREAM_REPORT_INTERVAL` the loop reports it to the UI, sizes that
+/// participant's jitter buffer to the measured jitter, and keeps the loss
+/// in `link_stats` for echoing back. Probes are handed to `link_stats` too.
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 async fn voice_receive_loop(
-    mut packet_rx: mpsc::Receiver<rekindle_voice::transport::VoicePacket>,
+    mut packet_rx: mpsc::Receiver<VoiceMessage>,
     playback_tx: Option<mpsc::Sender<Vec<f32>>>,
     mut shutdown_rx: mpsc::Receiver<()>,
     app: tauri::AppHandle,
     our_public_key: String,
     deafened_flag: Arc<AtomicBool>,
     speaker_ref_tx: broadcast::Sender<Vec<f32>>,
+    frame_keys: Arc<VoiceKeys>,
+    link_stats: Arc<LinkStats>,
+    relayed: bool,
 ) {
     let Some(playback_tx) = playback_tx else {
         tracing::warn!("voice receive loop started without playback_tx — exiting");
@@ -1066,6 +1398,7 @@ async fn voice_receive_loop(
     // Connection quality tracking
     let mut packets_received: u64 = 0;
     let mut last_quality_check = Instant::now();
+    let mut last_stream_report = Instant::now();
 
     tracing::info!("voice receive loop started");
 
@@ -1079,12 +1412,35 @@ async fn voice_receive_loop(
             }
 
             // Receive incoming voice packets and push into per-participant jitter buffers
-            Some(packet) = packet_rx.recv() => {
+            Some(message) = packet_rx.recv() => {
+                let mut packet = match message {
+                    VoiceMessage::Frame(packet) => packet,
+                    VoiceMessage::Probe(probe) => {
+                        if probe.sender_key == our_key_bytes {
+                            if probe.echo {
+                                link_stats.on_echo(&probe, unix_millis());
+                            }
+                        } else if !probe.echo {
+                            link_stats.queue_echo(probe);
+                        }
+                        continue;
+                    }
+                };
+
                 // Skip our own packets
                 if packet.sender_key == our_key_bytes {
                     continue;
                 }
 
+                // Open the frame before it reaches the jitter buffer
+                match frame_keys.open(&packet) {
+                    Ok(audio) => packet.audio_data = audio,
+                    Err(e) => {
+                        tracing::trace!(error = %e, key_id = packet.key_id, "dropping voice frame we cannot open");
+                        continue;
+                    }
+                }
+
                 packets_received += 1;
 
                 let sender_key = packet.sender_key.clone();
@@ -1097,15 +1453,18 @@ async fn voice_receive_loop(
                             tracing::info!(peer = %sender_hex, "new voice participant");
 
                             // Emit UserJoined event
-                            let event = VoiceEvent::UserJoined {
-                                public_key: sender_hex.clone(),
-                                display_name: sender_hex,
-                            };
-                            let _ = app.emit("voice-event", &event);
+                            if !relayed {
+                                let event = VoiceEvent::UserJoined {
+                                    public_key: sender_hex.clone(),
+                                    display_name: sender_hex,
+                                };
+                                let _ = app.emit("voice-event", &event);
+                            }
 
                             participants.insert(sender_key.clone(), ParticipantDecoder {
                                 codec,
                                 jitter_buffer: rekindle_voice::jitter::JitterBuffer::new(jitter_buffer_ms),
+                                stats: StreamStats::new(),
                                 is_speaking: false,
                                 last_packet_time: Instant::now(),
                             });
@@ -1118,11 +1477,12 @@ async fn voice_receive_loop(
                 }
 
                 if let Some(participant) = participants.get_mut(&sender_key) {
+                    participant.stats.on_packet(packet.sequence, packet.timestamp, unix_millis());
                     participant.jitter_buffer.push(packet);
                     participant.last_packet_time = Instant::now();
 
                     // Track speaking state
-                    if !participant.is_speaking {
+                    if !participant.is_speaking && !relayed {
                         participant.is_speaking = true;
                         let event = VoiceEvent::UserSpeaking {
                             public_key: hex::encode(&sender_key),
@@ -1207,9 +1567,14 @@ async fn voice_receive_loop(
                     .collect();
 
                 for key in timeout_keys {
+                    link_stats.forget(&key);
                     if let Some(participant) = participants.remove(&key) {
                         let peer_hex = hex::encode(&key);
                         tracing::info!(peer = %peer_hex, "voice participant timed out");
+                        if relayed {
+                            // Silent, not gone — the relay tells us when they leave
+                            continue;
+                        }
 
                         if participant.is_speaking {
                             let event = VoiceEvent::UserSpeaking {
@@ -1240,6 +1605,33 @@ async fn voice_receive_loop(
                     }
                 }
 
+                // Per-participant stream reports
+                if last_stream_report.elapsed() >= STREAM_REPORT_INTERVAL {
+                    let rtt_ms = link_stats.uplink().rtt_ms;
+                    for (key, participant) in &mut participants {
+                        let report = participant.stats.take_report();
+                        link_stats.record_received(key, &report);
+                        participant.jitter_buffer.set_target_delay_ms(
+                            rekindle_voice::stats::recommended_buffer_ms(report.jitter_ms),
+                        );
+                        let depth = u32::try_from(participant.jitter_buffer.depth()).unwrap_or(u32::MAX);
+                        let event = VoiceEvent::ParticipantStats {
+                            public_key: hex::encode(key),
+                            loss_pct: report.loss_pct,
+                            jitter_ms: report.jitter_ms,
+                            late_drops: participant.jitter_buffer.late_drops(),
+                            buffer_ms: depth.saturating_mul(20),
+                            bars: rekindle_voice::stats::connection_bars(
+                                report.loss_pct,
+                                report.jitter_ms,
+                                rtt_ms,
+                            ),
+                        };
+                        let _ = app.emit("voice-event", &event);
+                    }
+                    last_stream_report = Instant::now();
+                }
+
                 // Periodic quality report
                 if last_quality_check.elapsed() >= Duration::from_secs(5) {
                     tracing::debug!(
@@ -1254,20 +1646,23 @@ async fn voice_receive_loop(
         }
     }
 
-    // Emit UserLeft for all remaining participants
-    for (key, participant) in &participants {
-        let peer_hex = hex::encode(key);
-        if participant.is_speaking {
-            let event = VoiceEvent::UserSpeaking {
-                public_key: peer_hex.clone(),
-                speaking: false,
+    // Emit UserLeft for all remaining participants (a relayed channel's
+    // roster outlives this loop, e.g. across a device hot-swap)
+    if !relayed {
+        for (key, participant) in &participants {
+            let peer_hex = hex::encode(key);
+            if participant.is_speaking {
+                let event = VoiceEvent::UserSpeaking {
+                    public_key: peer_hex.clone(),
+                    speaking: false,
+                };
+                let _ = app.emit("voice-event", &event);
+            }
+            let event = VoiceEvent::UserLeft {
+                public_key: peer_hex,
             };
             let _ = app.emit("voice-event", &event);
         }
-        let event = VoiceEvent::UserLeft {
-            public_key: peer_hex,
-        };
-        let _ = app.emit("voice-event", &event);
     }
 
     tracing::info!("voice receive loop exited");
@@ -60,6 +60,16 @@ pub async fn open_community_window(
     windows::open_community_window(&app, &community_id, &community_name)
 }
 
+/// Open a group conversation window.
+#[tauri::command]
+pub async fn open_group_window(
+    group_id: String,
+    group_name: String,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    windows::open_group_window(&app, &group_id, &group_name)
+}
+
 /// Open a profile window for viewing a friend's profile.
 #[tauri::command]
 pub async fn open_profile_window(
@@ -1,18 +1,131 @@
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LockResult, Mutex, MutexGuard};
 
 use rusqlite::Connection;
+use zeroize::Zeroizing;
 
-/// Thread-safe database handle wrapping a single `rusqlite::Connection`.
+/// Database handle shared across the app.
 ///
-/// `rusqlite::Connection` is `Send` but not `Sync`, so we guard it with
-/// `std::sync::Mutex` (not `parking_lot` — its guards are `!Send` which can
-/// cause issues with async runtimes).
-pub type DbPool = Arc<Mutex<Connection>>;
+/// `rusqlite::Connection` is `Send` but not `Sync`, so each connection is
+/// guarded with `std::sync::Mutex` (not `parking_lot` — its guards are `!Send`
+/// which can cause issues with async runtimes).
+pub type DbPool = Arc<Database>;
+
+/// The 32-byte `SQLCipher` key for one identity's database.
+///
+/// Generated at random and stored only in that identity's Stronghold
+/// snapshot, so the database can't be read without the passphrase.
+pub type DatabaseKey = Zeroizing<[u8; 32]>;
 
 /// Bump this every time `001_init.sql` changes.  On mismatch the entire
 /// database is wiped and recreated from the schema — safe because the app
 /// is not live yet and identity keys live in Stronghold, not `SQLite`.
-const SCHEMA_VERSION: i64 = 16;
+const SCHEMA_VERSION: i64 = 26;
+
+/// The local databases.
+///
+/// `rekindle.db` is the plaintext *directory*: it only holds the public
+/// columns of each `identity` row. Everything else — messages, friends,
+/// communities — lives in a per-identity `SQLCipher` database
+/// (`{public_key}.db`) that is opened on login with a key from Stronghold.
+///
+/// Queries go through [`Database::lock`], which returns the unlocked
+/// identity's database while one is open and the directory otherwise.
+pub struct Database {
+    active: Mutex<Connection>,
+    /// The directory, parked here while an identity database is active.
+    directory: Mutex<Option<Connection>>,
+}
+
+impl Database {
+    /// Lock the active connection.
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, Connection>> {
+        self.active.lock()
+    }
+
+    /// Run `f` against the directory, whether or not an identity is open.
+    pub fn with_directory<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T, String>,
+    ) -> Result<T, String> {
+        // Always `active` before `directory`, matching the swaps below.
+        let active = self.active.lock().map_err(|e| e.to_string())?;
+        let parked = self.directory.lock().map_err(|e| e.to_string())?;
+        match parked.as_ref() {
+            Some(directory) => f(directory),
+            None => f(&active),
+        }
+    }
+
+    /// Open the encrypted database for `public_key` and make it active.
+    ///
+    /// Any identity database already open is closed first. On the first
+    /// unlock of an identity whose data still sits in the plaintext
+    /// directory (created before encryption, or just now by
+    /// `create_identity`), its rows are moved across and scrubbed from the
+    /// directory.
+    pub fn open_identity(
+        &self,
+        config_dir: &Path,
+        public_key: &str,
+        key: &DatabaseKey,
+    ) -> Result<(), String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+        let mut parked = self.directory.lock().map_err(|e| e.to_string())?;
+        if let Some(directory) = parked.take() {
+            drop(std::mem::replace(&mut *active, directory));
+        }
+
+        let path = identity_db_path(config_dir, public_key);
+        let conn = Connection::open(&path)
+            .map_err(|e| format!("failed to open identity database: {e}"))?;
+        apply_key(&conn, "PRAGMA key", key)?;
+        // The key is only checked once a page is read.
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+            .map_err(|_| "identity database key rejected".to_string())?;
+        migrate(&conn)?;
+
+        let migrated: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM identity WHERE public_key = ?)",
+                [public_key],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !migrated {
+            move_legacy_rows(&active, &path, public_key, key)?;
+            rebuild_search_index(&conn)?;
+        }
+
+        *parked = Some(std::mem::replace(&mut *active, conn));
+        Ok(())
+    }
+
+    /// Close the active identity database, making the directory active.
+    pub fn close_identity(&self) -> Result<(), String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+        let mut parked = self.directory.lock().map_err(|e| e.to_string())?;
+        if let Some(directory) = parked.take() {
+            drop(std::mem::replace(&mut *active, directory));
+        }
+        Ok(())
+    }
+
+    /// Re-encrypt the active identity database under `key`.
+    pub fn rekey(&self, key: &DatabaseKey) -> Result<(), String> {
+        let conn = self.active.lock().map_err(|e| e.to_string())?;
+        if self.directory.lock().map_err(|e| e.to_string())?.is_none() {
+            return Err("no identity database is open".into());
+        }
+        // SQLCipher can't rekey a database in WAL mode.
+        conn.execute_batch("PRAGMA journal_mode=DELETE;")
+            .map_err(|e| format!("failed to leave WAL mode: {e}"))?;
+        let rekeyed = apply_key(&conn, "PRAGMA rekey", key);
+        conn.execute_batch("PRAGMA journal_mode=WAL;")
+            .map_err(|e| format!("failed to set WAL mode: {e}"))?;
+        rekeyed
+    }
+}
 
 /// Result of opening the database — includes a flag indicating whether the
 /// schema was recreated from scratch (so the caller can wipe dependent storage).
@@ -24,12 +137,52 @@ pub struct DbOpenResult {
     pub schema_reset: bool,
 }
 
-/// Open (or create) a `SQLite` database at `db_path` and run the initial schema
-/// migration.  Returns a `DbOpenResult` with the pool and a reset flag.
+/// Open (or create) the plaintext directory database at `db_path` and run
+/// the initial schema migration.  Returns a `DbOpenResult` with the pool and
+/// a reset flag.
 pub fn create_pool(db_path: &str) -> Result<DbOpenResult, String> {
-    let conn = Connection::open(db_path)
-        .map_err(|e| format!("failed to connect to database: {e}"))?;
+    let conn =
+        Connection::open(db_path).map_err(|e| format!("failed to connect to database: {e}"))?;
+
+    // Overwrite deleted content rather than leaving it in free pages, so
+    // legacy rows moved into an identity database don't linger here.
+    conn.execute_batch("PRAGMA secure_delete=ON;")
+        .map_err(|e| format!("failed to enable secure delete: {e}"))?;
+
+    let schema_reset = migrate(&conn)?;
+
+    Ok(DbOpenResult {
+        pool: Arc::new(Database {
+            active: Mutex::new(conn),
+            directory: Mutex::new(None),
+        }),
+        schema_reset,
+    })
+}
+
+/// Path of the encrypted database for `public_key`.
+pub fn identity_db_path(config_dir: &Path, public_key: &str) -> PathBuf {
+    config_dir.join(format!("{public_key}.db"))
+}
+
+/// Delete the encrypted database of `public_key` along with its WAL files.
+pub fn delete_identity_db(config_dir: &Path, public_key: &str) -> Result<(), std::io::Error> {
+    let path = identity_db_path(config_dir, public_key);
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.clone().into_os_string();
+        file.push(suffix);
+        let file = PathBuf::from(file);
+        if file.exists() {
+            std::fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
 
+/// Set connection pragmas and bring the schema up to date.
+///
+/// Returns `true` if the schema was recreated from scratch.
+fn migrate(conn: &Connection) -> Result<bool, String> {
     // Enable WAL mode for better concurrent-read performance.
     conn.execute_batch("PRAGMA journal_mode=WAL;")
         .map_err(|e| format!("failed to set WAL mode: {e}"))?;
@@ -53,16 +206,232 @@ pub fn create_pool(db_path: &str) -> Result<DbOpenResult, String> {
                 "schema version mismatch — recreating database"
             );
         }
-        drop_all_tables(&conn)?;
+        drop_all_tables(conn)?;
         conn.execute_batch(include_str!("../migrations/001_init.sql"))
             .map_err(|e| format!("failed to run schema: {e}"))?;
         conn.pragma_update(None, "user_version", SCHEMA_VERSION)
             .map_err(|e| format!("failed to set schema version: {e}"))?;
+    } else {
+        ensure_search_index(conn)?;
     }
 
-    Ok(DbOpenResult {
-        pool: Arc::new(Mutex::new(conn)),
-        schema_reset,
+    Ok(schema_reset)
+}
+
+/// Run `pragma` (`PRAGMA key` or `PRAGMA rekey`) with `key` as a raw key,
+/// skipping `SQLCipher`'s passphrase derivation.
+fn apply_key(conn: &Connection, pragma: &str, key: &DatabaseKey) -> Result<(), String> {
+    let sql = Zeroizing::new(format!(
+        "{pragma} = \"x'{}'\";",
+        hex::encode(key.as_slice())
+    ));
+    conn.execute_batch(&sql)
+        .map_err(|e| format!("failed to set database key: {e}"))
+}
+
+/// Move every row belonging to `public_key` from the directory into the
+/// encrypted database at `path`, leaving only the directory columns of its
+/// `identity` row behind.
+fn move_legacy_rows(
+    directory: &Connection,
+    path: &Path,
+    public_key: &str,
+    key: &DatabaseKey,
+) -> Result<(), String> {
+    let listed: bool = directory
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM identity WHERE public_key = ?)",
+            [public_key],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !listed {
+        return Ok(());
+    }
+    let tables = copyable_tables(directory)?;
+    let path = path.to_str().ok_or("identity database path is not UTF-8")?;
+    let key_literal = Zeroizing::new(format!("x'{}'", hex::encode(key.as_slice())));
+
+    // Rows are copied table by table, so children may land before parents.
+    directory
+        .execute_batch("PRAGMA foreign_keys=OFF;")
+        .map_err(|e| format!("failed to disable foreign keys: {e}"))?;
+    directory
+        .execute(
+            "ATTACH DATABASE ?1 AS secure KEY ?2",
+            rusqlite::params![path, key_literal.as_str()],
+        )
+        .map_err(|e| format!("failed to attach identity database: {e}"))?;
+
+    let copied = copy_rows(directory, &tables, public_key);
+    let _ = directory.execute_batch("DETACH DATABASE secure; PRAGMA foreign_keys=ON;");
+    copied?;
+
+    scrub_directory(directory, public_key)
+}
+
+/// Copy the selected rows of each table from `main` into `secure` in one
+/// transaction.
+fn copy_rows(
+    directory: &Connection,
+    tables: &[(String, String)],
+    public_key: &str,
+) -> Result<(), String> {
+    let tx = directory
+        .unchecked_transaction()
+        .map_err(|e| e.to_string())?;
+    for (table, filter) in tables {
+        tx.execute(
+            &format!(
+                "INSERT INTO secure.\"{table}\" SELECT * FROM main.\"{table}\" WHERE {filter}"
+            ),
+            [public_key],
+        )
+        .map_err(|e| format!("failed to move {table}: {e}"))?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Tables to move into an identity database, each with a `WHERE` clause
+/// (taking the public key as `?1`) selecting that identity's rows.
+///
+/// FTS tables are skipped — the index is rebuilt in the identity database.
+fn copyable_tables(conn: &Connection) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT name, sql FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut all = Vec::new();
+    for row in rows {
+        all.push(row.map_err(|e| e.to_string())?);
+    }
+
+    let virtual_tables: Vec<&str> = all
+        .iter()
+        .filter(|(_, sql)| sql.starts_with("CREATE VIRTUAL"))
+        .map(|(name, _)| name.as_str())
+        .collect();
+
+    let mut tables = Vec::new();
+    for (name, sql) in &all {
+        if sql.starts_with("CREATE VIRTUAL")
+            || virtual_tables
+                .iter()
+                .any(|vt| name.starts_with(&format!("{vt}_")))
+        {
+            continue;
+        }
+        let filter = if name == "identity" {
+            "public_key = ?1".to_string()
+        } else if column_names(conn, name)?.iter().any(|c| c == "owner_key") {
+            "owner_key = ?1".to_string()
+        } else if let Some((column, parent, parent_column)) = owned_parent(conn, name)? {
+            format!("\"{column}\" IN (SELECT \"{parent_column}\" FROM main.\"{parent}\" WHERE owner_key = ?1)")
+        } else {
+            tracing::warn!(table = %name, "table has no owner — not moved to identity database");
+            continue;
+        };
+        tables.push((name.clone(), filter));
+    }
+    Ok(tables)
+}
+
+/// Column names of `table`.
+fn column_names(conn: &Connection, table: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info(\"{table}\")"))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>("name"))
+        .map_err(|e| e.to_string())?;
+    let mut names = Vec::new();
+    for row in rows {
+        names.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(names)
+}
+
+/// The first foreign key of `table` pointing at an owner-scoped table, as
+/// `(column, parent table, parent column)`.
+fn owned_parent(
+    conn: &Connection,
+    table: &str,
+) -> Result<Option<(String, String, String)>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA foreign_key_list(\"{table}\")"))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>("from")?,
+                row.get::<_, String>("table")?,
+                row.get::<_, String>("to")?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut keys = Vec::new();
+    for row in rows {
+        keys.push(row.map_err(|e| e.to_string())?);
+    }
+    for key in keys {
+        if column_names(conn, &key.1)?.iter().any(|c| c == "owner_key") {
+            return Ok(Some(key));
+        }
+    }
+    Ok(None)
+}
+
+/// Remove everything but the directory columns of `public_key` from the
+/// directory, then compact it so nothing is left in free pages or the WAL.
+fn scrub_directory(directory: &Connection, public_key: &str) -> Result<(), String> {
+    // Pull the directory row out first: deleting `identity` cascades to
+    // every owner-scoped table.
+    let (display_name, created_at, avatar): (String, i64, Option<Vec<u8>>) = directory
+        .query_row(
+            "SELECT display_name, created_at, avatar_webp FROM identity WHERE public_key = ?",
+            [public_key],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("failed to read directory entry: {e}"))?;
+    directory
+        .execute("DELETE FROM identity WHERE public_key = ?", [public_key])
+        .map_err(|e| format!("failed to scrub directory: {e}"))?;
+    directory
+        .execute(
+            "INSERT INTO identity (public_key, display_name, created_at, avatar_webp) VALUES (?, ?, ?, ?)",
+            rusqlite::params![public_key, display_name, created_at, avatar],
+        )
+        .map_err(|e| format!("failed to rewrite directory entry: {e}"))?;
+    directory
+        .execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+        .map_err(|e| format!("failed to compact directory: {e}"))
+}
+
+/// Copy the directory columns of `public_key` from the active identity
+/// database to the directory, e.g. after a display name change.
+pub fn sync_directory_entry(pool: &Database, public_key: &str) -> Result<(), String> {
+    let entry: (String, Option<Vec<u8>>) = {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT display_name, avatar_webp FROM identity WHERE public_key = ?",
+            [public_key],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?
+    };
+    pool.with_directory(|conn| {
+        conn.execute(
+            "UPDATE identity SET display_name = ?, avatar_webp = ? WHERE public_key = ?",
+            rusqlite::params![entry.0, entry.1, public_key],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
     })
 }
 
@@ -72,8 +441,12 @@ fn drop_all_tables(conn: &Connection) -> Result<(), String> {
     conn.execute_batch("PRAGMA foreign_keys=OFF;")
         .map_err(|e| format!("failed to disable foreign keys: {e}"))?;
 
+    // Virtual tables first, so their shadow tables go with them.
     let mut stmt = conn
-        .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'")
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' \
+             ORDER BY sql LIKE 'CREATE VIRTUAL%' DESC",
+        )
         .map_err(|e| format!("failed to list tables: {e}"))?;
     let tables: Vec<String> = stmt
         .query_map([], |row| row.get(0))
@@ -93,6 +466,30 @@ fn drop_all_tables(conn: &Connection) -> Result<(), String> {
     Ok(())
 }
 
+/// Rebuild `messages_fts` if it has fallen out of step with `messages`.
+///
+/// The triggers keep the index current, but a database written before the
+/// index existed, or restored from elsewhere, can hold rows it never saw.
+fn ensure_search_index(conn: &Connection) -> Result<(), String> {
+    let count = |sql: &str| -> Result<i64, String> {
+        conn.query_row(sql, [], |row| row.get(0))
+            .map_err(|e| format!("failed to check search index: {e}"))
+    };
+    let indexed = count("SELECT COUNT(*) FROM messages_fts_docsize")?;
+    let stored = count("SELECT COUNT(*) FROM messages")?;
+    if indexed != stored {
+        tracing::info!(indexed, stored, "search index out of date — rebuilding");
+        rebuild_search_index(conn)?;
+    }
+    Ok(())
+}
+
+/// Re-index every message body from scratch.
+pub fn rebuild_search_index(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("INSERT INTO messages_fts(messages_fts) VALUES ('rebuild');")
+        .map_err(|e| format!("failed to rebuild search index: {e}"))
+}
+
 /// Extract a `String` column by name, returning `""` on any failure.
 pub fn get_str(row: &rusqlite::Row<'_>, col: &str) -> String {
     row.get::<_, String>(col).unwrap_or_default()
@@ -108,6 +505,11 @@ pub fn get_i64(row: &rusqlite::Row<'_>, col: &str) -> i64 {
     row.get::<_, i64>(col).unwrap_or_default()
 }
 
+/// Extract an optional `i64` column by name.
+pub fn get_i64_opt(row: &rusqlite::Row<'_>, col: &str) -> Option<i64> {
+    row.get::<_, Option<i64>>(col).ok().flatten()
+}
+
 /// Current UNIX timestamp in milliseconds.
 pub fn timestamp_now() -> i64 {
     std::time::SystemTime::now()
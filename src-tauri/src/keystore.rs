@@ -6,8 +6,11 @@ use iota_stronghold::{KeyProvider, SnapshotPath, Stronghold};
 use parking_lot::Mutex;
 use zeroize::Zeroizing;
 
+use rekindle_crypto::keychain::{KEY_DATABASE, VAULT_IDENTITY};
 use rekindle_crypto::{CryptoError, Keychain};
 
+use crate::db::DatabaseKey;
+
 /// A Stronghold-backed keystore for securely persisting key material.
 ///
 /// Uses `iota_stronghold` directly: secrets are stored in the client `Store`
@@ -107,6 +110,45 @@ impl StrongholdKeystore {
             .map_err(|e| CryptoError::StorageError(format!("commit snapshot: {e}")))?;
         Ok(())
     }
+
+    /// Re-encrypt the snapshot under a new passphrase and save it.
+    pub fn change_passphrase(&mut self, passphrase: &str) -> Result<(), CryptoError> {
+        self.keyprovider = KeyProvider::try_from(Zeroizing::new(derive_key(passphrase)))
+            .map_err(|e| CryptoError::StorageError(format!("key provider init: {e:?}")))?;
+        self.save()
+    }
+
+    /// Load the identity's database key, generating and saving one if the
+    /// snapshot predates database encryption.
+    pub fn database_key(&self) -> Result<DatabaseKey, CryptoError> {
+        if let Some(key) = self.load_database_key(KEY_DATABASE)? {
+            return Ok(key);
+        }
+        let key = generate_database_key();
+        self.store_key(VAULT_IDENTITY, KEY_DATABASE, key.as_slice())?;
+        self.save()?;
+        Ok(key)
+    }
+
+    /// Load a database key stored under `name` in the identity vault.
+    pub fn load_database_key(&self, name: &str) -> Result<Option<DatabaseKey>, CryptoError> {
+        let Some(bytes) = self.load_key(VAULT_IDENTITY, name)? else {
+            return Ok(None);
+        };
+        let bytes = Zeroizing::new(bytes);
+        let key: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| CryptoError::StorageError("database key has wrong length".into()))?;
+        Ok(Some(Zeroizing::new(key)))
+    }
+}
+
+/// A fresh random database key.
+pub fn generate_database_key() -> DatabaseKey {
+    let mut key = Zeroizing::new([0u8; 32]);
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, key.as_mut_slice());
+    key
 }
 
 impl Keychain for StrongholdKeystore {
@@ -248,6 +290,38 @@ mod tests {
         assert!(result.is_err(), "wrong passphrase should fail to load snapshot");
     }
 
+    #[test]
+    fn change_passphrase_reencrypts_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let secret = [42u8; 32];
+
+        {
+            let mut ks = StrongholdKeystore::initialize(dir.path(), "old-pass").unwrap();
+            ks.store_key(VAULT_IDENTITY, KEY_ED25519_PRIVATE, &secret)
+                .unwrap();
+            ks.change_passphrase("new-pass").unwrap();
+        }
+
+        assert!(StrongholdKeystore::initialize(dir.path(), "old-pass").is_err());
+        let ks = StrongholdKeystore::initialize(dir.path(), "new-pass").unwrap();
+        let loaded = ks
+            .load_key(VAULT_IDENTITY, KEY_ED25519_PRIVATE)
+            .unwrap()
+            .expect("key should survive the passphrase change");
+        assert_eq!(loaded, secret);
+    }
+
+    #[test]
+    fn database_key_is_generated_once() {
+        let dir = TempDir::new().unwrap();
+        let first = {
+            let ks = StrongholdKeystore::initialize(dir.path(), "pass").unwrap();
+            ks.database_key().unwrap()
+        };
+        let ks = StrongholdKeystore::initialize(dir.path(), "pass").unwrap();
+        assert_eq!(*ks.database_key().unwrap(), *first);
+    }
+
     #[test]
     fn key_exists_and_delete() {
         let dir = TempDir::new().unwrap();
@@ -6,6 +6,7 @@ pub mod db;
 pub mod ipc_client;
 pub mod keystore;
 mod services;
+pub mod signal_store;
 pub mod state;
 mod tray;
 mod windows;
@@ -192,12 +193,34 @@ pub fn run() {
             commands::auth::logout,
             commands::auth::list_identities,
             commands::auth::delete_identity,
+            commands::auth::change_passphrase,
+            commands::auth::complete_device_link,
+            commands::auth::export_identity_backup,
+            commands::auth::import_identity_backup,
+            commands::auth::get_recovery_phrase,
+            commands::auth::restore_from_recovery_phrase,
+            // devices
+            commands::devices::create_device_link_request,
+            commands::devices::cancel_device_link,
+            commands::devices::approve_device_link,
+            commands::devices::get_devices,
+            commands::devices::remove_device,
             // chat
             commands::chat::prepare_chat_session,
             commands::chat::send_message,
             commands::chat::send_typing,
+            commands::chat::send_file,
+            commands::chat::download_attachment,
+            commands::chat::edit_message,
+            commands::chat::delete_message,
+            commands::chat::add_reaction,
+            commands::chat::remove_reaction,
+            commands::chat::set_disappearing_timer,
             commands::chat::get_message_history,
             commands::chat::mark_read,
+            // search
+            commands::search::search_messages,
+            commands::search::rebuild_search_index,
             // friends
             commands::friends::add_friend,
             commands::friends::remove_friend,
@@ -215,6 +238,9 @@ pub fn run() {
             commands::friends::get_blocked_users,
             commands::friends::cancel_request,
             commands::friends::emit_friends_presence,
+            commands::friends::get_safety_number,
+            commands::friends::set_friend_verified,
+            commands::friends::verify_safety_number_qr,
             // community
             commands::community::create_community,
             commands::community::join_community,
@@ -238,11 +264,39 @@ pub fn run() {
             commands::community::leave_community,
             commands::community::delete_channel,
             commands::community::rename_channel,
+            commands::community::set_channel_retention,
             commands::community::update_community_info,
             commands::community::ban_member,
             commands::community::unban_member,
             commands::community::get_ban_list,
+            commands::community::get_audit_log,
+            commands::community::get_channel_threads,
+            commands::community::create_thread,
+            commands::community::archive_thread,
+            commands::community::join_thread,
+            commands::community::leave_thread,
+            commands::community::add_thread_member,
+            commands::community::remove_thread_member,
+            commands::community::get_community_listing,
+            commands::community::set_community_listing,
+            commands::community::create_community_invite,
+            commands::community::list_community_invites,
+            commands::community::revoke_community_invite,
+            commands::community::search_directory,
+            commands::community::preview_directory_listing,
             commands::community::rotate_mek,
+            commands::community::enable_zero_knowledge,
+            commands::community::vouch_community_member,
+            commands::community::enable_tree_kem,
+            // groups
+            commands::groups::create_group_chat,
+            commands::groups::get_group_chats,
+            commands::groups::get_group_messages,
+            commands::groups::send_group_message,
+            commands::groups::add_group_member,
+            commands::groups::leave_group_chat,
+            commands::groups::mark_group_read,
+            commands::groups::refresh_group_chat,
             // voice
             commands::voice::join_voice_channel,
             commands::voice::leave_voice,
@@ -258,6 +312,7 @@ pub fn run() {
             commands::status::set_status_message,
             // game
             commands::game::get_game_status,
+            commands::game::get_known_games,
             // settings
             commands::settings::get_preferences,
             commands::settings::set_preferences,
@@ -267,6 +322,7 @@ pub fn run() {
             commands::window::open_chat_window,
             commands::window::open_settings_window,
             commands::window::open_community_window,
+            commands::window::open_group_window,
             commands::window::open_profile_window,
             commands::window::get_network_status,
         ])
@@ -343,6 +399,12 @@ async fn graceful_shutdown(state: &SharedState) {
         let _ = tx.send(()).await;
     }
 
+    // Signal prekey maintenance shutdown
+    let prekey_tx = state.prekey_shutdown_tx.write().take();
+    if let Some(tx) = prekey_tx {
+        let _ = tx.send(()).await;
+    }
+
     // Signal dispatch loop shutdown
     let shutdown_tx = state.shutdown_tx.read().clone();
     if let Some(tx) = shutdown_tx {
@@ -440,7 +502,12 @@ async fn graceful_shutdown(state: &SharedState) {
 
     // Clear community state
     state.mek_cache.lock().clear();
+    state.mek_sessions.lock().clear();
+    state.tree_groups.lock().clear();
+    state.call_keys.lock().clear();
     state.community_routes.write().clear();
+    state.community_lists.write().clear();
+    state.directory_listings.write().clear();
 
     // 8. Shut down the Veilid node (only on app exit)
     services::veilid_service::shutdown_app(state).await;
@@ -470,7 +537,28 @@ fn wipe_dependent_storage(config_dir: &std::path::Path, app: &tauri::App) {
         }
     }
 
-    // 2. Remove Veilid local storage directory (DHT record cache, table store, etc.)
+    // 2. Remove the per-identity encrypted databases (`{public_key}.db` and
+    //    their WAL files) — their keys were in the Stronghold files above.
+    if let Ok(entries) = std::fs::read_dir(config_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let stem = name
+                .strip_suffix(".db")
+                .or_else(|| name.strip_suffix(".db-wal"))
+                .or_else(|| name.strip_suffix(".db-shm"))
+                .unwrap_or("");
+            if stem.len() == 64 && stem.bytes().all(|b| b.is_ascii_hexdigit()) {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    tracing::warn!(path = %path.display(), error = %e, "failed to remove orphaned identity database");
+                } else {
+                    tracing::info!(path = %path.display(), "removed orphaned identity database");
+                }
+            }
+        }
+    }
+
+    // 3. Remove Veilid local storage directory (DHT record cache, table store, etc.)
     if let Ok(data_dir) = app.path().app_data_dir() {
         let veilid_dir = data_dir.join("veilid");
         if veilid_dir.exists() {
@@ -0,0 +1,617 @@
+//! The account record: our social graph, kept on the DHT.
+//!
+//! The record's owner keypair is derived from the identity secret (see
+//! `Identity::account_record_owner`), and Veilid derives a record key from
+//! its owner, so the record can be found again from the secret alone — after
+//! restoring an identity from its recovery phrase, for instance. The primary
+//! device keeps it in line with `SQLite`: accepted friends and their
+//! conversation keys in the contact list, friend groups and communities in
+//! the header.
+//!
+//! When login finds a header already on the network for a database that
+//! never pointed at it, the record is merged back into `SQLite` and a
+//! `SocialGraphRestored` notification reports what came back and what did
+//! not. The record does not hold message history, pending friend requests,
+//! blocked users, Signal sessions (restarted from the friends' published
+//! prekeys) or the member list of communities we host.
+
+use std::sync::Arc;
+
+use rekindle_protocol::capnp_codec::account::{CommunityEntry, ContactEntry};
+use rekindle_protocol::dht::account::AccountRecord;
+use tauri::{Emitter, Manager as _};
+
+use crate::channels::{ChatEvent, NotificationEvent};
+use crate::commands::auth::current_owner_key;
+use crate::db::{self, DbPool};
+use crate::keystore::KeystoreHandle;
+use crate::services::{device_service, presence_service};
+use crate::state::{AppState, FriendState, FriendshipState, UserStatus};
+
+/// What a recovery brought back, and what it could not.
+#[derive(Debug, Default)]
+pub struct RecoveryReport {
+    pub friends: u32,
+    pub groups: u32,
+    pub communities: u32,
+    /// Friends whose Signal session restarts with our next message.
+    pub sessions_pending: u32,
+    /// One line per item that could not be restored, with the reason.
+    pub failed: Vec<String>,
+}
+
+impl RecoveryReport {
+    fn is_empty(&self) -> bool {
+        self.friends == 0 && self.groups == 0 && self.communities == 0 && self.failed.is_empty()
+    }
+}
+
+/// Veilid owner keypair of our account record, derived from the identity secret.
+pub fn owner_keypair(secret: &[u8; 32]) -> veilid_core::KeyPair {
+    let owner = rekindle_crypto::Identity::from_secret_bytes(secret).account_record_owner();
+    let bare_pub = veilid_core::BarePublicKey::new(&owner.public_key_bytes());
+    let bare_secret = veilid_core::BareSecretKey::new(owner.secret_key_bytes());
+    let public = veilid_core::PublicKey::new(veilid_core::CRYPTO_KIND_VLD0, bare_pub);
+    veilid_core::KeyPair::new_from_parts(public, bare_secret)
+}
+
+/// Open our account record.
+pub async fn open_account_record(state: &AppState, pool: &DbPool) -> Result<AccountRecord, String> {
+    Ok(open_or_create(state, pool).await?.0)
+}
+
+/// Open our account record, or create it under the derived owner when the
+/// stored pointer is missing or predates it.
+///
+/// Also returns the stored pointer when it named a record with a random
+/// owner (from before the owner was derived), so its contents can be carried
+/// over.
+async fn open_or_create(
+    state: &AppState,
+    pool: &DbPool,
+) -> Result<(AccountRecord, Option<(String, String)>), String> {
+    let (owner_key, display_name, status_message) = {
+        let identity = state.identity.read();
+        let id = identity.as_ref().ok_or("not logged in")?;
+        (id.public_key.clone(), id.display_name.clone(), id.status_message.clone())
+    };
+    let secret = {
+        let sk = state.identity_secret.lock();
+        *sk.as_ref().ok_or("identity secret not available")?
+    };
+    let rc = state
+        .node
+        .read()
+        .as_ref()
+        .map(|nh| nh.routing_context.clone())
+        .ok_or("node not initialized")?;
+
+    let owner = owner_keypair(&secret);
+    let owner_str = owner.to_string();
+    let (stored_key, stored_keypair) = load_pointer(pool, &owner_key).await?;
+
+    if let Some(ref key) = stored_key {
+        if stored_keypair.as_deref() == Some(owner_str.as_str()) {
+            match AccountRecord::open(
+                &rc,
+                key,
+                owner.clone(),
+                rekindle_crypto::DhtRecordKey::derive_account_key(&secret),
+            )
+            .await
+            {
+                Ok(record) => return Ok((record, None)),
+                Err(e) => {
+                    tracing::warn!(key = %key, error = %e, "failed to open account record — recreating it");
+                }
+            }
+        }
+    }
+
+    let record = AccountRecord::create(
+        &rc,
+        owner,
+        rekindle_crypto::DhtRecordKey::derive_account_key(&secret),
+        &display_name,
+        &status_message,
+    )
+    .await
+    .map_err(|e| format!("create account record: {e}"))?;
+    store_pointer(pool, &owner_key, &record.record_key(), &owner_str).await?;
+
+    let legacy = match (stored_key, stored_keypair) {
+        (Some(key), Some(keypair)) if keypair != owner_str => Some((key, keypair)),
+        _ => None,
+    };
+    Ok((record, legacy))
+}
+
+async fn load_pointer(
+    pool: &DbPool,
+    owner_key: &str,
+) -> Result<(Option<String>, Option<String>), String> {
+    let pool = pool.clone();
+    let owner_key = owner_key.to_string();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT account_dht_key, account_owner_keypair FROM identity WHERE public_key = ?1",
+            rusqlite::params![owner_key],
+            |row| {
+                Ok((
+                    db::get_str_opt(row, "account_dht_key"),
+                    db::get_str_opt(row, "account_owner_keypair"),
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn store_pointer(
+    pool: &DbPool,
+    owner_key: &str,
+    account_key: &str,
+    owner_keypair: &str,
+) -> Result<(), String> {
+    let pool = pool.clone();
+    let params = (owner_key.to_string(), account_key.to_string(), owner_keypair.to_string());
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE identity SET account_dht_key = ?2, account_owner_keypair = ?3 WHERE public_key = ?1",
+            rusqlite::params![params.0, params.1, params.2],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Open (or create) the account record after login and track it.
+///
+/// On the primary device, a record that was already on the network before
+/// this database knew of it is restored into `SQLite`.
+pub async fn publish_account(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+) -> Result<(), String> {
+    let (record, legacy) = open_or_create(state, pool).await?;
+
+    if let Some(ref mut nh) = *state.node.write() {
+        nh.account_dht_key = Some(record.record_key());
+    }
+    if let Some(ref mut mgr) = *state.dht_manager.write() {
+        for key in record.all_record_keys() {
+            mgr.track_open_record(key);
+        }
+    }
+
+    if let Some((key, keypair)) = legacy {
+        carry_over_devices(state, &record, &key, &keypair).await;
+    }
+
+    if record.recovered() && !device_service::is_linked_device(state) {
+        let report = restore_social_graph(app, state, pool, &record).await;
+        tracing::info!(
+            friends = report.friends,
+            groups = report.groups,
+            communities = report.communities,
+            failed = report.failed.len(),
+            "restored social graph from account record"
+        );
+        if !report.is_empty() {
+            let _ = app.emit(
+                "notification-event",
+                &NotificationEvent::SocialGraphRestored {
+                    friends: report.friends,
+                    groups: report.groups,
+                    communities: report.communities,
+                    sessions_pending: report.sessions_pending,
+                    failed: report.failed,
+                },
+            );
+        }
+    }
+
+    tracing::info!(account_key = %record.record_key(), "published account record to DHT");
+    Ok(())
+}
+
+/// Copy the linked devices from a record with a random owner into the
+/// derived one. Best-effort: the primary republishes its devices anyway.
+async fn carry_over_devices(state: &AppState, record: &AccountRecord, key: &str, keypair: &str) {
+    let secret = { *state.identity_secret.lock() };
+    let rc = state.node.read().as_ref().map(|nh| nh.routing_context.clone());
+    let (Some(secret), Some(rc), Ok(keypair)) = (secret, rc, keypair.parse::<veilid_core::KeyPair>()) else {
+        return;
+    };
+    let old = match AccountRecord::open(
+        &rc,
+        key,
+        keypair,
+        rekindle_crypto::DhtRecordKey::derive_account_key(&secret),
+    )
+    .await
+    {
+        Ok(old) => old,
+        Err(e) => {
+            tracing::debug!(key = %key, error = %e, "previous account record not readable");
+            return;
+        }
+    };
+    let (Ok(old_header), Ok(mut header)) = (old.read_header().await, record.read_header().await)
+    else {
+        return;
+    };
+    if old_header.devices.is_empty() || !header.devices.is_empty() {
+        return;
+    }
+    header.devices = old_header.devices;
+    header.updated_at = db::timestamp_now().cast_unsigned();
+    if let Err(e) = record.write_header(&header).await {
+        tracing::warn!(error = %e, "failed to carry linked devices over to the account record");
+    }
+}
+
+/// Contact list, friend groups and communities as `SQLite` has them.
+struct SocialGraph {
+    contacts: Vec<ContactEntry>,
+    friend_groups: Vec<String>,
+    communities: Vec<CommunityEntry>,
+}
+
+async fn load_social_graph(pool: &DbPool, owner_key: &str) -> Result<SocialGraph, String> {
+    let pool = pool.clone();
+    let owner_key = owner_key.to_string();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT f.public_key, f.display_name, f.nickname, g.name AS group_name, f.added_at, \
+                 f.dht_record_key, f.local_conversation_key, f.local_conversation_keypair, \
+                 f.remote_conversation_key, f.mailbox_dht_key \
+                 FROM friends f LEFT JOIN friend_groups g ON g.id = f.group_id \
+                 WHERE f.owner_key = ?1 AND f.friendship_state = 'accepted' \
+                 ORDER BY f.added_at, f.public_key",
+            )
+            .map_err(|e| e.to_string())?;
+        let contacts: Vec<ContactEntry> = stmt
+            .query_map(rusqlite::params![owner_key], |row| {
+                let added_at = db::get_i64(row, "added_at").cast_unsigned();
+                Ok(ContactEntry {
+                    public_key: hex::decode(db::get_str(row, "public_key")).unwrap_or_default(),
+                    display_name: db::get_str_opt(row, "display_name").unwrap_or_default(),
+                    nickname: db::get_str_opt(row, "nickname").unwrap_or_default(),
+                    group: db::get_str_opt(row, "group_name").unwrap_or_default(),
+                    local_conversation_key: db::get_str_opt(row, "local_conversation_key")
+                        .unwrap_or_default(),
+                    remote_conversation_key: db::get_str_opt(row, "remote_conversation_key")
+                        .unwrap_or_default(),
+                    added_at,
+                    updated_at: added_at,
+                    local_conversation_keypair: db::get_str_opt(row, "local_conversation_keypair"),
+                    profile_key: db::get_str_opt(row, "dht_record_key").unwrap_or_default(),
+                    mailbox_key: db::get_str_opt(row, "mailbox_dht_key").unwrap_or_default(),
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .filter_map(Result::ok)
+            .filter(|c| c.public_key.len() == 32)
+            .collect();
+
+        let mut stmt = conn
+            .prepare("SELECT name FROM friend_groups WHERE owner_key = ?1 ORDER BY sort_order, id")
+            .map_err(|e| e.to_string())?;
+        let friend_groups: Vec<String> = stmt
+            .query_map(rusqlite::params![owner_key], |row| Ok(db::get_str(row, "name")))
+            .map_err(|e| e.to_string())?
+            .filter_map(Result::ok)
+            .collect();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, joined_at, dht_owner_keypair, is_hosted FROM communities \
+                 WHERE owner_key = ?1 AND dht_record_key IS NOT NULL ORDER BY joined_at, id",
+            )
+            .map_err(|e| e.to_string())?;
+        let communities: Vec<CommunityEntry> = stmt
+            .query_map(rusqlite::params![owner_key], |row| {
+                let hosted = db::get_i64(row, "is_hosted") != 0;
+                Ok(CommunityEntry {
+                    community_id: db::get_str(row, "id"),
+                    name: db::get_str(row, "name"),
+                    joined_at: db::get_i64(row, "joined_at").cast_unsigned(),
+                    owner_keypair: db::get_str_opt(row, "dht_owner_keypair").filter(|_| hosted),
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .filter_map(Result::ok)
+            .collect();
+
+        Ok(SocialGraph { contacts, friend_groups, communities })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Bring the account record in line with the friends, groups and
+/// communities in `SQLite`. Primary only, and only once login has published
+/// the record.
+pub async fn publish_social_graph(state: &Arc<AppState>, pool: &DbPool) -> Result<(), String> {
+    if device_service::is_linked_device(state) {
+        return Ok(());
+    }
+    let published = state.node.read().as_ref().is_some_and(|nh| nh.account_dht_key.is_some());
+    if !published {
+        return Ok(());
+    }
+    let owner_key = current_owner_key(state)?;
+    let graph = load_social_graph(pool, &owner_key).await?;
+    let record = open_account_record(state, pool).await?;
+
+    let contacts = record
+        .read_contacts()
+        .await
+        .map_err(|e| format!("read account contacts: {e}"))?;
+    if contacts != graph.contacts {
+        record
+            .replace_contacts(&graph.contacts)
+            .await
+            .map_err(|e| format!("write account contacts: {e}"))?;
+    }
+
+    let mut header = record
+        .read_header()
+        .await
+        .map_err(|e| format!("read account header: {e}"))?;
+    if header.friend_groups != graph.friend_groups || header.communities != graph.communities {
+        header.friend_groups = graph.friend_groups;
+        header.communities = graph.communities;
+        header.updated_at = db::timestamp_now().cast_unsigned();
+        record
+            .write_header(&header)
+            .await
+            .map_err(|e| format!("write account header: {e}"))?;
+    }
+    tracing::debug!(contacts = graph.contacts.len(), "account record up to date");
+    Ok(())
+}
+
+/// [`publish_social_graph`] in the background, for commands that change the
+/// friend list, groups or communities.
+pub fn publish_in_background(state: &Arc<AppState>, pool: &DbPool) {
+    let state = Arc::clone(state);
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = publish_social_graph(&state, &pool).await {
+            tracing::warn!(error = %e, "failed to update account record");
+        }
+    });
+}
+
+/// Merge a recovered account record into `SQLite` and `AppState`.
+async fn restore_social_graph(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    record: &AccountRecord,
+) -> RecoveryReport {
+    let mut report = RecoveryReport::default();
+    let header = match record.read_header().await {
+        Ok(header) => header,
+        Err(e) => {
+            report.failed.push(format!("Account record: {e}"));
+            return report;
+        }
+    };
+    let contacts = match record.read_contacts().await {
+        Ok(contacts) => contacts,
+        Err(e) => {
+            report.failed.push(format!("Contact list: {e}"));
+            Vec::new()
+        }
+    };
+
+    let restored =
+        restore_friends(app, state, pool, &header.friend_groups, contacts, &mut report).await;
+    if let Err(e) = restored {
+        report.failed.push(format!("Friends: {e}"));
+    }
+    restore_communities(app, state, pool, &header.communities, &mut report).await;
+    report
+}
+
+async fn restore_friends(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    friend_groups: &[String],
+    contacts: Vec<ContactEntry>,
+    report: &mut RecoveryReport,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
+    let friend_groups = friend_groups.to_vec();
+    let db = pool.clone();
+    let (groups_added, added) = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        insert_social_graph(&conn, &owner_key, friend_groups, contacts)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    report.groups = groups_added;
+
+    for (public_key, c) in added {
+        state.friends.write().insert(
+            public_key.clone(),
+            FriendState {
+                public_key: public_key.clone(),
+                display_name: c.display_name.clone(),
+                nickname: non_empty(&c.nickname),
+                status: UserStatus::Offline,
+                status_message: None,
+                game_info: None,
+                group: non_empty(&c.group),
+                unread_count: 0,
+                dht_record_key: non_empty(&c.profile_key),
+                last_seen_at: None,
+                local_conversation_key: non_empty(&c.local_conversation_key),
+                remote_conversation_key: non_empty(&c.remote_conversation_key),
+                mailbox_dht_key: non_empty(&c.mailbox_key),
+                last_heartbeat_at: None,
+                friendship_state: FriendshipState::Accepted,
+                disappearing_secs: 0,
+            },
+        );
+        report.friends += 1;
+
+        if c.profile_key.is_empty() {
+            // Without their profile there are no prekeys to start a session from
+            report.failed.push(format!(
+                "{}: no profile record, so messages can't be encrypted until they write to you",
+                c.display_name
+            ));
+        } else {
+            if let Err(e) = presence_service::watch_friend(state, &public_key, &c.profile_key).await {
+                tracing::trace!(friend = %public_key, error = %e, "failed to watch restored friend");
+            }
+            state.session_restarts.lock().insert(public_key.clone());
+            report.sessions_pending += 1;
+        }
+        let _ = app.emit(
+            "chat-event",
+            &ChatEvent::FriendAdded {
+                public_key,
+                display_name: c.display_name,
+                friendship_state: "accepted".to_string(),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Insert the recovered groups and friends we don't have yet. Returns the
+/// number of new groups and the new friends with their hex public keys.
+fn insert_social_graph(
+    conn: &rusqlite::Connection,
+    owner_key: &str,
+    mut groups: Vec<String>,
+    contacts: Vec<ContactEntry>,
+) -> Result<(u32, Vec<(String, ContactEntry)>), String> {
+    // Groups named only by a contact go after the ordered ones
+    for contact in &contacts {
+        if !contact.group.is_empty() && !groups.contains(&contact.group) {
+            groups.push(contact.group.clone());
+        }
+    }
+
+    let mut groups_added = 0u32;
+    for (order, name) in groups.iter().enumerate() {
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO friend_groups (owner_key, name, sort_order) VALUES (?1, ?2, ?3)",
+                rusqlite::params![owner_key, name, i64::try_from(order).unwrap_or(i64::MAX)],
+            )
+            .map_err(|e| e.to_string())?;
+        if inserted > 0 {
+            groups_added += 1;
+        }
+    }
+
+    let now = db::timestamp_now();
+    let mut added = Vec::new();
+    for c in contacts {
+        if c.public_key.len() != 32 {
+            continue;
+        }
+        let public_key = hex::encode(&c.public_key);
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO friends (owner_key, public_key, display_name, nickname, group_id, \
+                 added_at, dht_record_key, local_conversation_key, local_conversation_keypair, \
+                 remote_conversation_key, mailbox_dht_key, friendship_state) \
+                 VALUES (?1, ?2, ?3, ?4, \
+                 (SELECT id FROM friend_groups WHERE owner_key = ?1 AND name = ?5), \
+                 ?6, ?7, ?8, ?9, ?10, ?11, 'accepted')",
+                rusqlite::params![
+                    owner_key,
+                    public_key,
+                    c.display_name,
+                    non_empty(&c.nickname),
+                    c.group,
+                    i64::try_from(c.added_at).unwrap_or(now),
+                    non_empty(&c.profile_key),
+                    non_empty(&c.local_conversation_key),
+                    c.local_conversation_keypair,
+                    non_empty(&c.remote_conversation_key),
+                    non_empty(&c.mailbox_key),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if inserted > 0 {
+            added.push((public_key, c));
+        }
+    }
+    Ok((groups_added, added))
+}
+
+/// The record stores absent text fields as empty strings.
+fn non_empty(s: &str) -> Option<String> {
+    Some(s.to_string()).filter(|s| !s.is_empty())
+}
+
+async fn restore_communities(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    entries: &[CommunityEntry],
+    report: &mut RecoveryReport,
+) {
+    let keystore = app.state::<KeystoreHandle>();
+    for entry in entries {
+        if state.communities.read().contains_key(&entry.community_id) {
+            continue;
+        }
+        let result = match entry.owner_keypair {
+            Some(ref keypair) => {
+                crate::commands::community::restore_hosted_community(
+                    app,
+                    state,
+                    pool,
+                    keystore.inner(),
+                    &entry.community_id,
+                    &entry.name,
+                    keypair,
+                )
+                .await
+            }
+            None => {
+                crate::commands::community::join_and_persist(
+                    state,
+                    pool,
+                    keystore.inner(),
+                    &entry.community_id,
+                    None,
+                )
+                .await
+            }
+        };
+        match result {
+            Ok(()) => {
+                report.communities += 1;
+                if entry.owner_keypair.is_some() {
+                    report.failed.push(format!(
+                        "{}: members of a community you host have to rejoin it",
+                        entry.name
+                    ));
+                }
+            }
+            Err(e) => report.failed.push(format!("{}: {e}", entry.name)),
+        }
+    }
+}
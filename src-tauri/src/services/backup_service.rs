@@ -0,0 +1,522 @@
+//! Encrypted identity backups.
+//!
+//! An export is an [`IdentityBackup`] serialized as JSON and sealed with
+//! `rekindle_crypto::backup` under a passphrase the user picks for it. It
+//! holds what another machine needs to take over the identity: the Ed25519
+//! secret, the keys and owner keypairs of the profile, friend list, account
+//! and per-friend conversation records, friends and their groups, blocked
+//! users, pinned Signal identities and, optionally, the DM history.
+//!
+//! Left out on purpose: Signal sessions and prekeys (sessions live in memory
+//! and are rebuilt from fresh prekeys), community memberships and MEKs
+//! (communities are rejoined), channel history, and linked devices.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension as _};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize as _, Zeroizing};
+
+use crate::db;
+
+/// Layout version of [`IdentityBackup`]; bumped on incompatible changes.
+const BACKUP_FORMAT: u32 = 1;
+
+/// Everything an identity backup restores.
+#[derive(Serialize, Deserialize)]
+pub struct IdentityBackup {
+    pub format: u32,
+    pub exported_at: i64,
+    pub identity_secret: Vec<u8>,
+    pub display_name: String,
+    pub created_at: i64,
+    pub avatar_webp: Option<Vec<u8>>,
+    /// Prekey IDs already handed out are never reused, even after a restore.
+    pub next_prekey_id: i64,
+    pub profile_dht_key: Option<String>,
+    pub profile_owner_keypair: Option<String>,
+    pub friend_list_dht_key: Option<String>,
+    pub friend_list_owner_keypair: Option<String>,
+    pub account_dht_key: Option<String>,
+    pub account_owner_keypair: Option<String>,
+    pub mailbox_dht_key: Option<String>,
+    pub friend_groups: Vec<BackupGroup>,
+    pub friends: Vec<BackupFriend>,
+    pub blocked_users: Vec<BackupBlockedUser>,
+    pub trusted_identities: Vec<BackupTrustedIdentity>,
+    /// DM history, or `None` when the export left it out.
+    pub messages: Option<Vec<BackupMessage>>,
+}
+
+impl Drop for IdentityBackup {
+    fn drop(&mut self) {
+        self.identity_secret.zeroize();
+        for keypair in [
+            &mut self.profile_owner_keypair,
+            &mut self.friend_list_owner_keypair,
+            &mut self.account_owner_keypair,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            keypair.zeroize();
+        }
+        for friend in &mut self.friends {
+            if let Some(keypair) = friend.local_conversation_keypair.as_mut() {
+                keypair.zeroize();
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupGroup {
+    pub name: String,
+    pub sort_order: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupFriend {
+    pub public_key: String,
+    pub display_name: Option<String>,
+    pub nickname: Option<String>,
+    /// Name of the friend's group, if any.
+    pub group: Option<String>,
+    pub added_at: i64,
+    pub dht_record_key: Option<String>,
+    pub last_seen_at: Option<i64>,
+    pub local_conversation_key: Option<String>,
+    pub local_conversation_keypair: Option<String>,
+    pub remote_conversation_key: Option<String>,
+    pub mailbox_dht_key: Option<String>,
+    pub friendship_state: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupBlockedUser {
+    pub public_key: String,
+    pub display_name: String,
+    pub blocked_at: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupTrustedIdentity {
+    pub public_key: String,
+    pub identity_key: Vec<u8>,
+    pub verified: bool,
+    pub first_seen: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupMessage {
+    /// Row ID on the exporting machine, only used to re-link replies.
+    pub id: i64,
+    pub conversation_id: String,
+    pub sender_key: String,
+    pub body: String,
+    pub timestamp: i64,
+    pub is_read: bool,
+    pub reply_to_id: Option<i64>,
+    pub attachment_json: Option<String>,
+    pub message_id: Option<String>,
+    pub edited_at: Option<i64>,
+    pub delivery_state: Option<String>,
+    pub reactions: Vec<BackupReaction>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupReaction {
+    pub reactor_key: String,
+    pub emoji: String,
+    pub created_at: i64,
+}
+
+impl IdentityBackup {
+    /// The identity secret as a fixed-size key.
+    pub fn secret(&self) -> Result<[u8; 32], String> {
+        self.identity_secret
+            .as_slice()
+            .try_into()
+            .map_err(|_| "invalid identity key in backup".to_string())
+    }
+
+    /// Seal this backup under `passphrase`.
+    pub fn seal(&self, passphrase: &str) -> Result<Vec<u8>, String> {
+        let json = Zeroizing::new(serde_json::to_vec(self).map_err(|e| e.to_string())?);
+        rekindle_crypto::backup::seal_backup(passphrase, &json).map_err(|e| e.to_string())
+    }
+
+    /// Open an archive made by [`IdentityBackup::seal`].
+    pub fn open(archive: &[u8], passphrase: &str) -> Result<Self, String> {
+        let json = rekindle_crypto::backup::open_backup(passphrase, archive).map_err(|e| {
+            tracing::warn!(error = %e, "backup open failed");
+            "Wrong backup passphrase, or the file is not a Rekindle backup".to_string()
+        })?;
+        let backup: Self =
+            serde_json::from_slice(&json).map_err(|e| format!("invalid backup contents: {e}"))?;
+        if backup.format != BACKUP_FORMAT {
+            return Err(format!(
+                "This backup was made by a newer version of Rekindle (format {})",
+                backup.format
+            ));
+        }
+        backup.secret()?;
+        Ok(backup)
+    }
+}
+
+/// Gather the backup of `owner_key` from its open database.
+pub fn collect(
+    conn: &Connection,
+    owner_key: &str,
+    secret: &[u8; 32],
+    include_history: bool,
+) -> Result<IdentityBackup, String> {
+    let mut backup = conn
+        .query_row(
+            "SELECT display_name, created_at, avatar_webp, next_prekey_id, dht_record_key, \
+             dht_owner_keypair, friend_list_dht_key, friend_list_owner_keypair, \
+             account_dht_key, account_owner_keypair, mailbox_dht_key \
+             FROM identity WHERE public_key = ?1",
+            rusqlite::params![owner_key],
+            |row| {
+                Ok(IdentityBackup {
+                    format: BACKUP_FORMAT,
+                    exported_at: db::timestamp_now(),
+                    identity_secret: secret.to_vec(),
+                    display_name: db::get_str(row, "display_name"),
+                    created_at: db::get_i64(row, "created_at"),
+                    avatar_webp: row.get("avatar_webp")?,
+                    next_prekey_id: db::get_i64(row, "next_prekey_id"),
+                    profile_dht_key: db::get_str_opt(row, "dht_record_key"),
+                    profile_owner_keypair: db::get_str_opt(row, "dht_owner_keypair"),
+                    friend_list_dht_key: db::get_str_opt(row, "friend_list_dht_key"),
+                    friend_list_owner_keypair: db::get_str_opt(row, "friend_list_owner_keypair"),
+                    account_dht_key: db::get_str_opt(row, "account_dht_key"),
+                    account_owner_keypair: db::get_str_opt(row, "account_owner_keypair"),
+                    mailbox_dht_key: db::get_str_opt(row, "mailbox_dht_key"),
+                    friend_groups: Vec::new(),
+                    friends: Vec::new(),
+                    blocked_users: Vec::new(),
+                    trusted_identities: Vec::new(),
+                    messages: None,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("identity not found")?;
+
+    backup.friend_groups = collect_groups(conn, owner_key)?;
+    backup.friends = collect_friends(conn, owner_key)?;
+    backup.blocked_users = collect_blocked_users(conn, owner_key)?;
+    backup.trusted_identities = collect_trusted_identities(conn, owner_key)?;
+    if include_history {
+        backup.messages = Some(collect_messages(conn, owner_key)?);
+    }
+    Ok(backup)
+}
+
+fn collect_groups(conn: &Connection, owner_key: &str) -> Result<Vec<BackupGroup>, String> {
+    let mut stmt = conn
+        .prepare("SELECT name, sort_order FROM friend_groups WHERE owner_key = ?1 ORDER BY sort_order")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key], |row| {
+            Ok(BackupGroup {
+                name: db::get_str(row, "name"),
+                sort_order: db::get_i64(row, "sort_order"),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+fn collect_friends(conn: &Connection, owner_key: &str) -> Result<Vec<BackupFriend>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.public_key, f.display_name, f.nickname, g.name AS group_name, f.added_at, \
+             f.dht_record_key, f.last_seen_at, f.local_conversation_key, \
+             f.local_conversation_keypair, f.remote_conversation_key, f.mailbox_dht_key, \
+             f.friendship_state \
+             FROM friends f LEFT JOIN friend_groups g ON g.id = f.group_id \
+             WHERE f.owner_key = ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key], |row| {
+            Ok(BackupFriend {
+                public_key: db::get_str(row, "public_key"),
+                display_name: db::get_str_opt(row, "display_name"),
+                nickname: db::get_str_opt(row, "nickname"),
+                group: db::get_str_opt(row, "group_name"),
+                added_at: db::get_i64(row, "added_at"),
+                dht_record_key: db::get_str_opt(row, "dht_record_key"),
+                last_seen_at: db::get_i64_opt(row, "last_seen_at"),
+                local_conversation_key: db::get_str_opt(row, "local_conversation_key"),
+                local_conversation_keypair: db::get_str_opt(row, "local_conversation_keypair"),
+                remote_conversation_key: db::get_str_opt(row, "remote_conversation_key"),
+                mailbox_dht_key: db::get_str_opt(row, "mailbox_dht_key"),
+                friendship_state: db::get_str(row, "friendship_state"),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+fn collect_blocked_users(
+    conn: &Connection,
+    owner_key: &str,
+) -> Result<Vec<BackupBlockedUser>, String> {
+    let mut stmt = conn
+        .prepare("SELECT public_key, display_name, blocked_at FROM blocked_users WHERE owner_key = ?1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key], |row| {
+            Ok(BackupBlockedUser {
+                public_key: db::get_str(row, "public_key"),
+                display_name: db::get_str(row, "display_name"),
+                blocked_at: db::get_i64(row, "blocked_at"),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+fn collect_trusted_identities(
+    conn: &Connection,
+    owner_key: &str,
+) -> Result<Vec<BackupTrustedIdentity>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT public_key, identity_key, verified, first_seen \
+             FROM trusted_identities WHERE owner_key = ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key], |row| {
+            Ok(BackupTrustedIdentity {
+                public_key: db::get_str(row, "public_key"),
+                identity_key: row.get("identity_key")?,
+                verified: db::get_i64(row, "verified") != 0,
+                first_seen: db::get_i64(row, "first_seen"),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+/// Every DM of `owner_key` with its reactions, oldest first.
+fn collect_messages(conn: &Connection, owner_key: &str) -> Result<Vec<BackupMessage>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT message_row_id, reactor_key, emoji, created_at FROM message_reactions \
+             WHERE message_row_id IN \
+             (SELECT id FROM messages WHERE owner_key = ?1 AND conversation_type = 'dm')",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut reactions: HashMap<i64, Vec<BackupReaction>> = HashMap::new();
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key], |row| {
+            Ok((
+                db::get_i64(row, "message_row_id"),
+                BackupReaction {
+                    reactor_key: db::get_str(row, "reactor_key"),
+                    emoji: db::get_str(row, "emoji"),
+                    created_at: db::get_i64(row, "created_at"),
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (message_row_id, reaction) = row.map_err(|e| e.to_string())?;
+        reactions.entry(message_row_id).or_default().push(reaction);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, conversation_id, sender_key, body, timestamp, is_read, reply_to_id, \
+             attachment_json, message_id, edited_at, delivery_state \
+             FROM messages WHERE owner_key = ?1 AND conversation_type = 'dm' ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let messages = stmt
+        .query_map(rusqlite::params![owner_key], |row| {
+            let id = db::get_i64(row, "id");
+            Ok(BackupMessage {
+                id,
+                conversation_id: db::get_str(row, "conversation_id"),
+                sender_key: db::get_str(row, "sender_key"),
+                body: db::get_str(row, "body"),
+                timestamp: db::get_i64(row, "timestamp"),
+                is_read: db::get_i64(row, "is_read") != 0,
+                reply_to_id: db::get_i64_opt(row, "reply_to_id"),
+                attachment_json: db::get_str_opt(row, "attachment_json"),
+                message_id: db::get_str_opt(row, "message_id"),
+                edited_at: db::get_i64_opt(row, "edited_at"),
+                delivery_state: db::get_str_opt(row, "delivery_state"),
+                reactions: reactions.remove(&id).unwrap_or_default(),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(messages)
+}
+
+/// Write `backup` into the freshly opened database of `owner_key`, whose
+/// identity row already exists.
+pub fn restore(conn: &Connection, owner_key: &str, backup: &IdentityBackup) -> Result<(), String> {
+    conn.execute(
+        "UPDATE identity SET created_at = ?2, avatar_webp = ?3, next_prekey_id = ?4, \
+         dht_record_key = ?5, dht_owner_keypair = ?6, friend_list_dht_key = ?7, \
+         friend_list_owner_keypair = ?8, account_dht_key = ?9, account_owner_keypair = ?10, \
+         mailbox_dht_key = ?11 WHERE public_key = ?1",
+        rusqlite::params![
+            owner_key,
+            backup.created_at,
+            backup.avatar_webp,
+            backup.next_prekey_id.max(1),
+            backup.profile_dht_key,
+            backup.profile_owner_keypair,
+            backup.friend_list_dht_key,
+            backup.friend_list_owner_keypair,
+            backup.account_dht_key,
+            backup.account_owner_keypair,
+            backup.mailbox_dht_key,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut group_ids = HashMap::new();
+    for group in &backup.friend_groups {
+        conn.execute(
+            "INSERT OR IGNORE INTO friend_groups (owner_key, name, sort_order) VALUES (?1, ?2, ?3)",
+            rusqlite::params![owner_key, group.name, group.sort_order],
+        )
+        .map_err(|e| e.to_string())?;
+        let id: i64 = conn
+            .query_row(
+                "SELECT id FROM friend_groups WHERE owner_key = ?1 AND name = ?2",
+                rusqlite::params![owner_key, group.name],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        group_ids.insert(group.name.as_str(), id);
+    }
+
+    for friend in &backup.friends {
+        let group_id = friend.group.as_deref().and_then(|g| group_ids.get(g));
+        conn.execute(
+            "INSERT OR REPLACE INTO friends (owner_key, public_key, display_name, nickname, group_id, \
+             added_at, dht_record_key, last_seen_at, local_conversation_key, \
+             local_conversation_keypair, remote_conversation_key, mailbox_dht_key, friendship_state) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            rusqlite::params![
+                owner_key,
+                friend.public_key,
+                friend.display_name,
+                friend.nickname,
+                group_id,
+                friend.added_at,
+                friend.dht_record_key,
+                friend.last_seen_at,
+                friend.local_conversation_key,
+                friend.local_conversation_keypair,
+                friend.remote_conversation_key,
+                friend.mailbox_dht_key,
+                friend.friendship_state,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    for user in &backup.blocked_users {
+        conn.execute(
+            "INSERT OR REPLACE INTO blocked_users (owner_key, public_key, display_name, blocked_at) \
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![owner_key, user.public_key, user.display_name, user.blocked_at],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    for trusted in &backup.trusted_identities {
+        conn.execute(
+            "INSERT OR REPLACE INTO trusted_identities (owner_key, public_key, identity_key, verified, first_seen) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                owner_key,
+                trusted.public_key,
+                trusted.identity_key,
+                i64::from(trusted.verified),
+                trusted.first_seen,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    if let Some(messages) = &backup.messages {
+        restore_messages(conn, owner_key, messages)?;
+    }
+    Ok(())
+}
+
+fn restore_messages(
+    conn: &Connection,
+    owner_key: &str,
+    messages: &[BackupMessage],
+) -> Result<(), String> {
+    // Old row ID -> new row ID, so replies point at the restored messages
+    let mut row_ids: HashMap<i64, i64> = HashMap::new();
+    for message in messages {
+        let reply_to = message.reply_to_id.and_then(|id| row_ids.get(&id).copied());
+        // The outbox isn't part of the backup, so nothing will send these
+        let delivery_state = match message.delivery_state.as_deref() {
+            Some("queued") => Some("failed"),
+            other => other,
+        };
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO messages (owner_key, conversation_id, conversation_type, \
+                 sender_key, body, timestamp, is_read, reply_to_id, attachment_json, message_id, \
+                 edited_at, delivery_state) \
+                 VALUES (?1, ?2, 'dm', ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                rusqlite::params![
+                    owner_key,
+                    message.conversation_id,
+                    message.sender_key,
+                    message.body,
+                    message.timestamp,
+                    i64::from(message.is_read),
+                    reply_to,
+                    message.attachment_json,
+                    message.message_id,
+                    message.edited_at,
+                    delivery_state,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if inserted == 0 {
+            continue;
+        }
+        let row_id = conn.last_insert_rowid();
+        row_ids.insert(message.id, row_id);
+        for reaction in &message.reactions {
+            conn.execute(
+                "INSERT OR IGNORE INTO message_reactions (message_row_id, reactor_key, emoji, created_at) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![row_id, reaction.reactor_key, reaction.emoji, reaction.created_at],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
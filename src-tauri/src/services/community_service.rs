@@ -1,8 +1,13 @@
 use std::sync::Arc;
 
 use rekindle_crypto::group::media_key::MediaEncryptionKey;
-use rekindle_protocol::dht::community::{SUBKEY_CHANNELS, SUBKEY_METADATA, SUBKEY_SERVER_ROUTE};
+use rekindle_crypto::group::member_vouch;
+use rekindle_protocol::dht::community::{
+    ChannelEntry, SUBKEY_CHANNELS, SUBKEY_METADATA, SUBKEY_SERVER_ROUTE,
+};
+use rekindle_protocol::dht::paged_list::{self, ListChange, ListSync};
 use rekindle_protocol::dht::DHTManager;
+use rekindle_protocol::messaging::{CommunityInviteLink, HistoryVisibility, MemberVouchDto};
 
 use crate::state::{AppState, ChannelInfo, ChannelType, CommunityState, RoleDefinition};
 
@@ -76,6 +81,7 @@ async fn create_community_with_dht(
         name: "general".to_string(),
         channel_type: ChannelType::Text,
         unread_count: 0,
+        retention_secs: 0,
     };
 
     let mek = MediaEncryptionKey::generate(1);
@@ -83,7 +89,7 @@ async fn create_community_with_dht(
     tracing::debug!(community = %key, mek_generation, "generated initial MEK for community");
 
     let my_pseudonym_key = derive_pseudonym_key(state, &key);
-    state.mek_cache.lock().insert(key.clone(), mek);
+    state.mek_cache.lock().entry(key.clone()).or_default().insert(mek);
     let dht_owner_keypair = owner_keypair.map(|kp| kp.to_string());
 
     let community = CommunityState {
@@ -96,10 +102,13 @@ async fn create_community_with_dht(
         my_role: Some("owner".to_string()),
         dht_record_key: Some(key.clone()),
         dht_owner_keypair,
+        owner_pseudonym: my_pseudonym_key.clone(),
         my_pseudonym_key,
         mek_generation,
         server_route_blob: None,
         is_hosted: true,
+        history_visibility: HistoryVisibility::default(),
+        threads: Vec::new(),
     };
 
     state.communities.write().insert(key.clone(), community);
@@ -114,6 +123,7 @@ fn create_community_local(state: &Arc<AppState>, community_id: &str, name: &str)
         name: "general".to_string(),
         channel_type: ChannelType::Text,
         unread_count: 0,
+        retention_secs: 0,
     };
 
     let mek = MediaEncryptionKey::generate(1);
@@ -121,7 +131,7 @@ fn create_community_local(state: &Arc<AppState>, community_id: &str, name: &str)
     tracing::debug!(community = %community_id, mek_generation, "generated initial MEK for community (local only)");
 
     let my_pseudonym_key = derive_pseudonym_key(state, community_id);
-    state.mek_cache.lock().insert(community_id.to_string(), mek);
+    state.mek_cache.lock().entry(community_id.to_string()).or_default().insert(mek);
 
     let community = CommunityState {
         id: community_id.to_string(),
@@ -133,16 +143,83 @@ fn create_community_local(state: &Arc<AppState>, community_id: &str, name: &str)
         my_role: Some("owner".to_string()),
         dht_record_key: None,
         dht_owner_keypair: None,
+        owner_pseudonym: my_pseudonym_key.clone(),
         my_pseudonym_key,
         mek_generation,
         server_route_blob: None,
         is_hosted: true,
+        history_visibility: HistoryVisibility::default(),
+        threads: Vec::new(),
     };
 
     state.communities.write().insert(community_id.to_string(), community);
     tracing::info!(community = %community_id, name = %name, "community created (local only)");
 }
 
+/// Take back a community we host from its DHT record after a reinstall.
+///
+/// The owner keypair comes from our account record. The media key is not kept
+/// anywhere we can reach, so the community starts over at a fresh generation.
+pub async fn restore_hosted_community(
+    state: &Arc<AppState>,
+    community_id: &str,
+    name: &str,
+    owner_keypair: &str,
+) -> Result<(), String> {
+    let routing_context = {
+        let node = state.node.read();
+        node.as_ref()
+            .filter(|nh| nh.is_attached)
+            .map(|nh| nh.routing_context.clone())
+    };
+    let rc = routing_context.ok_or("node not attached")?;
+
+    let (dht_name, description, mut channels, _, server_route_blob) =
+        read_community_from_dht(state, Some(&rc), community_id).await;
+    let name = if dht_name == default_community_name(community_id) {
+        name.to_string()
+    } else {
+        dht_name
+    };
+    if channels.is_empty() {
+        channels.push(ChannelInfo {
+            id: format!("channel_{}", hex::encode(rand_bytes(8))),
+            name: "general".to_string(),
+            channel_type: ChannelType::Text,
+            unread_count: 0,
+            retention_secs: 0,
+        });
+    }
+
+    let mek = MediaEncryptionKey::generate(1);
+    let mek_generation = mek.generation();
+    let my_pseudonym_key = derive_pseudonym_key(state, community_id);
+    state.mek_cache.lock().entry(community_id.to_string()).or_default().insert(mek);
+
+    let community = CommunityState {
+        id: community_id.to_string(),
+        name,
+        description,
+        channels,
+        my_role_ids: vec![0, 1, 2, 3, 4], // @everyone, member, moderator, admin, owner
+        roles: default_roles(),
+        my_role: Some("owner".to_string()),
+        dht_record_key: Some(community_id.to_string()),
+        dht_owner_keypair: Some(owner_keypair.to_string()),
+        owner_pseudonym: my_pseudonym_key.clone(),
+        my_pseudonym_key,
+        mek_generation,
+        server_route_blob,
+        is_hosted: true,
+        history_visibility: HistoryVisibility::default(),
+        threads: Vec::new(),
+    };
+
+    state.communities.write().insert(community_id.to_string(), community);
+    tracing::info!(community = %community_id, "hosted community restored from account record");
+    Ok(())
+}
+
 /// Derive the pseudonym public key hex for a community from the identity secret.
 fn derive_pseudonym_key(state: &Arc<AppState>, community_id: &str) -> Option<String> {
     let secret = state.identity_secret.lock();
@@ -152,15 +229,19 @@ fn derive_pseudonym_key(state: &Arc<AppState>, community_id: &str) -> Option<Str
     })
 }
 
-/// Join an existing community by ID or invite code.
+/// Join an existing community by ID, redeeming `invite` if given.
 ///
 /// Reads community metadata from DHT, then sends a `CommunityRequest::Join`
 /// RPC to the community server via `app_call`. On success, the server returns
 /// the MEK, channel list, and assigned role.
+///
+/// Returns whether the community uses `TreeKEM`, in which case the caller
+/// still has to publish a key package before it receives any MEK.
 pub async fn join_community(
     state: &Arc<AppState>,
     community_id: &str,
-) -> Result<(), String> {
+    invite: Option<&CommunityInviteLink>,
+) -> Result<bool, String> {
     let routing_context = {
         let node = state.node.read();
         node.as_ref()
@@ -169,7 +250,7 @@ pub async fn join_community(
     };
 
     let (name, description, mut channels, dht_record_key, server_route_blob) =
-        read_community_from_dht(routing_context.as_ref(), community_id).await;
+        read_community_from_dht(state, routing_context.as_ref(), community_id).await;
 
     let my_pseudonym_key = derive_pseudonym_key(state, community_id);
     let our_display_name = {
@@ -188,6 +269,8 @@ pub async fn join_community(
     let mut role = "member".to_string();
     let mut role_ids = vec![0u32, 1]; // default: @everyone + members
     let mut roles = default_roles();
+    let mut history_visibility = HistoryVisibility::default();
+    let mut tree_kem = false;
 
     let identity_secret = { *state.identity_secret.lock() };
     if let (Some(ref route_blob), Some(ref rc), Some(secret)) =
@@ -199,6 +282,8 @@ pub async fn join_community(
             my_pseudonym_key: my_pseudonym_key.clone(),
             display_name: our_display_name,
             our_route_blob,
+            invite_code: invite.map(|link| link.code.clone()),
+            invite_secret: invite.and_then(|link| link.invite_secret.clone()),
         };
         match send_join_rpc(state, rc, route_blob, &join_params).await {
             Ok(Some(result)) => {
@@ -211,6 +296,8 @@ pub async fn join_community(
                 if !result.channels.is_empty() {
                     channels = result.channels;
                 }
+                history_visibility = result.history_visibility;
+                tree_kem = result.tree_kem;
             }
             Ok(None) => {} // RPC failed gracefully, join locally
             Err(e) => return Err(e), // Server explicitly rejected
@@ -229,10 +316,13 @@ pub async fn join_community(
         my_role: Some(role),
         dht_record_key,
         dht_owner_keypair: None,
+        owner_pseudonym: invite.and_then(|link| link.owner.clone()),
         my_pseudonym_key,
         mek_generation,
         server_route_blob,
         is_hosted: false,
+        history_visibility,
+        threads: Vec::new(),
     };
 
     state
@@ -241,13 +331,14 @@ pub async fn join_community(
         .insert(community_id.to_string(), community);
 
     tracing::info!(community = %community_id, "joined community");
-    Ok(())
+    Ok(tree_kem)
 }
 
 /// Read community metadata, channels, and server route from DHT.
 ///
 /// Returns `(name, description, channels, dht_record_key, server_route_blob)`.
 async fn read_community_from_dht(
+    state: &Arc<AppState>,
     routing_context: Option<&veilid_core::RoutingContext>,
     community_id: &str,
 ) -> (String, Option<String>, Vec<ChannelInfo>, Option<String>, Option<Vec<u8>>) {
@@ -271,10 +362,15 @@ async fn read_community_from_dht(
         }
     };
 
-    let channels = match mgr.get_value(community_id, SUBKEY_CHANNELS).await {
-        Ok(Some(data)) => parse_channel_list(&data),
-        Ok(None) | Err(_) => vec![],
-    };
+    // Read the whole channel list, whatever we applied before
+    state
+        .community_lists
+        .write()
+        .remove(&(community_id.to_string(), SUBKEY_CHANNELS));
+    let mut channels = Vec::new();
+    if let Ok(Some(sync)) = sync_community_list(state, &mgr, community_id, SUBKEY_CHANNELS).await {
+        apply_channel_sync(&mut channels, &sync);
+    }
 
     // Watch metadata(0), channels(1), roster(2), roles(3), MEK bundles(5), server route(6)
     if let Err(e) = mgr.watch_record(community_id, &[0, 1, 2, 3, 5, 6]).await {
@@ -294,6 +390,8 @@ struct JoinRpcResult {
     role_ids: Vec<u32>,
     roles: Vec<RoleDefinition>,
     channels: Vec<ChannelInfo>,
+    history_visibility: HistoryVisibility,
+    tree_kem: bool,
 }
 
 /// Parameters for sending a join RPC to the community server.
@@ -303,6 +401,9 @@ struct JoinRpcParams {
     my_pseudonym_key: Option<String>,
     display_name: String,
     our_route_blob: Option<Vec<u8>>,
+    invite_code: Option<String>,
+    /// Hex seed of the link's invite key, to vouch for our pseudonym.
+    invite_secret: Option<String>,
 }
 
 /// Send a `CommunityRequest::Join` RPC to the server.
@@ -322,12 +423,29 @@ async fn send_join_rpc(
     };
     let Some(api) = api else { return Ok(None) };
 
+    // Always start a fresh MEK delivery session on join
+    let prekey_bundle = super::mek_service::delivery_bundle(state, &params.community_id, true)?
+        .unwrap_or_default();
+
+    // Sign our pseudonym with the invite key so key holders can trace us
+    // back to whoever invited us
+    let vouch = params
+        .invite_secret
+        .as_deref()
+        .and_then(|secret| <[u8; 32]>::try_from(hex::decode(secret).ok()?).ok())
+        .map(|seed| {
+            let invite_key = ed25519_dalek::SigningKey::from_bytes(&seed);
+            let vouch = member_vouch::vouch(&invite_key, &params.community_id, &signing_key.verifying_key().to_bytes());
+            MemberVouchDto::from(&vouch)
+        });
+
     let request = rekindle_protocol::messaging::CommunityRequest::Join {
         pseudonym_pubkey: params.my_pseudonym_key.clone().unwrap_or_default(),
-        invite_code: None,
+        invite_code: params.invite_code.clone(),
         display_name: params.display_name.clone(),
-        prekey_bundle: Vec::new(),
+        prekey_bundle,
         route_blob: params.our_route_blob.clone(),
+        vouch,
     };
     let request_bytes = serde_json::to_vec(&request)
         .map_err(|e| format!("failed to serialize join request: {e}"))?;
@@ -369,7 +487,8 @@ async fn send_join_rpc(
 
     match serde_json::from_slice::<rekindle_protocol::messaging::CommunityResponse>(&response_bytes) {
         Ok(rekindle_protocol::messaging::CommunityResponse::Joined {
-            mek_encrypted, mek_generation, channels: server_channels, role_ids, roles: server_roles,
+            mek_encrypted, mek_generation, session_init, channels: server_channels, role_ids, roles: server_roles,
+            zero_knowledge, history_visibility, tree_kem,
         }) => {
             let role = crate::state::display_role_name(
                 &role_ids,
@@ -389,18 +508,43 @@ async fn send_join_rpc(
                     _ => ChannelType::Text,
                 },
                 unread_count: 0,
+                retention_secs: ch.retention_secs,
             }).collect();
 
             let roles = server_roles.iter().map(RoleDefinition::from_dto).collect();
 
-            if mek_encrypted.len() >= 40 {
-                let key_bytes: [u8; 32] = mek_encrypted[8..40].try_into().unwrap_or_default();
-                let mek = MediaEncryptionKey::from_bytes(key_bytes, mek_generation);
-                state.mek_cache.lock().insert(params.community_id.clone(), mek);
-                tracing::debug!(community = %params.community_id, generation = mek_generation, "MEK received and cached");
+            // The caller persists the keyring to Stronghold
+            // An empty copy means no key for us yet: a zero-knowledge
+            // community nobody has published for, or we are timed out
+            let opened = if mek_encrypted.is_empty() {
+                Ok(None)
+            } else if !zero_knowledge {
+                super::mek_service::open_delivery(state, &params.community_id, &mek_encrypted, session_init.as_ref())
+                    .map(Some)
+            } else {
+                super::mek_service::open_wrapped(state, &params.community_id, &mek_encrypted).map(Some)
+            };
+            match opened {
+                Ok(Some(mek)) if mek.generation() == mek_generation => {
+                    state.mek_cache.lock().entry(params.community_id.clone()).or_default().insert(mek);
+                    tracing::debug!(community = %params.community_id, generation = mek_generation, "MEK received and cached");
+                }
+                Ok(Some(mek)) => {
+                    tracing::warn!(
+                        community = %params.community_id,
+                        claimed = mek_generation, actual = mek.generation(),
+                        "delivered MEK generation mismatch — ignoring"
+                    );
+                }
+                Ok(None) => {
+                    tracing::info!(community = %params.community_id, zero_knowledge, "no MEK in join response — waiting for one to be delivered");
+                }
+                Err(e) => {
+                    tracing::warn!(community = %params.community_id, error = %e, "failed to open MEK from join response");
+                }
             }
 
-            Ok(Some(JoinRpcResult { mek_generation, role, role_ids, roles, channels }))
+            Ok(Some(JoinRpcResult { mek_generation, role, role_ids, roles, channels, history_visibility, tree_kem }))
         }
         Ok(rekindle_protocol::messaging::CommunityResponse::Error { message, .. }) => {
             tracing::warn!(error = %message, "server rejected join request");
@@ -469,6 +613,7 @@ pub async fn create_channel(
         name: channel_name.to_string(),
         channel_type: ch_type,
         unread_count: 0,
+        retention_secs: 0,
     };
 
     // Add to community state
@@ -506,6 +651,7 @@ pub async fn create_channel(
                         _ => ChannelType::Text,
                     },
                     unread_count: 0,
+                    retention_secs: 0,
                 });
 
                 let channels_wrapper = serde_json::json!({
@@ -517,6 +663,7 @@ pub async fn create_channel(
                                 ChannelType::Text => "text",
                                 ChannelType::Voice => "voice",
                             },
+                            "retentionSecs": ch.retention_secs,
                         })
                     }).collect::<Vec<_>>(),
                     "lastRefreshed": 0,
@@ -559,7 +706,101 @@ fn parse_community_metadata(data: &[u8], community_id: &str) -> (String, Option<
     }
 }
 
-/// Parse channel list JSON from DHT value bytes.
+/// Bring one of a community's paged lists up to date from its subkey,
+/// reading only the changes since the revision we last applied when they
+/// are still logged. The caller applies the result; its revision is
+/// recorded here. `None` when the subkey is empty.
+pub async fn sync_community_list(
+    state: &Arc<AppState>,
+    mgr: &DHTManager,
+    dht_key: &str,
+    subkey: u32,
+) -> Result<Option<ListSync>, String> {
+    let Some(data) = mgr.get_value(dht_key, subkey).await.map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let list = (dht_key.to_string(), subkey);
+    let since = state.community_lists.read().get(&list).copied();
+    let sync = paged_list::sync(mgr.routing_context(), &data, since)
+        .await
+        .map_err(|e| e.to_string())?;
+    match &sync {
+        ListSync::Changes { revision, .. } | ListSync::Full { revision, .. } => {
+            state.community_lists.write().insert(list, *revision);
+        }
+        ListSync::Legacy(_) => {
+            state.community_lists.write().remove(&list);
+        }
+        ListSync::Unchanged => {}
+    }
+    Ok(Some(sync))
+}
+
+/// Apply a channel list sync to `channels`, keeping unread counts.
+/// Returns whether anything changed.
+pub fn apply_channel_sync(channels: &mut Vec<ChannelInfo>, sync: &ListSync) -> bool {
+    match sync {
+        ListSync::Unchanged => false,
+        ListSync::Legacy(data) => {
+            let parsed = parse_channel_list(data);
+            if parsed.is_empty() {
+                return false;
+            }
+            *channels = parsed.into_iter().map(|ch| keep_unread(channels, ch)).collect();
+            true
+        }
+        ListSync::Full { entries, .. } => {
+            let mut published: Vec<ChannelEntry> = entries.iter().filter_map(|e| e.decode().ok()).collect();
+            published.sort_by_key(|ch| ch.sort_order);
+            *channels = published
+                .iter()
+                .map(|ch| keep_unread(channels, channel_info(ch)))
+                .collect();
+            true
+        }
+        ListSync::Changes { changes, .. } => {
+            for change in changes {
+                match change {
+                    ListChange::Upsert(entry) => {
+                        let Ok(published) = entry.decode::<ChannelEntry>() else {
+                            continue;
+                        };
+                        let info = keep_unread(channels, channel_info(&published));
+                        match channels.iter_mut().find(|ch| ch.id == info.id) {
+                            Some(existing) => *existing = info,
+                            None => channels.push(info),
+                        }
+                    }
+                    ListChange::Remove { id } => channels.retain(|ch| &ch.id != id),
+                }
+            }
+            !changes.is_empty()
+        }
+    }
+}
+
+fn channel_info(entry: &ChannelEntry) -> ChannelInfo {
+    ChannelInfo {
+        id: entry.id.clone(),
+        name: entry.name.clone(),
+        channel_type: match entry.channel_type.as_str() {
+            "voice" => ChannelType::Voice,
+            _ => ChannelType::Text,
+        },
+        unread_count: 0,
+        retention_secs: entry.retention_secs,
+    }
+}
+
+fn keep_unread(current: &[ChannelInfo], mut channel: ChannelInfo) -> ChannelInfo {
+    if let Some(existing) = current.iter().find(|ch| ch.id == channel.id) {
+        channel.unread_count = existing.unread_count;
+    }
+    channel
+}
+
+/// Parse a single-value channel list, the layout from before channel lists
+/// were paged.
 fn parse_channel_list(data: &[u8]) -> Vec<ChannelInfo> {
     // Support both wrapped format { channels: [...], lastRefreshed } and bare array [...]
     let channel_list: Vec<serde_json::Value> = match serde_json::from_slice::<serde_json::Value>(data) {
@@ -587,6 +828,11 @@ fn parse_channel_list(data: &[u8]) -> Vec<ChannelInfo> {
                 name: ch_name,
                 channel_type: ch_type,
                 unread_count: 0,
+                retention_secs: ch
+                    .get("retentionSecs")
+                    .and_then(serde_json::Value::as_u64)
+                    .and_then(|s| u32::try_from(s).ok())
+                    .unwrap_or(0),
             })
         })
         .collect()
@@ -0,0 +1,1424 @@
+//! Linked devices: one identity on several machines.
+//!
+//! The machine an identity was created on is its primary device. Another
+//! machine joins by showing a link request — a `rekindle-link://` URL signed
+//! with a fresh device key — which the user approves on the primary. The
+//! primary signs a `DeviceCertificate` for the new device and answers with a
+//! `DeviceLinkGrant` carrying the identity secret and account pointers, sealed
+//! to the device key. It then lists its certificates in profile subkey 7 and
+//! in the account header so friends and sibling devices learn about it.
+//!
+//! Every device keeps its own Signal sessions and prekeys. Sessions with a
+//! linked device are addressed `{identity}/{device}`; a primary keeps the
+//! bare identity key. Outgoing payloads go out once per recipient device,
+//! and what we send is mirrored to our other devices as a `SentTranscript`.
+//! The primary owns the friend list and pushes it to linked devices as a
+//! `ContactSync` snapshot whenever it changes.
+
+use std::sync::Arc;
+
+use rekindle_protocol::capnp_codec::account::DeviceEntry;
+use rekindle_protocol::dht::mailbox;
+use rand::seq::SliceRandom as _;
+use rekindle_crypto::CryptoError;
+use rekindle_protocol::dht::profile::{self, SUBKEY_DEVICES, SUBKEY_PREKEY_BUNDLE};
+use rekindle_protocol::messaging::envelope::{
+    create_link_request as sign_link_request, decode_link_request_url, encode_link_request_url,
+    sign_device_certificate, verify_device_certificate, verify_link_request, DeviceCertificate,
+    MessagePayload, SyncedContact, MAX_RECEIPT_BATCH,
+};
+use rekindle_protocol::messaging::sender::send_envelope;
+use tauri::Emitter;
+use zeroize::Zeroizing;
+
+use crate::channels::{ChatEvent, NotificationEvent};
+use crate::commands::auth::current_owner_key;
+use crate::db::{self, DbPool};
+use crate::services::disappearing_service;
+use crate::services::group_service;
+use crate::services::message_edit_service::{self, Change};
+use crate::services::message_service;
+use crate::state::{
+    AppState, DeviceInfo, FriendState, FriendshipState, LinkedAccount, LocalDevice, PendingLink,
+    UserStatus,
+};
+
+/// How long a link request can be approved after it was created.
+const LINK_REQUEST_TTL_MS: u64 = 15 * 60 * 1000;
+
+// ---------------------------------------------------------------------------
+// Addresses
+// ---------------------------------------------------------------------------
+
+/// Signal session and route cache address of a linked device.
+pub fn device_address(identity_key: &str, device_key: &str) -> String {
+    format!("{identity_key}/{device_key}")
+}
+
+/// Split an address into its identity key and, for a linked device, its
+/// device key.
+pub fn split_address(address: &str) -> (&str, Option<&str>) {
+    match address.split_once('/') {
+        Some((identity, device)) => (identity, Some(device)),
+        None => (address, None),
+    }
+}
+
+/// Whether `address` names a linked device rather than an identity's primary.
+pub fn is_device_address(address: &str) -> bool {
+    address.contains('/')
+}
+
+fn own_key(state: &AppState) -> Option<String> {
+    state.identity.read().as_ref().map(|id| id.public_key.clone())
+}
+
+/// Whether this machine is a linked (non-primary) device.
+pub fn is_linked_device(state: &AppState) -> bool {
+    state.local_device.read().is_some()
+}
+
+/// Reject operations only the primary device may perform.
+pub fn require_primary(state: &AppState) -> Result<(), String> {
+    if is_linked_device(state) {
+        Err("Manage friends and devices from your primary device".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Our device key as it goes into outgoing envelopes: empty on the primary.
+pub fn local_device_key_bytes(state: &AppState) -> Vec<u8> {
+    state
+        .local_device
+        .read()
+        .as_ref()
+        .and_then(|d| hex::decode(&d.device_key).ok())
+        .unwrap_or_default()
+}
+
+/// The mailbox our route blob is published in: the device's own mailbox on
+/// a linked device, the identity's on the primary.
+pub fn own_mailbox_key(state: &AppState) -> Option<String> {
+    if let Some(device) = state.local_device.read().as_ref() {
+        return Some(device.mailbox_dht_key.clone());
+    }
+    state.node.read().as_ref().and_then(|nh| nh.mailbox_dht_key.clone())
+}
+
+/// Addresses besides `identity` itself that a payload for `identity` must
+/// also reach.
+///
+/// For a friend that is their linked devices. For ourselves it is every
+/// device but this one — the primary included, seen from a linked device.
+pub fn other_devices(state: &AppState, identity: &str) -> Vec<String> {
+    let local = state.local_device.read().as_ref().map(|d| d.device_key.clone());
+    let mut addresses = Vec::new();
+    if local.is_some() && own_key(state).as_deref() == Some(identity) {
+        addresses.push(identity.to_string());
+    }
+    if let Some(devices) = state.devices.read().get(identity) {
+        addresses.extend(
+            devices
+                .iter()
+                .filter(|d| Some(&d.device_key) != local.as_ref())
+                .map(|d| device_address(identity, &d.device_key)),
+        );
+    }
+    addresses
+}
+
+/// Mailbox DHT key through which `address` publishes its route.
+pub fn mailbox_for_address(state: &AppState, address: &str) -> Option<String> {
+    match split_address(address) {
+        (identity, Some(device)) => state
+            .devices
+            .read()
+            .get(identity)?
+            .iter()
+            .find(|d| d.device_key == device)
+            .map(|d| d.mailbox_dht_key.clone()),
+        (identity, None) if own_key(state).as_deref() == Some(identity) => {
+            state.node.read().as_ref().and_then(|nh| nh.mailbox_dht_key.clone())
+        }
+        (identity, None) => {
+            let friend = state
+                .friends
+                .read()
+                .get(identity)
+                .and_then(|f| f.mailbox_dht_key.clone());
+            friend.or_else(|| group_service::member_mailbox_key(state, identity))
+        }
+    }
+}
+
+/// Profile DHT key of an identity: ours, a friend's, or that of someone we
+/// share a group conversation with.
+pub fn profile_key_for(state: &AppState, identity: &str) -> Option<String> {
+    if own_key(state).as_deref() == Some(identity) {
+        return state.node.read().as_ref().and_then(|nh| nh.profile_dht_key.clone());
+    }
+    let friend = state
+        .friends
+        .read()
+        .get(identity)
+        .and_then(|f| f.dht_record_key.clone());
+    friend.or_else(|| group_service::member_profile_key(state, identity))
+}
+
+/// Read the route blob a linked device last published in its mailbox.
+pub async fn read_device_route(state: &AppState, address: &str) -> Option<Vec<u8>> {
+    let mailbox_key = mailbox_for_address(state, address)?;
+    let rc = state.node.read().as_ref().map(|nh| nh.routing_context.clone())?;
+    match mailbox::read_peer_mailbox_route(&rc, &mailbox_key).await {
+        Ok(Some(blob)) if !blob.is_empty() => Some(blob),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::trace!(to = %address, error = %e, "failed to read device mailbox");
+            None
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Signal sessions with devices
+// ---------------------------------------------------------------------------
+
+/// Whether we set up a Signal session with `address` ourselves when there
+/// is none, rather than waiting for a friend request handshake.
+///
+/// Friend requests only ever reach primaries, so sessions with linked
+/// devices — a friend's or our own — and any session a linked device needs
+/// are started from published prekeys. So are sessions with group members
+/// we aren't friends with, who never see a friend request from us.
+pub fn starts_own_sessions(state: &AppState, address: &str) -> bool {
+    is_device_address(address)
+        || is_linked_device(state)
+        || own_key(state).as_deref() == Some(address)
+        || (!state.friends.read().contains_key(address) && group_service::is_in_any_group(state, address))
+}
+
+/// Fetch the prekey bundle `address` publishes: a linked device's from its
+/// mailbox, a primary's from its profile.
+///
+/// A primary's bundle carries no one-time prekey; one is taken from a random
+/// non-empty slot of its profile pool so concurrent fetchers rarely pick the
+/// same key. Without one the session falls back to the signed prekey alone.
+/// With `one_time_prekey` false no one-time prekey is used at all.
+async fn fetch_prekey_bundle(
+    state: &AppState,
+    address: &str,
+    one_time_prekey: bool,
+) -> Option<rekindle_crypto::signal::PreKeyBundle> {
+    let rc = state.node.read().as_ref().map(|nh| nh.routing_context.clone())?;
+    if is_device_address(address) {
+        let mailbox_key = mailbox_for_address(state, address)?;
+        let bytes = mailbox::read_device_mailbox_prekey_bundle(&rc, &mailbox_key)
+            .await
+            .ok()??;
+        let mut bundle: rekindle_crypto::signal::PreKeyBundle = serde_json::from_slice(&bytes).ok()?;
+        if !one_time_prekey {
+            bundle.one_time_prekey = None;
+            bundle.one_time_prekey_id = None;
+        }
+        return Some(bundle);
+    }
+
+    let profile_key = profile_key_for(state, address)?;
+    let record_key: veilid_core::RecordKey = profile_key.parse().ok()?;
+    let _ = rc.open_dht_record(record_key.clone(), None).await;
+    let bytes = rc
+        .get_dht_value(record_key, SUBKEY_PREKEY_BUNDLE, true)
+        .await
+        .ok()??
+        .data()
+        .to_vec();
+    let bundle: rekindle_crypto::signal::PreKeyBundle = serde_json::from_slice(&bytes).ok()?;
+    if !one_time_prekey {
+        return Some(bundle);
+    }
+
+    let dht = rekindle_protocol::dht::DHTManager::new(rc);
+    let mut slots: Vec<u32> = (0..profile::ONE_TIME_PREKEY_SLOTS).collect();
+    slots.shuffle(&mut rand::thread_rng());
+    for slot in slots {
+        let Ok(Some(value)) = profile::read_one_time_prekey(&dht, &profile_key, slot).await else {
+            continue;
+        };
+        if let Ok(prekey) = serde_json::from_slice::<rekindle_crypto::signal::OneTimePreKeyPublic>(&value) {
+            return Some(bundle.with_one_time_prekey(prekey));
+        }
+    }
+    tracing::debug!(peer = %address, "no one-time prekey in pool — using signed prekey only");
+    Some(bundle)
+}
+
+/// Start a Signal session with `address` from its published prekeys and
+/// wrap `plaintext`, encrypted on it, in a `SessionInit` payload.
+///
+/// A one-time prekey can be taken by another initiator before our init
+/// arrives, so an init that used one is remembered until `address` answers;
+/// if it rejects the init, [`retry_session_init`] sends the payload again
+/// without a one-time prekey.
+pub async fn session_init(
+    state: &AppState,
+    address: &str,
+    plaintext: &[u8],
+    one_time_prekey: bool,
+) -> Result<Vec<u8>, String> {
+    let bundle = fetch_prekey_bundle(state, address, one_time_prekey)
+        .await
+        .ok_or("no prekey bundle published for this device")?;
+
+    // Every device of an identity shares its Signal identity key, so a
+    // bundle under any other key was not published by them.
+    let (identity, _) = split_address(address);
+    let identity_bytes: [u8; 32] = hex::decode(identity)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or("invalid identity key")?;
+    let expected = rekindle_crypto::Identity::peer_ed25519_to_x25519(&identity_bytes)
+        .map_err(|e| e.to_string())?;
+    if bundle.identity_key != expected.as_bytes() {
+        return Err("prekey bundle does not belong to this identity".to_string());
+    }
+
+    let (info, ciphertext) = {
+        let signal = state.signal_manager.lock();
+        let handle = signal.as_ref().ok_or("signal manager not initialized")?;
+        let info = handle
+            .manager
+            .establish_session(address, &bundle)
+            .map_err(|e| format!("establish session: {e}"))?;
+        let ciphertext = handle
+            .manager
+            .encrypt(address, plaintext)
+            .map_err(|e| format!("Signal encrypt: {e}"))?;
+        (info, ciphertext)
+    };
+    tracing::info!(peer = %address, "established Signal session from published prekeys");
+    if let Some(prekey_id) = info.one_time_prekey_id {
+        state
+            .pending_session_inits
+            .lock()
+            .insert(address.to_string(), (prekey_id, plaintext.to_vec()));
+    }
+
+    let wrapped = MessagePayload::SessionInit {
+        ephemeral_key: info.ephemeral_public_key,
+        signed_prekey_id: info.signed_prekey_id,
+        one_time_prekey_id: info.one_time_prekey_id,
+        ciphertext,
+    };
+    serde_json::to_vec(&wrapped).map_err(|e| format!("serialize payload: {e}"))
+}
+
+/// Answer a `SessionInit` from `address` and decrypt the payload it carries.
+///
+/// Fails with [`CryptoError::OneTimePreKeyConsumed`] when another initiator
+/// already used the one-time prekey; the caller tells `address` to retry.
+pub fn accept_session_init(
+    state: &AppState,
+    address: &str,
+    sender_key: &[u8],
+    ephemeral_key: &[u8],
+    signed_prekey_id: u32,
+    one_time_prekey_id: Option<u32>,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let sender: [u8; 32] = sender_key
+        .try_into()
+        .map_err(|_| CryptoError::InvalidKey("invalid sender key".into()))?;
+    let their_identity = rekindle_crypto::Identity::peer_ed25519_to_x25519(&sender)?;
+
+    let signal = state.signal_manager.lock();
+    let handle = signal
+        .as_ref()
+        .ok_or_else(|| CryptoError::SessionError("signal manager not initialized".into()))?;
+    handle.manager.respond_to_session(
+        address,
+        their_identity.as_bytes(),
+        ephemeral_key,
+        signed_prekey_id,
+        one_time_prekey_id,
+    )?;
+    tracing::info!(from = %address, "established responder Signal session from SessionInit");
+    handle.manager.decrypt(address, ciphertext)
+}
+
+/// `address` rejected our `SessionInit` because `prekey_id` was already
+/// used: drop the session it never accepted and send the payload again on
+/// a new one from the signed prekey alone, which cannot be rejected this way.
+pub async fn retry_session_init(state: &Arc<AppState>, pool: &DbPool, address: &str, prekey_id: u32) {
+    let plaintext = {
+        let mut pending = state.pending_session_inits.lock();
+        match pending.get(address) {
+            Some((id, _)) if *id == prekey_id => pending.remove(address).map(|(_, pt)| pt),
+            _ => None,
+        }
+    };
+    let Some(plaintext) = plaintext else {
+        tracing::debug!(from = %address, prekey_id, "ignoring rejection of a session init we did not send");
+        return;
+    };
+
+    if let Some(handle) = state.signal_manager.lock().as_ref() {
+        let _ = handle.manager.delete_session(address);
+    }
+    let wrapped = match session_init(state, address, &plaintext, false).await {
+        Ok(wrapped) => wrapped,
+        Err(e) => {
+            tracing::warn!(to = %address, error = %e, "could not restart rejected Signal session");
+            return;
+        }
+    };
+    match serde_json::from_slice::<MessagePayload>(&wrapped) {
+        Ok(payload) => {
+            if let Err(e) = message_service::deliver_to_device(state, pool, address, &payload, false).await {
+                tracing::warn!(to = %address, error = %e, "failed to resend session init");
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "failed to re-read session init"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Fan-out and transcripts
+// ---------------------------------------------------------------------------
+
+/// Payloads every device of the recipient must see.
+fn reaches_every_device(payload: &MessagePayload) -> bool {
+    matches!(
+        payload,
+        MessagePayload::DirectMessage { .. }
+            | MessagePayload::EditMessage { .. }
+            | MessagePayload::DeleteMessage { .. }
+            | MessagePayload::AddReaction { .. }
+            | MessagePayload::RemoveReaction { .. }
+            | MessagePayload::DisappearingTimer { .. }
+            | MessagePayload::DeliveryReceipt { .. }
+            | MessagePayload::ReadReceipt { .. }
+            | MessagePayload::TypingIndicator { .. }
+            | MessagePayload::ProfileKeyRotated { .. }
+            | MessagePayload::Unfriended
+            | MessagePayload::GroupMessage { .. }
+            | MessagePayload::GroupInvite { .. }
+            | MessagePayload::GroupMemberAdded { .. }
+            | MessagePayload::GroupMemberLeft { .. }
+    )
+}
+
+/// Payloads our other devices should record as sent by us.
+fn is_mirrored(payload: &MessagePayload) -> bool {
+    matches!(
+        payload,
+        MessagePayload::DirectMessage { .. }
+            | MessagePayload::EditMessage { .. }
+            | MessagePayload::DeleteMessage { .. }
+            | MessagePayload::AddReaction { .. }
+            | MessagePayload::RemoveReaction { .. }
+            | MessagePayload::DisappearingTimer { .. }
+    )
+}
+
+/// Send `payload`, just delivered to `to`, on to `to`'s other devices and
+/// mirror it to ours. Runs in the background.
+pub fn fan_out(state: &Arc<AppState>, pool: &DbPool, to: &str, payload: &MessagePayload, encrypt: bool) {
+    let mut targets = Vec::new();
+    if reaches_every_device(payload) {
+        for address in other_devices(state, to) {
+            targets.push((address, payload.clone(), encrypt));
+        }
+    }
+    if is_mirrored(payload) {
+        if let Some(own) = own_key(state).filter(|own| own != to) {
+            let transcript = MessagePayload::SentTranscript {
+                peer: to.to_string(),
+                payload: Box::new(payload.clone()),
+            };
+            for address in other_devices(state, &own) {
+                targets.push((address, transcript.clone(), true));
+            }
+        }
+    }
+    send_in_background(state, pool, targets);
+}
+
+/// Tell our other devices about something we did in the conversation with
+/// `peer` that never goes to the peer itself, such as reading it.
+pub fn mirror_to_siblings(state: &Arc<AppState>, pool: &DbPool, peer: &str, payload: MessagePayload) {
+    let Some(own) = own_key(state) else {
+        return;
+    };
+    let transcript = MessagePayload::SentTranscript {
+        peer: peer.to_string(),
+        payload: Box::new(payload),
+    };
+    let targets = other_devices(state, &own)
+        .into_iter()
+        .map(|address| (address, transcript.clone(), true))
+        .collect();
+    send_in_background(state, pool, targets);
+}
+
+fn send_in_background(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    targets: Vec<(String, MessagePayload, bool)>,
+) {
+    if targets.is_empty() {
+        return;
+    }
+    let state = Arc::clone(state);
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        for (address, payload, encrypt) in targets {
+            if let Err(e) =
+                message_service::deliver_to_device(&state, &pool, &address, &payload, encrypt).await
+            {
+                tracing::debug!(to = %address, error = %e, "failed to reach device");
+            }
+        }
+    });
+}
+
+/// Apply a payload one of our other devices sent to `peer`.
+pub async fn apply_transcript(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    peer: &str,
+    payload: MessagePayload,
+    timestamp: i64,
+) {
+    let Some(owner_key) = own_key(state) else {
+        return;
+    };
+    match payload {
+        MessagePayload::DirectMessage { message_id, body, attachments, .. } => {
+            let attachment_json = if attachments.is_empty() {
+                None
+            } else {
+                serde_json::to_string(&attachments).ok()
+            };
+            let global_id = (!message_id.is_empty()).then_some(message_id);
+            let row_id = match store_sent_message(
+                pool,
+                &owner_key,
+                peer,
+                &body,
+                timestamp,
+                attachment_json,
+                global_id.clone(),
+            )
+            .await
+            {
+                Ok(Some(id)) => id,
+                Ok(None) => return,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to persist mirrored message");
+                    return;
+                }
+            };
+            let attachments =
+                crate::services::file_transfer_service::remote_attachment_infos(row_id, &attachments);
+            let event = ChatEvent::MessageReceived {
+                from: owner_key,
+                body,
+                timestamp: timestamp.cast_unsigned(),
+                conversation_id: peer.to_string(),
+                attachments,
+                message_id: global_id,
+                reply_to: None,
+            };
+            let _ = app.emit("chat-event", &event);
+        }
+        MessagePayload::EditMessage { message_id, body } => {
+            let edit = Change::Edit { body: &body, edited_at: timestamp };
+            message_edit_service::apply_remote(app, state, pool, peer, &message_id, Some(&owner_key), edit)
+                .await;
+        }
+        MessagePayload::DeleteMessage { message_id } => {
+            message_edit_service::apply_remote(
+                app, state, pool, peer, &message_id, Some(&owner_key), Change::Delete,
+            )
+            .await;
+        }
+        MessagePayload::AddReaction { message_id, emoji } => {
+            let react = Change::React { reactor: &owner_key, emoji: &emoji, add: true };
+            message_edit_service::apply_remote(app, state, pool, peer, &message_id, None, react).await;
+        }
+        MessagePayload::RemoveReaction { message_id, emoji } => {
+            let react = Change::React { reactor: &owner_key, emoji: &emoji, add: false };
+            message_edit_service::apply_remote(app, state, pool, peer, &message_id, None, react).await;
+        }
+        MessagePayload::ReadReceipt { message_ids } => {
+            if let Err(e) = mark_read_elsewhere(state, pool, &owner_key, peer, message_ids).await {
+                tracing::warn!(error = %e, "failed to apply read state from another device");
+            }
+        }
+        MessagePayload::DisappearingTimer { seconds } => {
+            disappearing_service::handle_timer(app, state, pool, peer, &owner_key, seconds).await;
+        }
+        _ => tracing::debug!(peer = %peer, "ignoring transcript of a payload that is never mirrored"),
+    }
+}
+
+/// Store a DM one of our other devices sent. Returns `None` if we already
+/// have it.
+async fn store_sent_message(
+    pool: &DbPool,
+    owner_key: &str,
+    peer: &str,
+    body: &str,
+    timestamp: i64,
+    attachment_json: Option<String>,
+    message_id: Option<String>,
+) -> Result<Option<i64>, String> {
+    let pool = pool.clone();
+    let owner_key = owner_key.to_string();
+    let peer = peer.to_string();
+    let body = body.to_string();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, \
+                 timestamp, is_read, attachment_json, message_id, delivery_state) \
+                 VALUES (?1, ?2, 'dm', ?1, ?3, ?4, 1, ?5, ?6, 'sent')",
+                rusqlite::params![owner_key, peer, body, timestamp, attachment_json, message_id],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok::<_, String>((inserted > 0).then(|| conn.last_insert_rowid()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Mark messages from `peer` read because we read them on another device.
+async fn mark_read_elsewhere(
+    state: &AppState,
+    pool: &DbPool,
+    owner_key: &str,
+    peer: &str,
+    message_ids: Vec<String>,
+) -> Result<(), String> {
+    let pool = pool.clone();
+    let owner_key = owner_key.to_string();
+    let peer_key = peer.to_string();
+    let unread = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        for message_id in message_ids.iter().take(MAX_RECEIPT_BATCH) {
+            conn.execute(
+                "UPDATE messages SET is_read = 1 WHERE owner_key = ?1 AND conversation_id = ?2 \
+                 AND conversation_type = 'dm' AND message_id = ?3",
+                rusqlite::params![owner_key, peer_key, message_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        conn.query_row(
+            "SELECT COUNT(*) FROM messages WHERE owner_key = ?1 AND conversation_id = ?2 \
+             AND conversation_type = 'dm' AND is_read = 0",
+            rusqlite::params![owner_key, peer_key],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if let Some(friend) = state.friends.write().get_mut(peer) {
+        friend.unread_count = u32::try_from(unread).unwrap_or(u32::MAX);
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Contact sync
+// ---------------------------------------------------------------------------
+
+/// Our accepted friends, as linked devices receive them.
+fn contact_snapshot(state: &AppState) -> Vec<SyncedContact> {
+    state
+        .friends
+        .read()
+        .values()
+        .filter(|f| f.friendship_state == FriendshipState::Accepted)
+        .map(|f| SyncedContact {
+            public_key: f.public_key.clone(),
+            display_name: f.display_name.clone(),
+            nickname: f.nickname.clone(),
+            profile_dht_key: f.dht_record_key.clone(),
+            mailbox_dht_key: f.mailbox_dht_key.clone(),
+        })
+        .collect()
+}
+
+/// Send our linked devices the current friend list and bring the account
+/// record up to date. Primary only; runs in the background.
+pub fn share_contacts(state: &Arc<AppState>, pool: &DbPool) {
+    if is_linked_device(state) {
+        return;
+    }
+    super::account_service::publish_in_background(state, pool);
+    let Some(own) = own_key(state) else {
+        return;
+    };
+    let contacts = contact_snapshot(state);
+    let payload = MessagePayload::ContactSync { contacts };
+    let targets = other_devices(state, &own)
+        .into_iter()
+        .map(|address| (address, payload.clone(), true))
+        .collect();
+    send_in_background(state, pool, targets);
+}
+
+/// Bring our friend list in line with a snapshot from the primary device.
+pub async fn apply_contact_sync(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    contacts: Vec<SyncedContact>,
+) {
+    if !is_linked_device(state) {
+        tracing::warn!("ignoring contact sync on the primary device");
+        return;
+    }
+    let Some(owner_key) = own_key(state) else {
+        return;
+    };
+
+    let removed: Vec<(String, Option<String>)> = state
+        .friends
+        .read()
+        .values()
+        .filter(|f| !contacts.iter().any(|c| c.public_key == f.public_key))
+        .map(|f| (f.public_key.clone(), f.dht_record_key.clone()))
+        .collect();
+
+    let db = pool.clone();
+    let ok = owner_key.clone();
+    let rows = contacts.clone();
+    let removed_keys: Vec<String> = removed.iter().map(|(k, _)| k.clone()).collect();
+    let now = db::timestamp_now();
+    let persisted = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        for c in &rows {
+            conn.execute(
+                "INSERT INTO friends (owner_key, public_key, display_name, nickname, added_at, \
+                 dht_record_key, mailbox_dht_key, friendship_state) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'accepted') \
+                 ON CONFLICT(owner_key, public_key) DO UPDATE SET display_name = excluded.display_name, \
+                 nickname = excluded.nickname, \
+                 dht_record_key = COALESCE(excluded.dht_record_key, dht_record_key), \
+                 mailbox_dht_key = COALESCE(excluded.mailbox_dht_key, mailbox_dht_key), \
+                 friendship_state = 'accepted'",
+                rusqlite::params![
+                    ok, c.public_key, c.display_name, c.nickname, now,
+                    c.profile_dht_key, c.mailbox_dht_key,
+                ],
+            )
+            .map_err(|e| format!("upsert friend: {e}"))?;
+        }
+        for key in &removed_keys {
+            conn.execute(
+                "DELETE FROM friends WHERE owner_key = ?1 AND public_key = ?2",
+                rusqlite::params![ok, key],
+            )
+            .map_err(|e| format!("delete friend: {e}"))?;
+        }
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+    if let Err(e) = persisted {
+        tracing::error!(error = %e, "failed to apply contact sync");
+        return;
+    }
+
+    for (public_key, dht_key) in removed {
+        state.friends.write().remove(&public_key);
+        if let Some(dht_key) = dht_key {
+            if let Some(mgr) = state.dht_manager.write().as_mut() {
+                mgr.unregister_friend_dht_key(&dht_key);
+            }
+        }
+        let _ = app.emit("chat-event", &ChatEvent::FriendRemoved { public_key });
+    }
+
+    for contact in contacts {
+        let is_new = {
+            let mut friends = state.friends.write();
+            if let Some(friend) = friends.get_mut(&contact.public_key) {
+                friend.display_name.clone_from(&contact.display_name);
+                friend.nickname.clone_from(&contact.nickname);
+                if contact.profile_dht_key.is_some() {
+                    friend.dht_record_key.clone_from(&contact.profile_dht_key);
+                }
+                if contact.mailbox_dht_key.is_some() {
+                    friend.mailbox_dht_key.clone_from(&contact.mailbox_dht_key);
+                }
+                friend.friendship_state = FriendshipState::Accepted;
+                false
+            } else {
+                friends.insert(
+                    contact.public_key.clone(),
+                    FriendState {
+                        public_key: contact.public_key.clone(),
+                        display_name: contact.display_name.clone(),
+                        nickname: contact.nickname.clone(),
+                        status: UserStatus::Offline,
+                        status_message: None,
+                        game_info: None,
+                        group: None,
+                        unread_count: 0,
+                        dht_record_key: contact.profile_dht_key.clone(),
+                        last_seen_at: None,
+                        local_conversation_key: None,
+                        remote_conversation_key: None,
+                        mailbox_dht_key: contact.mailbox_dht_key.clone(),
+                        last_heartbeat_at: None,
+                        friendship_state: FriendshipState::Accepted,
+                        disappearing_secs: 0,
+                    },
+                );
+                true
+            }
+        };
+        if !is_new {
+            continue;
+        }
+        if let Some(ref dht_key) = contact.profile_dht_key {
+            if let Err(e) =
+                super::presence_service::watch_friend(state, &contact.public_key, dht_key).await
+            {
+                tracing::trace!(friend = %contact.public_key, error = %e, "failed to watch synced friend");
+            }
+        }
+        let _ = app.emit(
+            "chat-event",
+            &ChatEvent::FriendAdded {
+                public_key: contact.public_key,
+                display_name: contact.display_name,
+                friendship_state: "accepted".to_string(),
+            },
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Linking a new device
+// ---------------------------------------------------------------------------
+
+/// Veilid keypair of a device, owner of its mailbox record.
+fn device_keypair(secret: &[u8; 32]) -> veilid_core::KeyPair {
+    let device = rekindle_crypto::Identity::from_secret_bytes(secret);
+    let bare_pub = veilid_core::BarePublicKey::new(&device.public_key_bytes());
+    let bare_secret = veilid_core::BareSecretKey::new(secret);
+    let public = veilid_core::PublicKey::new(veilid_core::CRYPTO_KIND_VLD0, bare_pub);
+    veilid_core::KeyPair::new_from_parts(public, bare_secret)
+}
+
+/// Start linking this machine to an existing identity (before login).
+///
+/// Generates a device key, publishes a mailbox for it and returns the link
+/// request URL to approve on the other device.
+pub async fn create_link_request(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    device_name: &str,
+) -> Result<String, String> {
+    let device_name = device_name.trim();
+    if device_name.is_empty() {
+        return Err("Give this device a name".to_string());
+    }
+    let rc = {
+        let node = state.node.read();
+        let nh = node.as_ref().ok_or("node not initialized")?;
+        if !nh.public_internet_ready {
+            return Err("Not connected to the network yet — try again in a moment".to_string());
+        }
+        nh.routing_context.clone()
+    };
+
+    let device = rekindle_crypto::Identity::generate();
+    let secret = *device.secret_key_bytes();
+    let mailbox_key = mailbox::create_device_mailbox(&rc, device_keypair(&secret))
+        .await
+        .map_err(|e| format!("create device mailbox: {e}"))?;
+    let route_blob = crate::commands::auth::allocate_route_with_retry(app, state, 5)
+        .await
+        .ok_or("could not allocate a private route")?;
+    mailbox::update_mailbox_route(&rc, &mailbox_key, &route_blob)
+        .await
+        .map_err(|e| format!("publish device route: {e}"))?;
+
+    let created_at = db::timestamp_now().cast_unsigned();
+    let request = sign_link_request(&secret, device_name, &mailbox_key, &route_blob, created_at);
+    *state.pending_link.lock() = Some(PendingLink {
+        secret,
+        device_name: device_name.to_string(),
+        mailbox_dht_key: mailbox_key,
+        grant: None,
+    });
+    tracing::info!(device = %request.device_key, "created device link request");
+    Ok(encode_link_request_url(&request))
+}
+
+/// Open a `DeviceLinkGrant` answering our pending link request.
+pub fn handle_link_grant(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    sender_hex: &str,
+    certificate: &DeviceCertificate,
+    sealed: &[u8],
+) {
+    let device = {
+        let pending = state.pending_link.lock();
+        match pending.as_ref() {
+            Some(link) if link.grant.is_none() => {
+                rekindle_crypto::Identity::from_secret_bytes(&link.secret)
+            }
+            _ => {
+                tracing::debug!(from = %sender_hex, "ignoring device link grant — no link pending");
+                return;
+            }
+        }
+    };
+    if certificate.device_key != device.public_key_hex() || certificate.identity_key != sender_hex {
+        tracing::warn!(from = %sender_hex, "device link grant is for another device");
+        return;
+    }
+    if let Err(e) = verify_device_certificate(certificate) {
+        tracing::warn!(from = %sender_hex, error = %e, "rejecting device link grant");
+        return;
+    }
+
+    let account = match rekindle_crypto::device_link::open_for_device(&device, sealed)
+        .map_err(|e| e.to_string())
+        .and_then(|plain| {
+            serde_json::from_slice::<LinkedAccount>(&plain).map_err(|e| e.to_string())
+        }) {
+        Ok(account) => account,
+        Err(e) => {
+            tracing::warn!(from = %sender_hex, error = %e, "failed to open device link grant");
+            return;
+        }
+    };
+    let secret_matches = <[u8; 32]>::try_from(account.identity_secret.as_slice())
+        .is_ok_and(|s| rekindle_crypto::Identity::from_secret_bytes(&s).public_key_hex() == sender_hex);
+    if !secret_matches {
+        tracing::warn!(from = %sender_hex, "device link grant carries another identity's key");
+        return;
+    }
+
+    let display_name = account.display_name.clone();
+    if let Some(link) = state.pending_link.lock().as_mut() {
+        link.grant = Some(account);
+    }
+    tracing::info!(identity = %sender_hex, "device link approved");
+    let _ = app.emit(
+        "notification-event",
+        &NotificationEvent::DeviceLinkGranted {
+            public_key: sender_hex.to_string(),
+            display_name,
+        },
+    );
+}
+
+/// Identity columns handed to a new device.
+struct AccountPointers {
+    display_name: String,
+    profile_dht_key: Option<String>,
+    friend_list_dht_key: Option<String>,
+    account_dht_key: Option<String>,
+    account_owner_keypair: Option<String>,
+    mailbox_dht_key: Option<String>,
+}
+
+async fn load_account_pointers(pool: &DbPool, owner_key: &str) -> Result<AccountPointers, String> {
+    let pool = pool.clone();
+    let owner_key = owner_key.to_string();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT display_name, dht_record_key, friend_list_dht_key, account_dht_key, \
+             account_owner_keypair, mailbox_dht_key FROM identity WHERE public_key = ?1",
+            rusqlite::params![owner_key],
+            |row| {
+                Ok(AccountPointers {
+                    display_name: db::get_str(row, "display_name"),
+                    profile_dht_key: db::get_str_opt(row, "dht_record_key"),
+                    friend_list_dht_key: db::get_str_opt(row, "friend_list_dht_key"),
+                    account_dht_key: db::get_str_opt(row, "account_dht_key"),
+                    account_owner_keypair: db::get_str_opt(row, "account_owner_keypair"),
+                    mailbox_dht_key: db::get_str_opt(row, "mailbox_dht_key"),
+                })
+            },
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Approve a link request shown by a new device: certify it, send it the
+/// account and publish the updated device list.
+pub async fn approve_link(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    url: &str,
+) -> Result<DeviceInfo, String> {
+    require_primary(state)?;
+    let request = decode_link_request_url(url)?;
+    verify_link_request(&request)?;
+    let now = db::timestamp_now().cast_unsigned();
+    if now.saturating_sub(request.created_at) > LINK_REQUEST_TTL_MS {
+        return Err("This link request has expired — create a new one on the other device".to_string());
+    }
+    let owner_key = current_owner_key(state)?;
+    let already_linked = state
+        .devices
+        .read()
+        .get(&owner_key)
+        .is_some_and(|ds| ds.iter().any(|d| d.device_key == request.device_key));
+    if request.device_key == owner_key || already_linked {
+        return Err("That device is already linked".to_string());
+    }
+
+    let secret = {
+        let sk = state.identity_secret.lock();
+        *sk.as_ref().ok_or("identity secret not available")?
+    };
+    let certificate = sign_device_certificate(
+        &secret,
+        &request.device_key,
+        &request.device_name,
+        &request.mailbox_dht_key,
+        now,
+    );
+    let mut devices = own_certificates(pool, &owner_key).await?;
+    devices.push(certificate.clone());
+
+    let pointers = load_account_pointers(pool, &owner_key).await?;
+    let contacts = contact_snapshot(state);
+    let account = LinkedAccount {
+        identity_secret: secret.to_vec(),
+        display_name: pointers.display_name,
+        profile_dht_key: pointers.profile_dht_key,
+        friend_list_dht_key: pointers.friend_list_dht_key,
+        account_dht_key: pointers.account_dht_key,
+        account_owner_keypair: pointers.account_owner_keypair,
+        mailbox_dht_key: pointers.mailbox_dht_key,
+        contacts,
+        devices,
+    };
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&account).map_err(|e| format!("serialize grant: {e}"))?,
+    );
+    let device_key = hex::decode(&request.device_key).map_err(|e| format!("invalid device key: {e}"))?;
+    let sealed = rekindle_crypto::device_link::seal_for_device(&device_key, &plaintext)
+        .map_err(|e| e.to_string())?;
+
+    let grant = MessagePayload::DeviceLinkGrant {
+        certificate: certificate.clone(),
+        sealed,
+    };
+    send_grant(state, &request.route_blob, &request.mailbox_dht_key, &grant).await?;
+
+    let info = DeviceInfo {
+        device_key: certificate.device_key.clone(),
+        name: certificate.device_name.clone(),
+        mailbox_dht_key: certificate.mailbox_dht_key.clone(),
+        added_at: i64::try_from(certificate.created_at).unwrap_or_default(),
+    };
+    insert_device(pool, &owner_key, &certificate, info.added_at).await?;
+    state
+        .devices
+        .write()
+        .entry(owner_key)
+        .or_default()
+        .push(info.clone());
+
+    if let Err(e) = publish_own_devices(state, pool).await {
+        tracing::warn!(error = %e, "failed to publish device list after linking");
+    }
+    tracing::info!(device = %info.device_key, "linked new device");
+    Ok(info)
+}
+
+/// Deliver a grant to the new device's route from its link request, falling
+/// back to the route in its mailbox.
+async fn send_grant(
+    state: &AppState,
+    route_blob: &[u8],
+    mailbox_key: &str,
+    grant: &MessagePayload,
+) -> Result<(), String> {
+    let payload = serde_json::to_vec(grant).map_err(|e| format!("serialize payload: {e}"))?;
+    let envelope = message_service::build_signed_envelope(state, payload)?;
+
+    if send_to_route(state, route_blob, &envelope).await.is_ok() {
+        return Ok(());
+    }
+    let rc = state.node.read().as_ref().map(|nh| nh.routing_context.clone());
+    let fresh = match rc {
+        Some(rc) => mailbox::read_peer_mailbox_route(&rc, mailbox_key).await.ok().flatten(),
+        None => None,
+    };
+    match fresh {
+        Some(blob) if !blob.is_empty() => send_to_route(state, &blob, &envelope).await,
+        _ => Err("The new device could not be reached — make sure it still shows the link request".to_string()),
+    }
+}
+
+async fn send_to_route(
+    state: &AppState,
+    route_blob: &[u8],
+    envelope: &rekindle_protocol::messaging::MessageEnvelope,
+) -> Result<(), String> {
+    let (route_id, rc) = {
+        let node = state.node.read();
+        let nh = node.as_ref().ok_or("node not initialized")?;
+        let mut dht_mgr = state.dht_manager.write();
+        let mgr = dht_mgr.as_mut().ok_or("DHT manager not initialized")?;
+        let route_id = mgr
+            .manager
+            .get_or_import_route(&nh.api, route_blob)
+            .map_err(|e| format!("import route: {e}"))?;
+        (route_id, nh.routing_context.clone())
+    };
+    send_envelope(&rc, route_id, envelope)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Unlink one of our devices and publish the shorter list.
+///
+/// The device keeps the identity secret it was given; removing it only
+/// stops us and our friends from sending to it.
+pub async fn remove_device(state: &Arc<AppState>, pool: &DbPool, device_key: &str) -> Result<(), String> {
+    require_primary(state)?;
+    let owner_key = current_owner_key(state)?;
+    let db = pool.clone();
+    let ok = owner_key.clone();
+    let dk = device_key.to_string();
+    let removed = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM devices WHERE owner_key = ?1 AND identity_key = ?1 AND device_key = ?2",
+            rusqlite::params![ok, dk],
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if removed == 0 {
+        return Err("device not found".to_string());
+    }
+
+    if let Some(devices) = state.devices.write().get_mut(&owner_key) {
+        devices.retain(|d| d.device_key != device_key);
+    }
+    {
+        let signal = state.signal_manager.lock();
+        if let Some(handle) = signal.as_ref() {
+            let _ = handle.manager.delete_session(&device_address(&owner_key, device_key));
+        }
+    }
+    publish_own_devices(state, pool).await
+}
+
+// ---------------------------------------------------------------------------
+// Device lists
+// ---------------------------------------------------------------------------
+
+/// Load the linked devices we know of (ours and our friends') into state.
+pub async fn load_devices(pool: &DbPool, state: &AppState, owner_key: &str) -> Result<(), String> {
+    let db = pool.clone();
+    let ok = owner_key.to_string();
+    let rows = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT identity_key, device_key, name, mailbox_dht_key, added_at \
+                 FROM devices WHERE owner_key = ?1 ORDER BY added_at",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(rusqlite::params![ok], |row| {
+                Ok((
+                    db::get_str(row, "identity_key"),
+                    DeviceInfo {
+                        device_key: db::get_str(row, "device_key"),
+                        name: db::get_str(row, "name"),
+                        mailbox_dht_key: db::get_str(row, "mailbox_dht_key"),
+                        added_at: db::get_i64(row, "added_at"),
+                    },
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok::<_, String>(out)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let mut devices = state.devices.write();
+    devices.clear();
+    for (identity, info) in rows {
+        devices.entry(identity).or_default().push(info);
+    }
+    Ok(())
+}
+
+/// Certificates of our own linked devices, as stored.
+async fn own_certificates(pool: &DbPool, owner_key: &str) -> Result<Vec<DeviceCertificate>, String> {
+    let db = pool.clone();
+    let ok = owner_key.to_string();
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT certificate FROM devices WHERE owner_key = ?1 AND identity_key = ?1 \
+                 ORDER BY added_at",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(rusqlite::params![ok], |row| Ok(db::get_str(row, "certificate")))
+            .map_err(|e| e.to_string())?;
+        let mut out = Vec::new();
+        for row in rows {
+            let json = row.map_err(|e| e.to_string())?;
+            match serde_json::from_str(&json) {
+                Ok(cert) => out.push(cert),
+                Err(e) => tracing::warn!(error = %e, "skipping unreadable device certificate"),
+            }
+        }
+        Ok(out)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn insert_device(
+    pool: &DbPool,
+    owner_key: &str,
+    certificate: &DeviceCertificate,
+    added_at: i64,
+) -> Result<(), String> {
+    let json = serde_json::to_string(certificate).map_err(|e| e.to_string())?;
+    let db = pool.clone();
+    let ok = owner_key.to_string();
+    let cert = certificate.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO devices (owner_key, identity_key, device_key, name, mailbox_dht_key, \
+             certificate, added_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                ok, cert.identity_key, cert.device_key, cert.device_name, cert.mailbox_dht_key, json,
+                added_at,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Replace what we know of `identity`'s linked devices with a published
+/// list, keeping only certificates the identity really signed.
+pub async fn apply_device_list(
+    state: &AppState,
+    pool: &DbPool,
+    identity: &str,
+    certificates: Vec<DeviceCertificate>,
+) -> Result<(), String> {
+    let owner_key = own_key(state).ok_or("not logged in")?;
+    let verified: Vec<DeviceCertificate> = certificates
+        .into_iter()
+        .filter(|c| c.identity_key == identity && verify_device_certificate(c).is_ok())
+        .collect();
+    let now = db::timestamp_now();
+    let infos: Vec<DeviceInfo> = verified
+        .iter()
+        .map(|c| DeviceInfo {
+            device_key: c.device_key.clone(),
+            name: c.device_name.clone(),
+            mailbox_dht_key: c.mailbox_dht_key.clone(),
+            added_at: i64::try_from(c.created_at).unwrap_or(now),
+        })
+        .collect();
+    let unchanged = state
+        .devices
+        .read()
+        .get(identity)
+        .map_or(infos.is_empty(), |known| *known == infos);
+    if unchanged {
+        return Ok(());
+    }
+
+    let db = pool.clone();
+    let ok = owner_key;
+    let id = identity.to_string();
+    let certs = verified;
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM devices WHERE owner_key = ?1 AND identity_key = ?2",
+            rusqlite::params![ok, id],
+        )
+        .map_err(|e| e.to_string())?;
+        for cert in &certs {
+            let json = serde_json::to_string(cert).map_err(|e| e.to_string())?;
+            conn.execute(
+                "INSERT OR REPLACE INTO devices (owner_key, identity_key, device_key, name, \
+                 mailbox_dht_key, certificate, added_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    ok, id, cert.device_key, cert.device_name, cert.mailbox_dht_key, json,
+                    i64::try_from(cert.created_at).unwrap_or(now),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    tracing::info!(identity = %identity, count = infos.len(), "updated linked devices");
+    let mut devices = state.devices.write();
+    if infos.is_empty() {
+        devices.remove(identity);
+    } else {
+        devices.insert(identity.to_string(), infos);
+    }
+    Ok(())
+}
+
+/// Apply the device list a friend published in profile subkey 7.
+pub async fn apply_published_devices(state: &AppState, pool: &DbPool, identity: &str, value: &[u8]) {
+    let certificates: Vec<DeviceCertificate> = if value.is_empty() {
+        Vec::new()
+    } else {
+        match serde_json::from_slice(value) {
+            Ok(certs) => certs,
+            Err(e) => {
+                tracing::debug!(friend = %identity, error = %e, "malformed device list");
+                return;
+            }
+        }
+    };
+    if let Err(e) = apply_device_list(state, pool, identity, certificates).await {
+        tracing::warn!(friend = %identity, error = %e, "failed to apply device list");
+    }
+}
+
+/// Publish our device certificates in profile subkey 7 and the account
+/// header.
+pub async fn publish_own_devices(state: &Arc<AppState>, pool: &DbPool) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
+    let certificates = own_certificates(pool, &owner_key).await?;
+    let json = serde_json::to_vec(&certificates).map_err(|e| e.to_string())?;
+    message_service::push_profile_update(state, SUBKEY_DEVICES, json).await?;
+
+    let record = super::account_service::open_account_record(state, pool).await?;
+    let mut header = record
+        .read_header()
+        .await
+        .map_err(|e| format!("read account header: {e}"))?;
+    header.devices = certificates
+        .iter()
+        .map(|c| DeviceEntry {
+            device_key: hex::decode(&c.device_key).unwrap_or_default(),
+            name: c.device_name.clone(),
+            mailbox_key: c.mailbox_dht_key.clone(),
+            added_at: c.created_at,
+            certificate: serde_json::to_vec(c).unwrap_or_default(),
+        })
+        .collect();
+    header.updated_at = db::timestamp_now().cast_unsigned();
+    record
+        .write_header(&header)
+        .await
+        .map_err(|e| format!("write account header: {e}"))?;
+    tracing::info!(count = certificates.len(), "published linked devices");
+    Ok(())
+}
+
+/// Refresh our own device list from the account header (linked devices).
+pub async fn refresh_own_devices(state: &AppState, pool: &DbPool) -> Result<(), String> {
+    let owner_key = own_key(state).ok_or("not logged in")?;
+    let record = super::account_service::open_account_record(state, pool).await?;
+    let header = record
+        .read_header()
+        .await
+        .map_err(|e| format!("read account header: {e}"))?;
+    let certificates = header
+        .devices
+        .iter()
+        .filter_map(|entry| serde_json::from_slice(&entry.certificate).ok())
+        .collect();
+    apply_device_list(state, pool, &owner_key, certificates).await
+}
+
+/// Publish a linked device's route and prekey bundle in its mailbox.
+pub async fn publish_device_mailbox(
+    state: &AppState,
+    route_blob: Option<&[u8]>,
+    prekey_bundle: Option<&[u8]>,
+) -> Result<(), String> {
+    let (secret, mailbox_key) = {
+        let device = state.local_device.read();
+        let device = device.as_ref().ok_or("not a linked device")?;
+        (device.secret, device.mailbox_dht_key.clone())
+    };
+    let rc = state
+        .node
+        .read()
+        .as_ref()
+        .map(|nh| nh.routing_context.clone())
+        .ok_or("node not initialized")?;
+
+    mailbox::open_mailbox_writable(&rc, &mailbox_key, device_keypair(&secret))
+        .await
+        .map_err(|e| format!("open device mailbox: {e}"))?;
+    if let Some(blob) = route_blob.filter(|b| !b.is_empty()) {
+        mailbox::update_mailbox_route(&rc, &mailbox_key, blob)
+            .await
+            .map_err(|e| format!("update device route: {e}"))?;
+    }
+    if let Some(bundle) = prekey_bundle {
+        mailbox::update_device_mailbox_prekey_bundle(&rc, &mailbox_key, bundle)
+            .await
+            .map_err(|e| format!("update device prekeys: {e}"))?;
+    }
+    if let Some(mgr) = state.dht_manager.write().as_mut() {
+        mgr.track_open_record(mailbox_key.clone());
+    }
+    tracing::info!(mailbox_key = %mailbox_key, "device mailbox published to DHT");
+    Ok(())
+}
+
+/// Our linked devices, for the settings window.
+pub fn own_devices(state: &AppState) -> Vec<DeviceInfo> {
+    own_key(state)
+        .and_then(|own| state.devices.read().get(&own).cloned())
+        .unwrap_or_default()
+}
+
+/// This device's key, if it is a linked device.
+pub fn local_device(state: &AppState) -> Option<String> {
+    state.local_device.read().as_ref().map(|d| d.device_key.clone())
+}
+
+/// Make `device` this session's local device.
+pub fn set_local_device(state: &AppState, device: LocalDevice) {
+    *state.local_device.write() = Some(device);
+}
@@ -0,0 +1,177 @@
+//! Public community directory (`rekindle_protocol::dht::directory`).
+//!
+//! A search follows the pointers in the `all` shard, or one game's shard
+//! when filtering by game, to the listing records, and keeps only the
+//! listings whose community metadata names their listing key — anyone can
+//! append to a shard, but only a community's host can vouch for its
+//! listing. Results are cached in
+//! `AppState::directory_listings` for [`preview`].
+
+use std::sync::Arc;
+
+use rekindle_protocol::dht::community::{
+    self, CommunityMetadata, SUBKEY_METADATA, SUBKEY_SERVER_ROUTE,
+};
+use rekindle_protocol::dht::directory::{self, DirectoryListing, Shard};
+use rekindle_protocol::dht::DHTManager;
+use serde::Serialize;
+
+use crate::db;
+use crate::state::AppState;
+
+/// Most listings one search checks against their communities' metadata.
+const MAX_SEARCH_RESULTS: usize = 50;
+
+/// A community in the directory, for the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryEntry {
+    pub community_id: String,
+    pub name: String,
+    pub description: String,
+    pub member_count: u32,
+    pub game_ids: Vec<u32>,
+    pub published_at: u64,
+    /// Whether we are already a member.
+    pub joined: bool,
+}
+
+/// A channel shown in a preview.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewChannel {
+    pub name: String,
+    pub channel_type: String,
+}
+
+/// What a community looks like before joining it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryPreview {
+    #[serde(flatten)]
+    pub entry: DirectoryEntry,
+    pub channels: Vec<PreviewChannel>,
+    /// Whether the community's server has published a route to reach it.
+    pub online: bool,
+}
+
+/// Search the directory for communities matching every word of `query`,
+/// optionally only those tagged with `game_id`.
+pub async fn search(
+    state: &Arc<AppState>,
+    query: &str,
+    game_id: Option<u32>,
+) -> Result<Vec<DirectoryEntry>, String> {
+    let rc = routing_context(state)?;
+    let shard = game_id.map_or(Shard::All, Shard::Game);
+    let candidates: Vec<DirectoryListing> = directory::read_shard(&rc, shard, now_secs())
+        .await
+        .map_err(|e| format!("failed to read the directory: {e}"))?
+        .into_iter()
+        .filter(|l| l.matches(query, game_id))
+        .take(MAX_SEARCH_RESULTS)
+        .collect();
+
+    // Check the candidates' metadata concurrently, keeping their order
+    let mut checks = tokio::task::JoinSet::new();
+    for (index, listing) in candidates.iter().enumerate() {
+        let mgr = DHTManager::new(rc.clone());
+        let listing = listing.clone();
+        checks.spawn(async move { (index, is_vouched_for(&mgr, &listing).await) });
+    }
+    let mut vouched = vec![false; candidates.len()];
+    while let Some(result) = checks.join_next().await {
+        if let Ok((index, ok)) = result {
+            vouched[index] = ok;
+        }
+    }
+    let listings: Vec<DirectoryListing> = candidates
+        .into_iter()
+        .zip(vouched)
+        .filter_map(|(listing, vouched)| vouched.then_some(listing))
+        .collect();
+
+    {
+        let mut cache = state.directory_listings.write();
+        cache.clear();
+        cache.extend(listings.iter().map(|l| (l.community_id.clone(), l.clone())));
+    }
+    Ok(listings.iter().map(|l| entry(state, l)).collect())
+}
+
+/// Preview a community from the last search: its listing, channels and
+/// whether its server is reachable.
+pub async fn preview(state: &Arc<AppState>, community_id: &str) -> Result<DirectoryPreview, String> {
+    let listing = state
+        .directory_listings
+        .read()
+        .get(community_id)
+        .cloned()
+        .ok_or_else(|| "community is not in the directory".to_string())?;
+    let mgr = DHTManager::new(routing_context(state)?);
+
+    // The listing may have been withdrawn since the search
+    if !is_vouched_for(&mgr, &listing).await {
+        state.directory_listings.write().remove(community_id);
+        return Err("community is no longer listed".to_string());
+    }
+
+    let channels = community::read_channels(&mgr, community_id)
+        .await
+        .map_err(|e| format!("failed to read channels: {e}"))?
+        .into_iter()
+        .map(|ch| PreviewChannel {
+            name: ch.name,
+            channel_type: ch.channel_type,
+        })
+        .collect();
+    let online = mgr
+        .get_value(community_id, SUBKEY_SERVER_ROUTE)
+        .await
+        .ok()
+        .flatten()
+        .is_some_and(|route| !route.is_empty());
+
+    Ok(DirectoryPreview {
+        entry: entry(state, &listing),
+        channels,
+        online,
+    })
+}
+
+/// Whether the community's metadata names the listing's key.
+async fn is_vouched_for(mgr: &DHTManager, listing: &DirectoryListing) -> bool {
+    if let Err(e) = mgr.open_record(&listing.community_id).await {
+        tracing::debug!(error = %e, community = %listing.community_id, "failed to open listed community");
+        return false;
+    }
+    match mgr.get_value(&listing.community_id, SUBKEY_METADATA).await {
+        Ok(Some(data)) => serde_json::from_slice::<CommunityMetadata>(&data)
+            .is_ok_and(|metadata| listing.is_vouched_for(&metadata)),
+        _ => false,
+    }
+}
+
+fn entry(state: &AppState, listing: &DirectoryListing) -> DirectoryEntry {
+    DirectoryEntry {
+        community_id: listing.community_id.clone(),
+        name: listing.name.clone(),
+        description: listing.description.clone(),
+        member_count: listing.member_count,
+        game_ids: listing.game_ids.clone(),
+        published_at: listing.published_at,
+        joined: state.communities.read().contains_key(&listing.community_id),
+    }
+}
+
+fn routing_context(state: &AppState) -> Result<veilid_core::RoutingContext, String> {
+    let node = state.node.read();
+    node.as_ref()
+        .filter(|nh| nh.is_attached)
+        .map(|nh| nh.routing_context.clone())
+        .ok_or_else(|| "not connected to the network".to_string())
+}
+
+fn now_secs() -> u64 {
+    u64::try_from(db::timestamp_now() / 1000).unwrap_or_default()
+}
@@ -0,0 +1,165 @@
+//! Disappearing messages.
+//!
+//! A DM's timer is set by either side with a `DisappearingTimer` payload and
+//! applies to both. A channel's retention is set by its community (see
+//! `CommunityRequest::SetChannelRetention`) and enforced by the server on
+//! its own copy. Either way, the `messages_expiry` trigger stamps each new
+//! message with `expires_at`, and [`purge_expired`] — run by `sync_service`
+//! — deletes the message once it passes, along with its search index entry,
+//! edits, reactions and any attachment we downloaded.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rekindle_protocol::messaging::MAX_DISAPPEARING_SECS;
+use tauri::Emitter;
+
+use crate::channels::ChatEvent;
+use crate::commands::auth::current_owner_key;
+use crate::db::{self, DbPool};
+use crate::services::message_service;
+use crate::state::{AppState, FriendshipState};
+
+/// Set the timer of our DM with `peer` and tell the peer.
+pub async fn set_timer(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    peer: &str,
+    seconds: u32,
+) -> Result<(), String> {
+    if seconds > MAX_DISAPPEARING_SECS {
+        return Err("timer is too long".to_string());
+    }
+    let accepted = state
+        .friends
+        .read()
+        .get(peer)
+        .is_some_and(|f| f.friendship_state == FriendshipState::Accepted);
+    if !accepted {
+        return Err("not a friend".to_string());
+    }
+    let owner_key = current_owner_key(state)?;
+    apply_timer(app, state, pool, peer, &owner_key, seconds).await?;
+    message_service::send_disappearing_timer(state, pool, peer, seconds).await
+}
+
+/// Apply a timer `by` set on our DM with `peer`: the peer itself, or us on
+/// another device.
+pub async fn handle_timer(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    peer: &str,
+    by: &str,
+    seconds: u32,
+) {
+    if seconds > MAX_DISAPPEARING_SECS {
+        tracing::warn!(peer = %peer, seconds, "ignoring over-long disappearing timer");
+        return;
+    }
+    if let Err(e) = apply_timer(app, state, pool, peer, by, seconds).await {
+        tracing::warn!(error = %e, peer = %peer, "failed to apply disappearing timer");
+    }
+}
+
+async fn apply_timer(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    peer: &str,
+    by: &str,
+    seconds: u32,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
+    let pool = pool.clone();
+    let pk = peer.to_string();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE friends SET disappearing_secs = NULLIF(?, 0) WHERE owner_key = ? AND public_key = ?",
+            rusqlite::params![seconds, owner_key, pk],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<_, String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if let Some(friend) = state.friends.write().get_mut(peer) {
+        friend.disappearing_secs = seconds;
+    }
+    let event = ChatEvent::DisappearingTimerChanged {
+        peer_id: peer.to_string(),
+        seconds,
+        by: by.to_string(),
+    };
+    let _ = app.emit("chat-event", &event);
+    Ok(())
+}
+
+/// Delete every message past its `expires_at`, and the files downloaded
+/// for them, then tell open windows which messages went.
+pub async fn purge_expired(app: &tauri::AppHandle, pool: &DbPool) -> Result<(), String> {
+    let pool = pool.clone();
+    let now = db::timestamp_now();
+    let (expired, files) = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        let expired = conn
+            .prepare("SELECT conversation_id, message_id FROM messages WHERE expires_at <= ?1")
+            .map_err(|e| e.to_string())?
+            .query_map(rusqlite::params![now], |row| {
+                Ok((db::get_str(row, "conversation_id"), db::get_str_opt(row, "message_id")))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if expired.is_empty() {
+            return Ok((expired, Vec::new()));
+        }
+        // Uploads point at the user's own file, which stays
+        let files = conn
+            .prepare(
+                "SELECT local_path FROM file_transfers WHERE direction = 'download' \
+                 AND message_id IN (SELECT id FROM messages WHERE expires_at <= ?1)",
+            )
+            .map_err(|e| e.to_string())?
+            .query_map(rusqlite::params![now], |row| Ok(db::get_str(row, "local_path")))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        // Triggers and cascades take the search index, edits, reactions and
+        // transfer rows with them
+        conn.execute("DELETE FROM messages WHERE expires_at <= ?1", rusqlite::params![now])
+            .map_err(|e| e.to_string())?;
+        Ok::<_, String>((expired, files))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if expired.is_empty() {
+        return Ok(());
+    }
+    for path in files {
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!(error = %e, path = %path, "failed to delete expired attachment"),
+        }
+    }
+    tracing::debug!(count = expired.len(), "purged expired messages");
+
+    let mut by_conversation: HashMap<String, Vec<String>> = HashMap::new();
+    for (conversation_id, message_id) in expired {
+        let ids = by_conversation.entry(conversation_id).or_default();
+        ids.extend(message_id);
+    }
+    for (conversation_id, message_ids) in by_conversation {
+        let event = ChatEvent::MessagesExpired {
+            conversation_id,
+            message_ids,
+        };
+        let _ = app.emit("chat-event", &event);
+    }
+    Ok(())
+}
@@ -0,0 +1,786 @@
+//! Chunked, encrypted file sharing between friends.
+//!
+//! `rekindle_protocol::transfer` does the chunking, sealing and DHT log
+//! I/O; this service drives it from the filesystem. Every transfer is a
+//! row in `file_transfers`, updated after each chunk, so an interrupted
+//! upload or download picks up where it stopped — when the user retries,
+//! or on the next sync tick once the network is back.
+//!
+//! An upload only announces its file (a `DirectMessage` carrying the
+//! attachment and its key) once the last chunk is in the DHT, so the
+//! recipient never waits on chunks that don't exist. A download writes to
+//! a `.part` file next to its destination and renames it only after the
+//! SHA-256 matches the one the sender announced.
+
+use std::io::{Read as _, Seek as _, SeekFrom, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rekindle_crypto::file_key::FileChecksum;
+use rekindle_protocol::messaging::new_message_id;
+use rekindle_protocol::transfer::{
+    FileAttachment, FileDownload, FileUpload, CHUNK_SIZE, MAX_FILE_SIZE,
+};
+use rekindle_protocol::ProtocolError;
+use tauri::{Emitter, Manager};
+use veilid_core::RoutingContext;
+
+use crate::channels::ChatEvent;
+use crate::commands::auth::current_owner_key;
+use crate::commands::chat::{AttachmentInfo, Message};
+use crate::db::{self, DbPool};
+use crate::services::message_service::{self, Delivery};
+use crate::services::receipt_service::{self, DeliveryState};
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Upload,
+    Download,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Download => "download",
+        }
+    }
+}
+
+/// Why a transfer task stopped early.
+enum TransferError {
+    /// Network trouble — leave the transfer active and resume later.
+    Retry(String),
+    /// The transfer can't succeed as it stands (file gone, bad checksum,
+    /// chunk that won't decrypt). Marked failed until the user retries.
+    Fatal(String),
+}
+
+impl From<ProtocolError> for TransferError {
+    fn from(e: ProtocolError) -> Self {
+        match e {
+            ProtocolError::CryptoError(msg) => Self::Fatal(msg),
+            other => Self::Retry(other.to_string()),
+        }
+    }
+}
+
+/// One row of `file_transfers`.
+struct Transfer {
+    id: i64,
+    message_id: i64,
+    index: u32,
+    direction: Direction,
+    peer_key: String,
+    local_path: PathBuf,
+    attachment: FileAttachment,
+    owner_keypair: Option<String>,
+    next_chunk: u32,
+    state: String,
+}
+
+impl Transfer {
+    fn info(&self, state: &str, done: u32) -> AttachmentInfo {
+        let on_disk = self.direction == Direction::Upload || state == "complete";
+        AttachmentInfo {
+            message_id: self.message_id,
+            index: self.index,
+            name: self.attachment.name.clone(),
+            mime_type: self.attachment.mime_type.clone(),
+            size: self.attachment.size,
+            state: state.to_string(),
+            done,
+            total: self.attachment.chunk_count(),
+            local_path: on_disk.then(|| self.local_path.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+const TRANSFER_COLUMNS: &str =
+    "id, message_id, attachment_index, direction, peer_key, local_path, \
+     attachment_json, dht_owner_keypair, next_chunk, state";
+
+fn transfer_from_row(row: &rusqlite::Row<'_>) -> Option<Transfer> {
+    let direction = match db::get_str(row, "direction").as_str() {
+        "upload" => Direction::Upload,
+        "download" => Direction::Download,
+        _ => return None,
+    };
+    let attachment = serde_json::from_str(&db::get_str(row, "attachment_json")).ok()?;
+    Some(Transfer {
+        id: db::get_i64(row, "id"),
+        message_id: db::get_i64(row, "message_id"),
+        index: u32::try_from(db::get_i64(row, "attachment_index")).unwrap_or(0),
+        direction,
+        peer_key: db::get_str(row, "peer_key"),
+        local_path: PathBuf::from(db::get_str(row, "local_path")),
+        attachment,
+        owner_keypair: db::get_str_opt(row, "dht_owner_keypair"),
+        next_chunk: u32::try_from(db::get_i64(row, "next_chunk")).unwrap_or(0),
+        state: db::get_str(row, "state"),
+    })
+}
+
+fn load_transfer(
+    conn: &rusqlite::Connection,
+    owner_key: &str,
+    message_id: i64,
+    index: u32,
+) -> Result<Option<Transfer>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TRANSFER_COLUMNS} FROM file_transfers \
+             WHERE owner_key = ? AND message_id = ? AND attachment_index = ?"
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![owner_key, message_id, index])
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .next()
+        .map_err(|e| e.to_string())?
+        .and_then(transfer_from_row))
+}
+
+/// Start sharing a file with `to`.
+pub async fn send_file(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    path: &str,
+) -> Result<Message, String> {
+    let owner_key = current_owner_key(state)?;
+    if !state.friends.read().contains_key(to) {
+        return Err("files can only be sent to friends".to_string());
+    }
+
+    let path = PathBuf::from(path);
+    let (size, checksum) = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || hash_file(&path))
+            .await
+            .map_err(|e| e.to_string())??
+    };
+    if size > MAX_FILE_SIZE {
+        return Err(format!(
+            "file is too large (limit {} MiB)",
+            MAX_FILE_SIZE / (1024 * 1024)
+        ));
+    }
+
+    let rc = routing_context(state)?;
+    let (upload, owner) = FileUpload::create(&rc, size)
+        .await
+        .map_err(|e| e.to_string())?;
+    let attachment = FileAttachment {
+        name: file_name(&path),
+        mime_type: mime_type(&path).to_string(),
+        size,
+        dht_key: upload.dht_key(),
+        checksum: checksum.to_vec(),
+        file_key: upload.file_key().as_bytes().to_vec(),
+    };
+    // The task reopens the log, the same way a resumed upload does
+    let _ = upload.close().await;
+
+    let timestamp = db::timestamp_now();
+    let message_json =
+        serde_json::to_string(std::slice::from_ref(&attachment)).map_err(|e| e.to_string())?;
+    let transfer_json = serde_json::to_string(&attachment).map_err(|e| e.to_string())?;
+    let pool_clone = pool.clone();
+    let ok = owner_key.clone();
+    let peer = to.to_string();
+    let local_path = path.to_string_lossy().into_owned();
+    let owner_keypair = owner.to_string();
+    let global_id = new_message_id();
+    let global_id_clone = global_id.clone();
+    let (message_id, transfer_id) = tokio::task::spawn_blocking(move || {
+        let conn = pool_clone.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, attachment_json, message_id, delivery_state) \
+             VALUES (?, ?, 'dm', ?, '', ?, 1, ?, ?, 'queued')",
+            rusqlite::params![ok, peer, ok, timestamp, message_json, global_id_clone],
+        )
+        .map_err(|e| e.to_string())?;
+        let message_id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO file_transfers (owner_key, message_id, attachment_index, direction, peer_key, local_path, attachment_json, dht_owner_keypair, updated_at) \
+             VALUES (?, ?, 0, 'upload', ?, ?, ?, ?, ?)",
+            rusqlite::params![ok, message_id, peer, local_path, transfer_json, owner_keypair, timestamp],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<_, String>((message_id, conn.last_insert_rowid()))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let transfer = Transfer {
+        id: transfer_id,
+        message_id,
+        index: 0,
+        direction: Direction::Upload,
+        peer_key: to.to_string(),
+        local_path: path,
+        attachment,
+        owner_keypair: Some(owner.to_string()),
+        next_chunk: 0,
+        state: "active".to_string(),
+    };
+    let info = transfer.info("active", 0);
+    tracing::info!(to = %to, size, chunks = info.total, "sharing file");
+    spawn_transfer(app, state, pool, transfer);
+
+    Ok(Message {
+        id: message_id,
+        sender_id: owner_key,
+        body: String::new(),
+        timestamp,
+        is_own: true,
+        attachments: vec![info],
+        message_id: Some(global_id),
+        edited_at: None,
+        reactions: Vec::new(),
+        delivery_state: Some("queued".to_string()),
+        reply_to: None,
+    })
+}
+
+/// Fetch attachment `index` of a received message into the downloads folder,
+/// resuming an earlier attempt. Also retries a failed upload of our own.
+pub async fn download_attachment(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    message_id: i64,
+    index: u32,
+) -> Result<AttachmentInfo, String> {
+    let owner_key = current_owner_key(state)?;
+    let downloads = app.path().download_dir().map_err(|e| e.to_string())?;
+
+    let pool_clone = pool.clone();
+    let transfer = tokio::task::spawn_blocking(move || {
+        let conn = pool_clone.lock().map_err(|e| e.to_string())?;
+        if let Some(mut transfer) = load_transfer(&conn, &owner_key, message_id, index)? {
+            if transfer.state == "failed" {
+                // A failed download threw away its partial file
+                if transfer.direction == Direction::Download {
+                    transfer.next_chunk = 0;
+                }
+                conn.execute(
+                    "UPDATE file_transfers SET state = 'active', next_chunk = ?, updated_at = ? WHERE id = ?",
+                    rusqlite::params![transfer.next_chunk, db::timestamp_now(), transfer.id],
+                )
+                .map_err(|e| e.to_string())?;
+                transfer.state = "active".to_string();
+            }
+            return Ok(transfer);
+        }
+
+        let (sender_key, attachment_json): (String, Option<String>) = conn
+            .query_row(
+                "SELECT sender_key, attachment_json FROM messages \
+                 WHERE owner_key = ? AND id = ? AND conversation_type = 'dm'",
+                rusqlite::params![owner_key, message_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| format!("message not found: {e}"))?;
+        let attachment = attachment_json
+            .and_then(|json| serde_json::from_str::<Vec<FileAttachment>>(&json).ok())
+            .and_then(|list| list.into_iter().nth(index as usize))
+            .ok_or("message has no such attachment")?;
+
+        let local_path = unique_path(&downloads, &file_name(Path::new(&attachment.name)));
+        conn.execute(
+            "INSERT INTO file_transfers (owner_key, message_id, attachment_index, direction, peer_key, local_path, attachment_json, updated_at) \
+             VALUES (?, ?, ?, 'download', ?, ?, ?, ?)",
+            rusqlite::params![
+                owner_key,
+                message_id,
+                index,
+                sender_key,
+                local_path.to_string_lossy(),
+                serde_json::to_string(&attachment).map_err(|e| e.to_string())?,
+                db::timestamp_now(),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<_, String>(Transfer {
+            id: conn.last_insert_rowid(),
+            message_id,
+            index,
+            direction: Direction::Download,
+            peer_key: sender_key,
+            local_path,
+            attachment,
+            owner_keypair: None,
+            next_chunk: 0,
+            state: "active".to_string(),
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let info = transfer.info(&transfer.state, transfer.next_chunk);
+    if transfer.state == "active" {
+        spawn_transfer(app, state, pool, transfer);
+    }
+    Ok(info)
+}
+
+/// Restart every unfinished transfer that has no task running.
+///
+/// Called from the sync loop, so transfers cut off by a restart or a lost
+/// connection carry on by themselves.
+pub async fn resume_transfers(app: &tauri::AppHandle, state: &Arc<AppState>, pool: &DbPool) {
+    let Ok(owner_key) = current_owner_key(state) else {
+        return;
+    };
+    if routing_context(state).is_err() {
+        return;
+    }
+    let pool_clone = pool.clone();
+    let transfers = tokio::task::spawn_blocking(move || {
+        let conn = pool_clone.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {TRANSFER_COLUMNS} FROM file_transfers WHERE owner_key = ? AND state = 'active'"
+            ))
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt
+            .query(rusqlite::params![owner_key])
+            .map_err(|e| e.to_string())?;
+        let mut transfers = Vec::new();
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            transfers.extend(transfer_from_row(row));
+        }
+        Ok::<_, String>(transfers)
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+
+    match transfers {
+        Ok(transfers) => {
+            for transfer in transfers {
+                spawn_transfer(app, state, pool, transfer);
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "failed to load unfinished file transfers"),
+    }
+}
+
+/// Attachment details for a stored message, with any transfer's progress.
+pub fn attachment_infos(
+    conn: &rusqlite::Connection,
+    owner_key: &str,
+    message_id: i64,
+    attachment_json: &str,
+) -> Vec<AttachmentInfo> {
+    let Ok(attachments) = serde_json::from_str::<Vec<FileAttachment>>(attachment_json) else {
+        return Vec::new();
+    };
+    let mut infos = remote_attachment_infos(message_id, &attachments);
+    for info in &mut infos {
+        if let Ok(Some(transfer)) = load_transfer(conn, owner_key, message_id, info.index) {
+            *info = transfer.info(&transfer.state, transfer.next_chunk);
+        }
+    }
+    infos
+}
+
+/// Attachment details for files nobody has started fetching yet.
+pub fn remote_attachment_infos(
+    message_id: i64,
+    attachments: &[FileAttachment],
+) -> Vec<AttachmentInfo> {
+    attachments
+        .iter()
+        .zip(0u32..)
+        .map(|(attachment, index)| AttachmentInfo {
+            message_id,
+            index,
+            name: attachment.name.clone(),
+            mime_type: attachment.mime_type.clone(),
+            size: attachment.size,
+            state: "available".to_string(),
+            done: 0,
+            total: attachment.chunk_count(),
+            local_path: None,
+        })
+        .collect()
+}
+
+/// Run a transfer in the background unless it is already running.
+fn spawn_transfer(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    transfer: Transfer,
+) {
+    if !state.file_transfers.lock().insert(transfer.id) {
+        return;
+    }
+    let app = app.clone();
+    let st = Arc::clone(state);
+    let pool = pool.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        let result = match transfer.direction {
+            Direction::Upload => run_upload(&app, &st, &pool, &transfer).await,
+            Direction::Download => run_download(&app, &st, &pool, &transfer).await,
+        };
+        match result {
+            Ok(()) => {
+                tracing::info!(
+                    id = transfer.id,
+                    direction = transfer.direction.as_str(),
+                    "file transfer complete"
+                );
+            }
+            Err(TransferError::Retry(e)) => {
+                tracing::debug!(id = transfer.id, error = %e, "file transfer interrupted — will resume");
+            }
+            Err(TransferError::Fatal(e)) => {
+                tracing::warn!(id = transfer.id, error = %e, "file transfer failed");
+                if transfer.direction == Direction::Download {
+                    let _ = std::fs::remove_file(part_path(&transfer.local_path));
+                }
+                set_state(&pool, transfer.id, "failed").await;
+                emit_progress(&app, &transfer, "failed", transfer.next_chunk);
+            }
+        }
+        st.file_transfers.lock().remove(&transfer.id);
+    });
+    let mut handles = state.background_handles.lock();
+    handles.retain(|h| !h.inner().is_finished());
+    handles.push(handle);
+}
+
+async fn run_upload(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    transfer: &Transfer,
+) -> Result<(), TransferError> {
+    let rc = routing_context(state).map_err(TransferError::Retry)?;
+    let owner: veilid_core::KeyPair = transfer
+        .owner_keypair
+        .as_deref()
+        .ok_or_else(|| TransferError::Fatal("upload has no log keypair".to_string()))?
+        .parse()
+        .map_err(|e| TransferError::Fatal(format!("invalid log keypair: {e}")))?;
+    let attachment = &transfer.attachment;
+    let upload = FileUpload::resume(
+        &rc,
+        &attachment.dht_key,
+        owner,
+        &attachment.file_key,
+        attachment.size,
+    )
+    .await?;
+    let result = upload_chunks(app, pool, transfer, &upload).await;
+    let _ = upload.close().await;
+    result?;
+
+    let message_id = global_message_id(pool, transfer.message_id)
+        .await
+        .map_err(TransferError::Fatal)?;
+    let delivery = message_service::send_message_with_attachments(
+        state,
+        pool,
+        &transfer.peer_key,
+        &message_id,
+        "",
+        vec![attachment.clone()],
+    )
+    .await
+    .map_err(TransferError::Retry)?;
+    if delivery == Delivery::Sent {
+        receipt_service::set_delivery_state(
+            app,
+            state,
+            pool,
+            &transfer.peer_key,
+            &[message_id],
+            DeliveryState::Sent,
+        )
+        .await;
+    }
+
+    set_state(pool, transfer.id, "complete").await;
+    let total = attachment.chunk_count();
+    emit_progress(app, transfer, "complete", total);
+    Ok(())
+}
+
+/// The global ID of the message an upload belongs to.
+async fn global_message_id(pool: &DbPool, row_id: i64) -> Result<String, String> {
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT message_id FROM messages WHERE id = ?",
+            rusqlite::params![row_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "upload's message has no global ID".to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn upload_chunks(
+    app: &tauri::AppHandle,
+    pool: &DbPool,
+    transfer: &Transfer,
+    upload: &FileUpload,
+) -> Result<(), TransferError> {
+    let start = upload.uploaded().await?;
+    for index in start..upload.chunk_count() {
+        let chunk = {
+            let path = transfer.local_path.clone();
+            let size = transfer.attachment.size;
+            tokio::task::spawn_blocking(move || read_chunk(&path, size, index))
+                .await
+                .map_err(|e| TransferError::Retry(e.to_string()))?
+                .map_err(TransferError::Fatal)?
+        };
+        upload.put_chunk(index, &chunk).await?;
+        set_next_chunk(pool, transfer.id, index + 1).await;
+        emit_progress(app, transfer, "active", index + 1);
+    }
+    Ok(())
+}
+
+async fn run_download(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    transfer: &Transfer,
+) -> Result<(), TransferError> {
+    let rc = routing_context(state).map_err(TransferError::Retry)?;
+    let download = FileDownload::open(&rc, &transfer.attachment).await?;
+    let result = download_chunks(app, pool, transfer, &download).await;
+    let _ = download.close().await;
+    result?;
+
+    let part = part_path(&transfer.local_path);
+    if transfer.attachment.size == 0 {
+        // No chunks, so nothing created the file
+        std::fs::File::create(&part).map_err(|e| TransferError::Fatal(e.to_string()))?;
+    }
+    let verified = {
+        let part = part.clone();
+        let checksum = transfer.attachment.checksum.clone();
+        tokio::task::spawn_blocking(move || {
+            hash_file(&part).map(|(_, digest)| digest.as_slice() == checksum)
+        })
+        .await
+        .map_err(|e| TransferError::Retry(e.to_string()))?
+        .map_err(TransferError::Fatal)?
+    };
+    if !verified {
+        return Err(TransferError::Fatal(
+            "SHA-256 of the downloaded file doesn't match".to_string(),
+        ));
+    }
+    std::fs::rename(&part, &transfer.local_path)
+        .map_err(|e| TransferError::Fatal(format!("move download into place: {e}")))?;
+
+    set_state(pool, transfer.id, "complete").await;
+    emit_progress(app, transfer, "complete", transfer.attachment.chunk_count());
+    Ok(())
+}
+
+async fn download_chunks(
+    app: &tauri::AppHandle,
+    pool: &DbPool,
+    transfer: &Transfer,
+    download: &FileDownload,
+) -> Result<(), TransferError> {
+    let part = part_path(&transfer.local_path);
+    for index in transfer.next_chunk..download.chunk_count() {
+        let Some(chunk) = download.get_chunk(index).await? else {
+            return Err(TransferError::Retry(format!(
+                "chunk {index} isn't in the DHT yet"
+            )));
+        };
+        {
+            let part = part.clone();
+            tokio::task::spawn_blocking(move || write_chunk(&part, index, &chunk))
+                .await
+                .map_err(|e| TransferError::Retry(e.to_string()))?
+                .map_err(TransferError::Fatal)?;
+        }
+        set_next_chunk(pool, transfer.id, index + 1).await;
+        emit_progress(app, transfer, "active", index + 1);
+    }
+    Ok(())
+}
+
+fn emit_progress(app: &tauri::AppHandle, transfer: &Transfer, state: &str, done: u32) {
+    let _ = app.emit(
+        "chat-event",
+        &ChatEvent::TransferProgress {
+            conversation_id: transfer.peer_key.clone(),
+            attachment: transfer.info(state, done),
+        },
+    );
+}
+
+async fn set_next_chunk(pool: &DbPool, id: i64, next_chunk: u32) {
+    let pool = pool.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE file_transfers SET next_chunk = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![next_chunk, db::timestamp_now(), id],
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    if let Err(e) = result {
+        tracing::warn!(id, error = %e, "failed to record file transfer progress");
+    }
+}
+
+async fn set_state(pool: &DbPool, id: i64, state: &'static str) {
+    let pool = pool.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE file_transfers SET state = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![state, db::timestamp_now(), id],
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    if let Err(e) = result {
+        tracing::warn!(id, error = %e, "failed to record file transfer state");
+    }
+}
+
+fn routing_context(state: &Arc<AppState>) -> Result<RoutingContext, String> {
+    let node = state.node.read();
+    match node.as_ref() {
+        Some(nh) if nh.is_attached => Ok(nh.routing_context.clone()),
+        _ => Err("not connected to the network".to_string()),
+    }
+}
+
+/// Size and SHA-256 of a file.
+fn hash_file(path: &Path) -> Result<(u64, [u8; 32]), String> {
+    let mut file =
+        std::fs::File::open(path).map_err(|e| format!("open {}: {e}", path.display()))?;
+    let mut checksum = FileChecksum::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut size = 0u64;
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| format!("read {}: {e}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        checksum.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((size, checksum.finalize()))
+}
+
+fn chunk_offset(index: u32) -> u64 {
+    u64::from(index) * CHUNK_SIZE as u64
+}
+
+/// Read chunk `index` of a file being uploaded, refusing if the file has
+/// changed size since it was shared (the checksum would no longer match).
+fn read_chunk(path: &Path, size: u64, index: u32) -> Result<Vec<u8>, String> {
+    let mut file =
+        std::fs::File::open(path).map_err(|e| format!("open {}: {e}", path.display()))?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    if len != size {
+        return Err(format!("{} changed since it was shared", path.display()));
+    }
+    file.seek(SeekFrom::Start(chunk_offset(index)))
+        .map_err(|e| e.to_string())?;
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    file.take(CHUNK_SIZE as u64)
+        .read_to_end(&mut chunk)
+        .map_err(|e| e.to_string())?;
+    Ok(chunk)
+}
+
+/// Write chunk `index` into a partial download.
+fn write_chunk(part: &Path, index: u32, chunk: &[u8]) -> Result<(), String> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(part)
+        .map_err(|e| format!("open {}: {e}", part.display()))?;
+    file.seek(SeekFrom::Start(chunk_offset(index)))
+        .map_err(|e| e.to_string())?;
+    file.write_all(chunk).map_err(|e| e.to_string())
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// The bare file name, so a sender can't point a download outside the
+/// downloads folder.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .filter(|n| !n.is_empty() && n != "." && n != "..")
+        .unwrap_or_else(|| "download".to_string())
+}
+
+/// `dir/name`, or `dir/name (2)` etc. if something is already there.
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() && !part_path(&candidate).exists() {
+        return candidate;
+    }
+    let path = Path::new(name);
+    let stem = path
+        .file_stem()
+        .map_or_else(|| name.to_string(), |s| s.to_string_lossy().into_owned());
+    let ext = path.extension().map(|e| e.to_string_lossy().into_owned());
+    (2..)
+        .map(|n| match &ext {
+            Some(ext) => dir.join(format!("{stem} ({n}).{ext}")),
+            None => dir.join(format!("{stem} ({n})")),
+        })
+        .find(|p| !p.exists() && !part_path(p).exists())
+        .unwrap_or(candidate)
+}
+
+/// MIME type from the file extension, for the recipient's preview.
+fn mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "mp3" => "audio/mpeg",
+        "ogg" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,971 @@
+//! Group conversations: two to ten friends talking without a community server.
+//!
+//! There is no server and no group key. Every message goes to each member on
+//! its own, over our pairwise Signal session with them, as a `GroupMessage`
+//! naming the group. The creator invites the friends it picked with a
+//! `GroupInvite`; from then on any member can add one of their own friends
+//! (`GroupMemberAdded` to the others, a `GroupInvite` to the newcomer) or
+//! leave (`GroupMemberLeft`). Members need not be friends with each other:
+//! the roster carries everyone's profile and mailbox keys, so a member's
+//! route and prekeys are found the same way as a friend's.
+//!
+//! Whoever changes the roster also rewrites it in the group's shared DHT
+//! record, whose owner keypair and encryption key travel in the invite. A
+//! member that missed a change picks up the newer roster from there when it
+//! opens the group. Messages are stored in `messages` with
+//! `conversation_type = 'group'` and the group ID as the conversation.
+
+use std::sync::Arc;
+
+use rand::RngCore as _;
+use rekindle_crypto::DhtRecordKey;
+use rekindle_protocol::capnp_codec::group::{GroupHeader, GroupMemberEntry};
+use rekindle_protocol::dht::group::GroupRecord;
+use rekindle_protocol::messaging::envelope::{
+    GroupInvite, GroupMember, MessagePayload, MAX_GROUP_MEMBERS,
+};
+use rekindle_protocol::messaging::new_message_id;
+use rusqlite::OptionalExtension as _;
+use tauri::Emitter;
+
+use crate::channels::ChatEvent;
+use crate::commands::auth::current_owner_key;
+use crate::db::{self, DbPool};
+use crate::services::message_service;
+use crate::state::{AppState, FriendshipState, GroupChatState, GroupMemberState};
+
+/// Longest group name accepted, in characters.
+const MAX_GROUP_NAME_LEN: usize = 64;
+
+/// Where a group's shared DHT record is and how to write it.
+struct RecordKeys {
+    record_key: String,
+    /// Owner keypair string of the record.
+    keypair: String,
+    secret: [u8; 32],
+}
+
+// ---------------------------------------------------------------------------
+// Lookups
+// ---------------------------------------------------------------------------
+
+/// Whether `public_key` is in group `group_id`.
+pub fn is_member(state: &AppState, group_id: &str, public_key: &str) -> bool {
+    state
+        .group_chats
+        .read()
+        .get(group_id)
+        .is_some_and(|g| g.members.iter().any(|m| m.public_key == public_key))
+}
+
+/// `public_key`'s roster entry in any of our groups.
+fn find_member(state: &AppState, public_key: &str) -> Option<GroupMemberState> {
+    state
+        .group_chats
+        .read()
+        .values()
+        .flat_map(|g| g.members.iter())
+        .find(|m| m.public_key == public_key)
+        .cloned()
+}
+
+/// Whether `public_key` shares a group with us.
+pub fn is_in_any_group(state: &AppState, public_key: &str) -> bool {
+    find_member(state, public_key).is_some()
+}
+
+/// Profile DHT key of a group member, for members we aren't friends with.
+pub fn member_profile_key(state: &AppState, public_key: &str) -> Option<String> {
+    find_member(state, public_key)?.profile_dht_key
+}
+
+/// Mailbox DHT key of a group member, for members we aren't friends with.
+pub fn member_mailbox_key(state: &AppState, public_key: &str) -> Option<String> {
+    find_member(state, public_key)?.mailbox_dht_key
+}
+
+// ---------------------------------------------------------------------------
+// Creating, joining and leaving
+// ---------------------------------------------------------------------------
+
+/// Start a group with the friends in `friend_keys` and invite them.
+pub async fn create_group(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    name: &str,
+    friend_keys: &[String],
+) -> Result<GroupChatState, String> {
+    let owner_key = current_owner_key(state)?;
+    let name = valid_name(name)?;
+
+    let mut members = vec![own_member(state, &owner_key)?];
+    for key in friend_keys {
+        if !members.iter().any(|m| &m.public_key == key) {
+            members.push(friend_member(state, key)?);
+        }
+    }
+    if members.len() < 2 {
+        return Err("pick at least one friend for the group".to_string());
+    }
+    if members.len() > MAX_GROUP_MEMBERS {
+        return Err(format!("a group can have at most {MAX_GROUP_MEMBERS} members"));
+    }
+
+    let mut group = GroupChatState {
+        id: new_group_id(),
+        name,
+        created_by: owner_key.clone(),
+        members,
+        unread_count: 0,
+        dht_record_key: None,
+    };
+    let keys = create_record(state, &group).await;
+    group.dht_record_key = keys.as_ref().map(|k| k.record_key.clone());
+
+    persist_group(pool, &owner_key, &group, keys.as_ref(), db::timestamp_now()).await?;
+    state.group_chats.write().insert(group.id.clone(), group.clone());
+    let _ = app.emit("chat-event", &ChatEvent::GroupUpdated { group: group.clone() });
+
+    let invite = MessagePayload::GroupInvite { group: invite_for(&group, keys.as_ref()) };
+    send_in_background(state, pool, others(&group, &owner_key), invite);
+    tracing::info!(group = %group.id, members = group.members.len(), "created group conversation");
+    Ok(group)
+}
+
+/// Add one of our friends to a group we are in.
+pub async fn add_member(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    group_id: &str,
+    friend_key: &str,
+) -> Result<GroupChatState, String> {
+    let owner_key = current_owner_key(state)?;
+    let before = state.group_chats.read().get(group_id).cloned().ok_or("not in this group")?;
+    if before.members.iter().any(|m| m.public_key == friend_key) {
+        return Err("already in this group".to_string());
+    }
+    if before.members.len() >= MAX_GROUP_MEMBERS {
+        return Err(format!("a group can have at most {MAX_GROUP_MEMBERS} members"));
+    }
+    let member = friend_member(state, friend_key)?;
+
+    let now = db::timestamp_now();
+    persist_member(pool, &owner_key, group_id, &member, now).await?;
+    let group = apply_roster(state, group_id, |members| members.push(member.clone()))
+        .ok_or("not in this group")?;
+    let _ = app.emit("chat-event", &ChatEvent::GroupUpdated { group: group.clone() });
+
+    let added = MessagePayload::GroupMemberAdded {
+        group_id: group_id.to_string(),
+        member: to_wire(&member),
+    };
+    send_in_background(state, pool, others(&before, &owner_key), added);
+
+    let keys = load_record_keys(pool, &owner_key, group_id).await?;
+    let invite = MessagePayload::GroupInvite { group: invite_for(&group, keys.as_ref()) };
+    send_in_background(state, pool, vec![friend_key.to_string()], invite);
+    if let Some(keys) = keys {
+        publish_roster(state, group.clone(), keys);
+    }
+    Ok(group)
+}
+
+/// Leave a group, telling the other members, and forget it.
+pub async fn leave_group(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    group_id: &str,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
+    let keys = load_record_keys(pool, &owner_key, group_id).await?;
+    let mut group = state.group_chats.write().remove(group_id).ok_or("not in this group")?;
+    group.members.retain(|m| m.public_key != owner_key);
+
+    let left = MessagePayload::GroupMemberLeft { group_id: group_id.to_string() };
+    send_in_background(state, pool, others(&group, &owner_key), left);
+    if let Some(keys) = keys {
+        publish_roster(state, group, keys);
+    }
+
+    let db = pool.clone();
+    let gid = group_id.to_string();
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM messages WHERE owner_key = ?1 AND conversation_id = ?2 AND conversation_type = 'group'",
+            rusqlite::params![owner_key, gid],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM group_chats WHERE owner_key = ?1 AND id = ?2",
+            rusqlite::params![owner_key, gid],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let _ = app.emit("chat-event", &ChatEvent::GroupRemoved { group_id: group_id.to_string() });
+    tracing::info!(group = %group_id, "left group conversation");
+    Ok(())
+}
+
+/// Send a message to every other member of a group.
+///
+/// Returns the message's global ID.
+pub async fn send_message(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    group_id: &str,
+    body: &str,
+) -> Result<String, String> {
+    let owner_key = current_owner_key(state)?;
+    let group = state.group_chats.read().get(group_id).cloned().ok_or("not in this group")?;
+    let message_id = new_message_id();
+    let timestamp = db::timestamp_now();
+
+    let db = pool.clone();
+    let (ok, gid, id, text) = (owner_key.clone(), group_id.to_string(), message_id.clone(), body.to_string());
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, message_id) \
+             VALUES (?1, ?2, 'group', ?1, ?3, ?4, 1, ?5)",
+            rusqlite::params![ok, gid, text, timestamp, id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let payload = MessagePayload::GroupMessage {
+        group_id: group_id.to_string(),
+        message_id: message_id.clone(),
+        body: body.to_string(),
+    };
+    send_in_background(state, pool, others(&group, &owner_key), payload);
+    Ok(message_id)
+}
+
+/// Adopt the roster in the group's DHT record if it is newer than ours.
+pub async fn refresh_roster(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    group_id: &str,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
+    let Some(keys) = load_record_keys(pool, &owner_key, group_id).await? else {
+        return Ok(());
+    };
+    let rc = state
+        .node
+        .read()
+        .as_ref()
+        .filter(|nh| nh.is_attached)
+        .map(|nh| nh.routing_context.clone());
+    let Some(rc) = rc else {
+        return Ok(());
+    };
+    let header = {
+        let record = open_record(&rc, &keys).await?;
+        let header = record.read_header().await;
+        let _ = record.close().await;
+        header.map_err(|e| e.to_string())?
+    };
+    let updated_at = i64::try_from(header.updated_at).unwrap_or(i64::MAX);
+    if updated_at <= roster_updated_at(pool, &owner_key, group_id).await?
+        || !header.members.iter().any(|m| hex::encode(&m.public_key) == owner_key)
+    {
+        return Ok(());
+    }
+
+    let members: Vec<GroupMemberState> = header.members.iter().map(from_entry).collect();
+    replace_members(pool, &owner_key, group_id, &members, updated_at).await?;
+    let group = apply_roster(state, group_id, |current| *current = members.clone());
+    if let Some(group) = group {
+        let _ = app.emit("chat-event", &ChatEvent::GroupUpdated { group });
+    }
+    tracing::debug!(group = %group_id, "adopted newer roster from group record");
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Incoming
+// ---------------------------------------------------------------------------
+
+/// Join a group a friend invited us to.
+pub async fn handle_invite(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    sender_hex: &str,
+    invite: GroupInvite,
+) {
+    let Ok(owner_key) = current_owner_key(state) else {
+        return;
+    };
+    if state.group_chats.read().contains_key(&invite.group_id) {
+        tracing::debug!(group = %invite.group_id, "already in invited group");
+        return;
+    }
+    let listed = |key: &str| invite.members.iter().any(|m| m.public_key == key);
+    if !listed(sender_hex)
+        || !listed(&owner_key)
+        || invite.members.len() > MAX_GROUP_MEMBERS
+        || !invite.members.iter().all(|m| is_identity_key(&m.public_key))
+    {
+        tracing::warn!(from = %sender_hex, group = %invite.group_id, "dropping malformed group invite");
+        return;
+    }
+    let Ok(name) = valid_name(&invite.name) else {
+        tracing::warn!(from = %sender_hex, "dropping group invite with an invalid name");
+        return;
+    };
+
+    let now = db::timestamp_now();
+    let keys = match (invite.record_key, invite.record_keypair, <[u8; 32]>::try_from(invite.record_secret.as_slice())) {
+        (Some(record_key), Some(keypair), Ok(secret)) => Some(RecordKeys { record_key, keypair, secret }),
+        _ => None,
+    };
+    let group = GroupChatState {
+        id: invite.group_id,
+        name,
+        created_by: invite.created_by,
+        members: invite.members.into_iter().map(|m| from_wire(m, now)).collect(),
+        unread_count: 0,
+        dht_record_key: keys.as_ref().map(|k| k.record_key.clone()),
+    };
+    if let Err(e) = persist_group(pool, &owner_key, &group, keys.as_ref(), now).await {
+        tracing::error!(error = %e, group = %group.id, "failed to store group invite");
+        return;
+    }
+    state.group_chats.write().insert(group.id.clone(), group.clone());
+    tracing::info!(from = %sender_hex, group = %group.id, "joined group conversation");
+    let _ = app.emit("chat-event", &ChatEvent::GroupUpdated { group });
+}
+
+/// Store a message a member sent to one of our groups.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_message(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    sender_hex: &str,
+    group_id: &str,
+    message_id: &str,
+    body: &str,
+    timestamp: i64,
+) {
+    if !is_member(state, group_id, sender_hex) || message_id.is_empty() {
+        tracing::debug!(from = %sender_hex, group = %group_id, "dropping group message from non-member");
+        return;
+    }
+    let Ok(owner_key) = current_owner_key(state) else {
+        return;
+    };
+    let db = pool.clone();
+    let (gid, sender, id, text) = (group_id.to_string(), sender_hex.to_string(), message_id.to_string(), body.to_string());
+    let stored = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR IGNORE INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, message_id) \
+             VALUES (?1, ?2, 'group', ?3, ?4, ?5, 0, ?6)",
+            rusqlite::params![owner_key, gid, sender, text, timestamp, id],
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    match stored {
+        Ok(0) => return,
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!(error = %e, "failed to persist group message");
+            return;
+        }
+    }
+
+    if let Some(group) = state.group_chats.write().get_mut(group_id) {
+        group.unread_count += 1;
+    }
+    let _ = app.emit(
+        "chat-event",
+        &ChatEvent::GroupMessageReceived {
+            group_id: group_id.to_string(),
+            from: sender_hex.to_string(),
+            body: body.to_string(),
+            timestamp: timestamp.cast_unsigned(),
+            message_id: message_id.to_string(),
+        },
+    );
+}
+
+/// Record that a member added someone to one of our groups.
+pub async fn handle_member_added(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    sender_hex: &str,
+    group_id: &str,
+    member: GroupMember,
+    timestamp: i64,
+) {
+    let Ok(owner_key) = current_owner_key(state) else {
+        return;
+    };
+    let size = state.group_chats.read().get(group_id).map(|g| g.members.len());
+    if !is_member(state, group_id, sender_hex)
+        || is_member(state, group_id, &member.public_key)
+        || member.public_key == owner_key
+        || !is_identity_key(&member.public_key)
+        || size.is_none_or(|n| n >= MAX_GROUP_MEMBERS)
+    {
+        tracing::debug!(from = %sender_hex, group = %group_id, "ignoring group member addition");
+        return;
+    }
+
+    let member = from_wire(member, timestamp);
+    if let Err(e) = persist_member(pool, &owner_key, group_id, &member, timestamp).await {
+        tracing::error!(error = %e, group = %group_id, "failed to store group member");
+        return;
+    }
+    if let Some(group) = apply_roster(state, group_id, |members| members.push(member.clone())) {
+        emit_membership(app, group, &member, sender_hex, true);
+    }
+}
+
+/// Record that a member left one of our groups.
+pub async fn handle_member_left(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    sender_hex: &str,
+    group_id: &str,
+    timestamp: i64,
+) {
+    let Ok(owner_key) = current_owner_key(state) else {
+        return;
+    };
+    let member = state
+        .group_chats
+        .read()
+        .get(group_id)
+        .and_then(|g| g.members.iter().find(|m| m.public_key == sender_hex).cloned());
+    let Some(member) = member else {
+        return;
+    };
+
+    let db = pool.clone();
+    let (gid, key) = (group_id.to_string(), sender_hex.to_string());
+    let removed = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM group_chat_members WHERE owner_key = ?1 AND group_id = ?2 AND public_key = ?3",
+            rusqlite::params![owner_key, gid, key],
+        )
+        .map_err(|e| e.to_string())?;
+        touch_roster(&conn, &owner_key, &gid, timestamp).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    if let Err(e) = removed {
+        tracing::error!(error = %e, group = %group_id, "failed to remove group member");
+        return;
+    }
+    if let Some(group) = apply_roster(state, group_id, |members| members.retain(|m| m.public_key != sender_hex)) {
+        emit_membership(app, group, &member, sender_hex, false);
+    }
+}
+
+fn emit_membership(
+    app: &tauri::AppHandle,
+    group: GroupChatState,
+    member: &GroupMemberState,
+    by: &str,
+    joined: bool,
+) {
+    let _ = app.emit(
+        "chat-event",
+        &ChatEvent::GroupMembershipChanged {
+            group_id: group.id.clone(),
+            public_key: member.public_key.clone(),
+            display_name: member.display_name.clone(),
+            joined,
+            by: by.to_string(),
+        },
+    );
+    let _ = app.emit("chat-event", &ChatEvent::GroupUpdated { group });
+}
+
+// ---------------------------------------------------------------------------
+// Sending
+// ---------------------------------------------------------------------------
+
+/// Everyone in `group` but `own_key`.
+fn others(group: &GroupChatState, own_key: &str) -> Vec<String> {
+    group
+        .members
+        .iter()
+        .filter(|m| m.public_key != own_key)
+        .map(|m| m.public_key.clone())
+        .collect()
+}
+
+/// Deliver `payload` to each of `recipients` in turn, in the background.
+///
+/// Each copy is Signal-encrypted on its own session and queued for retry
+/// when the member is offline.
+fn send_in_background(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    recipients: Vec<String>,
+    payload: MessagePayload,
+) {
+    if recipients.is_empty() {
+        return;
+    }
+    let state = Arc::clone(state);
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        for to in recipients {
+            if let Err(e) =
+                message_service::deliver_envelope_to_peer(&state, &pool, &to, &payload, true).await
+            {
+                tracing::warn!(to = %to, error = %e, "failed to send group payload");
+            }
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Group record
+// ---------------------------------------------------------------------------
+
+/// Create the group's DHT record, if the network is up.
+async fn create_record(state: &AppState, group: &GroupChatState) -> Option<RecordKeys> {
+    let rc = state
+        .node
+        .read()
+        .as_ref()
+        .filter(|nh| nh.is_attached)
+        .map(|nh| nh.routing_context.clone())?;
+    let key = DhtRecordKey::generate();
+    let secret = key.to_bytes();
+    match GroupRecord::create(&rc, key, &header_for(group, db::timestamp_now())).await {
+        Ok((record, keypair)) => {
+            let record_key = record.record_key();
+            let _ = record.close().await;
+            Some(RecordKeys { record_key, keypair: keypair.to_string(), secret })
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, group = %group.id, "failed to create group record — continuing without");
+            None
+        }
+    }
+}
+
+async fn open_record(rc: &veilid_core::RoutingContext, keys: &RecordKeys) -> Result<GroupRecord, String> {
+    let keypair: veilid_core::KeyPair = keys
+        .keypair
+        .parse()
+        .map_err(|e| format!("invalid group record keypair: {e}"))?;
+    GroupRecord::open(rc, &keys.record_key, keypair, DhtRecordKey::from_bytes(keys.secret))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Rewrite the roster in the group's DHT record, in the background.
+fn publish_roster(state: &Arc<AppState>, group: GroupChatState, keys: RecordKeys) {
+    let rc = state
+        .node
+        .read()
+        .as_ref()
+        .filter(|nh| nh.is_attached)
+        .map(|nh| nh.routing_context.clone());
+    let Some(rc) = rc else {
+        return;
+    };
+    tokio::spawn(async move {
+        let result = async {
+            let record = open_record(&rc, &keys).await?;
+            let written = record.write_header(&header_for(&group, db::timestamp_now())).await;
+            let _ = record.close().await;
+            written.map_err(|e| e.to_string())
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!(error = %e, group = %group.id, "failed to publish group roster");
+        }
+    });
+}
+
+fn header_for(group: &GroupChatState, now: i64) -> GroupHeader {
+    let now = now.cast_unsigned();
+    GroupHeader {
+        group_id: group.id.clone(),
+        name: group.name.clone(),
+        created_by: hex::decode(&group.created_by).unwrap_or_default(),
+        members: group.members.iter().map(to_entry).collect(),
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+fn invite_for(group: &GroupChatState, keys: Option<&RecordKeys>) -> GroupInvite {
+    GroupInvite {
+        group_id: group.id.clone(),
+        name: group.name.clone(),
+        created_by: group.created_by.clone(),
+        members: group.members.iter().map(to_wire).collect(),
+        record_key: keys.map(|k| k.record_key.clone()),
+        record_keypair: keys.map(|k| k.keypair.clone()),
+        record_secret: keys.map(|k| k.secret.to_vec()).unwrap_or_default(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Members
+// ---------------------------------------------------------------------------
+
+fn own_member(state: &AppState, owner_key: &str) -> Result<GroupMemberState, String> {
+    let display_name = state
+        .identity
+        .read()
+        .as_ref()
+        .map(|id| id.display_name.clone())
+        .ok_or("not logged in")?;
+    let (profile_dht_key, mailbox_dht_key) = state
+        .node
+        .read()
+        .as_ref()
+        .map(|nh| (nh.profile_dht_key.clone(), nh.mailbox_dht_key.clone()))
+        .unwrap_or_default();
+    Ok(GroupMemberState {
+        public_key: owner_key.to_string(),
+        display_name,
+        profile_dht_key,
+        mailbox_dht_key,
+        joined_at: db::timestamp_now(),
+    })
+}
+
+fn friend_member(state: &AppState, public_key: &str) -> Result<GroupMemberState, String> {
+    let friends = state.friends.read();
+    let friend = friends
+        .get(public_key)
+        .filter(|f| f.friendship_state == FriendshipState::Accepted)
+        .ok_or("only friends can be added to a group")?;
+    Ok(GroupMemberState {
+        public_key: friend.public_key.clone(),
+        display_name: friend.display_name.clone(),
+        profile_dht_key: friend.dht_record_key.clone(),
+        mailbox_dht_key: friend.mailbox_dht_key.clone(),
+        joined_at: db::timestamp_now(),
+    })
+}
+
+fn to_wire(member: &GroupMemberState) -> GroupMember {
+    GroupMember {
+        public_key: member.public_key.clone(),
+        display_name: member.display_name.clone(),
+        profile_dht_key: member.profile_dht_key.clone(),
+        mailbox_dht_key: member.mailbox_dht_key.clone(),
+    }
+}
+
+fn from_wire(member: GroupMember, joined_at: i64) -> GroupMemberState {
+    GroupMemberState {
+        public_key: member.public_key,
+        display_name: member.display_name,
+        profile_dht_key: member.profile_dht_key,
+        mailbox_dht_key: member.mailbox_dht_key,
+        joined_at,
+    }
+}
+
+fn to_entry(member: &GroupMemberState) -> GroupMemberEntry {
+    GroupMemberEntry {
+        public_key: hex::decode(&member.public_key).unwrap_or_default(),
+        display_name: member.display_name.clone(),
+        profile_key: member.profile_dht_key.clone().unwrap_or_default(),
+        mailbox_key: member.mailbox_dht_key.clone().unwrap_or_default(),
+        joined_at: member.joined_at.cast_unsigned(),
+    }
+}
+
+fn from_entry(entry: &GroupMemberEntry) -> GroupMemberState {
+    let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    GroupMemberState {
+        public_key: hex::encode(&entry.public_key),
+        display_name: entry.display_name.clone(),
+        profile_dht_key: non_empty(&entry.profile_key),
+        mailbox_dht_key: non_empty(&entry.mailbox_key),
+        joined_at: i64::try_from(entry.joined_at).unwrap_or(i64::MAX),
+    }
+}
+
+/// Change a group's roster in memory and return the group as it is now.
+fn apply_roster(
+    state: &AppState,
+    group_id: &str,
+    change: impl FnOnce(&mut Vec<GroupMemberState>),
+) -> Option<GroupChatState> {
+    let mut groups = state.group_chats.write();
+    let group = groups.get_mut(group_id)?;
+    change(&mut group.members);
+    Some(group.clone())
+}
+
+fn valid_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("group name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_GROUP_NAME_LEN {
+        return Err(format!("group name is longer than {MAX_GROUP_NAME_LEN} characters"));
+    }
+    Ok(name.to_string())
+}
+
+fn is_identity_key(public_key: &str) -> bool {
+    public_key.len() == 64 && public_key.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn new_group_id() -> String {
+    let mut id = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut id);
+    format!("group_{}", hex::encode(id))
+}
+
+// ---------------------------------------------------------------------------
+// Storage
+// ---------------------------------------------------------------------------
+
+/// Load our groups and their rosters from `SQLite` into `AppState`.
+pub async fn load_groups(pool: &DbPool, state: &AppState, owner_key: &str) -> Result<(), String> {
+    let db = pool.clone();
+    let ok = owner_key.to_string();
+    let groups = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT g.id, g.name, g.created_by, g.dht_record_key, \
+                 (SELECT COUNT(*) FROM messages m WHERE m.owner_key = g.owner_key \
+                  AND m.conversation_id = g.id AND m.conversation_type = 'group' AND m.is_read = 0) AS unread \
+                 FROM group_chats g WHERE g.owner_key = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut groups = stmt
+            .query_map(rusqlite::params![ok], |row| {
+                Ok(GroupChatState {
+                    id: db::get_str(row, "id"),
+                    name: db::get_str(row, "name"),
+                    created_by: db::get_str(row, "created_by"),
+                    members: Vec::new(),
+                    unread_count: u32::try_from(db::get_i64(row, "unread")).unwrap_or(u32::MAX),
+                    dht_record_key: db::get_str_opt(row, "dht_record_key"),
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        for group in &mut groups {
+            group.members = load_members(&conn, &ok, &group.id).map_err(|e| e.to_string())?;
+        }
+        Ok::<_, String>(groups)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let mut group_chats = state.group_chats.write();
+    group_chats.clear();
+    for group in groups {
+        group_chats.insert(group.id.clone(), group);
+    }
+    Ok(())
+}
+
+fn load_members(
+    conn: &rusqlite::Connection,
+    owner_key: &str,
+    group_id: &str,
+) -> rusqlite::Result<Vec<GroupMemberState>> {
+    let mut stmt = conn.prepare(
+        "SELECT public_key, display_name, profile_dht_key, mailbox_dht_key, joined_at \
+         FROM group_chat_members WHERE owner_key = ?1 AND group_id = ?2 ORDER BY joined_at, rowid",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![owner_key, group_id], |row| {
+        Ok(GroupMemberState {
+            public_key: db::get_str(row, "public_key"),
+            display_name: db::get_str(row, "display_name"),
+            profile_dht_key: db::get_str_opt(row, "profile_dht_key"),
+            mailbox_dht_key: db::get_str_opt(row, "mailbox_dht_key"),
+            joined_at: db::get_i64(row, "joined_at"),
+        })
+    })?;
+    rows.collect()
+}
+
+fn insert_member(
+    conn: &rusqlite::Connection,
+    owner_key: &str,
+    group_id: &str,
+    member: &GroupMemberState,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT OR IGNORE INTO group_chat_members \
+         (owner_key, group_id, public_key, display_name, profile_dht_key, mailbox_dht_key, joined_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            owner_key, group_id, member.public_key, member.display_name,
+            member.profile_dht_key, member.mailbox_dht_key, member.joined_at,
+        ],
+    )
+}
+
+fn touch_roster(
+    conn: &rusqlite::Connection,
+    owner_key: &str,
+    group_id: &str,
+    at: i64,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE group_chats SET roster_updated_at = MAX(roster_updated_at, ?3) WHERE owner_key = ?1 AND id = ?2",
+        rusqlite::params![owner_key, group_id, at],
+    )
+}
+
+async fn persist_group(
+    pool: &DbPool,
+    owner_key: &str,
+    group: &GroupChatState,
+    keys: Option<&RecordKeys>,
+    now: i64,
+) -> Result<(), String> {
+    let db = pool.clone();
+    let ok = owner_key.to_string();
+    let group = group.clone();
+    let keypair = keys.map(|k| k.keypair.clone());
+    let secret = keys.map(|k| hex::encode(k.secret));
+    tokio::task::spawn_blocking(move || {
+        let mut conn = db.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO group_chats (owner_key, id, name, created_by, created_at, dht_record_key, \
+             dht_owner_keypair, record_secret, roster_updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?5)",
+            rusqlite::params![ok, group.id, group.name, group.created_by, now, group.dht_record_key, keypair, secret],
+        )
+        .map_err(|e| e.to_string())?;
+        for member in &group.members {
+            insert_member(&tx, &ok, &group.id, member).map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn persist_member(
+    pool: &DbPool,
+    owner_key: &str,
+    group_id: &str,
+    member: &GroupMemberState,
+    at: i64,
+) -> Result<(), String> {
+    let db = pool.clone();
+    let (ok, gid, member) = (owner_key.to_string(), group_id.to_string(), member.clone());
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        insert_member(&conn, &ok, &gid, &member).map_err(|e| e.to_string())?;
+        touch_roster(&conn, &ok, &gid, at).map_err(|e| e.to_string())?;
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn replace_members(
+    pool: &DbPool,
+    owner_key: &str,
+    group_id: &str,
+    members: &[GroupMemberState],
+    at: i64,
+) -> Result<(), String> {
+    let db = pool.clone();
+    let (ok, gid, members) = (owner_key.to_string(), group_id.to_string(), members.to_vec());
+    tokio::task::spawn_blocking(move || {
+        let mut conn = db.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM group_chat_members WHERE owner_key = ?1 AND group_id = ?2",
+            rusqlite::params![ok, gid],
+        )
+        .map_err(|e| e.to_string())?;
+        for member in &members {
+            insert_member(&tx, &ok, &gid, member).map_err(|e| e.to_string())?;
+        }
+        touch_roster(&tx, &ok, &gid, at).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn roster_updated_at(pool: &DbPool, owner_key: &str, group_id: &str) -> Result<i64, String> {
+    let db = pool.clone();
+    let (ok, gid) = (owner_key.to_string(), group_id.to_string());
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT roster_updated_at FROM group_chats WHERE owner_key = ?1 AND id = ?2",
+            rusqlite::params![ok, gid],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn load_record_keys(
+    pool: &DbPool,
+    owner_key: &str,
+    group_id: &str,
+) -> Result<Option<RecordKeys>, String> {
+    let db = pool.clone();
+    let (ok, gid) = (owner_key.to_string(), group_id.to_string());
+    let row = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT dht_record_key, dht_owner_keypair, record_secret FROM group_chats \
+             WHERE owner_key = ?1 AND id = ?2",
+            rusqlite::params![ok, gid],
+            |row| {
+                Ok((
+                    db::get_str_opt(row, "dht_record_key"),
+                    db::get_str_opt(row, "dht_owner_keypair"),
+                    db::get_str_opt(row, "record_secret"),
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let Some((Some(record_key), Some(keypair), Some(secret))) = row else {
+        return Ok(None);
+    };
+    let secret = hex::decode(secret)
+        .ok()
+        .and_then(|b| <[u8; 32]>::try_from(b.as_slice()).ok())
+        .ok_or("corrupt group record key")?;
+    Ok(Some(RecordKeys { record_key, keypair, secret }))
+}
@@ -0,0 +1,382 @@
+//! Client side of the community MEK pipeline.
+//!
+//! Every MEK generation we hold for a community lives in a `MediaKeyRing`
+//! in `AppState::mek_cache` and is mirrored to Stronghold under
+//! `keychain::mek_key_name`, so channel history stays readable across
+//! rotations and restarts. Outgoing channel messages use the newest
+//! generation; incoming ones are decrypted with the generation they carry.
+//!
+//! New generations arrive from the community server as Signal messages on
+//! a pairwise session between our community pseudonym and the server. The
+//! session lives in memory only: after a restart we attach a fresh prekey
+//! bundle to the next `Join` / `RequestMEK` and the server starts over.
+//!
+//! In zero-knowledge communities the server never holds a MEK. Members
+//! with `MANAGE_COMMUNITY` generate each generation and publish a copy
+//! wrapped to the pseudonym of every member vouched for from the owner
+//! (`group::member_vouch`); everyone else fetches their copy with
+//! `RequestMEK` like before.
+//!
+//! Tree communities derive each generation from a `TreeKEM` group instead;
+//! see `tree_service`. The resulting MEKs land in the same keyring.
+//!
+//! Older generations can be requested by number when server history turns
+//! up messages we have no key for. Whether the server hands out generations
+//! from before we joined depends on the community's history visibility.
+
+use std::sync::Arc;
+
+use rekindle_crypto::group::media_key::{MediaEncryptionKey, MediaKeyRing};
+use rekindle_crypto::group::pseudonym::derive_community_pseudonym;
+use rekindle_crypto::group::{mek_delivery, member_vouch};
+use rekindle_crypto::keychain::{mek_key_name, tree_key_name, VAULT_COMMUNITIES};
+use rekindle_crypto::sframe::FrameKey;
+use rekindle_crypto::signal::{
+    MemoryIdentityStore, MemoryPreKeyStore, MemorySessionStore, SessionInitInfo,
+    SignalSessionManager,
+};
+use rekindle_crypto::{CryptoError, Keychain as _};
+use rekindle_protocol::dht::community::permissions;
+use rekindle_protocol::messaging::{
+    CommunityRequest, CommunityResponse, MekSessionInit, MemberVouchDto, WrappedMekDto,
+};
+
+use crate::db::DbPool;
+use crate::keystore::KeystoreHandle;
+use crate::state::AppState;
+
+/// Our half of the MEK delivery session with one community server.
+pub struct MekDeliverySession {
+    manager: SignalSessionManager,
+    next_signed_prekey_id: u32,
+}
+
+/// Prekey bundle to attach to a `Join` / `RequestMEK`, if one is needed.
+///
+/// Returns `None` when a delivery session with the server already exists.
+/// With `fresh`, any existing session is discarded first — used for `Join`,
+/// where the server may have dropped our member row (and its session).
+pub fn delivery_bundle(
+    state: &Arc<AppState>,
+    community_id: &str,
+    fresh: bool,
+) -> Result<Option<Vec<u8>>, String> {
+    let secret = state
+        .identity_secret
+        .lock()
+        .ok_or("identity not unlocked")?;
+
+    let mut sessions = state.mek_sessions.lock();
+    if fresh {
+        sessions.remove(community_id);
+    }
+    let session = sessions.entry(community_id.to_string()).or_insert_with(|| {
+        let pseudonym = derive_community_pseudonym(&secret, community_id);
+        let (identity_private, identity_public) = mek_delivery::pseudonym_signal_identity(&pseudonym);
+        MekDeliverySession {
+            manager: SignalSessionManager::new(
+                Box::new(MemoryIdentityStore::new(identity_private, identity_public, 0)),
+                Box::new(MemoryPreKeyStore::new()),
+                Box::new(MemorySessionStore::new()),
+            ),
+            next_signed_prekey_id: 1,
+        }
+    });
+
+    if session.manager.has_session(community_id).map_err(|e| e.to_string())? {
+        return Ok(None);
+    }
+
+    let prekey_id = session.next_signed_prekey_id;
+    session.next_signed_prekey_id += 1;
+    let bundle = session
+        .manager
+        .generate_prekey_bundle(prekey_id, None)
+        .map_err(|e| format!("failed to generate MEK delivery bundle: {e}"))?;
+    serde_json::to_vec(&bundle)
+        .map(Some)
+        .map_err(|e| format!("serialize prekey bundle: {e}"))
+}
+
+/// Decrypt a MEK delivered in `CommunityResponse::Joined` or `::MEK`.
+///
+/// On failure the delivery session is dropped, so the next request sends
+/// a fresh bundle and the server re-establishes it.
+pub fn open_delivery(
+    state: &Arc<AppState>,
+    community_id: &str,
+    mek_encrypted: &[u8],
+    session_init: Option<&MekSessionInit>,
+) -> Result<MediaEncryptionKey, String> {
+    let mut sessions = state.mek_sessions.lock();
+    let session = sessions
+        .get(community_id)
+        .ok_or("no MEK delivery session for community")?;
+
+    let init = session_init.map(|init| SessionInitInfo {
+        ephemeral_public_key: init.ephemeral_key.clone(),
+        signed_prekey_id: init.signed_prekey_id,
+        one_time_prekey_id: init.one_time_prekey_id,
+    });
+    let server_identity = session_init.map(|init| init.identity_key.as_slice());
+    let result = mek_delivery::open_mek(
+        &session.manager,
+        community_id,
+        server_identity.zip(init.as_ref()),
+        mek_encrypted,
+    );
+
+    result.map_err(|e| {
+        if matches!(e, CryptoError::IdentityKeyChanged(_)) {
+            tracing::warn!(community = %community_id, "community server identity changed — resetting MEK delivery session");
+        }
+        sessions.remove(community_id);
+        format!("failed to open delivered MEK: {e}")
+    })
+}
+
+/// Open a MEK a key holder wrapped to our pseudonym (zero-knowledge mode).
+pub fn open_wrapped(
+    state: &Arc<AppState>,
+    community_id: &str,
+    wrapped_key: &[u8],
+) -> Result<MediaEncryptionKey, String> {
+    let secret = state
+        .identity_secret
+        .lock()
+        .ok_or("identity not unlocked")?;
+    let pseudonym = derive_community_pseudonym(&secret, community_id);
+    mek_delivery::unwrap_mek(&pseudonym, wrapped_key)
+        .map_err(|e| format!("failed to open wrapped MEK: {e}"))
+}
+
+/// Answer a `MEKKeysNeeded` broadcast if we hold `MANAGE_COMMUNITY`.
+///
+/// A generation newer than ours is generated fresh; a generation we hold
+/// is re-wrapped for `members`. Returns the fresh MEK once the server has
+/// accepted it so the caller can store it. Losing a race to another key
+/// holder is not an error — their generation arrives via `MEKRotated`.
+///
+/// `members` comes from the server, so only those `vouches` trace back to
+/// the owner (or to us) get a copy; the rest are logged and skipped (see
+/// "Zero-Knowledge MEK Custody" in docs/security.md).
+pub async fn publish_wrapped_keys(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    community_id: &str,
+    generation: u64,
+    members: &[String],
+    vouches: &[MemberVouchDto],
+) -> Result<Option<MediaEncryptionKey>, String> {
+    let (may_publish, anchors) = {
+        let communities = state.communities.read();
+        communities.get(community_id).map_or((false, Vec::new()), |community| {
+            let my_perms = community.my_role_ids.iter().fold(0u64, |acc, role_id| {
+                community.roles.iter()
+                    .find(|r| r.id == *role_id)
+                    .map_or(acc, |r| acc | r.permissions)
+            });
+            let anchors = [&community.my_pseudonym_key, &community.owner_pseudonym]
+                .into_iter()
+                .flatten()
+                .filter_map(|key| <[u8; 32]>::try_from(hex::decode(key).ok()?).ok())
+                .collect();
+            (permissions::has_permission(my_perms, permissions::MANAGE_COMMUNITY), anchors)
+        })
+    };
+    if !may_publish {
+        return Ok(None);
+    }
+
+    let vouches: Vec<_> = vouches.iter().filter_map(MemberVouchDto::to_vouch).collect();
+    let vouched = member_vouch::vouched_members(community_id, &anchors, &vouches);
+    let (members, unvouched): (Vec<&String>, Vec<&String>) = members.iter().partition(|member| {
+        hex::decode(member)
+            .ok()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .is_some_and(|key| vouched.contains(&key))
+    });
+    if !unvouched.is_empty() {
+        tracing::warn!(
+            community = %community_id,
+            generation,
+            ?unvouched,
+            "not wrapping MEK for members nobody we trust vouched for"
+        );
+    }
+
+    let (held, current) = {
+        let mek_cache = state.mek_cache.lock();
+        let ring = mek_cache.get(community_id);
+        (
+            ring.and_then(|r| r.get(generation))
+                .map(|mek| MediaEncryptionKey::from_bytes(*mek.as_bytes(), generation)),
+            ring.and_then(MediaKeyRing::current_generation).unwrap_or(0),
+        )
+    };
+    let (mek, fresh) = match held {
+        Some(mek) => (mek, false),
+        None if generation > current => (MediaEncryptionKey::generate(generation), true),
+        // An older generation we never had — someone else has to answer
+        None => return Ok(None),
+    };
+
+    let wrapped_keys = members
+        .iter()
+        .map(|member| {
+            let pseudonym = hex::decode(member).map_err(|e| format!("invalid member key {member}: {e}"))?;
+            let wrapped_key = mek_delivery::wrap_mek_for_pseudonym(&mek, &pseudonym)
+                .map_err(|e| format!("failed to wrap MEK for {member}: {e}"))?;
+            Ok(WrappedMekDto { pseudonym_key: (*member).clone(), wrapped_key })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let response = crate::commands::community::send_community_rpc(
+        state,
+        pool,
+        community_id,
+        CommunityRequest::PublishMEK { generation, wrapped_keys },
+    )
+    .await?;
+
+    match response {
+        CommunityResponse::Ok => {
+            tracing::info!(community = %community_id, generation, members = members.len(), fresh, "published wrapped MEK");
+            Ok(fresh.then_some(mek))
+        }
+        CommunityResponse::Error { code: 409, message } => {
+            tracing::debug!(community = %community_id, generation, %message, "MEK already published by another key holder");
+            Ok(None)
+        }
+        CommunityResponse::Error { message, .. } => Err(format!("server rejected published MEK: {message}")),
+        other => Err(format!("unexpected response: {other:?}")),
+    }
+}
+
+/// Fetch one specific (usually older) MEK generation from the server.
+///
+/// Returns `Ok(None)` when the server won't or can't give it to us — the
+/// community hides history from before we joined, we are timed out, or
+/// (zero-knowledge) nobody published a copy for us.
+pub async fn fetch_generation(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    community_id: &str,
+    generation: u64,
+) -> Result<Option<MediaEncryptionKey>, String> {
+    let prekey_bundle = delivery_bundle(state, community_id, false)?;
+    let response = crate::commands::community::send_community_rpc(
+        state,
+        pool,
+        community_id,
+        CommunityRequest::RequestMEK {
+            prekey_bundle,
+            generation: Some(generation),
+        },
+    )
+    .await?;
+
+    let mek = match response {
+        CommunityResponse::MEK { mek_encrypted, session_init, .. } => {
+            open_delivery(state, community_id, &mek_encrypted, session_init.as_ref())?
+        }
+        CommunityResponse::WrappedMEK { wrapped_key, .. } => open_wrapped(state, community_id, &wrapped_key)?,
+        CommunityResponse::Error { code: 403 | 404, message } => {
+            tracing::debug!(community = %community_id, generation, %message, "MEK generation not available");
+            return Ok(None);
+        }
+        CommunityResponse::Error { code, message } => return Err(format!("server error {code}: {message}")),
+        other => return Err(format!("unexpected response: {other:?}")),
+    };
+    if mek.generation() != generation {
+        return Err(format!("asked for MEK generation {generation} but got {}", mek.generation()));
+    }
+    Ok(Some(mek))
+}
+
+/// Add a MEK generation to the community's keyring and persist the keyring.
+///
+/// Also hands the generation's voice frame key to the voice session if we
+/// are in one of the community's voice channels.
+///
+/// Returns the keyring's current (highest) generation, which is not
+/// necessarily `mek`'s if an older generation arrived late.
+pub fn store_mek(
+    state: &Arc<AppState>,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+    mek: MediaEncryptionKey,
+) -> u64 {
+    let generation = mek.generation();
+    let frame_key = FrameKey::from_mek(&mek);
+    let current = {
+        let mut mek_cache = state.mek_cache.lock();
+        let ring = mek_cache.entry(community_id.to_string()).or_default();
+        ring.insert(mek);
+        ring.current_generation().unwrap_or(generation)
+    };
+    persist_keyring(state, keystore_handle, community_id);
+    crate::commands::voice::channel_key_stored(state, community_id, frame_key, generation == current);
+    tracing::debug!(community = %community_id, generation, current, "MEK stored in keyring");
+    current
+}
+
+/// Write the community's cached keyring to Stronghold.
+pub fn persist_keyring(state: &Arc<AppState>, keystore_handle: &KeystoreHandle, community_id: &str) {
+    let payload = {
+        let mek_cache = state.mek_cache.lock();
+        match mek_cache.get(community_id) {
+            Some(ring) if !ring.is_empty() => ring.to_bytes(),
+            _ => return,
+        }
+    };
+
+    let ks = keystore_handle.lock();
+    if let Some(ref keystore) = *ks {
+        let key_name = mek_key_name(community_id);
+        if let Err(e) = keystore.store_key(VAULT_COMMUNITIES, &key_name, &payload) {
+            tracing::warn!(error = %e, community = %community_id, "failed to persist MEK keyring to Stronghold");
+        } else if let Err(e) = keystore.save() {
+            tracing::warn!(error = %e, "failed to save Stronghold snapshot after MEK persist");
+        } else {
+            tracing::debug!(community = %community_id, "MEK keyring persisted to Stronghold");
+        }
+    }
+}
+
+/// Load a community's keyring from Stronghold.
+///
+/// Accepts the older single-generation entries as well.
+pub fn load_keyring(keystore_handle: &KeystoreHandle, community_id: &str) -> Result<Option<MediaKeyRing>, String> {
+    let ks = keystore_handle.lock();
+    let Some(ref keystore) = *ks else {
+        return Ok(None);
+    };
+    let Some(bytes) = keystore
+        .load_key(VAULT_COMMUNITIES, &mek_key_name(community_id))
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(None);
+    };
+    MediaKeyRing::from_bytes(&bytes)
+        .map(|ring| (!ring.is_empty()).then_some(ring))
+        .map_err(|e| e.to_string())
+}
+
+/// Drop every MEK generation, the delivery session and any `TreeKEM` group
+/// state for a community.
+pub fn forget_community(state: &Arc<AppState>, keystore_handle: &KeystoreHandle, community_id: &str) {
+    state.mek_cache.lock().remove(community_id);
+    state.mek_sessions.lock().remove(community_id);
+    state.tree_groups.lock().remove(community_id);
+
+    let ks = keystore_handle.lock();
+    if let Some(ref keystore) = *ks {
+        if let Err(e) = keystore.delete_key(VAULT_COMMUNITIES, &mek_key_name(community_id)) {
+            tracing::warn!(error = %e, community = %community_id, "failed to remove MEK from Stronghold");
+        }
+        if let Err(e) = keystore.delete_key(VAULT_COMMUNITIES, &tree_key_name(community_id)) {
+            tracing::warn!(error = %e, community = %community_id, "failed to remove TreeKEM state from Stronghold");
+        }
+    }
+}
@@ -0,0 +1,474 @@
+//! Edits, deletions and reactions on stored messages.
+//!
+//! Messages are addressed by the global `message_id` their sender chose,
+//! within one conversation (a peer's key or a channel ID). DM changes are
+//! applied locally and then sent to the peer, who only lets a message's
+//! author edit or delete it. Channel changes go to the community server
+//! first — it checks authorship and permissions and broadcasts them — and
+//! are applied locally once it accepts. Changes from others land in
+//! [`apply_remote`].
+
+use std::sync::Arc;
+
+use rekindle_protocol::messaging::{is_valid_reaction, CommunityRequest, CommunityResponse};
+use tauri::Emitter;
+
+use crate::channels::ChatEvent;
+use crate::commands::auth::current_owner_key;
+use crate::commands::chat::ReactionInfo;
+use crate::commands::community::{encrypt_with_current_mek, send_community_rpc};
+use crate::db::{self, DbPool};
+use crate::services::message_service;
+use crate::state::AppState;
+
+/// A change to a stored message.
+#[derive(Clone, Copy)]
+pub enum Change<'a> {
+    Edit {
+        body: &'a str,
+        edited_at: i64,
+    },
+    Delete,
+    React {
+        reactor: &'a str,
+        emoji: &'a str,
+        add: bool,
+    },
+}
+
+/// Where a conversation's changes are sent.
+enum Route {
+    /// A DM with this peer.
+    Direct(String),
+    /// A channel in this community.
+    Channel(String),
+}
+
+struct StoredMessage {
+    row_id: i64,
+    sender_key: String,
+}
+
+/// Replace the body of one of our messages.
+pub async fn edit_message(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    conversation_id: &str,
+    message_id: &str,
+    body: &str,
+) -> Result<(), String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err("message cannot be empty".to_string());
+    }
+    let owner_key = current_owner_key(state)?;
+    let me = my_key(state, &owner_key, conversation_id);
+    let stored = lookup(pool, &owner_key, conversation_id, message_id)
+        .await?
+        .ok_or("message not found")?;
+    if stored.sender_key != me {
+        return Err("you can only edit your own messages".to_string());
+    }
+
+    let edit = Change::Edit {
+        body,
+        edited_at: db::timestamp_now(),
+    };
+    match route(state, conversation_id) {
+        Route::Direct(peer) => {
+            apply(app, state, pool, conversation_id, message_id, None, edit).await?;
+            message_service::send_edit(state, pool, &peer, message_id, body).await
+        }
+        Route::Channel(community_id) => {
+            let (ciphertext, mek_generation) =
+                encrypt_with_current_mek(state, &community_id, body)?;
+            let request = CommunityRequest::EditMessage {
+                channel_id: conversation_id.to_string(),
+                message_id: message_id.to_string(),
+                ciphertext,
+                mek_generation,
+            };
+            server_accepts(state, pool, &community_id, request, "edit").await?;
+            apply(app, state, pool, conversation_id, message_id, None, edit).await
+        }
+    }
+}
+
+/// Delete a message for everyone in the conversation.
+///
+/// In DMs only our own messages can be deleted; in channels the server
+/// decides (authors, or members with `MANAGE_MESSAGES`).
+pub async fn delete_message(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    conversation_id: &str,
+    message_id: &str,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
+    let me = my_key(state, &owner_key, conversation_id);
+    let stored = lookup(pool, &owner_key, conversation_id, message_id)
+        .await?
+        .ok_or("message not found")?;
+
+    match route(state, conversation_id) {
+        Route::Direct(peer) => {
+            if stored.sender_key != me {
+                return Err("you can only delete your own messages".to_string());
+            }
+            apply(
+                app,
+                state,
+                pool,
+                conversation_id,
+                message_id,
+                None,
+                Change::Delete,
+            )
+            .await?;
+            message_service::send_delete(state, pool, &peer, message_id).await
+        }
+        Route::Channel(community_id) => {
+            let request = CommunityRequest::DeleteMessage {
+                channel_id: conversation_id.to_string(),
+                message_id: message_id.to_string(),
+            };
+            server_accepts(state, pool, &community_id, request, "deletion").await?;
+            apply(
+                app,
+                state,
+                pool,
+                conversation_id,
+                message_id,
+                None,
+                Change::Delete,
+            )
+            .await
+        }
+    }
+}
+
+/// Add (`add`) or take back our `emoji` reaction to a message.
+pub async fn set_reaction(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    conversation_id: &str,
+    message_id: &str,
+    emoji: &str,
+    add: bool,
+) -> Result<(), String> {
+    if !is_valid_reaction(emoji) {
+        return Err("invalid reaction".to_string());
+    }
+    let owner_key = current_owner_key(state)?;
+    let me = my_key(state, &owner_key, conversation_id);
+    lookup(pool, &owner_key, conversation_id, message_id)
+        .await?
+        .ok_or("message not found")?;
+
+    let react = Change::React {
+        reactor: &me,
+        emoji,
+        add,
+    };
+    match route(state, conversation_id) {
+        Route::Direct(peer) => {
+            apply(app, state, pool, conversation_id, message_id, None, react).await?;
+            message_service::send_reaction(state, pool, &peer, message_id, emoji, add).await
+        }
+        Route::Channel(community_id) => {
+            let (channel_id, message_id_owned, emoji_owned) = (
+                conversation_id.to_string(),
+                message_id.to_string(),
+                emoji.to_string(),
+            );
+            let request = if add {
+                CommunityRequest::AddReaction {
+                    channel_id,
+                    message_id: message_id_owned,
+                    emoji: emoji_owned,
+                }
+            } else {
+                CommunityRequest::RemoveReaction {
+                    channel_id,
+                    message_id: message_id_owned,
+                    emoji: emoji_owned,
+                }
+            };
+            server_accepts(state, pool, &community_id, request, "reaction").await?;
+            apply(app, state, pool, conversation_id, message_id, None, react).await
+        }
+    }
+}
+
+/// Apply a change someone else made.
+///
+/// `author` is who the change came from when only the message's author may
+/// make it (a peer editing or deleting in a DM); `None` when the community
+/// server has already checked. Messages we never stored are ignored.
+pub async fn apply_remote(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    conversation_id: &str,
+    message_id: &str,
+    author: Option<&str>,
+    change: Change<'_>,
+) {
+    if let Change::React { emoji, .. } = change {
+        if !is_valid_reaction(emoji) {
+            tracing::debug!(conversation = %conversation_id, "dropping invalid reaction");
+            return;
+        }
+    }
+    if let Err(e) = apply(
+        app,
+        state,
+        pool,
+        conversation_id,
+        message_id,
+        author,
+        change,
+    )
+    .await
+    {
+        tracing::warn!(conversation = %conversation_id, message = %message_id, error = %e, "failed to apply message change");
+    }
+}
+
+/// Reaction tallies on a stored message, in the order each emoji was first used.
+pub fn reactions(conn: &rusqlite::Connection, row_id: i64, me: &str) -> Vec<ReactionInfo> {
+    let load = || -> rusqlite::Result<Vec<ReactionInfo>> {
+        let mut stmt = conn.prepare(
+            "SELECT emoji, COUNT(*) AS count, MAX(reactor_key = ?) AS mine FROM message_reactions \
+             WHERE message_row_id = ? GROUP BY emoji ORDER BY MIN(created_at)",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![me, row_id], |row| {
+            Ok(ReactionInfo {
+                emoji: db::get_str(row, "emoji"),
+                count: u32::try_from(db::get_i64(row, "count")).unwrap_or(0),
+                mine: db::get_i64(row, "mine") != 0,
+            })
+        })?;
+        let mut reactions = Vec::new();
+        for row in rows {
+            reactions.push(row?);
+        }
+        Ok(reactions)
+    };
+    load().unwrap_or_else(|e| {
+        tracing::warn!(row_id, error = %e, "failed to load reactions");
+        Vec::new()
+    })
+}
+
+/// Store a change and tell the frontend. Does nothing if we don't have the
+/// message; fails if `author` is given and didn't write it.
+async fn apply(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    conversation_id: &str,
+    message_id: &str,
+    author: Option<&str>,
+    change: Change<'_>,
+) -> Result<(), String> {
+    let owner_key = current_owner_key(state)?;
+    let me = my_key(state, &owner_key, conversation_id);
+    let pool = pool.clone();
+    let conversation = conversation_id.to_string();
+    let id = message_id.to_string();
+    let author = author.map(str::to_string);
+    let owned = OwnedChange::from(&change);
+    let event = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        let Some(stored) = find_message(&conn, &owner_key, &conversation, &id)? else {
+            tracing::debug!(conversation = %conversation, message = %id, "change for a message we don't have");
+            return Ok(None);
+        };
+        if author.as_ref().is_some_and(|a| *a != stored.sender_key) {
+            return Err("only the author can change this message".to_string());
+        }
+        let event = match owned {
+            OwnedChange::Edit { body, edited_at } => {
+                conn.execute(
+                    "INSERT INTO message_edits (message_row_id, body, replaced_at) \
+                     SELECT id, body, ? FROM messages WHERE id = ?",
+                    rusqlite::params![edited_at, stored.row_id],
+                )
+                .map_err(|e| e.to_string())?;
+                conn.execute(
+                    "UPDATE messages SET body = ?, edited_at = ? WHERE id = ?",
+                    rusqlite::params![body, edited_at, stored.row_id],
+                )
+                .map_err(|e| e.to_string())?;
+                ChatEvent::MessageEdited {
+                    conversation_id: conversation,
+                    message_id: id,
+                    body,
+                    edited_at: edited_at.cast_unsigned(),
+                }
+            }
+            OwnedChange::Delete => {
+                conn.execute(
+                    "DELETE FROM messages WHERE id = ?",
+                    rusqlite::params![stored.row_id],
+                )
+                .map_err(|e| e.to_string())?;
+                ChatEvent::MessageDeleted {
+                    conversation_id: conversation,
+                    message_id: id,
+                }
+            }
+            OwnedChange::React {
+                reactor,
+                emoji,
+                add,
+            } => {
+                let stored_reaction = if add {
+                    conn.execute(
+                        "INSERT OR IGNORE INTO message_reactions (message_row_id, reactor_key, emoji, created_at) \
+                         VALUES (?, ?, ?, ?)",
+                        rusqlite::params![stored.row_id, reactor, emoji, db::timestamp_now()],
+                    )
+                } else {
+                    conn.execute(
+                        "DELETE FROM message_reactions WHERE message_row_id = ? AND reactor_key = ? AND emoji = ?",
+                        rusqlite::params![stored.row_id, reactor, emoji],
+                    )
+                };
+                stored_reaction.map_err(|e| e.to_string())?;
+                ChatEvent::ReactionsChanged {
+                    conversation_id: conversation,
+                    message_id: id,
+                    reactions: reactions(&conn, stored.row_id, &me),
+                }
+            }
+        };
+        Ok::<_, String>(Some(event))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if let Some(event) = event {
+        let _ = app.emit("chat-event", &event);
+    }
+    Ok(())
+}
+
+/// `Change` with owned data, to move into a blocking task.
+enum OwnedChange {
+    Edit {
+        body: String,
+        edited_at: i64,
+    },
+    Delete,
+    React {
+        reactor: String,
+        emoji: String,
+        add: bool,
+    },
+}
+
+impl From<&Change<'_>> for OwnedChange {
+    fn from(change: &Change<'_>) -> Self {
+        match *change {
+            Change::Edit { body, edited_at } => Self::Edit {
+                body: body.to_string(),
+                edited_at,
+            },
+            Change::Delete => Self::Delete,
+            Change::React {
+                reactor,
+                emoji,
+                add,
+            } => Self::React {
+                reactor: reactor.to_string(),
+                emoji: emoji.to_string(),
+                add,
+            },
+        }
+    }
+}
+
+fn find_message(
+    conn: &rusqlite::Connection,
+    owner_key: &str,
+    conversation_id: &str,
+    message_id: &str,
+) -> Result<Option<StoredMessage>, String> {
+    match conn.query_row(
+        "SELECT id, sender_key FROM messages \
+         WHERE owner_key = ? AND conversation_id = ? AND message_id = ?",
+        rusqlite::params![owner_key, conversation_id, message_id],
+        |row| {
+            Ok(StoredMessage {
+                row_id: db::get_i64(row, "id"),
+                sender_key: db::get_str(row, "sender_key"),
+            })
+        },
+    ) {
+        Ok(stored) => Ok(Some(stored)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+async fn lookup(
+    pool: &DbPool,
+    owner_key: &str,
+    conversation_id: &str,
+    message_id: &str,
+) -> Result<Option<StoredMessage>, String> {
+    let pool = pool.clone();
+    let (owner_key, conversation_id, message_id) = (
+        owner_key.to_string(),
+        conversation_id.to_string(),
+        message_id.to_string(),
+    );
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        find_message(&conn, &owner_key, &conversation_id, &message_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn route(state: &Arc<AppState>, conversation_id: &str) -> Route {
+    let communities = state.communities.read();
+    communities
+        .values()
+        .find(|c| c.has_conversation(conversation_id))
+        .map_or_else(
+            || Route::Direct(conversation_id.to_string()),
+            |c| Route::Channel(c.id.clone()),
+        )
+}
+
+/// The key we appear under in a conversation: our pseudonym in a
+/// community's channels, our identity key everywhere else.
+fn my_key(state: &Arc<AppState>, owner_key: &str, conversation_id: &str) -> String {
+    let communities = state.communities.read();
+    communities
+        .values()
+        .find(|c| c.has_conversation(conversation_id))
+        .and_then(|c| c.my_pseudonym_key.clone())
+        .unwrap_or_else(|| owner_key.to_string())
+}
+
+async fn server_accepts(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    community_id: &str,
+    request: CommunityRequest,
+    what: &str,
+) -> Result<(), String> {
+    match send_community_rpc(state, pool, community_id, request).await? {
+        CommunityResponse::Error { message, .. } => {
+            Err(format!("server rejected message {what}: {message}"))
+        }
+        _ => Ok(()),
+    }
+}
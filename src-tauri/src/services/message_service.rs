@@ -4,11 +4,17 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use rand::RngCore as _;
 use rekindle_protocol::messaging::envelope::MessagePayload;
 use rekindle_protocol::messaging::receiver::{parse_payload, process_incoming};
-use rekindle_protocol::messaging::sender::{build_envelope_from_secret, send_envelope};
+use rekindle_protocol::messaging::sender::{build_device_envelope_from_secret, send_envelope};
+use rekindle_protocol::transfer::FileAttachment;
 use tauri::Emitter;
 
 use crate::channels::ChatEvent;
 use crate::db::DbPool;
+use crate::services::device_service;
+use crate::services::disappearing_service;
+use crate::services::group_service;
+use crate::services::message_edit_service::{self, Change};
+use crate::services::receipt_service::{self, ReceiptKind};
 use crate::state::AppState;
 
 /// Handle an incoming message from the Veilid network.
@@ -32,7 +38,14 @@ pub async fn handle_incoming_message(
     };
 
     let sender_hex = hex::encode(&envelope.sender_key);
-    tracing::debug!(from = %sender_hex, payload_len = envelope.payload.len(), "processing verified envelope");
+    // Signal sessions are per device: a linked device signs as its identity
+    // and names itself in `sender_device`.
+    let session_address = if envelope.sender_device.is_empty() {
+        sender_hex.clone()
+    } else {
+        device_service::device_address(&sender_hex, &hex::encode(&envelope.sender_device))
+    };
+    tracing::debug!(from = %session_address, payload_len = envelope.payload.len(), "processing verified envelope");
 
     // Block list filtering
     if is_blocked(state, pool, &sender_hex).await {
@@ -43,18 +56,23 @@ pub async fn handle_incoming_message(
     // Step 2: Decrypt payload — try plaintext JSON first, then Signal decrypt.
     // This avoids mangling payloads that were sent unencrypted (friend requests,
     // accepts, messages sent before a session was established).
-    let payload_bytes = if serde_json::from_slice::<serde_json::Value>(&envelope.payload).is_ok() {
+    let mut encrypted = serde_json::from_slice::<serde_json::Value>(&envelope.payload).is_err();
+    let payload_bytes = if !encrypted {
         // Already valid JSON — use as-is (plaintext or unencrypted message)
         envelope.payload.clone()
     } else {
         // Not valid JSON — must be Signal-encrypted ciphertext
         let signal = state.signal_manager.lock();
         if let Some(handle) = signal.as_ref() {
-            match handle.manager.decrypt(&sender_hex, &envelope.payload) {
-                Ok(pt) => pt,
+            match handle.manager.decrypt(&session_address, &envelope.payload) {
+                Ok(pt) => {
+                    // The peer answered on the session we started
+                    state.pending_session_inits.lock().remove(&session_address);
+                    pt
+                }
                 Err(e) => {
                     tracing::warn!(
-                        error = %e, from = %sender_hex,
+                        error = %e, from = %session_address,
                         payload_len = envelope.payload.len(),
                         "encrypted message could not be decrypted"
                     );
@@ -85,7 +103,7 @@ pub async fn handle_incoming_message(
     };
 
     // Step 3: Deserialize the payload into a structured MessagePayload
-    let payload = match parse_payload(&payload_bytes) {
+    let mut payload = match parse_payload(&payload_bytes) {
         Ok(p) => p,
         Err(e) => {
             tracing::error!(error = %e, from = %sender_hex, "failed to parse message payload");
@@ -93,17 +111,71 @@ pub async fn handle_incoming_message(
         }
     };
 
+    // A device that started a session from our published prekeys wraps its
+    // first message in a SessionInit.
+    if let MessagePayload::SessionInit { ephemeral_key, signed_prekey_id, one_time_prekey_id, ciphertext } = &payload {
+        let accepted = device_service::accept_session_init(
+            state, &session_address, &envelope.sender_key, ephemeral_key,
+            *signed_prekey_id, *one_time_prekey_id, ciphertext,
+        );
+        if let Err(rekindle_crypto::CryptoError::OneTimePreKeyConsumed(prekey_id)) = accepted {
+            tracing::info!(from = %session_address, prekey_id, "SessionInit used a spent one-time prekey — asking for a retry");
+            let rejection = MessagePayload::SessionInitRejected { one_time_prekey_id: prekey_id };
+            if let Err(e) = deliver_to_device(state, pool, &session_address, &rejection, false).await {
+                tracing::warn!(error = %e, to = %session_address, "failed to reject SessionInit");
+            }
+            return;
+        }
+        let inner = accepted
+            .map_err(|e| e.to_string())
+            .and_then(|pt| parse_payload(&pt).map_err(|e| e.to_string()));
+        match inner {
+            Ok(inner) => {
+                payload = inner;
+                encrypted = true;
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, from = %session_address, "failed to accept SessionInit");
+                return;
+            }
+        }
+    }
+    let from_self = state
+        .identity
+        .read()
+        .as_ref()
+        .is_some_and(|id| id.public_key == sender_hex);
+
+    // Members of our group conversations may talk to us in those groups
+    // without being our friends.
+    let from_group_member = match &payload {
+        MessagePayload::GroupMessage { group_id, .. }
+        | MessagePayload::GroupMemberAdded { group_id, .. }
+        | MessagePayload::GroupMemberLeft { group_id } => {
+            group_service::is_member(state, group_id, &sender_hex)
+        }
+        _ => false,
+    };
+
     // Non-friend filtering: only protocol-level messages allowed from non-friends.
     // Unfriended/FriendReject must pass so delayed deliveries still work even if
     // the sender was already removed from our friends list by some other path.
-    if !matches!(
+    // A SessionInitRejected only acts on a session init we sent that address.
+    if !from_group_member && !matches!(
         payload,
         MessagePayload::FriendRequest { .. }
             | MessagePayload::FriendRequestReceived
             | MessagePayload::Unfriended
             | MessagePayload::UnfriendedAck
             | MessagePayload::FriendReject
-    ) && !state.friends.read().contains_key(&sender_hex)
+            | MessagePayload::DeviceLinkGrant { .. }
+            | MessagePayload::SessionInitRejected { .. }
+    ) && !(from_self
+        && matches!(
+            payload,
+            MessagePayload::SentTranscript { .. } | MessagePayload::ContactSync { .. }
+        ))
+        && !state.friends.read().contains_key(&sender_hex)
     {
         tracing::debug!(from = %sender_hex, "dropping message from non-friend");
         return;
@@ -112,8 +184,35 @@ pub async fn handle_incoming_message(
     // Step 4: Dispatch by payload type
     let ts: i64 = envelope.timestamp.try_into().unwrap_or(i64::MAX);
     match payload {
-        MessagePayload::DirectMessage { body, .. } => {
-            handle_direct_message(app_handle, state, pool, &sender_hex, &body, ts).await;
+        MessagePayload::DirectMessage { message_id, body, attachments, .. } => {
+            handle_direct_message(app_handle, state, pool, &sender_hex, &message_id, &body, &attachments, ts).await;
+        }
+        MessagePayload::EditMessage { message_id, body } => {
+            let edit = Change::Edit { body: &body, edited_at: ts };
+            message_edit_service::apply_remote(app_handle, state, pool, &sender_hex, &message_id, Some(&sender_hex), edit)
+                .await;
+        }
+        MessagePayload::DeleteMessage { message_id } => {
+            message_edit_service::apply_remote(
+                app_handle, state, pool, &sender_hex, &message_id, Some(&sender_hex), Change::Delete,
+            )
+            .await;
+        }
+        MessagePayload::AddReaction { message_id, emoji } => {
+            let react = Change::React { reactor: &sender_hex, emoji: &emoji, add: true };
+            message_edit_service::apply_remote(app_handle, state, pool, &sender_hex, &message_id, None, react).await;
+        }
+        MessagePayload::RemoveReaction { message_id, emoji } => {
+            let react = Change::React { reactor: &sender_hex, emoji: &emoji, add: false };
+            message_edit_service::apply_remote(app_handle, state, pool, &sender_hex, &message_id, None, react).await;
+        }
+        MessagePayload::DeliveryReceipt { message_ids } => {
+            let kind = ReceiptKind::Delivered;
+            receipt_service::handle_receipt(app_handle, state, pool, &sender_hex, &message_ids, kind).await;
+        }
+        MessagePayload::ReadReceipt { message_ids } => {
+            let kind = ReceiptKind::Read;
+            receipt_service::handle_receipt(app_handle, state, pool, &sender_hex, &message_ids, kind).await;
         }
         MessagePayload::ChannelMessage { channel_id, body, .. } => {
             handle_channel_message(app_handle, state, pool, &sender_hex, &channel_id, &body, ts).await;
@@ -150,16 +249,110 @@ pub async fn handle_incoming_message(
         MessagePayload::UnfriendedAck => {
             handle_unfriended_ack(state, pool, &sender_hex).await;
         }
+        MessagePayload::CallKey { key_id, key } => {
+            if encrypted {
+                handle_call_key(state, &sender_hex, key_id, &key);
+            } else {
+                tracing::warn!(from = %sender_hex, "dropping call key sent without Signal encryption");
+            }
+        }
+        MessagePayload::SentTranscript { peer, payload } => {
+            if encrypted && from_self {
+                device_service::apply_transcript(app_handle, state, pool, &peer, *payload, ts).await;
+            } else {
+                tracing::warn!(from = %session_address, "dropping transcript not from one of our devices");
+            }
+        }
+        MessagePayload::ContactSync { contacts } => {
+            if encrypted && from_self {
+                device_service::apply_contact_sync(app_handle, state, pool, contacts).await;
+            } else {
+                tracing::warn!(from = %session_address, "dropping contact sync not from one of our devices");
+            }
+        }
+        MessagePayload::DeviceLinkGrant { certificate, sealed } => {
+            device_service::handle_link_grant(app_handle, state, &sender_hex, &certificate, &sealed);
+        }
+        MessagePayload::SessionInit { .. } => {
+            tracing::warn!(from = %session_address, "dropping nested SessionInit");
+        }
+        MessagePayload::SessionInitRejected { one_time_prekey_id } => {
+            device_service::retry_session_init(state, pool, &session_address, one_time_prekey_id).await;
+        }
+        MessagePayload::DisappearingTimer { seconds } => {
+            disappearing_service::handle_timer(app_handle, state, pool, &sender_hex, &sender_hex, seconds).await;
+        }
+        MessagePayload::GroupMessage { .. }
+        | MessagePayload::GroupInvite { .. }
+        | MessagePayload::GroupMemberAdded { .. }
+        | MessagePayload::GroupMemberLeft { .. } => {
+            if encrypted {
+                handle_group_payload(app_handle, state, pool, &sender_hex, payload, ts).await;
+            } else {
+                tracing::warn!(from = %sender_hex, "dropping group payload sent without Signal encryption");
+            }
+        }
+    }
+}
+
+/// Hand a group conversation payload to `group_service`.
+async fn handle_group_payload(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    sender_hex: &str,
+    payload: MessagePayload,
+    ts: i64,
+) {
+    match payload {
+        MessagePayload::GroupMessage { group_id, message_id, body } => {
+            group_service::handle_message(app_handle, state, pool, sender_hex, &group_id, &message_id, &body, ts)
+                .await;
+        }
+        MessagePayload::GroupInvite { group } => {
+            group_service::handle_invite(app_handle, state, pool, sender_hex, group).await;
+        }
+        MessagePayload::GroupMemberAdded { group_id, member } => {
+            group_service::handle_member_added(app_handle, state, pool, sender_hex, &group_id, member, ts).await;
+        }
+        MessagePayload::GroupMemberLeft { group_id } => {
+            group_service::handle_member_left(app_handle, state, pool, sender_hex, &group_id, ts).await;
+        }
+        _ => {}
     }
 }
 
+/// Install a peer's voice frame key for our 1:1 call with them, or keep it
+/// until we join.
+fn handle_call_key(state: &Arc<AppState>, sender_hex: &str, key_id: u64, key: &[u8]) {
+    let Ok(base) = <[u8; 32]>::try_from(key) else {
+        tracing::warn!(from = %sender_hex, len = key.len(), "malformed call key");
+        return;
+    };
+    let frame_key = rekindle_crypto::sframe::FrameKey::new(key_id, base);
+
+    {
+        let ve = state.voice_engine.lock();
+        if let Some(handle) = ve.as_ref() {
+            if handle.key_source == (crate::state::VoiceKeySource::Call { peer: sender_hex.to_string() }) {
+                handle.frame_keys.add_receive_key(frame_key.clone());
+                tracing::debug!(from = %sender_hex, key_id, "call frame key installed");
+            }
+        }
+    }
+    state.call_keys.lock().insert(sender_hex.to_string(), frame_key);
+}
+
 /// Store a direct message in `SQLite` and emit `ChatEvent` to frontend.
+#[allow(clippy::too_many_arguments)]
 async fn handle_direct_message(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
     pool: &DbPool,
     sender_hex: &str,
+    message_id: &str,
     body: &str,
+    attachments: &[FileAttachment],
     timestamp: i64,
 ) {
 
@@ -173,20 +366,32 @@ async fn handle_direct_message(
     let pool_clone = pool.clone();
     let sender = sender_hex.to_string();
     let body_clone = body.to_string();
-    if let Err(e) = tokio::task::spawn_blocking(move || {
+    let attachment_json = if attachments.is_empty() {
+        None
+    } else {
+        serde_json::to_string(attachments).ok()
+    };
+    let global_id = (!message_id.is_empty()).then(|| message_id.to_string());
+    let global_id_clone = global_id.clone();
+    let row_id = match tokio::task::spawn_blocking(move || {
         let conn = pool_clone.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read) \
-             VALUES (?, ?, 'dm', ?, ?, ?, 0)",
-            rusqlite::params![owner_key, sender, sender, body_clone, timestamp],
+            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, attachment_json, message_id) \
+             VALUES (?, ?, 'dm', ?, ?, ?, 0, ?, ?)",
+            rusqlite::params![owner_key, sender, sender, body_clone, timestamp, attachment_json, global_id_clone],
         )
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+        Ok::<_, String>(conn.last_insert_rowid())
     })
     .await
     .unwrap_or_else(|e| Err(e.to_string()))
     {
-        tracing::error!(error = %e, "failed to persist incoming message");
-    }
+        Ok(id) => Some(id),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to persist incoming message");
+            None
+        }
+    };
 
     // Update unread count
     {
@@ -196,14 +401,31 @@ async fn handle_direct_message(
         }
     }
 
-    // Emit to frontend
+    // Emit to frontend. Attachments can only be fetched through a stored message.
+    let attachments = row_id.map_or_else(Vec::new, |id| {
+        crate::services::file_transfer_service::remote_attachment_infos(id, attachments)
+    });
     let event = ChatEvent::MessageReceived {
         from: sender_hex.to_string(),
         body: body.to_string(),
         timestamp: timestamp.cast_unsigned(),
         conversation_id: sender_hex.to_string(),
+        attachments,
+        message_id: global_id,
+        reply_to: None,
     };
     let _ = app_handle.emit("chat-event", &event);
+
+    // Older peers send DMs without an ID and can't be acknowledged
+    if row_id.is_some() && !message_id.is_empty() {
+        receipt_service::queue_receipts(
+            state,
+            pool,
+            sender_hex,
+            &[message_id.to_string()],
+            ReceiptKind::Delivered,
+        );
+    }
 }
 
 /// Store a channel message in `SQLite` and emit `ChatEvent` to frontend.
@@ -247,10 +469,53 @@ async fn handle_channel_message(
         body: body.to_string(),
         timestamp: timestamp.cast_unsigned(),
         conversation_id: channel_id.to_string(),
+        attachments: Vec::new(),
+        message_id: None,
+        reply_to: None,
     };
     let _ = app_handle.emit("chat-event", &event);
 }
 
+/// Run a session-establishing operation under the identity-key change policy.
+///
+/// When `op` reports `IdentityKeyChanged` the UI is warned. Unverified
+/// contacts get the new key accepted and `op` is retried; verified contacts
+/// are blocked (their existing session is kept) until the user re-verifies.
+/// On any other failure the stale session is cleared.
+pub(crate) fn with_identity_policy<T>(
+    app_handle: &tauri::AppHandle,
+    manager: &rekindle_crypto::SignalSessionManager,
+    peer: &str,
+    identity_key: &[u8],
+    op: impl Fn() -> Result<T, rekindle_crypto::CryptoError>,
+) -> Result<T, rekindle_crypto::CryptoError> {
+    let result = match op() {
+        Err(rekindle_crypto::CryptoError::IdentityKeyChanged(_)) => {
+            let verified = manager.is_verified(peer).unwrap_or(false);
+            let _ = app_handle.emit(
+                "chat-event",
+                &ChatEvent::IdentityKeyChanged {
+                    public_key: peer.to_string(),
+                    verified,
+                },
+            );
+            if verified {
+                tracing::warn!(peer = %peer, "identity key changed for verified contact — session blocked until re-verified");
+                return Err(rekindle_crypto::CryptoError::IdentityKeyChanged(peer.to_string()));
+            }
+            tracing::warn!(peer = %peer, "identity key changed for unverified contact — accepting new key");
+            manager.accept_identity_change(peer, identity_key)?;
+            op()
+        }
+        other => other,
+    };
+    if result.is_err() {
+        // Clear any stale session (e.g., from a previous friendship that was removed)
+        let _ = manager.delete_session(peer);
+    }
+    result
+}
+
 /// Process incoming friend request — just log receipt.
 ///
 /// We do NOT establish a Signal session here. The session will be established
@@ -277,6 +542,7 @@ fn handle_friend_request(
 /// the responder. We use the ephemeral key they sent us to derive a matching
 /// shared secret via `respond_to_session()`.
 fn handle_friend_accept(
+    app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
     sender_hex: &str,
     prekey_bundle_bytes: &[u8],
@@ -303,15 +569,16 @@ fn handle_friend_accept(
 
     let signal = state.signal_manager.lock();
     if let Some(handle) = signal.as_ref() {
-        // Clear any stale session first (e.g., from a previous friendship that was removed)
-        let _ = handle.manager.delete_session(sender_hex);
-        match handle.manager.respond_to_session(
-            sender_hex,
-            &their_identity_key,
-            ephemeral_key,
-            signed_prekey_id,
-            one_time_prekey_id,
-        ) {
+        let result = with_identity_policy(app_handle, &handle.manager, sender_hex, &their_identity_key, || {
+            handle.manager.respond_to_session(
+                sender_hex,
+                &their_identity_key,
+                ephemeral_key,
+                signed_prekey_id,
+                one_time_prekey_id,
+            )
+        });
+        match result {
             Ok(()) => tracing::info!(from = %sender_hex, "established responder Signal session from FriendAccept"),
             Err(e) => tracing::warn!(from = %sender_hex, error = %e, "failed to establish responder Signal session"),
         }
@@ -458,7 +725,7 @@ async fn handle_friend_accept_full(
         return;
     }
 
-    handle_friend_accept(state, sender_hex, prekey_bundle, ephemeral_key, signed_prekey_id, one_time_prekey_id);
+    handle_friend_accept(app_handle, state, sender_hex, prekey_bundle, ephemeral_key, signed_prekey_id, one_time_prekey_id);
     // Cache the acceptor's route blob
     if !route_blob.is_empty() {
         let api = {
@@ -503,6 +770,7 @@ async fn handle_friend_accept_full(
             .get(sender_hex)
             .map_or_else(|| sender_hex.to_string(), |f| f.display_name.clone())
     };
+    device_service::share_contacts(state, pool);
     let event = ChatEvent::FriendRequestAccepted {
         from: sender_hex.to_string(),
         display_name,
@@ -853,6 +1121,7 @@ async fn handle_unfriended(
     if let Err(e) = push_friend_list_update(state).await {
         tracing::warn!(error = %e, "failed to update DHT friend list after peer unfriended us");
     }
+    device_service::share_contacts(state, pool);
 
     let _ = app_handle.emit(
         "chat-event",
@@ -909,15 +1178,15 @@ async fn auto_accept_cross_request(
     }
 
     // 2. Establish Signal session from their prekey bundle
-    // Clear any stale session first (e.g., from a previous friendship that was removed)
     let session_init = if prekey_bundle.is_empty() {
         None
     } else {
         let signal = state.signal_manager.lock();
         if let Some(handle) = signal.as_ref() {
-            let _ = handle.manager.delete_session(sender_hex);
             if let Ok(bundle) = serde_json::from_slice::<rekindle_crypto::signal::PreKeyBundle>(prekey_bundle) {
-                match handle.manager.establish_session(sender_hex, &bundle) {
+                match with_identity_policy(app_handle, &handle.manager, sender_hex, &bundle.identity_key, || {
+                    handle.manager.establish_session(sender_hex, &bundle)
+                }) {
                     Ok(info) => {
                         tracing::info!(peer = %sender_hex, "established Signal session on cross-request auto-accept");
                         Some(info)
@@ -951,6 +1220,8 @@ async fn auto_accept_cross_request(
         }
     }
 
+    device_service::share_contacts(state, pool);
+
     // 5. Emit accepted event
     let _ = app_handle.emit(
         "chat-event",
@@ -1057,10 +1328,18 @@ async fn try_fetch_route_from_dht(
     state: &Arc<AppState>,
     peer_id: &str,
 ) -> Option<Vec<u8>> {
-    let dht_key_str = {
-        let friends = state.friends.read();
-        friends.get(peer_id).and_then(|f| f.dht_record_key.clone())
-    }?;
+    // Linked devices publish their route in their own mailbox
+    if device_service::is_device_address(peer_id) {
+        let route_blob = device_service::read_device_route(state, peer_id).await?;
+        let api = state.node.read().as_ref().map(|nh| nh.api.clone())?;
+        let mut dht_mgr = state.dht_manager.write();
+        if let Some(mgr) = dht_mgr.as_mut() {
+            mgr.manager.cache_route(&api, peer_id, route_blob.clone());
+        }
+        return Some(route_blob);
+    }
+
+    let dht_key_str = device_service::profile_key_for(state, peer_id)?;
 
     let record_key: veilid_core::RecordKey = dht_key_str.parse().ok()?;
 
@@ -1136,11 +1415,20 @@ async fn try_inline_route_refresh_and_send(
     false
 }
 
+/// How a payload left for a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Handed to the peer's route.
+    Sent,
+    /// Queued in `pending_messages` for `sync_service` to retry.
+    Queued,
+    /// An ephemeral payload that couldn't be sent and was dropped.
+    Dropped,
+}
+
 /// Build a `MessageEnvelope`, optionally encrypt with Signal, and send via Veilid.
 ///
-/// If no route exists for the peer, the message is queued for retry by `sync_service`.
-/// Ephemeral payloads (typing indicators) are never queued — a stale typing indicator
-/// delivered minutes later is worse than no indicator.
+/// See [`deliver_envelope_to_peer`].
 async fn send_envelope_to_peer(
     state: &Arc<AppState>,
     pool: &DbPool,
@@ -1148,50 +1436,81 @@ async fn send_envelope_to_peer(
     payload: &MessagePayload,
     encrypt: bool,
 ) -> Result<(), String> {
+    deliver_envelope_to_peer(state, pool, to, payload, encrypt)
+        .await
+        .map(|_| ())
+}
+
+/// Build a `MessageEnvelope`, optionally encrypt with Signal, and send via Veilid.
+///
+/// If no route exists for the peer, the message is queued for retry by `sync_service`.
+/// Ephemeral payloads (typing indicators) are never queued — a stale typing indicator
+/// delivered minutes later is worse than no indicator. Payloads every device
+/// should see then go on to the peer's linked devices and ours.
+pub(crate) async fn deliver_envelope_to_peer(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    payload: &MessagePayload,
+    encrypt: bool,
+) -> Result<Delivery, String> {
+    let delivery = deliver_to_device(state, pool, to, payload, encrypt).await?;
+    device_service::fan_out(state, pool, to, payload, encrypt);
+    Ok(delivery)
+}
+
+/// Deliver a payload to a single device: an identity's primary, or a linked
+/// device addressed `{identity}/{device}`.
+pub(crate) async fn deliver_to_device(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    payload: &MessagePayload,
+    encrypt: bool,
+) -> Result<Delivery, String> {
     let is_ephemeral = matches!(payload, MessagePayload::TypingIndicator { .. });
+    // Call keys and file keys protect the media itself — never send them in
+    // plaintext. Receipts would leak what the user reads and when, and
+    // transcripts and contact syncs carry everything else. A timer change
+    // says how private the conversation is meant to be. Group payloads
+    // may go to members we aren't friends with, so they start their own
+    // session rather than fall back to plaintext.
+    let must_encrypt = match payload {
+        MessagePayload::CallKey { .. }
+        | MessagePayload::DeliveryReceipt { .. }
+        | MessagePayload::ReadReceipt { .. }
+        | MessagePayload::SentTranscript { .. }
+        | MessagePayload::ContactSync { .. }
+        | MessagePayload::DisappearingTimer { .. }
+        | MessagePayload::GroupMessage { .. }
+        | MessagePayload::GroupInvite { .. }
+        | MessagePayload::GroupMemberAdded { .. }
+        | MessagePayload::GroupMemberLeft { .. } => true,
+        MessagePayload::DirectMessage { attachments, .. } => !attachments.is_empty(),
+        _ => false,
+    };
+    // Queued DMs remember their ID so a later delivery can update them.
+    // Only the copy for the peer's primary drives the delivery state.
+    let dm_id = match payload {
+        MessagePayload::DirectMessage { message_id, .. }
+            if !message_id.is_empty() && !device_service::is_device_address(to) =>
+        {
+            Some(message_id.as_str())
+        }
+        _ => None,
+    };
     // Serialize the payload
     let payload_bytes =
         serde_json::to_vec(payload).map_err(|e| format!("serialize payload: {e}"))?;
 
     // Optionally encrypt with Signal
     let final_payload = if encrypt {
-        let signal = state.signal_manager.lock();
-        if let Some(handle) = signal.as_ref() {
-            match handle.manager.has_session(to) {
-                Ok(true) => handle
-                    .manager
-                    .encrypt(to, &payload_bytes)
-                    .map_err(|e| format!("Signal encrypt: {e}"))?,
-                _ => payload_bytes,
-            }
-        } else {
-            payload_bytes
-        }
+        seal_payload(state, to, payload_bytes, must_encrypt).await?
     } else {
         payload_bytes
     };
 
-    // Build signed envelope
-    let secret_key = {
-        let sk = state.identity_secret.lock();
-        *sk.as_ref().ok_or("signing key not initialized")?
-    };
-
-    let timestamp = u64::try_from(
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis(),
-    )
-    .unwrap_or(u64::MAX);
-
-    let nonce = {
-        let mut buf = [0u8; 16];
-        rand::thread_rng().fill_bytes(&mut buf);
-        buf.to_vec()
-    };
-
-    let envelope = build_envelope_from_secret(&secret_key, timestamp, nonce, final_payload);
+    let envelope = build_signed_envelope(state, final_payload)?;
 
     // Look up the peer's cached route blob and import the RouteId via cache
     let route_id_and_rc = {
@@ -1224,18 +1543,18 @@ async fn send_envelope_to_peer(
     let Some((route_id, routing_context)) = route_id_and_rc else {
         if is_ephemeral {
             tracing::debug!(to = %to, "no cached route for peer — dropping ephemeral message");
-            return Ok(());
+            return Ok(Delivery::Dropped);
         }
         // Inline DHT route re-fetch before queuing — avoids 30s wait for sync loop
         if try_inline_route_refresh_and_send(state, to, &envelope).await {
             tracing::info!(to = %to, "message sent via veilid (after inline route refresh)");
-            return Ok(());
+            return Ok(Delivery::Sent);
         }
         tracing::debug!(to = %to, "no cached route for peer — queuing message for retry");
         let envelope_json =
             serde_json::to_string(&envelope).map_err(|e| format!("serialize envelope: {e}"))?;
-        queue_pending_message(state, pool, to, &envelope_json).await?;
-        return Ok(());
+        queue_pending_message(state, pool, to, &envelope_json, dm_id).await?;
+        return Ok(Delivery::Queued);
     };
 
     if let Err(e) = send_envelope(&routing_context, route_id, &envelope).await {
@@ -1248,22 +1567,22 @@ async fn send_envelope_to_peer(
         }
         if is_ephemeral {
             tracing::debug!(to = %to, error = %e, "send failed — dropping ephemeral message");
-            return Ok(());
+            return Ok(Delivery::Dropped);
         }
         // Inline DHT route re-fetch before queuing — avoids 30s wait for sync loop
         if try_inline_route_refresh_and_send(state, to, &envelope).await {
             tracing::info!(to = %to, "message sent via veilid (after send failure + inline route refresh)");
-            return Ok(());
+            return Ok(Delivery::Sent);
         }
         tracing::warn!(to = %to, error = %e, "send failed — queuing for retry");
         let envelope_json =
             serde_json::to_string(&envelope).map_err(|e| format!("serialize envelope: {e}"))?;
-        queue_pending_message(state, pool, to, &envelope_json).await?;
-        return Ok(());
+        queue_pending_message(state, pool, to, &envelope_json, dm_id).await?;
+        return Ok(Delivery::Queued);
     }
 
     tracing::info!(to = %to, "message sent via veilid");
-    Ok(())
+    Ok(Delivery::Sent)
 }
 
 /// Send a direct message to a peer via the Veilid network.
@@ -1271,14 +1590,56 @@ pub async fn send_message(
     state: &Arc<AppState>,
     pool: &DbPool,
     to: &str,
+    message_id: &str,
     body: &str,
-) -> Result<(), String> {
+) -> Result<Delivery, String> {
+    send_message_with_attachments(state, pool, to, message_id, body, Vec::new()).await
+}
+
+/// Send a direct message announcing uploaded files.
+///
+/// The attachments carry the file keys, so this must only ever go out
+/// Signal-encrypted.
+pub async fn send_message_with_attachments(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    message_id: &str,
+    body: &str,
+    attachments: Vec<FileAttachment>,
+) -> Result<Delivery, String> {
     let payload = MessagePayload::DirectMessage {
+        message_id: message_id.to_string(),
         body: body.to_string(),
         reply_to: None,
+        attachments,
     };
     // Encrypt DMs when a Signal session exists
-    send_envelope_to_peer(state, pool, to, &payload, true).await
+    deliver_envelope_to_peer(state, pool, to, &payload, true).await
+}
+
+/// Build a `PreKeyBundle` carrying a fresh one-time prekey for one peer.
+///
+/// Returns an empty vec on failure — the peer can still fall back to the
+/// bundle published in our DHT profile.
+async fn peer_prekey_bundle(state: &Arc<AppState>, pool: &DbPool, purpose: &str) -> Vec<u8> {
+    let st = Arc::clone(state);
+    let db = pool.clone();
+    match tokio::task::spawn_blocking(move || {
+        crate::services::prekey_service::bundle_for_peer(&st, &db)
+    })
+    .await
+    {
+        Ok(Ok(bytes)) => bytes,
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, purpose, "failed to generate PreKeyBundle");
+            Vec::new()
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, purpose, "PreKeyBundle task panicked");
+            Vec::new()
+        }
+    }
 }
 
 /// Send a friend request to a peer via Veilid.
@@ -1291,25 +1652,12 @@ pub async fn send_friend_request(
     to: &str,
     message: &str,
 ) -> Result<(), String> {
-    let (display_name, prekey_bundle) = {
+    let display_name = {
         let identity = state.identity.read();
         let id = identity.as_ref().ok_or("identity not set")?;
-        let display_name = id.display_name.clone();
-
-        let signal = state.signal_manager.lock();
-        let bundle_bytes = if let Some(handle) = signal.as_ref() {
-            match handle.manager.generate_prekey_bundle(1, Some(1)) {
-                Ok(bundle) => serde_json::to_vec(&bundle).unwrap_or_default(),
-                Err(e) => {
-                    tracing::warn!(error = %e, "failed to generate PreKeyBundle for friend request");
-                    Vec::new()
-                }
-            }
-        } else {
-            Vec::new()
-        };
-        (display_name, bundle_bytes)
+        id.display_name.clone()
     };
+    let prekey_bundle = peer_prekey_bundle(state, pool, "friend request").await;
 
     // Gather our profile and mailbox DHT keys + route blob for the invite payload
     let (profile_dht_key, route_blob, mailbox_dht_key) = {
@@ -1348,20 +1696,7 @@ pub async fn send_friend_accept(
     to: &str,
     session_init: Option<rekindle_crypto::signal::SessionInitInfo>,
 ) -> Result<(), String> {
-    let prekey_bundle = {
-        let signal = state.signal_manager.lock();
-        if let Some(handle) = signal.as_ref() {
-            match handle.manager.generate_prekey_bundle(1, Some(1)) {
-                Ok(bundle) => serde_json::to_vec(&bundle).unwrap_or_default(),
-                Err(e) => {
-                    tracing::warn!(error = %e, "failed to generate PreKeyBundle for friend accept");
-                    Vec::new()
-                }
-            }
-        } else {
-            Vec::new()
-        }
-    };
+    let prekey_bundle = peer_prekey_bundle(state, pool, "friend accept").await;
 
     // Gather our profile and mailbox DHT keys + route blob
     let (profile_dht_key, route_blob, mailbox_dht_key) = {
@@ -1414,6 +1749,95 @@ pub async fn send_typing(
     send_envelope_to_peer(state, pool, to, &payload, true).await
 }
 
+/// Tell a peer we replaced the body of one of our messages to them.
+pub async fn send_edit(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    message_id: &str,
+    body: &str,
+) -> Result<(), String> {
+    let payload = MessagePayload::EditMessage {
+        message_id: message_id.to_string(),
+        body: body.to_string(),
+    };
+    send_envelope_to_peer(state, pool, to, &payload, true).await
+}
+
+/// Tell a peer to delete one of our messages to them.
+pub async fn send_delete(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    message_id: &str,
+) -> Result<(), String> {
+    let payload = MessagePayload::DeleteMessage {
+        message_id: message_id.to_string(),
+    };
+    send_envelope_to_peer(state, pool, to, &payload, true).await
+}
+
+/// Tell a peer we set the disappearing-message timer of our DM.
+pub async fn send_disappearing_timer(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    seconds: u32,
+) -> Result<(), String> {
+    let payload = MessagePayload::DisappearingTimer { seconds };
+    send_envelope_to_peer(state, pool, to, &payload, true).await
+}
+
+/// Add (`add`) or take back our reaction to a message in our DM with `to`.
+pub async fn send_reaction(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    message_id: &str,
+    emoji: &str,
+    add: bool,
+) -> Result<(), String> {
+    let (message_id, emoji) = (message_id.to_string(), emoji.to_string());
+    let payload = if add {
+        MessagePayload::AddReaction { message_id, emoji }
+    } else {
+        MessagePayload::RemoveReaction { message_id, emoji }
+    };
+    send_envelope_to_peer(state, pool, to, &payload, true).await
+}
+
+/// Acknowledge a batch of the peer's messages. Only ever sent Signal-encrypted.
+pub async fn send_receipt(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    kind: ReceiptKind,
+    message_ids: Vec<String>,
+) -> Result<(), String> {
+    let payload = match kind {
+        ReceiptKind::Delivered => MessagePayload::DeliveryReceipt { message_ids },
+        ReceiptKind::Read => MessagePayload::ReadReceipt { message_ids },
+    };
+    send_envelope_to_peer(state, pool, to, &payload, true).await
+}
+
+/// Send our voice frame key for a 1:1 call to the peer.
+///
+/// Fails rather than falling back to plaintext when there is no Signal
+/// session with them.
+pub async fn send_call_key(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    to: &str,
+    key: &rekindle_crypto::sframe::FrameKey,
+) -> Result<(), String> {
+    let payload = MessagePayload::CallKey {
+        key_id: key.id(),
+        key: key.as_bytes().to_vec(),
+    };
+    send_envelope_to_peer(state, pool, to, &payload, true).await
+}
+
 /// Send a raw (unencrypted) payload to a peer.
 ///
 /// Used for protocol-level messages like `ProfileKeyRotated` that don't need
@@ -1447,6 +1871,17 @@ pub(crate) async fn build_and_queue_envelope(
     let payload_bytes =
         serde_json::to_vec(payload).map_err(|e| format!("serialize payload: {e}"))?;
 
+    let envelope = build_signed_envelope(state, payload_bytes)?;
+    let envelope_json =
+        serde_json::to_string(&envelope).map_err(|e| format!("serialize envelope: {e}"))?;
+    queue_pending_message(state, pool, to, &envelope_json, None).await
+}
+
+/// Sign `payload_bytes` into an envelope from this device.
+pub(crate) fn build_signed_envelope(
+    state: &AppState,
+    payload_bytes: Vec<u8>,
+) -> Result<rekindle_protocol::messaging::MessageEnvelope, String> {
     let secret_key = {
         let sk = state.identity_secret.lock();
         *sk.as_ref().ok_or("signing key not initialized")?
@@ -1466,18 +1901,77 @@ pub(crate) async fn build_and_queue_envelope(
         buf.to_vec()
     };
 
-    let envelope = build_envelope_from_secret(&secret_key, timestamp, nonce, payload_bytes);
-    let envelope_json =
-        serde_json::to_string(&envelope).map_err(|e| format!("serialize envelope: {e}"))?;
-    queue_pending_message(state, pool, to, &envelope_json).await
+    let device_key = device_service::local_device_key_bytes(state);
+    Ok(build_device_envelope_from_secret(
+        &secret_key,
+        &device_key,
+        timestamp,
+        nonce,
+        payload_bytes,
+    ))
+}
+
+/// Signal-encrypt a payload for `to`.
+///
+/// Without a session, peers we never exchange friend requests with — our
+/// own devices and our friends' linked devices — and friends restored from
+/// our account record get a session started from their published prekeys.
+/// Anyone else gets plaintext unless the payload must be encrypted or the
+/// contact is verified.
+async fn seal_payload(
+    state: &Arc<AppState>,
+    to: &str,
+    payload_bytes: Vec<u8>,
+    must_encrypt: bool,
+) -> Result<Vec<u8>, String> {
+    {
+        let signal = state.signal_manager.lock();
+        let Some(handle) = signal.as_ref() else {
+            if must_encrypt {
+                return Err("signal manager not initialized".to_string());
+            }
+            return Ok(payload_bytes);
+        };
+        if handle.manager.has_session(to).unwrap_or(false) {
+            return handle
+                .manager
+                .encrypt(to, &payload_bytes)
+                .map_err(|e| format!("Signal encrypt: {e}"));
+        }
+        // Never fall back to plaintext for a contact the user verified
+        if handle.manager.is_verified(to).unwrap_or(false) {
+            return Err(
+                "no secure session with verified contact — re-verify their safety number".to_string(),
+            );
+        }
+    }
+
+    let restart = state.session_restarts.lock().contains(to);
+    if restart || device_service::starts_own_sessions(state, to) {
+        match device_service::session_init(state, to, &payload_bytes, true).await {
+            Ok(wrapped) => {
+                state.session_restarts.lock().remove(to);
+                return Ok(wrapped);
+            }
+            Err(e) if must_encrypt => return Err(format!("no secure session with peer: {e}")),
+            Err(e) => tracing::debug!(to = %to, error = %e, "could not start Signal session"),
+        }
+    }
+    if must_encrypt {
+        return Err("no secure session with peer".to_string());
+    }
+    Ok(payload_bytes)
 }
 
 /// Insert a message into the `pending_messages` table for later retry.
+///
+/// `message_id` is the global ID of the DM the envelope carries, if any.
 async fn queue_pending_message(
     state: &Arc<AppState>,
     pool: &DbPool,
     recipient_key: &str,
     body: &str,
+    message_id: Option<&str>,
 ) -> Result<(), String> {
     let owner_key = state
         .identity
@@ -1488,12 +1982,13 @@ async fn queue_pending_message(
     let pool = pool.clone();
     let recipient = recipient_key.to_string();
     let body = body.to_string();
+    let message_id = message_id.map(str::to_string);
     let now = crate::db::timestamp_now();
     tokio::task::spawn_blocking(move || {
         let conn = pool.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO pending_messages (owner_key, recipient_key, body, created_at) VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![owner_key, recipient, body, now],
+            "INSERT INTO pending_messages (owner_key, recipient_key, body, created_at, message_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![owner_key, recipient, body, now, message_id],
         )
         .map_err(|e| format!("queue pending message: {e}"))?;
         Ok::<(), String>(())
@@ -1512,6 +2007,10 @@ pub async fn push_profile_update(
     subkey: u32,
     value: Vec<u8>,
 ) -> Result<(), String> {
+    // Only the primary device holds the profile's owner keypair
+    if device_service::is_linked_device(state) {
+        return Ok(());
+    }
     let (profile_key, routing_context, owner_keypair) = {
         let node = state.node.read();
         let nh = node.as_ref().ok_or("node not initialized")?;
@@ -1544,6 +2043,9 @@ pub async fn push_profile_update(
 /// Serializes the current friend public keys as a JSON array and writes
 /// it to our friend list DHT record (subkey 0).
 pub async fn push_friend_list_update(state: &Arc<AppState>) -> Result<(), String> {
+    if device_service::is_linked_device(state) {
+        return Ok(());
+    }
     let (friend_list_key, routing_context, owner_keypair, friend_keys) = {
         let node = state.node.read();
         let nh = node.as_ref().ok_or("node not initialized")?;
@@ -1,8 +1,20 @@
+pub mod account_service;
+pub mod backup_service;
 pub mod community_service;
+pub mod device_service;
+pub mod directory_service;
+pub mod disappearing_service;
+pub mod file_transfer_service;
 pub mod game_service;
+pub mod group_service;
 pub mod idle_service;
+pub mod mek_service;
+pub mod message_edit_service;
 pub mod message_service;
+pub mod prekey_service;
 pub mod presence_service;
+pub mod receipt_service;
 pub mod server_health_service;
 pub mod sync_service;
+pub mod tree_service;
 pub mod veilid_service;
@@ -0,0 +1,373 @@
+//! Prekey lifecycle: signed-prekey rotation and one-time prekey replenishment.
+//!
+//! One-time prekeys come in two kinds:
+//! - **Pool keys** are published to the profile DHT record, one per subkey
+//!   starting at `SUBKEY_ONE_TIME_PREKEYS_START`, for contacts who fetch our
+//!   bundle from the DHT.
+//! - **Handed-out keys** are generated on demand and embedded directly in a
+//!   friend request, friend accept or invite. They are never published, so a
+//!   contact holding one cannot collide with a DHT fetcher.
+//!
+//! Consumed keys are deleted by `respond_to_session`. The maintenance loop
+//! refills the pool when it drops below a threshold, rotates the signed
+//! prekey on a schedule, and purges old signed prekeys once their grace
+//! window has passed.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rekindle_protocol::dht::profile::{self, ONE_TIME_PREKEY_SLOTS};
+use rusqlite::OptionalExtension;
+use tokio::sync::mpsc;
+
+use crate::commands::auth::current_owner_key;
+use crate::db::{self, DbPool};
+use crate::state::AppState;
+
+/// Interval between maintenance passes.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Number of pool keys kept available (one per DHT slot).
+const POOL_TARGET: u32 = ONE_TIME_PREKEY_SLOTS;
+
+/// Refill the pool once fewer than this many pool keys remain.
+const POOL_REFILL_THRESHOLD: usize = 6;
+
+/// Age after which the current signed prekey is replaced.
+const SIGNED_PREKEY_ROTATION_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// How long a replaced signed prekey stays usable for late session initiations.
+const SIGNED_PREKEY_GRACE_MS: i64 = 14 * 24 * 60 * 60 * 1000;
+
+/// How long an unused handed-out one-time prekey is kept before purging.
+const HANDED_OUT_PREKEY_TTL_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// What a maintenance pass changed, so the caller knows what to republish.
+#[derive(Debug, Default)]
+pub struct MaintenanceOutcome {
+    /// A new signed prekey was generated (subkey 5 must be republished).
+    pub signed_rotated: bool,
+    /// IDs of the pool keys that should currently be published, in slot order.
+    pub pool_ids: Vec<u32>,
+}
+
+/// Prekey maintenance loop: rotate, purge, replenish and republish.
+///
+/// Runs once immediately (after the network is ready), then every
+/// [`MAINTENANCE_INTERVAL`]. Shuts down when `shutdown_rx` fires.
+pub async fn prekey_maintenance_loop(
+    state: Arc<AppState>,
+    pool: DbPool,
+    mut shutdown_rx: mpsc::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+    // (profile key, pool IDs) last written to the DHT
+    let mut last_published: Option<(String, Vec<u32>)> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let st = Arc::clone(&state);
+                let db = pool.clone();
+                let outcome = match tokio::task::spawn_blocking(move || run_maintenance(&st, &db)).await {
+                    Ok(Ok(outcome)) => outcome,
+                    Ok(Err(e)) => {
+                        tracing::warn!(error = %e, "prekey maintenance failed");
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "prekey maintenance task panicked");
+                        continue;
+                    }
+                };
+                if let Err(e) = publish_prekeys(&state, &outcome, &mut last_published).await {
+                    tracing::warn!(error = %e, "prekey DHT publish failed — will retry next pass");
+                }
+            }
+            _ = shutdown_rx.recv() => {
+                tracing::debug!("prekey maintenance loop shutting down");
+                break;
+            }
+        }
+    }
+}
+
+/// Run one maintenance pass against the prekey store.
+///
+/// Blocking: performs `SQLite` I/O. Also used at login so a signed prekey
+/// and a full pool exist before the first bundle is handed out.
+pub fn run_maintenance(state: &Arc<AppState>, pool: &DbPool) -> Result<MaintenanceOutcome, String> {
+    let owner_key = current_owner_key(state)?;
+    let now = db::timestamp_now();
+    let mut outcome = MaintenanceOutcome::default();
+
+    // 1. Signed prekey: create or rotate
+    let signed = load_signed_prekeys(pool, &owner_key)?;
+    let current = signed.iter().max_by_key(|(id, _)| *id).copied();
+    let needs_rotation = match current {
+        None => true,
+        Some((_, created_at)) => now - created_at >= SIGNED_PREKEY_ROTATION_MS,
+    };
+    let current_id = if needs_rotation {
+        let next_id = current.map_or(1, |(id, _)| id + 1);
+        with_manager(state, |m| m.generate_signed_prekey(next_id))?;
+        tracing::info!(signed_prekey_id = next_id, "rotated signed prekey");
+        outcome.signed_rotated = true;
+        next_id
+    } else {
+        current.map_or(1, |(id, _)| id)
+    };
+
+    // 2. Purge signed prekeys whose grace window has expired
+    let expiry = now - SIGNED_PREKEY_ROTATION_MS - SIGNED_PREKEY_GRACE_MS;
+    for (id, created_at) in &signed {
+        if *id != current_id && *created_at < expiry {
+            with_manager(state, |m| m.remove_signed_prekey(*id))?;
+            tracing::debug!(signed_prekey_id = id, "purged expired signed prekey");
+        }
+    }
+
+    // 3. Purge handed-out one-time prekeys nobody ever used
+    for id in stale_handed_out_prekeys(pool, &owner_key, now - HANDED_OUT_PREKEY_TTL_MS)? {
+        with_manager(state, |m| m.remove_one_time_prekey(id))?;
+    }
+
+    // 4. Refill the DHT pool when it runs low
+    let mut pool_ids = available_pool_prekeys(pool, &owner_key)?;
+    if pool_ids.len() < POOL_REFILL_THRESHOLD {
+        let missing = POOL_TARGET.saturating_sub(u32::try_from(pool_ids.len()).unwrap_or(u32::MAX));
+        let new_ids = allocate_prekey_ids(pool, &owner_key, missing)?;
+        with_manager(state, |m| m.generate_one_time_prekeys(&new_ids))?;
+        tracing::info!(
+            remaining = pool_ids.len(),
+            added = new_ids.len(),
+            "replenished one-time prekey pool"
+        );
+        pool_ids.extend(new_ids);
+    }
+    outcome.pool_ids = pool_ids;
+
+    Ok(outcome)
+}
+
+/// Serialized bundle with the current signed prekey and no one-time prekey.
+///
+/// This is what lives in profile subkey 5; fetchers pick a one-time prekey
+/// from the pool subkeys separately.
+pub fn published_bundle(state: &Arc<AppState>) -> Result<Vec<u8>, String> {
+    let bundle = with_manager(state, |m| {
+        let current = current_signed_prekey_id(m)?;
+        m.prekey_bundle(current, None)
+    })?;
+    serde_json::to_vec(&bundle).map_err(|e| format!("serialize prekey bundle: {e}"))
+}
+
+/// Serialized bundle for direct delivery to one peer (friend request,
+/// friend accept or invite).
+///
+/// Includes a freshly generated one-time prekey that is recorded as handed
+/// out, so it is never published to the DHT pool or given to anyone else.
+pub fn bundle_for_peer(state: &Arc<AppState>, pool: &DbPool) -> Result<Vec<u8>, String> {
+    let owner_key = current_owner_key(state)?;
+    let prekey_id = allocate_prekey_ids(pool, &owner_key, 1)?
+        .pop()
+        .ok_or("failed to allocate one-time prekey ID")?;
+
+    let bundle = with_manager(state, |m| {
+        m.generate_one_time_prekeys(&[prekey_id])?;
+        let current = current_signed_prekey_id(m)?;
+        m.prekey_bundle(current, Some(prekey_id))
+    })?;
+
+    let conn = pool.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE prekeys SET handed_out_at = ?1 WHERE owner_key = ?2 AND is_signed = 0 AND id = ?3",
+        rusqlite::params![db::timestamp_now(), owner_key, prekey_id],
+    )
+    .map_err(|e| format!("mark prekey handed out: {e}"))?;
+    drop(conn);
+
+    serde_json::to_vec(&bundle).map_err(|e| format!("serialize prekey bundle: {e}"))
+}
+
+/// Write changed prekey material to our profile DHT record.
+///
+/// A profile record created before the pool existed has too few subkeys to
+/// hold it; login replaces such a record, so it is skipped here.
+async fn publish_prekeys(
+    state: &Arc<AppState>,
+    outcome: &MaintenanceOutcome,
+    last_published: &mut Option<(String, Vec<u32>)>,
+) -> Result<(), String> {
+    // A linked device publishes its own bundle in its device mailbox; the
+    // one-time pool lives in the profile, which only the primary writes.
+    if let Some(mailbox_key) = super::device_service::is_linked_device(state)
+        .then(|| super::device_service::own_mailbox_key(state))
+        .flatten()
+    {
+        let changed = last_published.as_ref().is_none_or(|(key, _)| *key != mailbox_key);
+        if changed || outcome.signed_rotated {
+            let bundle = published_bundle(state)?;
+            super::device_service::publish_device_mailbox(state, None, Some(&bundle)).await?;
+            *last_published = Some((mailbox_key, Vec::new()));
+        }
+        return Ok(());
+    }
+    let (profile_key, owner_keypair, routing_context) = {
+        let node = state.node.read();
+        let Some(nh) = node.as_ref() else {
+            return Err("node not initialized".to_string());
+        };
+        (
+            nh.profile_dht_key.clone(),
+            nh.profile_owner_keypair.clone(),
+            nh.routing_context.clone(),
+        )
+    };
+    let Some(profile_key) = profile_key else {
+        // Profile not created yet — the next pass will publish everything
+        return Ok(());
+    };
+    let Some(owner_keypair) = owner_keypair else {
+        return Err("no profile owner keypair".to_string());
+    };
+
+    let profile_changed = last_published
+        .as_ref()
+        .is_none_or(|(key, _)| *key != profile_key);
+    let pool_changed = last_published
+        .as_ref()
+        .is_none_or(|(_, ids)| *ids != outcome.pool_ids);
+    if !profile_changed && !pool_changed && !outcome.signed_rotated {
+        return Ok(());
+    }
+
+    let dht = rekindle_protocol::dht::DHTManager::new(routing_context);
+    let subkey_count = dht
+        .open_record_writable_subkeys(&profile_key, owner_keypair)
+        .await
+        .map_err(|e| format!("open profile record: {e}"))?;
+    if subkey_count < profile::PROFILE_SUBKEY_COUNT {
+        // Login moves such records to a new one; until then there is
+        // nowhere to put the pool
+        tracing::warn!(
+            profile_key = %profile_key,
+            subkey_count,
+            "profile record predates the one-time prekey pool — not publishing prekeys"
+        );
+        return Ok(());
+    }
+
+    if profile_changed || outcome.signed_rotated {
+        let bundle = published_bundle(state)?;
+        profile::update_subkey(&dht, &profile_key, profile::SUBKEY_PREKEY_BUNDLE, bundle)
+            .await
+            .map_err(|e| format!("publish prekey bundle: {e}"))?;
+    }
+
+    if profile_changed || pool_changed {
+        let entries = with_manager(state, |m| {
+            let mut entries = Vec::with_capacity(outcome.pool_ids.len());
+            for id in &outcome.pool_ids {
+                if let Some(public) = m.one_time_prekey_public(*id)? {
+                    entries.push(serde_json::to_vec(&public).unwrap_or_default());
+                }
+            }
+            Ok(entries)
+        })?;
+        profile::publish_one_time_prekeys(&dht, &profile_key, &entries)
+            .await
+            .map_err(|e| format!("publish one-time prekeys: {e}"))?;
+        tracing::debug!(count = entries.len(), "published one-time prekey pool");
+    }
+
+    *last_published = Some((profile_key, outcome.pool_ids.clone()));
+    Ok(())
+}
+
+/// Run `f` against the Signal session manager.
+fn with_manager<T>(
+    state: &Arc<AppState>,
+    f: impl FnOnce(&rekindle_crypto::SignalSessionManager) -> Result<T, rekindle_crypto::CryptoError>,
+) -> Result<T, String> {
+    let signal = state.signal_manager.lock();
+    let handle = signal.as_ref().ok_or("signal manager not initialized")?;
+    f(&handle.manager).map_err(|e| e.to_string())
+}
+
+/// ID of the newest retained signed prekey.
+fn current_signed_prekey_id(
+    manager: &rekindle_crypto::SignalSessionManager,
+) -> Result<u32, rekindle_crypto::CryptoError> {
+    manager
+        .signed_prekey_ids()?
+        .into_iter()
+        .max()
+        .ok_or_else(|| rekindle_crypto::CryptoError::PreKeyError("no signed prekey".into()))
+}
+
+/// `(id, created_at)` for every retained signed prekey.
+fn load_signed_prekeys(pool: &DbPool, owner_key: &str) -> Result<Vec<(u32, i64)>, String> {
+    let conn = pool.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, created_at FROM prekeys WHERE owner_key = ?1 AND is_signed = 1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// IDs of unconsumed pool keys (one-time prekeys that were never handed out).
+fn available_pool_prekeys(pool: &DbPool, owner_key: &str) -> Result<Vec<u32>, String> {
+    let conn = pool.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM prekeys WHERE owner_key = ?1 AND is_signed = 0 \
+             AND handed_out_at IS NULL ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// IDs of handed-out one-time prekeys older than `cutoff`.
+fn stale_handed_out_prekeys(pool: &DbPool, owner_key: &str, cutoff: i64) -> Result<Vec<u32>, String> {
+    let conn = pool.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM prekeys WHERE owner_key = ?1 AND is_signed = 0 \
+             AND handed_out_at IS NOT NULL AND handed_out_at < ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_key, cutoff], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Reserve `count` fresh one-time prekey IDs.
+///
+/// IDs come from a per-identity counter rather than `MAX(id)`, so an ID is
+/// never reused after its key has been consumed and deleted.
+fn allocate_prekey_ids(pool: &DbPool, owner_key: &str, count: u32) -> Result<Vec<u32>, String> {
+    let conn = pool.lock().map_err(|e| e.to_string())?;
+    let next: u32 = conn
+        .query_row(
+            "SELECT next_prekey_id FROM identity WHERE public_key = ?1",
+            rusqlite::params![owner_key],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("identity not found")?;
+    let end = next.checked_add(count).ok_or("one-time prekey IDs exhausted")?;
+    conn.execute(
+        "UPDATE identity SET next_prekey_id = ?1 WHERE public_key = ?2",
+        rusqlite::params![end, owner_key],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok((next..end).collect())
+}
@@ -2,8 +2,12 @@ use std::sync::Arc;
 
 use tauri::{Emitter, Manager};
 
+use rekindle_protocol::dht::community::{RoleDefinition, SUBKEY_CHANNELS, SUBKEY_MEMBERS, SUBKEY_ROLES};
+use rekindle_protocol::dht::paged_list::{ListChange, ListSync};
+
 use crate::channels::{NotificationEvent, PresenceEvent};
 use crate::db::{self, DbPool};
+use crate::services::community_service;
 use crate::state::{AppState, FriendshipState, GameInfoState, UserStatus};
 
 /// Handle a DHT value change event from a watched friend record.
@@ -13,6 +17,7 @@ use crate::state::{AppState, FriendshipState, GameInfoState, UserStatus};
 ///   2 = status enum
 ///   4 = game info
 ///   6 = route blob
+///   7 = linked devices
 pub async fn handle_value_change(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
@@ -40,6 +45,11 @@ pub async fn handle_value_change(
             2 => handle_status_change(app_handle, state, &friend_key, value),
             4 => handle_game_change(app_handle, state, &friend_key, value),
             6 => handle_route_change(state, &friend_key, value),
+            7 => {
+                let pool = app_handle.state::<DbPool>();
+                super::device_service::apply_published_devices(state, pool.inner(), &friend_key, value)
+                    .await;
+            }
             _ => tracing::trace!(subkey, "unhandled presence subkey change"),
         }
     }
@@ -313,7 +323,8 @@ async fn handle_community_metadata_change(
 
 /// Process a community channel list change (subkey 1).
 ///
-/// Re-reads the channel list from DHT and updates local state.
+/// Reads the changes since the revision we last applied (or the whole list)
+/// and updates local state.
 async fn handle_community_channel_list_change(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
@@ -321,81 +332,60 @@ async fn handle_community_channel_list_change(
     mgr: Option<&rekindle_protocol::dht::DHTManager>,
     dht_key: &str,
 ) {
-    let channel_data = if let Some(mgr) = mgr {
-        mgr.get_value(dht_key, 1).await.ok().flatten()
-    } else {
-        None
+    let Some(mgr) = mgr else {
+        return;
+    };
+    let sync = match community_service::sync_community_list(state, mgr, dht_key, SUBKEY_CHANNELS).await {
+        Ok(Some(sync)) => sync,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::debug!(community = %community_id, error = %e, "failed to read community channel list");
+            return;
+        }
     };
 
-    if let Some(data) = channel_data {
-        // Support both wrapped format { channels: [...], lastRefreshed } and bare array [...]
-        let channel_list_opt: Option<Vec<serde_json::Value>> =
-            serde_json::from_slice::<serde_json::Value>(&data)
-                .ok()
-                .and_then(|v| {
-                    if let Some(obj) = v.as_object() {
-                        obj.get("channels").and_then(|c| c.as_array().cloned())
-                    } else {
-                        v.as_array().cloned()
-                    }
-                });
-        if let Some(channel_list) = channel_list_opt {
-            let channels: Vec<crate::state::ChannelInfo> = channel_list
-                .iter()
-                .filter_map(|ch| {
-                    let id = ch.get("id")?.as_str()?.to_string();
-                    let name = ch.get("name")?.as_str()?.to_string();
-                    let ch_type = match ch.get("channelType").and_then(|v| v.as_str()) {
-                        Some("voice") => crate::state::ChannelType::Voice,
-                        _ => crate::state::ChannelType::Text,
-                    };
-                    Some(crate::state::ChannelInfo {
-                        id,
-                        name,
-                        channel_type: ch_type,
-                        unread_count: 0,
-                    })
-                })
-                .collect();
-
-            {
-                let mut communities = state.communities.write();
-                if let Some(community) = communities.get_mut(community_id) {
-                    community.channels.clone_from(&channels);
-                }
-            }
+    let channels = {
+        let mut communities = state.communities.write();
+        communities.get_mut(community_id).and_then(|community| {
+            community_service::apply_channel_sync(&mut community.channels, &sync)
+                .then(|| community.channels.clone())
+        })
+    };
+    let Some(channels) = channels else {
+        return;
+    };
 
-            // Persist to SQLite: DELETE + INSERT
-            let owner_key = state.identity.read().as_ref().map(|id| id.public_key.clone()).unwrap_or_default();
-            let pool: tauri::State<'_, DbPool> = app_handle.state();
-            let pool = pool.inner().clone();
-            let cid = community_id.to_string();
-            let _ = tokio::task::spawn_blocking(move || {
-                let conn = pool.lock().map_err(|e| e.to_string())?;
-                conn.execute(
-                    "DELETE FROM channels WHERE owner_key = ? AND community_id = ?",
-                    rusqlite::params![owner_key, cid],
-                ).map_err(|e| e.to_string())?;
-                for ch in &channels {
-                    let ch_type = match ch.channel_type {
-                        crate::state::ChannelType::Text => "text",
-                        crate::state::ChannelType::Voice => "voice",
-                    };
-                    conn.execute(
-                        "INSERT OR IGNORE INTO channels (owner_key, id, community_id, name, channel_type) VALUES (?, ?, ?, ?, ?)",
-                        rusqlite::params![owner_key, ch.id, cid, ch.name, ch_type],
-                    ).map_err(|e| e.to_string())?;
-                }
-                Ok::<_, String>(())
-            }).await;
+    // Persist to SQLite: DELETE + INSERT
+    let owner_key = state.identity.read().as_ref().map(|id| id.public_key.clone()).unwrap_or_default();
+    let pool: tauri::State<'_, DbPool> = app_handle.state();
+    let pool = pool.inner().clone();
+    let cid = community_id.to_string();
+    let _ = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM channels WHERE owner_key = ? AND community_id = ?",
+            rusqlite::params![owner_key, cid],
+        ).map_err(|e| e.to_string())?;
+        for ch in &channels {
+            let ch_type = match ch.channel_type {
+                crate::state::ChannelType::Text => "text",
+                crate::state::ChannelType::Voice => "voice",
+            };
+            conn.execute(
+                "INSERT OR IGNORE INTO channels (owner_key, id, community_id, name, channel_type, retention_secs) \
+                 VALUES (?, ?, ?, ?, ?, NULLIF(?, 0))",
+                rusqlite::params![owner_key, ch.id, cid, ch.name, ch_type, ch.retention_secs],
+            ).map_err(|e| e.to_string())?;
         }
-    }
+        Ok::<_, String>(())
+    }).await;
     tracing::debug!(community = %community_id, "community channel list updated");
 }
 
 /// Process a community member list change (subkey 2).
 ///
-/// Re-reads the member list from DHT and persists it to the local database.
+/// Reads the changes since the revision we last applied (or the whole list)
+/// and persists them to the local database.
 async fn handle_community_member_list_change(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
@@ -403,32 +393,56 @@ async fn handle_community_member_list_change(
     mgr: Option<&rekindle_protocol::dht::DHTManager>,
     dht_key: &str,
 ) {
-    let member_data = if let Some(mgr) = mgr {
-        mgr.get_value(dht_key, 2).await.ok().flatten()
-    } else {
-        None
-    };
-
-    let Some(data) = member_data else {
-        tracing::debug!(community = %community_id, "community member list updated (no data)");
+    let Some(mgr) = mgr else {
         return;
     };
+    let sync = match community_service::sync_community_list(state, mgr, dht_key, SUBKEY_MEMBERS).await {
+        Ok(Some(sync)) => sync,
+        Ok(None) => {
+            tracing::debug!(community = %community_id, "community member list updated (no data)");
+            return;
+        }
+        Err(e) => {
+            tracing::debug!(community = %community_id, error = %e, "failed to read community member list");
+            return;
+        }
+    };
 
-    // Parse member list JSON: wrapped { members: [...], lastRefreshed } or bare [...]
-    let member_list: Vec<serde_json::Value> = match serde_json::from_slice::<serde_json::Value>(&data) {
-        Ok(v) => {
-            if let Some(obj) = v.as_object() {
-                obj.get("members").and_then(|m| m.as_array().cloned()).unwrap_or_default()
-            } else {
-                v.as_array().cloned().unwrap_or_default()
+    // Members to write, members to delete, and whether the upserts are the
+    // whole list
+    let (member_list, removed, whole_list) = match sync {
+        ListSync::Unchanged => return,
+        ListSync::Legacy(data) => {
+            // Wrapped { members: [...], lastRefreshed } or bare [...]
+            let member_list: Vec<serde_json::Value> = match serde_json::from_slice::<serde_json::Value>(&data) {
+                Ok(v) => {
+                    if let Some(obj) = v.as_object() {
+                        obj.get("members").and_then(|m| m.as_array().cloned()).unwrap_or_default()
+                    } else {
+                        v.as_array().cloned().unwrap_or_default()
+                    }
+                }
+                Err(_) => return,
+            };
+            if member_list.is_empty() && data.len() > 2 {
+                // Data was non-trivial but couldn't parse — skip silently
+                return;
             }
+            (member_list, Vec::new(), false)
+        }
+        ListSync::Full { entries, .. } => (entries.into_iter().map(|e| e.entry).collect(), Vec::new(), true),
+        ListSync::Changes { changes, .. } => {
+            let mut upserts = Vec::new();
+            let mut removed = Vec::new();
+            for change in changes {
+                match change {
+                    ListChange::Upsert(entry) => upserts.push(entry.entry),
+                    ListChange::Remove { id } => removed.push(id),
+                }
+            }
+            (upserts, removed, false)
         }
-        Err(_) => return,
     };
-    if member_list.is_empty() && data.len() > 2 {
-        // Data was non-trivial but couldn't parse — skip silently
-        return;
-    }
 
     let member_count = member_list.len();
     let pool: tauri::State<'_, DbPool> = app_handle.state();
@@ -442,6 +456,20 @@ async fn handle_community_member_list_change(
         .unwrap_or_default();
     if let Err(e) = tokio::task::spawn_blocking(move || {
         let conn = pool.lock().map_err(|e| e.to_string())?;
+        if whole_list {
+            conn.execute(
+                "DELETE FROM community_members WHERE owner_key = ? AND community_id = ?",
+                rusqlite::params![owner_key, cid],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        for pk in &removed {
+            conn.execute(
+                "DELETE FROM community_members WHERE owner_key = ? AND community_id = ? AND pseudonym_key = ?",
+                rusqlite::params![owner_key, cid, pk],
+            )
+            .map_err(|e| e.to_string())?;
+        }
         for member in &member_list {
             let Some(pk) = member.get("pseudonymKey").and_then(|v| v.as_str()) else {
                 continue;
@@ -457,11 +485,12 @@ async fn handle_community_member_list_change(
                 .get("joinedAt")
                 .and_then(serde_json::Value::as_i64)
                 .unwrap_or_else(crate::db::timestamp_now);
+            let timeout_until = member.get("timeoutUntil").and_then(serde_json::Value::as_i64);
             conn.execute(
                 "INSERT OR REPLACE INTO community_members \
-                 (owner_key, community_id, pseudonym_key, display_name, role_ids, joined_at) \
-                 VALUES (?, ?, ?, ?, ?, ?)",
-                rusqlite::params![owner_key, cid, pk, dn, role_ids, joined_at],
+                 (owner_key, community_id, pseudonym_key, display_name, role_ids, timeout_until, joined_at) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                rusqlite::params![owner_key, cid, pk, dn, role_ids, timeout_until, joined_at],
             )
             .map_err(|e| e.to_string())?;
         }
@@ -485,8 +514,9 @@ async fn handle_community_member_list_change(
 
 /// Handle DHT subkey 3 change: community role definitions updated.
 ///
-/// Reads the updated role list from DHT, updates in-memory `CommunityState.roles`,
-/// persists to the `community_roles` table, and emits a `RolesChanged` event.
+/// Reads the changes since the revision we last applied (or the whole
+/// list), updates in-memory `CommunityState.roles`, persists to the
+/// `community_roles` table, and emits a `RolesChanged` event.
 async fn handle_community_roles_change(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
@@ -499,8 +529,9 @@ async fn handle_community_roles_change(
         return;
     };
 
-    let roles = match rekindle_protocol::dht::community::read_roles(mgr, dht_key).await {
-        Ok(r) => r,
+    let sync = match community_service::sync_community_list(state, mgr, dht_key, SUBKEY_ROLES).await {
+        Ok(Some(sync)) => sync,
+        Ok(None) => return,
         Err(e) => {
             tracing::warn!(community = %community_id, error = %e, "failed to read roles from DHT");
             return;
@@ -508,25 +539,17 @@ async fn handle_community_roles_change(
     };
 
     // Update in-memory state
-    let role_defs: Vec<crate::state::RoleDefinition> = roles
-        .iter()
-        .map(|r| crate::state::RoleDefinition {
-            id: r.id,
-            name: r.name.clone(),
-            color: r.color,
-            permissions: r.permissions,
-            position: r.position,
-            hoist: r.hoist,
-            mentionable: r.mentionable,
-        })
-        .collect();
-    {
+    let role_defs = {
         let mut communities = state.communities.write();
-        if let Some(c) = communities.get_mut(community_id) {
-            c.roles.clone_from(&role_defs);
-            c.my_role = Some(crate::state::display_role_name(&c.my_role_ids, &c.roles));
+        let Some(c) = communities.get_mut(community_id) else {
+            return;
+        };
+        if !apply_role_sync(&mut c.roles, &sync) {
+            return;
         }
-    }
+        c.my_role = Some(crate::state::display_role_name(&c.my_role_ids, &c.roles));
+        c.roles.clone()
+    };
 
     // Persist to SQLite
     let owner_key = state
@@ -594,6 +617,55 @@ async fn handle_community_roles_change(
     );
 }
 
+/// Apply a role list sync to `roles`. Returns whether anything changed.
+fn apply_role_sync(roles: &mut Vec<crate::state::RoleDefinition>, sync: &ListSync) -> bool {
+    let to_state = |r: RoleDefinition| crate::state::RoleDefinition {
+        id: r.id,
+        name: r.name,
+        color: r.color,
+        permissions: r.permissions,
+        position: r.position,
+        hoist: r.hoist,
+        mentionable: r.mentionable,
+    };
+    match sync {
+        ListSync::Unchanged => false,
+        ListSync::Legacy(data) => {
+            let Ok((_, _, legacy)) = rekindle_protocol::capnp_codec::community::decode_community(data, "") else {
+                return false;
+            };
+            *roles = legacy.into_iter().map(to_state).collect();
+            true
+        }
+        ListSync::Full { entries, .. } => {
+            *roles = entries
+                .iter()
+                .filter_map(|e| e.decode::<RoleDefinition>().ok())
+                .map(to_state)
+                .collect();
+            true
+        }
+        ListSync::Changes { changes, .. } => {
+            for change in changes {
+                match change {
+                    ListChange::Upsert(entry) => {
+                        let Ok(role) = entry.decode::<RoleDefinition>() else {
+                            continue;
+                        };
+                        let role = to_state(role);
+                        match roles.iter_mut().find(|r| r.id == role.id) {
+                            Some(existing) => *existing = role,
+                            None => roles.push(role),
+                        }
+                    }
+                    ListChange::Remove { id } => roles.retain(|r| r.id.to_string() != *id),
+                }
+            }
+            !changes.is_empty()
+        }
+    }
+}
+
 /// Handle DHT subkey 5 change: MEK bundles updated.
 ///
 /// When the server publishes new MEK bundles (e.g., after rotation), re-fetch from server
@@ -0,0 +1,270 @@
+//! Delivery and read receipts for direct messages.
+//!
+//! Every DM we send carries a global message ID and a `delivery_state`:
+//! `queued` until it leaves for the peer's route, `sent` once it has,
+//! `delivered` when the peer acknowledges storing it and `read` when they
+//! open the conversation. States only move forward — a late delivery
+//! receipt never downgrades a message the peer has already read.
+//!
+//! Receipts are batched per peer for a couple of seconds so a burst of
+//! messages is acknowledged with one envelope rather than one per message.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rekindle_protocol::messaging::envelope::MAX_RECEIPT_BATCH;
+use tauri::Emitter;
+use tauri_plugin_store::StoreExt;
+
+use crate::channels::ChatEvent;
+use crate::db::DbPool;
+use crate::services::message_service;
+use crate::state::AppState;
+
+/// How long to gather receipts for a peer before sending them.
+const FLUSH_DELAY: Duration = Duration::from_secs(2);
+
+/// Which acknowledgement a receipt carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptKind {
+    Delivered,
+    Read,
+}
+
+/// Where one of our DMs stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryState {
+    Queued,
+    Sent,
+    Delivered,
+    Read,
+    Failed,
+}
+
+impl DeliveryState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Sent => "sent",
+            Self::Delivered => "delivered",
+            Self::Read => "read",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// States a message may move into this one from.
+    ///
+    /// A failed message can still be delivered: the peer may have received
+    /// it on an attempt whose confirmation was lost.
+    fn predecessors(self) -> &'static [&'static str] {
+        match self {
+            Self::Queued => &[],
+            Self::Sent => &["queued", "failed"],
+            Self::Delivered => &["queued", "failed", "sent"],
+            Self::Read => &["queued", "failed", "sent", "delivered"],
+            Self::Failed => &["queued"],
+        }
+    }
+}
+
+impl From<ReceiptKind> for DeliveryState {
+    fn from(kind: ReceiptKind) -> Self {
+        match kind {
+            ReceiptKind::Delivered => Self::Delivered,
+            ReceiptKind::Read => Self::Read,
+        }
+    }
+}
+
+/// Move our DMs to `peer` into `new` and tell the frontend which changed.
+///
+/// IDs that are unknown, not ours, or already past `new` are ignored.
+pub async fn set_delivery_state(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    peer: &str,
+    message_ids: &[String],
+    new: DeliveryState,
+) {
+    if message_ids.is_empty() {
+        return;
+    }
+    let Some(owner_key) = state
+        .identity
+        .read()
+        .as_ref()
+        .map(|id| id.public_key.clone())
+    else {
+        return;
+    };
+    let pool = pool.clone();
+    let peer_owned = peer.to_string();
+    let ids = message_ids.to_vec();
+    let changed = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        let allowed = new
+            .predecessors()
+            .iter()
+            .map(|s| format!("'{s}'"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = conn
+            .prepare(&format!(
+                "UPDATE messages SET delivery_state = ?1 \
+                 WHERE owner_key = ?2 AND conversation_id = ?3 AND conversation_type = 'dm' \
+                 AND sender_key = ?2 AND message_id = ?4 AND delivery_state IN ({allowed})"
+            ))
+            .map_err(|e| e.to_string())?;
+        let mut changed = Vec::new();
+        for id in ids {
+            let rows = stmt
+                .execute(rusqlite::params![new.as_str(), owner_key, peer_owned, id])
+                .map_err(|e| e.to_string())?;
+            if rows > 0 {
+                changed.push(id);
+            }
+        }
+        Ok::<_, String>(changed)
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+
+    match changed {
+        Ok(changed) if !changed.is_empty() => {
+            let _ = app_handle.emit(
+                "chat-event",
+                &ChatEvent::DeliveryStateChanged {
+                    conversation_id: peer.to_string(),
+                    message_ids: changed,
+                    state: new.as_str().to_string(),
+                },
+            );
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!(peer = %peer, error = %e, "failed to update delivery state"),
+    }
+}
+
+/// Queue receipts for messages from `peer`, sending them after a short delay.
+pub fn queue_receipts(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    peer: &str,
+    message_ids: &[String],
+    kind: ReceiptKind,
+) {
+    if message_ids.is_empty() {
+        return;
+    }
+    let schedule_flush = {
+        let mut pending = state.pending_receipts.lock();
+        let batch = pending.entry(peer.to_string()).or_default();
+        // The first receipt in an empty batch schedules its flush; later
+        // ones ride along with it.
+        let was_empty = batch.delivered.is_empty() && batch.read.is_empty();
+        let list = match kind {
+            ReceiptKind::Delivered => &mut batch.delivered,
+            ReceiptKind::Read => &mut batch.read,
+        };
+        for id in message_ids {
+            if !list.contains(id) {
+                list.push(id.clone());
+            }
+        }
+        was_empty
+    };
+    if !schedule_flush {
+        return;
+    }
+
+    let state = Arc::clone(state);
+    let pool = pool.clone();
+    let peer = peer.to_string();
+    tokio::spawn(async move {
+        tokio::time::sleep(FLUSH_DELAY).await;
+        flush_receipts(&state, &pool, &peer).await;
+    });
+}
+
+/// Send everything batched for `peer`.
+///
+/// Sends that fail are queued in `pending_messages` like any other
+/// envelope, so receipts survive the peer being offline.
+async fn flush_receipts(state: &Arc<AppState>, pool: &DbPool, peer: &str) {
+    let Some(batch) = state.pending_receipts.lock().remove(peer) else {
+        return;
+    };
+    let kinds = [
+        (ReceiptKind::Delivered, batch.delivered),
+        (ReceiptKind::Read, batch.read),
+    ];
+    for (kind, ids) in kinds {
+        for chunk in ids.chunks(MAX_RECEIPT_BATCH) {
+            if let Err(e) =
+                message_service::send_receipt(state, pool, peer, kind, chunk.to_vec()).await
+            {
+                tracing::warn!(peer = %peer, error = %e, "failed to send receipt");
+            }
+        }
+    }
+}
+
+/// Apply a receipt from `peer` to our messages.
+///
+/// A receipt also proves the peer has the message, so any copy still
+/// waiting in `pending_messages` is dropped rather than sent again.
+pub async fn handle_receipt(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    peer: &str,
+    message_ids: &[String],
+    kind: ReceiptKind,
+) {
+    let ids = &message_ids[..message_ids.len().min(MAX_RECEIPT_BATCH)];
+    if ids.is_empty() {
+        return;
+    }
+    tracing::debug!(from = %peer, count = ids.len(), ?kind, "receipt received");
+    set_delivery_state(app_handle, state, pool, peer, ids, kind.into()).await;
+
+    let Some(owner_key) = state
+        .identity
+        .read()
+        .as_ref()
+        .map(|id| id.public_key.clone())
+    else {
+        return;
+    };
+    let pool = pool.clone();
+    let peer_owned = peer.to_string();
+    let ids = ids.to_vec();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        for id in ids {
+            conn.execute(
+                "DELETE FROM pending_messages WHERE owner_key = ? AND recipient_key = ? AND message_id = ?",
+                rusqlite::params![owner_key, peer_owned, id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok::<_, String>(())
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    if let Err(e) = result {
+        tracing::warn!(peer = %peer, error = %e, "failed to clear acknowledged pending messages");
+    }
+}
+
+/// Whether the user lets peers see when they've read a message.
+pub fn read_receipts_enabled(app_handle: &tauri::AppHandle) -> bool {
+    let Ok(store) = app_handle.store("preferences.json") else {
+        return true;
+    };
+    store
+        .get("preferences")
+        .and_then(|v| v.get("sendReadReceipts")?.as_bool())
+        .unwrap_or(true)
+}
@@ -1,9 +1,13 @@
 use std::sync::Arc;
 
+use rekindle_protocol::dht::community::SUBKEY_CHANNELS;
+use rekindle_protocol::dht::profile::SUBKEY_DEVICES;
 use rekindle_protocol::messaging::envelope::MessageEnvelope;
 use tokio::sync::mpsc;
 
 use crate::db::DbPool;
+use crate::services::community_service;
+use crate::services::receipt_service::{self, DeliveryState};
 use crate::state::{AppState, FriendshipState};
 
 /// Start the periodic sync service.
@@ -12,6 +16,7 @@ use crate::state::{AppState, FriendshipState};
 /// - Pull: Read latest from DHT -> update `SQLite`
 /// - Push: Send local changes to DHT
 /// - Retry: Attempt to deliver queued pending messages
+/// - Resume: Restart file transfers cut off by a restart or lost connection
 pub async fn start_sync_loop(
     state: Arc<AppState>,
     pool: DbPool,
@@ -44,9 +49,13 @@ pub async fn start_sync_loop(
                 if let Err(e) = sync_communities(&state, &pool).await {
                     tracing::warn!(error = %e, "community sync failed");
                 }
-                if let Err(e) = retry_pending_messages(&state, &pool).await {
+                if let Err(e) = retry_pending_messages(&app_handle, &state, &pool).await {
                     tracing::warn!(error = %e, "pending message retry failed");
                 }
+                crate::services::file_transfer_service::resume_transfers(&app_handle, &state, &pool).await;
+                if let Err(e) = crate::services::disappearing_service::purge_expired(&app_handle, &pool).await {
+                    tracing::warn!(error = %e, "expired message purge failed");
+                }
                 // Every ~6th tick (~3 minutes) — expire stale pending requests
                 if tick_count.is_multiple_of(6) {
                     expire_stale_requests(&state, &pool, &app_handle).await;
@@ -315,6 +324,7 @@ async fn sync_friend_dht_subkeys(
     sync_friend_status(state, routing_context, friend_key, &record_key, app_handle, force_refresh).await;
     sync_friend_game_info(state, routing_context, friend_key, &record_key, app_handle, force_refresh).await;
     sync_friend_prekey(state, routing_context, friend_key, &record_key, force_refresh).await;
+    sync_friend_devices(state, routing_context, friend_key, &record_key, app_handle, force_refresh).await;
     sync_friend_route_blob(state, routing_context, friend_key, record_key, force_refresh).await;
 }
 
@@ -490,6 +500,27 @@ async fn sync_friend_prekey(
     }
 }
 
+/// Read the friend's linked devices (subkey 7) from DHT.
+async fn sync_friend_devices(
+    state: &Arc<AppState>,
+    routing_context: &veilid_core::RoutingContext,
+    friend_key: &str,
+    record_key: &veilid_core::RecordKey,
+    app_handle: &tauri::AppHandle,
+    force_refresh: bool,
+) {
+    use tauri::Manager;
+
+    if let Ok(Some(value_data)) = routing_context
+        .get_dht_value(record_key.clone(), SUBKEY_DEVICES, force_refresh)
+        .await
+    {
+        let pool = app_handle.state::<DbPool>();
+        super::device_service::apply_published_devices(state, pool.inner(), friend_key, value_data.data())
+            .await;
+    }
+}
+
 /// Read route blob (subkey 6) from DHT and cache it.
 async fn sync_friend_route_blob(
     state: &Arc<AppState>,
@@ -659,35 +690,29 @@ async fn sync_single_conversation(
     let _ = record.close().await;
 }
 
-/// Parse a DHT channel list from JSON (supports both wrapped and bare array format).
-fn parse_dht_channel_list(data: &[u8]) -> Vec<crate::state::ChannelInfo> {
-    let channel_list: Vec<serde_json::Value> = match serde_json::from_slice::<serde_json::Value>(data) {
-        Ok(v) => {
-            if let Some(obj) = v.as_object() {
-                obj.get("channels").and_then(|c| c.as_array().cloned()).unwrap_or_default()
-            } else {
-                v.as_array().cloned().unwrap_or_default()
-            }
+/// Record each channel's retention, which new channel messages take their
+/// expiry from.
+async fn store_channel_retention(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    channels: &[crate::state::ChannelInfo],
+) -> Result<(), String> {
+    let owner_key = crate::commands::auth::current_owner_key(state)?;
+    let retention: Vec<(String, u32)> = channels.iter().map(|ch| (ch.id.clone(), ch.retention_secs)).collect();
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.lock().map_err(|e| e.to_string())?;
+        for (channel_id, secs) in retention {
+            conn.execute(
+                "UPDATE channels SET retention_secs = NULLIF(?, 0) WHERE owner_key = ? AND id = ?",
+                rusqlite::params![secs, owner_key, channel_id],
+            )
+            .map_err(|e| e.to_string())?;
         }
-        Err(_) => return vec![],
-    };
-    channel_list
-        .iter()
-        .filter_map(|ch| {
-            let id = ch.get("id")?.as_str()?.to_string();
-            let name = ch.get("name")?.as_str()?.to_string();
-            let ch_type = match ch.get("channelType").and_then(|v| v.as_str()) {
-                Some("voice") => crate::state::ChannelType::Voice,
-                _ => crate::state::ChannelType::Text,
-            };
-            Some(crate::state::ChannelInfo {
-                id,
-                name,
-                channel_type: ch_type,
-                unread_count: 0,
-            })
-        })
-        .collect()
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 /// Sync communities: read community DHT records and update local state.
@@ -759,13 +784,18 @@ async fn sync_communities(state: &Arc<AppState>, pool: &DbPool) -> Result<(), St
         }
 
         // Read channel list subkey (1) from DHT
-        match mgr.get_value(dht_key, 1).await {
-            Ok(Some(data)) => {
-                let channels = parse_dht_channel_list(&data);
-                if !channels.is_empty() {
+        match community_service::sync_community_list(state, &mgr, dht_key, SUBKEY_CHANNELS).await {
+            Ok(Some(sync)) => {
+                let channels = {
                     let mut communities = state.communities.write();
-                    if let Some(community) = communities.get_mut(community_id) {
-                        community.channels = channels;
+                    communities.get_mut(community_id).and_then(|community| {
+                        community_service::apply_channel_sync(&mut community.channels, &sync)
+                            .then(|| community.channels.clone())
+                    })
+                };
+                if let Some(channels) = channels {
+                    if let Err(e) = store_channel_retention(state, pool, &channels).await {
+                        tracing::warn!(error = %e, community = %community_id, "failed to store channel retention");
                     }
                 }
             }
@@ -856,9 +886,13 @@ async fn sync_community_server_route(
 /// directly via Veilid (the body is a JSON-serialized `MessageEnvelope`).
 /// Deletes on success, increments `retry_count` on failure.
 /// Messages exceeding 20 retries (~10 minutes at 30 s intervals) are dropped.
-async fn retry_pending_messages(state: &Arc<AppState>, pool: &DbPool) -> Result<(), String> {
-    const MAX_RETRIES: i64 = 20;
-
+/// DMs track the outcome in their `delivery_state`: `sent` once delivered,
+/// `failed` once dropped.
+async fn retry_pending_messages(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+) -> Result<(), String> {
     // Step 1: Read all pending messages from DB (scoped to current identity)
     let owner_key = state
         .identity
@@ -866,22 +900,23 @@ async fn retry_pending_messages(state: &Arc<AppState>, pool: &DbPool) -> Result<
         .as_ref()
         .map(|id| id.public_key.clone())
         .unwrap_or_default();
-    let pending: Vec<(i64, String, String, i64)> = {
+    let pending: Vec<PendingMessage> = {
         let pool = pool.clone();
         let ok = owner_key;
         tokio::task::spawn_blocking(move || {
             let conn = pool.lock().map_err(|e| e.to_string())?;
             let mut stmt = conn
-                .prepare("SELECT id, recipient_key, body, retry_count FROM pending_messages WHERE owner_key = ?1 ORDER BY id")
+                .prepare("SELECT id, recipient_key, body, retry_count, message_id FROM pending_messages WHERE owner_key = ?1 ORDER BY id")
                 .map_err(|e| e.to_string())?;
             let rows = stmt
                 .query_map(rusqlite::params![ok], |row| {
-                    Ok((
-                        row.get::<_, i64>(0)?,
-                        row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?,
-                        row.get::<_, i64>(3)?,
-                    ))
+                    Ok(PendingMessage {
+                        id: row.get(0)?,
+                        recipient_key: row.get(1)?,
+                        body: row.get(2)?,
+                        retry_count: row.get(3)?,
+                        message_id: row.get(4)?,
+                    })
                 })
                 .map_err(|e| e.to_string())?;
             let mut results = Vec::new();
@@ -900,24 +935,42 @@ async fn retry_pending_messages(state: &Arc<AppState>, pool: &DbPool) -> Result<
 
     tracing::debug!(count = pending.len(), "retrying pending messages");
 
-    for (id, recipient_key, body, retry_count) in pending {
-        retry_single_pending(state, pool, id, &recipient_key, &body, retry_count, MAX_RETRIES).await?;
+    for message in &pending {
+        retry_single_pending(app_handle, state, pool, message).await?;
     }
 
     Ok(())
 }
 
+/// Retries before a pending message is dropped.
+const MAX_RETRIES: i64 = 20;
+
+/// A row of `pending_messages`.
+struct PendingMessage {
+    id: i64,
+    recipient_key: String,
+    body: String,
+    retry_count: i64,
+    /// Global ID of the DM the envelope carries, if any.
+    message_id: Option<String>,
+}
+
 /// Attempt to deliver a single pending message, dropping or incrementing on failure.
 async fn retry_single_pending(
+    app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
     pool: &DbPool,
-    id: i64,
-    recipient_key: &str,
-    body: &str,
-    retry_count: i64,
-    max_retries: i64,
+    pending: &PendingMessage,
 ) -> Result<(), String> {
-    if retry_count >= max_retries {
+    let PendingMessage {
+        id,
+        ref recipient_key,
+        ref body,
+        retry_count,
+        ..
+    } = *pending;
+
+    if retry_count >= MAX_RETRIES {
         tracing::warn!(
             id,
             to = %recipient_key,
@@ -925,12 +978,15 @@ async fn retry_single_pending(
             "pending message exceeded max retries — dropping"
         );
         delete_pending_message(pool, id).await?;
+        set_dm_state(app_handle, state, pool, pending, DeliveryState::Failed).await;
         return Ok(());
     }
 
     // Try DM envelope first (existing logic), then channel message retry
     if let Ok(envelope) = serde_json::from_str::<MessageEnvelope>(body) {
-        retry_pending_dm(state, pool, id, recipient_key, &envelope).await?;
+        if retry_pending_dm(state, pool, id, recipient_key, &envelope).await? {
+            set_dm_state(app_handle, state, pool, pending, DeliveryState::Sent).await;
+        }
     } else if let Ok(channel_msg) =
         serde_json::from_str::<crate::commands::community::PendingChannelMessage>(body)
     {
@@ -946,16 +1002,7 @@ async fn retry_single_pending(
             return Ok(());
         };
 
-        match crate::commands::community::send_encrypted_to_server(
-            state,
-            &channel_msg.channel_id,
-            &channel_msg.community_id,
-            channel_msg.ciphertext,
-            channel_msg.mek_generation,
-            channel_msg.timestamp,
-            route_blob,
-        )
-        .await
+        match crate::commands::community::send_encrypted_to_server(state, &channel_msg, route_blob).await
         {
             Ok(()) => {
                 tracing::debug!(id, "pending channel message delivered");
@@ -974,14 +1021,37 @@ async fn retry_single_pending(
     Ok(())
 }
 
+/// Record the outcome of a pending DM. Other envelopes have no state to track.
+async fn set_dm_state(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    pending: &PendingMessage,
+    new: DeliveryState,
+) {
+    if let Some(message_id) = &pending.message_id {
+        receipt_service::set_delivery_state(
+            app_handle,
+            state,
+            pool,
+            &pending.recipient_key,
+            std::slice::from_ref(message_id),
+            new,
+        )
+        .await;
+    }
+}
+
 /// Retry delivering a single pending DM envelope via cached route or mailbox fallback.
+///
+/// Returns whether it was delivered.
 async fn retry_pending_dm(
     state: &Arc<AppState>,
     pool: &DbPool,
     id: i64,
     recipient_key: &str,
     envelope: &MessageEnvelope,
-) -> Result<(), String> {
+) -> Result<bool, String> {
     // Look up route and import RouteId via cache.
     // Clone Arc-based handles out before any .await (parking_lot guards are !Send).
     let route_id_and_rc = {
@@ -992,7 +1062,7 @@ async fn retry_pending_dm(
         };
         let Some((api, rc)) = api_and_rc else {
             increment_retry_count(pool, id).await?;
-            return Ok(());
+            return Ok(false);
         };
 
         let mut dht_mgr = state.dht_manager.write();
@@ -1026,7 +1096,7 @@ async fn retry_pending_dm(
 
     let Some((route_id, routing_context)) = route_id_and_rc else {
         increment_retry_count(pool, id).await?;
-        return Ok(());
+        return Ok(false);
     };
 
     match rekindle_protocol::messaging::sender::send_envelope(
@@ -1039,14 +1109,14 @@ async fn retry_pending_dm(
         Ok(()) => {
             tracing::debug!(id, to = %recipient_key, "pending DM delivered successfully");
             delete_pending_message(pool, id).await?;
+            Ok(true)
         }
         Err(e) => {
             tracing::debug!(id, to = %recipient_key, error = %e, "pending DM retry failed");
             increment_retry_count(pool, id).await?;
+            Ok(false)
         }
     }
-
-    Ok(())
 }
 
 /// Delete a single pending message by ID.
@@ -1070,13 +1140,8 @@ async fn try_mailbox_route_fallback(
     state: &Arc<AppState>,
     recipient_key: &str,
 ) -> Option<(veilid_core::RouteId, veilid_core::RoutingContext)> {
-    // Look up the friend's mailbox key
-    let mailbox_key = {
-        let friends = state.friends.read();
-        friends
-            .get(recipient_key)
-            .and_then(|f| f.mailbox_dht_key.clone())
-    }?;
+    // Look up the recipient's mailbox key (a friend's, or a linked device's)
+    let mailbox_key = super::device_service::mailbox_for_address(state, recipient_key)?;
 
     let rc = {
         let node = state.node.read();
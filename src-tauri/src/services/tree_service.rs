@@ -0,0 +1,467 @@
+//! Client side of `TreeKEM` communities (`rekindle_crypto::group::tree`).
+//!
+//! Our view of the group — the public tree, the path secrets we know and
+//! the current epoch secret — lives in `AppState::tree_groups` and is
+//! mirrored to Stronghold under `keychain::tree_key_name`. Each epoch's MEK
+//! goes into the community keyring like any other generation, so message
+//! encryption doesn't care where a MEK came from.
+//!
+//! To get into the tree we publish a key package; a member with
+//! `MANAGE_COMMUNITY` adds us in their next commit and the server keeps the
+//! welcome for us. The server orders commits — the first one for each epoch
+//! wins — and we replay them with `GetCommits` whenever the tree moves on.
+//! If a commit removes us, or we can't follow one, we drop our state and
+//! publish a new key package to be added back.
+
+use std::sync::Arc;
+
+use ed25519_dalek::SigningKey;
+use rekindle_crypto::group::media_key::MediaEncryptionKey;
+use rekindle_crypto::group::pseudonym::derive_community_pseudonym;
+use rekindle_crypto::group::tree::{
+    Commit, KeyPackage, KeyPackageSecret, Proposal, TreeGroup, Welcome,
+};
+use rekindle_crypto::keychain::{tree_key_name, VAULT_COMMUNITIES};
+use rekindle_crypto::{CryptoError, Keychain as _};
+use rekindle_protocol::dht::community::permissions;
+use rekindle_protocol::messaging::{
+    CommunityRequest, CommunityResponse, TreeCommitDto, TreeWelcomeDto,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::community::send_community_rpc;
+use crate::db::DbPool;
+use crate::keystore::KeystoreHandle;
+use crate::state::AppState;
+
+/// Our `TreeKEM` state for one community.
+#[derive(Default, Serialize, Deserialize)]
+pub struct TreeMembership {
+    /// Our view of the group, once someone has added us.
+    group: Option<TreeGroup>,
+    /// Secret half of the key package we last published.
+    pending: Option<KeyPackageSecret>,
+}
+
+/// Whether we take part in (or are waiting to join) a community's tree.
+pub fn is_tree_community(
+    state: &Arc<AppState>,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+) -> bool {
+    ensure_loaded(state, keystore_handle, community_id);
+    state.tree_groups.lock().contains_key(community_id)
+}
+
+/// Owner: switch the community to a `TreeKEM` group with ourselves as its
+/// only member.
+///
+/// The lone-member epoch's MEK is not used — nobody else could read it.
+/// The first commit adding members produces the first shared MEK.
+pub async fn enable(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+) -> Result<(), String> {
+    let response =
+        send_community_rpc(state, pool, community_id, CommunityRequest::EnableTreeKem).await?;
+    let epoch = match response {
+        CommunityResponse::TreeEpoch { epoch } => epoch,
+        CommunityResponse::Error { message, .. } => {
+            return Err(format!("server rejected TreeKEM mode: {message}"));
+        }
+        other => return Err(format!("unexpected response: {other:?}")),
+    };
+
+    let signing_key = pseudonym(state, community_id)?;
+    ensure_loaded(state, keystore_handle, community_id);
+    {
+        let mut groups = state.tree_groups.lock();
+        let membership = groups.entry(community_id.to_string()).or_default();
+        if membership.group.is_none() {
+            membership.group = Some(TreeGroup::create(
+                community_id.as_bytes(),
+                &signing_key,
+                epoch,
+            ));
+        }
+    }
+    persist(state, keystore_handle, community_id);
+
+    tracing::info!(community = %community_id, epoch, "TreeKEM group created");
+    Ok(())
+}
+
+/// Publish a fresh key package so a key holder adds us to the tree.
+pub async fn publish_key_package(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+) -> Result<(), String> {
+    let signing_key = pseudonym(state, community_id)?;
+    let (key_package, secret) = KeyPackage::generate(&signing_key);
+    let key_package =
+        serde_json::to_vec(&key_package).map_err(|e| format!("serialize key package: {e}"))?;
+
+    ensure_loaded(state, keystore_handle, community_id);
+    state
+        .tree_groups
+        .lock()
+        .entry(community_id.to_string())
+        .or_default()
+        .pending = Some(secret);
+    persist(state, keystore_handle, community_id);
+
+    let response = send_community_rpc(
+        state,
+        pool,
+        community_id,
+        CommunityRequest::PublishKeyPackage { key_package },
+    )
+    .await?;
+    match response {
+        CommunityResponse::Ok => {
+            tracing::info!(community = %community_id, "published TreeKEM key package");
+            Ok(())
+        }
+        CommunityResponse::Error { message, .. } => {
+            Err(format!("server rejected key package: {message}"))
+        }
+        other => Err(format!("unexpected response: {other:?}")),
+    }
+}
+
+/// Catch up with the server: join from our welcome if we are not in the
+/// tree yet, then apply every commit since our epoch.
+///
+/// Returns the MEK of each epoch we moved through, oldest first.
+pub async fn sync(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+) -> Result<Vec<MediaEncryptionKey>, String> {
+    ensure_loaded(state, keystore_handle, community_id);
+    let mut meks = Vec::new();
+    let lost = loop {
+        // Without a group there is nothing to replay — we only want the welcome
+        let since_epoch = current_epoch(state, community_id).unwrap_or(u64::MAX);
+        let response = send_community_rpc(
+            state,
+            pool,
+            community_id,
+            CommunityRequest::GetCommits { since_epoch },
+        )
+        .await?;
+        let (commits, welcome) = match response {
+            CommunityResponse::Commits { commits, welcome } => (commits, welcome),
+            CommunityResponse::Error { message, .. } => {
+                return Err(format!("failed to fetch commits: {message}"))
+            }
+            other => return Err(format!("unexpected response: {other:?}")),
+        };
+
+        let applied = apply(state, community_id, &commits, welcome.as_deref());
+        let progressed = !applied.meks.is_empty();
+        meks.extend(applied.meks);
+        if applied.lost || !progressed {
+            break applied.lost;
+        }
+    };
+    persist(state, keystore_handle, community_id);
+
+    if lost {
+        publish_key_package(state, pool, keystore_handle, community_id).await?;
+    }
+    Ok(meks)
+}
+
+/// Answer a `TreeCommitNeeded` broadcast if we hold `MANAGE_COMMUNITY`:
+/// remove every leaf whose member is not in `members` and add the
+/// published key packages.
+///
+/// Returns the MEK of each epoch we moved through, including the one our
+/// commit created once the server accepted it. Losing the race to another
+/// key holder is not an error — their commit arrives via `TreeCommitted`.
+pub async fn commit_pending(
+    state: &Arc<AppState>,
+    pool: &DbPool,
+    keystore_handle: &KeystoreHandle,
+    community_id: &str,
+    epoch: u64,
+    members: &[String],
+    key_packages: &[Vec<u8>],
+) -> Result<Vec<MediaEncryptionKey>, String> {
+    if !holds_manage_community(state, community_id)
+        || !is_tree_community(state, keystore_handle, community_id)
+    {
+        return Ok(Vec::new());
+    }
+    let mut meks = match current_epoch(state, community_id) {
+        Some(ours) if ours < epoch => sync(state, pool, keystore_handle, community_id).await?,
+        Some(_) => Vec::new(),
+        None => return Ok(Vec::new()),
+    };
+
+    let signing_key = pseudonym(state, community_id)?;
+    let output = {
+        let groups = state.tree_groups.lock();
+        let Some(group) = groups.get(community_id).and_then(|m| m.group.as_ref()) else {
+            return Ok(meks);
+        };
+        if group.epoch() != epoch {
+            return Ok(meks);
+        }
+        let proposals = proposals_for(group, members, key_packages);
+        if proposals.is_empty() {
+            return Ok(meks);
+        }
+        group
+            .commit(&signing_key, proposals)
+            .map_err(|e| format!("failed to build commit: {e}"))?
+    };
+
+    let welcomes = output
+        .welcomes
+        .iter()
+        .map(|welcome| {
+            let recipient = welcome.recipient().ok_or("welcome has no recipient")?;
+            Ok(TreeWelcomeDto {
+                pseudonym_key: hex::encode(recipient),
+                welcome: serde_json::to_vec(welcome)
+                    .map_err(|e| format!("serialize welcome: {e}"))?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let commit =
+        serde_json::to_vec(&output.commit).map_err(|e| format!("serialize commit: {e}"))?;
+    let proposals = output.commit.proposals.len();
+
+    let response = send_community_rpc(
+        state,
+        pool,
+        community_id,
+        CommunityRequest::SubmitCommit {
+            epoch,
+            commit,
+            welcomes,
+        },
+    )
+    .await?;
+    match response {
+        CommunityResponse::Ok => {
+            meks.push(output.next.mek());
+            if let Some(membership) = state.tree_groups.lock().get_mut(community_id) {
+                membership.group = Some(output.next);
+            }
+            persist(state, keystore_handle, community_id);
+            tracing::info!(community = %community_id, epoch = epoch + 1, proposals, "TreeKEM commit accepted");
+            Ok(meks)
+        }
+        CommunityResponse::Error { code: 409, message } => {
+            tracing::debug!(community = %community_id, epoch, %message, "another key holder committed first");
+            Ok(meks)
+        }
+        CommunityResponse::Error { message, .. } => {
+            Err(format!("server rejected commit: {message}"))
+        }
+        other => Err(format!("unexpected response: {other:?}")),
+    }
+}
+
+/// Membership changes that bring the tree in line with the server roster.
+fn proposals_for(group: &TreeGroup, members: &[String], key_packages: &[Vec<u8>]) -> Vec<Proposal> {
+    let tree = group.tree();
+    let mut proposals: Vec<Proposal> = tree
+        .members()
+        .filter(|(leaf, node)| {
+            *leaf != group.own_leaf() && !members.contains(&hex::encode(node.credential))
+        })
+        .map(|(leaf, _)| Proposal::Remove { leaf })
+        .collect();
+
+    for bytes in key_packages {
+        let Ok(key_package) = serde_json::from_slice::<KeyPackage>(bytes) else {
+            continue;
+        };
+        if key_package.verify().is_err()
+            || !members.contains(&hex::encode(key_package.leaf.credential))
+        {
+            continue;
+        }
+        // A member still in the tree lost their state — swap their leaf
+        match tree.find(&key_package.leaf.credential) {
+            Some(leaf) if leaf == group.own_leaf() => continue,
+            Some(leaf) => proposals.push(Proposal::Remove { leaf }),
+            None => {}
+        }
+        proposals.push(Proposal::Add(key_package));
+    }
+    proposals
+}
+
+/// Outcome of replaying one page of commits.
+struct Applied {
+    meks: Vec<MediaEncryptionKey>,
+    /// We were removed or could not follow a commit.
+    lost: bool,
+}
+
+fn apply(
+    state: &Arc<AppState>,
+    community_id: &str,
+    commits: &[TreeCommitDto],
+    welcome: Option<&[u8]>,
+) -> Applied {
+    let mut applied = Applied {
+        meks: Vec::new(),
+        lost: false,
+    };
+    let mut groups = state.tree_groups.lock();
+    let Some(membership) = groups.get_mut(community_id) else {
+        return applied;
+    };
+
+    if membership.group.is_none() {
+        if let (Some(bytes), Some(secret)) = (welcome, membership.pending.as_ref()) {
+            let joined = serde_json::from_slice::<Welcome>(bytes)
+                .map_err(|e| CryptoError::VerificationError(e.to_string()))
+                .and_then(|welcome| TreeGroup::join(&welcome, secret));
+            match joined {
+                Ok(group) => {
+                    tracing::info!(community = %community_id, epoch = group.epoch(), "joined TreeKEM group");
+                    applied.meks.push(group.mek());
+                    membership.group = Some(group);
+                    membership.pending = None;
+                }
+                // Usually a welcome for a key package we since replaced
+                Err(e) => {
+                    tracing::debug!(community = %community_id, error = %e, "welcome not usable")
+                }
+            }
+        }
+    }
+
+    let Some(group) = membership.group.as_mut() else {
+        return applied;
+    };
+    for dto in commits {
+        if dto.epoch < group.epoch() {
+            continue;
+        }
+        let result = serde_json::from_slice::<Commit>(&dto.commit)
+            .map_err(|e| CryptoError::VerificationError(e.to_string()))
+            .and_then(|commit| group.process_commit(&commit));
+        match result {
+            Ok(()) => applied.meks.push(group.mek()),
+            Err(CryptoError::RemovedFromGroup) => {
+                tracing::info!(community = %community_id, epoch = dto.epoch, "removed from TreeKEM group");
+                applied.lost = true;
+                break;
+            }
+            Err(e) => {
+                tracing::warn!(community = %community_id, epoch = dto.epoch, error = %e, "cannot follow TreeKEM commit — rejoining");
+                applied.lost = true;
+                break;
+            }
+        }
+    }
+    if applied.lost {
+        membership.group = None;
+    }
+    applied
+}
+
+fn current_epoch(state: &Arc<AppState>, community_id: &str) -> Option<u64> {
+    state
+        .tree_groups
+        .lock()
+        .get(community_id)
+        .and_then(|m| m.group.as_ref())
+        .map(TreeGroup::epoch)
+}
+
+fn pseudonym(state: &Arc<AppState>, community_id: &str) -> Result<SigningKey, String> {
+    let secret = state
+        .identity_secret
+        .lock()
+        .ok_or("identity not unlocked")?;
+    Ok(derive_community_pseudonym(&secret, community_id))
+}
+
+fn holds_manage_community(state: &Arc<AppState>, community_id: &str) -> bool {
+    let communities = state.communities.read();
+    communities.get(community_id).is_some_and(|community| {
+        let my_perms = community.my_role_ids.iter().fold(0u64, |acc, role_id| {
+            community
+                .roles
+                .iter()
+                .find(|r| r.id == *role_id)
+                .map_or(acc, |r| acc | r.permissions)
+        });
+        permissions::has_permission(my_perms, permissions::MANAGE_COMMUNITY)
+    })
+}
+
+/// Load a community's tree state from Stronghold if it isn't cached yet.
+fn ensure_loaded(state: &Arc<AppState>, keystore_handle: &KeystoreHandle, community_id: &str) {
+    if state.tree_groups.lock().contains_key(community_id) {
+        return;
+    }
+    let bytes = {
+        let ks = keystore_handle.lock();
+        let Some(ref keystore) = *ks else {
+            return;
+        };
+        match keystore.load_key(VAULT_COMMUNITIES, &tree_key_name(community_id)) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!(error = %e, community = %community_id, "failed to load TreeKEM state from Stronghold");
+                return;
+            }
+        }
+    };
+    match serde_json::from_slice::<TreeMembership>(&bytes) {
+        Ok(membership) => {
+            state
+                .tree_groups
+                .lock()
+                .entry(community_id.to_string())
+                .or_insert(membership);
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, community = %community_id, "corrupt TreeKEM state in Stronghold")
+        }
+    }
+}
+
+/// Write the community's tree state to Stronghold.
+fn persist(state: &Arc<AppState>, keystore_handle: &KeystoreHandle, community_id: &str) {
+    let payload = {
+        let groups = state.tree_groups.lock();
+        let Some(membership) = groups.get(community_id) else {
+            return;
+        };
+        match serde_json::to_vec(membership) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!(error = %e, community = %community_id, "failed to serialize TreeKEM state");
+                return;
+            }
+        }
+    };
+
+    let ks = keystore_handle.lock();
+    if let Some(ref keystore) = *ks {
+        if let Err(e) =
+            keystore.store_key(VAULT_COMMUNITIES, &tree_key_name(community_id), &payload)
+        {
+            tracing::warn!(error = %e, community = %community_id, "failed to persist TreeKEM state to Stronghold");
+        } else if let Err(e) = keystore.save() {
+            tracing::warn!(error = %e, "failed to save Stronghold snapshot after TreeKEM persist");
+        }
+    }
+}
@@ -6,6 +6,7 @@ use veilid_core::VeilidUpdate;
 
 use crate::channels::{NetworkStatusEvent, NotificationEvent};
 use crate::db::DbPool;
+use crate::services::message_edit_service::{self, Change};
 use crate::state::{
     AppState, DHTManagerHandle, NodeHandle, RoutingManagerHandle,
 };
@@ -90,7 +91,7 @@ async fn handle_veilid_update(
 /// Handle an incoming `AppMessage` by routing it through the message service.
 ///
 /// Routing order:
-/// 1. Voice packets (prefixed with `b'V'`) → voice engine receive channel
+/// 1. Voice packets (prefixed with `b'V'`) and probes (`b'E'`) → voice engine receive channel
 /// 2. Community broadcasts (JSON) → community handler
 /// 3. Everything else → standard message envelope handling
 async fn handle_app_message(
@@ -101,14 +102,21 @@ async fn handle_app_message(
     let message = msg.message().to_vec();
     tracing::debug!(msg_len = message.len(), "app_message received");
 
-    // 1. Check for voice packet (tagged with b'V' prefix)
-    if !message.is_empty() && message[0] == b'V' {
+    // 1. Check for voice packet (tagged with b'V' prefix) or probe (b'E')
+    if matches!(message.first(), Some(b'V' | b'E')) {
         let voice_data = &message[1..];
-        match rekindle_voice::transport::VoiceTransport::receive(voice_data) {
-            Ok(packet) => {
+        let parsed = if message[0] == b'V' {
+            rekindle_voice::transport::VoiceTransport::receive(voice_data)
+                .map(rekindle_voice::transport::VoiceMessage::Frame)
+        } else {
+            rekindle_voice::transport::VoiceTransport::receive_probe(voice_data)
+                .map(rekindle_voice::transport::VoiceMessage::Probe)
+        };
+        match parsed {
+            Ok(voice_message) => {
                 let tx = state.voice_packet_tx.read().clone();
                 if let Some(tx) = tx {
-                    if tx.try_send(packet).is_err() {
+                    if tx.try_send(voice_message).is_err() {
                         tracing::trace!("voice packet channel full or closed — dropping packet");
                     }
                 }
@@ -138,6 +146,7 @@ async fn handle_app_message(
 }
 
 /// Handle a community broadcast from the community server.
+#[allow(clippy::too_many_lines)]
 async fn handle_community_broadcast(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
@@ -149,23 +158,124 @@ async fn handle_community_broadcast(
         CommunityBroadcast::NewMessage {
             community_id,
             channel_id,
+            message_id,
             sender_pseudonym,
             ciphertext,
             mek_generation,
             timestamp,
+            reply_to,
         } => {
             let msg = BroadcastNewMessage {
-                community_id, channel_id, sender_pseudonym,
-                ciphertext, mek_generation, timestamp,
+                community_id, channel_id, message_id, sender_pseudonym,
+                ciphertext, mek_generation, timestamp, reply_to,
             };
             handle_broadcast_new_message(app_handle, state, &msg).await;
         }
+        CommunityBroadcast::MessageEdited {
+            community_id,
+            channel_id,
+            message_id,
+            ciphertext,
+            mek_generation,
+            edited_at,
+        } => {
+            let Some(body) =
+                decrypt_broadcast(app_handle, state, &community_id, &ciphertext, mek_generation).await
+            else {
+                return;
+            };
+            let pool: tauri::State<'_, crate::db::DbPool> = app_handle.state();
+            let edit = Change::Edit { body: &body, edited_at: edited_at.cast_signed() };
+            message_edit_service::apply_remote(app_handle, state, pool.inner(), &channel_id, &message_id, None, edit)
+                .await;
+        }
+        CommunityBroadcast::MessageDeleted {
+            channel_id,
+            message_id,
+            ..
+        } => {
+            let pool: tauri::State<'_, crate::db::DbPool> = app_handle.state();
+            message_edit_service::apply_remote(
+                app_handle, state, pool.inner(), &channel_id, &message_id, None, Change::Delete,
+            )
+            .await;
+        }
+        CommunityBroadcast::ThreadCreated {
+            community_id,
+            thread,
+        }
+        | CommunityBroadcast::ThreadUpdated {
+            community_id,
+            thread,
+        } => {
+            if let Some(community) = state.communities.write().get_mut(&community_id) {
+                community.upsert_thread(thread.clone());
+            }
+            let event = crate::channels::CommunityEvent::ThreadUpdated {
+                community_id,
+                thread,
+            };
+            let _ = app_handle.emit("community-event", &event);
+        }
+        CommunityBroadcast::ReactionAdded {
+            channel_id,
+            message_id,
+            pseudonym_key,
+            emoji,
+            ..
+        } => {
+            let pool: tauri::State<'_, crate::db::DbPool> = app_handle.state();
+            let react = Change::React { reactor: &pseudonym_key, emoji: &emoji, add: true };
+            message_edit_service::apply_remote(app_handle, state, pool.inner(), &channel_id, &message_id, None, react)
+                .await;
+        }
+        CommunityBroadcast::ReactionRemoved {
+            channel_id,
+            message_id,
+            pseudonym_key,
+            emoji,
+            ..
+        } => {
+            let pool: tauri::State<'_, crate::db::DbPool> = app_handle.state();
+            let react = Change::React { reactor: &pseudonym_key, emoji: &emoji, add: false };
+            message_edit_service::apply_remote(app_handle, state, pool.inner(), &channel_id, &message_id, None, react)
+                .await;
+        }
         CommunityBroadcast::MEKRotated {
             community_id,
             new_generation,
         } => {
             handle_broadcast_mek_rotated(app_handle, state, &community_id, new_generation).await;
         }
+        CommunityBroadcast::MEKKeysNeeded {
+            community_id,
+            generation,
+            members,
+            vouches,
+        } => {
+            handle_broadcast_mek_keys_needed(app_handle, state, &community_id, generation, &members, &vouches)
+                .await;
+        }
+        CommunityBroadcast::TreeKemEnabled {
+            community_id,
+            epoch,
+        } => {
+            handle_broadcast_tree_kem_enabled(app_handle, state, &community_id, epoch).await;
+        }
+        CommunityBroadcast::TreeCommitNeeded {
+            community_id,
+            epoch,
+            members,
+            key_packages,
+        } => {
+            handle_broadcast_tree_commit_needed(app_handle, state, &community_id, epoch, &members, &key_packages).await;
+        }
+        CommunityBroadcast::TreeCommitted {
+            community_id,
+            epoch,
+        } => {
+            handle_broadcast_tree_committed(app_handle, state, &community_id, epoch).await;
+        }
         CommunityBroadcast::MemberJoined {
             community_id,
             pseudonym_key,
@@ -223,6 +333,40 @@ async fn handle_community_broadcast(
             };
             let _ = app_handle.emit("community-event", &event);
         }
+        CommunityBroadcast::VoiceJoined {
+            community_id,
+            channel_id,
+            pseudonym_key,
+            display_name,
+        } => {
+            let event = crate::channels::VoiceEvent::UserJoined {
+                public_key: pseudonym_key,
+                display_name,
+            };
+            crate::commands::voice::relay_event(app_handle, state, &community_id, &channel_id, event).await;
+        }
+        CommunityBroadcast::VoiceLeft {
+            community_id,
+            channel_id,
+            pseudonym_key,
+        } => {
+            let event = crate::channels::VoiceEvent::UserLeft {
+                public_key: pseudonym_key,
+            };
+            crate::commands::voice::relay_event(app_handle, state, &community_id, &channel_id, event).await;
+        }
+        CommunityBroadcast::VoiceSpeaking {
+            community_id,
+            channel_id,
+            pseudonym_key,
+            speaking,
+        } => {
+            let event = crate::channels::VoiceEvent::UserSpeaking {
+                public_key: pseudonym_key,
+                speaking,
+            };
+            crate::commands::voice::relay_event(app_handle, state, &community_id, &channel_id, event).await;
+        }
     }
 }
 
@@ -237,10 +381,12 @@ enum MekDecryptResult {
 struct BroadcastNewMessage {
     community_id: String,
     channel_id: String,
+    message_id: String,
     sender_pseudonym: String,
     ciphertext: Vec<u8>,
     mek_generation: u64,
     timestamp: u64,
+    reply_to: Option<String>,
 }
 
 /// Handle a `NewMessage` community broadcast: decrypt, store, and emit.
@@ -259,26 +405,10 @@ async fn handle_broadcast_new_message(
         }
     }
 
-    let first_attempt = {
-        let mek_cache = state.mek_cache.lock();
-        decrypt_with_cached_mek(&mek_cache, &msg.community_id, &msg.ciphertext, msg.mek_generation)
-    }; // guard dropped here — safe to .await
-
-    let body = match first_attempt {
-        MekDecryptResult::Decrypted(body) => body,
-        MekDecryptResult::Failed => return,
-        MekDecryptResult::NeedRefresh => {
-            fetch_mek_from_server(app_handle, state, &msg.community_id).await;
-
-            // Retry with refreshed MEK
-            let mek_cache = state.mek_cache.lock();
-            if let MekDecryptResult::Decrypted(body) = decrypt_with_cached_mek(&mek_cache, &msg.community_id, &msg.ciphertext, msg.mek_generation) {
-                body
-            } else {
-                tracing::warn!("MEK still mismatched after refresh — dropping message");
-                return;
-            }
-        }
+    let Some(body) =
+        decrypt_broadcast(app_handle, state, &msg.community_id, &msg.ciphertext, msg.mek_generation).await
+    else {
+        return;
     };
 
     // Store locally
@@ -296,12 +426,15 @@ async fn handle_broadcast_new_message(
     let body_text = body.clone();
     let ts = msg.timestamp.cast_signed();
     let mg = msg.mek_generation.cast_signed();
+    let mid = msg.message_id.clone();
+    let reply_to = msg.reply_to.clone();
     let _ = tokio::task::spawn_blocking(move || {
         let conn = pool.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, mek_generation) \
-             VALUES (?, ?, 'channel', ?, ?, ?, 0, ?)",
-            rusqlite::params![owner_key, cid, spn, body_text, ts, mg],
+            "INSERT OR IGNORE INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp, is_read, mek_generation, message_id, reply_to_id) \
+             VALUES (?1, ?2, 'channel', ?3, ?4, ?5, 0, ?6, ?7, \
+                     (SELECT id FROM messages WHERE owner_key = ?1 AND conversation_id = ?2 AND message_id = ?8))",
+            rusqlite::params![owner_key, cid, spn, body_text, ts, mg, mid, reply_to],
         )
         .map_err(|e| e.to_string())
     })
@@ -313,41 +446,86 @@ async fn handle_broadcast_new_message(
         body,
         timestamp: msg.timestamp,
         conversation_id: msg.channel_id.clone(),
+        attachments: Vec::new(),
+        message_id: Some(msg.message_id.clone()),
+        reply_to: msg.reply_to.clone(),
     };
     let _ = app_handle.emit("chat-event", &event);
 }
 
-/// Try to decrypt ciphertext using the cached MEK for a community.
+/// Decrypt a broadcast channel ciphertext, fetching the MEK from the server
+/// once if we're missing its generation.
+async fn decrypt_broadcast(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    community_id: &str,
+    ciphertext: &[u8],
+    mek_generation: u64,
+) -> Option<String> {
+    let first_attempt = {
+        let mek_cache = state.mek_cache.lock();
+        decrypt_with_cached_mek(&mek_cache, community_id, ciphertext, mek_generation)
+    }; // guard dropped here — safe to .await
+
+    match first_attempt {
+        MekDecryptResult::Decrypted(body) => Some(body),
+        MekDecryptResult::Failed => None,
+        MekDecryptResult::NeedRefresh => {
+            fetch_mek_from_server(app_handle, state, community_id).await;
+
+            // Retry with refreshed MEK
+            let mek_cache = state.mek_cache.lock();
+            if let MekDecryptResult::Decrypted(body) =
+                decrypt_with_cached_mek(&mek_cache, community_id, ciphertext, mek_generation)
+            {
+                Some(body)
+            } else {
+                tracing::warn!("MEK still mismatched after refresh — dropping message");
+                None
+            }
+        }
+    }
+}
+
+/// Try to decrypt ciphertext using the cached MEK generation it was sent under.
+///
+/// A generation newer than any we hold means we missed a rotation, so the
+/// caller should fetch from the server. An older one we never held can't
+/// be recovered that way.
 fn decrypt_with_cached_mek(
-    mek_cache: &std::collections::HashMap<String, rekindle_crypto::group::media_key::MediaEncryptionKey>,
+    mek_cache: &std::collections::HashMap<String, rekindle_crypto::group::media_key::MediaKeyRing>,
     community_id: &str,
     ciphertext: &[u8],
     mek_generation: u64,
 ) -> MekDecryptResult {
-    match mek_cache.get(community_id) {
-        Some(mek) if mek.generation() == mek_generation => {
-            match mek.decrypt(ciphertext) {
-                Ok(plaintext) => MekDecryptResult::Decrypted(
-                    String::from_utf8(plaintext).unwrap_or_default(),
-                ),
-                Err(e) => {
-                    tracing::warn!(error = %e, "failed to decrypt community message");
-                    MekDecryptResult::Failed
-                }
+    let Some(ring) = mek_cache.get(community_id) else {
+        tracing::warn!(community = %community_id, "no MEK cached for community — fetching from server");
+        return MekDecryptResult::NeedRefresh;
+    };
+    if let Some(mek) = ring.get(mek_generation) {
+        return match mek.decrypt(ciphertext) {
+            Ok(plaintext) => MekDecryptResult::Decrypted(
+                String::from_utf8(plaintext).unwrap_or_default(),
+            ),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to decrypt community message");
+                MekDecryptResult::Failed
             }
+        };
+    }
+    match ring.current_generation() {
+        Some(have) if have > mek_generation => {
+            tracing::warn!(have, need = mek_generation, "message from an MEK generation we never held");
+            MekDecryptResult::Failed
         }
-        Some(mek) => {
+        have => {
             tracing::warn!(
-                have = mek.generation(),
+                ?have,
                 need = mek_generation,
                 "MEK generation mismatch — fetching updated MEK from server"
             );
             MekDecryptResult::NeedRefresh
         }
-        None => {
-            tracing::warn!(community = %community_id, "no MEK cached for community");
-            MekDecryptResult::Failed
-        }
     }
 }
 
@@ -358,13 +536,130 @@ async fn handle_broadcast_mek_rotated(
     community_id: &str,
     new_generation: u64,
 ) {
-    tracing::info!(
-        community = %community_id,
-        generation = new_generation,
-        "MEK rotated — fetching new key from server"
-    );
-    fetch_mek_from_server(app_handle, state, community_id).await;
+    let already_held = state
+        .mek_cache
+        .lock()
+        .get(community_id)
+        .is_some_and(|ring| ring.get(new_generation).is_some());
+    if already_held {
+        tracing::debug!(community = %community_id, generation = new_generation, "MEK generation already held");
+    } else {
+        tracing::info!(
+            community = %community_id,
+            generation = new_generation,
+            "MEK rotated — fetching new key from server"
+        );
+        fetch_mek_from_server(app_handle, state, community_id).await;
+    }
+
+    let event = crate::channels::CommunityEvent::MekRotated {
+        community_id: community_id.to_string(),
+        new_generation,
+    };
+    let _ = app_handle.emit("community-event", &event);
+}
+
+/// Handle a `MEKKeysNeeded` broadcast (zero-knowledge communities).
+async fn handle_broadcast_mek_keys_needed(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    community_id: &str,
+    generation: u64,
+    members: &[String],
+    vouches: &[rekindle_protocol::messaging::MemberVouchDto],
+) {
+    let pool: tauri::State<'_, DbPool> = app_handle.state();
+    match super::mek_service::publish_wrapped_keys(state, pool.inner(), community_id, generation, members, vouches)
+        .await
+    {
+        Ok(Some(mek)) => {
+            persist_mek(app_handle, state, community_id, mek).await;
+            let event = crate::channels::CommunityEvent::MekRotated {
+                community_id: community_id.to_string(),
+                new_generation: generation,
+            };
+            let _ = app_handle.emit("community-event", &event);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            tracing::warn!(community = %community_id, generation, error = %e, "failed to publish wrapped MEK");
+        }
+    }
+}
 
+/// Handle a `TreeKemEnabled` broadcast: publish a key package so a key
+/// holder adds us to the new tree.
+async fn handle_broadcast_tree_kem_enabled(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    community_id: &str,
+    epoch: u64,
+) {
+    tracing::info!(community = %community_id, epoch, "community switched to TreeKEM");
+    let pool: tauri::State<'_, DbPool> = app_handle.state();
+    let ks_handle: tauri::State<'_, crate::keystore::KeystoreHandle> = app_handle.state();
+    if let Err(e) = super::tree_service::publish_key_package(state, pool.inner(), ks_handle.inner(), community_id).await {
+        tracing::warn!(community = %community_id, error = %e, "failed to publish TreeKEM key package");
+    }
+}
+
+/// Handle a `TreeCommitNeeded` broadcast: key holders race to commit the
+/// pending membership changes; the server keeps the first commit.
+async fn handle_broadcast_tree_commit_needed(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    community_id: &str,
+    epoch: u64,
+    members: &[String],
+    key_packages: &[Vec<u8>],
+) {
+    let pool: tauri::State<'_, DbPool> = app_handle.state();
+    let ks_handle: tauri::State<'_, crate::keystore::KeystoreHandle> = app_handle.state();
+    let result = super::tree_service::commit_pending(
+        state, pool.inner(), ks_handle.inner(), community_id, epoch, members, key_packages,
+    ).await;
+    match result {
+        Ok(meks) => persist_tree_meks(app_handle, state, community_id, meks).await,
+        Err(e) => {
+            tracing::warn!(community = %community_id, epoch, error = %e, "failed to commit TreeKEM changes");
+        }
+    }
+}
+
+/// Handle a `TreeCommitted` broadcast: replay the new commit(s) to reach
+/// the announced epoch.
+async fn handle_broadcast_tree_committed(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    community_id: &str,
+    epoch: u64,
+) {
+    let pool: tauri::State<'_, DbPool> = app_handle.state();
+    let ks_handle: tauri::State<'_, crate::keystore::KeystoreHandle> = app_handle.state();
+    if !super::tree_service::is_tree_community(state, ks_handle.inner(), community_id) {
+        return;
+    }
+    match super::tree_service::sync(state, pool.inner(), ks_handle.inner(), community_id).await {
+        Ok(meks) => persist_tree_meks(app_handle, state, community_id, meks).await,
+        Err(e) => {
+            tracing::warn!(community = %community_id, epoch, error = %e, "failed to follow TreeKEM commit");
+        }
+    }
+}
+
+/// Persist the MEKs of the tree epochs we moved through and tell the UI.
+async fn persist_tree_meks(
+    app_handle: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    community_id: &str,
+    meks: Vec<rekindle_crypto::group::media_key::MediaEncryptionKey>,
+) {
+    let Some(new_generation) = meks.last().map(rekindle_crypto::group::media_key::MediaEncryptionKey::generation) else {
+        return;
+    };
+    for mek in meks {
+        persist_mek(app_handle, state, community_id, mek).await;
+    }
     let event = crate::channels::CommunityEvent::MekRotated {
         community_id: community_id.to_string(),
         new_generation,
@@ -441,22 +736,10 @@ async fn handle_broadcast_member_removed(
     if is_self {
         tracing::warn!(community = %community_id, "we were kicked from community");
 
-        // Clear MEK from cache
-        state.mek_cache.lock().remove(community_id);
-
-        // Remove MEK from Stronghold
+        // Clear every MEK generation from cache and Stronghold
         {
-            use rekindle_crypto::keychain::{mek_key_name, VAULT_COMMUNITIES};
-            use rekindle_crypto::Keychain as _;
-
             let ks_handle: tauri::State<'_, crate::keystore::KeystoreHandle> = app_handle.state();
-            let ks = ks_handle.lock();
-            if let Some(ref keystore) = *ks {
-                let key_name = mek_key_name(community_id);
-                if let Err(e) = keystore.delete_key(VAULT_COMMUNITIES, &key_name) {
-                    tracing::warn!(error = %e, "failed to remove MEK from Stronghold after kick");
-                }
-            }
+            super::mek_service::forget_community(state, ks_handle.inner(), community_id);
         }
 
         // Remove community from in-memory state
@@ -724,6 +1007,17 @@ async fn handle_broadcast_member_timed_out(
     })
     .await;
 
+    // Our timeout was lifted — pick up the generation rotated in while we were out
+    let is_me = state
+        .communities
+        .read()
+        .get(community_id)
+        .and_then(|c| c.my_pseudonym_key.as_deref())
+        == Some(pseudonym_key);
+    if is_me && timeout_until.is_none() {
+        fetch_mek_from_server(app_handle, state, community_id).await;
+    }
+
     let event = crate::channels::CommunityEvent::MemberTimedOut {
         community_id: community_id.to_string(),
         pseudonym_key: pseudonym_key.to_string(),
@@ -732,14 +1026,19 @@ async fn handle_broadcast_member_timed_out(
     let _ = app_handle.emit("community-event", &event);
 }
 
-/// Persist MEK to in-memory state, Stronghold, and the database.
+/// Add a MEK generation to the keyring, Stronghold, and the database.
 async fn persist_mek(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
     community_id: &str,
-    mek_generation: u64,
-    mek_encrypted: &[u8],
+    mek: rekindle_crypto::group::media_key::MediaEncryptionKey,
 ) {
+    // Add to the keyring and persist it to Stronghold
+    let mek_generation = {
+        let ks_handle: tauri::State<'_, crate::keystore::KeystoreHandle> = app_handle.state();
+        super::mek_service::store_mek(state, ks_handle.inner(), community_id, mek)
+    };
+
     // Update generation in community state
     {
         let mut communities = state.communities.write();
@@ -748,23 +1047,6 @@ async fn persist_mek(
         }
     }
 
-    // Persist to Stronghold
-    {
-        use rekindle_crypto::keychain::{mek_key_name, VAULT_COMMUNITIES};
-        use rekindle_crypto::Keychain as _;
-
-        let ks_handle: tauri::State<'_, crate::keystore::KeystoreHandle> = app_handle.state();
-        let ks = ks_handle.lock();
-        if let Some(ref keystore) = *ks {
-            let key_name = mek_key_name(community_id);
-            if let Err(e) = keystore.store_key(VAULT_COMMUNITIES, &key_name, mek_encrypted) {
-                tracing::warn!(error = %e, "failed to persist refreshed MEK to Stronghold");
-            } else if let Err(e) = keystore.save() {
-                tracing::warn!(error = %e, "failed to save Stronghold snapshot after MEK refresh");
-            }
-        }
-    }
-
     // Persist mek_generation to SQLite
     {
         let pool: tauri::State<'_, crate::db::DbPool> = app_handle.state();
@@ -797,13 +1079,55 @@ async fn persist_mek(
 
 /// Fetch the current MEK from the community server via `RequestMEK` RPC.
 ///
-/// Updates `mek_cache` and community state on success. Also persists the
-/// updated MEK to Stronghold so it survives restarts.
+/// Adds it to the community keyring and persists it to Stronghold so it
+/// survives restarts. If the server can't use our delivery session (e.g.
+/// it was restarted with a fresh database), retries once with a new one.
+/// `TreeKEM` communities never deliver MEKs this way — we replay the tree
+/// commits instead.
 pub(super) async fn fetch_mek_from_server(
     app_handle: &tauri::AppHandle,
     state: &Arc<AppState>,
     community_id: &str,
 ) {
+    {
+        let pool: tauri::State<'_, DbPool> = app_handle.state();
+        let ks_handle: tauri::State<'_, crate::keystore::KeystoreHandle> = app_handle.state();
+        if super::tree_service::is_tree_community(state, ks_handle.inner(), community_id) {
+            match super::tree_service::sync(state, pool.inner(), ks_handle.inner(), community_id).await {
+                Ok(meks) => {
+                    for mek in meks {
+                        persist_mek(app_handle, state, community_id, mek).await;
+                    }
+                }
+                Err(e) => tracing::warn!(community = %community_id, error = %e, "TreeKEM sync failed"),
+            }
+            return;
+        }
+    }
+
+    for attempt in 1..=2 {
+        match request_mek(state, community_id).await {
+            Ok(Some(mek)) => {
+                persist_mek(app_handle, state, community_id, mek).await;
+                return;
+            }
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!(community = %community_id, attempt, error = %e, "MEK delivery failed");
+                state.mek_sessions.lock().remove(community_id);
+            }
+        }
+    }
+}
+
+/// Send one `RequestMEK` and open the delivered key.
+///
+/// Returns `Ok(None)` when the request could not be sent at all, and `Err`
+/// when the server answered but the MEK could not be delivered.
+async fn request_mek(
+    state: &Arc<AppState>,
+    community_id: &str,
+) -> Result<Option<rekindle_crypto::group::media_key::MediaEncryptionKey>, String> {
     let server_route_blob = {
         let communities = state.communities.read();
         communities.get(community_id).and_then(|c| c.server_route_blob.clone())
@@ -827,13 +1151,15 @@ pub(super) async fn fetch_mek_from_server(
         (server_route_blob, node_info, signing_key)
     else {
         tracing::warn!(community = %community_id, "cannot fetch MEK — missing route/node/key");
-        return;
+        return Ok(None);
     };
 
-    let request = rekindle_protocol::messaging::CommunityRequest::RequestMEK;
-    let Ok(request_bytes) = serde_json::to_vec(&request) else {
-        return;
+    let prekey_bundle = super::mek_service::delivery_bundle(state, community_id, false)?;
+    let request = rekindle_protocol::messaging::CommunityRequest::RequestMEK {
+        prekey_bundle,
+        generation: None,
     };
+    let request_bytes = serde_json::to_vec(&request).map_err(|e| e.to_string())?;
 
     let timestamp = crate::db::timestamp_now().cast_unsigned();
     let mut nonce = vec![0u8; 24];
@@ -849,42 +1175,66 @@ pub(super) async fn fetch_mek_from_server(
                 Ok(rid) => rid,
                 Err(e) => {
                     tracing::warn!(community = %community_id, error = %e, "failed to import server route for MEK fetch");
-                    return;
+                    return Ok(None);
                 }
             }
         } else {
             let Ok(rid) = api.import_remote_private_route(route_blob) else {
                 tracing::warn!(community = %community_id, "failed to import server route for MEK fetch");
-                return;
+                return Ok(None);
             };
             rid
         }
     };
 
-    let call_result = rekindle_protocol::messaging::sender::send_call(&rc, route_id, &envelope).await;
+    let response_bytes = match rekindle_protocol::messaging::sender::send_call(&rc, route_id, &envelope).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!(error = %e, community = %community_id, "failed to fetch MEK from server");
+            return Ok(None);
+        }
+    };
 
-    match call_result {
-        Ok(response_bytes) => {
-            if let Ok(rekindle_protocol::messaging::CommunityResponse::MEK {
-                mek_encrypted,
-                mek_generation,
-            }) = serde_json::from_slice(&response_bytes)
-            {
-                if mek_encrypted.len() >= 40 {
-                    let key_bytes: [u8; 32] = mek_encrypted[8..40]
-                        .try_into()
-                        .unwrap_or_default();
-                    let mek = rekindle_crypto::group::media_key::MediaEncryptionKey::from_bytes(
-                        key_bytes, mek_generation,
-                    );
-                    state.mek_cache.lock().insert(community_id.to_string(), mek);
-                    persist_mek(app_handle, state, community_id, mek_generation, &mek_encrypted).await;
-                }
+    match serde_json::from_slice(&response_bytes) {
+        Ok(rekindle_protocol::messaging::CommunityResponse::MEK {
+            mek_encrypted,
+            mek_generation,
+            session_init,
+        }) => {
+            let mek = super::mek_service::open_delivery(
+                state, community_id, &mek_encrypted, session_init.as_ref(),
+            )?;
+            if mek.generation() != mek_generation {
+                return Err(format!(
+                    "server claimed generation {mek_generation} but delivered {}",
+                    mek.generation()
+                ));
             }
+            Ok(Some(mek))
         }
-        Err(e) => {
-            tracing::warn!(error = %e, community = %community_id, "failed to fetch MEK from server");
+        Ok(rekindle_protocol::messaging::CommunityResponse::WrappedMEK {
+            mek_generation,
+            wrapped_key,
+        }) => {
+            let mek = super::mek_service::open_wrapped(state, community_id, &wrapped_key)?;
+            if mek.generation() != mek_generation {
+                return Err(format!(
+                    "server claimed generation {mek_generation} but delivered {}",
+                    mek.generation()
+                ));
+            }
+            Ok(Some(mek))
+        }
+        // No copy published for us yet (zero-knowledge), or we are timed out
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { code: 403 | 404, message }) => {
+            tracing::info!(community = %community_id, %message, "no MEK available yet");
+            Ok(None)
         }
+        Ok(rekindle_protocol::messaging::CommunityResponse::Error { code, message }) => {
+            Err(format!("server error {code}: {message}"))
+        }
+        Ok(other) => Err(format!("unexpected response: {other:?}")),
+        Err(e) => Err(format!("failed to parse MEK response: {e}")),
     }
 }
 
@@ -1195,10 +1545,7 @@ pub(crate) async fn reallocate_private_route(app_handle: &tauri::AppHandle, stat
     }
 
     // Also update mailbox subkey 0 with the fresh route blob
-    let mailbox_key = {
-        let node = state.node.read();
-        node.as_ref().and_then(|nh| nh.mailbox_dht_key.clone())
-    };
+    let mailbox_key = super::device_service::own_mailbox_key(state);
     if let Some(mailbox_key) = mailbox_key {
         let rc = {
             let node = state.node.read();
@@ -1263,10 +1610,7 @@ async fn allocate_fresh_private_route(app_handle: &tauri::AppHandle, state: &Arc
             }
 
             // Also update mailbox subkey 0 with the fresh route blob
-            let mailbox_key = {
-                let node = state.node.read();
-                node.as_ref().and_then(|nh| nh.mailbox_dht_key.clone())
-            };
+            let mailbox_key = super::device_service::own_mailbox_key(state);
             if let Some(mailbox_key) = mailbox_key {
                 let rc = {
                     let node = state.node.read();
@@ -1468,6 +1812,14 @@ pub async fn logout_cleanup(app_handle: Option<&tauri::AppHandle>, state: &AppSt
         }
     }
 
+    // Shut down prekey maintenance loop
+    {
+        let tx = state.prekey_shutdown_tx.write().take();
+        if let Some(tx) = tx {
+            let _ = tx.send(()).await;
+        }
+    }
+
     // 1. Abort user-specific background tasks
     {
         let mut handles = state.background_handles.lock();
@@ -1550,12 +1902,25 @@ pub async fn logout_cleanup(app_handle: Option<&tauri::AppHandle>, state: &AppSt
     *state.identity.write() = None;
     state.friends.write().clear();
     state.communities.write().clear();
+    state.group_chats.write().clear();
     *state.signal_manager.lock() = None;
     *state.identity_secret.lock() = None;
 
     // 7. Clear community-specific state
     state.mek_cache.lock().clear();
+    state.mek_sessions.lock().clear();
+    state.tree_groups.lock().clear();
+    state.call_keys.lock().clear();
     state.community_routes.write().clear();
+    state.community_lists.write().clear();
+    state.directory_listings.write().clear();
+    // Transfer tasks were aborted with the background handles
+    state.file_transfers.lock().clear();
+    state.devices.write().clear();
+    *state.local_device.write() = None;
+    *state.pending_link.lock() = None;
+    state.session_restarts.lock().clear();
+    state.pending_session_inits.lock().clear();
 
     // 8. Shutdown server health check loop
     {
@@ -0,0 +1,221 @@
+//! `SQLite`-backed implementations of the Signal Protocol storage traits.
+//!
+//! Rows are scoped by `owner_key` so several identities can share one
+//! database. The traits are synchronous, so each call takes the `DbPool`
+//! mutex directly — callers must never hold the pool lock while calling
+//! into the `SignalSessionManager`.
+
+use rekindle_crypto::signal::{IdentityKeyStore, PreKeyStore};
+use rekindle_crypto::CryptoError;
+use rusqlite::OptionalExtension;
+
+use crate::db::{self, DbPool};
+
+fn storage_err(e: impl std::fmt::Display) -> CryptoError {
+    CryptoError::StorageError(e.to_string())
+}
+
+/// Prekey store backed by the `prekeys` table.
+///
+/// One-time and signed prekeys share the table and are told apart by
+/// `is_signed`. `created_at` drives signed-prekey rotation and the grace
+/// window in `prekey_service`.
+pub struct SqlitePreKeyStore {
+    pool: DbPool,
+    owner_key: String,
+}
+
+impl SqlitePreKeyStore {
+    pub fn new(pool: DbPool, owner_key: String) -> Self {
+        Self { pool, owner_key }
+    }
+
+    fn load(&self, id: u32, is_signed: bool) -> Result<Option<Vec<u8>>, CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        conn.query_row(
+            "SELECT key_data FROM prekeys WHERE owner_key = ?1 AND is_signed = ?2 AND id = ?3",
+            rusqlite::params![self.owner_key, is_signed, id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(storage_err)
+    }
+
+    fn store(&self, id: u32, is_signed: bool, key_data: &[u8]) -> Result<(), CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO prekeys (owner_key, id, key_data, is_signed, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![self.owner_key, id, key_data, is_signed, db::timestamp_now()],
+        )
+        .map_err(storage_err)?;
+        Ok(())
+    }
+
+    fn remove(&self, id: u32, is_signed: bool) -> Result<(), CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        conn.execute(
+            "DELETE FROM prekeys WHERE owner_key = ?1 AND is_signed = ?2 AND id = ?3",
+            rusqlite::params![self.owner_key, is_signed, id],
+        )
+        .map_err(storage_err)?;
+        Ok(())
+    }
+
+    fn list(&self, is_signed: bool) -> Result<Vec<u32>, CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        let mut stmt = conn
+            .prepare("SELECT id FROM prekeys WHERE owner_key = ?1 AND is_signed = ?2 ORDER BY id")
+            .map_err(storage_err)?;
+        let rows = stmt
+            .query_map(rusqlite::params![self.owner_key, is_signed], |row| row.get(0))
+            .map_err(storage_err)?;
+        rows.collect::<Result<Vec<u32>, _>>().map_err(storage_err)
+    }
+}
+
+impl PreKeyStore for SqlitePreKeyStore {
+    fn load_prekey(&self, prekey_id: u32) -> Result<Option<Vec<u8>>, CryptoError> {
+        self.load(prekey_id, false)
+    }
+
+    fn store_prekey(&self, prekey_id: u32, key_data: &[u8]) -> Result<(), CryptoError> {
+        self.store(prekey_id, false, key_data)
+    }
+
+    fn remove_prekey(&self, prekey_id: u32) -> Result<(), CryptoError> {
+        self.remove(prekey_id, false)
+    }
+
+    fn load_signed_prekey(&self, signed_prekey_id: u32) -> Result<Option<Vec<u8>>, CryptoError> {
+        self.load(signed_prekey_id, true)
+    }
+
+    fn store_signed_prekey(
+        &self,
+        signed_prekey_id: u32,
+        key_data: &[u8],
+    ) -> Result<(), CryptoError> {
+        self.store(signed_prekey_id, true, key_data)
+    }
+
+    fn remove_signed_prekey(&self, signed_prekey_id: u32) -> Result<(), CryptoError> {
+        self.remove(signed_prekey_id, true)
+    }
+
+    fn list_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        self.list(false)
+    }
+
+    fn list_signed_prekey_ids(&self) -> Result<Vec<u32>, CryptoError> {
+        self.list(true)
+    }
+}
+
+/// Identity key store backed by the `trusted_identities` table.
+///
+/// Our own X25519 identity key pair lives in memory (derived from the
+/// Stronghold secret at login); remote identity keys and their `verified`
+/// flag are persisted so key changes are detected across restarts.
+pub struct SqliteIdentityStore {
+    pool: DbPool,
+    owner_key: String,
+    identity_private: Vec<u8>,
+    identity_public: Vec<u8>,
+    registration_id: u32,
+}
+
+impl SqliteIdentityStore {
+    pub fn new(
+        pool: DbPool,
+        owner_key: String,
+        identity_private: Vec<u8>,
+        identity_public: Vec<u8>,
+        registration_id: u32,
+    ) -> Self {
+        Self {
+            pool,
+            owner_key,
+            identity_private,
+            identity_public,
+            registration_id,
+        }
+    }
+}
+
+impl IdentityKeyStore for SqliteIdentityStore {
+    fn get_identity_key_pair(&self) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+        Ok((self.identity_private.clone(), self.identity_public.clone()))
+    }
+
+    fn get_local_registration_id(&self) -> Result<u32, CryptoError> {
+        Ok(self.registration_id)
+    }
+
+    fn is_trusted_identity(&self, address: &str, identity_key: &[u8]) -> Result<bool, CryptoError> {
+        Ok(self
+            .get_identity(address)?
+            .is_none_or(|stored| stored == identity_key))
+    }
+
+    fn save_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
+        if !self.is_trusted_identity(address, identity_key)? {
+            return Err(CryptoError::IdentityKeyChanged(address.to_string()));
+        }
+        let conn = self.pool.lock().map_err(storage_err)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO trusted_identities (owner_key, public_key, identity_key, first_seen) \
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![self.owner_key, address, identity_key, db::timestamp_now()],
+        )
+        .map_err(storage_err)?;
+        Ok(())
+    }
+
+    fn get_identity(&self, address: &str) -> Result<Option<Vec<u8>>, CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        conn.query_row(
+            "SELECT identity_key FROM trusted_identities WHERE owner_key = ?1 AND public_key = ?2",
+            rusqlite::params![self.owner_key, address],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(storage_err)
+    }
+
+    fn replace_identity(&self, address: &str, identity_key: &[u8]) -> Result<(), CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        conn.execute(
+            "INSERT INTO trusted_identities (owner_key, public_key, identity_key, first_seen) \
+             VALUES (?1, ?2, ?3, ?4) \
+             ON CONFLICT(owner_key, public_key) DO UPDATE SET \
+             identity_key = excluded.identity_key, verified = 0, first_seen = excluded.first_seen",
+            rusqlite::params![self.owner_key, address, identity_key, db::timestamp_now()],
+        )
+        .map_err(storage_err)?;
+        Ok(())
+    }
+
+    fn is_verified(&self, address: &str) -> Result<bool, CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        let verified: Option<bool> = conn
+            .query_row(
+                "SELECT verified FROM trusted_identities WHERE owner_key = ?1 AND public_key = ?2",
+                rusqlite::params![self.owner_key, address],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage_err)?;
+        Ok(verified.unwrap_or(false))
+    }
+
+    fn set_verified(&self, address: &str, verified: bool) -> Result<(), CryptoError> {
+        let conn = self.pool.lock().map_err(storage_err)?;
+        conn.execute(
+            "UPDATE trusted_identities SET verified = ?1 WHERE owner_key = ?2 AND public_key = ?3",
+            rusqlite::params![verified, self.owner_key, address],
+        )
+        .map_err(storage_err)?;
+        Ok(())
+    }
+}
@@ -2,9 +2,12 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use parking_lot::{Mutex, RwLock};
-use rekindle_crypto::group::media_key::MediaEncryptionKey;
+use rekindle_crypto::group::media_key::MediaKeyRing;
+use rekindle_protocol::dht::directory::DirectoryListing;
+use rekindle_protocol::messaging::{DeviceCertificate, HistoryVisibility, SyncedContact, ThreadDto};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
+use zeroize::Zeroize as _;
 
 /// Wrapper around `std::process::Child` that kills the child process on drop.
 ///
@@ -49,14 +52,16 @@ impl Drop for KillOnDropChild {
 
 /// Central application state shared across all Tauri commands and services.
 pub struct AppState {
-    /// Channel for routing incoming voice packets from the dispatch loop to the receive loop.
-    pub voice_packet_tx: Arc<RwLock<Option<mpsc::Sender<rekindle_voice::transport::VoicePacket>>>>,
+    /// Channel for routing incoming voice packets and probes from the dispatch loop to the receive loop.
+    pub voice_packet_tx: Arc<RwLock<Option<mpsc::Sender<rekindle_voice::transport::VoiceMessage>>>>,
     /// Identity (loaded after Stronghold unlock).
     pub identity: Arc<RwLock<Option<IdentityState>>>,
     /// Friends list with presence info.
     pub friends: Arc<RwLock<HashMap<String, FriendState>>>,
     /// Joined communities.
     pub communities: Arc<RwLock<HashMap<String, CommunityState>>>,
+    /// Group conversations we are in: `group_id` -> group.
+    pub group_chats: RwLock<HashMap<String, GroupChatState>>,
     /// Veilid node handle (set after login/attach).
     pub node: Arc<RwLock<Option<NodeHandle>>>,
     /// DHT record manager for reading/writing distributed state.
@@ -85,8 +90,37 @@ pub struct AppState {
     /// that don't have their own shutdown channels. Aborted on logout to prevent
     /// stale tasks from interfering with re-login.
     pub background_handles: Mutex<Vec<tauri::async_runtime::JoinHandle<()>>>,
-    /// MEK cache: `community_id` -> current `MediaEncryptionKey` (decrypted from server).
-    pub mek_cache: Mutex<HashMap<String, MediaEncryptionKey>>,
+    /// MEK cache: `community_id` -> every MEK generation we hold (mirrored to Stronghold).
+    pub mek_cache: Mutex<HashMap<String, MediaKeyRing>>,
+    /// MEK delivery sessions with community servers: `community_id` -> our side.
+    pub mek_sessions: Mutex<HashMap<String, crate::services::mek_service::MekDeliverySession>>,
+    /// `TreeKEM` group state for tree communities: `community_id` -> our view
+    /// (mirrored to Stronghold).
+    pub tree_groups: Mutex<HashMap<String, crate::services::tree_service::TreeMembership>>,
+    /// Latest voice frame key each friend sent us for a 1:1 call: `peer_key` -> key.
+    /// Picked up when we join (or are already in) the call with them.
+    pub call_keys: Mutex<HashMap<String, rekindle_crypto::sframe::FrameKey>>,
+    /// Ids of `file_transfers` rows with a task running, so the sync loop
+    /// doesn't resume a transfer that is still going.
+    pub file_transfers: Mutex<HashSet<i64>>,
+    /// Receipts waiting to go out, per peer, so a burst of messages is
+    /// acknowledged with one receipt of each kind.
+    pub pending_receipts: Mutex<HashMap<String, ReceiptBatch>>,
+    /// Linked devices per identity — ours and our friends' — from verified
+    /// device certificates: identity key -> devices.
+    pub devices: RwLock<HashMap<String, Vec<DeviceInfo>>>,
+    /// This device's own key when it is a linked (non-primary) device.
+    pub local_device: RwLock<Option<LocalDevice>>,
+    /// Link request shown by this device while it waits for another device
+    /// to approve it (before login).
+    pub pending_link: Mutex<Option<PendingLink>>,
+    /// Friends restored from our account record. The next encrypted payload
+    /// to one starts a Signal session from their published prekeys.
+    pub session_restarts: Mutex<HashSet<String>>,
+    /// Sessions we started on one of the peer's one-time prekeys that the
+    /// peer has not answered yet: address -> (prekey id, first payload).
+    /// Kept so the payload can be resent if the prekey was already used.
+    pub pending_session_inits: Mutex<HashMap<String, (u32, Vec<u8>)>>,
     /// Community server child process handle (spawned on login if user owns communities).
     /// Wrapped in `KillOnDropChild` so the child is killed if the parent crashes.
     pub server_process: Mutex<Option<KillOnDropChild>>,
@@ -95,6 +129,13 @@ pub struct AppState {
     /// Community server route cache: `community_id` -> imported `RouteId`.
     /// For communities we're a MEMBER of (not owner) — the remote server's route.
     pub community_routes: RwLock<HashMap<String, veilid_core::RouteId>>,
+    /// Revision of each paged community list we have applied:
+    /// `(dht_key, subkey)` -> revision. Lets a re-read fetch only the
+    /// changes since.
+    pub community_lists: RwLock<HashMap<(String, u32), u64>>,
+    /// Directory listings from the last search, by community, so one can
+    /// be previewed without reading its shard again.
+    pub directory_listings: RwLock<HashMap<String, DirectoryListing>>,
     /// Friends whose DHT `watch_dht_values` returned false (watch not established).
     /// Per Veilid GitLab #377, apps must poll as fallback when watching fails.
     /// The sync service uses `force_refresh=true` for these friends.
@@ -107,6 +148,8 @@ pub struct AppState {
     pub idle_shutdown_tx: RwLock<Option<mpsc::Sender<()>>>,
     /// Shutdown sender for the presence heartbeat loop.
     pub heartbeat_shutdown_tx: RwLock<Option<mpsc::Sender<()>>>,
+    /// Shutdown sender for the prekey maintenance loop.
+    pub prekey_shutdown_tx: RwLock<Option<mpsc::Sender<()>>>,
     /// The status the user had before auto-away kicked in.
     /// When activity resumes, we restore to this status.
     pub pre_away_status: RwLock<Option<UserStatus>>,
@@ -120,6 +163,7 @@ impl Default for AppState {
             identity: Arc::new(RwLock::new(None)),
             friends: Arc::new(RwLock::new(HashMap::new())),
             communities: Arc::new(RwLock::new(HashMap::new())),
+            group_chats: RwLock::new(HashMap::new()),
             node: Arc::new(RwLock::new(None)),
             dht_manager: Arc::new(RwLock::new(None)),
             routing_manager: Arc::new(RwLock::new(None)),
@@ -133,19 +177,97 @@ impl Default for AppState {
             identity_secret: Mutex::new(None),
             background_handles: Mutex::new(Vec::new()),
             mek_cache: Mutex::new(HashMap::new()),
+            mek_sessions: Mutex::new(HashMap::new()),
+            tree_groups: Mutex::new(HashMap::new()),
+            call_keys: Mutex::new(HashMap::new()),
+            file_transfers: Mutex::new(HashSet::new()),
+            pending_receipts: Mutex::new(HashMap::new()),
+            devices: RwLock::new(HashMap::new()),
+            local_device: RwLock::new(None),
+            pending_link: Mutex::new(None),
+            session_restarts: Mutex::new(HashSet::new()),
+            pending_session_inits: Mutex::new(HashMap::new()),
             server_process: Mutex::new(None),
             server_health_shutdown_tx: Arc::new(RwLock::new(None)),
             community_routes: RwLock::new(HashMap::new()),
+            community_lists: RwLock::new(HashMap::new()),
+            directory_listings: RwLock::new(HashMap::new()),
             unwatched_friends: RwLock::new(HashSet::new()),
             dispatch_loop_handle: RwLock::new(None),
             route_refresh_shutdown_tx: RwLock::new(None),
             idle_shutdown_tx: RwLock::new(None),
             heartbeat_shutdown_tx: RwLock::new(None),
+            prekey_shutdown_tx: RwLock::new(None),
             pre_away_status: RwLock::new(None),
         }
     }
 }
 
+/// Message IDs to acknowledge to one peer.
+#[derive(Debug, Default)]
+pub struct ReceiptBatch {
+    pub delivered: Vec<String>,
+    pub read: Vec<String>,
+}
+
+/// A linked device of some identity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    /// Device Ed25519 public key (hex).
+    pub device_key: String,
+    pub name: String,
+    /// Device mailbox DHT record key (route blob and prekey bundle).
+    pub mailbox_dht_key: String,
+    /// Unix timestamp (ms) of when the identity linked the device.
+    pub added_at: i64,
+}
+
+/// The key of this device, when it was linked to an identity created elsewhere.
+pub struct LocalDevice {
+    /// Device Ed25519 public key (hex).
+    pub device_key: String,
+    /// Device Ed25519 secret, owner of the device mailbox record.
+    pub secret: [u8; 32],
+    /// Our device mailbox DHT record key.
+    pub mailbox_dht_key: String,
+}
+
+/// A device waiting for its link request to be approved.
+pub struct PendingLink {
+    /// Device Ed25519 secret generated for the request.
+    pub secret: [u8; 32],
+    pub device_name: String,
+    pub mailbox_dht_key: String,
+    /// The opened grant, once an existing device has approved us.
+    pub grant: Option<LinkedAccount>,
+}
+
+/// Account material a primary device hands to a newly linked one.
+///
+/// Only ever travels sealed to the new device's key.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LinkedAccount {
+    /// The identity's Ed25519 secret.
+    pub identity_secret: Vec<u8>,
+    pub display_name: String,
+    pub profile_dht_key: Option<String>,
+    pub friend_list_dht_key: Option<String>,
+    pub account_dht_key: Option<String>,
+    pub account_owner_keypair: Option<String>,
+    /// The identity's own mailbox; the new device gets its own alongside.
+    pub mailbox_dht_key: Option<String>,
+    pub contacts: Vec<SyncedContact>,
+    /// Certificates of every linked device, the new one included.
+    pub devices: Vec<DeviceCertificate>,
+}
+
+impl Drop for LinkedAccount {
+    fn drop(&mut self) {
+        self.identity_secret.zeroize();
+    }
+}
+
 /// Shared reference to `AppState`, used by both Tauri commands and background services.
 pub type SharedState = Arc<AppState>;
 
@@ -212,6 +334,22 @@ pub struct VoiceEngineHandle {
     pub muted_flag: std::sync::Arc<std::sync::atomic::AtomicBool>,
     /// Shared deafen flag — receive loop checks this to send silence.
     pub deafened_flag: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    /// Frame keys shared with the transport and the receive loop.
+    pub frame_keys: std::sync::Arc<rekindle_voice::frame_keys::VoiceKeys>,
+    /// Where `frame_keys` come from.
+    pub key_source: VoiceKeySource,
+    /// Token the community relay issued for this session (empty for calls).
+    /// Reused when the loops restart on a device change.
+    pub relay_token: Vec<u8>,
+}
+
+/// Where a voice session's frame keys come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoiceKeySource {
+    /// Community voice channel: one key per MEK generation, shared by everyone.
+    Channel { community_id: String },
+    /// 1:1 call: each side picks its own key and sends it over the Signal session.
+    Call { peer: String },
 }
 
 /// Handle to the DHT record manager.
@@ -352,6 +490,8 @@ pub struct FriendState {
     pub last_heartbeat_at: Option<i64>,
     /// Whether this friendship is pending (request sent) or fully accepted.
     pub friendship_state: FriendshipState,
+    /// Disappearing-message timer for our DMs, in seconds; 0 when off.
+    pub disappearing_secs: u32,
 }
 
 /// Game presence information.
@@ -385,12 +525,63 @@ pub struct CommunityState {
     pub dht_owner_keypair: Option<String>,
     /// Our pseudonym pubkey hex for this community.
     pub my_pseudonym_key: Option<String>,
+    /// The owner's pseudonym hex, from the invite link we joined with (ours
+    /// if we own the community). `None` after joining without a link.
+    pub owner_pseudonym: Option<String>,
     /// Current MEK generation we have.
     pub mek_generation: u64,
     /// Community server's private route blob (for sending `app_call`).
     pub server_route_blob: Option<Vec<u8>>,
     /// Whether we host this community's server process.
     pub is_hosted: bool,
+    /// Whether members can read history from before they joined.
+    pub history_visibility: HistoryVisibility,
+    /// Threads in its text channels we have fetched or been told about.
+    pub threads: Vec<ThreadDto>,
+}
+
+impl CommunityState {
+    /// Whether `id` is one of its channels or known threads. Messages in
+    /// a thread are addressed by the thread's ID like a channel's.
+    pub fn has_conversation(&self, id: &str) -> bool {
+        self.channels.iter().any(|ch| ch.id == id) || self.threads.iter().any(|t| t.id == id)
+    }
+
+    /// Add a thread, or replace our copy of it.
+    pub fn upsert_thread(&mut self, thread: ThreadDto) {
+        match self.threads.iter_mut().find(|t| t.id == thread.id) {
+            Some(existing) => *existing = thread,
+            None => self.threads.push(thread),
+        }
+    }
+}
+
+/// A group conversation: a few friends talking without a community server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupChatState {
+    pub id: String,
+    pub name: String,
+    /// Public key of the member who created the group.
+    pub created_by: String,
+    /// Every member, ourselves included, in the order they joined.
+    pub members: Vec<GroupMemberState>,
+    pub unread_count: u32,
+    /// The group's shared DHT record holding the roster.
+    pub dht_record_key: Option<String>,
+}
+
+/// One member of a group conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupMemberState {
+    pub public_key: String,
+    pub display_name: String,
+    /// The member's profile DHT key, used to reach members we aren't friends with.
+    pub profile_dht_key: Option<String>,
+    /// The member's mailbox DHT key.
+    pub mailbox_dht_key: Option<String>,
+    pub joined_at: i64,
 }
 
 /// A role definition cached from the server.
@@ -441,6 +632,8 @@ pub struct ChannelInfo {
     pub name: String,
     pub channel_type: ChannelType,
     pub unread_count: u32,
+    /// Seconds the community keeps this channel's messages; 0 forever.
+    pub retention_secs: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -152,6 +152,32 @@ pub fn open_community_window(
     Ok(())
 }
 
+/// Open a group conversation window (matches the group-* capability pattern).
+pub fn open_group_window(app: &AppHandle, group_id: &str, group_name: &str) -> Result<(), String> {
+    let id_part = group_id.strip_prefix("group_").unwrap_or(group_id);
+    let label = format!("group-{}", &id_part[..16.min(id_part.len())]);
+
+    if let Some(window) = app.get_webview_window(&label) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return Ok(());
+    }
+
+    let url = WebviewUrl::App(format!("/group?id={group_id}").into());
+    WebviewWindowBuilder::new(app, &label, url)
+        .title(format!("Group - {group_name}"))
+        .inner_size(640.0, 520.0)
+        .min_inner_size(480.0, 380.0)
+        .decorations(false)
+        .transparent(true)
+        .shadow(true)
+        .resizable(true)
+        .build()
+        .map_err(|e: tauri::Error| e.to_string())?;
+
+    Ok(())
+}
+
 /// Open a profile window for viewing a friend's profile.
 pub fn open_profile_window(
     app: &AppHandle,
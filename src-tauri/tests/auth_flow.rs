@@ -5,10 +5,14 @@
 
 use std::sync::Arc;
 
-use rekindle_lib::commands::auth::{create_identity_core, login_core};
+use rekindle_lib::commands::auth::{
+    create_identity_core, export_backup_core, import_backup_core, link_device_core, login_core,
+    restore_from_phrase_core,
+};
 use rekindle_lib::db::{self, DbPool};
 use rekindle_lib::keystore::{self, KeystoreHandle};
-use rekindle_lib::state::{AppState, SharedState, UserStatus};
+use rekindle_lib::state::{AppState, LinkedAccount, PendingLink, SharedState, UserStatus};
+use rekindle_protocol::messaging::envelope::{sign_device_certificate, SyncedContact};
 
 /// Create fresh test state with an in-memory `SQLite` database.
 fn test_state() -> (SharedState, DbPool, KeystoreHandle) {
@@ -317,3 +321,347 @@ async fn multiple_create_login_cycles_work() {
     assert_eq!(login1.public_key, result1.public_key);
     assert_eq!(login1.display_name, "First");
 }
+
+// ── Encryption at rest ───────────────────────────────────────────────
+
+#[tokio::test]
+async fn identity_database_is_encrypted_on_disk() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (state, pool, ks_handle) = test_state();
+
+    let (result, _) = create_identity_core(
+        dir.path(),
+        "disk-pass",
+        Some("Erin".into()),
+        &state,
+        &pool,
+        &ks_handle,
+    )
+    .await
+    .unwrap();
+
+    // The directory still lists the identity for the login screen
+    let listed: String = pool
+        .with_directory(|conn| {
+            conn.query_row(
+                "SELECT display_name FROM identity WHERE public_key = ?",
+                rusqlite::params![result.public_key],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())
+        })
+        .unwrap();
+    assert_eq!(listed, "Erin");
+
+    // Without the key the identity database is unreadable
+    let path = db::identity_db_path(dir.path(), &result.public_key);
+    assert!(path.exists());
+    let raw = rusqlite::Connection::open(&path).unwrap();
+    assert!(raw
+        .query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .is_err());
+}
+
+// ── Device linking ───────────────────────────────────────────────────
+
+#[tokio::test]
+async fn linked_device_keeps_identity_and_device_key() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (state, pool, ks_handle) = test_state();
+
+    let primary = rekindle_crypto::Identity::generate();
+    let device = rekindle_crypto::Identity::generate();
+    let certificate = sign_device_certificate(
+        primary.secret_key_bytes(),
+        &device.public_key_hex(),
+        "Laptop",
+        "VLD0:device-mailbox",
+        1_700_000_000_000,
+    );
+    *state.pending_link.lock() = Some(PendingLink {
+        secret: *device.secret_key_bytes(),
+        device_name: "Laptop".into(),
+        mailbox_dht_key: "VLD0:device-mailbox".into(),
+        grant: Some(LinkedAccount {
+            identity_secret: primary.secret_key_bytes().to_vec(),
+            display_name: "Frank".into(),
+            profile_dht_key: Some("VLD0:profile".into()),
+            friend_list_dht_key: None,
+            account_dht_key: None,
+            account_owner_keypair: None,
+            mailbox_dht_key: Some("VLD0:mailbox".into()),
+            contacts: vec![SyncedContact {
+                public_key: "ab".repeat(32),
+                display_name: "Grace".into(),
+                nickname: None,
+                profile_dht_key: None,
+                mailbox_dht_key: None,
+            }],
+            devices: vec![certificate],
+        }),
+    });
+
+    let (result, secret, dht_cols) =
+        link_device_core(dir.path(), "link-pass", &state, &pool, &ks_handle)
+            .await
+            .expect("linking should succeed");
+    assert_eq!(result.public_key, primary.public_key_hex());
+    assert_eq!(result.display_name, "Frank");
+    assert_eq!(&secret, primary.secret_key_bytes());
+    assert_eq!(dht_cols.existing_dht_key.as_deref(), Some("VLD0:profile"));
+    assert!(state.pending_link.lock().is_none());
+    assert!(state.friends.read().contains_key(&"ab".repeat(32)));
+    assert_eq!(state.devices.read()[&result.public_key].len(), 1);
+
+    // Logging in again restores this device's key alongside the identity
+    *state.identity.write() = None;
+    *state.local_device.write() = None;
+    *ks_handle.lock() = None;
+    let (_, secret, dht_cols) =
+        login_core(dir.path(), &result.public_key, "link-pass", &state, &pool, &ks_handle)
+            .await
+            .expect("login on the linked device should succeed");
+    assert_eq!(&secret, primary.secret_key_bytes());
+    assert_eq!(dht_cols.device_key, Some(device.public_key_hex()));
+    let local = state.local_device.read();
+    let local = local.as_ref().expect("device key should be restored");
+    assert_eq!(&local.secret, device.secret_key_bytes());
+    assert_eq!(local.mailbox_dht_key, "VLD0:device-mailbox");
+}
+
+// ── Backup and recovery ──────────────────────────────────────────────
+
+#[tokio::test]
+async fn backup_restores_identity_friends_and_history() {
+    let old_dir = tempfile::TempDir::new().unwrap();
+    let (state, pool, ks_handle) = test_state();
+    let (created, secret) = create_identity_core(
+        old_dir.path(),
+        "old-pass",
+        Some("Heidi".into()),
+        &state,
+        &pool,
+        &ks_handle,
+    )
+    .await
+    .unwrap();
+    *state.identity_secret.lock() = Some(secret);
+
+    let pk = created.public_key.clone();
+    let friend = "cd".repeat(32);
+    {
+        let conn = pool.lock().unwrap();
+        conn.execute(
+            "UPDATE identity SET dht_record_key = 'VLD0:profile', dht_owner_keypair = 'VLD0:owner' \
+             WHERE public_key = ?1",
+            rusqlite::params![pk],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO friend_groups (owner_key, name, sort_order) VALUES (?1, 'Raid', 1)",
+            rusqlite::params![pk],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO friends (owner_key, public_key, display_name, group_id, added_at, \
+             local_conversation_keypair) \
+             VALUES (?1, ?2, 'Ivan', (SELECT id FROM friend_groups WHERE name = 'Raid'), 1, 'VLD0:conv')",
+            rusqlite::params![pk, friend],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp) \
+             VALUES (?1, ?2, 'dm', ?2, 'ready?', 10)",
+            rusqlite::params![pk, friend],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, \
+             timestamp, reply_to_id, delivery_state) \
+             VALUES (?1, ?2, 'dm', ?1, 'ready', 20, last_insert_rowid(), 'queued')",
+            rusqlite::params![pk, friend],
+        )
+        .unwrap();
+    }
+
+    let archive = export_backup_core(&state, &pool, "backup-pass", true)
+        .await
+        .expect("export should succeed");
+
+    // Restore on a "new machine"
+    let new_dir = tempfile::TempDir::new().unwrap();
+    let (state, pool, ks_handle) = test_state();
+    let err = import_backup_core(
+        new_dir.path(), &archive, "wrong-pass", "new-pass", &state, &pool, &ks_handle,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.contains("Wrong backup passphrase"));
+
+    let (result, restored_secret, dht_cols) = import_backup_core(
+        new_dir.path(), &archive, "backup-pass", "new-pass", &state, &pool, &ks_handle,
+    )
+    .await
+    .expect("import should succeed");
+    assert_eq!(result.public_key, pk);
+    assert_eq!(result.display_name, "Heidi");
+    assert_eq!(restored_secret, secret);
+    assert_eq!(dht_cols.existing_dht_key.as_deref(), Some("VLD0:profile"));
+    assert_eq!(dht_cols.dht_owner_keypair.as_deref(), Some("VLD0:owner"));
+    assert!(state.friends.read().contains_key(&friend));
+
+    let (group, keypair, reply_linked, delivery): (String, String, bool, String) = {
+        let conn = pool.lock().unwrap();
+        let (group, keypair) = conn
+            .query_row(
+                "SELECT g.name, f.local_conversation_keypair FROM friends f \
+                 JOIN friend_groups g ON g.id = f.group_id WHERE f.public_key = ?1",
+                rusqlite::params![friend],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        let (reply_linked, delivery) = conn
+            .query_row(
+                "SELECT r.reply_to_id = m.id, r.delivery_state FROM messages r \
+                 JOIN messages m ON m.body = 'ready?' WHERE r.body = 'ready'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        (group, keypair, reply_linked, delivery)
+    };
+    assert_eq!(group, "Raid");
+    assert_eq!(keypair, "VLD0:conv");
+    assert!(reply_linked);
+    assert_eq!(delivery, "failed");
+
+    // The restored identity opens with its new passphrase
+    *state.identity.write() = None;
+    *ks_handle.lock() = None;
+    login_core(new_dir.path(), &pk, "new-pass", &state, &pool, &ks_handle)
+        .await
+        .expect("login with the new passphrase should succeed");
+
+    // Restoring over an identity that is already here is refused
+    let err = import_backup_core(
+        new_dir.path(), &archive, "backup-pass", "new-pass", &state, &pool, &ks_handle,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.contains("already on this device"));
+}
+
+#[tokio::test]
+async fn recovery_phrase_restores_same_identity() {
+    let identity = rekindle_crypto::Identity::generate();
+    let phrase = identity.recovery_phrase();
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let (state, pool, ks_handle) = test_state();
+    let (result, secret) = restore_from_phrase_core(
+        dir.path(),
+        &phrase,
+        "phrase-pass",
+        Some("Judy".into()),
+        &state,
+        &pool,
+        &ks_handle,
+    )
+    .await
+    .expect("restore from phrase should succeed");
+    assert_eq!(result.public_key, identity.public_key_hex());
+    assert_eq!(&secret, identity.secret_key_bytes());
+
+    let err = restore_from_phrase_core(
+        dir.path(), &phrase, "phrase-pass", None, &state, &pool, &ks_handle,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.contains("already on this device"));
+
+    let err = restore_from_phrase_core(
+        dir.path(), "not a phrase", "phrase-pass", None, &state, &pool, &ks_handle,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.contains("recovery phrase"));
+}
+
+// ── Disappearing Messages ────────────────────────────────────────────
+
+#[tokio::test]
+async fn disappearing_timer_stamps_new_messages_and_survives_login() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (state, pool, ks_handle) = test_state();
+    let (created, _) = create_identity_core(
+        dir.path(),
+        "timer-pass",
+        Some("Mallory".into()),
+        &state,
+        &pool,
+        &ks_handle,
+    )
+    .await
+    .unwrap();
+
+    let pk = created.public_key.clone();
+    let timed = "ab".repeat(32);
+    let untimed = "ef".repeat(32);
+    let before = db::timestamp_now();
+    let (timed_expiry, untimed_expiry) = {
+        let conn = pool.lock().unwrap();
+        conn.execute(
+            "INSERT INTO friends (owner_key, public_key, display_name, added_at, disappearing_secs) \
+             VALUES (?1, ?2, 'Niaj', 1, 60), (?1, ?3, 'Olivia', 1, NULL)",
+            rusqlite::params![pk, timed, untimed],
+        )
+        .unwrap();
+        for friend in [&timed, &untimed] {
+            conn.execute(
+                "INSERT INTO messages (owner_key, conversation_id, conversation_type, sender_key, body, timestamp) \
+                 VALUES (?1, ?2, 'dm', ?2, 'gone soon?', 10)",
+                rusqlite::params![pk, friend],
+            )
+            .unwrap();
+        }
+        let expiry = |friend: &str| {
+            conn.query_row(
+                "SELECT expires_at FROM messages WHERE conversation_id = ?1",
+                rusqlite::params![friend],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .unwrap()
+        };
+        (expiry(&timed), expiry(&untimed))
+    };
+    let after = db::timestamp_now();
+
+    // Counted from arrival (second precision), not the sender's timestamp
+    let expires_at = timed_expiry.expect("timed DM should expire");
+    assert!(expires_at >= before - 1000 + 60_000 && expires_at <= after + 60_000);
+    assert_eq!(untimed_expiry, None);
+
+    // Purging the row takes its search index entry with it
+    {
+        let conn = pool.lock().unwrap();
+        conn.execute("DELETE FROM messages WHERE expires_at <= ?1", rusqlite::params![expires_at])
+            .unwrap();
+        let indexed: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'gone'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(indexed, 1);
+    }
+
+    *state.identity.write() = None;
+    *ks_handle.lock() = None;
+    login_core(dir.path(), &pk, "timer-pass", &state, &pool, &ks_handle)
+        .await
+        .expect("login should succeed");
+    let friends = state.friends.read();
+    assert_eq!(friends.get(&timed).unwrap().disappearing_secs, 60);
+    assert_eq!(friends.get(&untimed).unwrap().disappearing_secs, 0);
+}
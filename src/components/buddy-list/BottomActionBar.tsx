@@ -3,12 +3,24 @@ import { handleToggleAddFriend } from "../../handlers/buddy.handlers";
 import { handleLogout } from "../../handlers/auth.handlers";
 import { setFriendsState } from "../../stores/friends.store";
 import { buddyListUI, setBuddyListUI } from "../../stores/buddylist-ui.store";
-import { ICON_NEW_CHAT, ICON_ADD_FRIEND, ICON_PLUS, ICON_COMMUNITIES, ICON_LOGOUT } from "../../icons";
+import {
+  ICON_NEW_CHAT,
+  ICON_GROUP_CHAT,
+  ICON_ADD_FRIEND,
+  ICON_PLUS,
+  ICON_COMMUNITIES,
+  ICON_COMPASS,
+  ICON_LOGOUT,
+} from "../../icons";
 
 function handleToggleNewChat(): void {
   setFriendsState("showNewChat", (prev) => !prev);
 }
 
+function handleToggleNewGroupChat(): void {
+  setBuddyListUI("showNewGroupChat", (prev) => !prev);
+}
+
 function handleToggleCreateCommunity(): void {
   setBuddyListUI("showCreateCommunity", (prev) => !prev);
 }
@@ -17,6 +29,10 @@ function handleToggleJoinCommunity(): void {
   setBuddyListUI("showJoinCommunity", (prev) => !prev);
 }
 
+function handleToggleCommunityBrowser(): void {
+  setBuddyListUI("showCommunityBrowser", (prev) => !prev);
+}
+
 const BottomActionBar: Component = () => {
   return (
     <div class="action-bar">
@@ -24,6 +40,9 @@ const BottomActionBar: Component = () => {
         <button class="action-bar-icon-btn" onClick={handleToggleNewChat} title="New Chat">
           <span class="nf-icon">{ICON_NEW_CHAT}</span>
         </button>
+        <button class="action-bar-icon-btn" onClick={handleToggleNewGroupChat} title="New Group Chat">
+          <span class="nf-icon">{ICON_GROUP_CHAT}</span>
+        </button>
         <button class="action-bar-icon-btn" onClick={handleToggleAddFriend} title="Add Friend">
           <span class="nf-icon">{ICON_ADD_FRIEND}</span>
         </button>
@@ -35,6 +54,9 @@ const BottomActionBar: Component = () => {
         <button class="action-bar-icon-btn" onClick={handleToggleJoinCommunity} title="Join Community">
           <span class="nf-icon">{ICON_COMMUNITIES}</span>
         </button>
+        <button class="action-bar-icon-btn" onClick={handleToggleCommunityBrowser} title="Browse Communities">
+          <span class="nf-icon">{ICON_COMPASS}</span>
+        </button>
       </Show>
       <div class="action-bar-spacer" />
       <button class="logout-icon-btn" onClick={handleLogout} title="Logout">
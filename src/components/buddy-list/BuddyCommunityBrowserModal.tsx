@@ -0,0 +1,189 @@
+import { Component, For, Show, createEffect, createMemo, createSignal } from "solid-js";
+import Modal from "../common/Modal";
+import { commands } from "../../ipc/commands";
+import type { DirectoryEntry, DirectoryPreview, KnownGame } from "../../ipc/commands";
+import {
+  handleJoinCommunity,
+  handlePreviewDirectoryListing,
+  handleSearchDirectory,
+} from "../../handlers/community.handlers";
+import { buddyListUI, setBuddyListUI } from "../../stores/buddylist-ui.store";
+import { ICON_CHANNEL_TEXT, ICON_JOIN, ICON_VOLUME_HIGH } from "../../icons";
+
+/** Discover public communities in the directory and join one. */
+const BuddyCommunityBrowserModal: Component = () => {
+  const [query, setQuery] = createSignal("");
+  const [gameId, setGameId] = createSignal<number | null>(null);
+  const [games, setGames] = createSignal<KnownGame[]>([]);
+  const [results, setResults] = createSignal<DirectoryEntry[]>([]);
+  const [searching, setSearching] = createSignal(false);
+  const [searched, setSearched] = createSignal(false);
+  const [preview, setPreview] = createSignal<DirectoryPreview | null>(null);
+  const [joining, setJoining] = createSignal(false);
+
+  const gameNames = createMemo(() => new Map(games().map((g) => [g.id, g.name])));
+
+  function gameName(id: number): string {
+    return gameNames().get(id) ?? `Game #${id}`;
+  }
+
+  createEffect(() => {
+    if (buddyListUI.showCommunityBrowser && games().length === 0) {
+      commands.getKnownGames().then(setGames).catch(() => {});
+    }
+  });
+
+  function handleClose(): void {
+    setBuddyListUI("showCommunityBrowser", false);
+    setQuery("");
+    setGameId(null);
+    setResults([]);
+    setSearched(false);
+    setPreview(null);
+  }
+
+  async function handleSearch(e: Event): Promise<void> {
+    e.preventDefault();
+    setSearching(true);
+    setPreview(null);
+    try {
+      setResults(await handleSearchDirectory(query().trim(), gameId()));
+      setSearched(true);
+    } finally {
+      setSearching(false);
+    }
+  }
+
+  async function handleSelect(entry: DirectoryEntry): Promise<void> {
+    const loaded = await handlePreviewDirectoryListing(entry.communityId);
+    if (loaded) {
+      setPreview(loaded);
+    } else {
+      // Withdrawn since the search
+      setResults(results().filter((r) => r.communityId !== entry.communityId));
+    }
+  }
+
+  async function handleJoin(): Promise<void> {
+    const target = preview();
+    if (!target) return;
+    setJoining(true);
+    try {
+      await handleJoinCommunity(target.communityId, target.name);
+      handleClose();
+    } finally {
+      setJoining(false);
+    }
+  }
+
+  return (
+    <Modal
+      isOpen={buddyListUI.showCommunityBrowser}
+      title="Browse Communities"
+      onClose={handleClose}
+      size="lg"
+    >
+      <form class="directory-search" onSubmit={handleSearch}>
+        <input
+          class="add-friend-input"
+          type="text"
+          placeholder="Search communities..."
+          value={query()}
+          onInput={(e) => setQuery(e.currentTarget.value)}
+        />
+        <select
+          class="settings-select"
+          value={gameId() ?? ""}
+          onChange={(e) => setGameId(e.currentTarget.value ? Number(e.currentTarget.value) : null)}
+        >
+          <option value="">All games</option>
+          <For each={games()}>
+            {(game) => <option value={game.id}>{game.name}</option>}
+          </For>
+        </select>
+        <button class="add-friend-btn directory-search-btn" type="submit" disabled={searching()}>
+          {searching() ? "Searching..." : "Search"}
+        </button>
+      </form>
+
+      <Show
+        when={preview()}
+        fallback={
+          <div class="directory-results">
+            <Show when={searched() && results().length === 0}>
+              <div class="empty-placeholder-subtitle">No listed communities match.</div>
+            </Show>
+            <For each={results()}>
+              {(entry) => (
+                <div class="directory-result" onClick={() => handleSelect(entry)}>
+                  <div class="directory-result-header">
+                    <span class="directory-result-name">{entry.name}</span>
+                    <span class="directory-result-members">{entry.memberCount} members</span>
+                  </div>
+                  <Show when={entry.description}>
+                    <div class="directory-result-description">{entry.description}</div>
+                  </Show>
+                  <div class="directory-result-games">
+                    <For each={entry.gameIds}>
+                      {(id) => <span class="directory-game-tag">{gameName(id)}</span>}
+                    </For>
+                    <Show when={entry.joined}>
+                      <span class="directory-joined">Joined</span>
+                    </Show>
+                  </div>
+                </div>
+              )}
+            </For>
+          </div>
+        }
+      >
+        {(shown) => (
+          <div class="directory-preview">
+            <div class="directory-result-header">
+              <span class="directory-result-name">{shown().name}</span>
+              <span class="directory-result-members">
+                {shown().memberCount} members · {shown().online ? "online" : "server offline"}
+              </span>
+            </div>
+            <Show when={shown().description}>
+              <div class="directory-result-description">{shown().description}</div>
+            </Show>
+            <div class="directory-result-games">
+              <For each={shown().gameIds}>
+                {(id) => <span class="directory-game-tag">{gameName(id)}</span>}
+              </For>
+            </div>
+            <div class="settings-field-label">Channels</div>
+            <div class="directory-preview-channels">
+              <For each={shown().channels}>
+                {(channel) => (
+                  <div class="directory-preview-channel">
+                    <span class="nf-icon">
+                      {channel.channelType === "voice" ? ICON_VOLUME_HIGH : ICON_CHANNEL_TEXT}
+                    </span>
+                    {channel.name}
+                  </div>
+                )}
+              </For>
+            </div>
+            <div class="directory-preview-actions">
+              <button class="settings-copy-btn" onClick={() => setPreview(null)}>
+                Back
+              </button>
+              <button
+                class="add-friend-btn"
+                onClick={handleJoin}
+                disabled={joining() || shown().joined || !shown().online}
+              >
+                <span class="nf-icon">{ICON_JOIN}</span>{" "}
+                {shown().joined ? "Already joined" : joining() ? "Joining..." : "Join"}
+              </button>
+            </div>
+          </div>
+        )}
+      </Show>
+    </Modal>
+  );
+};
+
+export default BuddyCommunityBrowserModal;
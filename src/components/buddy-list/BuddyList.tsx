@@ -14,6 +14,7 @@ import {
 } from "../../handlers/buddy.handlers";
 import { commands } from "../../ipc/commands";
 import BuddyGroup from "./BuddyGroup";
+import MessageSearchResults from "./MessageSearchResults";
 import ScrollArea from "../common/ScrollArea";
 import ContextMenu from "../common/ContextMenu";
 import type { ContextMenuItem } from "../common/ContextMenu";
@@ -197,6 +198,7 @@ const BuddyList: Component = () => {
           </For>
         </Show>
       </Show>
+      <MessageSearchResults query={buddyListUI.searchQuery} />
       <Show when={friendsState.contextMenu}>
         {(menu) => (
           <>
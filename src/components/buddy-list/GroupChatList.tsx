@@ -0,0 +1,52 @@
+import { Component, For, Show, createMemo, createSignal } from "solid-js";
+import { groupState } from "../../stores/group.store";
+import { buddyListUI } from "../../stores/buddylist-ui.store";
+import { commands } from "../../ipc/commands";
+import type { GroupChat } from "../../stores/group.store";
+
+const GroupChatList: Component = () => {
+  const [collapsed, setCollapsed] = createSignal(false);
+
+  const filteredGroups = createMemo(() => {
+    const all = Object.values(groupState.groups).sort((a, b) =>
+      a.name.localeCompare(b.name),
+    );
+    const query = buddyListUI.searchQuery.trim().toLowerCase();
+    if (!query) return all;
+    return all.filter((g) => g.name.toLowerCase().includes(query));
+  });
+
+  function handleDoubleClick(group: GroupChat): void {
+    commands.openGroupWindow(group.id, group.name);
+  }
+
+  return (
+    <Show when={filteredGroups().length > 0}>
+      <div class="group-chat-list">
+        <div class="buddy-group-header" onClick={() => setCollapsed(!collapsed())}>
+          {collapsed() ? "\u25B6" : "\u25BC"} Group Chats ({filteredGroups().length})
+        </div>
+        <Show when={!collapsed()}>
+          <For each={filteredGroups()}>
+            {(group) => (
+              <div
+                class="community-item"
+                onDblClick={() => handleDoubleClick(group)}
+                title={group.members.map((m) => m.displayName).join(", ")}
+              >
+                <div class="community-icon">{group.name.charAt(0).toUpperCase()}</div>
+                <span class="community-name">{group.name}</span>
+                <span class="group-chat-count">{group.members.length}</span>
+                <Show when={group.unreadCount > 0}>
+                  <span class="buddy-unread-badge">{group.unreadCount}</span>
+                </Show>
+              </div>
+            )}
+          </For>
+        </Show>
+      </div>
+    </Show>
+  );
+};
+
+export default GroupChatList;
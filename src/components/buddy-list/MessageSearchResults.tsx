@@ -0,0 +1,82 @@
+import { Component, For, Show, createEffect, createSignal, onCleanup } from "solid-js";
+import type { SearchResult } from "../../ipc/commands";
+import { handleSearchMessages, handleOpenSearchResult } from "../../handlers/chat.handlers";
+import { ICON_FILE } from "../../icons";
+
+/** Wait this long after the last keystroke before searching. */
+const SEARCH_DEBOUNCE_MS = 250;
+/** Shorter queries match too much to be useful. */
+const MIN_QUERY_LENGTH = 2;
+
+interface MessageSearchResultsProps {
+  query: string;
+}
+
+interface SnippetPart {
+  text: string;
+  match: boolean;
+}
+
+/** Split a snippet on the backend's \u0002 … \u0003 highlight markers. */
+function snippetParts(snippet: string): SnippetPart[] {
+  const parts: SnippetPart[] = [];
+  for (const [i, chunk] of snippet.split(/[\u0002\u0003]/).entries()) {
+    if (chunk) parts.push({ text: chunk, match: i % 2 === 1 });
+  }
+  return parts;
+}
+
+function formatDate(ts: number): string {
+  return new Date(ts).toLocaleDateString([], { month: "short", day: "numeric" });
+}
+
+const MessageSearchResults: Component<MessageSearchResultsProps> = (props) => {
+  const [results, setResults] = createSignal<SearchResult[]>([]);
+
+  createEffect(() => {
+    const query = props.query.trim();
+    if (query.length < MIN_QUERY_LENGTH) {
+      setResults([]);
+      return;
+    }
+    const timer = setTimeout(async () => {
+      const found = await handleSearchMessages(query);
+      // Drop results for a query the user has since changed
+      if (props.query.trim() === query) setResults(found);
+    }, SEARCH_DEBOUNCE_MS);
+    onCleanup(() => clearTimeout(timer));
+  });
+
+  return (
+    <Show when={results().length > 0}>
+      <div class="message-search">
+        <div class="buddy-group-header">Messages</div>
+        <For each={results()}>
+          {(result) => (
+            <div class="message-search-item" onDblClick={() => handleOpenSearchResult(result)}>
+              <div class="message-search-meta">
+                <span class="message-search-conversation">
+                  {result.conversationType === "channel" ? "#" : ""}
+                  {result.conversationName ?? result.conversationId.slice(0, 12)}
+                </span>
+                <span class="message-search-date">{formatDate(result.timestamp)}</span>
+              </div>
+              <div class="message-search-snippet">
+                <Show when={result.hasAttachment}>
+                  <span class="nf-icon">{ICON_FILE} </span>
+                </Show>
+                <For each={snippetParts(result.snippet)}>
+                  {(part) =>
+                    part.match ? <mark class="message-search-match">{part.text}</mark> : part.text
+                  }
+                </For>
+              </div>
+            </div>
+          )}
+        </For>
+      </div>
+    </Show>
+  );
+};
+
+export default MessageSearchResults;
@@ -0,0 +1,90 @@
+import { Component, For, Show, createMemo, createSignal } from "solid-js";
+import Modal from "../common/Modal";
+import { friendsState } from "../../stores/friends.store";
+import { buddyListUI, setBuddyListUI } from "../../stores/buddylist-ui.store";
+import { MAX_GROUP_MEMBERS } from "../../stores/group.store";
+import { handleCreateGroupChat } from "../../handlers/group.handlers";
+import { commands } from "../../ipc/commands";
+import { ICON_GROUP_CHAT } from "../../icons";
+
+const NewGroupChatModal: Component = () => {
+  const [name, setName] = createSignal("");
+  const [selected, setSelected] = createSignal<string[]>([]);
+
+  const friends = createMemo(() =>
+    Object.values(friendsState.friends)
+      .filter((f) => f.friendshipState === "accepted")
+      .sort((a, b) => a.displayName.localeCompare(b.displayName)),
+  );
+
+  function handleClose(): void {
+    setBuddyListUI("showNewGroupChat", false);
+    setName("");
+    setSelected([]);
+  }
+
+  function toggle(publicKey: string): void {
+    setSelected((prev) =>
+      prev.includes(publicKey) ? prev.filter((k) => k !== publicKey) : [...prev, publicKey],
+    );
+  }
+
+  const full = () => selected().length >= MAX_GROUP_MEMBERS - 1;
+
+  async function handleSubmit(e: Event): Promise<void> {
+    e.preventDefault();
+    const n = name().trim();
+    if (!n || selected().length === 0) return;
+    const group = await handleCreateGroupChat(n, selected());
+    if (group) {
+      handleClose();
+      commands.openGroupWindow(group.id, group.name);
+    }
+  }
+
+  return (
+    <Modal isOpen={buddyListUI.showNewGroupChat} title="New Group Chat" onClose={handleClose}>
+      <form class="add-friend-form" onSubmit={handleSubmit}>
+        <input
+          class="add-friend-input"
+          type="text"
+          placeholder="Group name..."
+          maxLength={64}
+          value={name()}
+          onInput={(e) => setName(e.currentTarget.value)}
+        />
+        <div class="group-friend-picker">
+          <Show when={friends().length > 0} fallback={
+            <div class="empty-placeholder-subtitle">Add some friends first</div>
+          }>
+            <For each={friends()}>
+              {(friend) => {
+                const checked = () => selected().includes(friend.publicKey);
+                return (
+                  <label class="group-friend-option">
+                    <input
+                      type="checkbox"
+                      checked={checked()}
+                      disabled={!checked() && full()}
+                      onChange={() => toggle(friend.publicKey)}
+                    />
+                    {friend.nickname ?? friend.displayName}
+                  </label>
+                );
+              }}
+            </For>
+          </Show>
+        </div>
+        <button
+          class="add-friend-btn"
+          type="submit"
+          disabled={!name().trim() || selected().length === 0}
+        >
+          <span class="nf-icon">{ICON_GROUP_CHAT}</span> Start Group ({selected().length + 1}/{MAX_GROUP_MEMBERS})
+        </button>
+      </form>
+    </Modal>
+  );
+};
+
+export default NewGroupChatModal;
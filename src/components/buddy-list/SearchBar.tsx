@@ -20,7 +20,7 @@ export function focusSearchInput(): void {
 
 const SearchBar: Component = () => {
   const placeholder = () =>
-    buddyListUI.activeTab === "friends" ? "Search friends..." : "Search communities...";
+    buddyListUI.activeTab === "friends" ? "Search friends and messages..." : "Search communities...";
 
   return (
     <div class="buddy-search-wrapper">
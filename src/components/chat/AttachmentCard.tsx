@@ -0,0 +1,74 @@
+import { Component, Show } from "solid-js";
+import { revealItemInDir } from "@tauri-apps/plugin-opener";
+import type { Attachment } from "../../stores/chat.store";
+import { ICON_FILE, ICON_DOWNLOAD, ICON_FOLDER_OPEN, ICON_REFRESH } from "../../icons";
+
+interface AttachmentCardProps {
+  attachment: Attachment;
+  isOwn: boolean;
+  onDownload?: (attachment: Attachment) => void;
+}
+
+function formatSize(bytes: number): string {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  if (bytes < 1024 * 1024 * 1024) return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+  return `${(bytes / (1024 * 1024 * 1024)).toFixed(2)} GB`;
+}
+
+const AttachmentCard: Component<AttachmentCardProps> = (props) => {
+  const percent = () =>
+    props.attachment.total === 0
+      ? 100
+      : Math.floor((props.attachment.done / props.attachment.total) * 100);
+
+  const statusLabel = () => {
+    switch (props.attachment.state) {
+      case "active": return props.isOwn ? `Uploading ${percent()}%` : `Downloading ${percent()}%`;
+      case "complete": return props.isOwn ? "Sent" : "Downloaded";
+      case "failed": return "Failed";
+      default: return "";
+    }
+  };
+
+  function handleReveal(): void {
+    if (props.attachment.localPath) {
+      revealItemInDir(props.attachment.localPath).catch(() => {});
+    }
+  }
+
+  return (
+    <div class={`attachment-card attachment-${props.attachment.state}`}>
+      <span class="attachment-icon nf-icon">{ICON_FILE}</span>
+      <div class="attachment-info">
+        <span class="attachment-name" title={props.attachment.name}>{props.attachment.name}</span>
+        <span class="attachment-meta">
+          {formatSize(props.attachment.size)}
+          <Show when={statusLabel()}> · {statusLabel()}</Show>
+        </span>
+        <Show when={props.attachment.state === "active"}>
+          <div class="attachment-progress">
+            <div class="attachment-progress-fill" style={{ width: `${percent()}%` }} />
+          </div>
+        </Show>
+      </div>
+      <Show when={props.attachment.state === "available"}>
+        <button class="attachment-btn" title="Download" onClick={() => props.onDownload?.(props.attachment)}>
+          <span class="nf-icon">{ICON_DOWNLOAD}</span>
+        </button>
+      </Show>
+      <Show when={props.attachment.state === "failed"}>
+        <button class="attachment-btn" title="Retry" onClick={() => props.onDownload?.(props.attachment)}>
+          <span class="nf-icon">{ICON_REFRESH}</span>
+        </button>
+      </Show>
+      <Show when={props.attachment.state === "complete" && props.attachment.localPath}>
+        <button class="attachment-btn" title="Show in folder" onClick={handleReveal}>
+          <span class="nf-icon">{ICON_FOLDER_OPEN}</span>
+        </button>
+      </Show>
+    </div>
+  );
+};
+
+export default AttachmentCard;
@@ -0,0 +1,67 @@
+import { Component, For, Show } from "solid-js";
+import { ICON_CLOCK } from "../../icons";
+
+/** Timers offered in the picker, in seconds. */
+const TIMER_OPTIONS: { seconds: number; label: string }[] = [
+  { seconds: 0, label: "Off" },
+  { seconds: 5 * 60, label: "5 minutes" },
+  { seconds: 60 * 60, label: "1 hour" },
+  { seconds: 24 * 60 * 60, label: "1 day" },
+  { seconds: 7 * 24 * 60 * 60, label: "1 week" },
+  { seconds: 28 * 24 * 60 * 60, label: "4 weeks" },
+];
+
+/** Short label for a timer, e.g. "1 day" or "90s". */
+export function formatTimer(seconds: number): string {
+  const preset = TIMER_OPTIONS.find((o) => o.seconds === seconds);
+  if (preset) return preset.label;
+  if (seconds % 86400 === 0) return `${seconds / 86400}d`;
+  if (seconds % 3600 === 0) return `${seconds / 3600}h`;
+  if (seconds % 60 === 0) return `${seconds / 60}m`;
+  return `${seconds}s`;
+}
+
+interface DisappearingTimerProps {
+  seconds: number;
+  /** Whether we may change it; otherwise a set timer is only shown. */
+  editable: boolean;
+  onChange: (seconds: number) => void;
+}
+
+/** Chat header control showing how long messages are kept. */
+const DisappearingTimer: Component<DisappearingTimerProps> = (props) => {
+  return (
+    <Show
+      when={props.editable}
+      fallback={
+        <Show when={props.seconds > 0}>
+          <span class="disappearing-timer" title="Messages disappear after this long">
+            <span class="nf-icon">{ICON_CLOCK}</span> {formatTimer(props.seconds)}
+          </span>
+        </Show>
+      }
+    >
+      <label
+        class={`disappearing-timer ${props.seconds > 0 ? "disappearing-timer-active" : ""}`}
+        title="Disappearing messages"
+      >
+        <span class="nf-icon">{ICON_CLOCK}</span>
+        <select
+          class="disappearing-timer-select"
+          value={props.seconds}
+          onChange={(e) => props.onChange(Number(e.currentTarget.value))}
+        >
+          {/* A timer set elsewhere that isn't a preset still shows */}
+          <Show when={!TIMER_OPTIONS.some((o) => o.seconds === props.seconds)}>
+            <option value={props.seconds}>{formatTimer(props.seconds)}</option>
+          </Show>
+          <For each={TIMER_OPTIONS}>
+            {(option) => <option value={option.seconds}>{option.label}</option>}
+          </For>
+        </select>
+      </label>
+    </Show>
+  );
+};
+
+export default DisappearingTimer;
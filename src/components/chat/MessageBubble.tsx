@@ -1,11 +1,41 @@
-import { Component, Show } from "solid-js";
-import type { Message } from "../../stores/chat.store";
-import { ICON_DOTS, ICON_CHECK, ICON_CLOSE_CIRCLE, ICON_REFRESH } from "../../icons";
+import { Component, For, Show, createSignal } from "solid-js";
+import type { Attachment, Message } from "../../stores/chat.store";
+import type { ThreadInfo } from "../../ipc/commands";
+import {
+  ICON_DOTS,
+  ICON_CHECK,
+  ICON_CHECK_ALL,
+  ICON_CLOCK,
+  ICON_CLOSE_CIRCLE,
+  ICON_REFRESH,
+  ICON_EMOTICON,
+  ICON_PENCIL,
+  ICON_DELETE,
+  ICON_REPLY,
+  ICON_THREAD,
+} from "../../icons";
+import AttachmentCard from "./AttachmentCard";
+import ConfirmDialog from "../common/ConfirmDialog";
+
+const QUICK_REACTIONS = ["👍", "❤️", "😂", "😮", "😢", "🎉"];
 
 interface MessageBubbleProps {
   message: Message;
   senderName: string;
+  /** Whether we may delete this message even if it isn't ours. */
+  canModerate?: boolean;
   onRetry?: (messageId: number) => void;
+  onDownload?: (attachment: Attachment) => void;
+  onEdit?: (message: Message, body: string) => void;
+  onDelete?: (message: Message) => void;
+  onReact?: (message: Message, emoji: string) => void;
+  /** The message this one replies to, if it is loaded. */
+  quoted?: { senderName: string; body: string };
+  /** The thread started from this message. */
+  thread?: ThreadInfo;
+  onReply?: (message: Message) => void;
+  onStartThread?: (message: Message) => void;
+  onOpenThread?: (thread: ThreadInfo) => void;
 }
 
 function formatTimestamp(ts: number): string {
@@ -23,7 +53,10 @@ const MessageBubble: Component<MessageBubbleProps> = (props) => {
     if (!props.message.isOwn || !props.message.status) return null;
     switch (props.message.status) {
       case "sending": return ICON_DOTS;
+      case "queued": return ICON_CLOCK;
       case "sent": return ICON_CHECK;
+      case "delivered":
+      case "read": return ICON_CHECK_ALL;
       case "failed": return ICON_CLOSE_CIRCLE;
       default: return null;
     }
@@ -40,8 +73,60 @@ const MessageBubble: Component<MessageBubbleProps> = (props) => {
     }
   }
 
+  const [editing, setEditing] = createSignal(false);
+  const [draft, setDraft] = createSignal("");
+  const [picking, setPicking] = createSignal(false);
+  const [confirmDelete, setConfirmDelete] = createSignal(false);
+
+  // Only messages with a global ID that finished sending can be changed
+  const canAct = () =>
+    !!props.message.messageId &&
+    props.message.status !== "sending" &&
+    props.message.status !== "failed";
+  const canEdit = () => props.message.isOwn && !!props.message.body && !!props.onEdit;
+  const canDelete = () => (props.message.isOwn || !!props.canModerate) && !!props.onDelete;
+
+  function startEdit(): void {
+    setDraft(props.message.body);
+    setEditing(true);
+  }
+
+  function saveEdit(): void {
+    const body = draft().trim();
+    if (body && body !== props.message.body) {
+      props.onEdit?.(props.message, body);
+    }
+    setEditing(false);
+  }
+
+  function handleEditKeyDown(e: KeyboardEvent): void {
+    if (e.key === "Enter" && !e.shiftKey) {
+      e.preventDefault();
+      saveEdit();
+    } else if (e.key === "Escape") {
+      setEditing(false);
+    }
+  }
+
+  function react(emoji: string): void {
+    setPicking(false);
+    props.onReact?.(props.message, emoji);
+  }
+
   return (
     <div class="chat-message message-enter">
+      <Show when={props.message.replyTo}>
+        <div class="message-reply-quote">
+          <span class="nf-icon">{ICON_REPLY}</span>{" "}
+          <Show when={props.quoted} fallback="Original message not loaded">
+            {(quoted) => (
+              <>
+                <span class="message-reply-sender">{quoted().senderName}</span> {quoted().body}
+              </>
+            )}
+          </Show>
+        </div>
+      </Show>
       <span class={senderClass()}>{props.senderName}</span>
       <span class="chat-message-timestamp">
         {formatTimestamp(props.message.timestamp)}
@@ -58,7 +143,116 @@ const MessageBubble: Component<MessageBubbleProps> = (props) => {
           <span class="nf-icon">{ICON_REFRESH}</span>
         </button>
       </Show>
-      <div class="chat-message-body">{props.message.body}</div>
+      <Show when={canAct()}>
+        <span class="message-actions">
+          <Show when={props.onReact}>
+            <button class="message-action-btn" title="React" onClick={() => setPicking(!picking())}>
+              <span class="nf-icon">{ICON_EMOTICON}</span>
+            </button>
+          </Show>
+          <Show when={props.onReply}>
+            <button class="message-action-btn" title="Reply" onClick={() => props.onReply?.(props.message)}>
+              <span class="nf-icon">{ICON_REPLY}</span>
+            </button>
+          </Show>
+          <Show when={props.onStartThread && !props.thread}>
+            <button
+              class="message-action-btn"
+              title="Start Thread"
+              onClick={() => props.onStartThread?.(props.message)}
+            >
+              <span class="nf-icon">{ICON_THREAD}</span>
+            </button>
+          </Show>
+          <Show when={canEdit()}>
+            <button class="message-action-btn" title="Edit" onClick={startEdit}>
+              <span class="nf-icon">{ICON_PENCIL}</span>
+            </button>
+          </Show>
+          <Show when={canDelete()}>
+            <button class="message-action-btn" title="Delete" onClick={() => setConfirmDelete(true)}>
+              <span class="nf-icon">{ICON_DELETE}</span>
+            </button>
+          </Show>
+        </span>
+      </Show>
+      <Show when={picking()}>
+        <div class="reaction-picker">
+          <For each={QUICK_REACTIONS}>
+            {(emoji) => (
+              <button class="reaction-picker-btn" onClick={() => react(emoji)}>{emoji}</button>
+            )}
+          </For>
+        </div>
+      </Show>
+      <Show
+        when={editing()}
+        fallback={
+          <Show when={props.message.body}>
+            <div class="chat-message-body">
+              {props.message.body}
+              <Show when={props.message.editedAt}>
+                <span class="message-edited">(edited)</span>
+              </Show>
+            </div>
+          </Show>
+        }
+      >
+        <input
+          class="message-edit-input"
+          value={draft()}
+          onInput={(e) => setDraft(e.currentTarget.value)}
+          onKeyDown={handleEditKeyDown}
+          onBlur={() => setEditing(false)}
+          ref={(el) => requestAnimationFrame(() => el.focus())}
+        />
+      </Show>
+      <For each={props.message.attachments ?? []}>
+        {(attachment) => (
+          <AttachmentCard
+            attachment={attachment}
+            isOwn={props.message.isOwn}
+            onDownload={props.onDownload}
+          />
+        )}
+      </For>
+      <Show when={props.thread}>
+        {(thread) => (
+          <button class="message-thread-link" onClick={() => props.onOpenThread?.(thread())}>
+            <span class="nf-icon">{ICON_THREAD}</span> {thread().name}
+            <span class="message-thread-count">
+              {thread().messageCount} {thread().messageCount === 1 ? "message" : "messages"}
+            </span>
+          </button>
+        )}
+      </Show>
+      <Show when={props.message.reactions?.length}>
+        <div class="message-reactions">
+          <For each={props.message.reactions}>
+            {(reaction) => (
+              <button
+                class={`reaction-chip${reaction.mine ? " reaction-chip-mine" : ""}`}
+                onClick={() => react(reaction.emoji)}
+                disabled={!canAct() || !props.onReact}
+              >
+                {reaction.emoji} {reaction.count}
+              </button>
+            )}
+          </For>
+        </div>
+      </Show>
+      <ConfirmDialog
+        isOpen={confirmDelete()}
+        title="Delete Message"
+        message="Delete this message for everyone?"
+        danger
+        confirmLabel="Delete"
+        onConfirm={() => {
+          setConfirmDelete(false);
+          props.onDelete?.(props.message);
+        }}
+        onCancel={() => setConfirmDelete(false)}
+      />
     </div>
   );
 };
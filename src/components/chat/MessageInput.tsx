@@ -1,9 +1,13 @@
-import { Component, createSignal } from "solid-js";
+import { Component, Show, createSignal } from "solid-js";
 import { handleKeyDown } from "../../handlers/chat.handlers";
+import { ICON_CLOSE, ICON_REPLY } from "../../icons";
 
 interface MessageInputProps {
   peerId: string;
   onSend?: (id: string, body: string) => void;
+  /** Who the next message replies to, shown above the input. */
+  replyingTo?: string | null;
+  onCancelReply?: () => void;
 }
 
 const MessageInput: Component<MessageInputProps> = (props) => {
@@ -26,6 +30,8 @@ const MessageInput: Component<MessageInputProps> = (props) => {
           props.onSend(props.peerId, text);
           clearInput();
         }
+      } else if (e.key === "Escape" && props.replyingTo) {
+        props.onCancelReply?.();
       }
     } else {
       handleKeyDown(e, props.peerId, getBody, clearInput);
@@ -38,6 +44,14 @@ const MessageInput: Component<MessageInputProps> = (props) => {
 
   return (
     <div class="message-input-wrapper">
+      <Show when={props.replyingTo}>
+        <div class="message-reply-bar">
+          <span class="nf-icon">{ICON_REPLY}</span> Replying to {props.replyingTo}
+          <button class="message-action-btn" title="Cancel Reply" onClick={() => props.onCancelReply?.()}>
+            <span class="nf-icon">{ICON_CLOSE}</span>
+          </button>
+        </div>
+      </Show>
       <textarea
         class="message-input message-input-field"
         placeholder="Type a message..."
@@ -1,17 +1,44 @@
-import { Component, For, createEffect, onMount } from "solid-js";
-import type { Message } from "../../stores/chat.store";
+import { Component, For, createEffect, createMemo, onMount } from "solid-js";
+import type { Attachment, Message } from "../../stores/chat.store";
+import type { ThreadInfo } from "../../ipc/commands";
 import MessageBubble from "./MessageBubble";
 
 interface MessageListProps {
   messages: Message[];
   ownName: string;
   peerName: string;
+  /** Names the sender of others' messages when there is more than one peer. */
+  senderName?: (senderId: string) => string;
+  /** Whether we may delete other people's messages. */
+  canModerate?: boolean;
   onRetry?: (messageId: number) => void;
+  onDownload?: (attachment: Attachment) => void;
+  onEdit?: (message: Message, body: string) => void;
+  onDelete?: (message: Message) => void;
+  onReact?: (message: Message, emoji: string) => void;
+  onReply?: (message: Message) => void;
+  onStartThread?: (message: Message) => void;
+  /** The thread started from a message, by the message's global ID. */
+  threadFor?: (messageId: string) => ThreadInfo | undefined;
+  onOpenThread?: (thread: ThreadInfo) => void;
 }
 
 const MessageList: Component<MessageListProps> = (props) => {
   let containerRef: HTMLDivElement | undefined;
 
+  const byMessageId = createMemo(
+    () => new Map(props.messages.filter((m) => m.messageId).map((m) => [m.messageId!, m])),
+  );
+
+  function nameOf(msg: Message): string {
+    return msg.isOwn ? props.ownName : props.senderName?.(msg.senderId) ?? props.peerName;
+  }
+
+  function quoted(msg: Message): { senderName: string; body: string } | undefined {
+    const original = msg.replyTo ? byMessageId().get(msg.replyTo) : undefined;
+    return original ? { senderName: nameOf(original), body: original.body } : undefined;
+  }
+
   function scrollToBottom(): void {
     requestAnimationFrame(() => {
       if (containerRef) {
@@ -34,8 +61,18 @@ const MessageList: Component<MessageListProps> = (props) => {
         {(msg) => (
           <MessageBubble
             message={msg}
-            senderName={msg.isOwn ? props.ownName : props.peerName}
+            senderName={nameOf(msg)}
+            canModerate={props.canModerate}
             onRetry={props.onRetry}
+            onDownload={props.onDownload}
+            onEdit={props.onEdit}
+            onDelete={props.onDelete}
+            onReact={props.onReact}
+            quoted={quoted(msg)}
+            thread={msg.messageId ? props.threadFor?.(msg.messageId) : undefined}
+            onReply={props.onReply}
+            onStartThread={props.onStartThread}
+            onOpenThread={props.onOpenThread}
           />
         )}
       </For>
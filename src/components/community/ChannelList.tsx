@@ -1,5 +1,6 @@
 import { Component, For, Show, createMemo, createSignal } from "solid-js";
 import { Channel } from "../../stores/community.store";
+import type { ThreadInfo } from "../../ipc/commands";
 import ContextMenu from "../common/ContextMenu";
 import type { ContextMenuItem } from "../common/ContextMenu";
 import {
@@ -8,6 +9,7 @@ import {
   ICON_PHONE,
   ICON_PENCIL,
   ICON_DELETE,
+  ICON_THREAD,
 } from "../../icons";
 
 interface ChannelListProps {
@@ -19,6 +21,11 @@ interface ChannelListProps {
   onVoiceJoin?: (id: string) => void;
   onRename?: (channelId: string, currentName: string) => void;
   onDelete?: (channelId: string) => void;
+  /** Open threads, listed under their channels. */
+  threads?: ThreadInfo[];
+  selectedThreadId?: string;
+  onSelectThread?: (thread: ThreadInfo) => void;
+  threadMenuItems?: (thread: ThreadInfo) => ContextMenuItem[];
 }
 
 const ChannelList: Component<ChannelListProps> = (props) => {
@@ -30,12 +37,29 @@ const ChannelList: Component<ChannelListProps> = (props) => {
     props.channels.filter((c) => c.type === "voice")
   );
 
+  function threadsOf(channelId: string): ThreadInfo[] {
+    return (props.threads ?? []).filter((t) => t.channelId === channelId && !t.archived);
+  }
+
   const [contextMenu, setContextMenu] = createSignal<{
     x: number;
     y: number;
     channel: Channel;
   } | null>(null);
 
+  const [threadMenu, setThreadMenu] = createSignal<{
+    x: number;
+    y: number;
+    items: ContextMenuItem[];
+  } | null>(null);
+
+  function handleThreadContextMenu(e: MouseEvent, thread: ThreadInfo): void {
+    e.preventDefault();
+    const items = props.threadMenuItems?.(thread) ?? [];
+    if (items.length === 0) return;
+    setThreadMenu({ x: e.clientX, y: e.clientY, items });
+  }
+
   function handleContextMenu(e: MouseEvent, channel: Channel): void {
     e.preventDefault();
     if (!props.canManage) return;
@@ -69,17 +93,31 @@ const ChannelList: Component<ChannelListProps> = (props) => {
       <div class="channel-section-header">Text Channels</div>
       <For each={textChannels()}>
         {(channel) => (
-          <div
-            class={`channel-item ${props.selectedId === channel.id ? "channel-item-selected" : ""}`}
-            onClick={() => props.onSelect(channel.id)}
-            onContextMenu={(e) => handleContextMenu(e, channel)}
-          >
-            <span class="nf-icon channel-icon">{ICON_CHANNEL_TEXT}</span>
-            <span class="channel-name">{channel.name}</span>
-            {channel.unreadCount > 0 && (
-              <span class="channel-unread-badge">{channel.unreadCount}</span>
-            )}
-          </div>
+          <>
+            <div
+              class={`channel-item ${props.selectedId === channel.id && !props.selectedThreadId ? "channel-item-selected" : ""}`}
+              onClick={() => props.onSelect(channel.id)}
+              onContextMenu={(e) => handleContextMenu(e, channel)}
+            >
+              <span class="nf-icon channel-icon">{ICON_CHANNEL_TEXT}</span>
+              <span class="channel-name">{channel.name}</span>
+              {channel.unreadCount > 0 && (
+                <span class="channel-unread-badge">{channel.unreadCount}</span>
+              )}
+            </div>
+            <For each={threadsOf(channel.id)}>
+              {(thread) => (
+                <div
+                  class={`channel-item channel-thread-item ${props.selectedThreadId === thread.id ? "channel-item-selected" : ""}`}
+                  onClick={() => props.onSelectThread?.(thread)}
+                  onContextMenu={(e) => handleThreadContextMenu(e, thread)}
+                >
+                  <span class="nf-icon channel-icon">{ICON_THREAD}</span>
+                  <span class="channel-name">{thread.name}</span>
+                </div>
+              )}
+            </For>
+          </>
         )}
       </For>
 
@@ -121,6 +159,16 @@ const ChannelList: Component<ChannelListProps> = (props) => {
           />
         )}
       </Show>
+      <Show when={threadMenu()}>
+        {(menu) => (
+          <ContextMenu
+            items={menu().items}
+            x={menu().x}
+            y={menu().y}
+            onClose={() => setThreadMenu(null)}
+          />
+        )}
+      </Show>
     </div>
   );
 };
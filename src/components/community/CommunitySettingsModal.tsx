@@ -4,7 +4,8 @@ import ConfirmDialog from "../common/ConfirmDialog";
 import StatusDot from "../status/StatusDot";
 import RoleTag from "./RoleTag";
 import { commands } from "../../ipc/commands";
-import type { Community, Member, Role } from "../../stores/community.store";
+import type { AuditAction, AuditEntry, CommunityInvite, KnownGame } from "../../ipc/commands";
+import type { Community, HistoryVisibility, Member, Role } from "../../stores/community.store";
 import {
   handleDeleteChannel,
   handleRenameChannel,
@@ -14,7 +15,18 @@ import {
   handleBanMember,
   handleUnbanMember,
   handleGetBanList,
+  handleGetAuditLog,
+  handleSetAuditRetention,
+  handleGetCommunityListing,
+  handleSetCommunityListing,
+  handleCreateCommunityInvite,
+  handleListCommunityInvites,
+  handleRevokeCommunityInvite,
+  handleSetInviteOnly,
   handleRotateMek,
+  handleEnableZeroKnowledge,
+  handleEnableTreeKem,
+  handleSetHistoryVisibility,
   handleAssignRole,
   handleUnassignRole,
   handleTimeoutMember,
@@ -27,12 +39,14 @@ import {
   calculateBasePermissions,
   highestPosition,
   hasPermission,
+  CREATE_INSTANT_INVITE,
   MANAGE_CHANNELS,
   MANAGE_COMMUNITY,
   MANAGE_ROLES,
   KICK_MEMBERS,
   BAN_MEMBERS,
   MODERATE_MEMBERS,
+  VIEW_AUDIT_LOG,
   PERMISSION_CATEGORIES,
 } from "../../ipc/permissions";
 import { addToast } from "../../stores/toast.store";
@@ -62,7 +76,81 @@ interface CommunitySettingsModalProps {
   onClose: () => void;
 }
 
-type TabId = "overview" | "channels" | "members" | "roles" | "bans" | "security";
+type TabId =
+  | "overview"
+  | "channels"
+  | "members"
+  | "roles"
+  | "invites"
+  | "bans"
+  | "audit"
+  | "security";
+
+/** Most games a directory listing may be tagged with (`MAX_LISTING_GAMES`). */
+const MAX_LISTING_GAMES = 8;
+
+/** Invite use limits offered when creating an invite; 0 is unlimited. */
+const INVITE_MAX_USES = [0, 1, 5, 10, 25, 50, 100];
+
+/** Invite lifetimes offered when creating an invite; 0 never expires. */
+const INVITE_EXPIRY: { secs: number; label: string }[] = [
+  { secs: 30 * 60, label: "30 minutes" },
+  { secs: 60 * 60, label: "1 hour" },
+  { secs: 6 * 60 * 60, label: "6 hours" },
+  { secs: 12 * 60 * 60, label: "12 hours" },
+  { secs: 24 * 60 * 60, label: "1 day" },
+  { secs: 7 * 24 * 60 * 60, label: "7 days" },
+  { secs: 0, label: "Never" },
+];
+
+/** Audit log entries fetched per page. */
+const AUDIT_PAGE_SIZE = 50;
+
+/** Audit log retention choices; 0 keeps entries forever. */
+const AUDIT_RETENTION: { secs: number; label: string }[] = [
+  { secs: 7 * 24 * 60 * 60, label: "7 days" },
+  { secs: 30 * 24 * 60 * 60, label: "30 days" },
+  { secs: 90 * 24 * 60 * 60, label: "90 days" },
+  { secs: 180 * 24 * 60 * 60, label: "180 days" },
+  { secs: 365 * 24 * 60 * 60, label: "1 year" },
+  { secs: 0, label: "Forever" },
+];
+
+/** How each audit action reads in the log. */
+const AUDIT_ACTION_LABELS: Record<AuditAction, string> = {
+  community_update: "Updated the community",
+  listing_update: "Changed the directory listing",
+  channel_create: "Created a channel",
+  channel_update: "Updated a channel",
+  channel_delete: "Deleted a channel",
+  channel_overwrite_update: "Changed channel permissions",
+  channel_overwrite_delete: "Removed channel permissions",
+  role_create: "Created a role",
+  role_update: "Updated a role",
+  role_delete: "Deleted a role",
+  member_role_add: "Gave a role",
+  member_role_remove: "Took away a role",
+  member_kick: "Kicked a member",
+  member_ban: "Banned a member",
+  member_unban: "Unbanned a member",
+  member_timeout: "Timed out a member",
+  member_timeout_remove: "Ended a timeout",
+  message_delete: "Deleted a message",
+  invite_create: "Created an invite",
+  invite_revoke: "Revoked an invite",
+  mek_rotate: "Rotated the encryption key",
+  zero_knowledge_enable: "Enabled zero-knowledge mode",
+  tree_kem_enable: "Enabled tree key agreement",
+  thread_update: "Archived or reopened a thread",
+  thread_member_remove: "Removed a thread member",
+};
+
+function inviteStatus(invite: CommunityInvite): string {
+  const uses = invite.maxUses === null ? `${invite.uses} uses` : `${invite.uses}/${invite.maxUses} uses`;
+  if (invite.expiresAt === null) return `${uses} · never expires`;
+  if (invite.expiresAt * 1000 <= Date.now()) return `${uses} · expired`;
+  return `${uses} · expires ${new Date(invite.expiresAt * 1000).toLocaleString()}`;
+}
 
 const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) => {
   const [activeTab, setActiveTab] = createSignal<TabId>("overview");
@@ -73,6 +161,13 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
   const [copied, setCopied] = createSignal(false);
   const [savingOverview, setSavingOverview] = createSignal(false);
 
+  // Directory listing state
+  const [listed, setListed] = createSignal(false);
+  const [listingGameIds, setListingGameIds] = createSignal<number[]>([]);
+  const [knownGames, setKnownGames] = createSignal<KnownGame[]>([]);
+  const [listingLoaded, setListingLoaded] = createSignal(false);
+  const [savingListing, setSavingListing] = createSignal(false);
+
   // Channels tab state
   const [renamingChannelId, setRenamingChannelId] = createSignal<string | null>(null);
   const [renameValue, setRenameValue] = createSignal("");
@@ -91,10 +186,28 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
   // Members tab state
   const [rolePickerTarget, setRolePickerTarget] = createSignal<string | null>(null);
 
+  // Invites tab state
+  const [invites, setInvites] = createSignal<CommunityInvite[]>([]);
+  const [inviteOnly, setInviteOnly] = createSignal(false);
+  const [invitesLoaded, setInvitesLoaded] = createSignal(false);
+  const [inviteMaxUses, setInviteMaxUses] = createSignal(0);
+  const [inviteExpiry, setInviteExpiry] = createSignal(24 * 60 * 60);
+  const [inviteRoleIds, setInviteRoleIds] = createSignal<number[]>([]);
+  const [creatingInvite, setCreatingInvite] = createSignal(false);
+  const [copiedInvite, setCopiedInvite] = createSignal<string | null>(null);
+
   // Bans tab state
   const [banList, setBanList] = createSignal<{ pseudonymKey: string; displayName: string; bannedAt: number }[]>([]);
   const [bansLoaded, setBansLoaded] = createSignal(false);
 
+  // Audit log tab state
+  const [auditEntries, setAuditEntries] = createSignal<AuditEntry[]>([]);
+  const [auditFilter, setAuditFilter] = createSignal<AuditAction | null>(null);
+  const [auditRetention, setAuditRetention] = createSignal(90 * 24 * 60 * 60);
+  const [auditLoaded, setAuditLoaded] = createSignal(false);
+  const [auditHasMore, setAuditHasMore] = createSignal(false);
+  const [loadingAudit, setLoadingAudit] = createSignal(false);
+
   // Roles tab state
   const [newRoleName, setNewRoleName] = createSignal("");
   const [newRoleColor, setNewRoleColor] = createSignal("#000000");
@@ -122,9 +235,11 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
   const canManageCommunity = createMemo(() => hasPermission(myPerms(), MANAGE_COMMUNITY));
   const canManageChannels = createMemo(() => hasPermission(myPerms(), MANAGE_CHANNELS));
   const canManageRoles = createMemo(() => hasPermission(myPerms(), MANAGE_ROLES));
+  const canCreateInvite = createMemo(() => hasPermission(myPerms(), CREATE_INSTANT_INVITE));
   const canKick = createMemo(() => hasPermission(myPerms(), KICK_MEMBERS));
   const canBan = createMemo(() => hasPermission(myPerms(), BAN_MEMBERS));
   const canModerate = createMemo(() => hasPermission(myPerms(), MODERATE_MEMBERS));
+  const canViewAuditLog = createMemo(() => hasPermission(myPerms(), VIEW_AUDIT_LOG));
 
   const tabs = createMemo((): { id: TabId; label: string }[] => {
     const base: { id: TabId; label: string }[] = [
@@ -135,9 +250,15 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
     if (canManageRoles()) {
       base.push({ id: "roles", label: "Roles" });
     }
+    if (canCreateInvite() || canManageCommunity()) {
+      base.push({ id: "invites", label: "Invites" });
+    }
     if (canBan()) {
       base.push({ id: "bans", label: "Bans" });
     }
+    if (canViewAuditLog()) {
+      base.push({ id: "audit", label: "Audit Log" });
+    }
     if (canManageCommunity()) {
       base.push({ id: "security", label: "Security" });
     }
@@ -153,6 +274,12 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
       setShowNewChannel(false);
       setRolePickerTarget(null);
       setBansLoaded(false);
+      setAuditLoaded(false);
+      setAuditFilter(null);
+      setListingLoaded(false);
+      setInvitesLoaded(false);
+      setInviteRoleIds([]);
+      setCopiedInvite(null);
       setShowNewRole(false);
       setNewRoleName("");
       setActiveTab("overview");
@@ -161,6 +288,29 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
     }
   });
 
+  createEffect(() => {
+    if (props.isOpen && activeTab() === "overview" && !listingLoaded() && canManageCommunity()) {
+      setListingLoaded(true);
+      handleGetCommunityListing(props.community.id).then((listing) => {
+        setListed(listing?.listed ?? false);
+        setListingGameIds(listing?.gameIds ?? []);
+      });
+      if (knownGames().length === 0) {
+        commands.getKnownGames().then(setKnownGames).catch(() => {});
+      }
+    }
+  });
+
+  createEffect(() => {
+    if (activeTab() === "invites" && !invitesLoaded()) {
+      setInvitesLoaded(true);
+      handleListCommunityInvites(props.community.id).then((loaded) => {
+        setInviteOnly(loaded?.inviteOnly ?? false);
+        setInvites(loaded?.invites ?? []);
+      });
+    }
+  });
+
   createEffect(() => {
     if (activeTab() === "bans" && !bansLoaded() && canBan()) {
       setBansLoaded(true);
@@ -168,6 +318,13 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
     }
   });
 
+  createEffect(() => {
+    if (activeTab() === "audit" && !auditLoaded() && canViewAuditLog()) {
+      setAuditLoaded(true);
+      loadAuditLog(false);
+    }
+  });
+
   function handleCopyId(): void {
     navigator.clipboard.writeText(props.community.id);
     setCopied(true);
@@ -193,6 +350,124 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
     }
   }
 
+  function gameName(id: number): string {
+    return knownGames().find((g) => g.id === id)?.name ?? `Game #${id}`;
+  }
+
+  async function handleSaveListing(): Promise<void> {
+    setSavingListing(true);
+    try {
+      const listing = await handleSetCommunityListing(props.community.id, listed(), listingGameIds());
+      if (listing) {
+        setListed(listing.listed);
+        setListingGameIds(listing.gameIds);
+      }
+    } finally {
+      setSavingListing(false);
+    }
+  }
+
+  /** Roles we may hand out with an invite: those below our own. */
+  const grantableRoles = createMemo(() => {
+    const myPos = props.community.isHosted
+      ? Infinity
+      : highestPosition(props.myRoleIds, props.community.roles);
+    return props.community.roles
+      .filter((r) => r.id !== 0 && r.position < myPos)
+      .sort((a, b) => b.position - a.position);
+  });
+
+  async function handleCreateInvite(): Promise<void> {
+    setCreatingInvite(true);
+    try {
+      const invite = await handleCreateCommunityInvite(
+        props.community.id,
+        inviteMaxUses() || null,
+        inviteExpiry() || null,
+        inviteRoleIds(),
+      );
+      if (invite) {
+        setInvites((prev) => [invite, ...prev]);
+        setInviteRoleIds([]);
+        handleCopyInvite(invite);
+      }
+    } finally {
+      setCreatingInvite(false);
+    }
+  }
+
+  /** Load the newest page of the audit log, or (`more`) the page after the last entry shown. */
+  async function loadAuditLog(more: boolean): Promise<void> {
+    const shown = more ? auditEntries() : [];
+    const before = shown.length > 0 ? shown[shown.length - 1].id : null;
+    setLoadingAudit(true);
+    try {
+      const page = await handleGetAuditLog(props.community.id, before, AUDIT_PAGE_SIZE, auditFilter());
+      if (page) {
+        setAuditEntries([...shown, ...page.entries]);
+        setAuditHasMore(page.entries.length === AUDIT_PAGE_SIZE);
+        setAuditRetention(page.retentionSecs);
+      }
+    } finally {
+      setLoadingAudit(false);
+    }
+  }
+
+  function handleAuditFilter(value: string): void {
+    setAuditFilter(value ? (value as AuditAction) : null);
+    loadAuditLog(false);
+  }
+
+  async function handleAuditRetention(secs: number): Promise<void> {
+    if (await handleSetAuditRetention(props.community.id, secs)) {
+      setAuditRetention(secs);
+      addToast("Audit log retention updated", "success");
+    }
+  }
+
+  /** Name what an entry acted on, where we still know it. */
+  function auditTarget(entry: AuditEntry): string | null {
+    const target = entry.target;
+    if (target === null) return null;
+    if (entry.action.startsWith("member_")) {
+      return props.community.members.find((m) => m.pseudonymKey === target)?.displayName
+        ?? target.slice(0, 16);
+    }
+    if (entry.action.startsWith("role_")) {
+      return props.community.roles.find((r) => String(r.id) === target)?.name ?? `role #${target}`;
+    }
+    if (entry.action.startsWith("channel_")) {
+      return props.community.channels.find((c) => c.id === target)?.name ?? target;
+    }
+    return target;
+  }
+
+  function handleCopyInvite(invite: CommunityInvite): void {
+    navigator.clipboard.writeText(invite.link);
+    setCopiedInvite(invite.code);
+    setTimeout(() => setCopiedInvite(null), 2000);
+  }
+
+  async function handleRevokeInvite(code: string): Promise<void> {
+    if (await handleRevokeCommunityInvite(props.community.id, code)) {
+      setInvites((prev) => prev.filter((i) => i.code !== code));
+    }
+  }
+
+  async function handleToggleInviteOnly(value: boolean): Promise<void> {
+    if (await handleSetInviteOnly(props.community.id, value)) {
+      setInviteOnly(value);
+    } else {
+      setInviteOnly(!value);
+    }
+  }
+
+  function toggleInviteRole(roleId: number): void {
+    setInviteRoleIds((prev) =>
+      prev.includes(roleId) ? prev.filter((id) => id !== roleId) : [...prev, roleId],
+    );
+  }
+
   function startRename(channel: { id: string; name: string }): void {
     setRenamingChannelId(channel.id);
     setRenameValue(channel.name);
@@ -269,6 +544,24 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
     });
   }
 
+  function confirmZeroKnowledge(): void {
+    setConfirmAction({
+      title: "Enable Zero-Knowledge Keys",
+      message: "The server will delete its encryption keys and members with Manage Community will generate new ones. This cannot be undone.",
+      confirmLabel: "Enable",
+      action: () => handleEnableZeroKnowledge(props.community.id),
+    });
+  }
+
+  function confirmTreeKem(): void {
+    setConfirmAction({
+      title: "Enable Large-Community Keys",
+      message: "The server will delete its encryption keys and members will agree on new ones through a key tree. Members get the new key once someone with Manage Community adds them. This cannot be undone.",
+      confirmLabel: "Enable",
+      action: () => handleEnableTreeKem(props.community.id),
+    });
+  }
+
   function memberAllRoles(member: Member): { name: string; color: number }[] {
     return member.roleIds
       .map((id) => props.community.roles.find((r) => r.id === id))
@@ -469,6 +762,58 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
                   <span class="nf-icon">{ICON_SAVE}</span> {savingOverview() ? "Saving..." : "Save Changes"}
                 </button>
               </div>
+              <div class="settings-field">
+                <label class="settings-field-label">Community Directory</label>
+                <div class="settings-hint">
+                  Listed communities can be found and joined by anyone from Browse
+                  Communities. The name, description, member count and games are public.
+                </div>
+                <div class="settings-field-row">
+                  <label class="settings-option">
+                    <input
+                      type="checkbox"
+                      checked={listed()}
+                      onChange={(e) => setListed(e.currentTarget.checked)}
+                    />
+                    List this community
+                  </label>
+                </div>
+                <div class="directory-result-games">
+                  <For each={listingGameIds()}>
+                    {(id) => (
+                      <span class="directory-game-tag">
+                        {gameName(id)}{" "}
+                        <span
+                          class="nf-icon"
+                          onClick={() => setListingGameIds(listingGameIds().filter((g) => g !== id))}
+                        >
+                          {ICON_CLOSE}
+                        </span>
+                      </span>
+                    )}
+                  </For>
+                </div>
+                <select
+                  class="settings-select"
+                  value=""
+                  disabled={listingGameIds().length >= MAX_LISTING_GAMES}
+                  onChange={(e) => {
+                    const id = Number(e.currentTarget.value);
+                    if (id && !listingGameIds().includes(id)) {
+                      setListingGameIds([...listingGameIds(), id]);
+                    }
+                    e.currentTarget.value = "";
+                  }}
+                >
+                  <option value="">Tag a game...</option>
+                  <For each={knownGames().filter((g) => !listingGameIds().includes(g.id))}>
+                    {(game) => <option value={game.id}>{game.name}</option>}
+                  </For>
+                </select>
+                <button class="settings-save-btn" onClick={handleSaveListing} disabled={savingListing()}>
+                  <span class="nf-icon">{ICON_SAVE}</span> {savingListing() ? "Saving..." : "Save Listing"}
+                </button>
+              </div>
             </Show>
           </div>
         </Show>
@@ -974,6 +1319,108 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
           </div>
         </Show>
 
+        {/* Invites Tab */}
+        <Show when={activeTab() === "invites"}>
+          <div class="settings-section">
+            <Show when={canManageCommunity()}>
+              <div class="settings-field">
+                <label class="settings-field-label">Who Can Join</label>
+                <div class="settings-field-row">
+                  <label class="settings-option">
+                    <input
+                      type="checkbox"
+                      checked={inviteOnly()}
+                      onChange={(e) => handleToggleInviteOnly(e.currentTarget.checked)}
+                    />
+                    Invite only
+                  </label>
+                </div>
+                <div class="settings-hint">
+                  Newcomers need a valid invite link. Members who already joined can always
+                  reconnect.
+                </div>
+              </div>
+            </Show>
+            <Show when={canCreateInvite()}>
+              <div class="settings-field">
+                <label class="settings-field-label">New Invite</label>
+                <div class="settings-field-row">
+                  <select
+                    class="settings-select"
+                    value={inviteMaxUses()}
+                    onChange={(e) => setInviteMaxUses(Number(e.currentTarget.value))}
+                  >
+                    <For each={INVITE_MAX_USES}>
+                      {(uses) => <option value={uses}>{uses === 0 ? "Unlimited uses" : `${uses} ${uses === 1 ? "use" : "uses"}`}</option>}
+                    </For>
+                  </select>
+                  <select
+                    class="settings-select"
+                    value={inviteExpiry()}
+                    onChange={(e) => setInviteExpiry(Number(e.currentTarget.value))}
+                  >
+                    <For each={INVITE_EXPIRY}>
+                      {(expiry) => <option value={expiry.secs}>{expiry.secs === 0 ? "Never expires" : `Expires in ${expiry.label}`}</option>}
+                    </For>
+                  </select>
+                </div>
+                <Show when={canManageRoles() && grantableRoles().length > 0}>
+                  <div class="settings-hint">Give whoever joins with it these roles:</div>
+                  <div class="role-picker-list settings-role-picker">
+                    <For each={grantableRoles()}>
+                      {(role) => {
+                        const has = () => inviteRoleIds().includes(role.id);
+                        return (
+                          <div
+                            class={`role-picker-item ${has() ? "role-picker-item-active" : ""}`}
+                            onClick={() => toggleInviteRole(role.id)}
+                          >
+                            <input type="checkbox" class="role-picker-checkbox" checked={has()} readOnly />
+                            {role.name}
+                          </div>
+                        );
+                      }}
+                    </For>
+                  </div>
+                </Show>
+                <button class="settings-save-btn" onClick={handleCreateInvite} disabled={creatingInvite()}>
+                  <span class="nf-icon">{ICON_PLUS}</span> {creatingInvite() ? "Creating..." : "Create Invite"}
+                </button>
+              </div>
+            </Show>
+            <Show when={invites().length > 0} fallback={
+              <div class="settings-hint">No invites.</div>
+            }>
+              <For each={invites()}>
+                {(invite) => (
+                  <div class="ban-list-item">
+                    <div class="ban-list-info">
+                      <span class="ban-list-name settings-value-mono">{invite.code}</span>
+                      <span class="ban-list-date">{inviteStatus(invite)}</span>
+                      <Show when={invite.grantRoleIds.length > 0}>
+                        <span class="ban-list-date">
+                          Grants {invite.grantRoleIds
+                            .map((id) => props.community.roles.find((r) => r.id === id)?.name ?? `#${id}`)
+                            .join(", ")}
+                        </span>
+                      </Show>
+                    </div>
+                    <button class="settings-copy-btn" onClick={() => handleCopyInvite(invite)}>
+                      <span class="nf-icon">{ICON_COPY}</span> {copiedInvite() === invite.code ? "Copied" : "Copy Link"}
+                    </button>
+                    <button
+                      class="settings-action-btn"
+                      onClick={() => handleRevokeInvite(invite.code)}
+                    >
+                      <span class="nf-icon">{ICON_DELETE}</span> Revoke
+                    </button>
+                  </div>
+                )}
+              </For>
+            </Show>
+          </div>
+        </Show>
+
         {/* Bans Tab */}
         <Show when={activeTab() === "bans"}>
           <div class="settings-section">
@@ -1002,6 +1449,70 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
           </div>
         </Show>
 
+        {/* Audit Log Tab */}
+        <Show when={activeTab() === "audit"}>
+          <div class="settings-section">
+            <div class="settings-field">
+              <div class="settings-field-row">
+                <select
+                  class="settings-select"
+                  value={auditFilter() ?? ""}
+                  onChange={(e) => handleAuditFilter(e.currentTarget.value)}
+                >
+                  <option value="">All actions</option>
+                  <For each={Object.entries(AUDIT_ACTION_LABELS)}>
+                    {([action, label]) => <option value={action}>{label}</option>}
+                  </For>
+                </select>
+                <Show when={canManageCommunity()}>
+                  <select
+                    class="settings-select"
+                    value={auditRetention()}
+                    onChange={(e) => handleAuditRetention(Number(e.currentTarget.value))}
+                  >
+                    <For each={AUDIT_RETENTION}>
+                      {(retention) => <option value={retention.secs}>{retention.secs === 0 ? "Keep forever" : `Keep for ${retention.label}`}</option>}
+                    </For>
+                  </select>
+                </Show>
+              </div>
+            </div>
+            <Show when={auditEntries().length > 0} fallback={
+              <div class="settings-hint">{loadingAudit() ? "Loading..." : "No audit log entries."}</div>
+            }>
+              <For each={auditEntries()}>
+                {(entry) => (
+                  <div class="ban-list-item">
+                    <div class="ban-list-info">
+                      <span class="ban-list-name">
+                        {entry.actorName ?? entry.actorPseudonym.slice(0, 16)}: {AUDIT_ACTION_LABELS[entry.action]}
+                        <Show when={auditTarget(entry)}>{(target) => <> · {target()}</>}</Show>
+                      </span>
+                      <Show when={entry.before || entry.after}>
+                        <span class="ban-list-date settings-value-mono audit-entry-change">
+                          {entry.before ?? "—"} → {entry.after ?? "—"}
+                        </span>
+                      </Show>
+                      <span class="ban-list-date">
+                        {new Date(entry.createdAt * 1000).toLocaleString()}
+                      </span>
+                    </div>
+                  </div>
+                )}
+              </For>
+              <Show when={auditHasMore()}>
+                <button
+                  class="settings-action-btn"
+                  onClick={() => loadAuditLog(true)}
+                  disabled={loadingAudit()}
+                >
+                  {loadingAudit() ? "Loading..." : "Load More"}
+                </button>
+              </Show>
+            </Show>
+          </div>
+        </Show>
+
         {/* Security Tab */}
         <Show when={activeTab() === "security"}>
           <div class="settings-section">
@@ -1014,12 +1525,54 @@ const CommunitySettingsModal: Component<CommunitySettingsModalProps> = (props) =
               <div class="settings-hint">
                 Rotating the encryption key generates a new Media Encryption Key (MEK).
                 All members will automatically receive the new key. Messages encrypted
-                with previous keys remain readable.
+                with previous keys remain readable. The key also rotates whenever a
+                member leaves, is kicked, banned or timed out.
               </div>
               <button class="settings-danger-btn" onClick={confirmRotateKey}>
                 <span class="nf-icon">{ICON_KEY}</span> Rotate Encryption Key
               </button>
             </div>
+            <div class="settings-field">
+              <label class="settings-field-label">Message History for New Members</label>
+              <div class="settings-hint">
+                Choose whether people who join later can read messages sent before they joined.
+              </div>
+              <select
+                class="settings-select"
+                value={props.community.historyVisibility}
+                onChange={(e) => handleSetHistoryVisibility(
+                  props.community.id,
+                  e.currentTarget.value as HistoryVisibility,
+                )}
+              >
+                <option value="full">Full history</option>
+                <option value="since_join">Only since they joined</option>
+              </select>
+            </div>
+            <Show when={props.community.isHosted}>
+              <div class="settings-field">
+                <label class="settings-field-label">Zero-Knowledge Keys</label>
+                <div class="settings-hint">
+                  Members with Manage Community generate each encryption key and the
+                  server only relays copies it cannot read. Rotations then need one of
+                  those members to be online.
+                </div>
+                <button class="settings-danger-btn" onClick={confirmZeroKnowledge}>
+                  <span class="nf-icon">{ICON_KEY}</span> Enable Zero-Knowledge Keys
+                </button>
+              </div>
+              <div class="settings-field">
+                <label class="settings-field-label">Large-Community Keys</label>
+                <div class="settings-hint">
+                  Members agree on each encryption key through a TreeKEM key tree, so
+                  adding or removing someone stays cheap in communities with hundreds
+                  of members. The server never sees the keys.
+                </div>
+                <button class="settings-danger-btn" onClick={confirmTreeKem}>
+                  <span class="nf-icon">{ICON_KEY}</span> Enable Large-Community Keys
+                </button>
+              </div>
+            </Show>
             <div class="settings-field">
               <label class="settings-field-label">Server Status</label>
               <div class="settings-value">
@@ -0,0 +1,72 @@
+import { Component, Show, createEffect, createSignal } from "solid-js";
+import Modal from "../common/Modal";
+import { handleCreateThread } from "../../handlers/community.handlers";
+import type { ThreadInfo } from "../../ipc/commands";
+
+interface CreateThreadModalProps {
+  isOpen: boolean;
+  communityId: string;
+  channelId: string;
+  parentMessageId: string;
+  /** Suggested name, from the parent message. */
+  defaultName: string;
+  canCreatePublic: boolean;
+  canCreatePrivate: boolean;
+  onClose: () => void;
+  onCreated: (thread: ThreadInfo) => void;
+}
+
+const CreateThreadModal: Component<CreateThreadModalProps> = (props) => {
+  const [name, setName] = createSignal("");
+  const [isPrivate, setIsPrivate] = createSignal(false);
+
+  createEffect(() => {
+    if (props.isOpen) {
+      setName(props.defaultName.slice(0, 100));
+      setIsPrivate(!props.canCreatePublic);
+    }
+  });
+
+  async function handleSubmit(e: Event): Promise<void> {
+    e.preventDefault();
+    const n = name().trim();
+    if (!n) return;
+    const thread = await handleCreateThread(
+      props.communityId, props.channelId, props.parentMessageId, n, isPrivate(),
+    );
+    if (thread) {
+      props.onCreated(thread);
+      props.onClose();
+    }
+  }
+
+  return (
+    <Modal isOpen={props.isOpen} title="Start Thread" onClose={props.onClose}>
+      <form class="add-friend-form" onSubmit={handleSubmit}>
+        <input
+          class="add-friend-input"
+          type="text"
+          placeholder="Thread name..."
+          maxLength={100}
+          value={name()}
+          onInput={(e) => setName(e.currentTarget.value)}
+        />
+        <Show when={props.canCreatePublic && props.canCreatePrivate}>
+          <label class="settings-option">
+            <input
+              type="checkbox"
+              checked={isPrivate()}
+              onChange={(e) => setIsPrivate(e.currentTarget.checked)}
+            />
+            Private — only people added to the thread can see it
+          </label>
+        </Show>
+        <button class="add-friend-btn" type="submit" disabled={!name().trim()}>
+          Create
+        </button>
+      </form>
+    </Modal>
+  );
+};
+
+export default CreateThreadModal;
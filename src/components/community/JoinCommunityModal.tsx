@@ -15,7 +15,8 @@ const JoinCommunityModal: Component<JoinCommunityModalProps> = (props) => {
     e.preventDefault();
     const id = communityId().trim();
     if (!id) return;
-    await handleJoinCommunity(id, name().trim() || id.slice(0, 12) + "...");
+    const fallbackName = id.startsWith("rekindle://") ? "Community" : id.slice(0, 12) + "...";
+    await handleJoinCommunity(id, name().trim() || fallbackName);
     setCommunityId("");
     setName("");
     props.onClose();
@@ -27,7 +28,7 @@ const JoinCommunityModal: Component<JoinCommunityModalProps> = (props) => {
         <input
           class="add-friend-input"
           type="text"
-          placeholder="Community ID..."
+          placeholder="Community ID or invite link..."
           value={communityId()}
           onInput={(e) => setCommunityId(e.currentTarget.value)}
         />
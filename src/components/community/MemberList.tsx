@@ -17,6 +17,7 @@ import {
   handleAssignRole,
   handleUnassignRole,
   handleTimeoutMember,
+  handleVouchMember,
 } from "../../handlers/community.handlers";
 import {
   calculateBasePermissions,
@@ -26,6 +27,7 @@ import {
   BAN_MEMBERS,
   MODERATE_MEMBERS,
   MANAGE_ROLES,
+  MANAGE_COMMUNITY,
 } from "../../ipc/permissions";
 
 interface MemberListProps {
@@ -96,6 +98,16 @@ const MemberList: Component<MemberListProps> = (props) => {
       });
     }
 
+    if (hasPermission(perms, MANAGE_COMMUNITY)) {
+      items.push({
+        label: "Vouch for Member",
+        icon: ICON_CHECK,
+        action: () => {
+          handleVouchMember(props.communityId, member.pseudonymKey);
+        },
+      });
+    }
+
     if (hasPermission(perms, MODERATE_MEMBERS)) {
       items.push({
         label: "Timeout (10min)",
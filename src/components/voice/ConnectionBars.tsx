@@ -0,0 +1,27 @@
+import { Component, For } from "solid-js";
+
+interface ConnectionBarsProps {
+  /** 0–4 */
+  bars: number;
+  title?: string;
+}
+
+const ConnectionBars: Component<ConnectionBarsProps> = (props) => {
+  return (
+    <span
+      class={`voice-bars ${props.bars <= 1 ? "voice-bars-poor" : props.bars === 2 ? "voice-bars-fair" : ""}`}
+      title={props.title}
+    >
+      <For each={[1, 2, 3, 4]}>
+        {(level) => (
+          <span
+            class={`voice-bar ${level <= props.bars ? "voice-bar-lit" : ""}`}
+            style={{ height: `${level * 2 + 1}px` }}
+          />
+        )}
+      </For>
+    </span>
+  );
+};
+
+export default ConnectionBars;
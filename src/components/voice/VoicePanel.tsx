@@ -1,6 +1,7 @@
 import { Component, For, Show } from "solid-js";
 import { voiceState } from "../../stores/voice.store";
 import VoiceParticipantItem from "./VoiceParticipant";
+import ConnectionBars from "./ConnectionBars";
 import { handleToggleMute, handleToggleDeafen, handleLeaveVoice } from "../../handlers/voice.handlers";
 import { ICON_MIC, ICON_MIC_OFF, ICON_HEADPHONES, ICON_HEADPHONES_OFF, ICON_HANGUP } from "../../icons";
 
@@ -14,6 +15,10 @@ const VoicePanel: Component = () => {
           {voiceState.isConnected ? "Voice Connected" : "Not Connected"}
         </span>
         <Show when={voiceState.isConnected}>
+          <ConnectionBars
+            bars={voiceState.connectionBars}
+            title={voiceState.rttMs !== null ? `${voiceState.rttMs} ms` : voiceState.connectionQuality}
+          />
           <div class="voice-panel-controls">
             <button
               class={`voice-btn ${voiceState.isMuted ? "voice-btn-active" : ""}`}
@@ -1,5 +1,6 @@
-import { Component } from "solid-js";
+import { Component, Show } from "solid-js";
 import { VoiceParticipant as VoiceParticipantType } from "../../stores/voice.store";
+import ConnectionBars from "./ConnectionBars";
 
 interface VoiceParticipantProps {
   participant: VoiceParticipantType;
@@ -15,6 +16,9 @@ const VoiceParticipantItem: Component<VoiceParticipantProps> = (props) => {
         {props.participant.displayName}
         {props.participant.isMuted && " (muted)"}
       </span>
+      <Show when={props.participant.connectionBars !== null}>
+        <ConnectionBars bars={props.participant.connectionBars ?? 0} />
+      </Show>
     </div>
   );
 };
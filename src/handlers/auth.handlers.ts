@@ -45,6 +45,57 @@ export async function handleCreateIdentity(
   }
 }
 
+/** Finish logging in once a restore or device link has produced an identity. */
+async function completeRestore(
+  restore: Promise<{ publicKey: string; displayName: string }>,
+  what: string,
+): Promise<{ success: true } | { success: false; error: string }> {
+  try {
+    const result = await restore;
+    const avatarUrl = await fetchAvatarUrl(result.publicKey);
+    setAuthState({
+      isLoggedIn: true,
+      publicKey: result.publicKey,
+      displayName: result.displayName,
+      avatarUrl,
+      status: "online",
+    });
+    return { success: true };
+  } catch (e) {
+    const message = e instanceof Error ? e.message : String(e);
+    console.error(`${what} failed:`, message);
+    return { success: false, error: message };
+  }
+}
+
+export function handleImportBackup(
+  data: number[],
+  backupPassphrase: string,
+  passphrase: string,
+): Promise<{ success: true } | { success: false; error: string }> {
+  return completeRestore(
+    commands.importIdentityBackup(data, backupPassphrase, passphrase),
+    "Backup restore",
+  );
+}
+
+export function handleRestoreFromPhrase(
+  phrase: string,
+  passphrase: string,
+  displayName?: string,
+): Promise<{ success: true } | { success: false; error: string }> {
+  return completeRestore(
+    commands.restoreFromRecoveryPhrase(phrase, passphrase, displayName),
+    "Recovery phrase restore",
+  );
+}
+
+export function handleCompleteDeviceLink(
+  passphrase: string,
+): Promise<{ success: true } | { success: false; error: string }> {
+  return completeRestore(commands.completeDeviceLink(passphrase), "Device link");
+}
+
 export async function handleLogout(): Promise<void> {
   try {
     // Capture the active key before clearing — the login screen uses it to
@@ -208,6 +208,7 @@ export async function handleRefreshFriends(): Promise<void> {
         lastSeenAt: f.lastSeenAt ?? null,
         voiceChannel: null,
         friendshipState: (f.friendshipState as Friend["friendshipState"]) ?? "accepted",
+        disappearingSecs: f.disappearingSecs ?? 0,
       };
     }
     setFriendsState("friends", reconcile(friendMap));
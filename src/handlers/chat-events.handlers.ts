@@ -5,8 +5,26 @@ import { subscribeChatEvents } from "../ipc/channels";
 import { friendsState, setFriendsState } from "../stores/friends.store";
 import { setNotificationState } from "../stores/notification.store";
 import { communityState, setCommunityState } from "../stores/community.store";
-import { handleTypingIndicator, handleIncomingMessage, handleResetUnread } from "./chat.handlers";
+import { chatState, setChatState } from "../stores/chat.store";
+import {
+  handleTypingIndicator,
+  handleIncomingMessage,
+  handleResetUnread,
+  handleTransferProgress,
+  handleDeliveryStateChanged,
+  handleDisappearingTimerChanged,
+  handleMessagesExpired,
+  applyMessageChange,
+} from "./chat.handlers";
 import { handleRefreshFriends } from "./buddy.handlers";
+import {
+  handleGroupUpdated,
+  handleGroupRemoved,
+  handleIncomingGroupMessage,
+  handleResetGroupUnread,
+} from "./group.handlers";
+import { groupState, setGroupState } from "../stores/group.store";
+import { addToast } from "../stores/toast.store";
 import type { Message } from "../stores/chat.store";
 
 export function subscribeBuddyListChatEvents(): Promise<UnlistenFn> {
@@ -74,6 +92,7 @@ export function subscribeBuddyListChatEvents(): Promise<UnlistenFn> {
           lastSeenAt: null,
           voiceChannel: null,
           friendshipState: state as "pendingOut" | "accepted",
+          disappearingSecs: 0,
         });
         break;
       }
@@ -109,6 +128,80 @@ export function subscribeBuddyListChatEvents(): Promise<UnlistenFn> {
         // Optional: could show a delivery indicator on the pending friend
         break;
       }
+      case "identityKeyChanged": {
+        const friend = friendsState.friends[event.data.publicKey];
+        const name = friend?.displayName ?? `${event.data.publicKey.slice(0, 8)}...`;
+        setNotificationState("notifications", (prev) => [
+          ...prev,
+          {
+            id: crypto.randomUUID(),
+            type: "system",
+            title: "Safety Number Changed",
+            body: event.data.verified
+              ? `${name}'s identity key changed. Messages are blocked until you verify their safety number again.`
+              : `${name}'s identity key changed. Verify their safety number to make sure it's really them.`,
+            timestamp: Date.now(),
+            read: false,
+          },
+        ]);
+        setNotificationState("unreadCount", (c) => c + 1);
+        break;
+      }
+      case "groupMessageReceived": {
+        if (groupState.groups[event.data.groupId]) {
+          setGroupState("groups", event.data.groupId, "unreadCount", (c) => (c ?? 0) + 1);
+        }
+        break;
+      }
+      case "groupUpdated": {
+        handleGroupUpdated(event.data.group);
+        break;
+      }
+      case "groupRemoved": {
+        handleGroupRemoved(event.data.groupId);
+        break;
+      }
+      case "disappearingTimerChanged": {
+        handleDisappearingTimerChanged(event.data.peerId, event.data.seconds);
+        break;
+      }
+    }
+  });
+}
+
+/** Events for the group conversation window showing `groupId`. */
+export function subscribeGroupChatEvents(groupId: string): Promise<UnlistenFn> {
+  return subscribeChatEvents((event) => {
+    switch (event.type) {
+      case "groupMessageReceived": {
+        if (event.data.groupId === groupId) {
+          handleIncomingGroupMessage(groupId, {
+            id: Date.now(),
+            messageId: event.data.messageId,
+            senderId: event.data.from,
+            body: event.data.body,
+            timestamp: event.data.timestamp,
+            isOwn: false,
+          });
+          handleResetGroupUnread(groupId);
+        }
+        break;
+      }
+      case "groupUpdated": {
+        if (event.data.group.id === groupId) handleGroupUpdated(event.data.group);
+        break;
+      }
+      case "groupRemoved": {
+        if (event.data.groupId === groupId) handleGroupRemoved(groupId);
+        break;
+      }
+      case "groupMembershipChanged": {
+        if (event.data.groupId === groupId) {
+          const { displayName, joined } = event.data;
+          addToast(joined ? `${displayName} joined the group` : `${displayName} left the group`);
+        }
+        break;
+      }
     }
   });
 }
@@ -121,17 +214,20 @@ export function subscribeDmChatEvents(
     switch (event.type) {
       case "messageReceived": {
         console.warn("[DM] messageReceived event:", event.data.conversationId, "peerId:", peerId);
-        if (event.data.from === getOwnKey()) break;
         if (event.data.conversationId === peerId) {
+          // Our own messages arrive here when sent from another of our devices
+          const isOwn = event.data.from === getOwnKey();
           batch(() => {
             handleIncomingMessage(peerId, {
               id: Date.now(),
               senderId: event.data.from,
               body: event.data.body,
               timestamp: event.data.timestamp,
-              isOwn: false,
+              isOwn,
+              attachments: event.data.attachments,
+              messageId: event.data.messageId,
             });
-            handleResetUnread(peerId);
+            if (!isOwn) handleResetUnread(peerId);
           });
         }
         break;
@@ -142,6 +238,46 @@ export function subscribeDmChatEvents(
         }
         break;
       }
+      case "transferProgress": {
+        if (event.data.conversationId === peerId) {
+          handleTransferProgress(peerId, event.data.attachment);
+        }
+        break;
+      }
+      case "deliveryStateChanged": {
+        if (event.data.conversationId === peerId) {
+          handleDeliveryStateChanged(peerId, event.data.messageIds, event.data.state);
+        }
+        break;
+      }
+      case "messageEdited":
+      case "messageDeleted":
+      case "reactionsChanged": {
+        if (event.data.conversationId === peerId && chatState.conversations[peerId]) {
+          setChatState("conversations", peerId, "messages", (msgs) => applyMessageChange(msgs, event));
+        }
+        break;
+      }
+      case "messagesExpired": {
+        if (event.data.conversationId === peerId) {
+          handleMessagesExpired(peerId, event.data.messageIds);
+        }
+        break;
+      }
+      case "disappearingTimerChanged": {
+        if (event.data.peerId === peerId) {
+          handleDisappearingTimerChanged(peerId, event.data.seconds);
+          if (event.data.by === peerId) {
+            const name = friendsState.friends[peerId]?.displayName ?? "Your friend";
+            addToast(
+              event.data.seconds > 0
+                ? `${name} turned on disappearing messages`
+                : `${name} turned off disappearing messages`,
+            );
+          }
+        }
+        break;
+      }
     }
   });
 }
@@ -160,6 +296,8 @@ export function subscribeCommunityChannelChatEvents(
         body: event.data.body,
         timestamp: event.data.timestamp,
         isOwn: false,
+        messageId: event.data.messageId,
+        replyTo: event.data.replyTo,
       };
       const existing = communityState.channelMessages[channelId];
       if (existing) {
@@ -174,23 +312,54 @@ export function subscribeCommunityChannelChatEvents(
       const { channelId, messages: serverMsgs } = event.data;
       const existing = communityState.channelMessages[channelId] ?? [];
       const existingKeys = new Set(
-        existing.map((m) => `${m.timestamp}:${m.senderId}`),
+        existing.map((m) => m.messageId ?? `${m.timestamp}:${m.senderId}`),
+      );
+      // Messages we already show pick up edits and reactions from the server
+      const fromServer = new Map(
+        serverMsgs.filter((m) => m.messageId).map((m) => [m.messageId, m]),
       );
+      const updated = existing.map((m) => {
+        const server = m.messageId ? fromServer.get(m.messageId) : undefined;
+        return server
+          ? { ...m, body: server.body, editedAt: server.editedAt, reactions: server.reactions }
+          : m;
+      });
       const newMsgs: Message[] = serverMsgs
-        .filter((m) => !existingKeys.has(`${m.timestamp}:${m.senderId}`))
+        .filter((m) => !existingKeys.has(m.messageId ?? `${m.timestamp}:${m.senderId}`))
         .map((m) => ({
           id: m.id,
           senderId: m.senderId,
           body: m.body,
           timestamp: m.timestamp,
           isOwn: m.isOwn,
+          messageId: m.messageId,
+          editedAt: m.editedAt,
+          reactions: m.reactions,
+          replyTo: m.replyTo,
         }));
-      if (newMsgs.length > 0) {
-        const merged = [...existing, ...newMsgs].sort(
+      if (newMsgs.length > 0 || fromServer.size > 0) {
+        const merged = [...updated, ...newMsgs].sort(
           (a, b) => a.timestamp - b.timestamp,
         );
         setCommunityState("channelMessages", channelId, merged);
       }
+    } else if (
+      event.type === "messageEdited" ||
+      event.type === "messageDeleted" ||
+      event.type === "reactionsChanged"
+    ) {
+      const channelId = event.data.conversationId;
+      if (communityState.channelMessages[channelId]) {
+        setCommunityState("channelMessages", channelId, (msgs) => applyMessageChange(msgs, event));
+      }
+    } else if (event.type === "messagesExpired") {
+      const channelId = event.data.conversationId;
+      if (communityState.channelMessages[channelId]) {
+        const ids = new Set(event.data.messageIds);
+        setCommunityState("channelMessages", channelId, (msgs) =>
+          msgs.filter((m) => !m.messageId || !ids.has(m.messageId)),
+        );
+      }
     }
   });
 }
@@ -2,7 +2,17 @@ import { commands } from "../ipc/commands";
 import { setChatState, chatState } from "../stores/chat.store";
 import { authState } from "../stores/auth.store";
 import { friendsState, setFriendsState } from "../stores/friends.store";
-import type { Message } from "../stores/chat.store";
+import { communityState } from "../stores/community.store";
+import type { Attachment, Message, MessageStatus } from "../stores/chat.store";
+import type { ChatEvent } from "../ipc/channels";
+import type { SearchFilters, SearchResult } from "../ipc/commands";
+import { addToast } from "../stores/toast.store";
+
+/** Events that change a message already in a conversation. */
+export type MessageChangeEvent = Extract<
+  ChatEvent,
+  { type: "messageEdited" | "messageDeleted" | "reactionsChanged" }
+>;
 
 export async function handleSendMessage(to: string, body: string): Promise<void> {
   const trimmed = body.trim();
@@ -22,13 +32,15 @@ export async function handleSendMessage(to: string, body: string): Promise<void>
   handleIncomingMessage(to, message);
 
   try {
-    await commands.sendMessage(to, trimmed);
-    // Update status to sent
+    const { messageId, deliveryState } = await commands.sendMessage(to, trimmed);
+    // Update status to sent, or queued if the peer is offline
     const convo = chatState.conversations[to];
     if (convo) {
       setChatState("conversations", to, {
         ...convo,
-        messages: convo.messages.map((m) => (m.id === tempId ? { ...m, status: "sent" as const } : m)),
+        messages: convo.messages.map((m) =>
+          m.id === tempId ? { ...m, messageId, status: deliveryState } : m,
+        ),
       });
     }
   } catch {
@@ -43,6 +55,45 @@ export async function handleSendMessage(to: string, body: string): Promise<void>
   }
 }
 
+export async function handleSendFile(to: string, path: string): Promise<void> {
+  try {
+    const message = await commands.sendFile(to, path);
+    handleIncomingMessage(to, { ...message, status: message.deliveryState ?? "queued" });
+  } catch (e) {
+    console.error("Failed to send file:", e);
+  }
+}
+
+export async function handleDownloadAttachment(
+  peerId: string,
+  attachment: Attachment,
+): Promise<void> {
+  try {
+    const updated = await commands.downloadAttachment(attachment.messageId, attachment.index);
+    handleTransferProgress(peerId, updated);
+  } catch (e) {
+    console.error("Failed to download attachment:", e);
+  }
+}
+
+export function handleTransferProgress(peerId: string, attachment: Attachment): void {
+  const convo = chatState.conversations[peerId];
+  if (!convo) return;
+  setChatState("conversations", peerId, {
+    ...convo,
+    messages: convo.messages.map((m) =>
+      m.attachments?.some((a) => a.messageId === attachment.messageId && a.index === attachment.index)
+        ? {
+            ...m,
+            attachments: m.attachments.map((a) =>
+              a.messageId === attachment.messageId && a.index === attachment.index ? attachment : a,
+            ),
+          }
+        : m,
+    ),
+  });
+}
+
 let typingTimeout: ReturnType<typeof setTimeout> | null = null;
 let isLocalTyping = false;
 
@@ -130,6 +181,11 @@ export async function handleLoadHistory(
       body: m.body,
       timestamp: m.timestamp,
       isOwn: m.isOwn,
+      attachments: m.attachments,
+      messageId: m.messageId,
+      editedAt: m.editedAt,
+      reactions: m.reactions,
+      status: m.deliveryState,
     }));
     const existing = chatState.conversations[peerId];
     if (mapped.length > 0 || !existing || existing.messages.length === 0) {
@@ -172,9 +228,11 @@ export async function handleRetrySendMessage(peerId: string, messageId: number):
   );
 
   try {
-    await commands.sendMessage(peerId, message.body);
+    const sent = await commands.sendMessage(peerId, message.body);
     setChatState("conversations", peerId, "messages", (msgs) =>
-      msgs.map((m) => (m.id === messageId ? { ...m, status: "sent" as const } : m)),
+      msgs.map((m) =>
+        m.id === messageId ? { ...m, messageId: sent.messageId, status: sent.deliveryState } : m,
+      ),
     );
   } catch {
     setChatState("conversations", peerId, "messages", (msgs) =>
@@ -182,3 +240,125 @@ export async function handleRetrySendMessage(peerId: string, messageId: number):
     );
   }
 }
+
+export async function handleEditMessage(
+  conversationId: string,
+  messageId: string,
+  body: string,
+): Promise<void> {
+  try {
+    await commands.editMessage(conversationId, messageId, body);
+  } catch (e) {
+    console.error("Failed to edit message:", e);
+  }
+}
+
+export async function handleDeleteMessage(conversationId: string, messageId: string): Promise<void> {
+  try {
+    await commands.deleteMessage(conversationId, messageId);
+  } catch (e) {
+    console.error("Failed to delete message:", e);
+  }
+}
+
+/** Add our `emoji` reaction to a message, or take it back if it's already there. */
+export async function handleToggleReaction(
+  conversationId: string,
+  message: Message,
+  emoji: string,
+): Promise<void> {
+  if (!message.messageId) return;
+  const mine = message.reactions?.some((r) => r.emoji === emoji && r.mine) ?? false;
+  try {
+    if (mine) {
+      await commands.removeReaction(conversationId, message.messageId, emoji);
+    } else {
+      await commands.addReaction(conversationId, message.messageId, emoji);
+    }
+  } catch (e) {
+    console.error("Failed to update reaction:", e);
+  }
+}
+
+export async function handleSearchMessages(
+  query: string,
+  filters?: SearchFilters,
+): Promise<SearchResult[]> {
+  if (!query.trim()) return [];
+  try {
+    return await commands.searchMessages(query, filters);
+  } catch (e) {
+    console.error("Failed to search messages:", e);
+    return [];
+  }
+}
+
+/** Open the conversation a search result belongs to. */
+export function handleOpenSearchResult(result: SearchResult): void {
+  if (result.conversationType === "dm") {
+    const friend = friendsState.friends[result.conversationId];
+    const name = result.conversationName ?? friend?.displayName ?? result.conversationId.slice(0, 12);
+    commands.openChatWindow(result.conversationId, name);
+  } else if (result.conversationType === "group") {
+    commands.openGroupWindow(result.conversationId, result.conversationName ?? "Group Chat");
+  } else if (result.communityId) {
+    const community = communityState.communities[result.communityId];
+    commands.openCommunityWindow(result.communityId, community?.name ?? "Community");
+  }
+}
+
+/** Move our DMs to a new delivery state as the peer acknowledges them. */
+export function handleDeliveryStateChanged(
+  peerId: string,
+  messageIds: string[],
+  state: MessageStatus,
+): void {
+  if (!chatState.conversations[peerId]) return;
+  const ids = new Set(messageIds);
+  setChatState("conversations", peerId, "messages", (msgs) =>
+    msgs.map((m) => (m.messageId && ids.has(m.messageId) ? { ...m, status: state } : m)),
+  );
+}
+
+/** Set the disappearing-message timer of our DM with `peerId`, for both sides. */
+export async function handleSetDisappearingTimer(peerId: string, seconds: number): Promise<void> {
+  try {
+    await commands.setDisappearingTimer(peerId, seconds);
+    handleDisappearingTimerChanged(peerId, seconds);
+  } catch (e) {
+    console.error("Failed to set disappearing timer:", e);
+    addToast(`Failed to set disappearing messages: ${e}`, "error");
+  }
+}
+
+export function handleDisappearingTimerChanged(peerId: string, seconds: number): void {
+  if (friendsState.friends[peerId]) {
+    setFriendsState("friends", peerId, "disappearingSecs", seconds);
+  }
+}
+
+/** Drop messages the backend purged once their timer ran out. */
+export function handleMessagesExpired(conversationId: string, messageIds: string[]): void {
+  if (!chatState.conversations[conversationId]) return;
+  const ids = new Set(messageIds);
+  setChatState("conversations", conversationId, "messages", (msgs) =>
+    msgs.filter((m) => !m.messageId || !ids.has(m.messageId)),
+  );
+}
+
+/** Apply an edit, deletion or reaction change to a conversation's messages. */
+export function applyMessageChange(messages: Message[], event: MessageChangeEvent): Message[] {
+  const { messageId } = event.data;
+  switch (event.type) {
+    case "messageEdited":
+      return messages.map((m) =>
+        m.messageId === messageId ? { ...m, body: event.data.body, editedAt: event.data.editedAt } : m,
+      );
+    case "messageDeleted":
+      return messages.filter((m) => m.messageId !== messageId);
+    case "reactionsChanged":
+      return messages.map((m) =>
+        m.messageId === messageId ? { ...m, reactions: event.data.reactions } : m,
+      );
+  }
+}
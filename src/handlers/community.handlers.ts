@@ -1,7 +1,19 @@
 import type { UnlistenFn } from "@tauri-apps/api/event";
 import { commands } from "../ipc/commands";
+import type {
+  AuditAction,
+  CommunityAuditLog,
+  CommunityInvite,
+  CommunityInvites,
+  CommunityListing,
+  DirectoryEntry,
+  DirectoryPreview,
+  ThreadInfo,
+} from "../ipc/commands";
 import { subscribeCommunityEvents } from "../ipc/channels";
+import { calculateBasePermissions, hasPermission, MANAGE_THREADS } from "../ipc/permissions";
 import { setCommunityState, communityState } from "../stores/community.store";
+import type { HistoryVisibility } from "../stores/community.store";
 import { authState } from "../stores/auth.store";
 import { addToast } from "../stores/toast.store";
 import type { Message } from "../stores/chat.store";
@@ -22,13 +34,16 @@ export async function handleCreateCommunity(name: string): Promise<void> {
           name: ch.name,
           type: ch.channelType as "text" | "voice",
           unreadCount: ch.unreadCount,
+          retentionSecs: ch.retentionSecs ?? 0,
         })),
         members: [],
+        threads: [],
         roles: created.roles ?? [],
         myRoleIds: created.myRoleIds ?? [0, 1],
         myPseudonymKey: created.myPseudonymKey ?? null,
         mekGeneration: created.mekGeneration ?? 0,
         isHosted: created.isHosted ?? true,
+        historyVisibility: created.historyVisibility ?? "full",
       });
     } else {
       setCommunityState("communities", id, {
@@ -37,11 +52,13 @@ export async function handleCreateCommunity(name: string): Promise<void> {
         description: null,
         channels: [],
         members: [],
+        threads: [],
         roles: [],
         myRoleIds: [0, 1],
         myPseudonymKey: null,
         mekGeneration: 0,
         isHosted: true,
+        historyVisibility: "full",
       });
     }
   } catch (e) {
@@ -50,12 +67,13 @@ export async function handleCreateCommunity(name: string): Promise<void> {
   }
 }
 
+/** Join by community ID or `rekindle://community/` invite link. */
 export async function handleJoinCommunity(
-  communityId: string,
+  invite: string,
   name: string,
 ): Promise<void> {
   try {
-    await commands.joinCommunity(communityId);
+    const communityId = await commands.joinCommunity(invite);
     // Re-fetch community details to get channels, pseudonym key, MEK generation, roles
     const details = await commands.getCommunityDetails();
     const joined = details.find((c) => c.id === communityId);
@@ -69,13 +87,16 @@ export async function handleJoinCommunity(
           name: ch.name,
           type: ch.channelType as "text" | "voice",
           unreadCount: ch.unreadCount,
+          retentionSecs: ch.retentionSecs ?? 0,
         })),
         members: [],
+        threads: [],
         roles: joined.roles ?? [],
         myRoleIds: joined.myRoleIds ?? [0, 1],
         myPseudonymKey: joined.myPseudonymKey ?? null,
         mekGeneration: joined.mekGeneration ?? 0,
         isHosted: joined.isHosted ?? false,
+        historyVisibility: joined.historyVisibility ?? "full",
       });
     } else {
       setCommunityState("communities", communityId, {
@@ -84,16 +105,18 @@ export async function handleJoinCommunity(
         description: null,
         channels: [],
         members: [],
+        threads: [],
         roles: [],
         myRoleIds: [0, 1],
         myPseudonymKey: null,
         mekGeneration: 0,
         isHosted: false,
+        historyVisibility: "full",
       });
     }
   } catch (e) {
     console.error("Failed to join community:", e);
-    addToast("Failed to join community", "error");
+    addToast(`Failed to join community: ${e}`, "error");
   }
 }
 
@@ -110,7 +133,7 @@ export async function handleCreateChannel(
     );
     setCommunityState("communities", communityId, "channels", (chs) => [
       ...chs,
-      { id: channelId, name, type: channelType, unreadCount: 0 },
+      { id: channelId, name, type: channelType, unreadCount: 0, retentionSecs: 0 },
     ]);
   } catch (e) {
     console.error("Failed to create channel:", e);
@@ -118,9 +141,11 @@ export async function handleCreateChannel(
   }
 }
 
+/** Send to a channel or thread, optionally as a reply to one of its messages. */
 export async function handleSendChannelMessage(
   channelId: string,
   body: string,
+  replyTo: string | null = null,
 ): Promise<void> {
   if (!body.trim()) return;
   const trimmed = body.trim();
@@ -135,6 +160,7 @@ export async function handleSendChannelMessage(
     body: trimmed,
     timestamp: Date.now(),
     isOwn: true,
+    replyTo: replyTo ?? undefined,
     status: "sending",
   };
 
@@ -149,10 +175,10 @@ export async function handleSendChannelMessage(
   }
 
   try {
-    await commands.sendChannelMessage(channelId, trimmed);
+    const messageId = await commands.sendChannelMessage(channelId, trimmed, replyTo);
     // Update status to sent
     setCommunityState("channelMessages", channelId, (msgs) =>
-      msgs.map((m) => (m.id === tempId ? { ...m, status: "sent" as const } : m)),
+      msgs.map((m) => (m.id === tempId ? { ...m, messageId, status: "sent" as const } : m)),
     );
   } catch (e) {
     console.error("Failed to send channel message:", e);
@@ -178,9 +204,11 @@ export async function handleRetryChannelMessage(
   );
 
   try {
-    await commands.sendChannelMessage(channelId, message.body);
+    const globalId = await commands.sendChannelMessage(
+      channelId, message.body, message.replyTo ?? null,
+    );
     setCommunityState("channelMessages", channelId, (msgs) =>
-      msgs.map((m) => (m.id === messageId ? { ...m, status: "sent" as const } : m)),
+      msgs.map((m) => (m.id === messageId ? { ...m, messageId: globalId, status: "sent" as const } : m)),
     );
   } catch {
     setCommunityState("channelMessages", channelId, (msgs) =>
@@ -201,6 +229,10 @@ export async function handleLoadChannelMessages(
       body: m.body,
       timestamp: m.timestamp,
       isOwn: m.isOwn,
+      messageId: m.messageId,
+      editedAt: m.editedAt,
+      reactions: m.reactions,
+      replyTo: m.replyTo,
     }));
     const existing = communityState.channelMessages[channelId];
     if (mapped.length > 0 || !existing || existing.length === 0) {
@@ -238,6 +270,7 @@ export function handleSelectCommunity(communityId: string): void {
       setCommunityState("communities", communityId, "myRoleIds", detail.myRoleIds ?? [0, 1]);
       setCommunityState("communities", communityId, "roles", detail.roles ?? []);
       setCommunityState("communities", communityId, "description", detail.description ?? null);
+      setCommunityState("communities", communityId, "historyVisibility", detail.historyVisibility ?? "full");
     }
   }).catch((e) => {
     console.error("Failed to refresh community details:", e);
@@ -320,6 +353,24 @@ export async function handleRenameChannel(
   }
 }
 
+export async function handleSetChannelRetention(
+  communityId: string,
+  channelId: string,
+  seconds: number,
+): Promise<void> {
+  try {
+    await commands.setChannelRetention(communityId, channelId, seconds);
+    setCommunityState("communities", communityId, "channels",
+      (ch) => ch.id === channelId,
+      "retentionSecs",
+      seconds,
+    );
+  } catch (e) {
+    console.error("Failed to set channel retention:", e);
+    addToast("Failed to set message retention", "error");
+  }
+}
+
 export async function handleUpdateCommunityInfo(
   communityId: string,
   name: string | null,
@@ -339,6 +390,19 @@ export async function handleUpdateCommunityInfo(
   }
 }
 
+export async function handleSetHistoryVisibility(
+  communityId: string,
+  historyVisibility: HistoryVisibility,
+): Promise<void> {
+  try {
+    await commands.updateCommunityInfo(communityId, null, null, historyVisibility);
+    setCommunityState("communities", communityId, "historyVisibility", historyVisibility);
+  } catch (e) {
+    console.error("Failed to update history visibility:", e);
+    addToast("Failed to update history visibility", "error");
+  }
+}
+
 export async function handleBanMember(
   communityId: string,
   pseudonymKey: string,
@@ -378,6 +442,253 @@ export async function handleGetBanList(
   }
 }
 
+/** Whether a thread is one we can see: public, joined, or with Manage Threads. */
+function canSeeThread(communityId: string, thread: ThreadInfo): boolean {
+  const community = communityState.communities[communityId];
+  if (!community) return false;
+  if (!thread.private || thread.members.includes(community.myPseudonymKey ?? "")) return true;
+  const perms = calculateBasePermissions(community.myRoleIds, community.roles, community.isHosted);
+  return hasPermission(perms, MANAGE_THREADS);
+}
+
+function upsertThread(communityId: string, thread: ThreadInfo): void {
+  const community = communityState.communities[communityId];
+  if (!community) return;
+  const others = community.threads.filter((t) => t.id !== thread.id);
+  setCommunityState(
+    "communities", communityId, "threads",
+    canSeeThread(communityId, thread) ? [...others, thread] : others,
+  );
+}
+
+export async function handleLoadChannelThreads(
+  communityId: string,
+  channelId: string,
+): Promise<void> {
+  try {
+    const threads = await commands.getChannelThreads(communityId, channelId, false);
+    setCommunityState("communities", communityId, "threads", (prev) => [
+      ...prev.filter((t) => t.channelId !== channelId),
+      ...threads,
+    ]);
+  } catch (e) {
+    console.error("Failed to load threads:", e);
+  }
+}
+
+export async function handleCreateThread(
+  communityId: string,
+  channelId: string,
+  parentMessageId: string,
+  name: string,
+  isPrivate: boolean,
+): Promise<ThreadInfo | null> {
+  try {
+    const thread = await commands.createThread(
+      communityId, channelId, parentMessageId, name, isPrivate,
+    );
+    upsertThread(communityId, thread);
+    return thread;
+  } catch (e) {
+    console.error("Failed to create thread:", e);
+    addToast(`Failed to create thread: ${e}`, "error");
+    return null;
+  }
+}
+
+export async function handleArchiveThread(
+  communityId: string,
+  threadId: string,
+  archived: boolean,
+): Promise<void> {
+  try {
+    await commands.archiveThread(communityId, threadId, archived);
+  } catch (e) {
+    console.error("Failed to archive thread:", e);
+    addToast(archived ? "Failed to archive thread" : "Failed to reopen thread", "error");
+  }
+}
+
+export async function handleJoinThread(communityId: string, threadId: string): Promise<void> {
+  try {
+    await commands.joinThread(communityId, threadId);
+  } catch (e) {
+    console.error("Failed to join thread:", e);
+    addToast("Failed to join thread", "error");
+  }
+}
+
+export async function handleLeaveThread(communityId: string, threadId: string): Promise<void> {
+  try {
+    await commands.leaveThread(communityId, threadId);
+  } catch (e) {
+    console.error("Failed to leave thread:", e);
+    addToast("Failed to leave thread", "error");
+  }
+}
+
+export async function handleAddThreadMember(
+  communityId: string,
+  threadId: string,
+  pseudonymKey: string,
+): Promise<void> {
+  try {
+    await commands.addThreadMember(communityId, threadId, pseudonymKey);
+  } catch (e) {
+    console.error("Failed to add thread member:", e);
+    addToast("Failed to add to thread", "error");
+  }
+}
+
+export async function handleRemoveThreadMember(
+  communityId: string,
+  threadId: string,
+  pseudonymKey: string,
+): Promise<void> {
+  try {
+    await commands.removeThreadMember(communityId, threadId, pseudonymKey);
+  } catch (e) {
+    console.error("Failed to remove thread member:", e);
+    addToast("Failed to remove from thread", "error");
+  }
+}
+
+export async function handleCreateCommunityInvite(
+  communityId: string,
+  maxUses: number | null,
+  expiresInSecs: number | null,
+  grantRoleIds: number[],
+): Promise<CommunityInvite | null> {
+  try {
+    return await commands.createCommunityInvite(communityId, maxUses, expiresInSecs, grantRoleIds);
+  } catch (e) {
+    console.error("Failed to create invite:", e);
+    addToast(`Failed to create invite: ${e}`, "error");
+    return null;
+  }
+}
+
+export async function handleListCommunityInvites(
+  communityId: string,
+): Promise<CommunityInvites | null> {
+  try {
+    return await commands.listCommunityInvites(communityId);
+  } catch (e) {
+    console.error("Failed to get invites:", e);
+    return null;
+  }
+}
+
+export async function handleRevokeCommunityInvite(
+  communityId: string,
+  code: string,
+): Promise<boolean> {
+  try {
+    await commands.revokeCommunityInvite(communityId, code);
+    addToast("Invite revoked", "success");
+    return true;
+  } catch (e) {
+    console.error("Failed to revoke invite:", e);
+    addToast("Failed to revoke invite", "error");
+    return false;
+  }
+}
+
+export async function handleSetInviteOnly(
+  communityId: string,
+  inviteOnly: boolean,
+): Promise<boolean> {
+  try {
+    await commands.updateCommunityInfo(communityId, null, null, null, inviteOnly);
+    return true;
+  } catch (e) {
+    console.error("Failed to update invite-only setting:", e);
+    addToast("Failed to update invite-only setting", "error");
+    return false;
+  }
+}
+
+export async function handleGetAuditLog(
+  communityId: string,
+  before: number | null,
+  limit: number,
+  actionFilter: AuditAction | null,
+): Promise<CommunityAuditLog | null> {
+  try {
+    return await commands.getAuditLog(communityId, before, limit, actionFilter);
+  } catch (e) {
+    console.error("Failed to get audit log:", e);
+    addToast("Failed to load audit log", "error");
+    return null;
+  }
+}
+
+export async function handleSetAuditRetention(
+  communityId: string,
+  retentionSecs: number,
+): Promise<boolean> {
+  try {
+    await commands.updateCommunityInfo(communityId, null, null, null, null, retentionSecs);
+    return true;
+  } catch (e) {
+    console.error("Failed to update audit log retention:", e);
+    addToast("Failed to update audit log retention", "error");
+    return false;
+  }
+}
+
+export async function handleGetCommunityListing(
+  communityId: string,
+): Promise<CommunityListing | null> {
+  try {
+    return await commands.getCommunityListing(communityId);
+  } catch (e) {
+    console.error("Failed to get directory listing:", e);
+    return null;
+  }
+}
+
+export async function handleSetCommunityListing(
+  communityId: string,
+  listed: boolean,
+  gameIds: number[],
+): Promise<CommunityListing | null> {
+  try {
+    const listing = await commands.setCommunityListing(communityId, listed, gameIds);
+    addToast(listed ? "Community listed in the directory" : "Community removed from the directory", "success");
+    return listing;
+  } catch (e) {
+    console.error("Failed to update directory listing:", e);
+    addToast("Failed to update directory listing", "error");
+    return null;
+  }
+}
+
+export async function handleSearchDirectory(
+  query: string,
+  gameId: number | null,
+): Promise<DirectoryEntry[]> {
+  try {
+    return await commands.searchDirectory(query, gameId);
+  } catch (e) {
+    console.error("Failed to search the directory:", e);
+    addToast("Failed to search the community directory", "error");
+    return [];
+  }
+}
+
+export async function handlePreviewDirectoryListing(
+  communityId: string,
+): Promise<DirectoryPreview | null> {
+  try {
+    return await commands.previewDirectoryListing(communityId);
+  } catch (e) {
+    console.error("Failed to preview community:", e);
+    addToast(`Failed to preview community: ${e}`, "error");
+    return null;
+  }
+}
+
 export async function handleRotateMek(
   communityId: string,
 ): Promise<void> {
@@ -389,6 +700,41 @@ export async function handleRotateMek(
   }
 }
 
+export async function handleEnableZeroKnowledge(
+  communityId: string,
+): Promise<void> {
+  try {
+    await commands.enableZeroKnowledge(communityId);
+  } catch (e) {
+    console.error("Failed to enable zero-knowledge mode:", e);
+    addToast("Failed to enable zero-knowledge mode", "error");
+  }
+}
+
+export async function handleVouchMember(
+  communityId: string,
+  pseudonymKey: string,
+): Promise<void> {
+  try {
+    await commands.vouchCommunityMember(communityId, pseudonymKey);
+    addToast("Member vouched for", "success");
+  } catch (e) {
+    console.error("Failed to vouch for member:", e);
+    addToast("Failed to vouch for member", "error");
+  }
+}
+
+export async function handleEnableTreeKem(
+  communityId: string,
+): Promise<void> {
+  try {
+    await commands.enableTreeKem(communityId);
+  } catch (e) {
+    console.error("Failed to enable TreeKEM:", e);
+    addToast("Failed to enable large-community keys", "error");
+  }
+}
+
 // --- Role management handlers ---
 
 export async function handleAssignRole(
@@ -617,6 +963,8 @@ export function subscribeCommunityEventDispatcher(): Promise<UnlistenFn> {
           }
         }).catch(() => {});
       }
+    } else if (event.type === "threadUpdated") {
+      upsertThread(event.data.communityId, event.data.thread);
     } else if (event.type === "mekRotated") {
       const { communityId, newGeneration } = event.data;
       if (communityState.communities[communityId]) {
@@ -0,0 +1,99 @@
+import { reconcile } from "solid-js/store";
+import { commands } from "../ipc/commands";
+import { authState } from "../stores/auth.store";
+import { groupState, setGroupState } from "../stores/group.store";
+import type { GroupChat } from "../stores/group.store";
+import type { Message } from "../stores/chat.store";
+import { addToast } from "../stores/toast.store";
+
+export function handleGroupUpdated(group: GroupChat): void {
+  setGroupState("groups", group.id, reconcile(group));
+}
+
+export function handleGroupRemoved(groupId: string): void {
+  const groups = { ...groupState.groups };
+  delete groups[groupId];
+  setGroupState("groups", reconcile(groups));
+  const messages = { ...groupState.messages };
+  delete messages[groupId];
+  setGroupState("messages", reconcile(messages));
+}
+
+export async function handleCreateGroupChat(
+  name: string,
+  members: string[],
+): Promise<GroupChat | null> {
+  try {
+    const group = await commands.createGroupChat(name, members);
+    handleGroupUpdated(group);
+    return group;
+  } catch (e) {
+    console.error("Failed to create group:", e);
+    addToast(`Failed to create group: ${e}`, "error");
+    return null;
+  }
+}
+
+export async function handleLoadGroupHistory(groupId: string, limit: number): Promise<void> {
+  try {
+    const history = await commands.getGroupMessages(groupId, limit);
+    setGroupState("messages", groupId, history);
+  } catch (e) {
+    console.error("Failed to load group history:", e);
+  }
+}
+
+export function handleIncomingGroupMessage(groupId: string, message: Message): void {
+  const existing = groupState.messages[groupId];
+  if (!existing) {
+    setGroupState("messages", groupId, [message]);
+    return;
+  }
+  if (message.messageId && existing.some((m) => m.messageId === message.messageId)) return;
+  setGroupState("messages", groupId, (msgs) => [...msgs, message]);
+}
+
+export async function handleSendGroupMessage(groupId: string, body: string): Promise<void> {
+  try {
+    const messageId = await commands.sendGroupMessage(groupId, body);
+    handleIncomingGroupMessage(groupId, {
+      id: Date.now(),
+      messageId,
+      senderId: authState.publicKey ?? "",
+      body,
+      timestamp: Date.now(),
+      isOwn: true,
+    });
+  } catch (e) {
+    console.error("Failed to send group message:", e);
+    addToast("Failed to send message", "error");
+  }
+}
+
+export async function handleAddGroupMember(groupId: string, publicKey: string): Promise<void> {
+  try {
+    handleGroupUpdated(await commands.addGroupMember(groupId, publicKey));
+  } catch (e) {
+    console.error("Failed to add group member:", e);
+    addToast(`Failed to add member: ${e}`, "error");
+  }
+}
+
+export async function handleLeaveGroupChat(groupId: string): Promise<boolean> {
+  try {
+    await commands.leaveGroupChat(groupId);
+    handleGroupRemoved(groupId);
+    return true;
+  } catch (e) {
+    console.error("Failed to leave group:", e);
+    addToast("Failed to leave group", "error");
+    return false;
+  }
+}
+
+export function handleResetGroupUnread(groupId: string): void {
+  if (groupState.groups[groupId]) {
+    setGroupState("groups", groupId, "unreadCount", 0);
+  }
+  commands.markGroupRead(groupId).catch((e) => console.error("Failed to mark group read:", e));
+}
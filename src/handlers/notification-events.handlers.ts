@@ -2,6 +2,7 @@ import type { UnlistenFn } from "@tauri-apps/api/event";
 import { subscribeNotificationEvents } from "../ipc/channels";
 import { setNotificationState } from "../stores/notification.store";
 import { authState } from "../stores/auth.store";
+import { hydrateState } from "../ipc/hydrate";
 
 export function subscribeNotificationHandler(): Promise<UnlistenFn> {
   return subscribeNotificationEvents((event) => {
@@ -37,6 +38,24 @@ export function subscribeNotificationHandler(): Promise<UnlistenFn> {
         setNotificationState("unreadCount", (c) => c + 1);
         break;
       }
+      case "socialGraphRestored": {
+        const { friends, groups, communities, failed } = event.data;
+        hydrateState();
+        const restored = `Restored ${friends} friend(s), ${groups} group(s) and ${communities} community(ies) from your account.`;
+        setNotificationState("notifications", (prev) => [
+          ...prev,
+          {
+            id: crypto.randomUUID(),
+            type: "system",
+            title: "Account Restored",
+            body: failed.length > 0 ? `${restored}\nNot restored:\n${failed.join("\n")}` : restored,
+            timestamp: Date.now(),
+            read: false,
+          },
+        ]);
+        setNotificationState("unreadCount", (c) => c + 1);
+        break;
+      }
     }
   });
 }
@@ -13,6 +13,7 @@ export async function handleLoadSettings(): Promise<void> {
       startMinimized: prefs.startMinimized,
       showGameActivity: prefs.gameDetectionEnabled,
       autoAwayMinutes: prefs.autoAwayMinutes,
+      sendReadReceipts: prefs.sendReadReceipts,
     });
   } catch (e) {
     console.error("Failed to load settings:", e);
@@ -44,6 +45,9 @@ export async function handleSaveSettings(
       ...(settings.autoAwayMinutes !== undefined && {
         autoAwayMinutes: settings.autoAwayMinutes,
       }),
+      ...(settings.sendReadReceipts !== undefined && {
+        sendReadReceipts: settings.sendReadReceipts,
+      }),
     };
     await commands.setPreferences(updated);
     setSettingsState(settings);
@@ -60,6 +64,35 @@ export async function handleSetAvatar(avatarData: number[]): Promise<void> {
   }
 }
 
+/** Export an encrypted backup. Returns the file path, or an error message. */
+export async function handleExportBackup(
+  passphrase: string,
+  backupPassphrase: string,
+  includeHistory: boolean,
+): Promise<{ path: string } | { error: string }> {
+  try {
+    const path = await commands.exportIdentityBackup(passphrase, backupPassphrase, includeHistory);
+    return { path };
+  } catch (e) {
+    console.error("Failed to export backup:", e);
+    return { error: String(e) };
+  }
+}
+
+/** Change the passphrase. Returns an error message, or null on success. */
+export async function handleChangePassphrase(
+  currentPassphrase: string,
+  newPassphrase: string,
+): Promise<string | null> {
+  try {
+    await commands.changePassphrase(currentPassphrase, newPassphrase);
+    return null;
+  } catch (e) {
+    console.error("Failed to change passphrase:", e);
+    return String(e);
+  }
+}
+
 export async function handleCheckForUpdates(): Promise<boolean> {
   try {
     const available = await commands.checkForUpdates();
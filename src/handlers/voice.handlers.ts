@@ -18,6 +18,7 @@ export async function initVoiceEventListener(): Promise<UnlistenFn> {
             displayName: event.data.displayName,
             isMuted: false,
             isSpeaking: false,
+            connectionBars: null,
           },
         ]);
         break;
@@ -44,7 +45,19 @@ export async function initVoiceEventListener(): Promise<UnlistenFn> {
         );
         break;
       case "connectionQuality":
-        setVoiceState("connectionQuality", event.data.quality);
+        setVoiceState({
+          connectionQuality: event.data.quality,
+          connectionBars: event.data.bars,
+          rttMs: event.data.rttMs,
+        });
+        break;
+      case "participantStats":
+        setVoiceState(
+          "participants",
+          (p) => p.publicKey === event.data.publicKey,
+          "connectionBars",
+          event.data.bars,
+        );
         break;
       case "deviceChanged":
         setVoiceState("deviceChangeCount", (prev) => prev + 1);
@@ -54,18 +67,21 @@ export async function initVoiceEventListener(): Promise<UnlistenFn> {
 }
 
 export async function handleJoinVoice(channelId: string): Promise<void> {
+  // Subscribe first: joining emits the participants already in the channel
+  const unlisten = await initVoiceEventListener();
+  voiceEventUnlisten = unlisten;
   try {
     await commands.joinVoiceChannel(channelId);
 
-    // Subscribe to voice events
-    voiceEventUnlisten = await initVoiceEventListener();
-
     setVoiceState({
       isConnected: true,
       channelId,
     });
   } catch (e) {
     console.error("Failed to join voice:", e);
+    unlisten();
+    voiceEventUnlisten = null;
+    setVoiceState("participants", []);
   }
 }
 
@@ -84,6 +100,8 @@ export async function handleLeaveVoice(): Promise<void> {
       channelId: null,
       participants: [],
       connectionQuality: "good",
+      connectionBars: 4,
+      rttMs: null,
       activeCallType: null,
     });
   } catch (e) {
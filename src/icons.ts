@@ -4,12 +4,16 @@
 export const ICON_NEW_CHAT = "\u{F140F}";       // nf-md-chat_plus
 export const ICON_ADD_FRIEND = "\u{F0014}";      // nf-md-account_plus
 export const ICON_COMMUNITIES = "\u{F0849}";     // nf-md-account_group
+export const ICON_GROUP_CHAT = "\u{F028C}";     // nf-md-forum
+export const ICON_COMPASS = "\u{F018B}";        // nf-md-compass
 export const ICON_SETTINGS = "\u{F0493}";        // nf-md-cog
 export const ICON_LOGOUT = "\u{F0343}";          // nf-md-logout
 
 // Notifications
 export const ICON_BELL = "\u{F009A}";            // nf-md-bell
 export const ICON_CHECK = "\u{F012C}";           // nf-md-check
+export const ICON_CHECK_ALL = "\u{F0139}";       // nf-md-check_all
+export const ICON_CLOCK = "\u{F0150}";           // nf-md-clock_outline
 
 // Voice
 export const ICON_MIC = "\u{F036C}";             // nf-md-microphone
@@ -26,6 +30,11 @@ export const ICON_ACCOUNT_REMOVE = "\u{F0015}";  // nf-md-account_remove
 export const ICON_DOTS = "\u{F01D8}";            // nf-md-dots_horizontal
 export const ICON_CLOSE_CIRCLE = "\u{F0159}";    // nf-md-close_circle
 export const ICON_REFRESH = "\u{F0450}";         // nf-md-refresh
+export const ICON_FILE = "\u{F0214}";            // nf-md-file
+export const ICON_DOWNLOAD = "\u{F01DA}";        // nf-md-download
+export const ICON_FOLDER_OPEN = "\u{F0770}";     // nf-md-folder_open
+export const ICON_EMOTICON = "\u{F01F2}";        // nf-md-emoticon_outline
+export const ICON_REPLY = "\u{F045A}";           // nf-md-reply
 
 // Community
 export const ICON_PLUS = "\u{F0415}";            // nf-md-plus_circle — create community
@@ -42,3 +51,5 @@ export const ICON_TIMEOUT = "\u{F0954}";          // nf-md-clock_alert
 export const ICON_SHIELD = "\u{F0B56}";           // nf-md-shield_account
 export const ICON_KEY = "\u{F033E}";              // nf-md-key
 export const ICON_PERMS = "\u{F099C}";            // nf-md-shield_lock
+export const ICON_THREAD = "\u{F0368}";           // nf-md-message_reply_text
+export const ICON_ARCHIVE = "\u{F003C}";          // nf-md-archive
@@ -1,4 +1,7 @@
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { Attachment, Reaction } from "../stores/chat.store";
+import type { DeliveryState, ThreadInfo } from "./commands";
+import type { GroupChat } from "../stores/group.store";
 
 export type ChatEvent =
   | {
@@ -8,10 +11,26 @@ export type ChatEvent =
         body: string;
         timestamp: number;
         conversationId: string;
+        attachments?: Attachment[];
+        messageId?: string;
+        replyTo?: string;
       };
     }
   | { type: "typingIndicator"; data: { from: string; typing: boolean } }
   | { type: "messageAck"; data: { messageId: number } }
+  | {
+      type: "deliveryStateChanged";
+      data: { conversationId: string; messageIds: string[]; state: DeliveryState };
+    }
+  | {
+      type: "messageEdited";
+      data: { conversationId: string; messageId: string; body: string; editedAt: number };
+    }
+  | { type: "messageDeleted"; data: { conversationId: string; messageId: string } }
+  | {
+      type: "reactionsChanged";
+      data: { conversationId: string; messageId: string; reactions: Reaction[] };
+    }
   | {
       type: "friendRequest";
       data: { from: string; displayName: string; message: string };
@@ -27,6 +46,11 @@ export type ChatEvent =
   | { type: "friendRequestRejected"; data: { from: string } }
   | { type: "friendRemoved"; data: { publicKey: string } }
   | { type: "friendRequestDelivered"; data: { to: string } }
+  | { type: "identityKeyChanged"; data: { publicKey: string; verified: boolean } }
+  | {
+      type: "transferProgress";
+      data: { conversationId: string; attachment: Attachment };
+    }
   | {
       type: "channelHistoryLoaded";
       data: {
@@ -37,9 +61,37 @@ export type ChatEvent =
           body: string;
           timestamp: number;
           isOwn: boolean;
+          messageId?: string;
+          editedAt?: number;
+          reactions?: Reaction[];
+          replyTo?: string;
         }[];
       };
-    };
+    }
+  | {
+      type: "groupMessageReceived";
+      data: {
+        groupId: string;
+        from: string;
+        body: string;
+        timestamp: number;
+        messageId: string;
+      };
+    }
+  | { type: "groupUpdated"; data: { group: GroupChat } }
+  | { type: "groupRemoved"; data: { groupId: string } }
+  | {
+      type: "groupMembershipChanged";
+      data: {
+        groupId: string;
+        publicKey: string;
+        displayName: string;
+        joined: boolean;
+        by: string;
+      };
+    }
+  | { type: "disappearingTimerChanged"; data: { peerId: string; seconds: number; by: string } }
+  | { type: "messagesExpired"; data: { conversationId: string; messageIds: string[] } };
 
 export type PresenceEvent =
   | { type: "friendOnline"; data: { publicKey: string } }
@@ -76,7 +128,27 @@ export type VoiceEvent =
       type: "userMuted";
       data: { publicKey: string; muted: boolean };
     }
-  | { type: "connectionQuality"; data: { quality: string } }
+  | {
+      type: "connectionQuality";
+      data: {
+        quality: string;
+        bars: number;
+        rttMs: number | null;
+        lossPct: number;
+        bitrate: number;
+      };
+    }
+  | {
+      type: "participantStats";
+      data: {
+        publicKey: string;
+        lossPct: number;
+        jitterMs: number;
+        lateDrops: number;
+        bufferMs: number;
+        bars: number;
+      };
+    }
   | {
       type: "deviceChanged";
       data: { deviceType: string; deviceName: string; reason: string };
@@ -122,11 +194,26 @@ export type CommunityEvent =
   | {
       type: "channelOverwriteChanged";
       data: { communityId: string; channelId: string };
+    }
+  | {
+      type: "threadUpdated";
+      data: { communityId: string; thread: ThreadInfo };
     };
 
 export type NotificationEvent =
   | { type: "systemAlert"; data: { title: string; body: string } }
-  | { type: "updateAvailable"; data: { version: string } };
+  | { type: "updateAvailable"; data: { version: string } }
+  | { type: "deviceLinkGranted"; data: { publicKey: string; displayName: string } }
+  | {
+      type: "socialGraphRestored";
+      data: {
+        friends: number;
+        groups: number;
+        communities: number;
+        sessionsPending: number;
+        failed: string[];
+      };
+    };
 
 export type NetworkStatusEvent = {
   attachmentState: string;
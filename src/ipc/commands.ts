@@ -1,4 +1,7 @@
 import { invoke } from "./invoke";
+import type { HistoryVisibility } from "../stores/community.store";
+import type { Attachment, Reaction } from "../stores/chat.store";
+import type { GroupChat } from "../stores/group.store";
 
 export interface LoginResult {
   publicKey: string;
@@ -19,6 +22,47 @@ export interface Message {
   body: string;
   timestamp: number;
   isOwn: boolean;
+  attachments?: Attachment[];
+  messageId?: string;
+  editedAt?: number;
+  reactions?: Reaction[];
+  /** Global ID of the channel message this one replies to. */
+  replyTo?: string;
+  /** Our DMs only. */
+  deliveryState?: DeliveryState;
+}
+
+export type DeliveryState = "queued" | "sent" | "delivered" | "read" | "failed";
+
+/** A DM we just sent. */
+export interface SentMessage {
+  messageId: string;
+  deliveryState: DeliveryState;
+}
+
+/** Narrows a message search; all given filters must match. */
+export interface SearchFilters {
+  conversationId?: string;
+  senderKey?: string;
+  /** Inclusive lower bound on the message timestamp. */
+  since?: number;
+  /** Exclusive upper bound on the message timestamp. */
+  until?: number;
+  hasAttachment?: boolean;
+}
+
+export interface SearchResult {
+  id: number;
+  messageId: string | null;
+  conversationId: string;
+  conversationType: "dm" | "channel" | "group";
+  conversationName: string | null;
+  communityId: string | null;
+  senderId: string;
+  timestamp: number;
+  /** Body excerpt; matched terms are wrapped in \u0002 … \u0003. */
+  snippet: string;
+  hasAttachment: boolean;
 }
 
 export interface FriendInfo {
@@ -32,6 +76,7 @@ export interface FriendInfo {
   unreadCount: number;
   lastSeenAt: number | null;
   friendshipState: "pendingOut" | "accepted";
+  disappearingSecs: number;
 }
 
 export interface GameStatus {
@@ -66,6 +111,7 @@ export interface Preferences {
   noiseSuppression: boolean;
   echoCancellation: boolean;
   autoAwayMinutes: number;
+  sendReadReceipts: boolean;
 }
 
 export interface NetworkStatus {
@@ -77,6 +123,135 @@ export interface NetworkStatus {
   friendListDhtKey: string | null;
 }
 
+/** A community found in the public directory. */
+export interface DirectoryEntry {
+  communityId: string;
+  name: string;
+  description: string;
+  memberCount: number;
+  /** `GameEntry.id` of each game it is tagged with. */
+  gameIds: number[];
+  publishedAt: number;
+  joined: boolean;
+}
+
+/** A directory community as seen before joining it. */
+export interface DirectoryPreview extends DirectoryEntry {
+  channels: { name: string; channelType: string }[];
+  /** Whether its server is reachable right now. */
+  online: boolean;
+}
+
+/** Whether a community is listed in the directory, and under which games. */
+export interface CommunityListing {
+  listed: boolean;
+  gameIds: number[];
+}
+
+/** A server-managed invite code and its `rekindle://community/` link. */
+export interface CommunityInvite {
+  code: string;
+  link: string;
+  creatorPseudonym: string;
+  maxUses: number | null;
+  uses: number;
+  expiresAt: number | null;
+  grantRoleIds: number[];
+  createdAt: number;
+}
+
+export interface CommunityInvites {
+  inviteOnly: boolean;
+  invites: CommunityInvite[];
+}
+
+/** What an audit log entry records; the server's `AuditAction`. */
+export type AuditAction =
+  | "community_update"
+  | "listing_update"
+  | "channel_create"
+  | "channel_update"
+  | "channel_delete"
+  | "channel_overwrite_update"
+  | "channel_overwrite_delete"
+  | "role_create"
+  | "role_update"
+  | "role_delete"
+  | "member_role_add"
+  | "member_role_remove"
+  | "member_kick"
+  | "member_ban"
+  | "member_unban"
+  | "member_timeout"
+  | "member_timeout_remove"
+  | "message_delete"
+  | "invite_create"
+  | "invite_revoke"
+  | "mek_rotate"
+  | "zero_knowledge_enable"
+  | "tree_kem_enable"
+  | "thread_update"
+  | "thread_member_remove";
+
+/** A moderation or configuration action in a community's audit log. */
+export interface AuditEntry {
+  id: number;
+  actorPseudonym: string;
+  /** Null once the actor has left. */
+  actorName: string | null;
+  action: AuditAction;
+  /** Pseudonym, channel ID, thread ID, role ID or invite code acted on. */
+  target: string | null;
+  /** JSON of the target before and after the action. */
+  before: string | null;
+  after: string | null;
+  createdAt: number;
+}
+
+/** A page of the audit log, newest first. */
+export interface CommunityAuditLog {
+  entries: AuditEntry[];
+  /** Seconds entries are kept for; 0 keeps them forever. */
+  retentionSecs: number;
+}
+
+/** A thread started from a message in a text channel. */
+export interface ThreadInfo {
+  /** Used as the channel ID of the thread's messages. */
+  id: string;
+  channelId: string;
+  parentMessageId: string;
+  name: string;
+  creatorPseudonym: string;
+  /** Only members, and those with Manage Threads, can see a private thread. */
+  private: boolean;
+  archived: boolean;
+  /** Pseudonym keys of the thread's members. */
+  members: string[];
+  messageCount: number;
+  lastMessageAt: number | null;
+  createdAt: number;
+}
+
+export interface KnownGame {
+  id: number;
+  name: string;
+}
+
+/** A device linked to our identity. */
+export interface DeviceInfo {
+  deviceKey: string;
+  name: string;
+  mailboxDhtKey: string;
+  addedAt: number;
+}
+
+export interface DeviceList {
+  devices: DeviceInfo[];
+  /** This device's key, or null on the primary device. */
+  localDeviceKey: string | null;
+}
+
 export const commands = {
   // Auth
   createIdentity: (passphrase: string, displayName?: string) =>
@@ -88,18 +263,63 @@ export const commands = {
   listIdentities: () => invoke<IdentitySummary[]>("list_identities"),
   deleteIdentity: (publicKey: string, passphrase: string) =>
     invoke<void>("delete_identity", { publicKey, passphrase }),
+  changePassphrase: (currentPassphrase: string, newPassphrase: string) =>
+    invoke<void>("change_passphrase", { currentPassphrase, newPassphrase }),
+  completeDeviceLink: (passphrase: string) =>
+    invoke<LoginResult>("complete_device_link", { passphrase }),
+  exportIdentityBackup: (passphrase: string, backupPassphrase: string, includeHistory: boolean) =>
+    invoke<string>("export_identity_backup", { passphrase, backupPassphrase, includeHistory }),
+  importIdentityBackup: (data: number[], backupPassphrase: string, passphrase: string) =>
+    invoke<LoginResult>("import_identity_backup", { data, backupPassphrase, passphrase }),
+  getRecoveryPhrase: (passphrase: string) =>
+    invoke<string>("get_recovery_phrase", { passphrase }),
+  restoreFromRecoveryPhrase: (phrase: string, passphrase: string, displayName?: string) =>
+    invoke<LoginResult>("restore_from_recovery_phrase", { phrase, passphrase, displayName }),
+
+  // Devices
+  createDeviceLinkRequest: (deviceName: string) =>
+    invoke<string>("create_device_link_request", { deviceName }),
+  cancelDeviceLink: () => invoke<void>("cancel_device_link"),
+  approveDeviceLink: (url: string) =>
+    invoke<DeviceInfo>("approve_device_link", { url }),
+  getDevices: () => invoke<DeviceList>("get_devices"),
+  removeDevice: (deviceKey: string) =>
+    invoke<void>("remove_device", { deviceKey }),
 
   // Chat
   prepareChatSession: (peerId: string) =>
     invoke<void>("prepare_chat_session", { peerId }),
   sendMessage: (to: string, body: string) =>
-    invoke<void>("send_message", { to, body }),
+    invoke<SentMessage>("send_message", { to, body }),
+  editMessage: (conversationId: string, messageId: string, body: string) =>
+    invoke<void>("edit_message", { conversationId, messageId, body }),
+  deleteMessage: (conversationId: string, messageId: string) =>
+    invoke<void>("delete_message", { conversationId, messageId }),
+  addReaction: (conversationId: string, messageId: string, emoji: string) =>
+    invoke<void>("add_reaction", { conversationId, messageId, emoji }),
+  removeReaction: (conversationId: string, messageId: string, emoji: string) =>
+    invoke<void>("remove_reaction", { conversationId, messageId, emoji }),
+  setDisappearingTimer: (peerId: string, seconds: number) =>
+    invoke<void>("set_disappearing_timer", { peerId, seconds }),
   sendTyping: (peerId: string, typing: boolean) =>
     invoke<void>("send_typing", { peerId, typing }),
+  sendFile: (to: string, path: string) =>
+    invoke<Message>("send_file", { to, path }),
+  downloadAttachment: (messageId: number, index: number) =>
+    invoke<Attachment>("download_attachment", { messageId, index }),
   getMessageHistory: (peerId: string, limit: number) =>
     invoke<Message[]>("get_message_history", { peerId, limit }),
   markRead: (peerId: string) => invoke<void>("mark_read", { peerId }),
 
+  // Search
+  searchMessages: (query: string, filters?: SearchFilters, limit?: number) =>
+    invoke<SearchResult[]>("search_messages", {
+      query,
+      filters: filters ?? null,
+      limit: limit ?? null,
+    }),
+  rebuildSearchIndex: () => invoke<void>("rebuild_search_index"),
+
   // Friends
   addFriend: (publicKey: string, displayName: string, message: string) =>
     invoke<void>("add_friend", { publicKey, displayName, message }),
@@ -133,6 +353,15 @@ export const commands = {
     invoke<void>("cancel_request", { publicKey }),
   emitFriendsPresence: () =>
     invoke<void>("emit_friends_presence"),
+  getSafetyNumber: (publicKey: string) =>
+    invoke<{ safetyNumber: string; qrPayload: string; verified: boolean }>(
+      "get_safety_number",
+      { publicKey },
+    ),
+  setFriendVerified: (publicKey: string, verified: boolean) =>
+    invoke<void>("set_friend_verified", { publicKey, verified }),
+  verifySafetyNumberQr: (publicKey: string, qrPayload: string) =>
+    invoke<boolean>("verify_safety_number_qr", { publicKey, qrPayload }),
 
   // Community
   getCommunities: () =>
@@ -142,13 +371,14 @@ export const commands = {
       id: string;
       name: string;
       description: string | null;
-      channels: { id: string; name: string; channelType: string; unreadCount: number }[];
+      channels: { id: string; name: string; channelType: string; unreadCount: number; retentionSecs: number }[];
       myRole: string | null;
       myRoleIds: number[];
       roles: { id: number; name: string; color: number; permissions: number; position: number; hoist: boolean; mentionable: boolean }[];
       myPseudonymKey: string | null;
       mekGeneration: number;
       isHosted: boolean;
+      historyVisibility: HistoryVisibility;
     }[]>("get_community_details"),
   getCommunityMembers: (communityId: string) =>
     invoke<{ pseudonymKey: string; displayName: string; roleIds: number[]; displayRole: string; status: string; timeoutUntil: number | null }[]>(
@@ -156,12 +386,12 @@ export const commands = {
     ),
   createCommunity: (name: string) =>
     invoke<string>("create_community", { name }),
-  joinCommunity: (communityId: string) =>
-    invoke<void>("join_community", { communityId }),
+  joinCommunity: (invite: string) =>
+    invoke<string>("join_community", { invite }),
   createChannel: (communityId: string, name: string, channelType: string) =>
     invoke<string>("create_channel", { communityId, name, channelType }),
-  sendChannelMessage: (channelId: string, body: string) =>
-    invoke<void>("send_channel_message", { channelId, body }),
+  sendChannelMessage: (channelId: string, body: string, replyTo: string | null = null) =>
+    invoke<string>("send_channel_message", { channelId, body, replyTo }),
   getChannelMessages: (channelId: string, limit: number) =>
     invoke<Message[]>("get_channel_messages", { channelId, limit }),
   removeCommunityMember: (communityId: string, pseudonymKey: string) =>
@@ -172,8 +402,19 @@ export const commands = {
     invoke<void>("delete_channel", { communityId, channelId }),
   renameChannel: (communityId: string, channelId: string, newName: string) =>
     invoke<void>("rename_channel", { communityId, channelId, newName }),
-  updateCommunityInfo: (communityId: string, name: string | null, description: string | null) =>
-    invoke<void>("update_community_info", { communityId, name, description }),
+  setChannelRetention: (communityId: string, channelId: string, seconds: number) =>
+    invoke<void>("set_channel_retention", { communityId, channelId, seconds }),
+  updateCommunityInfo: (
+    communityId: string,
+    name: string | null,
+    description: string | null,
+    historyVisibility: HistoryVisibility | null = null,
+    inviteOnly: boolean | null = null,
+    auditRetentionSecs: number | null = null,
+  ) =>
+    invoke<void>("update_community_info", {
+      communityId, name, description, historyVisibility, inviteOnly, auditRetentionSecs,
+    }),
   banMember: (communityId: string, pseudonymKey: string) =>
     invoke<void>("ban_member", { communityId, pseudonymKey }),
   unbanMember: (communityId: string, pseudonymKey: string) =>
@@ -182,8 +423,64 @@ export const commands = {
     invoke<{ pseudonymKey: string; displayName: string; bannedAt: number }[]>(
       "get_ban_list", { communityId },
     ),
+  getAuditLog: (
+    communityId: string,
+    before: number | null,
+    limit: number,
+    actionFilter: AuditAction | null = null,
+  ) =>
+    invoke<CommunityAuditLog>("get_audit_log", { communityId, before, limit, actionFilter }),
+  getChannelThreads: (communityId: string, channelId: string, includeArchived: boolean) =>
+    invoke<ThreadInfo[]>("get_channel_threads", { communityId, channelId, includeArchived }),
+  createThread: (
+    communityId: string,
+    channelId: string,
+    parentMessageId: string,
+    name: string,
+    isPrivate: boolean,
+  ) =>
+    invoke<ThreadInfo>("create_thread", {
+      communityId, channelId, parentMessageId, name, private: isPrivate,
+    }),
+  archiveThread: (communityId: string, threadId: string, archived: boolean) =>
+    invoke<void>("archive_thread", { communityId, threadId, archived }),
+  joinThread: (communityId: string, threadId: string) =>
+    invoke<void>("join_thread", { communityId, threadId }),
+  leaveThread: (communityId: string, threadId: string) =>
+    invoke<void>("leave_thread", { communityId, threadId }),
+  addThreadMember: (communityId: string, threadId: string, pseudonymKey: string) =>
+    invoke<void>("add_thread_member", { communityId, threadId, pseudonymKey }),
+  removeThreadMember: (communityId: string, threadId: string, pseudonymKey: string) =>
+    invoke<void>("remove_thread_member", { communityId, threadId, pseudonymKey }),
+  createCommunityInvite: (
+    communityId: string,
+    maxUses: number | null,
+    expiresInSecs: number | null,
+    grantRoleIds: number[],
+  ) =>
+    invoke<CommunityInvite>("create_community_invite", {
+      communityId, maxUses, expiresInSecs, grantRoleIds,
+    }),
+  listCommunityInvites: (communityId: string) =>
+    invoke<CommunityInvites>("list_community_invites", { communityId }),
+  revokeCommunityInvite: (communityId: string, code: string) =>
+    invoke<void>("revoke_community_invite", { communityId, code }),
+  getCommunityListing: (communityId: string) =>
+    invoke<CommunityListing>("get_community_listing", { communityId }),
+  setCommunityListing: (communityId: string, listed: boolean, gameIds: number[]) =>
+    invoke<CommunityListing>("set_community_listing", { communityId, listed, gameIds }),
+  searchDirectory: (query: string, gameId: number | null = null) =>
+    invoke<DirectoryEntry[]>("search_directory", { query, gameId }),
+  previewDirectoryListing: (communityId: string) =>
+    invoke<DirectoryPreview>("preview_directory_listing", { communityId }),
   rotateMek: (communityId: string) =>
     invoke<void>("rotate_mek", { communityId }),
+  enableZeroKnowledge: (communityId: string) =>
+    invoke<void>("enable_zero_knowledge", { communityId }),
+  vouchCommunityMember: (communityId: string, pseudonymKey: string) =>
+    invoke<void>("vouch_community_member", { communityId, pseudonymKey }),
+  enableTreeKem: (communityId: string) =>
+    invoke<void>("enable_tree_kem", { communityId }),
 
   // Roles
   getRoles: (communityId: string) =>
@@ -209,6 +506,23 @@ export const commands = {
   deleteChannelOverwrite: (communityId: string, channelId: string, targetType: string, targetId: string) =>
     invoke<void>("delete_channel_overwrite", { communityId, channelId, targetType, targetId }),
 
+  // Groups
+  createGroupChat: (name: string, members: string[]) =>
+    invoke<GroupChat>("create_group_chat", { name, members }),
+  getGroupChats: () => invoke<GroupChat[]>("get_group_chats"),
+  getGroupMessages: (groupId: string, limit: number) =>
+    invoke<Message[]>("get_group_messages", { groupId, limit }),
+  sendGroupMessage: (groupId: string, body: string) =>
+    invoke<string>("send_group_message", { groupId, body }),
+  addGroupMember: (groupId: string, publicKey: string) =>
+    invoke<GroupChat>("add_group_member", { groupId, publicKey }),
+  leaveGroupChat: (groupId: string) =>
+    invoke<void>("leave_group_chat", { groupId }),
+  markGroupRead: (groupId: string) =>
+    invoke<void>("mark_group_read", { groupId }),
+  refreshGroupChat: (groupId: string) =>
+    invoke<void>("refresh_group_chat", { groupId }),
+
   // Voice
   joinVoiceChannel: (channelId: string) =>
     invoke<void>("join_voice_channel", { channelId }),
@@ -235,6 +549,7 @@ export const commands = {
 
   // Game
   getGameStatus: () => invoke<GameStatus | null>("get_game_status"),
+  getKnownGames: () => invoke<KnownGame[]>("get_known_games"),
 
   // Settings
   getPreferences: () => invoke<Preferences>("get_preferences"),
@@ -249,6 +564,8 @@ export const commands = {
   openSettingsWindow: (tab?: string) => invoke<void>("open_settings_window", { tab: tab ?? null }),
   openCommunityWindow: (communityId: string, communityName: string) =>
     invoke<void>("open_community_window", { communityId, communityName }),
+  openGroupWindow: (groupId: string, groupName: string) =>
+    invoke<void>("open_group_window", { groupId, groupName }),
   openProfileWindow: (publicKey: string, displayName: string) =>
     invoke<void>("open_profile_window", { publicKey, displayName }),
   getNetworkStatus: () => invoke<NetworkStatus>("get_network_status"),
@@ -3,15 +3,18 @@ import { setAuthState } from "../stores/auth.store";
 import { fetchAvatarUrl } from "./avatar";
 import { setFriendsState } from "../stores/friends.store";
 import { setCommunityState } from "../stores/community.store";
+import { setGroupState } from "../stores/group.store";
 import type { Friend } from "../stores/friends.store";
 import type { Community } from "../stores/community.store";
+import type { GroupChat } from "../stores/group.store";
 
 /**
  * Hydrate frontend stores from the Rust backend.
  *
  * Each Tauri webview has its own isolated JavaScript context,
  * so SolidJS stores are empty when a new window opens.
- * This function loads identity + friends + communities from the backend.
+ * This function loads identity + friends + communities + group
+ * conversations from the backend.
  */
 export async function hydrateState(): Promise<void> {
   try {
@@ -48,6 +51,7 @@ export async function hydrateState(): Promise<void> {
         lastSeenAt: f.lastSeenAt ?? null,
         voiceChannel: null,
         friendshipState: (f.friendshipState as Friend["friendshipState"]) ?? "accepted",
+        disappearingSecs: f.disappearingSecs ?? 0,
       };
     }
     setFriendsState("friends", friendMap);
@@ -68,17 +72,31 @@ export async function hydrateState(): Promise<void> {
           name: ch.name,
           type: ch.channelType as "text" | "voice",
           unreadCount: ch.unreadCount,
+          retentionSecs: ch.retentionSecs ?? 0,
         })),
         members: [],
+        threads: [],
         roles: c.roles ?? [],
         myRoleIds: c.myRoleIds ?? [0, 1],
         myPseudonymKey: c.myPseudonymKey ?? null,
         mekGeneration: c.mekGeneration ?? 0,
         isHosted: c.isHosted ?? false,
+        historyVisibility: c.historyVisibility ?? "full",
       };
     }
     setCommunityState("communities", communityMap);
   } catch (e) {
     console.error("Failed to hydrate communities:", e);
   }
+
+  try {
+    const groups = await commands.getGroupChats();
+    const groupMap: Record<string, GroupChat> = {};
+    for (const g of groups) {
+      groupMap[g.id] = g;
+    }
+    setGroupState("groups", groupMap);
+  } catch (e) {
+    console.error("Failed to hydrate group conversations:", e);
+  }
 }
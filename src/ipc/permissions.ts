@@ -39,6 +39,12 @@ export const CHANGE_NICKNAME = 1 << 26;
 export const MANAGE_NICKNAMES = 1 << 27;
 export const MANAGE_ROLES = 1 << 28;
 
+// ── Threads ──
+// Above bit 31 too, so pre-computed like MODERATE_MEMBERS below.
+export const MANAGE_THREADS = 2 ** 34;
+export const CREATE_PUBLIC_THREADS = 2 ** 35;
+export const CREATE_PRIVATE_THREADS = 2 ** 36;
+
 // ── Moderation ──
 // MODERATE_MEMBERS is at bit 40 in Rust (u64). JavaScript bitwise operators
 // work on 32-bit ints so (1 << 40) === 0. We use a pre-computed value instead.
@@ -56,11 +62,14 @@ export const ALL_PERMISSIONS =
   | MUTE_MEMBERS | DEAFEN_MEMBERS | MOVE_MEMBERS | USE_VAD
   | CHANGE_NICKNAME | MANAGE_NICKNAMES | MANAGE_ROLES
   // High bits (> 31) added via arithmetic since JS bitwise ops truncate to 32 bits
-  + MODERATE_MEMBERS;
+  + MANAGE_THREADS + CREATE_PUBLIC_THREADS + CREATE_PRIVATE_THREADS + MODERATE_MEMBERS;
 
 // The ADMINISTRATOR bit for quick checks
 const ADMIN_BIT = ADMINISTRATOR;
 
+// Bit 32, where permissions spill past JS's 32-bit bitwise operators
+const HIGH_WORD = 2 ** 32;
+
 /**
  * Check if a permission set includes a specific permission.
  * Administrators bypass all checks.
@@ -68,7 +77,7 @@ const ADMIN_BIT = ADMINISTRATOR;
 export function hasPermission(perms: number, required: number): boolean {
   // Administrators have all permissions
   if ((perms & ADMIN_BIT) !== 0) return true;
-  // For bits > 31 (like MODERATE_MEMBERS at bit 40), we can't use
+  // For bits > 31 (threads at 34–36, MODERATE_MEMBERS at 40), we can't use
   // bitwise AND because JS bitwise ops truncate to 32 bits. Instead
   // we test with floating-point math for those high bits.
   if (required > 0x7FFF_FFFF) {
@@ -92,8 +101,8 @@ export function isAdministrator(perms: number): boolean {
  *
  * Note: Rust sends permissions as u64 but JavaScript numbers can safely
  * represent integers up to 2^53. Permission bits go up to 40, so this is fine.
- * However, JS bitwise operators truncate to 32 bits, so we use addition
- * to combine permissions above bit 31.
+ * However, JS bitwise operators truncate to 32 bits, so the bits above 31
+ * are OR'd separately as their own 32-bit word.
  */
 export function calculateBasePermissions(
   roleIds: number[],
@@ -106,15 +115,10 @@ export function calculateBasePermissions(
   for (const id of roleIds) {
     const role = allRoles.find((r) => r.id === id);
     if (role) {
-      // Can't use |= for bits above 31, so we combine manually.
-      // For low bits (0-30), bitwise OR is fine. For high bits, we add
-      // them if not already present.
-      const lowBits = (perms | role.permissions) & 0x7FFF_FFFF;
-      const highPerms = role.permissions - (role.permissions & 0x7FFF_FFFF);
-      const highCurrent = perms - (perms & 0x7FFF_FFFF);
-      // Combine high bits: if either has the high bit set, keep it.
-      // Since our only high bit is MODERATE_MEMBERS (2^40), simple max works.
-      perms = lowBits + Math.max(highPerms, highCurrent);
+      // Can't use |= for bits above 31, so each word is OR'd on its own
+      const lowBits = ((perms % HIGH_WORD) | (role.permissions % HIGH_WORD)) >>> 0;
+      const highBits = Math.floor(perms / HIGH_WORD) | Math.floor(role.permissions / HIGH_WORD);
+      perms = highBits * HIGH_WORD + lowBits;
     }
   }
   return perms;
@@ -192,6 +196,14 @@ export const PERMISSION_CATEGORIES = [
       { key: "PRIORITY_SPEAKER", label: "Priority Speaker", value: PRIORITY_SPEAKER },
     ],
   },
+  {
+    name: "Threads",
+    permissions: [
+      { key: "CREATE_PUBLIC_THREADS", label: "Create Public Threads", value: CREATE_PUBLIC_THREADS },
+      { key: "CREATE_PRIVATE_THREADS", label: "Create Private Threads", value: CREATE_PRIVATE_THREADS },
+      { key: "MANAGE_THREADS", label: "Manage Threads", value: MANAGE_THREADS },
+    ],
+  },
   {
     name: "Moderation",
     permissions: [
@@ -9,6 +9,7 @@ const LoginWindow = lazy(() => import("./windows/LoginWindow"));
 const BuddyListWindow = lazy(() => import("./windows/BuddyListWindow"));
 const ChatWindow = lazy(() => import("./windows/ChatWindow"));
 const CommunityWindow = lazy(() => import("./windows/CommunityWindow"));
+const GroupChatWindow = lazy(() => import("./windows/GroupChatWindow"));
 const SettingsWindow = lazy(() => import("./windows/SettingsWindow"));
 const ProfileWindow = lazy(() => import("./windows/ProfileWindow"));
 
@@ -34,6 +35,9 @@ function App() {
         <Match when={route().startsWith("/community")}>
           <CommunityWindow />
         </Match>
+        <Match when={route().startsWith("/group")}>
+          <GroupChatWindow />
+        </Match>
         <Match when={route() === "/settings"}>
           <SettingsWindow />
         </Match>
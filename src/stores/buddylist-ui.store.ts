@@ -8,6 +8,8 @@ export interface BuddyListUIState {
   menuOpen: string | null;
   showCreateCommunity: boolean;
   showJoinCommunity: boolean;
+  showCommunityBrowser: boolean;
+  showNewGroupChat: boolean;
 }
 
 const [buddyListUI, setBuddyListUI] = createStore<BuddyListUIState>({
@@ -16,6 +18,8 @@ const [buddyListUI, setBuddyListUI] = createStore<BuddyListUIState>({
   menuOpen: null,
   showCreateCommunity: false,
   showJoinCommunity: false,
+  showCommunityBrowser: false,
+  showNewGroupChat: false,
 });
 
 export function switchTab(tab: BuddyListTab): void {
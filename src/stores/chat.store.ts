@@ -1,15 +1,44 @@
 import { createStore } from "solid-js/store";
 
-export type MessageStatus = "sending" | "sent" | "failed";
+export type MessageStatus = "sending" | "queued" | "sent" | "delivered" | "read" | "failed";
+
+export type AttachmentState = "available" | "active" | "complete" | "failed";
+
+export interface Attachment {
+  messageId: number;
+  index: number;
+  name: string;
+  mimeType: string;
+  size: number;
+  state: AttachmentState;
+  /** Chunks transferred so far, out of `total`. */
+  done: number;
+  total: number;
+  localPath: string | null;
+}
+
+/** One emoji's reactions on a message. */
+export interface Reaction {
+  emoji: string;
+  count: number;
+  /** Whether one of them is ours. */
+  mine: boolean;
+}
 
 export interface Message {
   id: number;
+  /** Global ID used to edit, delete or react to the message; absent on legacy rows. */
+  messageId?: string;
   senderId: string;
   body: string;
   timestamp: number;
   isOwn: boolean;
-  replyTo?: number;
+  /** Global ID of the channel message this one replies to. */
+  replyTo?: string;
   status?: MessageStatus;
+  attachments?: Attachment[];
+  editedAt?: number;
+  reactions?: Reaction[];
 }
 
 export interface Conversation {
@@ -1,11 +1,14 @@
 import { createStore } from "solid-js/store";
 import type { Message } from "./chat.store";
+import type { ThreadInfo } from "../ipc/commands";
 
 export interface Channel {
   id: string;
   name: string;
   type: "text" | "voice";
   unreadCount: number;
+  /** Seconds the community keeps messages for; 0 keeps them forever. */
+  retentionSecs: number;
 }
 
 export interface Member {
@@ -27,17 +30,23 @@ export interface Role {
   mentionable: boolean;
 }
 
+/** Whether members can read history from before they joined. */
+export type HistoryVisibility = "full" | "since_join";
+
 export interface Community {
   id: string;
   name: string;
   description: string | null;
   channels: Channel[];
   members: Member[];
+  /** Threads of channels we have opened, loaded with the channel. */
+  threads: ThreadInfo[];
   roles: Role[];
   myRoleIds: number[];
   myPseudonymKey: string | null;
   mekGeneration: number;
   isHosted: boolean;
+  historyVisibility: HistoryVisibility;
 }
 
 export interface CommunityState {
@@ -21,6 +21,8 @@ export interface Friend {
   lastSeenAt: number | null;
   voiceChannel: string | null;
   friendshipState: FriendshipState;
+  /** Disappearing-message timer of our DMs, in seconds; 0 when off. */
+  disappearingSecs: number;
 }
 
 export interface PendingRequest {
@@ -0,0 +1,36 @@
+import { createStore } from "solid-js/store";
+import type { Message } from "./chat.store";
+
+/** Members a group may have, counting us. */
+export const MAX_GROUP_MEMBERS = 10;
+
+export interface GroupMember {
+  publicKey: string;
+  displayName: string;
+  profileDhtKey: string | null;
+  mailboxDhtKey: string | null;
+  joinedAt: number;
+}
+
+/** A group conversation: two to ten friends without a community server. */
+export interface GroupChat {
+  id: string;
+  name: string;
+  createdBy: string;
+  members: GroupMember[];
+  unreadCount: number;
+  dhtRecordKey: string | null;
+}
+
+export interface GroupState {
+  groups: Record<string, GroupChat>;
+  /** Loaded history per group ID. */
+  messages: Record<string, Message[]>;
+}
+
+const [groupState, setGroupState] = createStore<GroupState>({
+  groups: {},
+  messages: {},
+});
+
+export { groupState, setGroupState };
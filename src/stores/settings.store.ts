@@ -7,6 +7,7 @@ export interface SettingsState {
   startMinimized: boolean;
   showGameActivity: boolean;
   autoAwayMinutes: number;
+  sendReadReceipts: boolean;
 }
 
 const [settingsState, setSettingsState] = createStore<SettingsState>({
@@ -16,6 +17,7 @@ const [settingsState, setSettingsState] = createStore<SettingsState>({
   startMinimized: true,
   showGameActivity: true,
   autoAwayMinutes: 10,
+  sendReadReceipts: true,
 });
 
 export { settingsState, setSettingsState };
@@ -5,6 +5,8 @@ export interface VoiceParticipant {
   displayName: string;
   isMuted: boolean;
   isSpeaking: boolean;
+  /** Connection bars (0–4) for how their audio reaches us; null until measured. */
+  connectionBars: number | null;
 }
 
 export interface VoiceState {
@@ -14,6 +16,9 @@ export interface VoiceState {
   isDeafened: boolean;
   participants: VoiceParticipant[];
   connectionQuality: string;
+  /** Connection bars (0–4) for our own link. */
+  connectionBars: number;
+  rttMs: number | null;
   activeCallType: "dm" | "community" | null;
   inputDevice: string | null;
   outputDevice: string | null;
@@ -29,6 +34,8 @@ const [voiceState, setVoiceState] = createStore<VoiceState>({
   isDeafened: false,
   participants: [],
   connectionQuality: "good",
+  connectionBars: 4,
+  rttMs: null,
   activeCallType: null,
   inputDevice: null,
   outputDevice: null,
@@ -7,12 +7,15 @@ import TabBar from "../components/buddy-list/TabBar";
 import SearchBar, { focusSearchInput } from "../components/buddy-list/SearchBar";
 import PendingRequests from "../components/buddy-list/PendingRequests";
 import BuddyList from "../components/buddy-list/BuddyList";
+import GroupChatList from "../components/buddy-list/GroupChatList";
 import CommunityListCompact from "../components/buddy-list/CommunityListCompact";
 import BottomActionBar from "../components/buddy-list/BottomActionBar";
 import AddFriendModal from "../components/buddy-list/AddFriendModal";
 import NewChatModal from "../components/buddy-list/NewChatModal";
+import NewGroupChatModal from "../components/buddy-list/NewGroupChatModal";
 import BuddyCreateCommunityModal from "../components/buddy-list/BuddyCreateCommunityModal";
 import BuddyJoinCommunityModal from "../components/buddy-list/BuddyJoinCommunityModal";
+import BuddyCommunityBrowserModal from "../components/buddy-list/BuddyCommunityBrowserModal";
 import StatusPicker from "../components/status/StatusPicker";
 import NetworkIndicator from "../components/status/NetworkIndicator";
 import { authState, setAuthState } from "../stores/auth.store";
@@ -122,6 +125,7 @@ const BuddyListWindow: Component = () => {
       <SearchBar />
       <Show when={buddyListUI.activeTab === "friends"}>
         <PendingRequests />
+        <GroupChatList />
         <BuddyList />
       </Show>
       <Show when={buddyListUI.activeTab === "communities"}>
@@ -134,8 +138,10 @@ const BuddyListWindow: Component = () => {
       </div>
       <AddFriendModal />
       <NewChatModal />
+      <NewGroupChatModal />
       <BuddyCreateCommunityModal />
       <BuddyJoinCommunityModal />
+      <BuddyCommunityBrowserModal />
     </div>
   );
 };
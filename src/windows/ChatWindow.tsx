@@ -1,5 +1,6 @@
 import { Component, onMount, onCleanup, createMemo, createSignal, createEffect, Show } from "solid-js";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { getCurrentWebview } from "@tauri-apps/api/webview";
 import type { ChatEvent } from "../ipc/channels";
 import Titlebar from "../components/titlebar/Titlebar";
 import MessageList from "../components/chat/MessageList";
@@ -7,11 +8,23 @@ import MessageInput from "../components/chat/MessageInput";
 import TypingIndicator from "../components/chat/TypingIndicator";
 import StatusDot from "../components/status/StatusDot";
 import VoicePanel from "../components/voice/VoicePanel";
-import { chatState, setChatState, type Message } from "../stores/chat.store";
+import DisappearingTimer from "../components/chat/DisappearingTimer";
+import ToastContainer from "../components/common/Toast";
+import { chatState, setChatState, type Attachment, type Message } from "../stores/chat.store";
 import { authState } from "../stores/auth.store";
 import { friendsState } from "../stores/friends.store";
 import { voiceState } from "../stores/voice.store";
-import { handleLoadHistory, handleResetUnread, handleRetrySendMessage } from "../handlers/chat.handlers";
+import {
+  handleLoadHistory,
+  handleResetUnread,
+  handleRetrySendMessage,
+  handleSendFile,
+  handleDownloadAttachment,
+  handleEditMessage,
+  handleDeleteMessage,
+  handleToggleReaction,
+  handleSetDisappearingTimer,
+} from "../handlers/chat.handlers";
 import { handleJoinVoice, handleLeaveVoice } from "../handlers/voice.handlers";
 import { subscribeDmChatEvents } from "../handlers/chat-events.handlers";
 import { subscribeBuddyListPresenceEvents } from "../handlers/presence-events.handlers";
@@ -79,6 +92,24 @@ const ChatWindow: Component = () => {
     handleRetrySendMessage(peerId, messageId);
   }
 
+  function handleDownload(attachment: Attachment): void {
+    handleDownloadAttachment(peerId, attachment);
+  }
+
+  function handleEdit(message: Message, body: string): void {
+    if (message.messageId) handleEditMessage(peerId, message.messageId, body);
+  }
+
+  function handleDelete(message: Message): void {
+    if (message.messageId) handleDeleteMessage(peerId, message.messageId);
+  }
+
+  function handleReact(message: Message, emoji: string): void {
+    handleToggleReaction(peerId, message, emoji);
+  }
+
+  const [isDraggingFile, setIsDraggingFile] = createSignal(false);
+
   const unlisteners: Promise<UnlistenFn>[] = [];
   let refreshInterval: ReturnType<typeof setInterval> | undefined;
 
@@ -87,12 +118,35 @@ const ChatWindow: Component = () => {
     // queueMicrotask ensures handleIncomingMessage has already updated the store.
     const directUnsub = await listen<ChatEvent>("chat-event", (event) => {
       const p = event.payload;
-      if (p.type === "messageReceived" && p.data.conversationId === peerId) {
+      if (
+        (p.type === "messageReceived" || p.type === "transferProgress") &&
+        p.data.conversationId === peerId
+      ) {
         queueMicrotask(syncMessages);
       }
     });
     unlisteners.push(Promise.resolve(directUnsub));
 
+    // Files dropped on the window are shared with the peer
+    unlisteners.push(
+      getCurrentWebview().onDragDropEvent((event) => {
+        switch (event.payload.type) {
+          case "enter":
+          case "over":
+            setIsDraggingFile(true);
+            break;
+          case "drop":
+            setIsDraggingFile(false);
+            for (const path of event.payload.paths) {
+              handleSendFile(peerId, path);
+            }
+            break;
+          default:
+            setIsDraggingFile(false);
+        }
+      }),
+    );
+
     // Register event listeners FIRST so no events are missed during hydration.
     // subscribeBuddyListPresenceEvents updates the global friendsState store
     // (each Tauri webview has isolated JS context, so we need our own listener).
@@ -141,6 +195,11 @@ const ChatWindow: Component = () => {
       <div class="chat-peer-status">
         <StatusDot status={peerStatus()} />
         <span class="chat-peer-status-label">{peerStatus()}</span>
+        <DisappearingTimer
+          seconds={friendsState.friends[peerId]?.disappearingSecs ?? 0}
+          editable={friendsState.friends[peerId]?.friendshipState === "accepted"}
+          onChange={(seconds) => handleSetDisappearingTimer(peerId, seconds)}
+        />
         <button
           class={`chat-call-btn ${isInCallWithPeer() ? "chat-call-btn-active" : ""}`}
           onClick={handleCallToggle}
@@ -156,12 +215,20 @@ const ChatWindow: Component = () => {
         ownName={ownName()}
         peerName={peerName()}
         onRetry={handleRetry}
+        onDownload={handleDownload}
+        onEdit={handleEdit}
+        onDelete={handleDelete}
+        onReact={handleReact}
       />
+      <Show when={isDraggingFile()}>
+        <div class="chat-drop-overlay">Drop to send to {peerName()}</div>
+      </Show>
       <Show when={isInCallWithPeer()}>
         <VoicePanel />
       </Show>
       <TypingIndicator isTyping={conversation().isTyping} peerName={peerName()} />
       <MessageInput peerId={peerId} />
+      <ToastContainer />
     </div>
   );
 };
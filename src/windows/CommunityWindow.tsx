@@ -6,12 +6,14 @@ import ChannelList from "../components/community/ChannelList";
 import MemberList from "../components/community/MemberList";
 import MessageList from "../components/chat/MessageList";
 import MessageInput from "../components/chat/MessageInput";
+import DisappearingTimer from "../components/chat/DisappearingTimer";
 import VoicePanel from "../components/voice/VoicePanel";
 import CreateCommunityModal from "../components/community/CreateCommunityModal";
 import CreateChannelModal from "../components/community/CreateChannelModal";
 import JoinCommunityModal from "../components/community/JoinCommunityModal";
 import CommunitySettingsModal from "../components/community/CommunitySettingsModal";
 import RenameChannelModal from "../components/community/RenameChannelModal";
+import CreateThreadModal from "../components/community/CreateThreadModal";
 import ConfirmDialog from "../components/common/ConfirmDialog";
 import ToastContainer from "../components/common/Toast";
 import { communityState } from "../stores/community.store";
@@ -30,14 +32,30 @@ import {
   handleLeaveCommunity,
   handleDeleteChannel,
   handleRetryChannelMessage,
+  handleSetChannelRetention,
+  handleLoadChannelThreads,
+  handleArchiveThread,
+  handleJoinThread,
+  handleLeaveThread,
 } from "../handlers/community.handlers";
 import { handleJoinVoice } from "../handlers/voice.handlers";
+import {
+  handleEditMessage,
+  handleDeleteMessage,
+  handleToggleReaction,
+} from "../handlers/chat.handlers";
 import {
   calculateBasePermissions,
   hasPermission,
   MANAGE_CHANNELS,
+  MANAGE_MESSAGES,
+  MANAGE_THREADS,
+  CREATE_PUBLIC_THREADS,
+  CREATE_PRIVATE_THREADS,
 } from "../ipc/permissions";
 import type { Message } from "../stores/chat.store";
+import type { ThreadInfo } from "../ipc/commands";
+import type { ContextMenuItem } from "../components/common/ContextMenu";
 import {
   ICON_COMMUNITIES,
   ICON_PLUS,
@@ -45,6 +63,9 @@ import {
   ICON_SETTINGS,
   ICON_LOGOUT,
   ICON_CHANNEL_TEXT,
+  ICON_THREAD,
+  ICON_ARCHIVE,
+  ICON_JOIN,
 } from "../icons";
 
 const CommunityWindow: Component = () => {
@@ -61,6 +82,9 @@ const CommunityWindow: Component = () => {
   const [showSettings, setShowSettings] = createSignal(false);
   const [renameTarget, setRenameTarget] = createSignal<{ channelId: string; currentName: string } | null>(null);
   const [showLeaveConfirm, setShowLeaveConfirm] = createSignal(false);
+  const [selectedThreadId, setSelectedThreadId] = createSignal<string>("");
+  const [replyTarget, setReplyTarget] = createSignal<Message | null>(null);
+  const [threadSource, setThreadSource] = createSignal<Message | null>(null);
 
   const activeCommunity = createMemo(() => {
     const id = selectedCommunityId();
@@ -74,10 +98,19 @@ const CommunityWindow: Component = () => {
     return community.channels.find((c) => c.id === channelId);
   });
 
+  const activeThread = createMemo((): ThreadInfo | undefined => {
+    const threadId = selectedThreadId();
+    if (!threadId) return undefined;
+    return activeCommunity()?.threads.find((t) => t.id === threadId);
+  });
+
+  /** The open thread, or else the selected channel. */
+  const conversationId = createMemo((): string => selectedThreadId() || selectedChannelId());
+
   const channelMessages = createMemo((): Message[] => {
-    const channelId = selectedChannelId();
-    if (!channelId) return [];
-    return communityState.channelMessages[channelId] ?? [];
+    const id = conversationId();
+    if (!id) return [];
+    return communityState.channelMessages[id] ?? [];
   });
 
   const myRoleIds = createMemo((): number[] => {
@@ -92,16 +125,67 @@ const CommunityWindow: Component = () => {
     return hasPermission(perms, MANAGE_CHANNELS);
   });
 
-  // Load messages when channel changes
+  const canManageMessages = createMemo((): boolean => {
+    const community = activeCommunity();
+    if (!community) return false;
+    const perms = calculateBasePermissions(myRoleIds(), community.roles, community.isHosted);
+    return hasPermission(perms, MANAGE_MESSAGES);
+  });
+
+  const threadPerms = createMemo(() => {
+    const community = activeCommunity();
+    if (!community) return { manage: false, createPublic: false, createPrivate: false };
+    const perms = calculateBasePermissions(myRoleIds(), community.roles, community.isHosted);
+    return {
+      manage: hasPermission(perms, MANAGE_THREADS),
+      createPublic: hasPermission(perms, CREATE_PUBLIC_THREADS),
+      createPrivate: hasPermission(perms, CREATE_PRIVATE_THREADS),
+    };
+  });
+
+  const isThreadMember = (thread: ThreadInfo): boolean =>
+    thread.members.includes(activeCommunity()?.myPseudonymKey ?? "");
+
+  const canArchiveThread = (thread: ThreadInfo): boolean =>
+    threadPerms().manage || thread.creatorPseudonym === activeCommunity()?.myPseudonymKey;
+
+  function memberName(senderId: string): string {
+    return activeCommunity()?.members.find((m) => m.pseudonymKey === senderId)?.displayName
+      ?? "Channel";
+  }
+
+  function replyTargetName(): string | null {
+    const target = replyTarget();
+    if (!target) return null;
+    return target.isOwn ? "yourself" : memberName(target.senderId);
+  }
+
+  // Load messages when channel or thread changes
   createEffect(() => {
-    const channelId = selectedChannelId();
-    if (channelId) {
-      handleLoadChannelMessages(channelId, 50);
+    const id = conversationId();
+    setReplyTarget(null);
+    if (id) {
+      handleLoadChannelMessages(id, 50);
+    }
+  });
+
+  // Load a text channel's threads when it is opened
+  createEffect(() => {
+    if (activeChannel()?.type === "text") {
+      handleLoadChannelThreads(selectedCommunityId(), selectedChannelId());
+    }
+  });
+
+  // Close a thread we can no longer see
+  createEffect(() => {
+    if (selectedThreadId() && !activeThread()) {
+      setSelectedThreadId("");
     }
   });
 
   function handleSelectCommunity(id: string) {
     setSelectedCommunityId(id);
+    setSelectedThreadId("");
     storeSyncSelectCommunity(id);
     const community = communityState.communities[id];
     if (community?.channels.length) {
@@ -114,10 +198,41 @@ const CommunityWindow: Component = () => {
   }
 
   function handleSelectChannel(id: string) {
+    setSelectedThreadId("");
     setSelectedChannelId(id);
     storeSyncSelectChannel(id);
   }
 
+  function handleSelectThread(thread: ThreadInfo) {
+    setSelectedChannelId(thread.channelId);
+    setSelectedThreadId(thread.id);
+    storeSyncSelectChannel(thread.channelId);
+  }
+
+  function threadMenuItems(thread: ThreadInfo): ContextMenuItem[] {
+    const items: ContextMenuItem[] = [];
+    if (isThreadMember(thread)) {
+      items.push({
+        label: "Leave Thread",
+        action: () => handleLeaveThread(selectedCommunityId(), thread.id),
+      });
+    } else if (!thread.private) {
+      items.push({
+        label: "Join Thread",
+        icon: ICON_JOIN,
+        action: () => handleJoinThread(selectedCommunityId(), thread.id),
+      });
+    }
+    if (canArchiveThread(thread)) {
+      items.push({
+        label: "Archive Thread",
+        icon: ICON_ARCHIVE,
+        action: () => handleArchiveThread(selectedCommunityId(), thread.id, true),
+      });
+    }
+    return items;
+  }
+
   const unlisteners: Promise<UnlistenFn>[] = [];
 
   onMount(async () => {
@@ -206,6 +321,10 @@ const CommunityWindow: Component = () => {
               onVoiceJoin={handleJoinVoice}
               onRename={(channelId, currentName) => setRenameTarget({ channelId, currentName })}
               onDelete={(channelId) => handleDeleteChannel(selectedCommunityId(), channelId)}
+              threads={activeCommunity()!.threads}
+              selectedThreadId={selectedThreadId()}
+              onSelectThread={handleSelectThread}
+              threadMenuItems={threadMenuItems}
             />
           </Show>
           <Show when={voiceState.isConnected}>
@@ -231,20 +350,93 @@ const CommunityWindow: Component = () => {
               <div class="empty-placeholder-subtitle">Choose a community and channel to start chatting</div>
             </div>
           }>
-            <div class="community-channel-header">
-              <span class="nf-icon community-channel-header-icon">{ICON_CHANNEL_TEXT}</span>
-              {activeChannel()!.name}
-              <Show when={activeCommunity()?.description}>
-                <span class="community-description-hint">{activeCommunity()!.description}</span>
-              </Show>
-            </div>
+            <Show
+              when={activeThread()}
+              fallback={
+                <div class="community-channel-header">
+                  <span class="nf-icon community-channel-header-icon">{ICON_CHANNEL_TEXT}</span>
+                  {activeChannel()!.name}
+                  <Show when={activeCommunity()?.description}>
+                    <span class="community-description-hint">{activeCommunity()!.description}</span>
+                  </Show>
+                  <DisappearingTimer
+                    seconds={activeChannel()!.retentionSecs}
+                    editable={canManageChannels() && activeChannel()!.type === "text"}
+                    onChange={(seconds) =>
+                      handleSetChannelRetention(selectedCommunityId(), selectedChannelId(), seconds)}
+                  />
+                </div>
+              }
+            >
+              {(thread) => (
+                <div class="community-channel-header">
+                  <span class="nf-icon community-channel-header-icon">{ICON_THREAD}</span>
+                  {thread().name}
+                  <span class="community-description-hint">
+                    {thread().private ? "Private thread" : "Thread"} in #{activeChannel()!.name}
+                  </span>
+                  <span class="header-btn-group">
+                    <Show when={!isThreadMember(thread()) && !thread().private}>
+                      <button
+                        class="action-bar-btn header-add-btn"
+                        onClick={() => handleJoinThread(selectedCommunityId(), thread().id)}
+                        title="Join Thread"
+                      >
+                        <span class="nf-icon">{ICON_JOIN}</span>
+                      </button>
+                    </Show>
+                    <Show when={canArchiveThread(thread())}>
+                      <button
+                        class="action-bar-btn header-add-btn"
+                        onClick={() =>
+                          handleArchiveThread(selectedCommunityId(), thread().id, !thread().archived)}
+                        title={thread().archived ? "Reopen Thread" : "Archive Thread"}
+                      >
+                        <span class="nf-icon">{ICON_ARCHIVE}</span>
+                      </button>
+                    </Show>
+                  </span>
+                </div>
+              )}
+            </Show>
             <MessageList
               messages={channelMessages()}
               ownName={authState.displayName ?? "You"}
               peerName="Channel"
-              onRetry={(messageId) => handleRetryChannelMessage(selectedChannelId(), messageId)}
+              senderName={memberName}
+              canModerate={canManageMessages()}
+              onRetry={(messageId) => handleRetryChannelMessage(conversationId(), messageId)}
+              onEdit={(message, body) => {
+                if (message.messageId) handleEditMessage(conversationId(), message.messageId, body);
+              }}
+              onDelete={(message) => {
+                if (message.messageId) handleDeleteMessage(conversationId(), message.messageId);
+              }}
+              onReact={(message, emoji) => handleToggleReaction(conversationId(), message, emoji)}
+              onReply={setReplyTarget}
+              onStartThread={
+                !activeThread() && (threadPerms().createPublic || threadPerms().createPrivate)
+                  ? setThreadSource
+                  : undefined
+              }
+              threadFor={(messageId) =>
+                activeCommunity()?.threads.find((t) => t.parentMessageId === messageId)}
+              onOpenThread={handleSelectThread}
             />
-            <MessageInput peerId={selectedChannelId()} onSend={handleSendChannelMessage} />
+            <Show
+              when={!activeThread()?.archived}
+              fallback={<div class="empty-placeholder-subtitle">This thread is archived.</div>}
+            >
+              <MessageInput
+                peerId={conversationId()}
+                onSend={(id, body) => {
+                  handleSendChannelMessage(id, body, replyTarget()?.messageId ?? null);
+                  setReplyTarget(null);
+                }}
+                replyingTo={replyTargetName()}
+                onCancelReply={() => setReplyTarget(null)}
+              />
+            </Show>
           </Show>
         </div>
 
@@ -277,6 +469,21 @@ const CommunityWindow: Component = () => {
           communityId={selectedCommunityId()}
           onClose={() => setShowCreateChannel(false)}
         />
+        <Show when={threadSource()}>
+          {(source) => (
+            <CreateThreadModal
+              isOpen={true}
+              communityId={selectedCommunityId()}
+              channelId={selectedChannelId()}
+              parentMessageId={source().messageId ?? ""}
+              defaultName={source().body}
+              canCreatePublic={threadPerms().createPublic}
+              canCreatePrivate={threadPerms().createPrivate}
+              onClose={() => setThreadSource(null)}
+              onCreated={handleSelectThread}
+            />
+          )}
+        </Show>
         <CommunitySettingsModal
           isOpen={showSettings()}
           community={activeCommunity()!}
@@ -0,0 +1,164 @@
+import { Component, For, Show, createMemo, createSignal, onCleanup, onMount } from "solid-js";
+import type { UnlistenFn } from "@tauri-apps/api/event";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+import Titlebar from "../components/titlebar/Titlebar";
+import MessageList from "../components/chat/MessageList";
+import MessageInput from "../components/chat/MessageInput";
+import ConfirmDialog from "../components/common/ConfirmDialog";
+import ToastContainer from "../components/common/Toast";
+import { authState } from "../stores/auth.store";
+import { friendsState } from "../stores/friends.store";
+import { groupState, MAX_GROUP_MEMBERS } from "../stores/group.store";
+import {
+  handleAddGroupMember,
+  handleLeaveGroupChat,
+  handleLoadGroupHistory,
+  handleResetGroupUnread,
+  handleSendGroupMessage,
+} from "../handlers/group.handlers";
+import { subscribeGroupChatEvents } from "../handlers/chat-events.handlers";
+import { hydrateState } from "../ipc/hydrate";
+import { commands } from "../ipc/commands";
+import { ICON_ADD_FRIEND } from "../icons";
+
+function getGroupFromUrl(): string {
+  const params = new URLSearchParams(window.location.search);
+  return params.get("id") ?? "";
+}
+
+const GroupChatWindow: Component = () => {
+  const groupId = getGroupFromUrl();
+  const [showAddMember, setShowAddMember] = createSignal(false);
+  const [confirmLeave, setConfirmLeave] = createSignal(false);
+
+  const group = createMemo(() => groupState.groups[groupId]);
+  const messages = createMemo(() => groupState.messages[groupId] ?? []);
+  const ownName = createMemo(() => authState.displayName ?? "You");
+
+  function senderName(senderId: string): string {
+    const friend = friendsState.friends[senderId];
+    if (friend) return friend.nickname ?? friend.displayName;
+    const member = group()?.members.find((m) => m.publicKey === senderId);
+    return member?.displayName ?? `${senderId.slice(0, 8)}...`;
+  }
+
+  // Friends we could still add: accepted, not already in the group
+  const addableFriends = createMemo(() => {
+    const members = new Set(group()?.members.map((m) => m.publicKey) ?? []);
+    return Object.values(friendsState.friends).filter(
+      (f) => f.friendshipState === "accepted" && !members.has(f.publicKey),
+    );
+  });
+
+  const isFull = () => (group()?.members.length ?? 0) >= MAX_GROUP_MEMBERS;
+
+  async function handleAdd(publicKey: string): Promise<void> {
+    setShowAddMember(false);
+    await handleAddGroupMember(groupId, publicKey);
+  }
+
+  async function handleLeave(): Promise<void> {
+    setConfirmLeave(false);
+    if (await handleLeaveGroupChat(groupId)) {
+      await getCurrentWindow().close();
+    }
+  }
+
+  const unlisteners: Promise<UnlistenFn>[] = [];
+
+  onMount(async () => {
+    unlisteners.push(subscribeGroupChatEvents(groupId));
+    await hydrateState();
+    await handleLoadGroupHistory(groupId, 100);
+    handleResetGroupUnread(groupId);
+    // Pick up members added or removed while we were offline
+    commands.refreshGroupChat(groupId).catch(() => {});
+  });
+
+  onCleanup(() => {
+    for (const p of unlisteners) {
+      p.then((unlisten) => unlisten());
+    }
+  });
+
+  return (
+    <div class="app-frame">
+      <Titlebar title={`Group — ${group()?.name ?? "Group Chat"}`} showMaximize />
+      <Show
+        when={group()}
+        fallback={<div class="empty-placeholder-subtitle">You are no longer in this group.</div>}
+      >
+        <div class="group-chat-body">
+          <div class="group-chat-main">
+            <MessageList
+              messages={messages()}
+              ownName={ownName()}
+              peerName=""
+              senderName={senderName}
+            />
+            <MessageInput
+              peerId={groupId}
+              onSend={(id, body) => handleSendGroupMessage(id, body)}
+            />
+          </div>
+          <div class="group-member-sidebar">
+            <div class="member-list-header">
+              Members — {group()!.members.length}/{MAX_GROUP_MEMBERS}
+            </div>
+            <div class="member-list">
+              <For each={group()!.members}>
+                {(member) => (
+                  <div class="member-item">
+                    <span class="member-name">
+                      {member.publicKey === authState.publicKey ? ownName() : senderName(member.publicKey)}
+                    </span>
+                  </div>
+                )}
+              </For>
+            </div>
+            <Show when={showAddMember()}>
+              <div class="group-friend-picker">
+                <Show when={addableFriends().length > 0} fallback={
+                  <div class="empty-placeholder-subtitle">No friends left to add</div>
+                }>
+                  <For each={addableFriends()}>
+                    {(friend) => (
+                      <div class="group-friend-option" onClick={() => handleAdd(friend.publicKey)}>
+                        {friend.nickname ?? friend.displayName}
+                      </div>
+                    )}
+                  </For>
+                </Show>
+              </div>
+            </Show>
+            <div class="group-member-actions">
+              <button
+                class="action-bar-icon-btn"
+                onClick={() => setShowAddMember(!showAddMember())}
+                disabled={isFull()}
+                title={isFull() ? "This group is full" : "Add a friend"}
+              >
+                <span class="nf-icon">{ICON_ADD_FRIEND}</span>
+              </button>
+              <button class="settings-danger-btn" onClick={() => setConfirmLeave(true)}>
+                Leave
+              </button>
+            </div>
+          </div>
+        </div>
+      </Show>
+      <ConfirmDialog
+        isOpen={confirmLeave()}
+        title="Leave Group"
+        message="Leave this group? Its history will be deleted from this device."
+        confirmLabel="Leave"
+        danger
+        onConfirm={handleLeave}
+        onCancel={() => setConfirmLeave(false)}
+      />
+      <ToastContainer />
+    </div>
+  );
+};
+
+export default GroupChatWindow;
@@ -1,11 +1,18 @@
-import { Component, createSignal, onMount, For, Show } from "solid-js";
+import { Component, createSignal, onCleanup, onMount, For, Show } from "solid-js";
 import Titlebar from "../components/titlebar/Titlebar";
 import Avatar from "../components/common/Avatar";
 import Modal from "../components/common/Modal";
-import { handleLogin, handleCreateIdentity } from "../handlers/auth.handlers";
+import {
+  handleLogin,
+  handleCreateIdentity,
+  handleCompleteDeviceLink,
+  handleImportBackup,
+  handleRestoreFromPhrase,
+} from "../handlers/auth.handlers";
 import { commands, avatarDataUrl, IdentitySummary } from "../ipc/commands";
+import { subscribeNotificationEvents } from "../ipc/channels";
 
-type Mode = "picker" | "login" | "create";
+type Mode = "picker" | "login" | "create" | "link" | "restore";
 
 function truncateKey(key: string): string {
   if (key.length <= 16) return key;
@@ -21,6 +28,18 @@ const LoginWindow: Component = () => {
   const [error, setError] = createSignal<string | null>(null);
   const [loading, setLoading] = createSignal(false);
 
+  // Device link state: the request URL to approve elsewhere, then the
+  // identity that approved it
+  const [deviceName, setDeviceName] = createSignal("");
+  const [linkUrl, setLinkUrl] = createSignal<string | null>(null);
+  const [linkedName, setLinkedName] = createSignal<string | null>(null);
+
+  // Restore state: a backup file or a recovery phrase
+  const [restoreSource, setRestoreSource] = createSignal<"backup" | "phrase">("backup");
+  const [backupFile, setBackupFile] = createSignal<File | null>(null);
+  const [backupPassphrase, setBackupPassphrase] = createSignal("");
+  const [recoveryPhrase, setRecoveryPhrase] = createSignal("");
+
   // Delete confirmation state
   const [deleteTarget, setDeleteTarget] = createSignal<IdentitySummary | null>(null);
   const [deletePass, setDeletePass] = createSignal("");
@@ -53,8 +72,14 @@ const LoginWindow: Component = () => {
     }
   }
 
-  onMount(() => {
+  onMount(async () => {
     loadIdentities();
+    const unlisten = await subscribeNotificationEvents((event) => {
+      if (event.type === "deviceLinkGranted") {
+        setLinkedName(event.data.displayName);
+      }
+    });
+    onCleanup(unlisten);
   });
 
   function selectAccount(id: IdentitySummary): void {
@@ -123,6 +148,110 @@ const LoginWindow: Component = () => {
     }
   }
 
+  function startLink(): void {
+    setDeviceName("");
+    setPassphrase("");
+    setLinkUrl(null);
+    setLinkedName(null);
+    setError(null);
+    setMode("link");
+  }
+
+  function cancelLink(): void {
+    if (linkUrl() !== null) {
+      commands.cancelDeviceLink().catch(() => {});
+    }
+    setLinkUrl(null);
+    setLinkedName(null);
+    goBack();
+  }
+
+  async function handleLinkRequest(e: Event): Promise<void> {
+    e.preventDefault();
+    if (!deviceName().trim() || loading()) return;
+
+    setLoading(true);
+    setError(null);
+    try {
+      setLinkUrl(await commands.createDeviceLinkRequest(deviceName().trim()));
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+    setLoading(false);
+  }
+
+  async function handleLinkSubmit(e: Event): Promise<void> {
+    e.preventDefault();
+    if (!passphrase().trim() || loading()) return;
+
+    setLoading(true);
+    setError(null);
+
+    const result = await handleCompleteDeviceLink(passphrase());
+
+    if (result.success) {
+      try {
+        await commands.showBuddyList();
+      } catch (err) {
+        const msg = err instanceof Error ? err.message : String(err);
+        console.error("Failed to open buddy list:", msg);
+        setError(msg);
+        setLoading(false);
+      }
+    } else {
+      setError(result.error);
+      setLoading(false);
+    }
+  }
+
+  function startRestore(): void {
+    setPassphrase("");
+    setDisplayName("");
+    setBackupFile(null);
+    setBackupPassphrase("");
+    setRecoveryPhrase("");
+    setError(null);
+    setMode("restore");
+  }
+
+  async function handleRestoreSubmit(e: Event): Promise<void> {
+    e.preventDefault();
+    if (!passphrase().trim() || loading()) return;
+
+    let result: { success: true } | { success: false; error: string };
+    if (restoreSource() === "backup") {
+      const file = backupFile();
+      if (!file || !backupPassphrase()) return;
+      setLoading(true);
+      setError(null);
+      const data = Array.from(new Uint8Array(await file.arrayBuffer()));
+      result = await handleImportBackup(data, backupPassphrase(), passphrase());
+    } else {
+      if (!recoveryPhrase().trim()) return;
+      setLoading(true);
+      setError(null);
+      result = await handleRestoreFromPhrase(
+        recoveryPhrase(),
+        passphrase(),
+        displayName() || undefined,
+      );
+    }
+
+    if (result.success) {
+      try {
+        await commands.showBuddyList();
+      } catch (err) {
+        const msg = err instanceof Error ? err.message : String(err);
+        console.error("Failed to open buddy list:", msg);
+        setError(msg);
+        setLoading(false);
+      }
+    } else {
+      setError(result.error);
+      setLoading(false);
+    }
+  }
+
   function confirmDelete(id: IdentitySummary, e: Event): void {
     e.stopPropagation();
     setDeleteTarget(id);
@@ -182,6 +311,12 @@ const LoginWindow: Component = () => {
           <button class="account-create-btn" onClick={() => { setPassphrase(""); setDisplayName(""); setError(null); setMode("create"); }}>
             + Create New Identity
           </button>
+          <button class="account-create-btn" onClick={startLink}>
+            Link This Device
+          </button>
+          <button class="account-create-btn" onClick={startRestore}>
+            Restore an Identity
+          </button>
         </div>
       </Show>
 
@@ -248,9 +383,156 @@ const LoginWindow: Component = () => {
           <button class="login-btn" type="submit" disabled={loading()}>
             {loading() ? "..." : "Create Identity"}
           </button>
+          <button type="button" class="account-back-btn" onClick={startLink}>
+            Already have an identity? Link this device
+          </button>
+          <button type="button" class="account-back-btn" onClick={startRestore}>
+            Restore from a backup or recovery phrase
+          </button>
         </form>
       </Show>
 
+      {/* Restore mode — from a backup file or a recovery phrase */}
+      <Show when={mode() === "restore"}>
+        <form class="login-container" onSubmit={handleRestoreSubmit}>
+          <button type="button" class="account-back-btn" onClick={goBack}>
+            ← Back
+          </button>
+          <div class="login-title">Restore Identity</div>
+          <div class="login-mode-toggle">
+            <button
+              type="button"
+              class={`login-toggle-btn ${restoreSource() === "backup" ? "login-toggle-active" : ""}`}
+              onClick={() => { setRestoreSource("backup"); setError(null); }}
+            >
+              Backup File
+            </button>
+            <button
+              type="button"
+              class={`login-toggle-btn ${restoreSource() === "phrase" ? "login-toggle-active" : ""}`}
+              onClick={() => { setRestoreSource("phrase"); setError(null); }}
+            >
+              Recovery Phrase
+            </button>
+          </div>
+          <Show when={restoreSource() === "backup"} fallback={
+            <>
+              <div class="login-subtitle">
+                Restores your identity only — friends and history need a backup file
+              </div>
+              <textarea
+                class="login-input"
+                rows={4}
+                placeholder="24-word recovery phrase"
+                value={recoveryPhrase()}
+                onInput={(e: InputEvent) => setRecoveryPhrase((e.target as HTMLTextAreaElement).value)}
+              />
+              <input
+                class="login-input"
+                type="text"
+                placeholder="Display Name (optional)"
+                value={displayName()}
+                onInput={(e: InputEvent) => setDisplayName((e.target as HTMLInputElement).value)}
+              />
+            </>
+          }>
+            <input
+              class="login-input"
+              type="file"
+              accept=".rkbackup"
+              onChange={(e: Event) => setBackupFile((e.target as HTMLInputElement).files?.[0] ?? null)}
+            />
+            <input
+              class="login-input"
+              type="password"
+              placeholder="Backup passphrase"
+              value={backupPassphrase()}
+              onInput={(e: InputEvent) => setBackupPassphrase((e.target as HTMLInputElement).value)}
+            />
+          </Show>
+          <input
+            class="login-input"
+            type="password"
+            placeholder="New passphrase for this device"
+            value={passphrase()}
+            onInput={(e: InputEvent) => setPassphrase((e.target as HTMLInputElement).value)}
+          />
+          <Show when={error() !== null}>
+            <div class="login-error">{error()}</div>
+          </Show>
+          <button class="login-btn" type="submit" disabled={loading()}>
+            {loading() ? "..." : "Restore"}
+          </button>
+        </form>
+      </Show>
+
+      {/* Link mode — join an identity from another device */}
+      <Show when={mode() === "link"}>
+        <Show when={linkUrl() === null}>
+          <form class="login-container" onSubmit={handleLinkRequest}>
+            <button type="button" class="account-back-btn" onClick={cancelLink}>
+              ← Back
+            </button>
+            <div class="login-title">Link Device</div>
+            <div class="login-subtitle">Name this device</div>
+            <input
+              class="login-input"
+              type="text"
+              placeholder="e.g. Laptop"
+              value={deviceName()}
+              onInput={(e: InputEvent) => setDeviceName((e.target as HTMLInputElement).value)}
+              autofocus
+            />
+            <Show when={error() !== null}>
+              <div class="login-error">{error()}</div>
+            </Show>
+            <button class="login-btn" type="submit" disabled={loading()}>
+              {loading() ? "..." : "Create Link Request"}
+            </button>
+          </form>
+        </Show>
+        <Show when={linkUrl() !== null && linkedName() === null}>
+          <div class="login-container">
+            <div class="login-title">Link Device</div>
+            <div class="login-subtitle">
+              On your other device, open Settings → Devices and paste this link
+            </div>
+            <textarea class="login-input" readOnly rows={4} value={linkUrl()!} />
+            <button
+              type="button"
+              class="login-btn"
+              onClick={() => navigator.clipboard.writeText(linkUrl()!)}
+            >
+              Copy Link
+            </button>
+            <div class="login-subtitle">Waiting for approval…</div>
+            <button type="button" class="account-back-btn" onClick={cancelLink}>
+              Cancel
+            </button>
+          </div>
+        </Show>
+        <Show when={linkedName() !== null}>
+          <form class="login-container" onSubmit={handleLinkSubmit}>
+            <div class="login-title">{linkedName()}</div>
+            <div class="login-subtitle">Choose a passphrase for this device</div>
+            <input
+              class="login-input"
+              type="password"
+              placeholder="Passphrase"
+              value={passphrase()}
+              onInput={(e: InputEvent) => setPassphrase((e.target as HTMLInputElement).value)}
+              autofocus
+            />
+            <Show when={error() !== null}>
+              <div class="login-error">{error()}</div>
+            </Show>
+            <button class="login-btn" type="submit" disabled={loading()}>
+              {loading() ? "..." : "Finish Linking"}
+            </button>
+          </form>
+        </Show>
+      </Show>
+
       {/* Delete confirmation modal */}
       <Modal
         isOpen={deleteTarget() !== null}
@@ -9,22 +9,24 @@ import {
   handleSaveSettings,
   handleSetAvatar,
   handleCheckForUpdates,
+  handleChangePassphrase,
+  handleExportBackup,
 } from "../handlers/settings.handlers";
-import { commands } from "../ipc/commands";
+import { commands, DeviceList } from "../ipc/commands";
 import { hydrateState } from "../ipc/hydrate";
 import { fetchAvatarUrl } from "../ipc/avatar";
 
 function getInitialTab(): SettingsTab {
   const params = new URLSearchParams(window.location.search);
   const tab = params.get("tab");
-  const valid: SettingsTab[] = ["profile", "application", "notifications", "audio", "privacy", "about"];
+  const valid: SettingsTab[] = ["profile", "application", "notifications", "audio", "privacy", "devices", "about"];
   if (tab && valid.includes(tab as SettingsTab)) {
     return tab as SettingsTab;
   }
   return "profile";
 }
 
-type SettingsTab = "profile" | "application" | "notifications" | "audio" | "privacy" | "about";
+type SettingsTab = "profile" | "application" | "notifications" | "audio" | "privacy" | "devices" | "about";
 
 const TAB_LABELS: { id: SettingsTab; label: string }[] = [
   { id: "profile", label: "Profile" },
@@ -32,6 +34,7 @@ const TAB_LABELS: { id: SettingsTab; label: string }[] = [
   { id: "notifications", label: "Notifications" },
   { id: "audio", label: "Audio" },
   { id: "privacy", label: "Privacy" },
+  { id: "devices", label: "Devices" },
   { id: "about", label: "About" },
 ];
 
@@ -41,7 +44,20 @@ const SettingsWindow: Component = () => {
   const [statusMsgInput, setStatusMsgInput] = createSignal("");
   const [checkingUpdates, setCheckingUpdates] = createSignal(false);
   const [updateResult, setUpdateResult] = createSignal<string | null>(null);
+  const [currentPassphrase, setCurrentPassphrase] = createSignal("");
+  const [newPassphrase, setNewPassphrase] = createSignal("");
+  const [confirmPassphrase, setConfirmPassphrase] = createSignal("");
+  const [passphraseResult, setPassphraseResult] = createSignal<string | null>(null);
+  const [changingPassphrase, setChangingPassphrase] = createSignal(false);
+  const [identityPassphrase, setIdentityPassphrase] = createSignal("");
+  const [backupPassphrase, setBackupPassphrase] = createSignal("");
+  const [includeHistory, setIncludeHistory] = createSignal(true);
+  const [backupResult, setBackupResult] = createSignal<string | null>(null);
+  const [recoveryPhrase, setRecoveryPhrase] = createSignal<string | null>(null);
   const [blockedUsers, setBlockedUsers] = createSignal<{ publicKey: string; displayName: string; blockedAt: number }[]>([]);
+  const [deviceList, setDeviceList] = createSignal<DeviceList>({ devices: [], localDeviceKey: null });
+  const [linkUrlInput, setLinkUrlInput] = createSignal("");
+  const [deviceResult, setDeviceResult] = createSignal<string | null>(null);
 
   let unlistenSwitchTab: Promise<UnlistenFn> | undefined;
 
@@ -52,7 +68,7 @@ const SettingsWindow: Component = () => {
     handleLoadSettings();
 
     unlistenSwitchTab = listen<string>("settings-switch-tab", (event) => {
-      const valid: SettingsTab[] = ["profile", "application", "notifications", "audio", "privacy", "about"];
+      const valid: SettingsTab[] = ["profile", "application", "notifications", "audio", "privacy", "devices", "about"];
       if (valid.includes(event.payload as SettingsTab)) {
         setActiveTab(event.payload as SettingsTab);
       }
@@ -72,6 +88,42 @@ const SettingsWindow: Component = () => {
     }
   });
 
+  // Load linked devices when devices tab is selected
+  createEffect(() => {
+    if (activeTab() === "devices") {
+      loadDevices();
+    }
+  });
+
+  function loadDevices(): void {
+    commands.getDevices().then(setDeviceList).catch((e) => {
+      console.error("Failed to load devices:", e);
+    });
+  }
+
+  async function handleApproveDevice(): Promise<void> {
+    const url = linkUrlInput().trim();
+    if (!url) return;
+    setDeviceResult(null);
+    try {
+      const device = await commands.approveDeviceLink(url);
+      setLinkUrlInput("");
+      setDeviceResult(`Linked ${device.name}.`);
+      loadDevices();
+    } catch (e) {
+      setDeviceResult(e instanceof Error ? e.message : String(e));
+    }
+  }
+
+  async function handleRemoveDevice(deviceKey: string): Promise<void> {
+    try {
+      await commands.removeDevice(deviceKey);
+      loadDevices();
+    } catch (e) {
+      console.error("Failed to remove device:", e);
+    }
+  }
+
   function handleToggle(key: keyof typeof settingsState): void {
     handleSaveSettings({ [key]: !settingsState[key] });
   }
@@ -274,6 +326,61 @@ const SettingsWindow: Component = () => {
     }
   }
 
+  async function handleSubmitPassphrase(): Promise<void> {
+    if (!newPassphrase()) {
+      setPassphraseResult("Enter a new passphrase.");
+      return;
+    }
+    if (newPassphrase() !== confirmPassphrase()) {
+      setPassphraseResult("New passphrases don't match.");
+      return;
+    }
+    setChangingPassphrase(true);
+    setPassphraseResult(null);
+    const error = await handleChangePassphrase(currentPassphrase(), newPassphrase());
+    setChangingPassphrase(false);
+    if (error) {
+      setPassphraseResult(error);
+      return;
+    }
+    setCurrentPassphrase("");
+    setNewPassphrase("");
+    setConfirmPassphrase("");
+    setPassphraseResult("Passphrase changed.");
+  }
+
+  async function handleSubmitExport(): Promise<void> {
+    if (!identityPassphrase() || !backupPassphrase()) {
+      setBackupResult("Enter your passphrase and a passphrase for the backup.");
+      return;
+    }
+    setBackupResult(null);
+    const result = await handleExportBackup(identityPassphrase(), backupPassphrase(), includeHistory());
+    if ("error" in result) {
+      setBackupResult(result.error);
+      return;
+    }
+    setBackupPassphrase("");
+    setBackupResult(`Backup saved to ${result.path}`);
+  }
+
+  async function handleShowRecoveryPhrase(): Promise<void> {
+    if (recoveryPhrase() !== null) {
+      setRecoveryPhrase(null);
+      return;
+    }
+    if (!identityPassphrase()) {
+      setBackupResult("Enter your passphrase to show the recovery phrase.");
+      return;
+    }
+    setBackupResult(null);
+    try {
+      setRecoveryPhrase(await commands.getRecoveryPhrase(identityPassphrase()));
+    } catch (e) {
+      setBackupResult(String(e));
+    }
+  }
+
   function renderPrivacy() {
     return (
       <>
@@ -282,12 +389,97 @@ const SettingsWindow: Component = () => {
           <label class="settings-field-label">Public Key</label>
           <div class="profile-key-display">{authState.publicKey ?? "Not logged in"}</div>
         </div>
-        <div class="settings-section-title">Identity</div>
+        <label class="settings-option">
+          <input
+            type="checkbox"
+            checked={settingsState.sendReadReceipts}
+            onChange={() => handleToggle("sendReadReceipts")}
+          />
+          <span class="buddy-name">Send Read Receipts</span>
+        </label>
+        <div class="settings-hint">When off, friends can't see when you've read their messages.</div>
+        <div class="settings-section-title">Passphrase</div>
+        <div class="settings-field settings-passphrase">
+          <input
+            class="settings-input"
+            type="password"
+            placeholder="Current passphrase"
+            value={currentPassphrase()}
+            onInput={(e: InputEvent) => setCurrentPassphrase((e.target as HTMLInputElement).value)}
+          />
+          <input
+            class="settings-input"
+            type="password"
+            placeholder="New passphrase"
+            value={newPassphrase()}
+            onInput={(e: InputEvent) => setNewPassphrase((e.target as HTMLInputElement).value)}
+          />
+          <div class="settings-field-row">
+            <input
+              class="settings-input"
+              type="password"
+              placeholder="Confirm new passphrase"
+              value={confirmPassphrase()}
+              onInput={(e: InputEvent) => setConfirmPassphrase((e.target as HTMLInputElement).value)}
+              onKeyDown={(e: KeyboardEvent) => { if (e.key === "Enter") handleSubmitPassphrase(); }}
+            />
+            <button
+              class="settings-save-btn"
+              onClick={handleSubmitPassphrase}
+              disabled={changingPassphrase()}
+            >
+              Change
+            </button>
+          </div>
+        </div>
+        <Show when={passphraseResult()}>
+          <div class="settings-hint">{passphraseResult()}</div>
+        </Show>
+        <div class="settings-hint">Your keys and local message history are encrypted with this passphrase.</div>
+        <div class="settings-section-title">Backup</div>
+        <div class="settings-field settings-passphrase">
+          <input
+            class="settings-input"
+            type="password"
+            placeholder="Current passphrase"
+            value={identityPassphrase()}
+            onInput={(e: InputEvent) => setIdentityPassphrase((e.target as HTMLInputElement).value)}
+          />
+          <div class="settings-field-row">
+            <input
+              class="settings-input"
+              type="password"
+              placeholder="Backup passphrase"
+              value={backupPassphrase()}
+              onInput={(e: InputEvent) => setBackupPassphrase((e.target as HTMLInputElement).value)}
+              onKeyDown={(e: KeyboardEvent) => { if (e.key === "Enter") handleSubmitExport(); }}
+            />
+            <button class="settings-save-btn" onClick={handleSubmitExport}>Export</button>
+          </div>
+        </div>
+        <label class="settings-option">
+          <input
+            type="checkbox"
+            checked={includeHistory()}
+            onChange={() => setIncludeHistory(!includeHistory())}
+          />
+          <span class="buddy-name">Include message history</span>
+        </label>
         <div class="settings-field-row">
-          <button class="settings-action-btn" disabled>Export Identity</button>
-          <button class="settings-action-btn" disabled>Import Identity</button>
+          <button class="settings-action-btn" onClick={handleShowRecoveryPhrase}>
+            {recoveryPhrase() !== null ? "Hide Recovery Phrase" : "Show Recovery Phrase"}
+          </button>
+        </div>
+        <Show when={recoveryPhrase()}>
+          {(phrase) => <div class="profile-key-display">{phrase()}</div>}
+        </Show>
+        <Show when={backupResult()}>
+          <div class="settings-hint">{backupResult()}</div>
+        </Show>
+        <div class="settings-hint">
+          A backup restores your identity, friends and history on another machine. The recovery
+          phrase restores only your identity — anyone who has either can act as you.
         </div>
-        <div class="settings-hint">Identity export/import requires Stronghold integration.</div>
         <div class="settings-section-title">Blocked Users</div>
         <Show when={blockedUsers().length > 0} fallback={
           <div class="settings-hint">No blocked users.</div>
@@ -307,6 +499,59 @@ const SettingsWindow: Component = () => {
     );
   }
 
+  function renderDevices() {
+    const isPrimary = () => deviceList().localDeviceKey === null;
+    return (
+      <>
+        <div class="settings-section-title">Linked Devices</div>
+        <Show when={deviceList().devices.length > 0} fallback={
+          <div class="settings-hint">No other devices are linked to this identity.</div>
+        }>
+          <For each={deviceList().devices}>
+            {(device) => (
+              <div class="blocked-user-item">
+                <span class="buddy-name">
+                  {device.name}
+                  {device.deviceKey === deviceList().localDeviceKey ? " (this device)" : ""}
+                </span>
+                <Show when={isPrimary()}>
+                  <button class="settings-action-btn" onClick={() => handleRemoveDevice(device.deviceKey)}>
+                    Remove
+                  </button>
+                </Show>
+              </div>
+            )}
+          </For>
+        </Show>
+        <Show when={isPrimary()} fallback={
+          <div class="settings-hint">Devices are managed from your primary device.</div>
+        }>
+          <div class="settings-section-title">Link a New Device</div>
+          <div class="settings-field">
+            <div class="settings-field-row">
+              <input
+                class="settings-input"
+                type="text"
+                placeholder="rekindle-link://..."
+                value={linkUrlInput()}
+                onInput={(e: InputEvent) => setLinkUrlInput((e.target as HTMLInputElement).value)}
+                onKeyDown={(e: KeyboardEvent) => { if (e.key === "Enter") handleApproveDevice(); }}
+              />
+              <button class="settings-save-btn" onClick={handleApproveDevice}>Approve</button>
+            </div>
+          </div>
+          <Show when={deviceResult()}>
+            <div class="settings-hint">{deviceResult()}</div>
+          </Show>
+          <div class="settings-hint">
+            Choose "Link This Device" on the new device's login screen and paste its link here.
+            Removed devices can no longer be reached, but keep any history they already have.
+          </div>
+        </Show>
+      </>
+    );
+  }
+
   function renderAbout() {
     return (
       <>
@@ -369,6 +614,7 @@ const SettingsWindow: Component = () => {
         <Show when={activeTab() === "notifications"}>{renderNotifications()}</Show>
         <Show when={activeTab() === "audio"}>{renderAudio()}</Show>
         <Show when={activeTab() === "privacy"}>{renderPrivacy()}</Show>
+        <Show when={activeTab() === "devices"}>{renderDevices()}</Show>
         <Show when={activeTab() === "about"}>{renderAbout()}</Show>
       </div>
     </div>